@@ -65,13 +65,13 @@ def main():
     while len(hands_list) < total_hands:
         # Generate batch of 100
         batch_size = 100
-        (hands_flat, boards, history, trumps, tricks_won, players) = coinche_engine.generate_raw_gameplay_batch(batch_size)
+        batch = coinche_engine.generate_raw_gameplay_batch(batch_size)
         
-        for i in range(batch_size):
+        for i in range(len(batch)):
+            sample = batch[i]
             # Check if this hand is "strong" for the current player
-            p = players[i]
-            my_hand = hands_flat[i*4 + p]
-            trump = trumps[i]
+            my_hand = sample.hand
+            trump = sample.trump
             
             cnt, has_J, has_9 = count_trumps(my_hand, trump)
             
@@ -94,12 +94,12 @@ def main():
             if is_strong:
                 strong_count += 1
                 
-            hands_list.append((hands_flat[i*4:i*4+4]))
-            boards_list.append(boards[i])
-            history_list.append(history[i])
-            trumps_list.append(trumps[i])
-            tricks_won_list.append(tricks_won[i])
-            players_list.append(players[i])
+            hands_list.append(list(sample.hands))
+            boards_list.append(sample.board)
+            history_list.append(sample.history)
+            trumps_list.append(sample.trump)
+            tricks_won_list.append(list(sample.tricks_won))
+            players_list.append(sample.player)
             
             if len(hands_list) >= total_hands:
                 if strong_count < strong_hands_needed:
@@ -165,7 +165,7 @@ def main():
         for h in batch_hands_lists:
             batch_hands_flat.extend(h)
             
-        (b_cards, b_scores, b_valid) = coinche_engine.solve_gameplay_batch(
+        (b_cards, b_scores, b_valid, *_) = coinche_engine.solve_gameplay_batch(
             batch_hands_flat,
             batch_boards,
             batch_history,
@@ -263,9 +263,17 @@ def main():
     h3 = make_hand(3) # Clubs
     
     hands_full = [h0, h1, h2, h3]
+
+    # The dedicated detector proves the capot without counting points.
+    god_state = coinche_engine.PlayingState(god_trump)
+    for seat, hand in enumerate(hands_full):
+        god_state.set_hand(seat, hand)
+    start_detect = time.time()
+    forced = coinche_engine.forces_capot(god_state)
+    print(f"Forced Capot (detector): {forced} (Time: {time.time() - start_detect:.4f}s)")
     
     start_god = time.time()
-    (g_best, g_scores, g_valid) = coinche_engine.solve_gameplay_batch(
+    (g_best, g_scores, g_valid, *_) = coinche_engine.solve_gameplay_batch(
         hands_full,
         [god_board],
         [god_history],
@@ -74,9 +74,6 @@ class AIAgent:
 
     def get_play(self, game_state, hand_int, legal_moves_mask):
         # 1. Prepare Features
-        # Hand
-        hand_vec = self._bits_to_vec(hand_int)
-        
         # History (from PlayedCards mask if available, or track manually?)
         # GameState from engine usually has history or cards played.
         # PlayingState in engine has `tricks_won`, `current_trick`.
@@ -86,23 +83,13 @@ class AIAgent:
         # We might need to approximate or pass it.
         # For MVP, let's use 0 for history if not available, or rebuild it from known tricks?
         # Rebuilding is hard without full log. Let's assume 0 for now to unblock.
-        history_vec = np.zeros(32, dtype=np.float32) 
         
-        # Board (Current Trick)
+        # Board (Current Trick) and trump
         current_trick = game_state['current_trick'] # List of card IDs (or 255)
-        board_vec = np.zeros(32, dtype=np.float32)
-        for card in current_trick:
-             if card < 32:
-                 board_vec[card] = 1.0
-                 
-        # Trump
         trump = game_state['trump']
-        trump_vec = np.zeros(6, dtype=np.float32)
-        if trump < 6:
-            trump_vec[trump] = 1.0
-            
-        # Concat
-        features = np.concatenate([hand_vec, history_vec, board_vec, trump_vec])
+        features = np.array(coinche_engine.encode_gameplay_features(
+            hand_int, 0, list(current_trick), trump
+        ), dtype=np.float32)
         inputs = torch.from_numpy(features).unsqueeze(0).to(self.device) # (1, 102)
         
         # 2. Predict Policy
@@ -123,8 +110,4 @@ class AIAgent:
         return int(best_card)
 
     def _bits_to_vec(self, bits):
-        vec = np.zeros(32, dtype=np.float32)
-        for i in range(32):
-            if (bits & (1 << i)) != 0:
-                vec[i] = 1.0
-        return vec
+        return np.array(coinche_engine.encode_hand(bits), dtype=np.float32)
@@ -1,12 +1,15 @@
-from fastapi import FastAPI, HTTPException
+from fastapi import FastAPI, HTTPException, WebSocket, WebSocketDisconnect
 from fastapi.middleware.cors import CORSMiddleware
 from pydantic import BaseModel
 import coinche_engine
-from typing import Optional, List
+from coinche_engine import Phase
+from typing import Dict, Optional, List, Union
 import uuid
 import os
+import time
 
 from ai_agent import AIAgent
+from tables import TableRegistry, redact, redact_event, result_state
 
 app = FastAPI()
 
@@ -33,10 +36,29 @@ app.add_middleware(
 # In-memory storage for games
 games = {} 
 
+# Multiplayer tables, dropped after this many seconds without activity
+TABLE_IDLE_SECONDS = float(os.environ.get("TABLE_IDLE_SECONDS", 3600))
+tables = TableRegistry(TABLE_IDLE_SECONDS)
+
 class CreateGameRequest(BaseModel):
     dealer: int = 0
     # Flattened hands [u32] for 4 players (4 integers)
     hands: Optional[List[int]] = None 
+    # Illegal plays of a held card end the deal as a revoke instead of being refused
+    revoke_penalty: bool = False
+
+class BotSeat(BaseModel):
+    # "beginner", "intermediate", "expert" or "perfect"
+    level: str = "intermediate"
+    # Share of random cards, instead of the level's own rate
+    blunder_rate: Optional[float] = None
+
+class CreateTableRequest(BaseModel):
+    dealer: int = 0
+    hands: Optional[List[int]] = None
+    revoke_penalty: bool = False
+    # Seats (0=S, 1=W, 2=N, 3=E) played by bots; the others get a token
+    bots: Dict[int, BotSeat] = {}
 
 class BidRequest(BaseModel):
     value: int
@@ -45,18 +67,43 @@ class BidRequest(BaseModel):
 class PlayCardRequest(BaseModel):
     card_index: int # 0-31
 
+class AnalyzeRequest(BaseModel):
+    # Either a match transcript (CoincheMatch.to_transcript format)...
+    transcript: Optional[str] = None
+    # ...or a JSON position: remaining hands, calls and cards played so far.
+    # Calls are "pass", "coinche", "surcoinche" or {"value": 80, "trump": 2}.
+    dealer: int = 0
+    hands: Optional[List[int]] = None
+    auction: List[Union[str, dict]] = []
+    played_cards: List[int] = []
+    # Search options: soft time limit, whose points ("current", "ns", "declarer")
+    time_budget_ms: Optional[int] = 1000
+    perspective: str = "current"
+    tt_log2: Optional[int] = None
+
+
+def position_from_request(req: AnalyzeRequest) -> coinche_engine.CoincheMatch:
+    if req.transcript is not None:
+        return coinche_engine.CoincheMatch.from_transcript(req.transcript)
+    if req.hands is None:
+        raise ValueError("Either a transcript or hands are required")
+    auction = [coinche_engine.Bid(c["value"], c["trump"]) if isinstance(c, dict) else c for c in req.auction]
+    match = coinche_engine.CoincheMatch(req.dealer, req.hands)
+    match.set_position(req.hands, auction, req.played_cards)
+    return match
+
 
 def make_ai_move(match: coinche_engine.CoincheMatch) -> bool:
     """
     Makes a SINGLE move for the current AI player.
     Returns True if a move was made, False otherwise (e.g. Human turn or Game Over).
     """
-    phase = match.phase_name()
-    if phase == "FINISHED":
+    phase = match.phase
+    if phase == Phase.FINISHED:
         return False
         
     current_player = 0
-    if phase == "BIDDING":
+    if phase == Phase.BIDDING:
         bs = match.get_bidding_state()
         if not bs: return False
         current_player = bs.current_player
@@ -70,7 +117,7 @@ def make_ai_move(match: coinche_engine.CoincheMatch) -> bool:
         match.bid(bid)
         return True
         
-    elif phase == "PLAYING":
+    elif phase == Phase.PLAYING:
         ps = match.get_playing_state()
         if not ps: return False
         current_player = ps.current_player
@@ -110,13 +157,8 @@ def step_game(game_id: str):
 def create_game(req: CreateGameRequest):
     game_id = str(uuid.uuid4())
     
-    hands = req.hands
-    if hands is None:
-        generated_hands, _ = coinche_engine.generate_bidding_hands(1)
-        hands = generated_hands
-        
     try:
-        match = coinche_engine.CoincheMatch(req.dealer, hands)
+        match = new_match(req.dealer, req.hands, req.revoke_penalty)
         games[game_id] = match
         # No auto-play here
     except Exception as e:
@@ -128,18 +170,19 @@ def create_game(req: CreateGameRequest):
 def get_game(game_id: str):
     if game_id not in games:
         raise HTTPException(status_code=404, detail="Game not found")
-    match = games[game_id]
-    
+    return {"game_id": game_id, **match_state(games[game_id])}
+
+def match_state(match: coinche_engine.CoincheMatch) -> dict:
+    """Full state of a match, every hand included."""
     state = {
-        "game_id": game_id,
-        "phase": match.phase_name(),
+        "phase": match.phase.name,
         "dealer": match.dealer,
         "coinche_level": match.coinche_level,
         "contract_owner": match.contract_owner,
         "hands": match.hands 
     }
     
-    if match.phase_name() == "BIDDING":
+    if match.phase == Phase.BIDDING:
         bs = match.get_bidding_state()
         if bs:
             state["bidding"] = {
@@ -148,7 +191,7 @@ def get_game(game_id: str):
                 "contract": {"value": bs.contract.value, "trump": bs.contract.trump} if bs.contract else None,
                 "contract_owner": bs.contract_owner
             }
-    elif match.phase_name() == "PLAYING":
+    elif match.phase == Phase.PLAYING:
         ps = match.get_playing_state()
         if ps:
             state["playing"] = {
@@ -161,19 +204,18 @@ def get_game(game_id: str):
                 "legal_moves": ps.get_legal_moves(),
                 "last_trick": ps.last_trick,
                 "last_trick_starter": ps.last_trick_starter,
-                "last_trick_winner": ps.last_trick_winner
+                "last_trick_winner": ps.last_trick_winner,
+                "score_bounds": ps.score_bounds,
+                # True once the contract is made whatever happens, False once it cannot be.
+                "contract_decided": ps.contract_decided(match.contract.value, match.contract_owner) if match.contract else None
             }
         
         state["contract"] = {"value": match.contract.value, "trump": match.contract.trump} if match.contract else None
         
-    elif match.phase_name() == "FINISHED":
+    elif match.phase == Phase.FINISHED:
         res = match.get_result()
         if res:
-            state["result"] = {
-                 "points_ns": res.points_ns,
-                 "points_ew": res.points_ew,
-                 "contract_made": res.contract_made
-            }
+            state["result"] = result_state(res)
         
     return state
 
@@ -236,3 +278,164 @@ def play_card(game_id: str, req: PlayCardRequest):
         return get_game(game_id)
     except Exception as e:
          raise HTTPException(status_code=400, detail=str(e))
+
+def new_match(dealer: int, hands: Optional[List[int]], revoke_penalty: bool) -> coinche_engine.CoincheMatch:
+    if hands is None:
+        hands, _ = coinche_engine.generate_bidding_hands(1)
+    return coinche_engine.CoincheMatch(dealer, hands, revoke_penalty=revoke_penalty)
+
+def get_table_or_404(table_id: str):
+    try:
+        return tables.get(table_id)
+    except KeyError:
+        raise HTTPException(status_code=404, detail="Table not found")
+
+def table_view(table, seat: Optional[int]) -> dict:
+    view = redact(match_state(table.match), seat)
+    view["table_id"] = table.id
+    view["current_seat"] = table.current_seat()
+    view["bot_seats"] = sorted(table.bots)
+    return view
+
+def seat_action(table_id: str, token: str, kind: str, action, **details):
+    """
+    Applies `action` to the table's match on behalf of the seat holding `token`,
+    logged as a `kind` event with `details`.
+    """
+    table = get_table_or_404(table_id)
+    try:
+        seat = table.seat_of(token)
+    except KeyError as e:
+        raise HTTPException(status_code=403, detail=str(e))
+    with table.lock:
+        if table.current_seat() != seat:
+            raise HTTPException(status_code=409, detail=f"Not the turn of seat {seat}")
+        try:
+            table.apply(seat, kind, action, **details)
+            table.run_bots()
+        except Exception as e:
+            raise HTTPException(status_code=400, detail=str(e))
+        return table_view(table, seat)
+
+@app.post("/table")
+def create_table(req: CreateTableRequest):
+    """
+    Opens a table for four remote players, or fewer with bots in the other seats.
+    The seat tokens are only given here: each player keeps theirs to act and to
+    reconnect.
+    """
+    try:
+        bots = {}
+        for seat, bot in req.bots.items():
+            if not 0 <= seat < 4:
+                raise ValueError(f"Invalid seat {seat}")
+            bots[seat] = coinche_engine.BotPreset(bot.level)
+            if bot.blunder_rate is not None:
+                bots[seat].blunder_rate = bot.blunder_rate
+        table = tables.create(new_match(req.dealer, req.hands, req.revoke_penalty), bots)
+        with table.lock:
+            table.run_bots()
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    return {"table_id": table.id, "tokens": table.tokens, "state": table_view(table, None)}
+
+@app.get("/tables")
+def list_tables():
+    now = time.monotonic()
+    return [
+        {"table_id": t.id, "phase": t.match.phase.name, "idle_seconds": now - t.last_activity}
+        for t in tables.list()
+    ]
+
+@app.get("/table/{table_id}")
+def get_table(table_id: str, token: Optional[str] = None):
+    """State seen by the seat holding `token`, or by a spectator without one."""
+    table = get_table_or_404(table_id)
+    try:
+        seat = table.seat_of(token)
+    except KeyError as e:
+        raise HTTPException(status_code=403, detail=str(e))
+    with table.lock:
+        return table_view(table, seat)
+
+@app.post("/table/{table_id}/bid")
+def table_bid(table_id: str, token: str, req: BidRequest):
+    return seat_action(
+        table_id, token, "bid", lambda m: m.bid(coinche_engine.Bid(req.value, req.trump)), value=req.value, trump=req.trump
+    )
+
+@app.post("/table/{table_id}/pass")
+def table_pass(table_id: str, token: str):
+    return seat_action(table_id, token, "pass", lambda m: m.bid(None))
+
+@app.post("/table/{table_id}/coinche")
+def table_coinche(table_id: str, token: str):
+    return seat_action(table_id, token, "coinche", lambda m: m.coinche())
+
+@app.post("/table/{table_id}/surcoinche")
+def table_surcoinche(table_id: str, token: str):
+    return seat_action(table_id, token, "surcoinche", lambda m: m.surcoinche())
+
+@app.post("/table/{table_id}/play")
+def table_play(table_id: str, token: str, req: PlayCardRequest):
+    return seat_action(table_id, token, "play", lambda m: m.play_card(req.card_index), card=req.card_index)
+
+@app.websocket("/table/{table_id}/events")
+async def table_events(websocket: WebSocket, table_id: str, token: Optional[str] = None, since: int = 0):
+    """
+    Pushes the table's events as JSON messages, redacted for the seat holding
+    `token` (a spectator without one). Events from `since` on are replayed first,
+    so a client reconnecting with the last `seq` it got + 1 misses nothing.
+    """
+    try:
+        table = tables.get(table_id)
+        seat = table.seat_of(token)
+    except KeyError as e:
+        await websocket.close(code=4404, reason=str(e))
+        return
+    await websocket.accept()
+    queue, backlog = table.subscribe()
+    try:
+        for event in backlog[since:]:
+            await websocket.send_json(redact_event(event, seat))
+        while True:
+            event = await queue.get()
+            table.touch()
+            await websocket.send_json(redact_event(event, seat))
+    except WebSocketDisconnect:
+        pass
+    finally:
+        table.unsubscribe(queue)
+
+@app.post("/analyze")
+def analyze(req: AnalyzeRequest):
+    """
+    Double-dummy analysis of a position in the card play: best move, principal
+    variation, score and the value of every legal move, within the time budget.
+    """
+    try:
+        match = position_from_request(req)
+    except Exception as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    if match.phase != Phase.PLAYING:
+        raise HTTPException(status_code=400, detail=f"Position is not in the card play ({match.phase.name})")
+
+    try:
+        analysis = coinche_engine.analyze_position(
+            match.get_playing_state(),
+            time_budget_ms=req.time_budget_ms,
+            perspective=req.perspective,
+            declarer=match.contract_owner,
+            tt_log2=req.tt_log2,
+        )
+    except ValueError as e:
+        raise HTTPException(status_code=400, detail=str(e))
+    return {
+        "best_move": analysis.best_move,
+        "score": analysis.score,
+        "pv": analysis.pv,
+        "moves": [{"card": card, "value": value} for card, value in analysis.moves],
+        "depth": analysis.depth,
+        "complete": analysis.complete,
+        "nodes": analysis.nodes,
+    }
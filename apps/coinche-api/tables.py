@@ -0,0 +1,197 @@
+"""
+Multiplayer tables: one CoincheMatch shared by four remote seats.
+
+Creating a table deals one secret token per seat. Every action of a seat carries
+its token, which is all a player needs to reconnect: the same token gives the same
+seat back. Views are redacted for whoever asks: a seat sees its own hand only, a
+spectator (no token) sees no hand. Tables nobody acted on or looked at for
+`idle_timeout` seconds are dropped.
+
+Seats can be given to bots instead (a `BotPreset` of the difficulty ladder):
+they get no token and play as soon as they are to move.
+
+Each table also keeps the log of what happened, in order: the deal, every call
+and card, finished tricks, the contract and the result. Subscribers (the
+WebSocket clients) get every new event pushed, redacted like the views.
+"""
+import asyncio
+import secrets
+import threading
+import time
+import uuid
+from typing import Callable, Dict, List, Optional, Tuple
+
+import coinche_engine
+from coinche_engine import Phase
+
+SEATS = 4
+
+
+class Table:
+    def __init__(self, match: coinche_engine.CoincheMatch, bots: Optional[Dict[int, coinche_engine.BotPreset]] = None):
+        self.id = str(uuid.uuid4())
+        self.match = match
+        self.bots = bots or {}
+        # Index = seat (0=S, 1=W, 2=N, 3=E), None for the seats of bots
+        self.tokens: List[Optional[str]] = [
+            None if seat in self.bots else secrets.token_urlsafe(16) for seat in range(SEATS)
+        ]
+        # Serializes the actions of the four seats on the match
+        self.lock = threading.Lock()
+        self.last_activity = time.monotonic()
+        # Event `seq` is at index `seq`
+        self.events: List[dict] = []
+        self.subscribers: List[Tuple[asyncio.AbstractEventLoop, asyncio.Queue]] = []
+        self.publish("deal", None, dealer=match.dealer, hands=list(match.hands))
+
+    def seat_of(self, token: Optional[str]) -> Optional[int]:
+        """Seat holding `token`, None for a spectator. Raises KeyError on an unknown token."""
+        if token is None:
+            return None
+        for seat, seat_token in enumerate(self.tokens):
+            if seat_token is not None and secrets.compare_digest(seat_token, token):
+                return seat
+        raise KeyError("Unknown seat token")
+
+    def current_seat(self) -> Optional[int]:
+        """Seat to act, None once the deal is over."""
+        phase = self.match.phase
+        if phase == Phase.BIDDING:
+            return self.match.bidding_state().current_player
+        if phase == Phase.PLAYING:
+            return self.match.playing_state().current_player
+        return None
+
+    def touch(self):
+        self.last_activity = time.monotonic()
+
+    def apply(self, seat: int, kind: str, action: Callable, **details):
+        """
+        Applies `action` to the match for `seat` and logs it as a `kind` event,
+        followed by the events it caused. Callers hold `lock`.
+        """
+        phase = self.match.phase
+        tricks = len(self.match.completed_tricks())
+        action(self.match)
+        self.publish(kind, seat, **details)
+        for trick in self.match.completed_tricks()[tricks:]:
+            self.publish("trick", None, leader=trick.leader, cards=list(trick.cards), winner=trick.winner)
+        if phase == Phase.BIDDING and self.match.phase == Phase.PLAYING:
+            contract = self.match.contract
+            self.publish(
+                "contract",
+                self.match.contract_owner,
+                value=contract.value,
+                trump=contract.trump,
+                coinche_level=self.match.coinche_level,
+            )
+        if self.match.phase == Phase.FINISHED:
+            self.publish("result", None, **result_state(self.match.get_result()))
+
+    def run_bots(self):
+        """Lets the bots act until a human seat is to move or the deal is over. Callers hold `lock`."""
+        while (seat := self.current_seat()) in self.bots:
+            action = self.bots[seat].action(self.match, seat)
+            kind, details = describe_action(action)
+            self.apply(seat, kind, lambda m: m.apply_action(action), **details)
+
+    def publish(self, kind: str, seat: Optional[int], **details):
+        event = {"seq": len(self.events), "type": kind, "seat": seat, **details}
+        self.events.append(event)
+        for loop, queue in self.subscribers:
+            loop.call_soon_threadsafe(queue.put_nowait, event)
+
+    def subscribe(self) -> Tuple[asyncio.Queue, List[dict]]:
+        """
+        Queue receiving every event published from now on, and the events so far.
+        To be called from the event loop that reads the queue.
+        """
+        queue = asyncio.Queue()
+        with self.lock:
+            self.subscribers.append((asyncio.get_running_loop(), queue))
+            return queue, list(self.events)
+
+    def unsubscribe(self, queue: asyncio.Queue):
+        with self.lock:
+            self.subscribers = [(l, q) for l, q in self.subscribers if q is not queue]
+
+
+class TableRegistry:
+    """Open tables by id, expiring those idle for more than `idle_timeout` seconds."""
+
+    def __init__(self, idle_timeout: float):
+        self.idle_timeout = idle_timeout
+        self.tables: Dict[str, Table] = {}
+        self.lock = threading.Lock()
+
+    def create(self, match: coinche_engine.CoincheMatch, bots: Optional[Dict[int, coinche_engine.BotPreset]] = None) -> Table:
+        table = Table(match, bots)
+        with self.lock:
+            self.expire_idle()
+            self.tables[table.id] = table
+        return table
+
+    def get(self, table_id: str) -> Table:
+        """The table, marked active. Raises KeyError if it does not exist or expired."""
+        with self.lock:
+            self.expire_idle()
+            table = self.tables[table_id]
+        table.touch()
+        return table
+
+    def list(self) -> List[Table]:
+        with self.lock:
+            self.expire_idle()
+            return list(self.tables.values())
+
+    def expire_idle(self, now: Optional[float] = None) -> List[str]:
+        """Drops the idle tables and returns their ids. Callers hold `lock`."""
+        now = time.monotonic() if now is None else now
+        expired = [i for i, t in self.tables.items() if now - t.last_activity > self.idle_timeout]
+        for table_id in expired:
+            del self.tables[table_id]
+        return expired
+
+
+def redact(state: dict, seat: Optional[int]) -> dict:
+    """
+    Copy of a full game state (as main.match_state builds it) as `seat` may see it:
+    the other hands hidden, and the legal moves only when it is that seat's turn.
+    A seat of None is a spectator. Hands are shown to everybody once the deal is over.
+    """
+    view = dict(state)
+    if state["phase"] != "FINISHED":
+        view["hands"] = [hand if s == seat else None for s, hand in enumerate(state["hands"])]
+    view["hand_sizes"] = [bin(hand).count("1") for hand in state["hands"]]
+    if "playing" in state and state["playing"]["current_player"] != seat:
+        view["playing"] = dict(state["playing"], legal_moves=None)
+    view["seat"] = seat
+    return view
+
+
+def describe_action(action) -> Tuple[str, dict]:
+    """Event type and details of an action as `BotPreset.action` returns it."""
+    if action is None:
+        return "pass", {}
+    if isinstance(action, str):
+        return action, {}
+    if isinstance(action, int):
+        return "play", {"card": action}
+    return "bid", {"value": action.value, "trump": action.trump}
+
+
+def redact_event(event: dict, seat: Optional[int]) -> dict:
+    """`event` as `seat` may see it (None for a spectator): only its own hand is dealt."""
+    if "hands" not in event:
+        return event
+    return dict(event, hands=[hand if s == seat else None for s, hand in enumerate(event["hands"])])
+
+
+def result_state(result: coinche_engine.MatchResult) -> dict:
+    return {
+        "points_ns": result.points_ns,
+        "points_ew": result.points_ew,
+        "contract_made": result.contract_made,
+        "revoke_seat": result.revoke_seat,
+        "revoke_card": result.revoke_card,
+    }
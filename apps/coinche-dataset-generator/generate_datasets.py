@@ -7,8 +7,264 @@ import numpy as np
 import pyarrow as pa
 import pyarrow.parquet as pq
 
-def generate_datasets(bidding_samples, gameplay_samples, bidding_output_dir, gameplay_file, batch_size=1000, pimc_iterations=0, tt_log2=None):
+def deal_id_array(ids):
+    """`deal_id` column: deal IDs (see coinche_engine.deal_id) as 16 big-endian
+    bytes, which sort like the IDs. Datasets generated from the same deals join on it."""
+    return pa.array([None if i is None else i.to_bytes(16, 'big') for i in ids], type=pa.binary(16))
+
+def gameplay_intermediate_table(batch):
+    """Intermediate gameplay table of a raw batch (generate_raw_gameplay_batch): the
+    positions to solve, whole deals and ordered plays included."""
+    # Convert to PyArrow Table
+    # Hands need to be stored as list of 4? No, flat in Rust, but here we can structuralize them.
+    # Let's store them as FixedSizeList? Or just keep flattened and reshape on read?
+    # PyArrow Table is cleaner.
+
+    hands_np = np.array(batch.hands, dtype=np.uint32)
+
+    # Boards: List[List[uint8]]
+    # PyArrow handles list of lists naturally
+
+    # Tricks won: [N, 2]
+    tricks_won_np = np.array(batch.tricks_won, dtype=np.uint8)
+
+    return pa.Table.from_pydict({
+        'hands': list(hands_np), # List of Arrays
+        'board': batch.boards,
+        'history': batch.history,
+        'trump': batch.trumps,
+        'tricks_won': list(tricks_won_np),
+        'player': batch.players,
+        'declarer': batch.declarers,
+        'contract_value': batch.contract_values,
+        # Ordered plays, so that the solver counts the points already won
+        'plays': batch.plays,
+        'deal_id': deal_id_array(batch.deal_ids)
+    })
+
+def read_gameplay_intermediate(coinche_engine, intermediate_file):
+    """The intermediate gameplay table at `intermediate_file`, with its deal IDs."""
+    full_table = pq.read_table(intermediate_file)
+    if 'deal_id' not in full_table.column_names:
+        # Older intermediate files: the IDs come back from the hands and plays
+        try:
+            ids = coinche_engine.recompute_deal_ids(intermediate_file)
+        except ValueError as e:
+            print(f"Warning: no deal IDs for {intermediate_file} ({e})")
+            ids = [None] * full_table.num_rows
+        full_table = full_table.append_column('deal_id', deal_id_array(ids))
+    return full_table
+
+def downsample_gameplay_part(coinche_engine, batch, seed, downsample, tt_log2):
+    """Rows of `batch` kept by downsample_easy_positions with the threshold and keep
+    fraction of `downsample`, with their weights and difficulties."""
+    hands_flat = [h for sub in batch['hands'].to_pylist() for h in sub]
+    keep, weights, difficulty = coinche_engine.downsample_easy_positions(
+        hands_flat,
+        batch['board'].to_pylist(),
+        batch['trump'].to_pylist(),
+        batch['tricks_won'].to_pylist(),
+        batch['player'].to_pylist(),
+        downsample['threshold'],
+        downsample['keep_fraction'],
+        seed=seed,
+        tt_log2=tt_log2,
+    )
+    kept = [idx for idx, k in enumerate(keep) if k]
+    return batch.filter(pa.array(keep)), [weights[idx] for idx in kept], [difficulty[idx] for idx in kept]
+
+def solve_gameplay_part(coinche_engine, batch, seed, solve_options, schema_version, contracts, checkpoint=None, checkpoint_every=1000, downsample=None):
+    """Solves the positions of `batch` (rows of the intermediate gameplay table) with
+    the solve_gameplay_batch arguments of `solve_options` and returns the table of the
+    valid ones (None if there is none) and the invalid reason of every position.
+    The labels only depend on the rows, `seed` and `solve_options`. With
+    `downsample` ({'threshold', 'keep_fraction'}), easy positions are downsampled
+    before solving (see --downsample-easy)."""
+    gameplay_columns = coinche_engine.gameplay_schema_columns(schema_version)
+    sample_weights = None
+    if downsample is not None:
+        batch, sample_weights, difficulties = downsample_gameplay_part(coinche_engine, batch, seed, downsample, solve_options['tt_log2'])
+        if batch.num_rows == 0:
+            return None, []
+    # Prepare inputs for Rust
+    # Hands: Needs to be flattened Vec<u32>
+    # Batch['hands'] is List<FixedSizeList<u32>[4]>.
+    # We need to flatten it.
+    hands_col = batch['hands'].to_pylist() # List[List[u32]]
+    # flatten
+    hands_flat = [h for sub in hands_col for h in sub]
+
+    boards_col = batch['board'].to_pylist()
+    history_col = batch['history'].to_pylist()
+    trumps_col = batch['trump'].to_pylist()
+    tricks_won_col = batch['tricks_won'].to_pylist()
+    players_col = batch['player'].to_pylist()
+    deal_ids_col = batch['deal_id'].to_pylist()
+    # Intermediate files from before contract sources have no contract columns
+    declarers_col = batch['declarer'].to_pylist() if 'declarer' in batch.column_names else None
+    contract_values_col = batch['contract_value'].to_pylist() if 'contract_value' in batch.column_names else None
+    # Older intermediate files have no plays: their scores leave out the points won before the position
+    plays_col = batch['plays'].to_pylist() if 'plays' in batch.column_names else None
+
+    best_cards, best_scores, valid_mask, nodes_searched, solve_times, agreement, vote_entropy, value_variance, optimal_cards, invalid_reasons, forced = coinche_engine.solve_gameplay_batch(
+        hands_flat,
+        boards_col,
+        history_col,
+        trumps_col,
+        tricks_won_col,
+        players_col,
+        seed=seed,
+        checkpoint=checkpoint,
+        checkpoint_every=max(checkpoint_every, 1),
+        resume=True,
+        plays=plays_col,
+        **solve_options
+    )
+
+    # Filter invalid results (forced moves etc)
+    # We need to reconstruct the rows that are valid
+    # Python list filtering is slow?
+    # Use PyArrow filtering or list comprehension.
+
+    valid_indices = [idx for idx, v in enumerate(valid_mask) if v]
+    if not valid_indices:
+        return None, invalid_reasons
+
+    # Filter inputs to save (User wants: Hand, Board, History, Trump + Label)
+    # NOTE: Only save "My Hand" (the current player's hand) for the final dataset?
+    # The `gameplay.rs` original writer saved only `hand` (u32).
+    # Let's extract My Hand from the hands list.
+    # hands_col[idx] is [H0, H1, H2, H3]. Player is players_col[idx].
+
+    final_hands = []
+    final_boards = []
+    final_history = []
+    final_trumps = []
+    final_cards = []
+    final_scores = []
+    final_nodes = []
+    final_times = []
+    final_agreement = []
+    final_entropy = []
+    final_variance = []
+    final_deals = []
+    final_tricks_won = []
+    final_players = []
+    final_optimal = []
+    final_forced = []
+
+    for idx in valid_indices:
+         player = players_col[idx]
+         my_hand = hands_col[idx][player]
+
+         final_hands.append(my_hand)
+         final_boards.append(boards_col[idx])
+         final_history.append(history_col[idx])
+         final_trumps.append(trumps_col[idx])
+         final_cards.append(best_cards[idx])
+         final_scores.append(best_scores[idx])
+         final_nodes.append(nodes_searched[idx])
+         final_times.append(solve_times[idx])
+         final_agreement.append(agreement[idx])
+         final_entropy.append(vote_entropy[idx])
+         final_variance.append(value_variance[idx])
+         final_deals.append(hands_col[idx])
+         final_tricks_won.append(tricks_won_col[idx])
+         final_players.append(player)
+         final_optimal.append(optimal_cards[idx])
+         final_forced.append(forced[idx])
+
+    # Create Batch Table
+    out_table = pa.Table.from_pydict({
+        'hand': final_hands,
+        'board': final_boards,
+        'history': final_history,
+        'trump': final_trumps,
+        'best_card': final_cards,
+        # Float: PIMC expected-value labels are not whole numbers
+        'best_score': pa.array(final_scores, type=pa.float32()),
+        # Difficulty metrics (curriculum learning / generation health)
+        'nodes_searched': pa.array(final_nodes, type=pa.uint64()),
+        'solve_time_us': pa.array(final_times, type=pa.uint64()),
+        # PIMC label confidence (1 / 0 / 0 for double-dummy labels), to weight noisy labels
+        'agreement': pa.array(final_agreement, type=pa.float32()),
+        'vote_entropy': pa.array(final_entropy, type=pa.float32()),
+        'value_variance': pa.array(final_variance, type=pa.float32()),
+        # Full position, so labels can be re-solved by coinche_engine.verify_dataset
+        'deal': pa.array(final_deals, type=pa.list_(pa.uint32())),
+        'tricks_won': pa.array(final_tricks_won, type=pa.list_(pa.uint8())),
+        'player': pa.array(final_players, type=pa.uint8()),
+        # Every card as good as best_card (bit i = card i), see --optimal-epsilon
+        'optimal_cards': pa.array(final_optimal, type=pa.uint32()),
+        # Deal the position comes from, to join with the bidding dataset
+        'deal_id': pa.array([deal_ids_col[idx] for idx in valid_indices], type=pa.binary(16)),
+        # Single legal card: nothing to learn for a policy; NaN best_score with --skip-forced
+        'forced': pa.array(final_forced, type=pa.bool_())
+    })
+    # Older schema versions only get their own columns, with their own types
+    out_table = out_table.select(gameplay_columns)
+    if schema_version == 1:
+        out_table = out_table.set_column(out_table.schema.get_field_index('best_score'), 'best_score', pa.array([round(v) for v in final_scores], type=pa.int16()))
+    # Auction contracts: who declared what, for role-aware training
+    if contracts != "random" and contract_values_col is not None:
+        out_table = out_table.append_column('declarer', pa.array([declarers_col[idx] for idx in valid_indices], type=pa.uint8()))
+        out_table = out_table.append_column('contract_value', pa.array([contract_values_col[idx] for idx in valid_indices], type=pa.uint8()))
+    # Mover-relative values: whether they are the declaring side's or the defenders'
+    if solve_options['perspective'] == "current" and declarers_col is not None:
+        out_table = out_table.append_column('declaring_side', pa.array([players_col[idx] % 2 == declarers_col[idx] % 2 for idx in valid_indices], type=pa.bool_()))
+    # Downsampled easy positions stand for the dropped ones: weight the loss with sample_weight
+    if sample_weights is not None:
+        out_table = out_table.append_column('sample_weight', pa.array([sample_weights[idx] for idx in valid_indices], type=pa.float32()))
+        out_table = out_table.append_column('difficulty', pa.array([difficulties[idx] for idx in valid_indices], type=pa.float32()))
+    return out_table, invalid_reasons
+
+def regenerate_shard(manifest_file, shard_idx, output_file=None):
+    """Solves shard `shard_idx` of a gameplay run again from its manifest
+    (gameplay_manifest.json, next to the parts) and returns its table: the same
+    rows and labels as the part, only the solver timings and node counts may
+    differ. Without the intermediate file, the positions are generated again from
+    the seed. With `output_file`, the table is written there too."""
+    import coinche_engine
+    with open(manifest_file) as f:
+        manifest = json.load(f)
+    shards = manifest['shards']
+    if not 0 <= shard_idx < len(shards):
+        raise IndexError(f"Shard {shard_idx} out of range: {manifest_file} has {len(shards)} shards")
+    shard = shards[shard_idx]
+
+    intermediate_file = os.path.join(os.path.dirname(manifest_file), manifest['intermediate_file'])
+    if os.path.exists(intermediate_file):
+        full_table = read_gameplay_intermediate(coinche_engine, intermediate_file)
+    else:
+        batch = coinche_engine.generate_raw_gameplay_batch(manifest['gameplay_samples'], seed=manifest['seed'], side=manifest['gameplay_side'], contracts=manifest['contracts'])
+        full_table = gameplay_intermediate_table(batch)
+    batch = full_table.slice(shard['start'], shard['rows'])
+
+    coinche_engine.set_solve_options(discard_pruning=manifest['discard_pruning'])
+    try:
+        table, _ = solve_gameplay_part(coinche_engine, batch, manifest['seed'] + shard['start'], manifest['solve_options'], manifest['schema_version'], manifest['contracts'], downsample=manifest.get('downsample'))
+    finally:
+        coinche_engine.set_solve_options()
+    if table is None:
+        return None
+    table = table.replace_schema_metadata(manifest['metadata'])
+    if output_file:
+        pq.write_table(table, output_file)
+    return table
+
+def generate_datasets(bidding_samples, gameplay_samples, bidding_output_dir, gameplay_file, batch_size=1000, pimc_iterations=0, tt_log2=None, perspective="ns", seed=None, score_label="double_dummy", schema_version=None, checkpoint_every=1000, difficulty=False, gameplay_side=None, optimal_epsilon=None, objective="points", discard_pruning=False, contracts="random", tags=False, skip_forced=False, pimc_error_rate=None, max_exact_worlds=None, downsample=None):
     import coinche_engine
+    coinche_engine.set_solve_options(discard_pruning=discard_pruning)
+    if schema_version is None:
+        schema_version = coinche_engine.SCHEMA_VERSION
+    if schema_version == 1 and score_label == "ev":
+        raise ValueError("Schema version 1 stores integer scores and cannot hold 'ev' labels")
+    if schema_version < 3 and optimal_epsilon is not None:
+        raise ValueError("Optimal card sets need schema version 3 or later")
+    if schema_version < 6 and skip_forced:
+        raise ValueError("Unsolved forced moves need schema version 6 or later (their 'forced' column flags the NaN scores)")
+    gameplay_columns = coinche_engine.gameplay_schema_columns(schema_version)
+    gameplay_metadata = {'score_perspective': perspective, 'score_label': score_label, 'score_objective': objective, 'schema_version': str(schema_version), 'contract_source': contracts}
     print(f"Starting data generation (PIMC={pimc_iterations}, TT_LOG2={tt_log2})...")
     
     # --- BIDDING DATA GENERATION (Crash Resilient) ---
@@ -25,7 +281,7 @@ def generate_datasets(bidding_samples, gameplay_samples, bidding_output_dir, gam
             start_time = time.time()
             try:
                 # Returns (flattened_hands, strategies)
-                hands, strategies = coinche_engine.generate_bidding_hands(bidding_samples)
+                hands, strategies = coinche_engine.generate_bidding_hands(bidding_samples, seed)
                 
                 print("Saving raw hands to numpy files (uncompressed for mmap)...")
                 np.save(raw_hands_file, np.array(hands, dtype=np.uint32))
@@ -62,7 +318,7 @@ def generate_datasets(bidding_samples, gameplay_samples, bidding_output_dir, gam
         if processed_count >= total_samples:
             print("All samples already processed.")
         else:
-            strat_map = {0: "Random", 1: "ForceCapot", 2: "ForceBelote", 3: "ForceShape"}
+            strat_map = {0: "Random", 1: "ForceCapot", 2: "ForceBelote", 3: "ForceShape", 4: "TrumpSplit"}
             
             start_time = time.time()
             
@@ -88,7 +344,11 @@ def generate_datasets(bidding_samples, gameplay_samples, bidding_output_dir, gam
                 
                 try:
                     # Returns List[List[int]] (scores per sample)
-                    scores_batch = coinche_engine.solve_bidding_batch(hands_slice_list, pimc_iterations, tt_log2)
+                    # Offset the seed by batch start so a resumed run solves each batch identically
+                    batch_seed = None if seed is None else seed + i
+                    # Within a batch, solved samples are checkpointed so a crash only loses the last few
+                    checkpoint = os.path.join(bidding_output_dir, "bidding_checkpoint.json") if checkpoint_every > 0 else None
+                    scores_batch = coinche_engine.solve_bidding_batch(hands_slice_list, pimc_iterations, tt_log2, batch_seed, checkpoint=checkpoint, checkpoint_every=max(checkpoint_every, 1), resume=True)
                 except Exception as e:
                     print(f"Error solving batch {i}: {e}")
                     break
@@ -122,14 +382,30 @@ def generate_datasets(bidding_samples, gameplay_samples, bidding_output_dir, gam
                 schema = pa.schema([
                     ('hand_south', pa.uint32()),
                     ('scores', score_type),
-                    ('strategy', pa.string())
+                    ('strategy', pa.string()),
+                    ('deal_id', pa.binary(16))
                 ])
-
-                table = pa.Table.from_pydict({
+                columns = {
                     'hand_south': south_hands,
                     'scores': scores_batch,
-                    'strategy': strat_names
-                }, schema=schema)
+                    'strategy': strat_names,
+                    # Whole deal, to join with the gameplay datasets of the same deals
+                    'deal_id': deal_id_array(coinche_engine.deal_ids(hands_slice_list))
+                }
+
+                if difficulty:
+                    # Difficulty of each deal played in its best contract (highest NS score)
+                    best_trumps = [int(np.argmax(scores)) for scores in scores_batch]
+                    columns['difficulty'] = coinche_engine.deal_difficulty_batch(hands_slice_list, best_trumps, tt_log2)
+                    schema = schema.append(pa.field('difficulty', pa.float32()))
+
+                if tags:
+                    # Play motifs of each deal in its best contract, as for difficulty
+                    best_trumps = [int(np.argmax(scores)) for scores in scores_batch]
+                    columns['tags'] = coinche_engine.deal_tags_batch(hands_slice_list, best_trumps, tt_log2=tt_log2)
+                    schema = schema.append(pa.field('tags', pa.list_(pa.string())))
+
+                table = pa.Table.from_pydict(columns, schema=schema)
                 
                 # Write to Dataset with Partitioning
                 pq.write_to_dataset(
@@ -201,37 +477,54 @@ def generate_datasets(bidding_samples, gameplay_samples, bidding_output_dir, gam
         intermediate_file = os.path.join(gameplay_dir, "raw_gameplay_intermediate.parquet")
         
         gameplay_state_file = os.path.join(gameplay_dir, "gameplay_state.json")
+        # Everything needed to solve any part again (see regenerate_shard)
+        manifest_file = os.path.join(gameplay_dir, "gameplay_manifest.json")
+        manifest = None
+        if os.path.exists(manifest_file):
+            with open(manifest_file) as f:
+                manifest = json.load(f)
+        gameplay_seed = seed
+        if gameplay_seed is None:
+            # Unseeded runs get a seed too, kept by the manifest, so that every part can be replayed
+            gameplay_seed = manifest['seed'] if manifest else int.from_bytes(os.urandom(4), 'big')
+            print(f"Gameplay seed: {gameplay_seed}")
+        solve_options = {
+            'pimc_iterations': pimc_iterations,
+            'tt_log2': tt_log2,
+            'perspective': perspective,
+            'score_label': score_label,
+            'optimal_epsilon': optimal_epsilon,
+            'objective': objective,
+            'skip_forced': skip_forced,
+            'pimc_error_rate': pimc_error_rate,
+            'max_exact_worlds': max_exact_worlds,
+        }
+        if manifest is None:
+            manifest = {
+                'seed': gameplay_seed,
+                'gameplay_samples': gameplay_samples,
+                'gameplay_side': gameplay_side,
+                'contracts': contracts,
+                'intermediate_file': os.path.basename(intermediate_file),
+                'schema_version': schema_version,
+                'discard_pruning': discard_pruning,
+                'solve_options': solve_options,
+                'downsample': downsample,
+                'metadata': gameplay_metadata,
+                'shards': [],
+            }
+        elif (manifest['seed'], manifest['solve_options'], manifest.get('downsample')) != (gameplay_seed, solve_options, downsample):
+            raise ValueError(f"{manifest_file} was written with another seed, other solve options or another downsampling: resuming would mix them (remove the gameplay files to start over)")
 
         # 1. GENERATE RAW STATES
         if not os.path.exists(intermediate_file):
             print(f"Phase 1: Generating {gameplay_samples} raw gameplay states...")
             start_time = time.time()
             try:
-                # Returns (hands [flat], boards [list], history, trumps, tricks_won, players)
-                hands, boards, history, trumps, tricks_won, players = coinche_engine.generate_raw_gameplay_batch(gameplay_samples)
+                # GameplayBatch: one accessor per column (hands are [N][4])
+                batch = coinche_engine.generate_raw_gameplay_batch(gameplay_samples, seed=gameplay_seed, side=gameplay_side, contracts=contracts)
                 
-                # Convert to PyArrow Table
-                # Hands need to be stored as list of 4? No, flat in Rust, but here we can structuralize them.
-                # Let's store them as FixedSizeList? Or just keep flattened and reshape on read?
-                # PyArrow Table is cleaner.
-                
-                # Reshape hands to [N, 4]
-                hands_np = np.array(hands, dtype=np.uint32).reshape(-1, 4)
-                
-                # Boards: List[List[uint8]]
-                # PyArrow handles list of lists naturally
-                
-                # Tricks won: [N, 2]
-                tricks_won_np = np.array(tricks_won, dtype=np.uint8) # already list of list
-                
-                table = pa.Table.from_pydict({
-                    'hands': list(hands_np), # List of Arrays
-                    'board': boards,
-                    'history': history,
-                    'trump': trumps,
-                    'tricks_won': list(tricks_won_np),
-                    'player': players
-                })
+                table = gameplay_intermediate_table(batch)
                 
                 print(f"Saving raw states to {intermediate_file}...")
                 pq.write_table(table, intermediate_file)
@@ -256,11 +549,14 @@ def generate_datasets(bidding_samples, gameplay_samples, bidding_output_dir, gam
         
         # Load State
         processed_count = 0
+        # Samples per solve outcome ("valid" or an invalid reason), to monitor generation health
+        invalid_counts = {}
         if os.path.exists(gameplay_state_file):
             try:
                 with open(gameplay_state_file, 'r') as f:
                     state = json.load(f)
                     processed_count = state.get('processed_count', 0)
+                    invalid_counts = state.get('invalid_counts', {})
                     print(f"Resuming from offset: {processed_count}")
             except:
                 print("Could not load state file, starting from 0")
@@ -281,7 +577,7 @@ def generate_datasets(bidding_samples, gameplay_samples, bidding_output_dir, gam
             # So let's read the whole raw table (it's 100x smaller than the generated Bidding trees).
             
             # If dataset is HUGE (10M+), this might be an issue, but for <10M it's fine.
-            full_table = raw_dataset.read()
+            full_table = read_gameplay_intermediate(coinche_engine, intermediate_file)
             
             # Calculate total batches
             total_batches = (total_rows + batch_size - 1) // batch_size
@@ -293,77 +589,28 @@ def generate_datasets(bidding_samples, gameplay_samples, bidding_output_dir, gam
                 
                 batch = full_table.slice(i, batch_end - i)
                 
-                # Prepare inputs for Rust
-                # Hands: Needs to be flattened Vec<u32>
-                # Batch['hands'] is List<FixedSizeList<u32>[4]>.
-                # We need to flatten it.
-                hands_col = batch['hands'].to_pylist() # List[List[u32]]
-                # flatten
-                hands_flat = [h for sub in hands_col for h in sub]
-                
-                boards_col = batch['board'].to_pylist()
-                history_col = batch['history'].to_pylist()
-                trumps_col = batch['trump'].to_pylist()
-                tricks_won_col = batch['tricks_won'].to_pylist()
-                players_col = batch['player'].to_pylist()
-                
+                batch_seed = gameplay_seed + i
+
                 try:
-                    # Call Rust Solver
-                    best_cards, best_scores, valid_mask = coinche_engine.solve_gameplay_batch(
-                        hands_flat,
-                        boards_col,
-                        history_col,
-                        trumps_col,
-                        tricks_won_col,
-                        players_col,
-                        pimc_iterations,
-                        tt_log2
-                    )
-                    
-                    # Filter invalid results (forced moves etc)
-                    # We need to reconstruct the rows that are valid
-                    # Python list filtering is slow? 
-                    # Use PyArrow filtering or list comprehension.
-                    
-                    valid_indices = [idx for idx, v in enumerate(valid_mask) if v]
-                    
-                    if not valid_indices:
+                    checkpoint = os.path.join(gameplay_dir, "gameplay_checkpoint.json") if checkpoint_every > 0 else None
+                    out_table, invalid_reasons = solve_gameplay_part(coinche_engine, batch, batch_seed, solve_options, schema_version, contracts, checkpoint=checkpoint, checkpoint_every=checkpoint_every, downsample=downsample)
+                    for name, count in coinche_engine.invalid_reason_counts(invalid_reasons):
+                        invalid_counts[name] = invalid_counts.get(name, 0) + count
+                    # Reasons 1 and 2 are positions with nothing to play; the others (but timeouts) cannot arise
+                    impossible = sum(1 for r in invalid_reasons if 2 < r < 7)
+                    if impossible:
+                        print(f"Warning: {impossible} impossible positions in batch at {i} (reasons: {sorted(set(r for r in invalid_reasons if 2 < r < 7))})")
+
+                    # Every solved batch is a shard of the manifest, even without valid rows
+                    part_name = os.path.join("gameplay_parts", f"part_{i}.parquet")
+                    shard = {'start': i, 'rows': batch_end - i, 'file': part_name if out_table is not None else None}
+                    manifest['shards'] = sorted([s for s in manifest['shards'] if s['start'] != i] + [shard], key=lambda s: s['start'])
+                    with open(manifest_file, 'w') as f:
+                        json.dump(manifest, f, indent=2)
+
+                    if out_table is None:
                         continue
-                        
-                    # Filter inputs to save (User wants: Hand, Board, History, Trump + Label)
-                    # NOTE: Only save "My Hand" (the current player's hand) for the final dataset?
-                    # The `gameplay.rs` original writer saved only `hand` (u32).
-                    # Let's extract My Hand from the hands list.
-                    # hands_col[idx] is [H0, H1, H2, H3]. Player is players_col[idx].
-                    
-                    final_hands = []
-                    final_boards = []
-                    final_history = []
-                    final_trumps = []
-                    final_cards = []
-                    final_scores = []
-                    
-                    for idx in valid_indices:
-                         player = players_col[idx]
-                         my_hand = hands_col[idx][player]
-                         
-                         final_hands.append(my_hand)
-                         final_boards.append(boards_col[idx])
-                         final_history.append(history_col[idx])
-                         final_trumps.append(trumps_col[idx])
-                         final_cards.append(best_cards[idx])
-                         final_scores.append(best_scores[idx])
-                         
-                    # Create Batch Table
-                    out_table = pa.Table.from_pydict({
-                        'hand': final_hands,
-                        'board': final_boards,
-                        'history': final_history,
-                        'trump': final_trumps,
-                        'best_card': final_cards,
-                        'best_score': final_scores
-                    })
-                    
+
                     # Write to Output File (Append mode?)
                     # Parquet doesn't support random append easily to single file without some trickery.
                     # `write_to_dataset` creates folders.
@@ -384,8 +631,10 @@ def generate_datasets(bidding_samples, gameplay_samples, bidding_output_dir, gam
                     # Let's use the partitioned approach because it is robust, then merge if needed.
                     # Or just write `part-{i}.parquet`.
                     
-                    part_file = os.path.join(gameplay_dir, "gameplay_parts", f"part_{i}.parquet")
+                    part_file = os.path.join(gameplay_dir, part_name)
                     os.makedirs(os.path.dirname(part_file), exist_ok=True)
+                    # Record which team best_score refers to ("ns" / "current"), what it measures and the column layout
+                    out_table = out_table.replace_schema_metadata(gameplay_metadata)
                     pq.write_table(out_table, part_file)
                     
                 except Exception as e:
@@ -397,10 +646,12 @@ def generate_datasets(bidding_samples, gameplay_samples, bidding_output_dir, gam
                 # Update State
                 processed_count = batch_end
                 with open(gameplay_state_file, 'w') as f:
-                    json.dump({'processed_count': processed_count}, f)
+                    json.dump({'processed_count': processed_count, 'invalid_counts': invalid_counts}, f)
 
             total_duration = time.time() - start_time
             print(f"Gameplay generation complete. Parts saved in {os.path.join(gameplay_dir, 'gameplay_parts')}")
+            print("Solve outcomes: " + ", ".join(f"{name}={count}" for name, count in invalid_counts.items() if count))
+            gameplay_metadata['solve_outcomes'] = json.dumps(invalid_counts)
             
             # Optional: Merge parts into final file?
             # User specified `gameplay_output` (e.g. gameplay.parquet).
@@ -421,6 +672,7 @@ def generate_datasets(bidding_samples, gameplay_samples, bidding_output_dir, gam
                 if os.path.exists(parts_dir):
                     dataset = pq.ParquetDataset(parts_dir)
                     merged_table = dataset.read()
+                    merged_table = merged_table.replace_schema_metadata(gameplay_metadata)
                     pq.write_table(merged_table, final_gameplay_file)
                     print(f"Merge complete: {final_gameplay_file}")
                     # Optional: Cleanup parts?
@@ -428,6 +680,135 @@ def generate_datasets(bidding_samples, gameplay_samples, bidding_output_dir, gam
             except Exception as e:
                 print(f"Error merging: {e}")
 
+def generate_opening_leads(num_deals, contract_value, contract_trump, declarer, output_file, batch_size=1000, seed=None, tt_log2=None):
+    """Opening lead table: deals where `declarer` bid the contract, with the
+    double-dummy defenders' points of every card the leader may lead."""
+    import coinche_engine
+    contract = coinche_engine.Bid(contract_value, contract_trump)
+    schema = pa.schema([
+        ('hands', pa.list_(pa.uint32())),
+        ('deal_id', pa.binary(16)),
+        ('hand', pa.uint32()),
+        ('declarer', pa.uint8()),
+        ('leader', pa.uint8()),
+        ('contract_value', pa.uint8()),
+        ('contract_trump', pa.uint8()),
+        ('auction', pa.list_(pa.uint8())),
+        ('leads', pa.list_(pa.uint8())),
+        ('lead_scores', pa.list_(pa.int16())),
+    ])
+    metadata = {'score_perspective': 'defenders', 'auction_layout': 'flat_action_index'}
+    os.makedirs(os.path.dirname(output_file) or ".", exist_ok=True)
+    print(f"Generating {num_deals} opening lead tables ({contract_value} {contract_trump}, declarer {declarer})...")
+    start_time = time.time()
+    with pq.ParquetWriter(output_file, schema.with_metadata(metadata)) as writer:
+        for i in range(0, num_deals, batch_size):
+            batch_seed = None if seed is None else seed + i
+            samples = coinche_engine.generate_opening_lead_batch(min(batch_size, num_deals - i), contract, declarer, seed=batch_seed, tt_log2=tt_log2)
+            table = pa.Table.from_pydict({
+                'hands': [list(s.hands) for s in samples],
+                'deal_id': deal_id_array([s.deal_id for s in samples]),
+                'hand': [s.hands[s.leader] for s in samples],
+                'declarer': [s.declarer for s in samples],
+                'leader': [s.leader for s in samples],
+                'contract_value': [contract_value] * len(samples),
+                'contract_trump': [contract_trump] * len(samples),
+                'auction': [s.auction for s in samples],
+                'leads': [s.leads for s in samples],
+                'lead_scores': [s.lead_scores for s in samples],
+            }, schema=schema)
+            writer.write_table(table)
+            print(f"  {i + len(samples)}/{num_deals} deals")
+    print(f"Opening lead tables written to {output_file} in {time.time() - start_time:.2f}s.")
+
+def generate_evaluation(num_samples, output_file, min_cards=4, max_cards=20, batch_size=1000, seed=None, tt_log2=None):
+    """Static evaluation dataset: random positions with their features, the
+    solver's heuristic value and the exact value, both for the team to move."""
+    import coinche_engine
+    schema = pa.schema([
+        ('state', pa.string()),
+        ('team', pa.uint8()),
+        ('cards_left', pa.uint8()),
+        ('features', pa.list_(pa.float32(), coinche_engine.EVALUATION_FEATURES)),
+        ('heuristic', pa.int32()),
+        ('exact', pa.int32()),
+    ])
+    metadata = {'score_perspective': 'current_player', 'score_objective': 'points'}
+    os.makedirs(os.path.dirname(output_file) or ".", exist_ok=True)
+    print(f"Generating {num_samples} evaluation samples ({min_cards}-{max_cards} cards left)...")
+    start_time = time.time()
+    with pq.ParquetWriter(output_file, schema.with_metadata(metadata)) as writer:
+        for i in range(0, num_samples, batch_size):
+            batch_seed = None if seed is None else seed + i
+            samples = coinche_engine.generate_evaluation_batch(min(batch_size, num_samples - i), seed=batch_seed, min_cards=min_cards, max_cards=max_cards, tt_log2=tt_log2)
+            table = pa.Table.from_pydict({
+                'state': [s.state for s in samples],
+                'team': [s.team for s in samples],
+                'cards_left': [s.cards_left for s in samples],
+                'features': [s.features for s in samples],
+                'heuristic': [s.heuristic for s in samples],
+                'exact': [s.exact for s in samples],
+            }, schema=schema)
+            writer.write_table(table)
+            print(f"  {i + len(samples)}/{num_samples} samples")
+    print(f"Evaluation samples written to {output_file} in {time.time() - start_time:.2f}s.")
+
+def generate_human_play(num_games, output_file, temperature=10.0, override_rate=0.05, batch_size=1000, seed=None, tt_log2=None):
+    """Human-like play traces for imitation learning: whole deals whose cards are
+    drawn from the softmax of the solver's values, with occasional rule-of-thumb
+    plays. One row per deal."""
+    import coinche_engine
+    schema = pa.schema([
+        ('hands', pa.list_(pa.uint32(), 4)),
+        ('deal_id', pa.binary(16)),
+        ('trump', pa.uint8()),
+        ('plays', pa.list_(pa.uint8(), 32)),
+        ('points_ns', pa.uint16()),
+        ('points_ew', pa.uint16()),
+        ('point_loss', pa.list_(pa.int32(), 32)),
+        ('overridden', pa.list_(pa.bool_(), 32)),
+    ])
+    metadata = {'temperature': str(temperature), 'override_rate': str(override_rate)}
+    os.makedirs(os.path.dirname(output_file) or ".", exist_ok=True)
+    print(f"Generating {num_games} human-like games (temperature {temperature}, override rate {override_rate})...")
+    start_time = time.time()
+    with pq.ParquetWriter(output_file, schema.with_metadata(metadata)) as writer:
+        for i in range(0, num_games, batch_size):
+            batch_seed = None if seed is None else seed + i
+            games = coinche_engine.generate_human_play_batch(min(batch_size, num_games - i), temperature=temperature, override_rate=override_rate, seed=batch_seed, tt_log2=tt_log2)
+            table = pa.Table.from_pydict({
+                'hands': [list(g.hands) for g in games],
+                'deal_id': deal_id_array([g.deal_id for g in games]),
+                'trump': [g.trump for g in games],
+                'plays': [g.plays for g in games],
+                'points_ns': [g.points[0] for g in games],
+                'points_ew': [g.points[1] for g in games],
+                'point_loss': [g.point_loss for g in games],
+                'overridden': [g.overridden for g in games],
+            }, schema=schema)
+            writer.write_table(table)
+            print(f"  {i + len(games)}/{num_games} games")
+    print(f"Human-like games written to {output_file} in {time.time() - start_time:.2f}s.")
+
+def generate_puzzle_file(num_puzzles, output_file, min_swing=20, min_cards=4, max_cards=16, motif=None, batch_size=1000, seed=None, tt_log2=None):
+    """Card-play puzzles for training apps: positions with a single winning card,
+    one JSON record per line (see Puzzle.to_json)."""
+    import coinche_engine
+    constraints = coinche_engine.PuzzleConstraints(min_swing=min_swing, min_cards=min_cards, max_cards=max_cards, motif=motif)
+    os.makedirs(os.path.dirname(output_file) or ".", exist_ok=True)
+    print(f"Generating {num_puzzles} puzzles ({constraints})...")
+    start_time = time.time()
+    written = 0
+    with open(output_file, "w") as f:
+        for i in range(0, num_puzzles, batch_size):
+            batch_seed = None if seed is None else seed + i
+            puzzles = coinche_engine.generate_puzzles(min(batch_size, num_puzzles - i), constraints, seed=batch_seed, tt_log2=tt_log2)
+            for puzzle in puzzles:
+                f.write(puzzle.to_json() + "\n")
+            written += len(puzzles)
+            print(f"  {written} puzzles from {min(i + batch_size, num_puzzles)} searches")
+    print(f"{written} puzzles written to {output_file} in {time.time() - start_time:.2f}s.")
+
 if __name__ == "__main__":
     parser = argparse.ArgumentParser(description="Generate Coinche datasets.")
     parser.add_argument("--bidding-samples", type=int, default=10000, help="Number of bidding samples")
@@ -437,14 +818,63 @@ if __name__ == "__main__":
     parser.add_argument("--batch-size", type=int, default=10000, help="Batch size for solving")
     parser.add_argument("--threads", type=int, default=None, help="Number of threads to use (limit CPU usage)")
     parser.add_argument("--pimc", type=int, default=0, help="Number of PIMC iterations per hand (Bidding & Gameplay). 0 = Double Dummy.")
+    parser.add_argument("--perspective", type=str, default="ns", choices=["ns", "current"], help="Team whose points best_score reports: 'ns' (North-South) or 'current' (team of the player to move, whatever its seat, no sign flip needed). 'current' adds a 'declaring_side' column, true when the player to move is the declarer or its partner.")
+    parser.add_argument("--pimc-error-rate", type=float, default=None, help="Adaptive PIMC for gameplay: stop sampling worlds once the voted card is decided at this error rate (e.g. 0.05). --pimc is then the most worlds per position: clear decisions stop early, close ones get the full budget.")
+    parser.add_argument("--max-exact-worlds", type=int, default=None, help="With --pimc, positions with at most this many ways to deal the hidden cards (e.g. 1680 = 3 cards per hidden hand) solve every one of them instead of sampling: exact labels whatever the seed.")
+    parser.add_argument("--seed", type=int, default=None, help="Seed for reproducible generation and PIMC sampling. Identical seeds give identical datasets whatever the thread count. Without it, gameplay gets a random seed, kept in gameplay_manifest.json with the solve options so that any part can be regenerated (see --regenerate-shard).")
+    parser.add_argument("--score-label", type=str, default="double_dummy", choices=["double_dummy", "ev"], help="With --pimc, what best_score holds: 'double_dummy' (value of the true deal, uses the hidden cards) or 'ev' (mean value over the PIMC worlds).")
+    parser.add_argument("--schema-version", type=int, default=None, help="Gameplay file layout to write. Default: latest. 1 = hand, board, history, trump, best_card, best_score (int16) only.")
+    parser.add_argument("--checkpoint-every", type=int, default=1000, help="Samples solved between two checkpoints inside a solve batch; a crashed run resumes from the last one. 0 = no checkpoints.")
+    parser.add_argument("--difficulty", action="store_true", help="Add a 'difficulty' column (0-1) to the bidding data for curricula: solver cost, opening lead sensitivity and trump balance of each deal in its best contract. Solves every opening lead again.")
+    parser.add_argument("--skip-forced", action="store_true", help="Do not solve gameplay positions with a single legal card: they keep that card as best_card, a NaN best_score and forced=true. Saves solver time; filter them out (or mask their value) when training.")
+    parser.add_argument("--tags", action="store_true", help="Add a 'tags' column to the bidding data: play motifs (throw_in, trump_promotion, discard_squeeze) of each deal in its best contract along the solver's line, to build themed training sets. Plays every deal out with the solver, several full solves per deal.")
+    parser.add_argument("--downsample-easy", type=float, default=None, help="Keep only this fraction (e.g. 0.2) of the easy gameplay positions, drawn before solving: those with a difficulty under --easy-threshold (same label as --difficulty, at the position) or a single legal card. Harder positions are all kept. Adds 'sample_weight' (1 / fraction for kept easy positions, 1 otherwise: weight the loss with it for unbiased training) and 'difficulty' columns.")
+    parser.add_argument("--easy-threshold", type=float, default=0.3, help="Difficulty (0-1) under which --downsample-easy treats a gameplay position as easy. Scores depend on --tt-log2.")
     parser.add_argument("--tt-log2", type=int, default=None, help="Transposition Table size (log2). Default: None (22 -> 64MB). Example: 24 -> 256MB.")
+    parser.add_argument("--gameplay-side", type=str, default=None, choices=["declarer", "defense"], help="Keep only gameplay positions whose player to move is on this side. The declarer is the contract owner with --contracts threshold; random contracts have no auction, so the seat with the strongest hand in the trump is taken as the declarer.")
+    parser.add_argument("--objective", type=str, default="points", choices=["points", "tricks", "lexicographic"], help="What gameplay best_score counts: final 'points', 'tricks' won, or 'lexicographic' (tricks * 400 + points, tricks first).")
+    parser.add_argument("--discard-pruning", action="store_true", help="Experimental: prune the search by assuming pointless discards (faster, labels may be off by a few points). Check the trade-off with the engine's 'bench pruning' first.")
+    parser.add_argument("--contracts", type=str, default="random", choices=["random", "threshold"], help="Contract of each gameplay deal: a 'random' suit, or the one 'threshold' bidders reach in an auction (deals they all pass are redrawn). With 'threshold', 'declarer' and 'contract_value' columns are added.")
+    parser.add_argument("--optimal-epsilon", type=float, default=None, help="Solve every legal card of each gameplay position and store in 'optimal_cards' all those within this many points of the best (0 = exact ties). Default: optimal_cards only holds best_card.")
+    parser.add_argument("--opening-leads", type=int, default=0, help="Number of opening lead tables to generate for the contract of --lead-contract.")
+    parser.add_argument("--lead-contract", type=str, default="80:2", help="Contract of the opening lead tables as VALUE:TRUMP (trump 0-5 as in Suit, e.g. 100:2 for 100 hearts).")
+    parser.add_argument("--lead-declarer", type=int, default=0, help="Seat that bid the contract of the opening lead tables (0=S, 1=W, 2=N, 3=E); the seat on its left leads.")
+    parser.add_argument("--lead-output", type=str, default="../../dist/datasets/opening_leads.parquet", help="Output file for the opening lead tables")
+    parser.add_argument("--human-games", type=int, default=0, help="Number of human-like games to generate: solver play with softmax noise and rule-of-thumb overrides, for imitation-learning baselines.")
+    parser.add_argument("--human-temperature", type=float, default=10.0, help="Softmax temperature of the human-like games, in points: a card worth this much less than another is e times less likely. 0 = always a best card.")
+    parser.add_argument("--human-override-rate", type=float, default=0.05, help="Probability that a card of the human-like games is the rule-of-thumb card instead.")
+    parser.add_argument("--human-output", type=str, default="../../dist/datasets/human_play.parquet", help="Output file for the human-like games")
+    parser.add_argument("--puzzles", type=int, default=0, help="Number of card-play puzzles to search for: positions where a single legal card reaches the double-dummy optimum. Searches that find none are dropped, so the file may hold fewer.")
+    parser.add_argument("--puzzle-swing", type=int, default=20, help="Points every other card of a puzzle must cost at least.")
+    parser.add_argument("--puzzle-cards", type=str, default="4:16", help="Range MIN:MAX of the cards left in the puzzle positions.")
+    parser.add_argument("--puzzle-motif", type=str, default=None, choices=["throw_in", "trump_promotion", "discard_squeeze"], help="Keep only puzzles whose solution shows this motif.")
+    parser.add_argument("--puzzle-output", type=str, default="../../dist/datasets/puzzles.jsonl", help="Output file for the puzzles (JSON lines)")
+    parser.add_argument("--deal-cache", type=str, default=None, help="File caching the double-dummy values of full deals across runs (created if missing). Bidding solves look deals up there first, so rerunning with the same seed only solves new deals.")
+    parser.add_argument("--evaluation-samples", type=int, default=0, help="Number of static evaluation samples (position features, heuristic value, exact value) to generate.")
+    parser.add_argument("--evaluation-cards", type=str, default="4:20", help="Range MIN:MAX of the cards left in the evaluation positions.")
+    parser.add_argument("--evaluation-output", type=str, default="../../dist/datasets/evaluation.parquet", help="Output file for the evaluation samples")
     
+    parser.add_argument("--regenerate-shard", type=int, default=None, help="Only solve again this gameplay shard (index in the 'shards' of --manifest) and write it to --shard-output: same rows and labels as the original part.")
+    parser.add_argument("--manifest", type=str, default=None, help="Manifest of the gameplay run for --regenerate-shard (gameplay_manifest.json, written next to the gameplay output).")
+    parser.add_argument("--shard-output", type=str, default=None, help="Output file of --regenerate-shard.")
+
     args = parser.parse_args()
 
     if args.threads is not None and args.threads > 0:
         os.environ["RAYON_NUM_THREADS"] = str(args.threads)
         print(f"Setting RAYON_NUM_THREADS={args.threads}")
 
+    if args.deal_cache:
+        import coinche_engine
+        print(f"Deal cache {args.deal_cache}: {coinche_engine.open_deal_cache(args.deal_cache)} deals")
+
+    if args.regenerate_shard is not None:
+        if not args.manifest or not args.shard_output:
+            parser.error("--regenerate-shard needs --manifest and --shard-output")
+        table = regenerate_shard(args.manifest, args.regenerate_shard, args.shard_output)
+        print(f"Shard {args.regenerate_shard}: " + (f"{table.num_rows} rows written to {args.shard_output}" if table is not None else "no valid position"))
+        sys.exit(0)
+
     try:
         generate_datasets(
             args.bidding_samples, 
@@ -453,8 +883,40 @@ if __name__ == "__main__":
             args.gameplay_output,
             args.batch_size,
             args.pimc,
-            args.tt_log2
+            args.tt_log2,
+            args.perspective,
+            args.seed,
+            args.score_label,
+            args.schema_version,
+            args.checkpoint_every,
+            args.difficulty,
+            args.gameplay_side,
+            args.optimal_epsilon,
+            args.objective,
+            args.discard_pruning,
+            args.contracts,
+            args.tags,
+            args.skip_forced,
+            args.pimc_error_rate,
+            args.max_exact_worlds,
+            {'threshold': args.easy_threshold, 'keep_fraction': args.downsample_easy} if args.downsample_easy is not None else None
         )
+        if args.opening_leads > 0:
+            value, trump = (int(x) for x in args.lead_contract.split(":"))
+            generate_opening_leads(args.opening_leads, value, trump, args.lead_declarer, args.lead_output, args.batch_size, args.seed, args.tt_log2)
+        if args.evaluation_samples > 0:
+            min_cards, max_cards = (int(x) for x in args.evaluation_cards.split(":"))
+            generate_evaluation(args.evaluation_samples, args.evaluation_output, min_cards, max_cards, args.batch_size, args.seed, args.tt_log2)
+        if args.human_games > 0:
+            generate_human_play(args.human_games, args.human_output, args.human_temperature, args.human_override_rate, args.batch_size, args.seed, args.tt_log2)
+        if args.puzzles > 0:
+            min_cards, max_cards = (int(x) for x in args.puzzle_cards.split(":"))
+            generate_puzzle_file(args.puzzles, args.puzzle_output, args.puzzle_swing, min_cards, max_cards, args.puzzle_motif, args.batch_size, args.seed, args.tt_log2)
+        if args.deal_cache:
+            entries, hits, misses, write_errors = coinche_engine.deal_cache_stats()
+            print(f"Deal cache: {entries} deals, {hits} hits, {misses} misses")
+            if write_errors:
+                print(f"⚠️ Deal cache: {write_errors} deals could not be saved to {args.deal_cache}")
     except KeyboardInterrupt:
         print("\n\n⚠️ Generation interrupted by user.")
         print("✅ Progress has been saved. Run the command again to resume.")
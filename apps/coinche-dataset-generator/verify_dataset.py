@@ -0,0 +1,33 @@
+import argparse
+import sys
+
+import coinche_engine
+
+
+def main():
+    parser = argparse.ArgumentParser(description="Re-solve a sample of a generated dataset and compare labels")
+    parser.add_argument("path", type=str, help="Parquet file or dataset directory")
+    parser.add_argument("--fraction", type=float, default=0.01, help="Fraction of rows to re-solve")
+    parser.add_argument("--seed", type=int, default=None, help="Seed for row sampling")
+    parser.add_argument("--tt-log2", type=int, default=None, help="Transposition Table size (log2)")
+    args = parser.parse_args()
+
+    report = coinche_engine.verify_dataset(args.path, args.fraction, args.seed, args.tt_log2)
+    print(report)
+    for error in report.errors:
+        print(f"  {error}")
+
+    if report.kind == "gameplay" and report.rows_resolved < report.rows_checked:
+        print(f"⚠️ {report.rows_checked - report.rows_resolved} rows lack the full deal and were only checked for legality.")
+    if report.card_mismatches:
+        print(f"ℹ️ {report.card_mismatches} best_card differences (expected on ties or with PIMC labels).")
+
+    if report.is_ok():
+        print("✅ Dataset labels match the engine.")
+    else:
+        print("❌ Dataset labels disagree with the engine.")
+        sys.exit(1)
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,560 @@
+//! Solver benchmarks.
+//!
+//! Build without the Python extension feature so the binary links:
+//! `cargo run --release --no-default-features --bin bench -- bidding --size 500`
+//!
+//! Every subcommand prints a single JSON object on stdout (progress bars go to stderr).
+//! With `--features profiling`, hot-path call counts are printed to stderr as well.
+
+use clap::{Args, Parser, Subcommand};
+use coinche_engine::data_gen::bidding::{generate_hand_batch, solve_hand_batch};
+use coinche_engine::data_gen::common::{generate_random_hands, sample_rng};
+use coinche_engine::data_gen::gameplay::{
+    generate_raw_gameplay_batch, invalid_reason_counts, solve_gameplay_batch, AdaptivePimc,
+    GameplaySolveOptions, GameplaySolveRequest, ScoreLabel,
+};
+use coinche_engine::data_gen::policy_eval::evaluate_policy_on_dataset;
+use coinche_engine::gameplay::bot::BotStrength;
+use coinche_engine::gameplay::corpus::{corpus_hands, load_corpus, try_embedded_corpus};
+use coinche_engine::gameplay::playing::PlayingState;
+use coinche_engine::solver::{
+    forces_capot, nodes_searched, random_ending, set_partition_cache, set_pimc_tt_sharing,
+    set_solve_options, solve, solve_for_team, solve_with_options, tt_stats, Objective,
+    SolveOptions, TtStats,
+};
+use rand::Rng;
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+#[derive(Parser)]
+#[command(name = "bench", about = "Coinche solver benchmarks (JSON output)")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Args)]
+struct CommonArgs {
+    /// Number of deals / positions to solve.
+    #[arg(long, default_value_t = 100)]
+    size: usize,
+    /// Seed for reproducible runs.
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Worker threads (default: all cores).
+    #[arg(long)]
+    threads: Option<usize>,
+    /// Transposition table size (log2 of entries).
+    #[arg(long)]
+    tt_log2: Option<u8>,
+    /// Share full-depth results across deals (solver partition cache).
+    #[arg(long)]
+    partition_cache: bool,
+    /// Let PIMC worlds share each thread's TT (unseeded runs only, not reproducible).
+    #[arg(long)]
+    tt_sharing: bool,
+    /// Experimental "pass the trick" pruning (inexact, see `SolveOptions`).
+    #[arg(long)]
+    discard_pruning: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Solve bidding deals (one score per trump).
+    Bidding {
+        #[command(flatten)]
+        common: CommonArgs,
+        /// PIMC iterations per deal (1 = double dummy).
+        #[arg(long, default_value_t = 1)]
+        pimc: usize,
+        /// Corpus of recorded deals (transcripts, `.gz` with the deal-corpus feature)
+        /// whose first `size` deals replace the random ones; `embedded` for the
+        /// corpus built in with the deal-corpus feature.
+        #[arg(long)]
+        corpus: Option<PathBuf>,
+    },
+    /// Solve random mid-game positions.
+    Gameplay {
+        #[command(flatten)]
+        common: CommonArgs,
+        /// PIMC iterations per position (0 or 1 = double dummy).
+        #[arg(long, default_value_t = 0)]
+        pimc: usize,
+        /// PIMC score label: "double_dummy" (true deal) or "ev" (mean over worlds).
+        #[arg(long, default_value = "double_dummy")]
+        score_label: String,
+        /// Adaptive PIMC: stop once the vote is decided with this error rate, `pimc`
+        /// being the most worlds per position.
+        #[arg(long)]
+        pimc_error_rate: Option<f64>,
+    },
+    /// Time full deals with a suit trump at increasing search depths.
+    SolverDepth {
+        #[command(flatten)]
+        common: CommonArgs,
+        /// Depths (in cards) to benchmark.
+        #[arg(long, value_delimiter = ',', default_value = "4,8,12,16,20,24,28,32")]
+        depths: Vec<u8>,
+    },
+    /// Compare bidding scores and cost across PIMC iteration counts.
+    Pimc {
+        #[command(flatten)]
+        common: CommonArgs,
+        /// PIMC iteration counts to benchmark.
+        #[arg(long, value_delimiter = ',', default_value = "1,5,10,20")]
+        iterations: Vec<usize>,
+    },
+    /// Detect forced capots for the leading team on full deals (one per suit trump).
+    Capot {
+        #[command(flatten)]
+        common: CommonArgs,
+        /// Cross-check every deal against a full solve (capot iff score >= 252).
+        #[arg(long)]
+        verify: bool,
+    },
+    /// Accuracy and cost of discard pruning against exact solves of random endings.
+    Pruning {
+        #[command(flatten)]
+        common: CommonArgs,
+        /// Cards left in the endings.
+        #[arg(long, default_value_t = 20)]
+        cards_left: u32,
+    },
+    /// Move-match rate and point loss of a bot strength against the labels of a
+    /// gameplay dataset (its first `size` rows).
+    Policy {
+        #[command(flatten)]
+        common: CommonArgs,
+        /// Gameplay dataset: a Parquet file or a directory of Parquet files.
+        #[arg(long)]
+        dataset: PathBuf,
+        /// Bot strength: "heuristic", "pimc", "pimc_ev" or "double_dummy".
+        #[arg(long, default_value = "heuristic")]
+        policy: String,
+        /// PIMC iterations per position.
+        #[arg(long, default_value_t = 20)]
+        iterations: usize,
+    },
+}
+
+impl CommonArgs {
+    /// Applies the solver-wide settings: thread pool, partition cache, TT sharing,
+    /// solve options.
+    fn init_runtime(&self) {
+        set_partition_cache(self.partition_cache);
+        set_pimc_tt_sharing(self.tt_sharing);
+        set_solve_options(SolveOptions {
+            discard_pruning: self.discard_pruning,
+        });
+        if let Some(n) = self.threads {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(n)
+                .build_global()
+                .expect("thread pool already initialised");
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "size": self.size,
+            "seed": self.seed,
+            "threads": rayon::current_num_threads(),
+            "tt_log2": self.tt_log2,
+            "partition_cache": self.partition_cache,
+            "tt_sharing": self.tt_sharing,
+            "discard_pruning": self.discard_pruning,
+        })
+    }
+}
+
+fn timing(elapsed_s: f64, size: usize) -> Value {
+    json!({
+        "total_s": elapsed_s,
+        "per_sample_ms": 1000.0 * elapsed_s / size.max(1) as f64,
+        "samples_per_s": size as f64 / elapsed_s.max(f64::EPSILON),
+    })
+}
+
+fn bench_bidding(common: &CommonArgs, pimc: usize, corpus: Option<&Path>) -> Result<Value, String> {
+    let (hands, strategies) = match corpus {
+        Some(path) => {
+            let matches = if path == Path::new("embedded") {
+                try_embedded_corpus()?
+            } else {
+                load_corpus(path)?
+            };
+            let deals = &matches[..common.size.min(matches.len())];
+            (corpus_hands(deals), vec![0; deals.len()])
+        }
+        None => generate_hand_batch(common.size, common.seed),
+    };
+    let size = hands.len() / 4;
+
+    let start = Instant::now();
+    let scores = solve_hand_batch(hands, pimc, common.tt_log2, common.seed, None)?;
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let max_score = scores
+        .iter()
+        .flatten()
+        .cloned()
+        .fold(f32::NEG_INFINITY, f32::max);
+    let deals_with_capot = scores
+        .iter()
+        .filter(|s| s.iter().any(|&v| v >= 250.0))
+        .count();
+    let capot_strategy = strategies.iter().filter(|&&s| s == 1).count();
+
+    Ok(json!({
+        "benchmark": "bidding",
+        "config": common.to_json(),
+        "pimc": pimc,
+        "corpus": corpus.map(|p| p.display().to_string()),
+        "deals": size,
+        "timing": timing(elapsed, size),
+        "max_score": max_score,
+        "deals_with_capot": deals_with_capot,
+        "capot_strategy_deals": capot_strategy,
+    }))
+}
+
+fn bench_gameplay(
+    common: &CommonArgs,
+    pimc: usize,
+    score_label: &str,
+    pimc_error_rate: Option<f64>,
+) -> Result<Value, String> {
+    let score_label = ScoreLabel::parse(score_label)?;
+    let adaptive = pimc_error_rate
+        .map(|rate| AdaptivePimc::new(8, rate))
+        .transpose()?;
+    let mut request =
+        GameplaySolveRequest::new(generate_raw_gameplay_batch(common.size, common.seed), pimc)?;
+    request.options = GameplaySolveOptions {
+        tt_log2: common.tt_log2,
+        seed: common.seed,
+        score_label,
+        adaptive,
+        ..GameplaySolveOptions::default()
+    };
+
+    let start = Instant::now();
+    let (_, scores, valid, nodes, solve_times, agreement, .., invalid_reasons, forced) =
+        solve_gameplay_batch(&request, None)?;
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let total_nodes: u64 = nodes.iter().sum();
+    let max_time_us = solve_times.iter().max().cloned().unwrap_or(0);
+    let mean_agreement = agreement.iter().sum::<f32>() / agreement.len().max(1) as f32;
+    let mean_score = scores.iter().sum::<f32>() / scores.len().max(1) as f32;
+
+    Ok(json!({
+        "benchmark": "gameplay",
+        "config": common.to_json(),
+        "pimc": pimc,
+        "score_label": score_label.name(),
+        "pimc_error_rate": pimc_error_rate,
+        "timing": timing(elapsed, common.size),
+        "valid": valid.iter().filter(|&&v| v).count(),
+        "invalid_reasons": invalid_reason_counts(&invalid_reasons)?
+            .into_iter()
+            .filter(|&(name, n)| name != "valid" && n > 0)
+            .map(|(name, n)| (name.to_string(), json!(n)))
+            .collect::<serde_json::Map<_, _>>(),
+        "forced": forced.iter().filter(|&&f| f).count(),
+        "nodes_total": total_nodes,
+        "nodes_per_s": total_nodes as f64 / elapsed.max(f64::EPSILON),
+        "max_solve_time_us": max_time_us,
+        "mean_agreement": mean_agreement,
+        "mean_score": mean_score,
+    }))
+}
+
+fn bench_solver_depth(common: &CommonArgs, depths: &[u8]) -> Value {
+    let states: Vec<PlayingState> = (0..common.size)
+        .map(|i| {
+            let mut rng = sample_rng(common.seed, i as u64);
+            // Suit trumps only: NT and AT deals search differently.
+            let mut state = PlayingState::new(rng.gen_range(0..4));
+            state.hands = generate_random_hands(&mut rng);
+            state
+        })
+        .collect();
+
+    // Reference moves at full depth, to measure how often shallower searches agree.
+    let reference: Vec<u8> = states
+        .par_iter()
+        .map(|s| solve(s, false, Some(32), common.tt_log2).1)
+        .collect();
+
+    let results: Vec<Value> = depths
+        .iter()
+        .map(|&depth| {
+            let start = Instant::now();
+            let (moves, searches): (Vec<u8>, Vec<(u64, TtStats)>) = states
+                .par_iter()
+                .map(|s| {
+                    let (before, tt_before) = (nodes_searched(), tt_stats());
+                    let (_, mv) = solve(s, false, Some(depth), common.tt_log2);
+                    (
+                        mv,
+                        (nodes_searched() - before, tt_stats().since(&tt_before)),
+                    )
+                })
+                .unzip();
+            let elapsed = start.elapsed().as_secs_f64();
+            let agree = moves.iter().zip(&reference).filter(|(a, b)| a == b).count();
+            let nodes: u64 = searches.iter().map(|s| s.0).sum();
+            let tt = searches
+                .iter()
+                .fold(TtStats::default(), |t, (_, s)| TtStats {
+                    probes: t.probes + s.probes,
+                    hits: t.hits + s.hits,
+                    stores: t.stores + s.stores,
+                    replacements: t.replacements + s.replacements,
+                });
+
+            json!({
+                "depth": depth,
+                "timing": timing(elapsed, common.size),
+                "nodes_total": nodes,
+                "tt": {
+                    "hit_rate": tt.hit_rate(),
+                    "stores": tt.stores,
+                    "replacements": tt.replacements,
+                },
+                "move_agreement": agree as f64 / common.size.max(1) as f64,
+            })
+        })
+        .collect();
+
+    json!({
+        "benchmark": "solver-depth",
+        "config": common.to_json(),
+        "results": results,
+    })
+}
+
+fn bench_pimc(common: &CommonArgs, iterations: &[usize]) -> Result<Value, String> {
+    let (hands, _) = generate_hand_batch(common.size, common.seed);
+
+    let results: Vec<Value> = iterations
+        .iter()
+        .map(|&n| {
+            let start = Instant::now();
+            let scores = solve_hand_batch(hands.clone(), n, common.tt_log2, common.seed, None)?;
+            let elapsed = start.elapsed().as_secs_f64();
+            let flat: Vec<f32> = scores.into_iter().flatten().collect();
+            let mean = flat.iter().sum::<f32>() / flat.len().max(1) as f32;
+
+            Ok(json!({
+                "iterations": n,
+                "timing": timing(elapsed, common.size),
+                "mean_score": mean,
+            }))
+        })
+        .collect::<Result<_, String>>()?;
+
+    Ok(json!({
+        "benchmark": "pimc",
+        "config": common.to_json(),
+        "results": results,
+    }))
+}
+
+fn bench_capot(common: &CommonArgs, verify: bool) -> Value {
+    let states: Vec<PlayingState> = (0..common.size)
+        .flat_map(|i| {
+            let mut rng = sample_rng(common.seed, i as u64);
+            let hands = generate_random_hands(&mut rng);
+            (0..4).map(move |trump| {
+                let mut state = PlayingState::new(trump);
+                state.hands = hands;
+                state
+            })
+        })
+        .collect();
+
+    let start = Instant::now();
+    let forced: Vec<bool> = states.par_iter().map(|s| forces_capot(s, 0)).collect();
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let mut report = json!({
+        "benchmark": "capot",
+        "config": common.to_json(),
+        "timing": timing(elapsed, states.len()),
+        "positions": states.len(),
+        "forced_capots": forced.iter().filter(|&&f| f).count(),
+    });
+
+    if verify {
+        let start = Instant::now();
+        let solved: Vec<bool> = states
+            .par_iter()
+            .map(|s| solve_for_team(s, 0, Some(32), common.tt_log2).0 >= 252)
+            .collect();
+        let elapsed = start.elapsed().as_secs_f64();
+        let mismatches = forced.iter().zip(&solved).filter(|(a, b)| a != b).count();
+        report["verify"] = json!({
+            "timing": timing(elapsed, states.len()),
+            "mismatches": mismatches,
+        });
+    }
+    report
+}
+
+fn bench_pruning(common: &CommonArgs, cards_left: u32) -> Value {
+    let states: Vec<PlayingState> = (0..common.size)
+        .map(|i| random_ending(cards_left, &mut sample_rng(common.seed, i as u64)))
+        .collect();
+    let exact = SolveOptions::default();
+    let pruned = SolveOptions {
+        discard_pruning: true,
+    };
+    // (value, move, nodes) of every ending
+    let run = |options: SolveOptions| {
+        let start = Instant::now();
+        let results: Vec<(i32, u8, u64)> = states
+            .par_iter()
+            .map(|s| {
+                let before = nodes_searched();
+                let (value, card) = solve_with_options(s, 0, Objective::Points, Some(32), options);
+                (value, card, nodes_searched() - before)
+            })
+            .collect();
+        (results, start.elapsed().as_secs_f64())
+    };
+    let (exact_results, exact_time) = run(exact);
+    let (pruned_results, pruned_time) = run(pruned);
+
+    let errors: Vec<i32> = exact_results
+        .iter()
+        .zip(&pruned_results)
+        .map(|(e, p)| (p.0 - e.0).abs())
+        .collect();
+    // Points the pruned move gives away, each move replayed exactly.
+    let move_losses: Vec<i32> = states
+        .par_iter()
+        .zip(&exact_results)
+        .zip(&pruned_results)
+        .map(|((s, e), p)| {
+            let mut child = *s;
+            child.play_card(p.1);
+            let reached = if child.is_terminal() {
+                Objective::Points.value(&child, 0)
+            } else {
+                solve_with_options(&child, 0, Objective::Points, Some(32), exact).0
+            };
+            (reached - e.0).abs()
+        })
+        .collect();
+    let nodes = |results: &[(i32, u8, u64)]| results.iter().map(|r| r.2).sum::<u64>();
+    let n = states.len().max(1) as f64;
+
+    json!({
+        "benchmark": "pruning",
+        "config": common.to_json(),
+        "cards_left": cards_left,
+        "exact": {"timing": timing(exact_time, states.len()), "nodes": nodes(&exact_results)},
+        "pruned": {"timing": timing(pruned_time, states.len()), "nodes": nodes(&pruned_results)},
+        "exact_values": errors.iter().filter(|&&e| e == 0).count(),
+        "mean_abs_error": errors.iter().sum::<i32>() as f64 / n,
+        "max_abs_error": errors.iter().max(),
+        "exact_moves": move_losses.iter().filter(|&&l| l == 0).count(),
+        "mean_move_loss": move_losses.iter().sum::<i32>() as f64 / n,
+    })
+}
+
+fn bench_policy(
+    common: &CommonArgs,
+    dataset: &Path,
+    policy: &str,
+    iterations: usize,
+) -> Result<Value, String> {
+    let strength = BotStrength::parse(policy)?;
+    let start = Instant::now();
+    let evaluation = evaluate_policy_on_dataset(
+        dataset,
+        strength,
+        iterations,
+        common.seed,
+        Some(common.size),
+        common.tt_log2,
+    )?;
+    let elapsed = start.elapsed().as_secs_f64();
+    Ok(json!({
+        "benchmark": "policy",
+        "config": common.to_json(),
+        "dataset": dataset.display().to_string(),
+        "policy": evaluation.policy,
+        "iterations": iterations,
+        "timing": timing(elapsed, evaluation.rows_evaluated),
+        "rows": evaluation.rows_total,
+        "evaluated": evaluation.rows_evaluated,
+        "matches": evaluation.matches,
+        "match_rate": evaluation.match_rate(),
+        "mean_loss": evaluation.mean_loss(),
+        "max_loss": evaluation.max_loss,
+    }))
+}
+
+fn main() {
+    let cli = Cli::parse();
+
+    let report = match &cli.command {
+        Command::Bidding {
+            common,
+            pimc,
+            corpus,
+        } => {
+            common.init_runtime();
+            bench_bidding(common, *pimc, corpus.as_deref())
+        }
+        Command::Gameplay {
+            common,
+            pimc,
+            score_label,
+            pimc_error_rate,
+        } => {
+            common.init_runtime();
+            bench_gameplay(common, *pimc, score_label, *pimc_error_rate)
+        }
+        Command::SolverDepth { common, depths } => {
+            common.init_runtime();
+            Ok(bench_solver_depth(common, depths))
+        }
+        Command::Pimc { common, iterations } => {
+            common.init_runtime();
+            bench_pimc(common, iterations)
+        }
+        Command::Capot { common, verify } => {
+            common.init_runtime();
+            Ok(bench_capot(common, *verify))
+        }
+        Command::Pruning { common, cards_left } => {
+            common.init_runtime();
+            Ok(bench_pruning(common, *cards_left))
+        }
+        Command::Policy {
+            common,
+            dataset,
+            policy,
+            iterations,
+        } => {
+            common.init_runtime();
+            bench_policy(common, dataset, policy, *iterations)
+        }
+    };
+    // Batch solvers already dumped their own counts; this covers direct solves.
+    coinche_engine::profiling::dump("bench");
+
+    match report {
+        Ok(value) => println!("{}", serde_json::to_string_pretty(&value).unwrap()),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Coordinator of a dataset generation spread over machines (see
+//! `data_gen::distributed`): hands out work units to `gen-worker`s and collects
+//! their Parquet shards.
+//!
+//! `cargo run --release --no-default-features --bin gen-coordinator -- \
+//!     --bind 0.0.0.0:7878 --rows 1000000 --unit-rows 10000 --seed 1 \
+//!     --output dist/datasets/bidding_parts`
+//!
+//! The job options (`--pimc`, `--tt-log2`, or a command after `--` replacing the
+//! built-in bidding generation) describe what the workers run: only workers started
+//! with the same ones are served. Prints a JSON summary on stdout.
+
+use clap::Parser;
+use coinche_engine::data_gen::distributed::{run_coordinator, CoordinatorConfig, GenerationJob};
+use serde_json::json;
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[derive(Parser)]
+#[command(
+    name = "gen-coordinator",
+    about = "Hand out dataset generation units to workers and collect their shards"
+)]
+struct Cli {
+    /// Address the workers connect to.
+    #[arg(long, default_value = "0.0.0.0:7878")]
+    bind: String,
+    /// Rows of the whole run.
+    #[arg(long)]
+    rows: u64,
+    /// Rows per work unit (and shard).
+    #[arg(long, default_value_t = 10_000)]
+    unit_rows: usize,
+    /// Seed of the run; the unit starting at row `start` uses `seed + start`.
+    #[arg(long)]
+    seed: u64,
+    /// Directory receiving part-00000.parquet, ... and the run settings.
+    #[arg(long)]
+    output: PathBuf,
+    /// Tries of a unit before the run fails.
+    #[arg(long, default_value_t = 3)]
+    max_attempts: usize,
+    /// Seconds without news of a worker generating a unit before the unit is handed
+    /// out again.
+    #[arg(long, default_value_t = 3600)]
+    unit_timeout: u64,
+    /// PIMC iterations of the built-in bidding generation (1 = double dummy).
+    #[arg(long, default_value_t = 1)]
+    pimc: usize,
+    /// Transposition table size of the built-in bidding generation (log2 of entries).
+    #[arg(long)]
+    tt_log2: Option<u8>,
+    /// Command the workers run for a unit, instead of the built-in bidding generation.
+    #[arg(last = true)]
+    command: Vec<String>,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let job = if cli.command.is_empty() {
+        GenerationJob::Bidding {
+            pimc_iterations: cli.pimc,
+            tt_log2: cli.tt_log2,
+        }
+    } else {
+        GenerationJob::Command(cli.command)
+    };
+    let config = CoordinatorConfig {
+        rows: cli.rows,
+        unit_rows: cli.unit_rows,
+        seed: cli.seed,
+        job,
+        output_dir: cli.output,
+        max_attempts: cli.max_attempts,
+        unit_timeout: Duration::from_secs(cli.unit_timeout),
+    };
+
+    let start = Instant::now();
+    let result = TcpListener::bind(&cli.bind)
+        .map_err(|e| format!("{}: {}", cli.bind, e))
+        .and_then(|listener| run_coordinator(listener, config));
+    match result {
+        Ok(report) => {
+            let summary = json!({
+                "units": report.units,
+                "skipped": report.skipped,
+                "generated": report.generated,
+                "retries": report.retries,
+                "shards": report.shards,
+                "elapsed_s": start.elapsed().as_secs_f64(),
+            });
+            println!("{}", serde_json::to_string_pretty(&summary).unwrap());
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//! Worker of a dataset generation spread over machines (see
+//! `data_gen::distributed`): generates the units a `gen-coordinator` hands out
+//! until the run is over.
+//!
+//! `cargo run --release --no-default-features --bin gen-worker -- \
+//!     --coordinator host:7878 --work-dir /tmp/coinche-worker`
+//!
+//! The worker runs its own job, never one sent by the coordinator: the built-in
+//! bidding generation (`--pimc`, `--tt-log2`), or the command after `--`, with
+//! `{seed}`, `{start}`, `{rows}` and `{output}` replaced in its arguments; it must
+//! write the unit's Parquet shard at `{output}`. The job must be the coordinator's.
+//! Prints a JSON summary on stdout.
+
+use clap::Parser;
+use coinche_engine::data_gen::distributed::{run_worker, GenerationJob};
+use serde_json::json;
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Parser)]
+#[command(
+    name = "gen-worker",
+    about = "Generate the dataset units handed out by a coordinator"
+)]
+struct Cli {
+    /// Address of the coordinator.
+    #[arg(long)]
+    coordinator: String,
+    /// Directory holding a unit's shard until it is sent.
+    #[arg(long, default_value = "worker")]
+    work_dir: PathBuf,
+    /// Worker threads (default: all cores).
+    #[arg(long)]
+    threads: Option<usize>,
+    /// PIMC iterations of the built-in bidding generation (1 = double dummy).
+    #[arg(long, default_value_t = 1)]
+    pimc: usize,
+    /// Transposition table size of the built-in bidding generation (log2 of entries).
+    #[arg(long)]
+    tt_log2: Option<u8>,
+    /// Command generating a unit, instead of the built-in bidding generation.
+    #[arg(last = true)]
+    command: Vec<String>,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Some(threads) = cli.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .expect("thread pool already initialised");
+    }
+
+    let job = if cli.command.is_empty() {
+        GenerationJob::Bidding {
+            pimc_iterations: cli.pimc,
+            tt_log2: cli.tt_log2,
+        }
+    } else {
+        GenerationJob::Command(cli.command)
+    };
+
+    let start = Instant::now();
+    match run_worker(&cli.coordinator, &job, &cli.work_dir) {
+        Ok(report) => {
+            let summary = json!({
+                "units": report.units,
+                "failures": report.failures,
+                "elapsed_s": start.elapsed().as_secs_f64(),
+            });
+            println!("{}", serde_json::to_string_pretty(&summary).unwrap());
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! Samples the bidding strength of random hands and writes the percentile table
+//! shipped with `data_gen::hand_percentile`.
+//!
+//! `cargo run --release --no-default-features --bin hand-percentiles -- \
+//!     --output src/data_gen/hand_percentile/table.rs`
+//!
+//! Prints a JSON summary on stdout.
+
+use clap::Parser;
+use coinche_engine::data_gen::hand_percentile::{strength_counts, table_source};
+use serde_json::json;
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Parser)]
+#[command(
+    name = "hand-percentiles",
+    about = "Sample hand strengths into the hand percentile table"
+)]
+struct Cli {
+    /// Random hands to sample.
+    #[arg(long, default_value_t = 10_000_000)]
+    hands: usize,
+    /// Seed for a reproducible table.
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    /// Rust source file receiving the table.
+    #[arg(long)]
+    output: PathBuf,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let start = Instant::now();
+    let counts = strength_counts(cli.hands, Some(cli.seed));
+    if let Err(e) = std::fs::write(
+        &cli.output,
+        table_source(&counts, cli.hands, Some(cli.seed)),
+    ) {
+        eprintln!("Error: {}: {}", cli.output.display(), e);
+        std::process::exit(1);
+    }
+    let summary = json!({
+        "hands": cli.hands,
+        "strengths": counts.iter().map(|c| c.len()).collect::<Vec<_>>(),
+        "elapsed_s": start.elapsed().as_secs_f64(),
+    });
+    println!("{}", serde_json::to_string_pretty(&summary).unwrap());
+}
@@ -0,0 +1,64 @@
+//! Consolidation of dataset shards (e.g. from several machines) into size-balanced
+//! shards, after checking that their columns and label metadata agree.
+//!
+//! `cargo run --release --no-default-features --bin merge-dataset -- \
+//!     host-a/gameplay_parts host-b/gameplay_parts --output dist/datasets/gameplay_merged`
+//!
+//! Prints a JSON summary on stdout.
+
+use clap::Parser;
+use coinche_engine::data_gen::merge::{merge_datasets, MergeOptions};
+use serde_json::json;
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Parser)]
+#[command(
+    name = "merge-dataset",
+    about = "Check and merge Parquet dataset shards into size-balanced shards"
+)]
+struct Cli {
+    /// Parquet files or directories holding them, merged in this order.
+    #[arg(required = true)]
+    inputs: Vec<PathBuf>,
+    /// Directory receiving part-00000.parquet, ... (must hold no Parquet file).
+    #[arg(long)]
+    output: PathBuf,
+    /// Largest number of rows per output shard.
+    #[arg(long, default_value_t = 1_000_000)]
+    rows_per_shard: usize,
+    /// Number of output shards, instead of `rows_per_shard`.
+    #[arg(long)]
+    shards: Option<usize>,
+    /// Only check the inputs and print the planned shards.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    let options = MergeOptions {
+        rows_per_shard: cli.rows_per_shard,
+        shards: cli.shards,
+        dry_run: cli.dry_run,
+    };
+
+    let start = Instant::now();
+    match merge_datasets(&cli.inputs, &cli.output, &options) {
+        Ok(report) => {
+            let summary = json!({
+                "files": report.files,
+                "rows": report.rows,
+                "shard_rows": report.shard_rows,
+                "shards": report.shards,
+                "metadata": report.metadata,
+                "elapsed_s": start.elapsed().as_secs_f64(),
+            });
+            println!("{}", serde_json::to_string_pretty(&summary).unwrap());
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -1,7 +1,9 @@
+use crate::gameplay::deal::validate_deal;
 use crate::gameplay::playing::{
     PlayingState, RANK_10, RANK_7, RANK_8, RANK_9, RANK_A, RANK_J, RANK_K, RANK_Q,
 };
-use crate::solver::solve;
+use crate::gameplay::rules::RuleSet;
+use crate::solver::{pimc_tt_scope, solve_all_leaders, solve_deal, with_tt_scope, Score};
 use arrow::array::{Float32Array, Int16Array, ListArray, UInt32Array};
 use arrow::datatypes::{DataType, Field, Schema};
 use arrow::record_batch::RecordBatch;
@@ -11,17 +13,20 @@ use parquet::file::properties::WriterProperties;
 use rand::distributions::WeightedIndex;
 use rand::prelude::*;
 use rayon::prelude::*;
+use serde_json::json;
 use std::fs::File;
 use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
 
-use super::common::{generate_biased_hands, GenStrategy};
+use super::checkpoint::{fingerprint, run_checkpointed, CheckpointConfig};
+use super::common::{generate_biased_hands, sample_rng, GenStrategy};
 
-pub fn generate_hand_batch(batch_size: usize) -> (Vec<u32>, Vec<u8>) {
-    // Strategy Weights: Random=40, Capot=20, Belote=20, Shape=20
-    let weights = [40, 20, 20, 20];
+/// With `seed`, the batch is reproducible (see `common::sample_rng`).
+pub fn generate_hand_batch(batch_size: usize, seed: Option<u64>) -> (Vec<u32>, Vec<u8>) {
+    // Strategy Weights: Random=40, Capot=20, Belote=20, Shape=20, TrumpSplit=20
+    let weights = [40, 20, 20, 20, 20];
 
     // Common shapes for Shape Bias
     // Common shapes for Shape Bias (Must sum to 8)
@@ -32,39 +37,49 @@ pub fn generate_hand_batch(batch_size: usize) -> (Vec<u32>, Vec<u8>) {
         [3, 3, 2, 0], // Distributional (void)
     ];
 
+    // Trumps of (South, West, North, East), South declaring: fits, support and
+    // defensive splits that change how the play goes
+    let trump_splits = [
+        [5, 1, 2, 0], // Long trumps, partner support, one defender void
+        [5, 2, 1, 0],
+        [4, 2, 2, 0],
+        [4, 1, 3, 0], // Strong fit
+        [4, 2, 1, 1], // Even defensive split
+        [6, 1, 1, 0],
+        [3, 2, 3, 0],
+        [3, 3, 2, 0], // Trumps stacked behind the declarer
+    ];
+
     // We return a tuple:
     // 1. Flattened hands: Vec<u32> of size batch_size * 4.
     //    Each block of 4 u32s represents one deal: [South, West, North, East].
     // 2. Strategies: Vec<u8> of size batch_size.
+    let dist = WeightedIndex::new(weights).unwrap();
     let (hands_flattened, strategies): (Vec<Vec<u32>>, Vec<u8>) = (0..batch_size)
         .into_par_iter()
         .progress_count(batch_size as u64)
-        .map_init(
-            || {
-                let rng = rand::thread_rng();
-                let dist = WeightedIndex::new(&weights).unwrap();
-                (rng, dist)
-            },
-            |(rng, dist), _| {
-                let target_trump = rng.gen_range(0..4) as u8;
-
-                let strategy_idx = dist.sample(rng);
-                let strategy = match strategy_idx {
-                    0 => GenStrategy::Random,
-                    1 => GenStrategy::ForceCapot,
-                    2 => GenStrategy::ForceBelote,
-                    3 => {
-                        let shape = shapes[rng.gen_range(0..shapes.len())];
-                        GenStrategy::ForceShape(shape)
-                    }
-                    _ => GenStrategy::Random,
-                };
-
-                let hands = generate_biased_hands(target_trump, strategy);
-                // hands is [u32; 4]. Convert to Vec<u32>.
-                (hands.to_vec(), strategy_idx as u8)
-            },
-        )
+        .map(|i| {
+            let mut rng = sample_rng(seed, i as u64);
+            let target_trump = rng.gen_range(0..4) as u8;
+
+            let strategy_idx = dist.sample(&mut rng);
+            let strategy = match strategy_idx {
+                0 => GenStrategy::Random,
+                1 => GenStrategy::ForceCapot,
+                2 => GenStrategy::ForceBelote,
+                3 => {
+                    let shape = shapes[rng.gen_range(0..shapes.len())];
+                    GenStrategy::ForceShape(shape)
+                }
+                4 => GenStrategy::TrumpSplit(trump_splits[rng.gen_range(0..trump_splits.len())]),
+                _ => GenStrategy::Random,
+            };
+
+            let hands = generate_biased_hands(target_trump, strategy, &mut rng)
+                .expect("built-in strategies are satisfiable");
+            // hands is [u32; 4]. Convert to Vec<u32>.
+            (hands.to_vec(), strategy_idx as u8)
+        })
         .unzip();
 
     // Flatten the list of lists into a single Vec<u32>
@@ -193,28 +208,29 @@ fn evaluate_hand_potential(hand: u32, trump: u8) -> i32 {
     score
 }
 
+/// Heuristic strength of `hand` for a contract in `trump`, on the scale of
+/// `evaluate_hand_potential` (NT/AT: half the face value of the cards).
+pub(crate) fn contract_strength(hand: u32, trump: u8) -> i32 {
+    if trump < 4 {
+        evaluate_hand_potential(hand, trump)
+    } else {
+        crate::gameplay::playing::cards_points(hand, trump) as i32 / 2
+    }
+}
+
 // Helper to compute weak hand face value (heuristic fallback)
 fn compute_face_value(hand: u32, trump: u8) -> f32 {
-    let mut points = 0;
-    for c in 0..32 {
-        if (hand & (1 << c)) != 0 {
-            let s = (c / 8) as u8;
-            let r = (c % 8) as usize;
-            if s == trump {
-                points += crate::gameplay::playing::POINTS_TRUMP[r];
-            } else {
-                points += crate::gameplay::playing::POINTS_NON_TRUMP[r];
-            }
-        }
-    }
-    points as f32
+    crate::gameplay::playing::cards_points(hand, trump) as f32
 }
 
+/// With `seed`, PIMC worlds are reproducible (see `common::sample_rng`).
 pub fn solve_hand_batch(
     flattened_hands: Vec<u32>,
     pimc_iterations: usize,
     tt_log2: Option<u8>,
-) -> Vec<Vec<f32>> {
+    seed: Option<u64>,
+    checkpoint: Option<&CheckpointConfig>,
+) -> Result<Vec<Vec<f32>>, String> {
     // flattened_hands length should be divisible by 4
     let num_samples = flattened_hands.len() / 4;
 
@@ -250,110 +266,154 @@ pub fn solve_hand_batch(
     let weak_ref = weak_count.clone();
     let capot_ref = capot_count.clone();
 
-    let scores_batch: Vec<Vec<f32>> = flattened_hands
-        .par_chunks(4)
-        .progress_with(pb)
-        .map(|hand_chunk| {
-            // hand_chunk is &[u32] of length 4
-            let mut hands = [0u32; 4];
-            hands.copy_from_slice(hand_chunk);
-
-            // Contracts: 0=D, 1=S, 2=H, 3=C (No NT/AT)
-            if pimc_iterations > 1 {
-                // PIMC Logic: Ignore other hands, regenerate world based on South Hand
-                let south_hand = hands[0];
-                let mut unseen_cards = Vec::with_capacity(24);
-
-                // Pre-calculate unseen
-                for c in 0..32 {
-                    if (south_hand & (1 << c)) == 0 {
-                        unseen_cards.push(c);
-                    }
+    let solve_sample = |sample_idx: usize, hand_chunk: &[u32]| {
+        // hand_chunk is &[u32] of length 4
+        let mut hands = [0u32; 4];
+        hands.copy_from_slice(hand_chunk);
+
+        // Contracts: 0=D, 1=S, 2=H, 3=C (No NT/AT)
+        if pimc_iterations > 1 {
+            // PIMC Logic: Ignore other hands, regenerate world based on South Hand
+            let south_hand = hands[0];
+            let mut unseen_cards = Vec::with_capacity(24);
+
+            // Pre-calculate unseen
+            for c in 0..32 {
+                if (south_hand & (1 << c)) == 0 {
+                    unseen_cards.push(c);
                 }
+            }
 
-                let mut rng = rand::thread_rng();
-                let mut scores = Vec::with_capacity(4);
+            let mut rng = sample_rng(seed, sample_idx as u64);
+            let mut scores = Vec::with_capacity(4);
 
-                for trump in 0..4 {
-                    // 1. FILTER WEAK HANDS (Junk Hand Heuristic)
-                    let potential = evaluate_hand_potential(south_hand, trump as u8);
+            for trump in 0..4 {
+                // 1. FILTER WEAK HANDS (Junk Hand Heuristic)
+                let potential = evaluate_hand_potential(south_hand, trump);
 
-                    /*
-                    if potential >= 10000 {
-                        // FORCE CAPOT DETECTED
-                        capot_ref.fetch_add(1, Ordering::Relaxed);
-                        scores.push(252.0);
-                        continue;
+                /*
+                if potential >= 10000 {
+                    // FORCE CAPOT DETECTED
+                    capot_ref.fetch_add(1, Ordering::Relaxed);
+                    scores.push(252.0);
+                    continue;
+                }
+
+                if potential < 40 {
+                    // Skip PIMC, return fallback
+                    weak_ref.fetch_add(1, Ordering::Relaxed);
+                    scores.push(compute_face_value(south_hand, trump));
+                    continue;
+                }
+                */
+
+                let mut total_score: i32 = 0;
+                let scope = pimc_tt_scope(seed);
+
+                for _ in 0..pimc_iterations {
+                    unseen_cards.shuffle(&mut rng);
+
+                    let mut state = PlayingState::new(trump);
+                    state.hands[0] = south_hand;
+
+                    // Distribute 8 to West, 8 to North, 8 to East
+                    // (Indices 0..8, 8..16, 16..24)
+                    let mut w = 0;
+                    for i in 0..8 {
+                        w |= 1 << unseen_cards[i];
                     }
+                    state.hands[1] = w;
 
-                    if potential < 40 {
-                        // Skip PIMC, return fallback
-                        weak_ref.fetch_add(1, Ordering::Relaxed);
-                        scores.push(compute_face_value(south_hand, trump as u8));
-                        continue;
+                    let mut n = 0;
+                    for i in 8..16 {
+                        n |= 1 << unseen_cards[i];
                     }
-                    */
-
-                    let mut total_score: i32 = 0;
-
-                    for _ in 0..pimc_iterations {
-                        unseen_cards.shuffle(&mut rng);
-
-                        let mut state = PlayingState::new(trump as u8);
-                        state.hands[0] = south_hand;
-
-                        // Distribute 8 to West, 8 to North, 8 to East
-                        // (Indices 0..8, 8..16, 16..24)
-                        let mut w = 0;
-                        for i in 0..8 {
-                            w |= 1 << unseen_cards[i];
-                        }
-                        state.hands[1] = w;
-
-                        let mut n = 0;
-                        for i in 8..16 {
-                            n |= 1 << unseen_cards[i];
-                        }
-                        state.hands[2] = n;
-
-                        let mut e = 0;
-                        for i in 16..24 {
-                            e |= 1 << unseen_cards[i];
-                        }
-                        state.hands[3] = e;
-
-                        let (s, _) = solve(&state, false, Some(32), tt_log2);
-                        total_score += s as i32;
+                    state.hands[2] = n;
+
+                    let mut e = 0;
+                    for i in 16..24 {
+                        e |= 1 << unseen_cards[i];
                     }
+                    state.hands[3] = e;
 
-                    let avg = total_score as f32 / pimc_iterations as f32;
-                    scores.push(avg);
-                }
-                scores
-            } else {
-                // Double Dummy on specific deal
-                let mut scores = Vec::with_capacity(4);
-                for trump in 0..4 {
-                    let mut state = PlayingState::new(trump as u8);
-                    state.hands = hands;
-                    let (score, _) = solve(&state, false, Some(32), tt_log2);
-                    scores.push(score as f32);
+                    total_score +=
+                        with_tt_scope(scope, || solve_deal(&state.hands, trump, 0, tt_log2));
                 }
-                scores
+
+                let avg = total_score as f32 / pimc_iterations as f32;
+                scores.push(avg);
             }
-        })
-        .collect();
+            scores
+        } else {
+            // Double Dummy on specific deal
+            let mut scores = Vec::with_capacity(4);
+            for trump in 0..4 {
+                scores.push(solve_deal(&hands, trump, 0, tt_log2) as f32);
+            }
+            scores
+        }
+    };
+
+    let inputs = fingerprint(
+        &json!([&flattened_hands, pimc_iterations, seed]),
+        RuleSet::default(),
+    );
+    let scores_batch = run_checkpointed(num_samples, inputs, checkpoint, |i| {
+        let scores = solve_sample(i, &flattened_hands[i * 4..i * 4 + 4]);
+        pb.inc(1);
+        scores
+    });
+    pb.finish();
 
     running.store(false, Ordering::Relaxed);
-    println!(
+    eprintln!(
         "Stats: Weak Hands: {}, Force Capot: {}",
         weak_count.load(Ordering::Relaxed),
         capot_count.load(Ordering::Relaxed)
     );
+    crate::profiling::dump("solve_hand_batch");
 
     scores_batch
 }
 
+/// Checks a batch of full deals: 4 flattened hands and one contract per deal.
+pub(crate) fn validate_deals(flattened_hands: &[u32], trumps: &[u8]) -> Result<(), String> {
+    if flattened_hands.len() != trumps.len() * 4 {
+        return Err(format!(
+            "{} hands for {} trumps, expected 4 per deal",
+            flattened_hands.len(),
+            trumps.len()
+        ));
+    }
+    for (i, (chunk, &trump)) in flattened_hands.chunks(4).zip(trumps).enumerate() {
+        if trump >= 6 {
+            return Err(format!("Deal {}: invalid trump {}", i, trump));
+        }
+        let hands: [u32; 4] = chunk.try_into().unwrap();
+        validate_deal(&hands).map_err(|e| format!("Deal {}: {}", i, e))?;
+    }
+    Ok(())
+}
+
+/// Double-dummy NS points of each deal with every seat leading the first trick in
+/// turn (see `solve_all_leaders`). `flattened_hands` holds 4 hands per deal,
+/// `trumps` one contract suit per deal.
+pub fn solve_leaders_batch(
+    flattened_hands: &[u32],
+    trumps: &[u8],
+    tt_log2: Option<u8>,
+) -> Result<Vec<[Score; 4]>, String> {
+    validate_deals(flattened_hands, trumps)?;
+
+    let scores = flattened_hands
+        .par_chunks(4)
+        .zip(trumps.par_iter())
+        .map(|(chunk, &trump)| solve_all_leaders(chunk.try_into().unwrap(), trump, tt_log2))
+        .collect();
+    crate::profiling::dump("solve_leaders_batch");
+    Ok(scores)
+}
+
 // NOTE: This function is kept but needs updates if we want to use it with the new format directly.
 // For now, I'm assuming we do the writing in Python or update this signature later.
 // The Python plan says we write Parquet from Python using PyArrow,
@@ -548,8 +608,9 @@ mod tests {
         // Test that hands generated by GenStrategy::ForceCapot are actually detected as such.
         // run 100 times to cover random variations
         for _ in 0..100 {
-            let trump = rand::thread_rng().gen_range(0..4);
-            let hands = generate_biased_hands(trump, GenStrategy::ForceCapot);
+            let mut rng = rand::thread_rng();
+            let trump = rng.gen_range(0..4);
+            let hands = generate_biased_hands(trump, GenStrategy::ForceCapot, &mut rng).unwrap();
             let south_hand = hands[0];
 
             let score = evaluate_hand_potential(south_hand, trump);
@@ -560,4 +621,38 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_seeded_batches_are_reproducible() {
+        let (hands_a, strategies_a) = generate_hand_batch(20, Some(7));
+        let (hands_b, strategies_b) = generate_hand_batch(20, Some(7));
+        assert_eq!(hands_a, hands_b);
+        assert_eq!(strategies_a, strategies_b);
+
+        let (hands_c, _) = generate_hand_batch(20, Some(8));
+        assert_ne!(hands_a, hands_c);
+
+        // The hands ignore the thread count.
+        for threads in [1, 4] {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            let (hands, strategies) = pool.install(|| generate_hand_batch(20, Some(7)));
+            assert_eq!(hands, hands_a);
+            assert_eq!(strategies, strategies_a);
+        }
+    }
+
+    #[test]
+    fn test_leaders_batch_rejects_invalid_deals() {
+        let deal = [0x0000_00FF, 0x0000_FF00, 0x00FF_0000, 0xFF00_0000];
+        assert!(solve_leaders_batch(&deal, &[HEARTS, SPADES], None).is_err());
+        assert!(solve_leaders_batch(&deal, &[6], None)
+            .unwrap_err()
+            .contains("invalid trump"));
+        let err = solve_leaders_batch(&[deal[0], deal[1], deal[2], deal[2]], &[HEARTS], None)
+            .unwrap_err();
+        assert!(err.starts_with("Deal 0:"), "{}", err);
+    }
 }
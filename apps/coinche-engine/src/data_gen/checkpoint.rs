@@ -0,0 +1,394 @@
+//! Checkpoints of long solve batches: the results solved so far are appended to a
+//! JSON Lines file every few samples, and a run started with `resume` reloads them
+//! and only solves the missing samples. The first line records the file format, the
+//! batch size and a fingerprint of the inputs, so a checkpoint is never resumed into
+//! a different batch or read as another format; every other line is one
+//! `[sample, result]` pair.
+//!
+//! Writes only append the new rows, so they cost the same however far the batch
+//! is, and the workers hand their rows over without waiting for the disk. A crash
+//! while writing leaves at most a partial last line, which resuming drops. The
+//! file is deleted once the batch completes.
+
+use super::gameplay::{InvalidReason, PimcConfidence, SolvedGameplaySample};
+use crate::gameplay::rules::RuleSet;
+use crate::solver::solve_options;
+use rayon::prelude::*;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Version of the checkpoint files, in their first line.
+pub const CHECKPOINT_FORMAT: u32 = 2;
+
+/// Where and how often a batch saves its progress.
+#[derive(Clone, Debug)]
+pub struct CheckpointConfig {
+    pub path: PathBuf,
+    /// Samples solved between two writes.
+    pub every: usize,
+    /// Reload the results of an existing checkpoint instead of starting over.
+    pub resume: bool,
+}
+
+impl CheckpointConfig {
+    pub fn new(path: impl Into<PathBuf>, every: usize, resume: bool) -> Self {
+        Self {
+            path: path.into(),
+            every: every.max(1),
+            resume,
+        }
+    }
+}
+
+/// A solved sample that can be stored in a checkpoint.
+pub trait CheckpointRow: Sized {
+    fn to_json(&self) -> Value;
+    fn from_json(value: &Value) -> Option<Self>;
+}
+
+impl CheckpointRow for Vec<f32> {
+    fn to_json(&self) -> Value {
+        json!(self)
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        value
+            .as_array()?
+            .iter()
+            .map(|v| v.as_f64().map(|f| f as f32))
+            .collect()
+    }
+}
+
+impl CheckpointRow for SolvedGameplaySample {
+    fn to_json(&self) -> Value {
+        let c = &self.confidence;
+        json!([
+            self.best_card,
+            self.best_score,
+            self.valid(),
+            self.nodes,
+            self.solve_time_us,
+            c.agreement,
+            c.vote_entropy,
+            c.value_variance,
+            self.optimal_cards,
+            self.invalid_reason.map_or(0, InvalidReason::code),
+            self.forced
+        ])
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let v = value.as_array().filter(|v| (9..=11).contains(&v.len()))?;
+        let float = |i: usize| v[i].as_f64().map(|f| f as f32);
+        // Forced moves left unsolved have a NaN score, which JSON writes as null.
+        let best_score = if v[1].is_null() { f32::NAN } else { float(1)? };
+        // Rows written before reason codes were only ever invalid for lack of a move;
+        // they count as terminal.
+        let invalid_reason = match (v[2].as_bool()?, v.get(9)) {
+            (true, _) => None,
+            (false, None) => Some(InvalidReason::Terminal),
+            (false, Some(code)) => Some(InvalidReason::from_code(code.as_u64()? as u8)?),
+        };
+        Some(SolvedGameplaySample {
+            best_card: v[0].as_u64()? as u8,
+            best_score,
+            invalid_reason,
+            nodes: v[3].as_u64()?,
+            solve_time_us: v[4].as_u64()?,
+            confidence: PimcConfidence {
+                agreement: float(5)?,
+                vote_entropy: float(6)?,
+                value_variance: float(7)?,
+            },
+            optimal_cards: v[8].as_u64()? as u32,
+            forced: match v.get(10) {
+                Some(forced) => forced.as_bool()?,
+                None => false,
+            },
+        })
+    }
+}
+
+/// Fingerprint of the inputs of a batch solved under `rules`, stored in its
+/// checkpoints: FNV-1a of their JSON encoding, with the process-wide solver options,
+/// so that it is the same for every build and run given the same batch.
+pub fn fingerprint(inputs: &Value, rules: RuleSet) -> u64 {
+    let canonical = json!({
+        "inputs": inputs,
+        "capot_bonus": rules.capot_bonus,
+        "der_in_capot": rules.der_in_capot,
+        "belote": rules.belote,
+        "discard_pruning": solve_options().discard_pruning,
+    });
+    canonical
+        .to_string()
+        .bytes()
+        .fold(0xCBF2_9CE4_8422_2325, |h: u64, b| {
+            (h ^ b as u64).wrapping_mul(0x0000_0100_0000_01B3)
+        })
+}
+
+/// Rows solved since the last write.
+struct Progress<T> {
+    done: BTreeMap<usize, T>,
+    pending: Vec<String>,
+}
+
+/// Solves samples `0..num_samples` in parallel with `solve`, checkpointing the
+/// results as configured. Returns them in sample order.
+pub fn run_checkpointed<T, F>(
+    num_samples: usize,
+    inputs_fingerprint: u64,
+    checkpoint: Option<&CheckpointConfig>,
+    solve: F,
+) -> Result<Vec<T>, String>
+where
+    T: CheckpointRow + Send,
+    F: Fn(usize) -> T + Sync + Send,
+{
+    let Some(config) = checkpoint else {
+        return Ok((0..num_samples).into_par_iter().map(solve).collect());
+    };
+
+    let done = if config.resume && config.path.exists() {
+        load(config, num_samples, inputs_fingerprint)?
+    } else {
+        BTreeMap::new()
+    };
+    // A fresh file, which also drops a partial last line of the resumed one.
+    let file = Mutex::new(create(config, num_samples, inputs_fingerprint, &done)?);
+    let todo: Vec<usize> = (0..num_samples).filter(|i| !done.contains_key(i)).collect();
+    let progress = Mutex::new(Progress {
+        done,
+        pending: Vec::new(),
+    });
+
+    todo.into_par_iter().try_for_each(|i| {
+        let row = solve(i);
+        let line = json!([i, row.to_json()]).to_string();
+        let full = {
+            let mut p = progress.lock().unwrap();
+            p.done.insert(i, row);
+            p.pending.push(line);
+            if p.pending.len() >= config.every {
+                std::mem::take(&mut p.pending)
+            } else {
+                Vec::new()
+            }
+        };
+        if !full.is_empty() {
+            append(config, &mut file.lock().unwrap(), &full)?;
+        }
+        Ok::<_, String>(())
+    })?;
+
+    let done = progress.into_inner().unwrap().done;
+    drop(file);
+    // Nothing left to resume: a stale checkpoint must not outlive its batch.
+    if config.path.exists() {
+        std::fs::remove_file(&config.path)
+            .map_err(|e| format!("Cannot remove {}: {}", config.path.display(), e))?;
+    }
+    Ok(done.into_values().collect())
+}
+
+/// Writes a checkpoint holding the header and the rows of `done`, through a
+/// temporary file so that the previous one stays whole until it is replaced, and
+/// returns it open for appending.
+fn create<T: CheckpointRow>(
+    config: &CheckpointConfig,
+    num_samples: usize,
+    inputs_fingerprint: u64,
+    done: &BTreeMap<usize, T>,
+) -> Result<File, String> {
+    let header = json!({
+        "format": CHECKPOINT_FORMAT,
+        "num_samples": num_samples,
+        "fingerprint": inputs_fingerprint.to_string(),
+    });
+    let mut text = header.to_string() + "\n";
+    for (i, row) in done {
+        text += &(json!([i, row.to_json()]).to_string() + "\n");
+    }
+    let tmp = config.path.with_extension("tmp");
+    std::fs::write(&tmp, text)
+        .and_then(|_| std::fs::rename(&tmp, &config.path))
+        .and_then(|_| OpenOptions::new().append(true).open(&config.path))
+        .map_err(|e| format!("Cannot write checkpoint {}: {}", config.path.display(), e))
+}
+
+fn append(config: &CheckpointConfig, file: &mut File, lines: &[String]) -> Result<(), String> {
+    let text: String = lines.iter().map(|l| l.clone() + "\n").collect();
+    file.write_all(text.as_bytes())
+        .and_then(|_| file.flush())
+        .map_err(|e| format!("Cannot write checkpoint {}: {}", config.path.display(), e))
+}
+
+fn load<T: CheckpointRow>(
+    config: &CheckpointConfig,
+    num_samples: usize,
+    inputs_fingerprint: u64,
+) -> Result<BTreeMap<usize, T>, String> {
+    let path = config.path.display();
+    let text = std::fs::read_to_string(&config.path)
+        .map_err(|e| format!("Cannot read checkpoint {}: {}", path, e))?;
+    let mut lines = text.lines();
+    let header: Value = serde_json::from_str(lines.next().unwrap_or_default())
+        .map_err(|e| format!("Checkpoint {} is not valid JSON: {}", path, e))?;
+
+    if header["format"].as_u64() != Some(CHECKPOINT_FORMAT as u64) {
+        return Err(format!(
+            "Checkpoint {} is in another format than version {}",
+            path, CHECKPOINT_FORMAT
+        ));
+    }
+    if header["num_samples"].as_u64() != Some(num_samples as u64)
+        || header["fingerprint"].as_str() != Some(inputs_fingerprint.to_string().as_str())
+    {
+        return Err(format!(
+            "Checkpoint {} was written for a different batch",
+            path
+        ));
+    }
+    let rows: Vec<&str> = lines.collect();
+    // Only the last line can be cut short, by a crash while appending.
+    let complete = if text.ends_with('\n') {
+        rows.len()
+    } else {
+        rows.len().saturating_sub(1)
+    };
+    rows[..complete]
+        .iter()
+        .map(|line| {
+            let row: Value = serde_json::from_str(line).unwrap_or_default();
+            match (row[0].as_u64(), T::from_json(&row[1])) {
+                (Some(i), Some(row)) if (i as usize) < num_samples => Ok((i as usize, row)),
+                _ => Err(format!("Checkpoint {} holds an invalid result", path)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_resume_skips_completed_samples() {
+        let path = std::env::temp_dir().join(format!("checkpoint_{}.json", std::process::id()));
+        let config = CheckpointConfig::new(&path, 2, true);
+        let solve = |i: usize| vec![i as f32, 0.5];
+
+        // A run interrupted after five samples, while appending a sixth.
+        let partial: BTreeMap<usize, Vec<f32>> = [0, 1, 2, 5, 7].map(|i| (i, solve(i))).into();
+        let mut file = create(&config, 10, 42, &partial).unwrap();
+        file.write_all(b"[3,[3.0,").unwrap();
+
+        let calls = AtomicUsize::new(0);
+        let results = run_checkpointed(10, 42, Some(&config), |i| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            solve(i)
+        })
+        .unwrap();
+        assert_eq!(calls.load(Ordering::Relaxed), 5);
+        assert_eq!(results, (0..10).map(solve).collect::<Vec<_>>());
+        assert!(!path.exists());
+
+        // Another format or batch cannot pick it up.
+        std::fs::write(
+            &path,
+            r#"{"num_samples":10,"fingerprint":"42","results":[]}"#,
+        )
+        .unwrap();
+        let err = run_checkpointed(10, 42, Some(&config), solve).unwrap_err();
+        assert!(err.contains("another format"), "{}", err);
+        create(&config, 10, 42, &partial).unwrap();
+        let err = run_checkpointed(10, 43, Some(&config), solve).unwrap_err();
+        assert!(err.contains("different batch"), "{}", err);
+        // Without resume the old checkpoint is ignored and replaced.
+        let fresh = CheckpointConfig::new(&path, 2, false);
+        assert_eq!(
+            run_checkpointed(10, 43, Some(&fresh), solve).unwrap().len(),
+            10
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_rows_are_appended() {
+        let path =
+            std::env::temp_dir().join(format!("checkpoint_rows_{}.json", std::process::id()));
+        let config = CheckpointConfig::new(&path, 3, true);
+        let solve = |i: usize| vec![i as f32];
+
+        // Every third row is written as it is solved, so a failing batch keeps them.
+        let solved = Mutex::new(Vec::new());
+        let count = |i: usize| {
+            let mut s = solved.lock().unwrap();
+            s.push(i);
+            assert!(s.len() <= 7, "stop");
+            solve(i)
+        };
+        let run = std::panic::catch_unwind(|| run_checkpointed(10, 7, Some(&config), count));
+        assert!(run.is_err());
+        let kept: BTreeMap<usize, Vec<f32>> = load(&config, 10, 7).unwrap();
+        assert_eq!(kept.len(), 6);
+        assert!(kept.iter().all(|(&i, row)| *row == solve(i)));
+        let text = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(text.lines().count(), 7);
+
+        let results = run_checkpointed(10, 7, Some(&config), solve).unwrap();
+        assert_eq!(results, (0..10).map(solve).collect::<Vec<_>>());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_fingerprint_is_canonical() {
+        let inputs = json!([[1, 2, 3], "points", null]);
+        let rules = RuleSet::default();
+        // FNV-1a of the encoding: no per-build or per-run hashing state.
+        assert_eq!(fingerprint(&inputs, rules), fingerprint(&inputs, rules));
+        assert_ne!(
+            fingerprint(&inputs, rules),
+            fingerprint(&json!([[1, 2]]), rules)
+        );
+        assert_ne!(
+            fingerprint(&inputs, rules),
+            fingerprint(&inputs, RuleSet::flat_capot())
+        );
+    }
+
+    #[test]
+    fn test_gameplay_rows_keep_invalid_reasons() {
+        let sample = SolvedGameplaySample::invalid(InvalidReason::HandSizes);
+        let row = SolvedGameplaySample::from_json(&sample.to_json()).unwrap();
+        assert_eq!(row.invalid_reason, Some(InvalidReason::HandSizes));
+
+        // Rows of checkpoints written before the reasons.
+        let old = json!([0, 0.0, false, 0, 0, 1.0, 0.0, 0.0, 0]);
+        let row = SolvedGameplaySample::from_json(&old).unwrap();
+        assert_eq!(row.invalid_reason, Some(InvalidReason::Terminal));
+        let old = json!([3, 81.0, true, 10, 5, 1.0, 0.0, 0.0, 8]);
+        assert!(SolvedGameplaySample::from_json(&old).unwrap().valid());
+        assert!(
+            SolvedGameplaySample::from_json(&json!([0, 0.0, false, 0, 0, 1, 0, 0, 0, 9])).is_none()
+        );
+    }
+
+    #[test]
+    fn test_gameplay_rows_keep_unsolved_forced_moves() {
+        let sample = SolvedGameplaySample::unsolved_forced(12);
+        let row = SolvedGameplaySample::from_json(&sample.to_json()).unwrap();
+        assert!(row.forced && row.valid() && row.best_score.is_nan());
+        assert_eq!((row.best_card, row.optimal_cards), (12, 1 << 12));
+        // Rows of checkpoints written before the flag.
+        let old = json!([3, 81.0, true, 10, 5, 1.0, 0.0, 0.0, 8, 0]);
+        assert!(!SolvedGameplaySample::from_json(&old).unwrap().forced);
+    }
+}
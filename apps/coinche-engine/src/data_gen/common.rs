@@ -1,10 +1,40 @@
-use crate::gameplay::playing::{RANK_10, RANK_7, RANK_8, RANK_9, RANK_A, RANK_J, RANK_K, RANK_Q};
+use crate::gameplay::notation::{card_names, seat_name, suit_name};
+use crate::gameplay::playing::{
+    card_points, RANK_10, RANK_7, RANK_8, RANK_9, RANK_A, RANK_J, RANK_K, RANK_Q,
+};
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyByteArray;
 use rand::prelude::*;
+use rand::rngs::StdRng;
+use rayon::prelude::*;
+use std::fmt;
+
+/// RNG for sample `index` of a batch. With a seed, the stream only depends on
+/// (seed, index), so batch results are identical whatever the thread count or
+/// scheduling. Without a seed, a fresh stream is drawn from the thread RNG.
+pub fn sample_rng(seed: Option<u64>, index: u64) -> StdRng {
+    match seed {
+        Some(s) => StdRng::seed_from_u64(s ^ index.wrapping_mul(0x9E37_79B9_7F4A_7C15)),
+        None => StdRng::from_rng(rand::thread_rng()).expect("thread RNG never fails"),
+    }
+}
 
-pub fn generate_random_hands() -> [u32; 4] {
-    let mut rng = rand::thread_rng();
+/// Items of `items` as bytes, each encoded by `to_bytes`.
+pub fn ne_bytes<T: Copy, const N: usize>(items: &[T], to_bytes: fn(T) -> [u8; N]) -> Vec<u8> {
+    items.iter().flat_map(|&x| to_bytes(x)).collect()
+}
+
+/// Writable 1-D numpy array of `dtype` over `bytes`, its items in native byte order.
+pub fn numpy_array<'py>(py: Python<'py>, bytes: &[u8], dtype: &str) -> PyResult<&'py PyAny> {
+    py.import("numpy")?
+        .call_method1("frombuffer", (PyByteArray::new(py, bytes), dtype))
+}
+
+pub fn generate_random_hands<R: Rng>(rng: &mut R) -> [u32; 4] {
     let mut deck: Vec<u8> = (0..32).collect();
-    deck.shuffle(&mut rng);
+    deck.shuffle(rng);
 
     let mut hands = [0u32; 4];
     for i in 0..4 {
@@ -15,134 +45,533 @@ pub fn generate_random_hands() -> [u32; 4] {
     hands
 }
 
+/// Deals the 24 cards not in `south_hand` randomly to West, North and East.
+pub fn generate_hands_with_south<R: Rng>(south_hand: u32, rng: &mut R) -> [u32; 4] {
+    let mut deck: Vec<u8> = (0..32).filter(|&c| (south_hand & (1 << c)) == 0).collect();
+    deck.shuffle(rng);
+
+    let mut hands = [south_hand, 0, 0, 0];
+    for (i, &c) in deck.iter().enumerate() {
+        hands[1 + i / 8] |= 1 << c;
+    }
+    hands
+}
+
 #[derive(Clone, Debug)]
 pub enum GenStrategy {
     Random,
     ForceCapot,          // Strong hand
     ForceBelote,         // K+Q of trump
     ForceShape([u8; 4]), // Specific suit distribution (e.g. [5, 3, 2, 1])
+    /// Trumps held by each seat (S, W, N, E), the defenders West and East
+    /// having balanced side suits.
+    TrumpSplit([u8; 4]),
+}
+
+/// Constraints on one seat's hand, all bounds inclusive.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SeatConstraints {
+    /// Allowed length of each suit (D, S, H, C).
+    pub suit_length: [(u8, u8); 4],
+    /// Allowed card points, counted under the builder's trump.
+    pub points: (u16, u16),
+    pub must_hold: u32,
+    pub must_not_hold: u32,
+    /// Whether the seat holds the king and queen of trumps (None: either way).
+    pub belote: Option<bool>,
+}
+
+impl Default for SeatConstraints {
+    fn default() -> Self {
+        Self {
+            suit_length: [(0, 8); 4],
+            points: (0, 162),
+            must_hold: 0,
+            must_not_hold: 0,
+            belote: None,
+        }
+    }
+}
+
+impl SeatConstraints {
+    fn accepts(&self, hand: u32, trump: u8) -> bool {
+        let lengths_ok = (0..4u8).all(|suit| {
+            let (min, max) = self.suit_length[suit as usize];
+            (min..=max).contains(&suit_length(hand, suit))
+        });
+        let points = (0..32u8)
+            .filter(|&c| hand & (1 << c) != 0)
+            .map(|c| card_points(c, trump))
+            .sum::<u16>();
+        let belote = belote_cards(trump) & !hand == 0;
+        lengths_ok
+            && (self.points.0..=self.points.1).contains(&points)
+            && self.belote.is_none_or(|b| b == belote)
+    }
+}
+
+fn suit_length(hand: u32, suit: u8) -> u8 {
+    (hand & (0xFF << (suit * 8))).count_ones() as u8
+}
+
+/// King and queen of `trump`.
+fn belote_cards(trump: u8) -> u32 {
+    1 << (trump * 8 + RANK_K) | 1 << (trump * 8 + RANK_Q)
+}
+
+/// Why `HandBuilder` could not produce a deal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandBuilderError {
+    InvalidTrump(u8),
+    /// Cards that cannot all be placed: required in two hands, both held and
+    /// excluded, or more than 8 for one hand.
+    TooManyForcedCards(String),
+    /// Suit lengths or points no hand can have, alone or given the others.
+    ShapeImpossible(String),
+    /// No deal met the constraints within the given number of attempts.
+    Unsatisfiable(u32),
+}
+
+impl fmt::Display for HandBuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidTrump(trump) => write!(f, "Invalid trump suit {}", trump),
+            Self::TooManyForcedCards(reason) | Self::ShapeImpossible(reason) => f.write_str(reason),
+            Self::Unsatisfiable(attempts) => write!(
+                f,
+                "No deal met the constraints in {} attempts: they are unsatisfiable or too rare",
+                attempts
+            ),
+        }
+    }
+}
+
+impl std::error::Error for HandBuilderError {}
+
+create_exception!(coinche_engine, TooManyForcedCardsError, PyValueError);
+create_exception!(coinche_engine, ShapeImpossibleError, PyValueError);
+create_exception!(coinche_engine, UnsatisfiableError, PyValueError);
+
+/// Each error raises its own `ValueError` subclass in Python.
+impl From<HandBuilderError> for PyErr {
+    fn from(err: HandBuilderError) -> PyErr {
+        let msg = err.to_string();
+        match err {
+            HandBuilderError::InvalidTrump(_) => PyValueError::new_err(msg),
+            HandBuilderError::TooManyForcedCards(_) => TooManyForcedCardsError::new_err(msg),
+            HandBuilderError::ShapeImpossible(_) => ShapeImpossibleError::new_err(msg),
+            HandBuilderError::Unsatisfiable(_) => UnsatisfiableError::new_err(msg),
+        }
+    }
 }
 
+/// Deals under per-seat constraints: suit lengths, point ranges, cards each seat
+/// must or must not hold, belote. Held cards and minimum lengths are placed first
+/// and the rest is dealt at random within the length caps; deals breaking a
+/// constraint are then rejected and redrawn, up to `max_attempts` times. Deals
+/// are not exactly uniform over all those satisfying the constraints.
+///
+/// From Python, each setter returns the builder so constraints chain:
+/// `HandBuilder(2).suit_length(0, 2, 5, 6).points(2, 0, 30).build()`.
+#[pyclass]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct HandBuilder {
     trump: u8,
-    forced_cards: Vec<u8>,  // Cards forced into South's hand
-    shape: Option<[u8; 4]>, // Desired shape for South (Trump, Suit 1, Suit 2, Suit 3) - relative to trump
+    seats: [SeatConstraints; 4],
+    max_attempts: u32,
 }
 
+/// Default number of deals drawn before constraints are declared unsatisfiable.
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 10_000;
+
 impl HandBuilder {
     pub fn new(trump: u8) -> Self {
         Self {
             trump,
-            forced_cards: Vec::new(),
-            shape: None,
+            seats: Default::default(),
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
         }
     }
 
+    /// Forces `card` into South's hand.
     pub fn force_card(&mut self, card: u8) -> &mut Self {
-        if !self.forced_cards.contains(&card) {
-            self.forced_cards.push(card);
-        }
-        self
+        self.hold(0, 1 << card)
     }
 
+    /// South holds at least `shape[i]` cards of the suit `i` steps after the trump.
     pub fn force_shape(&mut self, shape: [u8; 4]) -> &mut Self {
-        self.shape = Some(shape);
+        for (i, &count) in shape.iter().enumerate() {
+            let suit = (self.trump + i as u8) % 4;
+            let max = self.seats[0].suit_length[suit as usize].1;
+            self.suit_length(0, suit, count, max);
+        }
         self
     }
 
-    pub fn build(&self) -> [u32; 4] {
-        let mut rng = rand::thread_rng();
-        let mut hands = [0u32; 4];
-        let mut deck: Vec<u8> = (0..32).collect();
+    /// `seat` holds exactly `shape[i]` cards of the suit `i` steps after the trump.
+    pub fn shape(&mut self, seat: u8, shape: [u8; 4]) -> &mut Self {
+        for (i, &count) in shape.iter().enumerate() {
+            self.suit_length(seat, (self.trump + i as u8) % 4, count, count);
+        }
+        self
+    }
 
-        // Remove forced cards from deck
-        deck.retain(|c| !self.forced_cards.contains(c));
+    /// Exactly `split[seat]` trumps in each hand.
+    pub fn trump_split(&mut self, split: [u8; 4]) -> &mut Self {
+        for (seat, &count) in split.iter().enumerate() {
+            self.suit_length(seat as u8, self.trump, count, count);
+        }
+        self
+    }
 
-        // 1. Assign forced cards to South
-        for &c in &self.forced_cards {
-            hands[0] |= 1 << c;
+    /// 1 to 3 cards in each side suit of `seat` (within the lengths already set);
+    /// the trump length is left alone.
+    pub fn balanced(&mut self, seat: u8) -> &mut Self {
+        let trump = self.trump;
+        for suit in (0..4).filter(|&s| s != trump) {
+            let (min, max) = self.seats[seat as usize].suit_length[suit as usize];
+            self.suit_length(seat, suit, min.max(1), max.min(3));
         }
+        self
+    }
 
-        // 2. Fulfill Shape for South
-        if let Some(shape) = self.shape {
-            // shape is [count_trump, count_s1, count_s2, count_s3]
-            // We need to map s1, s2, s3 to actual suits.
-            // Let's say s1 = (trump + 1) % 4, s2 = (trump + 2) % 4, etc.
-            let suits = [
-                self.trump,
-                (self.trump + 1) % 4,
-                (self.trump + 2) % 4,
-                (self.trump + 3) % 4,
-            ];
+    pub fn suit_length(&mut self, seat: u8, suit: u8, min: u8, max: u8) -> &mut Self {
+        self.seats[seat as usize].suit_length[suit as usize] = (min, max);
+        self
+    }
 
-            for (i, &count) in shape.iter().enumerate() {
-                let suit = suits[i];
-                let current_count = self.count_suit(hands[0], suit);
-
-                if current_count < count {
-                    let needed = count - current_count;
-                    // Find available cards of this suit in deck
-                    let mut available: Vec<u8> =
-                        deck.iter().cloned().filter(|&c| c / 8 == suit).collect();
-
-                    available.shuffle(&mut rng);
-
-                    for _ in 0..needed {
-                        if let Some(c) = available.pop() {
-                            hands[0] |= 1 << c;
-                            // Remove from deck
-                            if let Some(pos) = deck.iter().position(|&x| x == c) {
-                                deck.remove(pos);
-                            }
-                        }
-                    }
-                }
+    pub fn points(&mut self, seat: u8, min: u16, max: u16) -> &mut Self {
+        self.seats[seat as usize].points = (min, max);
+        self
+    }
+
+    /// `seat` holds every card of the mask `cards`.
+    pub fn hold(&mut self, seat: u8, cards: u32) -> &mut Self {
+        self.seats[seat as usize].must_hold |= cards;
+        self
+    }
+
+    /// `seat` holds none of the cards of the mask `cards`.
+    pub fn exclude(&mut self, seat: u8, cards: u32) -> &mut Self {
+        self.seats[seat as usize].must_not_hold |= cards;
+        self
+    }
+
+    pub fn belote(&mut self, seat: u8, present: bool) -> &mut Self {
+        self.seats[seat as usize].belote = Some(present);
+        self
+    }
+
+    pub fn max_attempts(&mut self, attempts: u32) -> &mut Self {
+        self.max_attempts = attempts;
+        self
+    }
+
+    pub fn build(&self) -> Result<[u32; 4], HandBuilderError> {
+        self.build_with(&mut rand::thread_rng())
+    }
+
+    /// A deal meeting every constraint, or why none could be found.
+    pub fn build_with<R: Rng>(&self, rng: &mut R) -> Result<[u32; 4], HandBuilderError> {
+        let holds = self.validate()?;
+        for _ in 0..self.max_attempts {
+            if let Some(hands) = self.draw(holds, rng) {
+                return Ok(hands);
             }
         }
+        Err(HandBuilderError::Unsatisfiable(self.max_attempts))
+    }
 
-        // 3. Fill remaining slots for South (up to 8)
-        let south_count = self.count_cards(hands[0]);
-        if south_count < 8 {
-            let needed = 8 - south_count;
+    /// Cards each seat must hold, belote included, after checking that the
+    /// constraints do not contradict each other outright.
+    fn validate(&self) -> Result<[u32; 4], HandBuilderError> {
+        if self.trump >= 4 {
+            return Err(HandBuilderError::InvalidTrump(self.trump));
+        }
+        let mut holds = [0u32; 4];
+        for (seat, c) in self.seats.iter().enumerate() {
+            holds[seat] = c.must_hold;
+            if c.belote == Some(true) {
+                holds[seat] |= belote_cards(self.trump);
+            }
+        }
 
-            deck.shuffle(&mut rng);
-            for _ in 0..needed {
-                let c = deck.pop().unwrap();
-                hands[0] |= 1 << c;
+        use HandBuilderError::{ShapeImpossible, TooManyForcedCards};
+        let mut taken = 0u32;
+        for (seat, c) in self.seats.iter().enumerate() {
+            let name = seat_name(seat as u8);
+            if holds[seat] & taken != 0 {
+                return Err(TooManyForcedCards(format!(
+                    "Cards {} are required in two hands",
+                    card_names(holds[seat] & taken).join(" ")
+                )));
+            }
+            taken |= holds[seat];
+            if holds[seat] & c.must_not_hold != 0 {
+                return Err(TooManyForcedCards(format!(
+                    "{} must both hold and not hold {}",
+                    name,
+                    card_names(holds[seat] & c.must_not_hold).join(" ")
+                )));
+            }
+            if holds[seat].count_ones() > 8 {
+                return Err(TooManyForcedCards(format!(
+                    "{} must hold {} cards",
+                    name,
+                    holds[seat].count_ones()
+                )));
+            }
+            if c.points.0 > c.points.1 {
+                return Err(ShapeImpossible(format!(
+                    "{} has an empty point range",
+                    name
+                )));
+            }
+            for suit in 0..4u8 {
+                let (min, max) = c.suit_length[suit as usize];
+                let held = suit_length(holds[seat], suit);
+                if min > max || held > max {
+                    return Err(ShapeImpossible(format!(
+                        "{} cannot hold {} to {} cards in {} ({} required)",
+                        name,
+                        min,
+                        max,
+                        suit_name(suit),
+                        held
+                    )));
+                }
+            }
+            let mins: u8 = c.suit_length.iter().map(|l| l.0).sum();
+            let maxs: u8 = c.suit_length.iter().map(|l| l.1.min(8)).sum();
+            if mins > 8 || maxs < 8 {
+                return Err(ShapeImpossible(format!(
+                    "{}'s suit lengths allow {} to {} cards, not 8",
+                    name, mins, maxs
+                )));
+            }
+        }
+        for suit in 0..4usize {
+            let mins: u8 = self.seats.iter().map(|c| c.suit_length[suit].0).sum();
+            let maxs: u8 = self
+                .seats
+                .iter()
+                .map(|c| c.suit_length[suit].1.min(8))
+                .sum();
+            if mins > 8 || maxs < 8 {
+                return Err(ShapeImpossible(format!(
+                    "The seats allow {} to {} cards in {}, not 8",
+                    mins,
+                    maxs,
+                    suit_name(suit as u8)
+                )));
             }
         }
+        Ok(holds)
+    }
 
-        // 4. Deal remaining cards to other players
-        deck.shuffle(&mut rng);
-        for i in 1..4 {
-            for _ in 0..8 {
-                if let Some(c) = deck.pop() {
-                    hands[i] |= 1 << c;
+    /// One attempt: held cards, then minimum lengths, then random cards within
+    /// the length caps. None if the deal breaks a constraint.
+    fn draw<R: Rng>(&self, holds: [u32; 4], rng: &mut R) -> Option<[u32; 4]> {
+        let mut hands = holds;
+        let mut deck = !holds.iter().fold(0, |m, h| m | h);
+        let deal = |hand: &mut u32, deck: &mut u32, allowed: u32, rng: &mut R| {
+            let candidates = *deck & allowed;
+            if candidates == 0 {
+                return false;
+            }
+            let card = nth_card(candidates, rng.gen_range(0..candidates.count_ones()));
+            *hand |= 1 << card;
+            *deck &= !(1 << card);
+            true
+        };
+
+        for (seat, c) in self.seats.iter().enumerate() {
+            for suit in 0..4u8 {
+                let suit_cards = (0xFFu32 << (suit * 8)) & !c.must_not_hold;
+                while suit_length(hands[seat], suit) < c.suit_length[suit as usize].0 {
+                    if !deal(&mut hands[seat], &mut deck, suit_cards, rng) {
+                        return None;
+                    }
+                }
+            }
+        }
+        for (seat, c) in self.seats.iter().enumerate() {
+            while hands[seat].count_ones() < 8 {
+                let open_suits = (0..4u8)
+                    .filter(|&s| suit_length(hands[seat], s) < c.suit_length[s as usize].1)
+                    .fold(0u32, |m, s| m | 0xFF << (s * 8));
+                if !deal(
+                    &mut hands[seat],
+                    &mut deck,
+                    open_suits & !c.must_not_hold,
+                    rng,
+                ) {
+                    return None;
                 }
             }
         }
 
-        hands
+        let trump = self.trump;
+        (0..4)
+            .all(|seat| self.seats[seat].accepts(hands[seat], trump))
+            .then_some(hands)
     }
+}
 
-    fn count_suit(&self, hand: u32, suit: u8) -> u8 {
-        let mut count = 0;
-        for r in 0..8 {
-            if (hand & (1 << (suit * 8 + r))) != 0 {
-                count += 1;
-            }
+/// Card of rank `n` (from 0) among the set bits of `cards`.
+fn nth_card(mut cards: u32, n: u32) -> u8 {
+    for _ in 0..n {
+        cards &= cards - 1;
+    }
+    cards.trailing_zeros() as u8
+}
+
+#[pymethods]
+impl HandBuilder {
+    #[new]
+    fn py_new(trump: u8) -> PyResult<Self> {
+        if trump >= 4 {
+            return Err(PyValueError::new_err(format!(
+                "Invalid trump suit {}",
+                trump
+            )));
         }
-        count
+        Ok(Self::new(trump))
+    }
+
+    /// Length of `suit` (0-3) in `seat`'s hand between `min` and `max`.
+    #[pyo3(name = "suit_length")]
+    fn py_suit_length(
+        mut slf: PyRefMut<'_, Self>,
+        seat: u8,
+        suit: u8,
+        min: u8,
+        max: u8,
+    ) -> PyResult<PyRefMut<'_, Self>> {
+        check_seat(seat)?;
+        if suit >= 4 {
+            return Err(PyValueError::new_err(format!("Invalid suit {}", suit)));
+        }
+        slf.suit_length(seat, suit, min, max);
+        Ok(slf)
+    }
+
+    /// Exact lengths of `seat`'s suits, trumps first then the following suits.
+    #[pyo3(name = "shape")]
+    fn py_shape(
+        mut slf: PyRefMut<'_, Self>,
+        seat: u8,
+        shape: [u8; 4],
+    ) -> PyResult<PyRefMut<'_, Self>> {
+        check_seat(seat)?;
+        slf.shape(seat, shape);
+        Ok(slf)
+    }
+
+    /// Exact number of trumps of each seat (S, W, N, E).
+    #[pyo3(name = "trump_split")]
+    fn py_trump_split(mut slf: PyRefMut<'_, Self>, split: [u8; 4]) -> PyRefMut<'_, Self> {
+        slf.trump_split(split);
+        slf
+    }
+
+    /// 1 to 3 cards in each side suit of `seat`.
+    #[pyo3(name = "balanced")]
+    fn py_balanced(mut slf: PyRefMut<'_, Self>, seat: u8) -> PyResult<PyRefMut<'_, Self>> {
+        check_seat(seat)?;
+        slf.balanced(seat);
+        Ok(slf)
     }
 
-    fn count_cards(&self, hand: u32) -> u8 {
-        hand.count_ones() as u8
+    /// Card points of `seat`'s hand between `min` and `max`.
+    #[pyo3(name = "points")]
+    fn py_points(
+        mut slf: PyRefMut<'_, Self>,
+        seat: u8,
+        min: u16,
+        max: u16,
+    ) -> PyResult<PyRefMut<'_, Self>> {
+        check_seat(seat)?;
+        slf.points(seat, min, max);
+        Ok(slf)
     }
+
+    /// `seat` holds all the cards of the mask `cards`.
+    #[pyo3(name = "hold")]
+    fn py_hold(mut slf: PyRefMut<'_, Self>, seat: u8, cards: u32) -> PyResult<PyRefMut<'_, Self>> {
+        check_seat(seat)?;
+        slf.hold(seat, cards);
+        Ok(slf)
+    }
+
+    /// `seat` holds none of the cards of the mask `cards`.
+    #[pyo3(name = "exclude")]
+    fn py_exclude(
+        mut slf: PyRefMut<'_, Self>,
+        seat: u8,
+        cards: u32,
+    ) -> PyResult<PyRefMut<'_, Self>> {
+        check_seat(seat)?;
+        slf.exclude(seat, cards);
+        Ok(slf)
+    }
+
+    /// `seat` holds (or not) the king and queen of trumps.
+    #[pyo3(name = "belote", signature = (seat, present=true))]
+    fn py_belote(
+        mut slf: PyRefMut<'_, Self>,
+        seat: u8,
+        present: bool,
+    ) -> PyResult<PyRefMut<'_, Self>> {
+        check_seat(seat)?;
+        slf.belote(seat, present);
+        Ok(slf)
+    }
+
+    #[pyo3(name = "max_attempts")]
+    fn py_max_attempts(mut slf: PyRefMut<'_, Self>, attempts: u32) -> PyRefMut<'_, Self> {
+        slf.max_attempts(attempts);
+        slf
+    }
+
+    /// One deal [S, W, N, E]; with `seed`, always the same one.
+    #[pyo3(name = "build", signature = (seed=None))]
+    fn py_build(&self, seed: Option<u64>) -> PyResult<[u32; 4]> {
+        Ok(self.build_with(&mut sample_rng(seed, 0))?)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("HandBuilder(trump={}, seats={:?})", self.trump, self.seats)
+    }
+}
+
+fn check_seat(seat: u8) -> PyResult<()> {
+    if seat >= 4 {
+        return Err(PyValueError::new_err(format!("Invalid seat {}", seat)));
+    }
+    Ok(())
+}
+
+/// `batch_size` deals from `builder`, flattened 4 hands per deal. With `seed`,
+/// the batch is reproducible (see `sample_rng`).
+pub fn generate_constrained_batch(
+    builder: &HandBuilder,
+    batch_size: usize,
+    seed: Option<u64>,
+) -> Result<Vec<u32>, HandBuilderError> {
+    let deals: Vec<[u32; 4]> = (0..batch_size)
+        .into_par_iter()
+        .map(|i| builder.build_with(&mut sample_rng(seed, i as u64)))
+        .collect::<Result<_, _>>()?;
+    Ok(deals.into_iter().flatten().collect())
 }
 
-pub fn generate_biased_hands(trump: u8, strategy: GenStrategy) -> [u32; 4] {
+pub fn generate_biased_hands<R: Rng>(
+    trump: u8,
+    strategy: GenStrategy,
+    rng: &mut R,
+) -> Result<[u32; 4], HandBuilderError> {
     let mut builder = HandBuilder::new(trump);
-    let mut rng = rand::thread_rng();
 
     match strategy {
         GenStrategy::Random => {
@@ -185,7 +614,7 @@ pub fn generate_biased_hands(trump: u8, strategy: GenStrategy) -> [u32; 4] {
                 let mut counts = [0u8; 4]; // Only indices 1,2,3 will be used
 
                 for _ in 0..remaining {
-                    let idx = *side_indices.choose(&mut rng).unwrap();
+                    let idx = *side_indices.choose(rng).unwrap();
                     counts[idx] += 1;
                 }
 
@@ -207,7 +636,155 @@ pub fn generate_biased_hands(trump: u8, strategy: GenStrategy) -> [u32; 4] {
         GenStrategy::ForceShape(shape) => {
             builder.force_shape(shape);
         }
+        GenStrategy::TrumpSplit(split) => {
+            builder.trump_split(split).balanced(1).balanced(3);
+        }
     }
 
-    builder.build()
+    builder.build_with(rng)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::notation::parse_card;
+    use crate::gameplay::playing::{CLUBS, HEARTS, SPADES};
+
+    fn mask(cards: &[&str]) -> u32 {
+        cards.iter().fold(0, |m, c| m | 1 << parse_card(c).unwrap())
+    }
+
+    #[test]
+    fn test_hand_builder_constraints() {
+        let mut builder = HandBuilder::new(HEARTS);
+        builder
+            .suit_length(0, HEARTS, 5, 6)
+            .suit_length(2, HEARTS, 0, 1)
+            .points(1, 0, 25)
+            .hold(3, mask(&["AS", "10S"]))
+            .exclude(2, mask(&["AC"]))
+            .belote(0, true);
+        let mut rng = sample_rng(Some(1), 0);
+        for _ in 0..50 {
+            let hands = builder.build_with(&mut rng).unwrap();
+            assert_eq!(hands.iter().fold(0, |m, h| m | h), u32::MAX);
+            assert!(hands.iter().all(|h| h.count_ones() == 8));
+            assert!((5..=6).contains(&suit_length(hands[0], HEARTS)));
+            assert!(suit_length(hands[2], HEARTS) <= 1);
+            assert_eq!(hands[0] & belote_cards(HEARTS), belote_cards(HEARTS));
+            assert_eq!(hands[3] & mask(&["AS", "10S"]), mask(&["AS", "10S"]));
+            assert_eq!(hands[2] & mask(&["AC"]), 0);
+            let points: u16 = (0..32)
+                .filter(|&c| hands[1] & (1 << c) != 0)
+                .map(|c| card_points(c, HEARTS))
+                .sum();
+            assert!(points <= 25);
+        }
+
+        // Contradictions are reported before sampling, rare constraints after.
+        let contradictions = [
+            HandBuilder::new(HEARTS)
+                .hold(0, mask(&["JH"]))
+                .hold(2, mask(&["JH"]))
+                .clone(),
+            HandBuilder::new(HEARTS)
+                .belote(1, true)
+                .exclude(1, mask(&["KH"]))
+                .clone(),
+            HandBuilder::new(SPADES)
+                .suit_length(0, CLUBS, 5, 8)
+                .suit_length(1, CLUBS, 4, 8)
+                .clone(),
+            HandBuilder::new(SPADES)
+                .suit_length(0, CLUBS, 0, 0)
+                .hold(0, mask(&["7C"]))
+                .clone(),
+            HandBuilder::new(SPADES).points(2, 50, 40).clone(),
+        ];
+        for builder in contradictions {
+            let err = builder.build_with(&mut rng).unwrap_err();
+            assert!(
+                !matches!(err, HandBuilderError::Unsatisfiable(_)),
+                "{}",
+                err
+            );
+        }
+        // Shapes of all four seats.
+        let mut builder = HandBuilder::new(SPADES);
+        builder
+            .trump_split([5, 1, 2, 0])
+            .balanced(1)
+            .balanced(3)
+            .shape(2, [2, 3, 3, 0]);
+        for _ in 0..50 {
+            let hands = builder.build_with(&mut rng).unwrap();
+            let lengths = hands.map(|h| (0..4).map(|s| suit_length(h, s)).collect::<Vec<_>>());
+            assert_eq!(lengths.clone().map(|l| l[SPADES as usize]), [5, 1, 2, 0]);
+            assert_eq!(lengths[2], vec![0, 2, 3, 3]);
+            for seat in [1, 3] {
+                assert!(lengths[seat]
+                    .iter()
+                    .enumerate()
+                    .all(|(s, &l)| s == SPADES as usize || (1..=3).contains(&l)));
+            }
+        }
+
+        let err = HandBuilder::new(SPADES)
+            .points(0, 150, 162)
+            .max_attempts(100)
+            .build_with(&mut rng)
+            .unwrap_err();
+        assert_eq!(err, HandBuilderError::Unsatisfiable(100));
+        assert!(err.to_string().contains("100 attempts"), "{}", err);
+    }
+
+    #[test]
+    fn test_hand_builder_errors() {
+        let mut rng = sample_rng(Some(2), 0);
+        let forced = |builder: &HandBuilder| match builder.build_with(&mut sample_rng(None, 0)) {
+            Err(HandBuilderError::TooManyForcedCards(_)) => {}
+            other => panic!("expected TooManyForcedCards, got {:?}", other),
+        };
+        let shape = |builder: &HandBuilder| match builder.build_with(&mut sample_rng(None, 0)) {
+            Err(HandBuilderError::ShapeImpossible(_)) => {}
+            other => panic!("expected ShapeImpossible, got {:?}", other),
+        };
+
+        // Nine forced cards, or eight plus the belote pair.
+        let mut nine = HandBuilder::new(HEARTS);
+        for card in 0..9 {
+            nine.force_card(card);
+        }
+        forced(&nine);
+        forced(
+            HandBuilder::new(HEARTS)
+                .hold(0, mask(&["7D", "8D", "9D", "10D", "JD", "QD", "KD", "AD"]))
+                .belote(0, true),
+        );
+        forced(HandBuilder::new(HEARTS).belote(0, true).belote(2, true));
+
+        // Shapes not adding up to 8, alone, with forced cards or across seats.
+        shape(HandBuilder::new(SPADES).force_shape([5, 4, 0, 0]));
+        shape(HandBuilder::new(SPADES).shape(1, [2, 2, 2, 1]));
+        shape(
+            HandBuilder::new(SPADES)
+                .shape(0, [1, 3, 2, 2])
+                .hold(0, mask(&["JS", "9S"])),
+        );
+        shape(HandBuilder::new(SPADES).trump_split([3, 3, 3, 0]));
+        shape(HandBuilder::new(SPADES).trump_split([8, 1, 0, 0]));
+        shape(HandBuilder::new(SPADES).suit_length(3, HEARTS, 4, 2));
+        assert_eq!(
+            HandBuilder::new(7).build_with(&mut rng),
+            Err(HandBuilderError::InvalidTrump(7))
+        );
+
+        // Tight but satisfiable constraints still deal.
+        let mut full = HandBuilder::new(CLUBS);
+        full.shape(0, [8, 0, 0, 0])
+            .shape(1, [0, 8, 0, 0])
+            .shape(2, [0, 0, 8, 0]);
+        let hands = full.build_with(&mut rng).unwrap();
+        assert_eq!(suit_length(hands[3], (CLUBS + 3) % 4), 8);
+    }
 }
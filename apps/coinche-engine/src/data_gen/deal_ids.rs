@@ -0,0 +1,111 @@
+//! Deal IDs of existing Parquet datasets, to join datasets generated separately
+//! from the same deals (e.g. a bidding and a gameplay dataset sharing their seeds).
+//!
+//! Generators write the ID in a `deal_id` column of 16 big-endian bytes, so that
+//! byte order is numeric order. Files written before that column existed get their
+//! IDs back from a column holding the 4 hands of each row; when the file also has
+//! the ordered `plays` of gameplay positions, the cards already played are given
+//! back to their seats first, so that the remaining hands of a position give the
+//! ID of the deal it comes from.
+
+use arrow::datatypes::{UInt16Type, UInt32Type};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use std::fs::File;
+use std::path::Path;
+
+use super::verify::{list_column, parquet_files};
+use crate::gameplay::deal::deal_id;
+use crate::gameplay::history::decode_history;
+
+/// ID of every row of the dataset at `path` (a Parquet file or a directory of
+/// Parquet files, read in sorted order), from the hands of `hands_column`.
+pub fn recompute_deal_ids(path: &Path, hands_column: &str) -> Result<Vec<u128>, String> {
+    let files = parquet_files(path)?;
+    if files.is_empty() {
+        return Err(format!("No Parquet file found at {}", path.display()));
+    }
+
+    let mut ids = Vec::new();
+    for file in &files {
+        let at = |e: String| format!("{}: {}", file.display(), e);
+        let reader = File::open(file)
+            .map_err(|e| e.to_string())
+            .and_then(|f| ParquetRecordBatchReaderBuilder::try_new(f).map_err(|e| e.to_string()))
+            .and_then(|b| b.build().map_err(|e| e.to_string()))
+            .map_err(at)?;
+        let mut row = 0;
+        for batch in reader {
+            let batch = batch.map_err(|e| at(e.to_string()))?;
+            let hands = list_column::<UInt32Type>(&batch, hands_column)
+                .map_err(at)?
+                .ok_or_else(|| at(format!("Missing column {}", hands_column)))?;
+            let plays = list_column::<UInt16Type>(&batch, "plays").map_err(at)?;
+            for (i, hands) in hands.iter().enumerate() {
+                let mut dealt: [u32; 4] = hands
+                    .as_slice()
+                    .try_into()
+                    .map_err(|_| at(format!("row {}: {} hands, expected 4", row, hands.len())))?;
+                for p in plays.iter().flat_map(|plays| decode_history(&plays[i])) {
+                    dealt[p.seat as usize % 4] |= 1 << p.card;
+                }
+                ids.push(deal_id(&dealt).map_err(|e| at(format!("row {}: {}", row, e)))?);
+                row += 1;
+            }
+        }
+    }
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_gen::gameplay::generate_gameplay_batch;
+    use arrow::array::{ArrayRef, ListBuilder, UInt16Builder, UInt32Builder};
+    use arrow::record_batch::RecordBatch;
+    use parquet::arrow::ArrowWriter;
+
+    #[test]
+    fn test_recompute_deal_ids() {
+        let dir = std::env::temp_dir().join(format!("deal_ids_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let samples = generate_gameplay_batch(6, Some(3)).samples;
+        let mut hands = ListBuilder::new(UInt32Builder::new());
+        let mut plays = ListBuilder::new(UInt16Builder::new());
+        for s in &samples {
+            hands.values().append_slice(&s.hands);
+            hands.append(true);
+            plays.values().append_slice(&s.plays);
+            plays.append(true);
+        }
+        let hands: ArrayRef = std::sync::Arc::new(hands.finish());
+        let plays: ArrayRef = std::sync::Arc::new(plays.finish());
+        let write = |name: &str, columns: Vec<(&str, ArrayRef)>| {
+            let batch = RecordBatch::try_from_iter(columns).unwrap();
+            let path = dir.join(name);
+            let mut writer =
+                ArrowWriter::try_new(File::create(&path).unwrap(), batch.schema(), None).unwrap();
+            writer.write(&batch).unwrap();
+            writer.close().unwrap();
+            path
+        };
+
+        let positions = write(
+            "positions.parquet",
+            vec![("hands", hands.clone()), ("plays", plays)],
+        );
+        let ids = recompute_deal_ids(&positions, "hands").unwrap();
+        let expected: Vec<u128> = samples.iter().map(|s| s.deal_id().unwrap()).collect();
+        assert_eq!(ids, expected);
+
+        // Without the plays, positions past the first card are not whole deals.
+        let remaining = write("remaining.parquet", vec![("hands", hands)]);
+        let err = recompute_deal_ids(&remaining, "hands").unwrap_err();
+        assert!(err.contains("row "), "{}", err);
+        assert!(recompute_deal_ids(&positions, "deal")
+            .unwrap_err()
+            .contains("Missing column deal"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,296 @@
+//! Difficulty label of a deal, for training curricula and hard-case mining.
+//!
+//! Three signals, each mapped to [0, 1] where 1 is hard, averaged into the label:
+//! - search cost: solver nodes to solve every opening lead, on a log scale;
+//! - lead sensitivity: spread of the double-dummy value over the opening leads,
+//!   so deals where the lead decides the outcome rank harder;
+//! - trump balance: one minus the skew of the trump split between the seats; a
+//!   lopsided split plays itself.
+//!
+//! Node counts depend on the transposition table size and partition cache, so
+//! labels are only comparable between runs with the same solver settings.
+//!
+//! The same label scores gameplay positions, so that batches dominated by trivial
+//! endgames can be downsampled: positions under a difficulty threshold are kept
+//! with a probability, and weighted by its inverse so that weighted statistics
+//! (and the training loss) stay unbiased; every harder position is kept.
+
+use super::bidding::validate_deals;
+use super::common::sample_rng;
+use super::gameplay::reconstruct_state;
+use crate::gameplay::playing::PlayingState;
+use crate::solver::{nodes_searched, solve_root_moves, Score};
+use rand::Rng;
+use rayon::prelude::*;
+
+/// Nodes at which the search cost term saturates.
+const NODES_SCALE: f64 = 1e7;
+/// Value spread over the opening leads at which the sensitivity term saturates.
+const SPREAD_SCALE: f32 = 162.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DifficultyComponents {
+    /// Solver nodes searched over all opening leads.
+    pub nodes: u64,
+    /// Best minus worst opening lead (card of the player to move, for a
+    /// position), in points of their team.
+    pub lead_spread: Score,
+    /// Most minus fewest trumps held by a seat, over the cards per hand (0 in
+    /// no trump, where no card is a trump, and in all trump, where all are).
+    pub trump_skew: f32,
+}
+
+impl DifficultyComponents {
+    /// Scalar label in [0, 1]: the mean of the three normalized terms.
+    pub fn score(&self) -> f32 {
+        let cost = ((1.0 + self.nodes as f64).ln() / (1.0 + NODES_SCALE).ln()).min(1.0) as f32;
+        let sensitivity = (self.lead_spread as f32 / SPREAD_SCALE).min(1.0);
+        (cost + sensitivity + (1.0 - self.trump_skew)) / 3.0
+    }
+}
+
+/// Difficulty components of `state`: each card of the player to move is solved to
+/// the end (the opening leads, for a full deal; the spread is over their cards
+/// in the middle of a trick).
+pub fn difficulty_components(state: &PlayingState, tt_log2: Option<u8>) -> DifficultyComponents {
+    let team = (state.current_player % 2) as usize;
+    let before = nodes_searched();
+    let values: Vec<Score> = solve_root_moves(state, team, Some(32), tt_log2)
+        .into_iter()
+        .map(|(_, v)| v)
+        .collect();
+    let nodes = nodes_searched() - before;
+    let lead_spread = values.iter().max().unwrap_or(&0) - values.iter().min().unwrap_or(&0);
+
+    let trump_skew = if state.trump < 4 {
+        let suit = 0xFFu32 << (state.trump * 8);
+        let counts = state.hands.map(|h| (h & suit).count_ones());
+        let per_hand = state
+            .hands
+            .iter()
+            .map(|h| h.count_ones())
+            .max()
+            .unwrap_or(0);
+        let spread = counts.iter().max().unwrap() - counts.iter().min().unwrap();
+        if per_hand > 0 {
+            spread as f32 / per_hand as f32
+        } else {
+            0.0
+        }
+    } else {
+        0.0
+    };
+
+    DifficultyComponents {
+        nodes,
+        lead_spread,
+        trump_skew,
+    }
+}
+
+/// Difficulty of the full deal `hands` played in `trump`, South leading (the
+/// convention of the bidding datasets).
+pub fn deal_difficulty(hands: &[u32; 4], trump: u8, tt_log2: Option<u8>) -> f32 {
+    let mut state = PlayingState::new(trump);
+    state.hands = *hands;
+    difficulty_components(&state, tt_log2).score()
+}
+
+/// `deal_difficulty` over a batch: 4 flattened hands and one trump per deal.
+pub fn difficulty_batch(
+    flattened_hands: &[u32],
+    trumps: &[u8],
+    tt_log2: Option<u8>,
+) -> Result<Vec<f32>, String> {
+    validate_deals(flattened_hands, trumps)?;
+    Ok(flattened_hands
+        .par_chunks(4)
+        .zip(trumps.par_iter())
+        .map(|(chunk, &trump)| deal_difficulty(chunk.try_into().unwrap(), trump, tt_log2))
+        .collect())
+}
+
+/// Outcome of `downsample_easy_positions`, one entry per position.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Downsampling {
+    pub keep: Vec<bool>,
+    /// Training weight of the kept positions: 1 for hard ones, the inverse of the
+    /// keep fraction for easy ones; 0 for dropped positions.
+    pub weights: Vec<f32>,
+    pub difficulty: Vec<f32>,
+}
+
+/// Downsamples the easy positions of a gameplay batch (4 flattened hands per
+/// position, as the generators hold them): positions with a difficulty under
+/// `threshold` or a single legal card are kept with probability `keep_fraction`,
+/// the others always. With a seed, the draw of position `i` only depends on
+/// (seed, i).
+#[allow(clippy::too_many_arguments)]
+pub fn downsample_easy_positions(
+    flattened_hands: &[u32],
+    boards: &[Vec<u8>],
+    trumps: &[u8],
+    tricks_won: &[Vec<u8>],
+    players: &[u8],
+    threshold: f32,
+    keep_fraction: f32,
+    seed: Option<u64>,
+    tt_log2: Option<u8>,
+) -> Result<Downsampling, String> {
+    let n = boards.len();
+    let lengths = [
+        ("hands", flattened_hands.len(), n * 4),
+        ("trumps", trumps.len(), n),
+        ("tricks_won", tricks_won.len(), n),
+        ("players", players.len(), n),
+    ];
+    if let Some((name, len, expected)) = lengths.iter().find(|(_, len, e)| len != e) {
+        return Err(format!(
+            "{} has {} entries for {} boards, expected {}",
+            name, len, n, expected
+        ));
+    }
+    if !(keep_fraction > 0.0 && keep_fraction <= 1.0) {
+        return Err(format!("Keep fraction {} is not in (0, 1]", keep_fraction));
+    }
+    if let Some(i) = tricks_won.iter().position(|t| t.len() != 2) {
+        return Err(format!("tricks_won of sample {} is not a pair", i));
+    }
+    if let Some(i) = trumps.iter().position(|&t| t >= 6) {
+        return Err(format!("Invalid trump {} in sample {}", trumps[i], i));
+    }
+    if let Some(i) = players.iter().position(|&p| p >= 4) {
+        return Err(format!("Invalid player {} in sample {}", players[i], i));
+    }
+
+    let rows: Vec<(bool, f32, f32)> = (0..n)
+        .into_par_iter()
+        .map(|i| {
+            let state = reconstruct_state(
+                flattened_hands[i * 4..i * 4 + 4].try_into().unwrap(),
+                &boards[i],
+                trumps[i],
+                [tricks_won[i][0], tricks_won[i][1]],
+                players[i],
+            );
+            let difficulty = difficulty_components(&state, tt_log2).score();
+            // A single legal card is trivial whatever the score says.
+            let easy = difficulty < threshold || state.get_legal_moves().count_ones() < 2;
+            if !easy {
+                (true, 1.0, difficulty)
+            } else if sample_rng(seed, i as u64).gen::<f32>() < keep_fraction {
+                (true, 1.0 / keep_fraction, difficulty)
+            } else {
+                (false, 0.0, difficulty)
+            }
+        })
+        .collect();
+    let mut downsampling = Downsampling::default();
+    for (keep, weight, difficulty) in rows {
+        downsampling.keep.push(keep);
+        downsampling.weights.push(weight);
+        downsampling.difficulty.push(difficulty);
+    }
+    Ok(downsampling)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_gen::gameplay::{generate_positions_for_hand, StageConfig};
+    use crate::gameplay::playing::{CLUBS, DIAMONDS, HEARTS, SPADES};
+
+    fn card(suit: u8, rank: u8) -> u8 {
+        suit * 8 + rank
+    }
+
+    #[test]
+    fn test_difficulty_components() {
+        // Two cards each, South to lead. Leading the ace of spades gets it
+        // trumped, leading the nine of trumps loses it to the jack.
+        let mut state = PlayingState::new(HEARTS);
+        state.hands = [
+            1 << card(SPADES, 7) | 1 << card(HEARTS, 2),
+            1 << card(HEARTS, 4) | 1 << card(DIAMONDS, 0),
+            1 << card(SPADES, 3) | 1 << card(DIAMONDS, 1),
+            1 << card(SPADES, 6) | 1 << card(CLUBS, 0),
+        ];
+        let c = difficulty_components(&state, None);
+        assert!(c.nodes > 0);
+        assert_eq!(c.lead_spread, 24 - 21);
+        assert_eq!(c.trump_skew, 0.5);
+        assert!((0.0..=1.0).contains(&c.score()));
+
+        // No trump: no skew.
+        state.trump = 4;
+        assert_eq!(difficulty_components(&state, None).trump_skew, 0.0);
+
+        assert!(difficulty_batch(&[0; 4], &[HEARTS], None).is_err());
+        assert!(difficulty_batch(&[0; 8], &[HEARTS], None).is_err());
+    }
+
+    #[test]
+    fn test_downsample_easy_positions() {
+        let config = StageConfig {
+            opening_weight: 0,
+            midgame_weight: 0,
+            endgame_weight: 1,
+        };
+        let (hands, boards, _, trumps, tricks_won, players) =
+            generate_positions_for_hand(0x0F0F_0000, 40, &config, Some(8)).unwrap();
+        let downsample = |threshold, keep_fraction, seed| {
+            downsample_easy_positions(
+                &hands,
+                &boards,
+                &trumps,
+                &tricks_won,
+                &players,
+                threshold,
+                keep_fraction,
+                seed,
+                Some(16),
+            )
+        };
+
+        // Every position easy: about half kept, each standing for two.
+        let half = downsample(2.0, 0.5, Some(1)).unwrap();
+        let kept = half.keep.iter().filter(|&&k| k).count();
+        assert!((10..=30).contains(&kept), "{} of 40 kept", kept);
+        for (&keep, &weight) in half.keep.iter().zip(&half.weights) {
+            assert_eq!(weight, if keep { 2.0 } else { 0.0 });
+        }
+        assert_eq!(downsample(2.0, 0.5, Some(1)).unwrap(), half);
+
+        // No position under the threshold: only the forced ones are sampled.
+        let hard = downsample(0.0, 0.5, Some(1)).unwrap();
+        assert_eq!(hard.difficulty, half.difficulty);
+        for i in 0..players.len() {
+            let state = reconstruct_state(
+                hands[i * 4..i * 4 + 4].try_into().unwrap(),
+                &boards[i],
+                trumps[i],
+                [tricks_won[i][0], tricks_won[i][1]],
+                players[i],
+            );
+            if state.get_legal_moves().count_ones() > 1 {
+                assert!(hard.keep[i] && hard.weights[i] == 1.0);
+            } else {
+                assert_eq!(hard.keep[i], half.keep[i]);
+            }
+        }
+
+        assert!(downsample(0.5, 0.0, None).is_err());
+        assert!(downsample_easy_positions(
+            &hands,
+            &boards[1..],
+            &trumps,
+            &tricks_won,
+            &players,
+            0.5,
+            0.5,
+            None,
+            None
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,650 @@
+//! Dataset generation spread over machines: a coordinator splits a run into work
+//! units (a range of rows and the seed generating them) and hands them out over TCP
+//! to workers, which generate each unit as a Parquet shard and send it back.
+//!
+//! The protocol is one JSON object per line. Workers run the job given on their
+//! own command line, never one sent over the network: a worker asks for work with
+//! `{"type": "request", "job": ...}`, the job only describing what it runs, and gets
+//! a `unit` (its id, rows and seed), `wait` (every unit is out, but one may come
+//! back), `done`, or `refused` when its job is not the run's. It answers a unit with
+//! `{"type": "result", "bytes": n}` followed by the `n` bytes of the shard, or with
+//! `{"type": "failed", "error": ...}`; results are acknowledged with `ack`. Units of
+//! a worker that fails, sends something else than a Parquet file, disconnects or
+//! stays silent for `unit_timeout` go back in the queue, up to `max_attempts`
+//! tries. Shards are written as `part-00000.parquet`, ... next to a `run.json` of
+//! the run settings: a coordinator restarted on the same directory only hands out
+//! the missing shards, and refuses other settings.
+
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::bidding::{generate_hand_batch, solve_hand_batch, write_bidding_parquet};
+
+/// How long an idle worker waits before asking again, and the coordinator between
+/// two checks for new connections.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Settings of a run, stored in the output directory.
+const RUN_FILE: &str = "run.json";
+
+/// Largest shard a worker may send.
+const MAX_SHARD_BYTES: u64 = 1 << 32;
+
+/// What a worker runs to generate a unit.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GenerationJob {
+    /// Bidding deals solved in-process (`solve_hand_batch`) and written by
+    /// `write_bidding_parquet`.
+    Bidding {
+        pimc_iterations: usize,
+        tt_log2: Option<u8>,
+    },
+    /// An external command, e.g. the Python generator, run with `{seed}`, `{start}`,
+    /// `{rows}` and `{output}` replaced in its arguments; it must write the shard at
+    /// `{output}`.
+    Command(Vec<String>),
+}
+
+impl GenerationJob {
+    /// Description of the job, in the run settings and in the workers' requests.
+    pub fn to_json(&self) -> Value {
+        match self {
+            GenerationJob::Bidding {
+                pimc_iterations,
+                tt_log2,
+            } => json!({"kind": "bidding", "pimc_iterations": pimc_iterations, "tt_log2": tt_log2}),
+            GenerationJob::Command(args) => json!({"kind": "command", "args": args}),
+        }
+    }
+
+    /// Generates `unit` as a Parquet file at `output`.
+    pub fn run(&self, unit: &WorkUnit, output: &Path) -> Result<(), String> {
+        match self {
+            GenerationJob::Bidding {
+                pimc_iterations,
+                tt_log2,
+            } => {
+                let (hands, _) = generate_hand_batch(unit.rows, Some(unit.seed));
+                let south: Vec<u32> = hands.iter().step_by(4).copied().collect();
+                let scores =
+                    solve_hand_batch(hands, *pimc_iterations, *tt_log2, Some(unit.seed), None)?;
+                write_bidding_parquet(&output.to_string_lossy(), &south, &scores);
+            }
+            GenerationJob::Command(args) => {
+                let args: Vec<String> = args
+                    .iter()
+                    .map(|a| {
+                        a.replace("{seed}", &unit.seed.to_string())
+                            .replace("{start}", &unit.start.to_string())
+                            .replace("{rows}", &unit.rows.to_string())
+                            .replace("{output}", &output.to_string_lossy())
+                    })
+                    .collect();
+                let status = Command::new(&args[0])
+                    .args(&args[1..])
+                    .status()
+                    .map_err(|e| format!("{}: {}", args[0], e))?;
+                if !status.success() {
+                    return Err(format!("{} exited with {}", args.join(" "), status));
+                }
+            }
+        }
+        if !output.exists() {
+            return Err(format!("The job wrote no shard at {}", output.display()));
+        }
+        Ok(())
+    }
+}
+
+/// Rows `start..start + rows` of a run, generated from `seed`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WorkUnit {
+    pub id: usize,
+    pub start: u64,
+    pub rows: usize,
+    pub seed: u64,
+}
+
+/// Units of `unit_rows` rows (the last one may be smaller) covering `rows` rows;
+/// the unit starting at row `start` is generated from `seed + start`.
+pub fn plan_units(rows: u64, unit_rows: usize, seed: u64) -> Vec<WorkUnit> {
+    (0..rows.div_ceil(unit_rows.max(1) as u64))
+        .map(|id| {
+            let start = id * unit_rows as u64;
+            WorkUnit {
+                id: id as usize,
+                start,
+                rows: (rows - start).min(unit_rows as u64) as usize,
+                seed: seed.wrapping_add(start),
+            }
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug)]
+pub struct CoordinatorConfig {
+    pub rows: u64,
+    pub unit_rows: usize,
+    pub seed: u64,
+    pub job: GenerationJob,
+    pub output_dir: PathBuf,
+    /// Tries of a unit before the run fails.
+    pub max_attempts: usize,
+    /// Silence of a worker, generating a unit or sending it, after which the unit
+    /// is handed out again.
+    pub unit_timeout: Duration,
+}
+
+impl CoordinatorConfig {
+    fn to_json(&self) -> Value {
+        json!({
+            "rows": self.rows,
+            "unit_rows": self.unit_rows,
+            "seed": self.seed,
+            "job": self.job.to_json(),
+        })
+    }
+
+    fn shard(&self, unit: &WorkUnit) -> PathBuf {
+        self.output_dir.join(format!("part-{:05}.parquet", unit.id))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CoordinatorReport {
+    pub units: usize,
+    /// Units found already generated in the output directory.
+    pub skipped: usize,
+    pub generated: usize,
+    /// Units handed out again after a failure or a lost worker.
+    pub retries: usize,
+    /// Every shard of the run, in row order.
+    pub shards: Vec<PathBuf>,
+}
+
+#[derive(Default)]
+struct Queue {
+    pending: VecDeque<WorkUnit>,
+    in_flight: usize,
+    attempts: HashMap<usize, usize>,
+    report: CoordinatorReport,
+    error: Option<String>,
+}
+
+impl Queue {
+    fn finished(&self) -> bool {
+        self.in_flight == 0 && (self.pending.is_empty() || self.error.is_some())
+    }
+
+    /// Puts `unit` back after `error`, or fails the run once it used its tries.
+    fn retry(&mut self, unit: WorkUnit, error: String, max_attempts: usize) {
+        self.in_flight -= 1;
+        if self.attempts[&unit.id] >= max_attempts {
+            self.error.get_or_insert(format!(
+                "Unit {} failed {} times: {}",
+                unit.id, max_attempts, error
+            ));
+            self.pending.clear();
+        } else {
+            self.report.retries += 1;
+            self.pending.push_back(unit);
+        }
+    }
+}
+
+/// Serves the units of `config` to the workers connecting to `listener` until every
+/// shard is in the output directory.
+pub fn run_coordinator(
+    listener: TcpListener,
+    config: CoordinatorConfig,
+) -> Result<CoordinatorReport, String> {
+    if config.unit_rows == 0 || config.max_attempts == 0 {
+        return Err("unit_rows and max_attempts must be positive".to_string());
+    }
+    let dir = &config.output_dir;
+    std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    let run_file = dir.join(RUN_FILE);
+    let settings = config.to_json();
+    if run_file.exists() {
+        let text = std::fs::read_to_string(&run_file).map_err(|e| e.to_string())?;
+        let stored: Value = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+        if stored != settings {
+            return Err(format!(
+                "{} was started with {}, not {}",
+                dir.display(),
+                stored,
+                settings
+            ));
+        }
+    } else {
+        std::fs::write(&run_file, settings.to_string()).map_err(|e| e.to_string())?;
+    }
+
+    let units = plan_units(config.rows, config.unit_rows, config.seed);
+    let mut queue = Queue::default();
+    queue.report.units = units.len();
+    queue.report.shards = units.iter().map(|u| config.shard(u)).collect();
+    for unit in units {
+        if config.shard(&unit).exists() {
+            queue.report.skipped += 1;
+        } else {
+            queue.pending.push_back(unit);
+        }
+    }
+
+    let queue = Arc::new(Mutex::new(queue));
+    let config = Arc::new(config);
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    // Connections are served on their own threads, left behind once the run is
+    // over: a worker that stopped answering must not hold the coordinator.
+    loop {
+        {
+            let queue = queue.lock().unwrap();
+            if queue.finished() {
+                return match &queue.error {
+                    Some(e) => Err(e.clone()),
+                    None => Ok(queue.report.clone()),
+                };
+            }
+        }
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let (queue, config) = (queue.clone(), config.clone());
+                std::thread::spawn(move || serve_worker(stream, &queue, &config));
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(POLL_INTERVAL)
+            }
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
+
+fn send(stream: &mut TcpStream, message: &Value) -> std::io::Result<()> {
+    stream.write_all(format!("{}\n", message).as_bytes())
+}
+
+/// Next message of `reader`; `None` once the peer closed the connection.
+fn receive(reader: &mut impl BufRead) -> Result<Option<Value>, String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).map_err(|e| e.to_string())? == 0 {
+        return Ok(None);
+    }
+    serde_json::from_str(&line)
+        .map(Some)
+        .map_err(|e| format!("Invalid message {:?}: {}", line.trim(), e))
+}
+
+fn serve_worker(stream: TcpStream, queue: &Mutex<Queue>, config: &CoordinatorConfig) {
+    if stream.set_nonblocking(false).is_err()
+        || stream.set_read_timeout(Some(config.unit_timeout)).is_err()
+    {
+        return;
+    }
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let mut reader = BufReader::new(stream);
+    while let Ok(Some(request)) = receive(&mut reader) {
+        if request["type"] != "request" {
+            return;
+        }
+        if request["job"] != config.job.to_json() {
+            let error = format!(
+                "The run generates {}, not {}",
+                config.job.to_json(),
+                request["job"]
+            );
+            let _ = send(&mut writer, &json!({"type": "refused", "error": error}));
+            return;
+        }
+        let reply = {
+            let mut queue = queue.lock().unwrap();
+            match queue.pending.pop_front() {
+                Some(unit) => {
+                    queue.in_flight += 1;
+                    *queue.attempts.entry(unit.id).or_default() += 1;
+                    Ok(unit)
+                }
+                None if queue.finished() => Err("done"),
+                None => Err("wait"),
+            }
+        };
+        let unit = match reply {
+            Ok(unit) => unit,
+            Err(kind) => {
+                if send(&mut writer, &json!({"type": kind})).is_err() || kind == "done" {
+                    return;
+                }
+                continue;
+            }
+        };
+        let outcome = send(
+            &mut writer,
+            &json!({
+                "type": "unit",
+                "id": unit.id,
+                "start": unit.start,
+                "rows": unit.rows,
+                "seed": unit.seed,
+            }),
+        )
+        .map_err(|e| (true, e.to_string()))
+        .and_then(|_| receive_shard(&mut reader, &config.shard(&unit)));
+
+        let lost = outcome.as_ref().is_err_and(|(lost, _)| *lost);
+        {
+            let mut queue = queue.lock().unwrap();
+            match outcome {
+                Ok(()) => {
+                    queue.in_flight -= 1;
+                    queue.report.generated += 1;
+                }
+                Err((_, error)) => queue.retry(unit, error, config.max_attempts),
+            }
+        }
+        if lost || send(&mut writer, &json!({"type": "ack"})).is_err() {
+            return;
+        }
+    }
+}
+
+/// Reads the worker's answer to a unit and writes its shard at `path`. The error
+/// says whether the connection is lost.
+fn receive_shard(reader: &mut impl BufRead, path: &Path) -> Result<(), (bool, String)> {
+    let lost = |e: String| (true, e);
+    let answer = receive(reader)
+        .map_err(lost)?
+        .ok_or_else(|| lost("The worker disconnected".to_string()))?;
+    match answer["type"].as_str() {
+        Some("result") => {
+            let size = answer["bytes"]
+                .as_u64()
+                .filter(|&size| size <= MAX_SHARD_BYTES)
+                .ok_or_else(|| lost(format!("Invalid message {}", answer)))?;
+            // Written aside first, so a partial shard is never taken for a done unit.
+            let partial = path.with_extension("parquet.partial");
+            let at = |e: std::io::Error| format!("{}: {}", partial.display(), e);
+            let mut file = File::create(&partial).map_err(|e| (false, at(e)))?;
+            let copied = std::io::copy(&mut reader.take(size), &mut file).map_err(|e| lost(at(e)));
+            drop(file);
+            let shard = match copied {
+                Ok(n) if n != size => Err(lost(format!("The worker sent {} of {} bytes", n, size))),
+                Ok(_) => check_shard(&partial)
+                    .and_then(|_| std::fs::rename(&partial, path).map_err(at))
+                    .map_err(|e| (false, e)),
+                Err(e) => Err(e),
+            };
+            if shard.is_err() {
+                let _ = std::fs::remove_file(&partial);
+            }
+            shard
+        }
+        Some("failed") => Err((
+            false,
+            answer["error"]
+                .as_str()
+                .unwrap_or("unknown error")
+                .to_string(),
+        )),
+        _ => Err(lost(format!("Invalid message {}", answer))),
+    }
+}
+
+/// Whether `path` holds a Parquet file.
+fn check_shard(path: &Path) -> Result<(), String> {
+    File::open(path)
+        .map_err(|e| e.to_string())
+        .and_then(|f| ParquetRecordBatchReaderBuilder::try_new(f).map_err(|e| e.to_string()))
+        .map(|_| ())
+        .map_err(|e| format!("The shard is not a Parquet file: {}", e))
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WorkerReport {
+    pub units: usize,
+    pub failures: usize,
+}
+
+/// Generates with `job` the units handed out by the coordinator at `address`, in
+/// `work_dir`, until the run is over.
+pub fn run_worker(
+    address: &str,
+    job: &GenerationJob,
+    work_dir: &Path,
+) -> Result<WorkerReport, String> {
+    std::fs::create_dir_all(work_dir).map_err(|e| format!("{}: {}", work_dir.display(), e))?;
+    let mut stream = TcpStream::connect(address).map_err(|e| format!("{}: {}", address, e))?;
+    let mut reader = BufReader::new(stream.try_clone().map_err(|e| e.to_string())?);
+    let request = json!({"type": "request", "job": job.to_json()});
+    let mut report = WorkerReport::default();
+    loop {
+        send(&mut stream, &request).map_err(|e| e.to_string())?;
+        let message = receive(&mut reader)?.ok_or("The coordinator disconnected")?;
+        match message["type"].as_str() {
+            Some("unit") => {}
+            Some("wait") => {
+                std::thread::sleep(POLL_INTERVAL);
+                continue;
+            }
+            Some("done") => return Ok(report),
+            Some("refused") => {
+                return Err(message["error"]
+                    .as_str()
+                    .unwrap_or("The coordinator refused the worker")
+                    .to_string())
+            }
+            _ => return Err(format!("Invalid message {}", message)),
+        }
+        let unit = WorkUnit {
+            id: message["id"].as_u64().ok_or("Unit without id")? as usize,
+            start: message["start"].as_u64().ok_or("Unit without start")?,
+            rows: message["rows"].as_u64().ok_or("Unit without rows")? as usize,
+            seed: message["seed"].as_u64().ok_or("Unit without seed")?,
+        };
+        let output = work_dir.join(format!("unit-{:05}.parquet", unit.id));
+        let _ = std::fs::remove_file(&output);
+        let shard = job
+            .run(&unit, &output)
+            .and_then(|_| std::fs::read(&output).map_err(|e| e.to_string()));
+        let _ = std::fs::remove_file(&output);
+        match shard {
+            Ok(bytes) => {
+                send(
+                    &mut stream,
+                    &json!({"type": "result", "bytes": bytes.len()}),
+                )
+                .and_then(|_| stream.write_all(&bytes))
+                .map_err(|e| e.to_string())?;
+                report.units += 1;
+            }
+            Err(error) => {
+                send(&mut stream, &json!({"type": "failed", "error": error}))
+                    .map_err(|e| e.to_string())?;
+                report.failures += 1;
+            }
+        }
+        let ack = receive(&mut reader)?.ok_or("The coordinator disconnected")?;
+        if ack["type"] != "ack" {
+            return Err(format!("Invalid message {}", ack));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_units() {
+        let units = plan_units(10, 4, 100);
+        assert_eq!(units.len(), 3);
+        assert_eq!(
+            units[2],
+            WorkUnit {
+                id: 2,
+                start: 8,
+                rows: 2,
+                seed: 108
+            }
+        );
+    }
+
+    /// Parquet file of `rows` rows holding `seed`.
+    fn write_shard(path: &Path, seed: u64, rows: usize) {
+        use arrow::array::{ArrayRef, UInt64Array};
+        use arrow::record_batch::RecordBatch;
+        use parquet::arrow::ArrowWriter;
+        let column: ArrayRef = std::sync::Arc::new(UInt64Array::from(vec![seed; rows]));
+        let batch = RecordBatch::try_from_iter([("seed", column)]).unwrap();
+        let mut writer =
+            ArrowWriter::try_new(File::create(path).unwrap(), batch.schema(), None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    fn read_shard(path: &Path) -> Vec<u64> {
+        use arrow::array::AsArray;
+        use arrow::datatypes::UInt64Type;
+        ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+            .unwrap()
+            .build()
+            .unwrap()
+            .flat_map(|b| {
+                b.unwrap()
+                    .column(0)
+                    .as_primitive::<UInt64Type>()
+                    .values()
+                    .to_vec()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_coordinator_and_workers() {
+        let dir = std::env::temp_dir().join(format!("distributed_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        // The command copies a shard written beforehand for each unit, failing once.
+        let units = plan_units(10, 2, 7);
+        for unit in &units {
+            write_shard(
+                &dir.join(format!("unit-{}.parquet", unit.seed)),
+                unit.seed,
+                unit.rows,
+            );
+        }
+        let job = GenerationJob::Command(vec![
+            "sh".into(),
+            "-c".into(),
+            "[ {start} != 4 ] || [ -e $0.failed ] || { touch $0.failed; exit 1; }; \
+             cp $0-{seed}.parquet {output}"
+                .into(),
+            dir.join("unit").to_string_lossy().into_owned(),
+        ]);
+        let config = CoordinatorConfig {
+            rows: 10,
+            unit_rows: 2,
+            seed: 7,
+            job: job.clone(),
+            output_dir: dir.join("out"),
+            max_attempts: 2,
+            unit_timeout: Duration::from_secs(60),
+        };
+        let start = |config: CoordinatorConfig| {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let address = listener.local_addr().unwrap().to_string();
+            (
+                address,
+                std::thread::spawn(move || run_coordinator(listener, config)),
+            )
+        };
+        let run_workers = |address: &str, job: &GenerationJob, workers: usize| {
+            let handles: Vec<_> = (0..workers)
+                .map(|w| {
+                    let (address, job) = (address.to_string(), job.clone());
+                    let work_dir = dir.join(format!("worker{}", w));
+                    std::thread::spawn(move || run_worker(&address, &job, &work_dir))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap().unwrap())
+                .collect::<Vec<WorkerReport>>()
+        };
+
+        let (address, coordinator) = start(config.clone());
+        // Workers running another job are turned away.
+        let other_job = GenerationJob::Command(vec!["true".into()]);
+        let err = run_worker(&address, &other_job, &dir.join("other")).unwrap_err();
+        assert!(err.starts_with("The run generates"), "{}", err);
+        let workers = run_workers(&address, &job, 2);
+        let report = coordinator.join().unwrap().unwrap();
+        assert_eq!((report.units, report.generated, report.retries), (5, 5, 1));
+        assert_eq!(workers.iter().map(|w| w.units).sum::<usize>(), 5);
+        assert_eq!(workers.iter().map(|w| w.failures).sum::<usize>(), 1);
+        for (unit, shard) in units.iter().zip(&report.shards) {
+            assert_eq!(read_shard(shard), vec![unit.seed; unit.rows]);
+        }
+
+        // A restart only generates the missing shards, with the same settings; the
+        // unit of a worker gone silent is handed out again.
+        std::fs::remove_file(&report.shards[3]).unwrap();
+        let (address, coordinator) = start(CoordinatorConfig {
+            unit_timeout: Duration::from_millis(500),
+            ..config.clone()
+        });
+        let mut stalled = TcpStream::connect(&address).unwrap();
+        send(
+            &mut stalled,
+            &json!({"type": "request", "job": job.to_json()}),
+        )
+        .unwrap();
+        let unit = receive(&mut BufReader::new(stalled.try_clone().unwrap())).unwrap();
+        assert_eq!(unit.unwrap()["id"], 3);
+        run_workers(&address, &job, 1);
+        let report = coordinator.join().unwrap().unwrap();
+        assert_eq!(
+            (report.skipped, report.generated, report.retries),
+            (4, 1, 1)
+        );
+        drop(stalled);
+        let other = CoordinatorConfig {
+            seed: 8,
+            ..config.clone()
+        };
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        assert!(run_coordinator(listener, other).is_err());
+
+        // Shards that are not Parquet files are refused; a unit failing every try
+        // fails the run.
+        let garbage = GenerationJob::Command(vec![
+            "sh".into(),
+            "-c".into(),
+            "echo {seed} > {output}".into(),
+        ]);
+        let (address, coordinator) = start(CoordinatorConfig {
+            job: garbage.clone(),
+            output_dir: dir.join("garbage"),
+            ..config
+        });
+        run_workers(&address, &garbage, 1);
+        let err = coordinator.join().unwrap().unwrap_err();
+        assert!(
+            err.contains("failed 2 times: The shard is not a Parquet file"),
+            "{}",
+            err
+        );
+        assert!(std::fs::read_dir(dir.join("garbage"))
+            .unwrap()
+            .all(|f| f.unwrap().file_name() == RUN_FILE));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+//! Static evaluation samples: random positions with the solver's heuristic value
+//! and their exact double-dummy value, for fitting a better evaluator.
+//!
+//! Positions are random endings (`random_ending`), the number of cards left
+//! drawn uniformly in a range, and are seen from the team of the player to move.
+//! Both values are that team's final points.
+
+use crate::gameplay::encoding::evaluation_features;
+use crate::gameplay::snapshot::dump_state;
+use crate::solver::{random_ending, solve_for_team, static_evaluation, Score};
+use pyo3::prelude::*;
+use rand::Rng;
+use rayon::prelude::*;
+
+use super::common::sample_rng;
+
+/// One position of an evaluation dataset.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvaluationSample {
+    /// The position as `dump_state` text.
+    #[pyo3(get)]
+    pub state: String,
+    /// Team of the player to move, whose points both values are.
+    #[pyo3(get)]
+    pub team: u8,
+    #[pyo3(get)]
+    pub cards_left: u8,
+    /// `evaluation_features` of the position for `team`.
+    #[pyo3(get)]
+    pub features: Vec<f32>,
+    /// Value of `static_evaluation`.
+    #[pyo3(get)]
+    pub heuristic: Score,
+    /// Double-dummy value of the position.
+    #[pyo3(get)]
+    pub exact: Score,
+}
+
+#[pymethods]
+impl EvaluationSample {
+    pub fn __repr__(&self) -> String {
+        format!(
+            "EvaluationSample(team={}, cards_left={}, heuristic={}, exact={})",
+            self.team, self.cards_left, self.heuristic, self.exact
+        )
+    }
+}
+
+/// `num_samples` positions with between `min_cards` and `max_cards` cards left.
+/// With a seed, sample `i` only depends on (seed, i).
+pub fn generate_evaluation_batch(
+    num_samples: usize,
+    seed: Option<u64>,
+    min_cards: u8,
+    max_cards: u8,
+    tt_log2: Option<u8>,
+) -> Result<Vec<EvaluationSample>, String> {
+    if min_cards == 0 || min_cards > max_cards || max_cards > 32 {
+        return Err(format!(
+            "Invalid cards left range {}..={}",
+            min_cards, max_cards
+        ));
+    }
+    let samples = (0..num_samples)
+        .into_par_iter()
+        .map(|i| {
+            let mut rng = sample_rng(seed, i as u64);
+            let cards_left = rng.gen_range(min_cards..=max_cards);
+            let state = random_ending(cards_left as u32, &mut rng);
+            let team = state.current_player % 2;
+            EvaluationSample {
+                state: dump_state(&state),
+                team,
+                cards_left,
+                features: evaluation_features(&state, team as usize),
+                heuristic: static_evaluation(&state, team as usize),
+                exact: solve_for_team(&state, team as usize, Some(32), tt_log2).0,
+            }
+        })
+        .collect();
+    crate::profiling::dump("generate_evaluation_batch");
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::encoding::EVALUATION_FEATURES;
+    use crate::gameplay::snapshot::load_state;
+
+    #[test]
+    fn test_evaluation_batch() {
+        let samples = generate_evaluation_batch(6, Some(3), 1, 8, None).unwrap();
+        assert_eq!(samples.len(), 6);
+        for sample in &samples {
+            assert!((1..=8).contains(&sample.cards_left));
+            assert_eq!(sample.features.len(), EVALUATION_FEATURES);
+            let state = load_state(&sample.state).unwrap();
+            let team = sample.team as usize;
+            assert_eq!(sample.team, state.current_player % 2);
+            let (lower, upper) = state.team_score_bounds(team);
+            assert!((lower as Score..=upper as Score).contains(&sample.heuristic));
+            assert_eq!(sample.exact, solve_for_team(&state, team, Some(32), None).0);
+            // The player to move is the first seat of its team or its partner.
+            assert_eq!(
+                sample.features[8 * 32 + state.current_player as usize / 2 * 2],
+                1.0
+            );
+        }
+        assert_eq!(
+            generate_evaluation_batch(6, Some(3), 1, 8, None).unwrap(),
+            samples
+        );
+        assert!(generate_evaluation_batch(1, None, 0, 8, None).is_err());
+        assert!(generate_evaluation_batch(1, None, 9, 8, None).is_err());
+    }
+
+    /// The heuristic value follows the exact one on the positions of recorded games.
+    #[cfg(feature = "deal-corpus")]
+    #[test]
+    fn test_evaluator_correlation_on_corpus() {
+        use crate::gameplay::corpus::{corpus_positions, embedded_corpus};
+        let positions = corpus_positions(&embedded_corpus().unwrap(), 5);
+        let values: Vec<(f64, f64)> = positions
+            .par_iter()
+            .map(|state| {
+                let team = (state.current_player % 2) as usize;
+                let exact = solve_for_team(state, team, Some(32), Some(16)).0;
+                (static_evaluation(state, team) as f64, exact as f64)
+            })
+            .collect();
+        let n = values.len() as f64;
+        let mean = |f: fn(&(f64, f64)) -> f64| values.iter().map(f).sum::<f64>() / n;
+        let (mx, my) = (mean(|v| v.0), mean(|v| v.1));
+        let cov: f64 = values.iter().map(|(x, y)| (x - mx) * (y - my)).sum();
+        let var = |f: fn(&(f64, f64)) -> f64, m: f64| {
+            values.iter().map(|v| (f(v) - m).powi(2)).sum::<f64>()
+        };
+        let r = cov / (var(|v| v.0, mx) * var(|v| v.1, my)).sqrt();
+        assert!(values.len() > 100);
+        assert!(r > 0.6, "correlation {}", r);
+    }
+}
@@ -1,10 +1,26 @@
+use crate::gameplay::bidding::{Bid, BiddingState};
+use crate::gameplay::deal::deal_id;
+use crate::gameplay::history::{decode_history, encode_history, history_mask, PlayRecord};
 use crate::gameplay::playing::PlayingState;
-use crate::solver::solve;
+use crate::gameplay::rules::RuleSet;
+use crate::gameplay::threshold_bidder::ThresholdBidder;
+use crate::gameplay::worlds::{voids_from_plays, WorldConstraints};
+use crate::solver::{
+    nodes_searched, pimc_tt_scope, search_aborted, solve_root_moves_with_objective,
+    solve_with_objective, with_deadline, with_tt_scope, Objective, Perspective, Score,
+};
 use indicatif::ParallelProgressIterator;
+use pyo3::exceptions::{PyIndexError, PyValueError};
+use pyo3::prelude::*;
 use rand::prelude::*;
 use rayon::prelude::*;
+use serde_json::{json, Value};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-use super::common::generate_random_hands;
+use super::bidding::contract_strength;
+use super::checkpoint::{fingerprint, run_checkpointed, CheckpointConfig};
+use super::common::{generate_hands_with_south, generate_random_hands, sample_rng};
 
 // Phase 1 Output: Just the state snapshot
 pub struct RawGameplayState {
@@ -14,81 +30,619 @@ pub struct RawGameplayState {
     pub trump: u8,
     pub tricks_won: [u8; 2],
     pub player: u8,
+    pub plays: Vec<PlayRecord>, // Ordered history behind `history`
+    pub declarer: u8,
+    pub contract: Option<Bid>,
 }
 
 // Phase 2 Output: The solved sample
 pub struct SolvedGameplaySample {
     pub best_card: u8,
-    pub best_score: i16,
-    pub valid: bool, // If filtered out
+    pub best_score: f32,
+    pub invalid_reason: Option<InvalidReason>, // If filtered out
+    pub nodes: u64, // Solver nodes searched for this sample (all PIMC worlds included)
+    pub solve_time_us: u64,
+    pub confidence: PimcConfidence,
+    /// Cards within the optimal epsilon of the best value, `best_card` alone when the
+    /// batch was solved without one (0 if filtered out).
+    pub optimal_cards: u32,
+    /// The seat to move had a single legal card.
+    pub forced: bool,
 }
 
-pub fn generate_raw_gameplay_batch(
-    batch_size: usize,
-) -> (
+impl SolvedGameplaySample {
+    pub fn valid(&self) -> bool {
+        self.invalid_reason.is_none()
+    }
+
+    /// A sample filtered out for `reason`, with empty labels.
+    pub fn invalid(reason: InvalidReason) -> Self {
+        SolvedGameplaySample {
+            best_card: 0,
+            best_score: 0.0,
+            invalid_reason: Some(reason),
+            nodes: 0,
+            solve_time_us: 0,
+            confidence: PimcConfidence::certain(),
+            optimal_cards: 0,
+            forced: false,
+        }
+    }
+
+    /// A forced move labelled without a solve: `card` and no score (NaN).
+    pub fn unsolved_forced(card: u8) -> Self {
+        SolvedGameplaySample {
+            best_card: card,
+            best_score: f32::NAN,
+            invalid_reason: None,
+            nodes: 0,
+            solve_time_us: 0,
+            confidence: PimcConfidence::certain(),
+            optimal_cards: 1 << card,
+            forced: true,
+        }
+    }
+}
+
+/// Why a sample of a solved batch is invalid. Its `code` fills the `invalid_reasons`
+/// column, where valid samples have 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum InvalidReason {
+    /// The deal is over.
+    Terminal = 1,
+    /// The seat to move has no legal card.
+    NoLegalMoves = 2,
+    /// The board has 4 cards or more, a card twice, or a card still in a hand.
+    InconsistentBoard = 3,
+    /// The history misses a board card, holds a card still in hand, or is not four
+    /// cards per trick won plus the board.
+    HistoryMismatch = 4,
+    /// The hand sizes do not fit the trick starter implied by the seat to move and
+    /// the board: the seats that played to the board hold one card less.
+    HandSizes = 5,
+    /// A board card could not have been played: its seat still holds the led suit or
+    /// a trump it had to play.
+    IllegalBoard = 6,
+    /// The solve ran past the time limit of the batch.
+    Timeout = 7,
+}
+
+impl InvalidReason {
+    pub const ALL: [InvalidReason; 7] = [
+        InvalidReason::Terminal,
+        InvalidReason::NoLegalMoves,
+        InvalidReason::InconsistentBoard,
+        InvalidReason::HistoryMismatch,
+        InvalidReason::HandSizes,
+        InvalidReason::IllegalBoard,
+        InvalidReason::Timeout,
+    ];
+
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|r| r.code() == code)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            InvalidReason::Terminal => "terminal",
+            InvalidReason::NoLegalMoves => "no_legal_moves",
+            InvalidReason::InconsistentBoard => "inconsistent_board",
+            InvalidReason::HistoryMismatch => "history_mismatch",
+            InvalidReason::HandSizes => "hand_sizes",
+            InvalidReason::IllegalBoard => "illegal_board",
+            InvalidReason::Timeout => "timeout",
+        }
+    }
+}
+
+/// Number of samples per outcome of an `invalid_reasons` column: "valid" first, then
+/// every `InvalidReason` by name, in code order (zero counts included).
+pub fn invalid_reason_counts(reasons: &[u8]) -> Result<Vec<(&'static str, usize)>, String> {
+    let mut counts = vec![0; InvalidReason::ALL.len() + 1];
+    for &code in reasons {
+        if code as usize >= counts.len() {
+            return Err(format!("Unknown invalid reason code {}", code));
+        }
+        counts[code as usize] += 1;
+    }
+    let names = std::iter::once("valid").chain(InvalidReason::ALL.iter().map(|r| r.name()));
+    Ok(names.zip(counts).collect())
+}
+
+/// Columnar solved batch: (best_cards, best_scores, valid, nodes_searched, solve_time_us,
+/// agreement, vote_entropy, value_variance, optimal_cards, invalid_reasons, forced).
+/// Scores are floats so that `ScoreLabel::Expected` labels fit; double-dummy scores are
+/// whole numbers. The next three are the `PimcConfidence` of each label (1, 0, 0 for
+/// double-dummy labels), `optimal_cards` the mask of the cards as good as the label,
+/// `invalid_reasons` the `InvalidReason` code of each sample (0 if valid) and `forced`
+/// whether the seat to move had a single legal card.
+pub type SolvedGameplayBatch = (
+    Vec<u8>,
+    Vec<f32>,
+    Vec<bool>,
+    Vec<u64>,
+    Vec<u64>,
+    Vec<f32>,
+    Vec<f32>,
+    Vec<f32>,
+    Vec<u32>,
+    Vec<u8>,
+    Vec<bool>,
+);
+
+/// Columnar raw batch: (flattened_hands, boards, history, trumps, tricks_won_pair, current_player)
+pub type RawGameplayBatch = (
     Vec<u32>,
     Vec<Vec<u8>>,
     Vec<u32>,
     Vec<u8>,
     Vec<Vec<u8>>,
     Vec<u8>,
-) {
-    // Returns: (flattened_hands, boards, history, trumps, tricks_won_pair, current_player)
+);
+
+/// One generated position, as handed to Python.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameplaySample {
+    /// Remaining cards of the four seats.
+    #[pyo3(get)]
+    pub hands: [u32; 4],
+    /// Cards of the current trick, in play order.
+    #[pyo3(get)]
+    pub board: Vec<u8>,
+    /// Mask of the cards played in completed tricks.
+    #[pyo3(get)]
+    pub history: u32,
+    #[pyo3(get)]
+    pub trump: u8,
+    #[pyo3(get)]
+    pub tricks_won: [u8; 2],
+    /// Seat to move.
+    #[pyo3(get)]
+    pub player: u8,
+    /// Ordered plays so far, packed as in `decode_play_history`.
+    #[pyo3(get)]
+    pub plays: Vec<u16>,
+    /// Seat taken as the declarer: the owner of the contract when there is one,
+    /// otherwise the one whose dealt hand is the strongest in the trump (see
+    /// `deal_declarer`).
+    #[pyo3(get)]
+    pub declarer: u8,
+    /// Contract of the auction the deal was given, see `ContractSource`.
+    #[pyo3(get)]
+    pub contract: Option<Bid>,
+}
+
+impl From<RawGameplayState> for GameplaySample {
+    fn from(s: RawGameplayState) -> Self {
+        GameplaySample {
+            plays: encode_history(&s.plays),
+            hands: s.hands,
+            board: s.board,
+            history: s.history,
+            trump: s.trump,
+            tricks_won: s.tricks_won,
+            player: s.player,
+            declarer: s.declarer,
+            contract: s.contract,
+        }
+    }
+}
+
+#[pymethods]
+impl GameplaySample {
+    /// Hand of the seat to move.
+    #[getter]
+    pub fn hand(&self) -> u32 {
+        self.hands[self.player as usize]
+    }
+
+    /// Whether the seat to move is the declarer or its partner. Values solved with
+    /// the "current" perspective are the declaring side's when it is, the
+    /// defenders' otherwise.
+    #[getter]
+    pub fn declaring_side(&self) -> bool {
+        self.player % 2 == self.declarer % 2
+    }
+
+    /// ID of the deal the position comes from, see `deal_id`.
+    #[getter]
+    pub fn deal_id(&self) -> PyResult<u128> {
+        let mut dealt = self.hands;
+        for p in decode_history(&self.plays) {
+            dealt[p.seat as usize % 4] |= 1 << p.card;
+        }
+        deal_id(&dealt).map_err(PyValueError::new_err)
+    }
+
+    /// Solver state of the position, replayed from `plays` so that it has the points
+    /// won and the belote announced so far.
+    pub fn state(&self) -> PyResult<PlayingState> {
+        replay_state(
+            self.hands,
+            &decode_history(&self.plays),
+            self.trump,
+            self.player,
+        )
+        .map_err(PyValueError::new_err)
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "GameplaySample(trump={}, player={}, board={:?}, tricks_won={:?})",
+            self.trump, self.player, self.board, self.tricks_won
+        )
+    }
+}
+
+/// Generated positions, indexable like a list of `GameplaySample` and with one
+/// columnar accessor per field.
+#[pyclass]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GameplayBatch {
+    pub samples: Vec<GameplaySample>,
+}
+
+impl GameplayBatch {
+    fn column<T>(&self, f: impl Fn(&GameplaySample) -> T) -> Vec<T> {
+        self.samples.iter().map(f).collect()
+    }
+
+    /// The batch in the legacy columnar tuple layout.
+    pub fn columns(&self) -> RawGameplayBatch {
+        (
+            self.flat_hands(),
+            self.boards(),
+            self.history(),
+            self.trumps(),
+            self.column(|s| s.tricks_won.to_vec()),
+            self.players(),
+        )
+    }
+}
+
+#[pymethods]
+impl GameplayBatch {
+    pub fn __len__(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn __getitem__(&self, index: isize) -> PyResult<GameplaySample> {
+        let len = self.samples.len() as isize;
+        let i = if index < 0 { index + len } else { index };
+        if !(0..len).contains(&i) {
+            return Err(PyIndexError::new_err("sample index out of range"));
+        }
+        Ok(self.samples[i as usize].clone())
+    }
+
+    #[getter]
+    pub fn hands(&self) -> Vec<[u32; 4]> {
+        self.column(|s| s.hands)
+    }
+
+    /// Hands of all samples back to back (4 per sample), as `solve_gameplay_batch` takes them.
+    #[getter]
+    pub fn flat_hands(&self) -> Vec<u32> {
+        self.samples.iter().flat_map(|s| s.hands).collect()
+    }
+
+    #[getter]
+    pub fn boards(&self) -> Vec<Vec<u8>> {
+        self.column(|s| s.board.clone())
+    }
+
+    #[getter]
+    pub fn history(&self) -> Vec<u32> {
+        self.column(|s| s.history)
+    }
+
+    #[getter]
+    pub fn trumps(&self) -> Vec<u8> {
+        self.column(|s| s.trump)
+    }
+
+    #[getter]
+    pub fn tricks_won(&self) -> Vec<[u8; 2]> {
+        self.column(|s| s.tricks_won)
+    }
+
+    #[getter]
+    pub fn players(&self) -> Vec<u8> {
+        self.column(|s| s.player)
+    }
+
+    #[getter]
+    pub fn plays(&self) -> Vec<Vec<u16>> {
+        self.column(|s| s.plays.clone())
+    }
+
+    #[getter]
+    pub fn declarers(&self) -> Vec<u8> {
+        self.column(|s| s.declarer)
+    }
+
+    #[getter]
+    pub fn declaring_side(&self) -> Vec<bool> {
+        self.column(|s| s.declaring_side())
+    }
+
+    #[getter]
+    pub fn deal_ids(&self) -> PyResult<Vec<u128>> {
+        self.samples.iter().map(|s| s.deal_id()).collect()
+    }
+
+    /// Value of each sample's contract, 0 for deals without an auction.
+    #[getter]
+    pub fn contract_values(&self) -> Vec<u8> {
+        self.column(|s| s.contract.map_or(0, |c| c.value))
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!("GameplayBatch(len={})", self.samples.len())
+    }
+}
+
+/// Relative weights of the game stages sampled when generating positions.
+/// Opening = 0-2 tricks played, Midgame = 3-4, Endgame = 5-7.
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct StageConfig {
+    #[pyo3(get, set)]
+    pub opening_weight: u32,
+    #[pyo3(get, set)]
+    pub midgame_weight: u32,
+    #[pyo3(get, set)]
+    pub endgame_weight: u32,
+}
+
+impl Default for StageConfig {
+    fn default() -> Self {
+        // 50% Endgame, 30% Midgame, 20% Opening (the slow part to solve)
+        StageConfig {
+            opening_weight: 20,
+            midgame_weight: 30,
+            endgame_weight: 50,
+        }
+    }
+}
+
+#[pymethods]
+impl StageConfig {
+    #[new]
+    #[pyo3(signature = (opening_weight=20, midgame_weight=30, endgame_weight=50))]
+    pub fn new(opening_weight: u32, midgame_weight: u32, endgame_weight: u32) -> PyResult<Self> {
+        if opening_weight + midgame_weight + endgame_weight == 0 {
+            return Err(PyValueError::new_err(
+                "At least one stage weight must be positive",
+            ));
+        }
+        Ok(StageConfig {
+            opening_weight,
+            midgame_weight,
+            endgame_weight,
+        })
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "StageConfig(opening={}, midgame={}, endgame={})",
+            self.opening_weight, self.midgame_weight, self.endgame_weight
+        )
+    }
+}
+
+impl StageConfig {
+    /// Draws the number of complete tricks to play before the snapshot.
+    fn sample_target_trick<R: Rng>(&self, rng: &mut R) -> usize {
+        let total = self.opening_weight + self.midgame_weight + self.endgame_weight;
+        let r = rng.gen_range(0..total.max(1));
+        if r < self.endgame_weight {
+            rng.gen_range(5..8)
+        } else if r < self.endgame_weight + self.midgame_weight {
+            rng.gen_range(3..5)
+        } else {
+            rng.gen_range(0..3)
+        }
+    }
+}
+
+pub fn generate_raw_gameplay_batch(batch_size: usize, seed: Option<u64>) -> RawGameplayBatch {
+    generate_gameplay_batch(batch_size, seed).columns()
+}
+
+/// Same as `generate_raw_gameplay_batch`, plus the ordered history of each sample
+/// encoded with `history::encode_history`.
+pub fn generate_raw_gameplay_batch_with_plays(
+    batch_size: usize,
+    seed: Option<u64>,
+) -> (RawGameplayBatch, Vec<Vec<u16>>) {
+    let batch = generate_gameplay_batch(batch_size, seed);
+    (batch.columns(), batch.plays())
+}
+
+/// Side of the player to move a generated position must be on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SideFilter {
+    /// The declarer or its partner.
+    Declarer,
+    /// The declarer's opponents.
+    Defense,
+}
+
+impl SideFilter {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "declarer" => Ok(SideFilter::Declarer),
+            "defense" | "defence" => Ok(SideFilter::Defense),
+            _ => Err(format!(
+                "Unknown side '{}' (expected 'declarer' or 'defense')",
+                name
+            )),
+        }
+    }
+
+    fn accepts(&self, player: u8, declarer: u8) -> bool {
+        (player % 2 == declarer % 2) == (*self == SideFilter::Declarer)
+    }
+}
 
-    let states: Vec<RawGameplayState> = (0..batch_size)
+/// Where the trump and declarer of a generated deal come from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ContractSource {
+    /// A random suit, the strongest hand in it declaring (see `deal_declarer`).
+    #[default]
+    Random,
+    /// The contract `ThresholdBidder::default()` bidders reach at all four
+    /// seats, South speaking first. Deals they all pass are redrawn.
+    Threshold,
+}
+
+impl ContractSource {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "random" => Ok(ContractSource::Random),
+            "threshold" => Ok(ContractSource::Threshold),
+            _ => Err(format!(
+                "Unknown contract source '{}' (expected 'random' or 'threshold')",
+                name
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContractSource::Random => "random",
+            ContractSource::Threshold => "threshold",
+        }
+    }
+
+    /// (trump, declarer, contract) of `hands`, None when the auction found no
+    /// contract.
+    fn contract<R: Rng>(&self, hands: &[u32; 4], rng: &mut R) -> Option<(u8, u8, Option<Bid>)> {
+        match self {
+            ContractSource::Random => {
+                let trump = rng.gen_range(0..4) as u8;
+                Some((trump, deal_declarer(hands, trump), None))
+            }
+            ContractSource::Threshold => {
+                // South leads, so the dealer is East.
+                let mut state = BiddingState::new(3);
+                for call in ThresholdBidder::default().auction(3, hands) {
+                    call.apply(&mut state).expect("threshold calls are legal");
+                }
+                let contract = state.contract?;
+                Some((contract.trump, state.contract_owner?, Some(contract)))
+            }
+        }
+    }
+}
+
+/// Random positions from random deals, as structured samples.
+pub fn generate_gameplay_batch(batch_size: usize, seed: Option<u64>) -> GameplayBatch {
+    generate_gameplay_batch_for_side(batch_size, seed, None, ContractSource::Random)
+}
+
+/// Like `generate_gameplay_batch`, with contracts from `contracts` and keeping
+/// only positions whose player to move is on `side`. Rejected positions are
+/// redrawn before anything is solved; the player to move is on either side
+/// about half the time.
+pub fn generate_gameplay_batch_for_side(
+    batch_size: usize,
+    seed: Option<u64>,
+    side: Option<SideFilter>,
+    contracts: ContractSource,
+) -> GameplayBatch {
+    let config = StageConfig::default();
+    let samples = (0..batch_size)
         .into_par_iter()
         .progress_count(batch_size as u64)
-        .map(|_| generate_single_raw_state())
+        .map(|i| {
+            let mut rng = sample_rng(seed, i as u64);
+            loop {
+                let hands = generate_random_hands(&mut rng);
+                let Some(sample) = simulate_random_position(hands, &config, contracts, &mut rng)
+                else {
+                    continue;
+                };
+                if side.is_none_or(|s| s.accepts(sample.player, sample.declarer)) {
+                    return sample.into();
+                }
+            }
+        })
         .collect();
+    GameplayBatch { samples }
+}
 
-    let mut hands_data = Vec::with_capacity(batch_size * 4);
-    let mut boards_data = Vec::with_capacity(batch_size);
-    let mut history_data = Vec::with_capacity(batch_size);
-    let mut trumps_data = Vec::with_capacity(batch_size);
-    let mut tricks_won_data = Vec::with_capacity(batch_size);
-    let mut player_data = Vec::with_capacity(batch_size);
+/// Seat whose dealt hand is the strongest in `trump` (`contract_strength`), the
+/// lowest seat on ties: the one most likely to have bid the contract.
+pub fn deal_declarer(hands: &[u32; 4], trump: u8) -> u8 {
+    (0..4u8)
+        .rev()
+        .max_by_key(|&seat| contract_strength(hands[seat as usize], trump))
+        .expect("four seats")
+}
 
-    for s in states {
-        hands_data.extend_from_slice(&s.hands);
-        boards_data.push(s.board);
-        history_data.push(s.history);
-        trumps_data.push(s.trump);
-        tricks_won_data.push(s.tricks_won.to_vec());
-        player_data.push(s.player);
+/// Generates `batch_size` positions where South (seat 0) was dealt `south_hand`
+/// and the other 24 cards, the trump and the play so far are random.
+pub fn generate_positions_for_hand(
+    south_hand: u32,
+    batch_size: usize,
+    config: &StageConfig,
+    seed: Option<u64>,
+) -> Result<RawGameplayBatch, String> {
+    generate_positions_batch(south_hand, batch_size, config, seed).map(|b| b.columns())
+}
+
+/// Same as `generate_positions_for_hand`, as structured samples.
+pub fn generate_positions_batch(
+    south_hand: u32,
+    batch_size: usize,
+    config: &StageConfig,
+    seed: Option<u64>,
+) -> Result<GameplayBatch, String> {
+    if south_hand.count_ones() != 8 {
+        return Err(format!(
+            "South hand must have 8 cards, got {}",
+            south_hand.count_ones()
+        ));
     }
 
-    (
-        hands_data,
-        boards_data,
-        history_data,
-        trumps_data,
-        tricks_won_data,
-        player_data,
-    )
-}
+    let samples = (0..batch_size)
+        .into_par_iter()
+        .map(|i| {
+            let mut rng = sample_rng(seed, i as u64);
+            let hands = generate_hands_with_south(south_hand, &mut rng);
+            simulate_random_position(hands, config, ContractSource::Random, &mut rng)
+                .expect("random contracts always exist")
+                .into()
+        })
+        .collect();
 
-fn generate_single_raw_state() -> RawGameplayState {
-    let mut rng = rand::thread_rng();
+    Ok(GameplayBatch { samples })
+}
 
+/// Plays random legal cards from a fresh deal up to a stage drawn from `config`,
+/// in the contract `contracts` gives it (None when it gives none).
+fn simulate_random_position<R: Rng>(
+    hands: [u32; 4],
+    config: &StageConfig,
+    contracts: ContractSource,
+    rng: &mut R,
+) -> Option<RawGameplayState> {
     // 1. Temporal Bias
-    // 50% Endgame (Played 5-7 tricks -> 3-1 remaining)
-    // 30% Midgame (Played 3-4 tricks -> 5-4 remaining)
-    // 20% Opening (Played 0-2 tricks -> 8-6 remaining) - SLOW PART
-    let r = rng.gen_range(0..100);
-    let target_trick = if r < 50 {
-        rng.gen_range(5..8)
-    } else if r < 80 {
-        rng.gen_range(3..5)
-    } else {
-        rng.gen_range(0..3)
-    };
+    let target_trick = config.sample_target_trick(rng);
 
-    let hands = generate_random_hands();
-    let trump = rng.gen_range(0..4) as u8;
+    let (trump, declarer, contract) = contracts.contract(&hands, rng)?;
 
     let mut state = PlayingState::new(trump);
     state.hands = hands;
     let mut history_mask = 0u32;
+    let mut plays = Vec::with_capacity(32);
 
     // Simulate to target trick
     for _ in 0..target_trick {
@@ -105,6 +659,11 @@ fn generate_single_raw_state() -> RawGameplayState {
                 break;
             }
             let m = moves[rng.gen_range(0..moves.len())];
+            plays.push(PlayRecord {
+                trick: state.tricks_won[0] + state.tricks_won[1],
+                seat: state.current_player,
+                card: m,
+            });
             state.play_card(m);
             history_mask |= 1 << m;
         }
@@ -124,180 +683,1936 @@ fn generate_single_raw_state() -> RawGameplayState {
             break;
         }
         let m = moves[rng.gen_range(0..moves.len())];
+        plays.push(PlayRecord {
+            trick: state.tricks_won[0] + state.tricks_won[1],
+            seat: state.current_player,
+            card: m,
+        });
         state.play_card(m);
         history_mask |= 1 << m;
     }
 
-    // Capture board snapshot
-    let mut board = Vec::new();
-    for i in 0..4 {
-        if state.current_trick[i] != 0xFF {
-            board.push(state.current_trick[i]);
-        }
-    }
+    // Capture board snapshot, in play order (`current_trick` is seat-indexed)
+    let board: Vec<u8> = plays[plays.len() - state.trick_size as usize..]
+        .iter()
+        .map(|p| p.card)
+        .collect();
 
-    RawGameplayState {
+    Some(RawGameplayState {
         hands: state.hands,
         board,
         history: history_mask,
         trump: state.trump,
         tricks_won: state.tricks_won,
         player: state.current_player,
+        plays,
+        declarer,
+        contract,
+    })
+}
+
+fn has_hidden_cards(state: &PlayingState) -> bool {
+    let me = state.current_player as usize;
+    (0..4).any(|p| p != me && state.hands[p] != 0)
+}
+
+/// What the auction revealed about a seat: it bid `bid`. `played` holds the cards
+/// that seat has already played, so the check applies to its original hand.
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BidConstraint {
+    #[pyo3(get)]
+    pub seat: u8,
+    #[pyo3(get)]
+    pub bid: Bid,
+    #[pyo3(get)]
+    pub played: u32,
+}
+
+#[pymethods]
+impl BidConstraint {
+    #[new]
+    #[pyo3(signature = (seat, bid, played=0))]
+    pub fn new(seat: u8, bid: Bid, played: u32) -> Self {
+        Self { seat, bid, played }
+    }
+
+    /// Whether a seat holding `hand` (its remaining cards) could plausibly have made
+    /// the bid: two trumps or more for a suit contract, and enough heuristic strength
+    /// (80 needs little, 120 hearts roughly the Jack and an Ace).
+    pub fn is_consistent(&self, hand: u32) -> bool {
+        let full = hand | self.played;
+        let trump = self.bid.trump;
+        if trump < 4 && (full & (0xFF << (trump * 8))).count_ones() < 2 {
+            return false;
+        }
+        let required = ((self.bid.value as i32 - 70) / 2).min(45);
+        contract_strength(full, trump) >= required
     }
 }
 
-pub fn solve_gameplay_batch(
+/// Re-deals tried per world before giving up on the bid constraints.
+const MAX_REDEALS: usize = 64;
+
+/// Like `determinize`, but keeping the suits each seat showed it lacks (`voids`, as
+/// from `voids_from_plays`) and re-dealing worlds in which a constrained seat could
+/// not have made its bid. After `MAX_REDEALS` tries the last world is kept, so
+/// unsatisfiable bid constraints only cost time.
+pub fn determinize_consistent<R: Rng>(
+    state: &PlayingState,
+    voids: [u8; 4],
+    constraints: &[BidConstraint],
+    rng: &mut R,
+) -> PlayingState {
+    let me = state.current_player;
+    let worlds = WorldConstraints::new(state).with_voids(voids);
+    let determinize = |rng: &mut R| {
+        worlds
+            .sample(state, rng)
+            .expect("the voids of legal plays fit the deal")
+    };
+    let mut world = determinize(rng);
+    for _ in 1..MAX_REDEALS {
+        let consistent = constraints
+            .iter()
+            .all(|c| c.seat == me || c.is_consistent(world.hands[c.seat as usize]));
+        if consistent {
+            break;
+        }
+        world = determinize(rng);
+    }
+    world
+}
+
+/// Most voted card; ties go to the lowest card index.
+fn majority_vote(votes: &[u32; 32]) -> u8 {
+    let mut best = 0;
+    for c in 1..32 {
+        if votes[c] > votes[best] {
+            best = c;
+        }
+    }
+    best as u8
+}
+
+/// Share of the votes of the leader and the runner-up that the leader takes when
+/// it is decided, the alternative hypothesis of `AdaptivePimc`.
+const DECIDED_LEADER_SHARE: f64 = 2.0 / 3.0;
+
+/// Adaptive PIMC: worlds are solved until the plurality vote is decided rather than
+/// a fixed number of them, the iteration count of the batch being the most a
+/// position gets. Clear decisions stop after a few worlds, close ones use the budget.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptivePimc {
+    /// Worlds solved before the vote is first tested.
+    pub min_iterations: usize,
+    /// Largest chance of stopping on a leader that does not beat the runner-up.
+    pub error_rate: f64,
+}
+
+impl AdaptivePimc {
+    pub fn new(min_iterations: usize, error_rate: f64) -> Result<Self, String> {
+        if !(error_rate > 0.0 && error_rate < 0.5) {
+            return Err("error_rate must be in (0, 0.5)".to_string());
+        }
+        Ok(AdaptivePimc {
+            min_iterations: min_iterations.max(1),
+            error_rate,
+        })
+    }
+
+    /// Wald's sequential probability ratio test between the leader and the runner-up
+    /// of `votes`: an even split of their votes against the leader taking
+    /// `DECIDED_LEADER_SHARE` of them. Only the latter stops the sampling.
+    fn is_decided(&self, votes: &[u32; 32]) -> bool {
+        if votes.iter().sum::<u32>() < self.min_iterations as u32 {
+            return false;
+        }
+        let leader = majority_vote(votes) as usize;
+        let runner_up = (0..32)
+            .filter(|&c| c != leader)
+            .map(|c| votes[c])
+            .max()
+            .unwrap_or(0);
+        let log_ratio = votes[leader] as f64 * (2.0 * DECIDED_LEADER_SHARE).ln()
+            + runner_up as f64 * (2.0 * (1.0 - DECIDED_LEADER_SHARE)).ln();
+        log_ratio >= ((1.0 - self.error_rate) / self.error_rate).ln()
+    }
+}
+
+/// How the sampled worlds of a PIMC decision are aggregated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PimcVoting {
+    /// Each world votes for its best card; the most voted card wins.
+    Plurality,
+    /// Every legal card is solved in each world; the best mean value wins.
+    ExpectedValue,
+}
+
+impl PimcVoting {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "plurality" | "vote" => Ok(PimcVoting::Plurality),
+            "expected_value" | "ev" => Ok(PimcVoting::ExpectedValue),
+            _ => Err(format!(
+                "Unknown PIMC voting '{}' (expected 'plurality' or 'ev')",
+                name
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PimcVoting::Plurality => "plurality",
+            PimcVoting::ExpectedValue => "ev",
+        }
+    }
+}
+
+/// What the score label of a PIMC sample measures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScoreLabel {
+    /// Double-dummy value of the true deal. Exact, but it uses the hidden cards the
+    /// player to move cannot see.
+    DoubleDummy,
+    /// Mean double-dummy value over the sampled worlds: only what the player knows.
+    Expected,
+}
+
+impl ScoreLabel {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "double_dummy" | "dd" => Ok(ScoreLabel::DoubleDummy),
+            "expected_value" | "ev" => Ok(ScoreLabel::Expected),
+            _ => Err(format!(
+                "Unknown score label '{}' (expected 'double_dummy' or 'ev')",
+                name
+            )),
+        }
+    }
+
+    /// Canonical name, as stored in dataset metadata.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ScoreLabel::DoubleDummy => "double_dummy",
+            ScoreLabel::Expected => "ev",
+        }
+    }
+}
+
+/// How sure a PIMC decision is, from the spread of its sampled worlds.
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PimcConfidence {
+    /// Share of the worlds in which the chosen card was the best move.
+    #[pyo3(get)]
+    pub agreement: f32,
+    /// Shannon entropy (bits) of the vote distribution; 0 when all worlds agree.
+    #[pyo3(get)]
+    pub vote_entropy: f32,
+    /// Variance over the worlds of the value of the chosen card.
+    #[pyo3(get)]
+    pub value_variance: f32,
+}
+
+impl PimcConfidence {
+    /// Confidence of a decision taken with no hidden information.
+    pub fn certain() -> Self {
+        Self {
+            agreement: 1.0,
+            vote_entropy: 0.0,
+            value_variance: 0.0,
+        }
+    }
+}
+
+/// Outcome of a PIMC decision.
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct PimcDecision {
+    #[pyo3(get)]
+    pub best_card: u8,
+    /// Mean over the sampled worlds of the double-dummy value for `team`.
+    #[pyo3(get)]
+    pub expected_score: f32,
+    /// Number of worlds in which each card was the best move.
+    #[pyo3(get)]
+    pub votes: [u32; 32],
+    /// Mean value for `team` of each legal card (`ExpectedValue` voting only, 0 otherwise).
+    #[pyo3(get)]
+    pub card_values: [f32; 32],
+    #[pyo3(get)]
+    pub confidence: PimcConfidence,
+}
+
+#[pymethods]
+impl PimcDecision {
+    pub fn __repr__(&self) -> String {
+        format!(
+            "PimcDecision(best_card={}, expected_score={:.1}, agreement={:.2}, vote_entropy={:.2}, value_variance={:.1})",
+            self.best_card,
+            self.expected_score,
+            self.confidence.agreement,
+            self.confidence.vote_entropy,
+            self.confidence.value_variance
+        )
+    }
+}
+
+/// Best move of one sampled world and, for `ExpectedValue`, the value of every root move.
+struct WorldOutcome {
+    card: u8,
+    score: Score,
+    moves: Vec<(u8, Score)>,
+}
+
+// `maximize`: whether the player to move belongs to `team`.
+fn solve_world(
+    world: &PlayingState,
+    team: usize,
+    objective: Objective,
+    voting: PimcVoting,
+    maximize: bool,
+    tt_log2: Option<u8>,
+) -> WorldOutcome {
+    match voting {
+        PimcVoting::Plurality => {
+            // PIMC Playout: Use FULL depth (32) for accurate Capot/Der scoring
+            let (score, card) = solve_with_objective(world, team, objective, Some(32), tt_log2);
+            WorldOutcome {
+                card,
+                score,
+                moves: Vec::new(),
+            }
+        }
+        PimcVoting::ExpectedValue => {
+            let moves = solve_root_moves_with_objective(world, team, objective, Some(32), tt_log2);
+            let best = if maximize {
+                moves
+                    .iter()
+                    .max_by_key(|&&(c, v)| (v, std::cmp::Reverse(c)))
+            } else {
+                moves.iter().min_by_key(|&&(c, v)| (v, c))
+            };
+            let &(card, score) = best.expect("no legal move");
+            WorldOutcome { card, score, moves }
+        }
+    }
+}
+
+/// Running sums over the solved worlds of a PIMC decision.
+#[derive(Default)]
+struct PimcTally {
+    worlds: u32,
+    votes: [u32; 32],
+    score_sum: i64,
+    score_sq_sum: i64,
+    value_sums: [i64; 32],
+    value_sq_sums: [i64; 32],
+}
+
+impl PimcTally {
+    fn add(&mut self, outcome: &WorldOutcome) {
+        let score = outcome.score as i64;
+        self.worlds += 1;
+        self.votes[outcome.card as usize] += 1;
+        self.score_sum += score;
+        self.score_sq_sum += score * score;
+        for &(c, v) in &outcome.moves {
+            self.value_sums[c as usize] += v as i64;
+            self.value_sq_sums[c as usize] += (v as i64) * (v as i64);
+        }
+    }
+
+    fn decide(&self, legal: u32, voting: PimcVoting, maximize: bool) -> PimcDecision {
+        let n = self.worlds.max(1) as f32;
+        let variance = |sum: i64, sq_sum: i64| {
+            let mean = sum as f32 / n;
+            (sq_sum as f32 / n - mean * mean).max(0.0)
+        };
+
+        let (best_card, expected_score, value_variance, card_values) = match voting {
+            PimcVoting::Plurality => {
+                let best = majority_vote(&self.votes);
+                let mean = self.score_sum as f32 / n;
+                let var = variance(self.score_sum, self.score_sq_sum);
+                (best, mean, var, [0.0; 32])
+            }
+            PimcVoting::ExpectedValue => {
+                let card_values = self.value_sums.map(|v| v as f32 / n);
+                // Ties go to the lowest card index, as with plurality voting.
+                let mut best = legal.trailing_zeros() as u8;
+                for c in 0..32u8 {
+                    let (v, b) = (card_values[c as usize], card_values[best as usize]);
+                    if legal & (1 << c) != 0 && (if maximize { v > b } else { v < b }) {
+                        best = c;
+                    }
+                }
+                let b = best as usize;
+                let var = variance(self.value_sums[b], self.value_sq_sums[b]);
+                (best, card_values[b], var, card_values)
+            }
+        };
+
+        let vote_entropy = self
+            .votes
+            .iter()
+            .filter(|&&v| v > 0)
+            .map(|&v| {
+                let p = v as f32 / n;
+                -p * p.log2()
+            })
+            .sum::<f32>()
+            .max(0.0);
+
+        PimcDecision {
+            best_card,
+            expected_score,
+            votes: self.votes,
+            card_values,
+            confidence: PimcConfidence {
+                agreement: self.votes[best_card as usize] as f32 / n,
+                vote_entropy,
+                value_variance,
+            },
+        }
+    }
+}
+
+/// PIMC for a single decision, with the determinizations spread over the rayon
+/// pool and voting into a shared accumulator. Worlds keep `voids` (none when all
+/// zero) and are re-dealt until they are consistent with `constraints` (may be
+/// empty). World `i` is drawn from `sample_rng(seed, i)` and seeded worlds are all
+/// solved from a cold TT, so a seeded decision does not depend on the thread count.
+#[allow(clippy::too_many_arguments)]
+pub fn solve_pimc_parallel(
+    state: &PlayingState,
+    team: usize,
+    iterations: usize,
+    voting: PimcVoting,
+    constraints: &[BidConstraint],
+    voids: [u8; 4],
+    tt_log2: Option<u8>,
+    seed: Option<u64>,
+) -> PimcDecision {
+    let iterations = if has_hidden_cards(state) {
+        iterations.max(1)
+    } else {
+        1
+    };
+    // The player to move picks the card; `team` may be their opponents.
+    let maximize = (state.current_player % 2) as usize == team;
+    let tally = Mutex::new(PimcTally::default());
+    // Worlds solved by the same thread may share its TT, unless seeded: which
+    // worlds share a thread is up to the scheduler.
+    let scope = pimc_tt_scope(seed);
+
+    (0..iterations).into_par_iter().for_each(|i| {
+        let world = if iterations > 1 {
+            determinize_consistent(state, voids, constraints, &mut sample_rng(seed, i as u64))
+        } else {
+            *state
+        };
+        let outcome = with_tt_scope(scope, || {
+            solve_world(&world, team, Objective::Points, voting, maximize, tt_log2)
+        });
+        tally.lock().unwrap().add(&outcome);
+    });
+
+    tally
+        .into_inner()
+        .unwrap()
+        .decide(state.get_legal_moves(), voting, maximize)
+}
+
+/// Rebuilds the solver state of a dataset row. `player` is the seat to move and
+/// `board` the cards of the current trick in play order.
+pub fn reconstruct_state(
+    hands: [u32; 4],
+    board: &[u8],
+    trump: u8,
+    tricks_won: [u8; 2],
+    player: u8,
+) -> PlayingState {
+    let mut state = PlayingState::new(trump);
+    state.hands = hands;
+    state.current_player = player;
+    state.tricks_won = tricks_won;
+
+    // Current player is the one to move NEXT, so the starter is (current - len) % 4.
+    let trick_len = board.len() as u8;
+    state.trick_size = trick_len;
+    state.trick_starter = (player as i8 - trick_len as i8).rem_euclid(4) as u8;
+
+    for (idx, &card) in board.iter().enumerate() {
+        let seat = (state.trick_starter as usize + idx) % 4;
+        state.current_trick[seat] = card;
+    }
+    state.recount_points_in_play();
+    state
+}
+
+/// Checks that a position can arise in a deal, as `reconstruct_state` trusts its
+/// inputs: the trick starter it infers (the seat to move less the board length) must
+/// fit the hand sizes, and the board must be legal for the hands left.
+pub fn check_reconstruction(
+    hands: [u32; 4],
+    board: &[u8],
+    history: u32,
+    trump: u8,
+    tricks_won: [u8; 2],
+    player: u8,
+) -> Result<(), InvalidReason> {
+    let in_hands = hands.iter().fold(0u32, |m, &h| m | h);
+    let on_table = board
+        .iter()
+        .fold(0u32, |m, &c| m | 1u32.wrapping_shl(c as u32));
+    if board.len() > 3
+        || board.iter().any(|&c| c >= 32)
+        || on_table.count_ones() as usize != board.len()
+        || on_table & in_hands != 0
+    {
+        return Err(InvalidReason::InconsistentBoard);
+    }
+
+    let tricks = (tricks_won[0] + tricks_won[1]) as usize;
+    if tricks > 7
+        || history & on_table != on_table
+        || history & in_hands != 0
+        || history.count_ones() as usize != 4 * tricks + board.len()
+    {
+        return Err(InvalidReason::HistoryMismatch);
+    }
+
+    let starter = (player as usize + 4 - board.len()) % 4;
+    for (seat, hand) in hands.iter().enumerate() {
+        let played = ((seat + 4 - starter) % 4 < board.len()) as usize;
+        if hand.count_ones() as usize != 8 - tricks - played {
+            return Err(InvalidReason::HandSizes);
+        }
+    }
+
+    // Each board card must have been legal with its seat's cards at the time: the
+    // hand it still holds plus the card itself.
+    let mut state = PlayingState::new(trump);
+    state.hands = hands;
+    state.current_player = starter as u8;
+    state.trick_starter = starter as u8;
+    for &card in board {
+        let seat = state.current_player as usize;
+        state.hands[seat] |= 1 << card;
+        if state.get_legal_moves() & (1 << card) == 0 {
+            return Err(InvalidReason::IllegalBoard);
+        }
+        state.play_card(card);
+    }
+    Ok(())
+}
+
+/// Rebuilds a position by replaying its ordered `plays` from the deal, so that the
+/// points already won and the belote already announced are those of the game,
+/// which `reconstruct_state` cannot know. `hands` are the cards still in hand; each
+/// seat's dealt hand adds back the cards it played. Fails if a card shows up twice,
+/// a play is out of turn or illegal, or the plays do not end with `player` to move.
+pub fn replay_state(
+    hands: [u32; 4],
+    plays: &[PlayRecord],
+    trump: u8,
+    player: u8,
+) -> Result<PlayingState, String> {
+    let mut dealt = hands;
+    for p in plays {
+        if dealt.iter().any(|&h| h & (1 << p.card) != 0) {
+            return Err(format!("Card {} is played twice or still in hand", p.card));
+        }
+        dealt[p.seat as usize % 4] |= 1 << p.card;
+    }
+
+    let mut state = PlayingState::new(trump);
+    state.hands = dealt;
+    let leader = plays.first().map_or(player, |p| p.seat);
+    state.current_player = leader;
+    state.trick_starter = leader;
+    for (i, p) in plays.iter().enumerate() {
+        if p.seat != state.current_player || p.trick as usize != i / 4 {
+            return Err(format!(
+                "Play {} (seat {}, trick {}) is out of turn",
+                i, p.seat, p.trick
+            ));
+        }
+        // The voids PIMC infers from the plays hold only if the seats followed suit.
+        if state.get_legal_moves() & (1 << p.card) == 0 {
+            return Err(format!("Play {} (card {}) is illegal", i, p.card));
+        }
+        state.play_card(p.card);
+    }
+    if state.current_player != player {
+        return Err(format!(
+            "Plays leave seat {} to move, not {}",
+            state.current_player, player
+        ));
+    }
+    Ok(state)
+}
+
+/// How the samples of a `GameplaySolveRequest` are solved and labelled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameplaySolveOptions {
+    pub tt_log2: Option<u8>,
+    /// Whose final points the scores are.
+    pub perspective: Perspective,
+    /// Declaring seat of each sample, required by `Perspective::Declarer`.
+    pub declarers: Option<Vec<u8>>,
+    /// Makes the PIMC worlds reproducible (see `common::sample_rng`).
+    pub seed: Option<u64>,
+    pub score_label: ScoreLabel,
+    /// What the scores count.
+    pub objective: Objective,
+    /// Solves every root move and keeps in `optimal_cards` all the cards within that
+    /// many points of the best one.
+    pub optimal_epsilon: Option<f32>,
+    /// Longest solve of a sample; slower samples are invalid (`InvalidReason::Timeout`).
+    pub time_limit: Option<Duration>,
+    /// Labels forced moves with their only card and no score (NaN) instead of solving
+    /// them.
+    pub skip_forced: bool,
+    /// Stops the PIMC worlds of a position once its vote is decided.
+    pub adaptive: Option<AdaptivePimc>,
+    /// Positions with at most that many worlds (see `WorldConstraints::count`) solve each of
+    /// them once instead of sampling.
+    pub max_exact_worlds: Option<u64>,
+}
+
+impl Default for GameplaySolveOptions {
+    fn default() -> Self {
+        GameplaySolveOptions {
+            tt_log2: None,
+            perspective: Perspective::Absolute,
+            declarers: None,
+            seed: None,
+            score_label: ScoreLabel::DoubleDummy,
+            objective: Objective::Points,
+            optimal_epsilon: None,
+            time_limit: None,
+            skip_forced: false,
+            adaptive: None,
+            max_exact_worlds: None,
+        }
+    }
+}
+
+/// Version of `GameplaySolveRequest::fingerprint_inputs`.
+const SOLVE_INPUTS_VERSION: u32 = 1;
+
+/// Positions to label with `solve_gameplay_batch`, and how. The columns can only
+/// be set by `new`, which checks they describe the same samples.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GameplaySolveRequest {
     flattened_hands: Vec<u32>,
     boards: Vec<Vec<u8>>,
     history: Vec<u32>,
     trumps: Vec<u8>,
     tricks_won: Vec<Vec<u8>>,
     players: Vec<u8>,
-    pimc_iterations: usize,
-    tt_log2: Option<u8>,
-) -> (Vec<u8>, Vec<i16>, Vec<bool>) {
-    // flattened_hands is size N*4.
-    let num_samples = boards.len();
-
-    let results: Vec<SolvedGameplaySample> = (0..num_samples)
-        .into_par_iter()
-        .map(|i| {
-            // ... Reconstruct State ...
-            let mut state = PlayingState::new(trumps[i]);
+    /// Ordered plays of each sample, packed as in `decode_play_history`, when known.
+    plays: Option<Vec<Vec<u16>>>,
+    /// Worlds sampled per position; 0 solves the true deal double dummy.
+    pub pimc_iterations: usize,
+    pub options: GameplaySolveOptions,
+}
 
-            // Reconstruct hands
-            for h in 0..4 {
-                state.hands[h] = flattened_hands[i * 4 + h];
-            }
+impl GameplaySolveRequest {
+    /// Request for the positions of a raw batch (as the generators and the dataset
+    /// columns hold them), with the default options.
+    pub fn new(positions: RawGameplayBatch, pimc_iterations: usize) -> Result<Self, String> {
+        let (flattened_hands, boards, history, trumps, tricks_won, players) = positions;
+        let n = boards.len();
+        let lengths = [
+            ("hands", flattened_hands.len(), n * 4),
+            ("history", history.len(), n),
+            ("trumps", trumps.len(), n),
+            ("tricks_won", tricks_won.len(), n),
+            ("players", players.len(), n),
+        ];
+        if let Some((name, len, expected)) = lengths.iter().find(|(_, len, e)| len != e) {
+            return Err(format!(
+                "{} has {} entries for {} boards, expected {}",
+                name, len, n, expected
+            ));
+        }
+        if let Some(i) = tricks_won.iter().position(|t| t.len() != 2) {
+            return Err(format!("tricks_won of sample {} is not a pair", i));
+        }
+        if let Some(i) = trumps.iter().position(|&t| t >= 6) {
+            return Err(format!("Invalid trump {} in sample {}", trumps[i], i));
+        }
+        if let Some(i) = players.iter().position(|&p| p >= 4) {
+            return Err(format!("Invalid player {} in sample {}", players[i], i));
+        }
+        Ok(GameplaySolveRequest {
+            flattened_hands,
+            boards,
+            history,
+            trumps,
+            tricks_won,
+            players,
+            plays: None,
+            pimc_iterations,
+            options: GameplaySolveOptions::default(),
+        })
+    }
 
-            state.current_player = players[i];
-            state.tricks_won[0] = tricks_won[i][0];
-            state.tricks_won[1] = tricks_won[i][1];
-
-            // Reconstruct current trick
-            let trick_len = boards[i].len() as u8;
-            state.trick_size = trick_len;
-            if trick_len > 0 {
-                // Current player is the one to move NEXT.
-                // So the starter is (current - len) % 4.
-                state.trick_starter =
-                    (state.current_player as i8 - trick_len as i8).rem_euclid(4) as u8;
-            } else {
-                state.trick_starter = state.current_player;
-            }
+    pub fn len(&self) -> usize {
+        self.boards.len()
+    }
 
-            for (idx, &card) in boards[i].iter().enumerate() {
-                // ...
-                let start_player = state.trick_starter as usize;
-                let seat = (start_player + idx) % 4;
-                state.current_trick[seat] = card;
-            }
+    pub fn is_empty(&self) -> bool {
+        self.boards.is_empty()
+    }
 
-            if state.is_terminal() || state.get_legal_moves() == 0 {
-                return SolvedGameplaySample {
-                    best_card: 0,
-                    best_score: 0,
-                    valid: false,
-                };
+    /// Solves the samples from their ordered plays (see `replay_state`), so that the
+    /// scores count the points won and the belote announced before the position.
+    /// The plays must lead to the board, history and tricks won of each sample.
+    pub fn with_plays(mut self, plays: Vec<Vec<u16>>) -> Result<Self, String> {
+        if plays.len() != self.len() {
+            return Err(format!(
+                "plays has {} entries for {} boards",
+                plays.len(),
+                self.len()
+            ));
+        }
+        for (i, encoded) in plays.iter().enumerate() {
+            let records = decode_history(encoded);
+            let state = replay_state(self.hands(i), &records, self.trumps[i], self.players[i])
+                .map_err(|e| format!("Sample {}: {}", i, e))?;
+            let board = &records[records.len() - state.trick_size as usize..];
+            if history_mask(&records) != self.history[i]
+                || state.tricks_won[..] != self.tricks_won[i][..]
+                || !board
+                    .iter()
+                    .map(|p| p.card)
+                    .eq(self.boards[i].iter().copied())
+            {
+                return Err(format!(
+                    "Sample {}: plays do not match the board, history and tricks won",
+                    i
+                ));
             }
+        }
+        self.plays = Some(plays);
+        Ok(self)
+    }
 
-            // PIMC Logic
-            if pimc_iterations > 1 {
-                let mut rng = rand::thread_rng();
-                let mut votes = [0; 32];
-
-                // Identify hidden cards (belonging to others)
-                let mut hidden_cards = Vec::new();
-                let my_player = state.current_player as usize;
-
-                let mut hand_sizes = [0; 4];
-
-                for p in 0..4 {
-                    hand_sizes[p] = state.hands[p].count_ones(); // u32::count_ones
-                    if p != my_player {
-                        let mut h = state.hands[p];
-                        while h != 0 {
-                            let c = h.trailing_zeros();
-                            hidden_cards.push(c);
-                            h &= !(1 << c);
-                        }
-                    }
-                }
+    /// Whether sample `i` can arise in a deal, see `check_reconstruction`.
+    pub fn check(&self, i: usize) -> Result<(), InvalidReason> {
+        check_reconstruction(
+            self.hands(i),
+            &self.boards[i],
+            self.history[i],
+            self.trumps[i],
+            [self.tricks_won[i][0], self.tricks_won[i][1]],
+            self.players[i],
+        )
+    }
 
-                if hidden_cards.is_empty() {
-                    // No hidden info (e.g. 2 players left or all revealed?), just solve EXACTLY
-                    let (best_score, best_card) = solve(&state, false, Some(32), tt_log2);
-                    return SolvedGameplaySample {
-                        best_card,
-                        best_score,
-                        valid: true,
-                    };
-                }
+    fn hands(&self, i: usize) -> [u32; 4] {
+        self.flattened_hands[i * 4..i * 4 + 4].try_into().unwrap()
+    }
 
-                for _ in 0..pimc_iterations {
-                    // Shuffle
-                    hidden_cards.shuffle(&mut rng);
-
-                    // Re-deal consistent with counts
-                    let mut temp_state = state.clone();
-                    let mut idx = 0;
-                    for p in 0..4 {
-                        if p != my_player {
-                            let mut new_hand = 0;
-                            let count = hand_sizes[p];
-                            for _ in 0..count {
-                                new_hand |= 1 << hidden_cards[idx];
-                                idx += 1;
-                            }
-                            temp_state.hands[p] = new_hand;
-                        }
-                    }
+    /// Solver state of sample `i`.
+    pub fn state(&self, i: usize) -> PlayingState {
+        let hands = self.hands(i);
+        if let Some(plays) = &self.plays {
+            return replay_state(
+                hands,
+                &decode_history(&plays[i]),
+                self.trumps[i],
+                self.players[i],
+            )
+            .expect("plays checked by with_plays");
+        }
+        reconstruct_state(
+            hands,
+            &self.boards[i],
+            self.trumps[i],
+            [self.tricks_won[i][0], self.tricks_won[i][1]],
+            self.players[i],
+        )
+    }
 
-                    // PIMC Playout: Use FULL depth (32) for accurate Capot/Der scoring
-                    let (_, move_) = solve(&temp_state, false, Some(32), tt_log2);
-                    votes[move_ as usize] += 1;
-                }
+    /// Everything the labels depend on, fingerprinted into the checkpoints. Every field
+    /// is named so that none can be left out; `SOLVE_INPUTS_VERSION` changes with the
+    /// meaning of one.
+    fn fingerprint_inputs(&self) -> Value {
+        let GameplaySolveRequest {
+            flattened_hands,
+            boards,
+            history,
+            trumps,
+            tricks_won,
+            players,
+            plays,
+            pimc_iterations,
+            options,
+        } = self;
+        let GameplaySolveOptions {
+            // The table size changes the speed, not the labels.
+            tt_log2: _,
+            perspective,
+            declarers,
+            seed,
+            score_label,
+            objective,
+            optimal_epsilon,
+            time_limit,
+            skip_forced,
+            adaptive,
+            max_exact_worlds,
+        } = options;
+        json!({
+            "version": SOLVE_INPUTS_VERSION,
+            "hands": flattened_hands,
+            "boards": boards,
+            "history": history,
+            "trumps": trumps,
+            "tricks_won": tricks_won,
+            "players": players,
+            "plays": plays,
+            "pimc_iterations": pimc_iterations,
+            "perspective": perspective.name(),
+            "declarers": declarers,
+            "seed": seed,
+            "score_label": score_label.name(),
+            "objective": objective.name(),
+            "optimal_epsilon": optimal_epsilon.map(f32::to_bits),
+            "time_limit_us": time_limit.map(|t| t.as_micros() as u64),
+            "skip_forced": skip_forced,
+            "adaptive": adaptive.map(|a| (a.min_iterations, a.error_rate.to_bits())),
+            "max_exact_worlds": max_exact_worlds,
+        })
+    }
 
-                // Majority Vote
-                let mut max_votes = -1;
-                let mut best_card_pimc = 0;
-                for c in 0..32 {
-                    if votes[c] > max_votes {
-                        max_votes = votes[c];
-                        best_card_pimc = c as u8;
-                    }
-                }
+    /// Suits each seat of sample `i` showed it lacks, none without plays.
+    pub fn voids(&self, i: usize) -> [u8; 4] {
+        self.plays
+            .as_ref()
+            .map_or([0; 4], |plays| voids_from_plays(&decode_history(&plays[i])))
+    }
+}
 
-                // Score: Use Perfect Information Value of the TRUE state (Target Label)
-                let (best_score, _) = solve(&state, false, Some(32), tt_log2);
+/// Scores are in the units of the request's `objective` (final points unless asked
+/// otherwise). With an `optimal_epsilon`, every root move is solved and `optimal_cards`
+/// holds all the cards whose value is within the epsilon of the best one (the mean over
+/// the PIMC worlds for PIMC samples); without it, `optimal_cards` is just the label card.
+pub fn solve_gameplay_batch(
+    request: &GameplaySolveRequest,
+    checkpoint: Option<&CheckpointConfig>,
+) -> Result<SolvedGameplayBatch, String> {
+    let num_samples = request.len();
+    let options = &request.options;
+    if options.perspective == Perspective::Declarer
+        && options.declarers.as_ref().map(|d| d.len()) != Some(num_samples)
+    {
+        return Err("Declarer perspective requires one declarer per sample".to_string());
+    }
 
-                SolvedGameplaySample {
-                    best_card: best_card_pimc,
-                    best_score,
-                    valid: true,
-                }
-            } else {
-                // Determine Double Dummy
-                let (best_score, best_card) = solve(&state, false, Some(32), tt_log2);
-                SolvedGameplaySample {
-                    best_card,
-                    best_score,
-                    valid: true,
-                }
+    let inputs = fingerprint(&request.fingerprint_inputs(), RuleSet::default());
+    let labels = LabelSpec {
+        score_label: options.score_label,
+        objective: options.objective,
+        optimal_epsilon: options.optimal_epsilon,
+    };
+    let worlds = WorldBudget {
+        iterations: request.pimc_iterations,
+        adaptive: options.adaptive,
+        max_exact_worlds: options.max_exact_worlds,
+        seed: options.seed,
+    };
+    let results: Vec<SolvedGameplaySample> =
+        run_checkpointed(num_samples, inputs, checkpoint, |i| {
+            if let Err(reason) = request.check(i) {
+                return SolvedGameplaySample::invalid(reason);
             }
-        })
-        .collect();
+            let state = request.state(i);
+            let legal = state.get_legal_moves();
+            let forced = legal.count_ones() == 1;
+            if forced && options.skip_forced {
+                return SolvedGameplaySample::unsolved_forced(legal.trailing_zeros() as u8);
+            }
+            let declarer = options.declarers.as_ref().map(|d| d[i]);
+            let team = options.perspective.team(&state, declarer).unwrap_or(0);
+
+            let nodes_before = nodes_searched();
+            let start = Instant::now();
+            let mut rng = sample_rng(options.seed, i as u64);
+            let hidden = WorldConstraints::new(&state).with_voids(request.voids(i));
+            let mut solve = || {
+                solve_sample(
+                    state,
+                    team,
+                    &hidden,
+                    &worlds,
+                    &labels,
+                    options.tt_log2,
+                    &mut rng,
+                )
+            };
+            let mut sample = match options.time_limit {
+                Some(limit) => with_deadline(start + limit, solve)
+                    .unwrap_or_else(|| SolvedGameplaySample::invalid(InvalidReason::Timeout)),
+                None => solve(),
+            };
+            sample.nodes = nodes_searched() - nodes_before;
+            sample.solve_time_us = start.elapsed().as_micros() as u64;
+            sample.forced = forced;
+            sample
+        })?;
+    crate::profiling::dump("solve_gameplay_batch");
 
     // Unzip results
     let mut best_cards = Vec::with_capacity(num_samples);
     let mut best_scores = Vec::with_capacity(num_samples);
     let mut valid_mask = Vec::with_capacity(num_samples);
+    let mut nodes = Vec::with_capacity(num_samples);
+    let mut solve_times = Vec::with_capacity(num_samples);
+    let mut agreement = Vec::with_capacity(num_samples);
+    let mut vote_entropy = Vec::with_capacity(num_samples);
+    let mut value_variance = Vec::with_capacity(num_samples);
+    let mut optimal_cards = Vec::with_capacity(num_samples);
+    let mut invalid_reasons = Vec::with_capacity(num_samples);
+    let mut forced = Vec::with_capacity(num_samples);
 
     for r in results {
         best_cards.push(r.best_card);
         best_scores.push(r.best_score);
-        valid_mask.push(r.valid);
+        valid_mask.push(r.valid());
+        invalid_reasons.push(r.invalid_reason.map_or(0, InvalidReason::code));
+        nodes.push(r.nodes);
+        solve_times.push(r.solve_time_us);
+        agreement.push(r.confidence.agreement);
+        vote_entropy.push(r.confidence.vote_entropy);
+        value_variance.push(r.confidence.value_variance);
+        optimal_cards.push(r.optimal_cards);
+        forced.push(r.forced);
+    }
+
+    Ok((
+        best_cards,
+        best_scores,
+        valid_mask,
+        nodes,
+        solve_times,
+        agreement,
+        vote_entropy,
+        value_variance,
+        optimal_cards,
+        invalid_reasons,
+        forced,
+    ))
+}
+
+/// Worlds the PIMC labels of a solved batch are drawn from.
+struct WorldBudget {
+    iterations: usize,
+    adaptive: Option<AdaptivePimc>,
+    max_exact_worlds: Option<u64>,
+    /// Seed of the batch: seeded worlds never share the TT.
+    seed: Option<u64>,
+}
+
+impl WorldBudget {
+    /// Every world of `hidden` when there are few enough of them, `None` to sample.
+    fn exact_worlds(
+        &self,
+        hidden: &WorldConstraints,
+        state: &PlayingState,
+    ) -> Option<Vec<PlayingState>> {
+        let limit = self.max_exact_worlds?;
+        (hidden.count() <= limit).then(|| hidden.enumerate(state))
+    }
+}
+
+/// What the labels of a solved batch hold.
+struct LabelSpec {
+    score_label: ScoreLabel,
+    objective: Objective,
+    optimal_epsilon: Option<f32>,
+}
+
+// Scores are the final values of `team` (see `Perspective`). PIMC worlds are those of
+// `hidden`. Under a deadline, the sample is invalid as soon as a search is aborted,
+// its values being meaningless.
+fn solve_sample<R: Rng>(
+    state: PlayingState,
+    team: usize,
+    hidden: &WorldConstraints,
+    worlds: &WorldBudget,
+    labels: &LabelSpec,
+    tt_log2: Option<u8>,
+    rng: &mut R,
+) -> SolvedGameplaySample {
+    let objective = labels.objective;
+    let optimal_epsilon = labels.optimal_epsilon;
+    if state.is_terminal() {
+        return SolvedGameplaySample::invalid(InvalidReason::Terminal);
+    }
+    if state.get_legal_moves() == 0 {
+        return SolvedGameplaySample::invalid(InvalidReason::NoLegalMoves);
+    }
+    let maximize = (state.current_player % 2) as usize == team;
+    let timeout = || SolvedGameplaySample::invalid(InvalidReason::Timeout);
+
+    // PIMC Logic
+    if worlds.iterations > 1 && has_hidden_cards(&state) {
+        // Root move values are only needed for the optimal set; the label stays the
+        // plurality vote either way.
+        let voting = match optimal_epsilon {
+            Some(_) => PimcVoting::ExpectedValue,
+            None => PimcVoting::Plurality,
+        };
+        let mut tally = PimcTally::default();
+        let scope = pimc_tt_scope(worlds.seed);
+        // Few enough worlds are all solved once, the exact average of the sampling.
+        let exact = worlds.exact_worlds(hidden, &state);
+        let iterations = exact.as_ref().map_or(worlds.iterations, Vec::len);
+        for i in 0..iterations {
+            let world = match &exact {
+                Some(all) => all[i],
+                None => hidden
+                    .sample(&state, rng)
+                    .expect("the voids of legal plays fit the deal"),
+            };
+            let values = with_tt_scope(scope, || {
+                solve_world(&world, team, objective, voting, maximize, tt_log2)
+            });
+            if search_aborted() {
+                return timeout();
+            }
+            tally.add(&values);
+            if exact.is_none() && worlds.adaptive.is_some_and(|a| a.is_decided(&tally.votes)) {
+                break;
+            }
+        }
+        let legal = state.get_legal_moves();
+        let decision = tally.decide(legal, PimcVoting::Plurality, maximize);
+
+        let best_score = match labels.score_label {
+            // Perfect Information Value of the TRUE state
+            ScoreLabel::DoubleDummy => {
+                solve_with_objective(&state, team, objective, Some(32), tt_log2).0 as f32
+            }
+            ScoreLabel::Expected => decision.expected_score,
+        };
+        if search_aborted() {
+            return timeout();
+        }
+        let optimal_cards = match optimal_epsilon {
+            Some(epsilon) => {
+                let n = tally.worlds as f32;
+                let means: Vec<(u8, f32)> = (0..32u8)
+                    .filter(|&c| legal & (1 << c) != 0)
+                    .map(|c| (c, tally.value_sums[c as usize] as f32 / n))
+                    .collect();
+                within_epsilon(&means, maximize, epsilon)
+            }
+            None => 1 << decision.best_card,
+        };
+
+        SolvedGameplaySample {
+            best_card: decision.best_card,
+            best_score,
+            invalid_reason: None,
+            nodes: 0,
+            solve_time_us: 0,
+            confidence: decision.confidence,
+            optimal_cards,
+            forced: false,
+        }
+    } else if let Some(epsilon) = optimal_epsilon {
+        let moves: Vec<(u8, f32)> =
+            solve_root_moves_with_objective(&state, team, objective, Some(32), tt_log2)
+                .into_iter()
+                .map(|(c, v)| (c, v as f32))
+                .collect();
+        if search_aborted() {
+            return timeout();
+        }
+        let optimal_cards = within_epsilon(&moves, maximize, 0.0);
+        // The lowest optimal card, as the search may pick any of them.
+        let best_card = optimal_cards.trailing_zeros() as u8;
+        let best_score = moves
+            .iter()
+            .find(|&&(c, _)| c == best_card)
+            .expect("the best card is legal")
+            .1;
+        SolvedGameplaySample {
+            best_card,
+            best_score,
+            invalid_reason: None,
+            nodes: 0,
+            solve_time_us: 0,
+            confidence: PimcConfidence::certain(),
+            optimal_cards: within_epsilon(&moves, maximize, epsilon),
+            forced: false,
+        }
+    } else {
+        // Determine Double Dummy (also when nothing is hidden, e.g. the last trick)
+        let (best_score, best_card) =
+            solve_with_objective(&state, team, objective, Some(32), tt_log2);
+        if search_aborted() {
+            return timeout();
+        }
+        SolvedGameplaySample {
+            best_card,
+            best_score: best_score as f32,
+            invalid_reason: None,
+            nodes: 0,
+            solve_time_us: 0,
+            confidence: PimcConfidence::certain(),
+            optimal_cards: 1 << best_card,
+            forced: false,
+        }
+    }
+}
+
+/// Cards of `values` within `epsilon` of the best value for the player to move.
+fn within_epsilon(values: &[(u8, f32)], maximize: bool, epsilon: f32) -> u32 {
+    let gain = |v: f32| if maximize { v } else { -v };
+    let best = values
+        .iter()
+        .map(|&(_, v)| gain(v))
+        .fold(f32::NEG_INFINITY, f32::max);
+    values
+        .iter()
+        .filter(|&&(_, v)| best - gain(v) <= epsilon)
+        .fold(0, |mask, &(c, _)| mask | 1 << c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::history::{decode_history, history_mask};
+    use crate::gameplay::playing::{cards_points, HEARTS};
+    use crate::gameplay::worlds::determinize;
+    use crate::solver::{new_tt_scope, solve_root_moves};
+
+    #[test]
+    fn test_positions_keep_south_hand() {
+        // South holds the four top Diamonds and the four top Spades.
+        let south = 0x0000_F0F0;
+        let config = StageConfig::default();
+        let (hands, boards, history, _, _, _) =
+            generate_positions_for_hand(south, 50, &config, None).unwrap();
+
+        for i in 0..boards.len() {
+            let deal = &hands[i * 4..i * 4 + 4];
+            // South only ever holds (a subset of) its original cards.
+            assert_eq!(deal[0] & !south, 0);
+            let mut remaining = 0;
+            for &h in deal {
+                assert_eq!(remaining & h, 0);
+                remaining |= h;
+            }
+            // Every card is either still held or already played.
+            assert_eq!(remaining ^ history[i], 0xFFFF_FFFF);
+        }
+    }
+
+    #[test]
+    fn test_recorded_plays_match_history_mask() {
+        let ((_, _, history, _, _, _), plays) = generate_raw_gameplay_batch_with_plays(50, None);
+        for (mask, encoded) in history.iter().zip(plays.iter()) {
+            let records = decode_history(encoded);
+            assert_eq!(history_mask(&records), *mask);
+            assert_eq!(records.len() as u32, mask.count_ones());
+        }
+    }
+
+    #[test]
+    fn test_replayed_states_match_reconstruction() {
+        let (columns, plays) = generate_raw_gameplay_batch_with_plays(50, Some(7));
+        let (hands, boards, history, trumps, tricks_won, players) = columns.clone();
+        for i in 0..boards.len() {
+            let hands: [u32; 4] = hands[i * 4..i * 4 + 4].try_into().unwrap();
+            let tricks = [tricks_won[i][0], tricks_won[i][1]];
+            let rebuilt = reconstruct_state(hands, &boards[i], trumps[i], tricks, players[i]);
+            let replayed =
+                replay_state(hands, &decode_history(&plays[i]), trumps[i], players[i]).unwrap();
+            assert_eq!(replayed.hands, rebuilt.hands);
+            assert_eq!(replayed.current_trick, rebuilt.current_trick);
+            assert_eq!(replayed.trick_starter, rebuilt.trick_starter);
+            assert_eq!(replayed.trick_size, rebuilt.trick_size);
+            assert_eq!(replayed.tricks_won, rebuilt.tricks_won);
+            assert_eq!(replayed.points_in_play, rebuilt.points_in_play);
+            assert_eq!(rebuilt.points, [0, 0]);
+
+            // Banked points: the finished tricks and the belote, if announced.
+            let board = boards[i].iter().fold(0u32, |m, &c| m | 1 << c);
+            let belote = replayed.belote_scored.iter().filter(|&&b| b).count() as u16 * 20;
+            assert_eq!(
+                replayed.points[0] + replayed.points[1],
+                cards_points(history[i] & !board, trumps[i]) + belote
+            );
+        }
+
+        let request = GameplaySolveRequest::new(columns.clone(), 0).unwrap();
+        let with_plays = request.clone().with_plays(plays.clone()).unwrap();
+        assert_eq!(with_plays.state(3).points, {
+            let p = decode_history(&plays[3]);
+            replay_state(with_plays.hands(3), &p, trumps[3], players[3])
+                .unwrap()
+                .points
+        });
+        // The worlds keep the suits the plays showed missing, and the true deal fits them.
+        let mut with_voids = 0;
+        for i in 0..boards.len() {
+            let state = with_plays.state(i);
+            let voids = with_plays.voids(i);
+            assert!(WorldConstraints::new(&state)
+                .with_voids(voids)
+                .is_consistent(&state));
+            let world = determinize_consistent(&state, voids, &[], &mut sample_rng(Some(5), 0));
+            for seat in (0..4).filter(|&p| p != state.current_player as usize) {
+                let void_cards = (0..4)
+                    .filter(|&s| voids[seat] & 1 << s != 0)
+                    .fold(0u32, |m, s| m | 0xFF << (s * 8));
+                assert_eq!(world.hands[seat] & void_cards, 0);
+            }
+            with_voids += (voids != [0; 4]) as usize;
+        }
+        assert!(with_voids > 0);
+        assert_eq!(request.voids(0), [0; 4]);
+        assert!(request.clone().with_plays(plays[1..].to_vec()).is_err());
+        let mut shuffled = plays.clone();
+        shuffled.swap(0, 1);
+        assert!(request.with_plays(shuffled).is_err());
+    }
+
+    #[test]
+    fn test_reconstruction_checks() {
+        let (hands, boards, history, trumps, tricks_won, players) =
+            generate_raw_gameplay_batch(100, Some(5));
+        let check = |i: usize, board: &[u8], history: u32, player: u8| {
+            check_reconstruction(
+                hands[i * 4..i * 4 + 4].try_into().unwrap(),
+                board,
+                history,
+                trumps[i],
+                [tricks_won[i][0], tricks_won[i][1]],
+                player,
+            )
+        };
+        for i in 0..boards.len() {
+            assert_eq!(check(i, &boards[i], history[i], players[i]), Ok(()));
+        }
+
+        let i = boards.iter().position(|b| b.len() == 2).unwrap();
+        let (board, mask) = (&boards[i], history[i]);
+        let doubled = [board[0], board[0]];
+        assert_eq!(
+            check(i, &doubled, mask, players[i]),
+            Err(InvalidReason::InconsistentBoard)
+        );
+        assert_eq!(
+            check(i, board, mask & !(1 << board[1]), players[i]),
+            Err(InvalidReason::HistoryMismatch)
+        );
+        // Another seat to move makes another starter, whose hand sizes do not fit.
+        assert_eq!(
+            check(i, board, mask, (players[i] + 1) % 4),
+            Err(InvalidReason::HandSizes)
+        );
+
+        // S leads the 7D; W follows with the 8D, or discards the 9S holding the 8D.
+        let (south, north, east) = (0x0001_00FC, 0x00FE_8000, 0xFF00_0000);
+        let follows = [south, 0x7F00, north, east];
+        assert_eq!(
+            check_reconstruction(follows, &[0, 1], 0b11, HEARTS, [0, 0], 2),
+            Ok(())
+        );
+        let discards = [south, 0x7D02, north, east];
+        assert_eq!(
+            check_reconstruction(discards, &[0, 9], 1 | 1 << 9, HEARTS, [0, 0], 2),
+            Err(InvalidReason::IllegalBoard)
+        );
+
+        for reason in InvalidReason::ALL {
+            assert_eq!(InvalidReason::from_code(reason.code()), Some(reason));
+        }
+        assert_eq!(InvalidReason::from_code(0), None);
+    }
+
+    #[test]
+    fn test_solve_marks_impossible_positions() {
+        let config = StageConfig {
+            opening_weight: 0,
+            midgame_weight: 0,
+            endgame_weight: 1,
+        };
+        let mut positions = generate_positions_for_hand(0x0000_0F0F, 3, &config, Some(2)).unwrap();
+        positions.2[1] = 0;
+        let request = GameplaySolveRequest::new(positions, 0).unwrap();
+        let batch = solve_gameplay_batch(&request, None).unwrap();
+        assert_eq!(batch.2, [true, false, true]);
+        assert_eq!(batch.9, [0, InvalidReason::HistoryMismatch.code(), 0]);
+        let counts = invalid_reason_counts(&batch.9).unwrap();
+        assert_eq!(counts[0], ("valid", 2));
+        assert_eq!(
+            counts[InvalidReason::HistoryMismatch.code() as usize],
+            ("history_mismatch", 1)
+        );
+        assert_eq!(counts.iter().map(|c| c.1).sum::<usize>(), 3);
+        assert!(invalid_reason_counts(&[8]).is_err());
+
+        // Every solve overruns a zero time limit.
+        let mut request = request;
+        request.options.time_limit = Some(Duration::ZERO);
+        let batch = solve_gameplay_batch(&request, None).unwrap();
+        assert_eq!(batch.2, [false; 3]);
+        let timeout = InvalidReason::Timeout.code();
+        assert_eq!(
+            batch.9,
+            [timeout, InvalidReason::HistoryMismatch.code(), timeout]
+        );
+        request.options.time_limit = Some(Duration::from_secs(60));
+        assert_eq!(
+            solve_gameplay_batch(&request, None).unwrap().2,
+            [true, false, true]
+        );
+    }
+
+    #[test]
+    fn test_forced_moves() {
+        // Last trick (forced for everyone), then the last two tricks.
+        let last = [1, 1 << 8, 1 << 16, 1 << 24];
+        let two = [0b11, 0b11 << 8, 0b11 << 16, 0b11 << 24];
+        let positions = (
+            [last, two].concat(),
+            vec![vec![], vec![]],
+            vec![!0x0101_0101, !0x0303_0303],
+            vec![HEARTS; 2],
+            vec![vec![7, 0], vec![6, 0]],
+            vec![0, 0],
+        );
+        let mut request = GameplaySolveRequest::new(positions, 0).unwrap();
+        let solved = solve_gameplay_batch(&request, None).unwrap();
+        assert_eq!(solved.2, [true, true]);
+        assert_eq!(solved.10, [true, false]);
+
+        request.options.skip_forced = true;
+        let skipped = solve_gameplay_batch(&request, None).unwrap();
+        assert_eq!(skipped.10, [true, false]);
+        assert_eq!((skipped.0[0], skipped.8[0], skipped.3[0]), (0, 1, 0));
+        assert!(skipped.1[0].is_nan() && skipped.2[0]);
+        assert_eq!((skipped.0[1], skipped.1[1]), (solved.0[1], solved.1[1]));
+    }
+
+    #[test]
+    fn test_replay_keeps_belote() {
+        // S announces the belote with the KH in the first trick, then holds the QH.
+        let suits: Vec<u8> = (0..4).filter(|&s| s != HEARTS).collect();
+        let hands = [
+            1 << (HEARTS * 8 + 5),
+            1 << (suits[0] * 8 + 1),
+            1 << (suits[1] * 8 + 1),
+            1 << (suits[2] * 8 + 1),
+        ];
+        let plays: Vec<PlayRecord> = [HEARTS * 8 + 6, suits[0] * 8, suits[1] * 8, suits[2] * 8]
+            .iter()
+            .enumerate()
+            .map(|(seat, &card)| PlayRecord {
+                trick: 0,
+                seat: seat as u8,
+                card,
+            })
+            .collect();
+
+        let mut replayed = replay_state(hands, &plays, HEARTS, 0).unwrap();
+        assert_eq!(replayed.points[0], 4 + 20);
+        assert_eq!(replayed.belote_scored, [true, false]);
+        let mut rebuilt = reconstruct_state(hands, &[], HEARTS, [1, 0], 0);
+        for state in [&mut replayed, &mut rebuilt] {
+            for seat in 0..4 {
+                state.play_card(state.hands[seat].trailing_zeros() as u8);
+            }
+        }
+        assert_eq!(replayed.points[0] - rebuilt.points[0], 4 + 20);
+
+        assert!(replay_state(hands, &plays, HEARTS, 1).is_err());
+        let mut out_of_turn = plays.clone();
+        out_of_turn.swap(1, 2);
+        assert!(replay_state(hands, &out_of_turn, HEARTS, 0).is_err());
+        let mut twice = plays.clone();
+        twice[1].card = twice[0].card;
+        assert!(replay_state(hands, &twice, HEARTS, 0).is_err());
+        // West discards while holding a heart.
+        let mut must_follow = hands;
+        must_follow[1] = 1 << (HEARTS * 8 + 1);
+        assert!(replay_state(must_follow, &plays, HEARTS, 0).is_err());
+        assert_eq!(replay_state(hands, &[], HEARTS, 2).unwrap().hands, hands);
+    }
+
+    #[test]
+    fn test_positions_reject_bad_hand() {
+        let config = StageConfig::default();
+        assert!(generate_positions_for_hand(0xFF, 1, &config, None).is_ok());
+        assert!(generate_positions_for_hand(0x7F, 1, &config, None).is_err());
+    }
+
+    #[test]
+    fn test_fingerprint_inputs_cover_the_options() {
+        let request =
+            GameplaySolveRequest::new(generate_raw_gameplay_batch(3, Some(2)), 4).unwrap();
+        let inputs = request.fingerprint_inputs();
+        let mut faster = request.clone();
+        faster.options.tt_log2 = Some(12);
+        assert_eq!(faster.fingerprint_inputs(), inputs);
+
+        let edits: [fn(&mut GameplaySolveOptions); 5] = [
+            |o| o.seed = Some(1),
+            |o| o.skip_forced = true,
+            |o| o.time_limit = Some(Duration::from_millis(5)),
+            |o| o.adaptive = Some(AdaptivePimc::new(4, 0.05).unwrap()),
+            |o| o.max_exact_worlds = Some(100),
+        ];
+        for edit in edits {
+            let mut other = request.clone();
+            edit(&mut other.options);
+            assert_ne!(other.fingerprint_inputs(), inputs);
+        }
+        assert_eq!(inputs["version"], SOLVE_INPUTS_VERSION);
+    }
+
+    #[test]
+    fn test_gameplay_batch_accessors() {
+        let batch = generate_gameplay_batch(5, Some(3));
+        assert_eq!(batch.__len__(), 5);
+        assert_eq!(batch.columns(), generate_raw_gameplay_batch(5, Some(3)));
+        assert_eq!(batch.flat_hands().len(), 20);
+
+        let last = batch.__getitem__(-1).unwrap();
+        assert_eq!(last, batch.samples[4]);
+        assert!(batch.__getitem__(5).is_err());
+        assert_eq!(last.hand(), last.hands[last.player as usize]);
+        assert_eq!(last.state().unwrap().current_player, last.player);
+        assert_eq!(history_mask(&decode_history(&last.plays)), last.history);
+        let ids = batch.deal_ids().unwrap();
+        assert_eq!(ids[4], last.deal_id().unwrap());
+        let dealt = crate::gameplay::deal::deal_from_id(ids[4]).unwrap();
+        for (&full, &hand) in dealt.iter().zip(&last.hands) {
+            assert_eq!(full & hand, hand);
+        }
+    }
+
+    #[test]
+    fn test_optimal_cards_cover_equivalent_moves() {
+        // S leads 7D or 8D, N wins with the AD and leads the KD: either diamond
+        // of S is worth the same.
+        let mut state = PlayingState::new(HEARTS);
+        state.hands = [0b11, 0b11 << 8, 0b11 << 6, 0b11 << 24];
+        let mut rng = sample_rng(Some(1), 0);
+        let mut solve = |optimal_epsilon| {
+            let labels = LabelSpec {
+                score_label: ScoreLabel::DoubleDummy,
+                objective: Objective::Points,
+                optimal_epsilon,
+            };
+            let worlds = WorldBudget {
+                iterations: 0,
+                adaptive: None,
+                max_exact_worlds: None,
+                seed: None,
+            };
+            let hidden = WorldConstraints::new(&state);
+            solve_sample(state, 0, &hidden, &worlds, &labels, None, &mut rng)
+        };
+
+        let single = solve(None);
+        assert_eq!(single.optimal_cards, 1 << single.best_card);
+        let all = solve(Some(0.0));
+        assert_eq!(all.optimal_cards, 0b11);
+        assert_eq!((all.best_card, all.best_score), (0, single.best_score));
     }
 
-    (best_cards, best_scores, valid_mask)
+    #[test]
+    fn test_side_filter() {
+        for side in [SideFilter::Declarer, SideFilter::Defense] {
+            let batch =
+                generate_gameplay_batch_for_side(20, Some(9), Some(side), ContractSource::Random);
+            assert!(batch
+                .samples
+                .iter()
+                .all(|s| s.declaring_side() == (side == SideFilter::Declarer)));
+            assert_eq!(
+                batch.declaring_side(),
+                vec![side == SideFilter::Declarer; 20]
+            );
+        }
+        // Without a filter, the first position of every stream is kept.
+        assert_eq!(
+            generate_gameplay_batch_for_side(5, Some(3), None, ContractSource::Random),
+            generate_gameplay_batch(5, Some(3))
+        );
+        assert!(SideFilter::parse("dummy").is_err());
+
+        let hands = [0xFF, 0xFF00, 0xFF0000, 0xFF000000];
+        assert_eq!(deal_declarer(&hands, HEARTS), 2);
+    }
+
+    #[test]
+    fn test_threshold_contracts() {
+        let batch = generate_gameplay_batch_for_side(10, Some(4), None, ContractSource::Threshold);
+        for sample in &batch.samples {
+            let contract = sample.contract.expect("threshold deals have a contract");
+            assert_eq!(sample.trump, contract.trump);
+            // Replaying the auction on the dealt hands gives the same contract.
+            let mut dealt = sample.hands;
+            for play in decode_history(&sample.plays) {
+                dealt[play.seat as usize] |= 1 << play.card;
+            }
+            let mut state = BiddingState::new(3);
+            for call in ThresholdBidder::default().auction(3, &dealt) {
+                call.apply(&mut state).unwrap();
+            }
+            assert_eq!(state.contract, Some(contract));
+            assert_eq!(state.contract_owner, Some(sample.declarer));
+        }
+        assert!(batch.contract_values().iter().all(|&v| v >= 80));
+        assert_eq!(
+            generate_gameplay_batch(3, Some(4)).contract_values(),
+            vec![0; 3]
+        );
+        assert_eq!(
+            ContractSource::parse("Threshold"),
+            Ok(ContractSource::Threshold)
+        );
+        assert!(ContractSource::parse("par").is_err());
+    }
+
+    #[test]
+    fn test_adaptive_pimc() {
+        let adaptive = AdaptivePimc::new(4, 0.05).unwrap();
+        let mut votes = [0; 32];
+        votes[3] = 10;
+        assert!(!adaptive.is_decided(&votes));
+        votes[3] = 11;
+        assert!(adaptive.is_decided(&votes));
+        votes[5] = 6;
+        assert!(!adaptive.is_decided(&votes));
+        assert!(!AdaptivePimc::new(20, 0.05).unwrap().is_decided(&[1; 32]));
+        assert!(AdaptivePimc::new(4, 0.0).is_err());
+        assert!(AdaptivePimc::new(4, 0.5).is_err());
+
+        // Endgames: most votes are unanimous and stop long before the budget.
+        let config = StageConfig {
+            opening_weight: 0,
+            midgame_weight: 0,
+            endgame_weight: 1,
+        };
+        let positions = generate_positions_for_hand(0x0000_F0F0, 4, &config, Some(5)).unwrap();
+        let mut request = GameplaySolveRequest::new(positions, 64).unwrap();
+        request.options.seed = Some(5);
+        let fixed = solve_gameplay_batch(&request, None).unwrap();
+        request.options.adaptive = Some(adaptive);
+        let early = solve_gameplay_batch(&request, None).unwrap();
+        assert_eq!(early.2, fixed.2);
+        assert!(early.3.iter().sum::<u64>() < fixed.3.iter().sum::<u64>());
+    }
+
+    #[test]
+    fn test_exact_worlds() {
+        // Endgames have at most 9 hidden cards, 1680 worlds: every label is exact.
+        let config = StageConfig {
+            opening_weight: 0,
+            midgame_weight: 0,
+            endgame_weight: 1,
+        };
+        let positions = generate_positions_for_hand(0x0000_F0F0, 4, &config, Some(5)).unwrap();
+        let mut request = GameplaySolveRequest::new(positions, 4).unwrap();
+        request.options.score_label = ScoreLabel::Expected;
+        request.options.max_exact_worlds = Some(1680);
+        let solve = |seed| {
+            let mut request = request.clone();
+            request.options.seed = Some(seed);
+            solve_gameplay_batch(&request, None).unwrap()
+        };
+        let (a, b) = (solve(5), solve(6));
+        assert_eq!(a.0, b.0);
+        assert_eq!(a.1, b.1);
+        assert_eq!(a.5, b.5);
+    }
+
+    #[test]
+    fn test_seeded_solve_is_reproducible() {
+        let batch = generate_raw_gameplay_batch(6, Some(11));
+        assert_eq!(batch, generate_raw_gameplay_batch(6, Some(11)));
+        let in_pool = |threads| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap()
+        };
+        for threads in [1, 4] {
+            assert_eq!(
+                in_pool(threads).install(|| generate_raw_gameplay_batch(6, Some(11))),
+                batch
+            );
+        }
+
+        // Midgames and endgames, with enough worlds for a shared TT to change votes.
+        let config = StageConfig {
+            opening_weight: 0,
+            midgame_weight: 1,
+            endgame_weight: 1,
+        };
+        let positions = generate_positions_for_hand(0x0000_F0F0, 6, &config, Some(5)).unwrap();
+        let solve = || {
+            let mut request = GameplaySolveRequest::new(positions.clone(), 20).unwrap();
+            request.options.seed = Some(5);
+            solve_gameplay_batch(&request, None).unwrap()
+        };
+        let (cards_a, scores_a, valid_a, _, _, agreement_a, entropy_a, variance_a, ..) = solve();
+        let (cards_b, scores_b, valid_b, _, _, agreement_b, entropy_b, variance_b, ..) = solve();
+        assert_eq!(cards_a, cards_b);
+        assert_eq!(scores_a, scores_b);
+        assert_eq!(valid_a, valid_b);
+        assert_eq!(agreement_a, agreement_b);
+        assert_eq!(entropy_a, entropy_b);
+        assert_eq!(variance_a, variance_b);
+
+        // Replayed shards run on other machines: the labels ignore the thread count.
+        for threads in [1, 4] {
+            let (cards_c, scores_c, valid_c, _, _, agreement_c, entropy_c, variance_c, ..) =
+                in_pool(threads).install(solve);
+            assert_eq!(cards_c, cards_a);
+            assert_eq!(scores_c, scores_a);
+            assert_eq!(valid_c, valid_a);
+            assert_eq!(agreement_c, agreement_a);
+            assert_eq!(entropy_c, entropy_a);
+            assert_eq!(variance_c, variance_a);
+        }
+
+        // Parallel decisions: the worlds of one position spread over the threads.
+        assert_eq!(pimc_tt_scope(Some(9)), None);
+        let (hands, boards, _, trumps, tricks_won, players) = &positions;
+        let decide = |i: usize| {
+            let state = reconstruct_state(
+                hands[i * 4..i * 4 + 4].try_into().unwrap(),
+                &boards[i],
+                trumps[i],
+                [tricks_won[i][0], tricks_won[i][1]],
+                players[i],
+            );
+            let team = (state.current_player % 2) as usize;
+            let decision = solve_pimc_parallel(
+                &state,
+                team,
+                20,
+                PimcVoting::Plurality,
+                &[],
+                [0; 4],
+                None,
+                Some(9),
+            );
+            (decision.votes, decision.best_card)
+        };
+        let single: Vec<_> = in_pool(1).install(|| (0..players.len()).map(decide).collect());
+        let several: Vec<_> = in_pool(4).install(|| (0..players.len()).map(decide).collect());
+        assert_eq!(single, several);
+    }
+
+    #[test]
+    fn test_expected_score_labels() {
+        let config = StageConfig {
+            opening_weight: 0,
+            midgame_weight: 0,
+            endgame_weight: 1,
+        };
+        let positions = generate_positions_for_hand(0x00F0_000F, 6, &config, Some(6)).unwrap();
+        let solve = |pimc: usize, label: ScoreLabel| {
+            let mut request = GameplaySolveRequest::new(positions.clone(), pimc).unwrap();
+            request.options.seed = Some(6);
+            request.options.score_label = label;
+            solve_gameplay_batch(&request, None).unwrap()
+        };
+
+        // Without PIMC there is nothing to average: both labels are double dummy.
+        assert_eq!(
+            solve(0, ScoreLabel::DoubleDummy).1,
+            solve(0, ScoreLabel::Expected).1
+        );
+
+        // With PIMC the cards do not depend on the label, the scores do.
+        let dd = solve(4, ScoreLabel::DoubleDummy);
+        let ev = solve(4, ScoreLabel::Expected);
+        assert_eq!(dd.0, ev.0);
+        assert!(dd.1.iter().all(|s| s.fract() == 0.0));
+        assert!(ev.1.iter().all(|s| (0.0..=272.0).contains(s)));
+        assert_ne!(dd.1, ev.1);
+
+        assert_eq!(ScoreLabel::parse("ev"), Ok(ScoreLabel::Expected));
+        assert_eq!(
+            ScoreLabel::parse(ScoreLabel::DoubleDummy.name()),
+            Ok(ScoreLabel::DoubleDummy)
+        );
+    }
+
+    #[test]
+    fn test_current_player_values_are_rotation_invariant() {
+        let config = StageConfig {
+            opening_weight: 0,
+            midgame_weight: 0,
+            endgame_weight: 1,
+        };
+        let positions = generate_positions_for_hand(0x0000_0F0F, 4, &config, Some(6)).unwrap();
+        // Every seat moves one place left: the other team is now to move.
+        let (hands, boards, history, trumps, tricks_won, players) = positions.clone();
+        let hands = (0..hands.len())
+            .map(|i| hands[i - i % 4 + (i + 3) % 4])
+            .collect();
+        let tricks_won = tricks_won.iter().map(|t| vec![t[1], t[0]]).collect();
+        let players = players.iter().map(|p| (p + 1) % 4).collect();
+        let rotated = (hands, boards, history, trumps, tricks_won, players);
+
+        let solve = |positions, perspective| {
+            let mut request = GameplaySolveRequest::new(positions, 0).unwrap();
+            request.options.perspective = perspective;
+            let (cards, scores, valid, ..) = solve_gameplay_batch(&request, None).unwrap();
+            assert!(valid.iter().all(|&v| v));
+            (cards, scores)
+        };
+        let current = solve(positions.clone(), Perspective::CurrentPlayer);
+        assert_eq!(solve(rotated, Perspective::CurrentPlayer), current);
+        let ns = solve(positions.clone(), Perspective::Absolute);
+        for (i, &player) in positions.5.iter().enumerate() {
+            if player % 2 == 0 {
+                assert_eq!(ns.1[i], current.1[i]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_solve_request_checks_columns() {
+        let config = StageConfig {
+            opening_weight: 0,
+            midgame_weight: 0,
+            endgame_weight: 1,
+        };
+        let positions = generate_positions_for_hand(0x0000_0F0F, 3, &config, Some(2)).unwrap();
+        let request = GameplaySolveRequest::new(positions.clone(), 0).unwrap();
+        assert_eq!(request.len(), 3);
+        assert_eq!(request.options, GameplaySolveOptions::default());
+        let (hands, boards, history, trumps, tricks_won, players) = positions.clone();
+        assert_eq!(
+            request.state(1).hands,
+            [hands[4], hands[5], hands[6], hands[7]]
+        );
+        assert_eq!(request.state(1).current_player, players[1]);
+
+        let mut short = positions.clone();
+        short.5.pop();
+        let err = GameplaySolveRequest::new(short, 0).unwrap_err();
+        assert!(err.contains("players"), "{}", err);
+        let mut short = positions.clone();
+        short.0.pop();
+        assert!(GameplaySolveRequest::new(short, 0).is_err());
+        let mut bad = positions.clone();
+        bad.4[2] = vec![1];
+        assert!(GameplaySolveRequest::new(bad, 0).is_err());
+        let mut bad = (hands, boards, history, trumps, tricks_won, players);
+        bad.3[0] = 6;
+        assert!(GameplaySolveRequest::new(bad, 0).is_err());
+
+        // The declarers are checked when solving, as the options may change.
+        let mut request = request;
+        request.options.perspective = Perspective::Declarer;
+        assert!(solve_gameplay_batch(&request, None).is_err());
+        request.options.declarers = Some(vec![0; 3]);
+        let (_, scores, valid, ..) = solve_gameplay_batch(&request, None).unwrap();
+        assert_eq!(scores.len(), 3);
+        assert!(valid.iter().all(|&v| v));
+    }
+
+    #[test]
+    fn test_shared_tt_keeps_world_values() {
+        let mut rng = sample_rng(Some(4), 0);
+        for _ in 0..6 {
+            let state = crate::solver::random_ending(16, &mut rng);
+            let team = (state.current_player % 2) as usize;
+            let worlds: Vec<PlayingState> = (0..8).map(|_| determinize(&state, &mut rng)).collect();
+            let scope = Some(new_tt_scope());
+            for world in &worlds {
+                let shared = with_tt_scope(scope, || solve_root_moves(world, team, Some(32), None));
+                assert_eq!(shared, solve_root_moves(world, team, Some(32), None));
+            }
+        }
+    }
+
+    #[test]
+    fn test_shared_tt_votes_only_break_ties() {
+        let config = StageConfig {
+            opening_weight: 0,
+            midgame_weight: 1,
+            endgame_weight: 0,
+        };
+        let (hands, boards, _, trumps, tricks_won, players) =
+            generate_positions_for_hand(0x0000_F0F0, 4, &config, Some(3)).unwrap();
+        let mut rng = sample_rng(Some(9), 0);
+        for i in 0..players.len() {
+            let state = reconstruct_state(
+                hands[i * 4..i * 4 + 4].try_into().unwrap(),
+                &boards[i],
+                trumps[i],
+                [tricks_won[i][0], tricks_won[i][1]],
+                players[i],
+            );
+            let team = (state.current_player % 2) as usize;
+            let worlds: Vec<PlayingState> =
+                (0..20).map(|_| determinize(&state, &mut rng)).collect();
+            let vote = |scope: Option<u64>, world: &PlayingState| {
+                with_tt_scope(scope, || {
+                    solve_world(
+                        world,
+                        team,
+                        Objective::Points,
+                        PimcVoting::Plurality,
+                        true,
+                        None,
+                    )
+                })
+            };
+            let scope = Some(new_tt_scope());
+            for world in &worlds {
+                let shared = vote(scope, world);
+                let cold = vote(None, world);
+                // Same world value; the card may differ only between equal ones.
+                assert_eq!(shared.score, cold.score);
+                if shared.card != cold.card {
+                    let values = solve_root_moves(world, team, Some(32), None);
+                    let value = |card| values.iter().find(|&&(c, _)| c == card).unwrap().1;
+                    assert_eq!(value(shared.card), value(cold.card));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_parallel_pimc_is_seeded() {
+        let config = StageConfig {
+            opening_weight: 0,
+            midgame_weight: 0,
+            endgame_weight: 1,
+        };
+        let (hands, boards, _, trumps, tricks_won, players) =
+            generate_positions_for_hand(0x0000_F0F0, 1, &config, Some(3)).unwrap();
+        let hands: [u32; 4] = hands[0..4].try_into().unwrap();
+        let state = reconstruct_state(
+            hands,
+            &boards[0],
+            trumps[0],
+            [tricks_won[0][0], tricks_won[0][1]],
+            players[0],
+        );
+
+        let a = solve_pimc_parallel(
+            &state,
+            0,
+            8,
+            PimcVoting::Plurality,
+            &[],
+            [0; 4],
+            None,
+            Some(9),
+        );
+        let b = solve_pimc_parallel(
+            &state,
+            0,
+            8,
+            PimcVoting::Plurality,
+            &[],
+            [0; 4],
+            None,
+            Some(9),
+        );
+        assert_eq!(a.votes, b.votes);
+        assert_eq!(a.best_card, b.best_card);
+        assert_eq!(a.votes.iter().sum::<u32>(), 8);
+        assert!(state.get_legal_moves() & (1 << a.best_card) != 0);
+
+        let agreement = a.votes[a.best_card as usize] as f32 / 8.0;
+        assert_eq!(a.confidence.agreement, agreement);
+        if agreement == 1.0 {
+            assert_eq!(a.confidence.vote_entropy, 0.0);
+        } else {
+            assert!(a.confidence.vote_entropy > 0.0);
+        }
+        assert!(a.confidence.value_variance >= 0.0);
+
+        let world = determinize(&state, &mut sample_rng(Some(1), 0));
+        let me = state.current_player as usize;
+        assert_eq!(world.hands[me], state.hands[me]);
+        for p in 0..4 {
+            assert_eq!(world.hands[p].count_ones(), state.hands[p].count_ones());
+        }
+    }
+
+    #[test]
+    fn test_expected_value_voting() {
+        let config = StageConfig {
+            opening_weight: 0,
+            midgame_weight: 0,
+            endgame_weight: 1,
+        };
+        let (hands, boards, _, trumps, tricks_won, players) =
+            generate_positions_for_hand(0x0F00_00F0, 1, &config, Some(4)).unwrap();
+        let state = reconstruct_state(
+            hands[0..4].try_into().unwrap(),
+            &boards[0],
+            trumps[0],
+            [tricks_won[0][0], tricks_won[0][1]],
+            players[0],
+        );
+        let team = (state.current_player % 2) as usize;
+
+        let ev = solve_pimc_parallel(
+            &state,
+            team,
+            6,
+            PimcVoting::ExpectedValue,
+            &[],
+            [0; 4],
+            None,
+            Some(2),
+        );
+        let legal = state.get_legal_moves();
+        assert!(legal & (1 << ev.best_card) != 0);
+        assert_eq!(ev.votes.iter().sum::<u32>(), 6);
+        for c in 0..32 {
+            if legal & (1 << c) != 0 {
+                assert!(ev.card_values[c] <= ev.card_values[ev.best_card as usize]);
+            } else {
+                assert_eq!(ev.card_values[c], 0.0);
+            }
+        }
+        assert_eq!(PimcVoting::parse("ev"), Ok(PimcVoting::ExpectedValue));
+        assert!(PimcVoting::parse("median").is_err());
+    }
+
+    #[test]
+    fn test_bid_consistent_worlds() {
+        let hearts_120 = BidConstraint::new(1, Bid::new(120, 2), 0);
+        // Trumpless yarborough vs Jack, Nine and Ace of trumps.
+        assert!(!hearts_120.is_consistent(0x0000_0303));
+        assert!(hearts_120.is_consistent((1 << 20) | (1 << 18) | (1 << 23) | 0x0303));
+
+        // South (to move) holds all Diamonds and Spades, so East's hearts come from the deal.
+        let mut state = PlayingState::new(2);
+        state.hands = generate_hands_with_south(0x0000_FFFF, &mut sample_rng(Some(8), 0));
+        for i in 0..20 {
+            let world =
+                determinize_consistent(&state, [0; 4], &[hearts_120], &mut sample_rng(Some(8), i));
+            assert_eq!(world.hands[0], state.hands[0]);
+            assert!(hearts_120.is_consistent(world.hands[1]));
+        }
+    }
 }
@@ -0,0 +1,138 @@
+//! Percentile of a hand's bidding strength among random hands, per trump.
+//!
+//! Strength is `contract_strength`, the hand heuristic the bidding bots and
+//! constraints use. Its distribution over random 8-card hands is sampled once by
+//! the `hand-percentiles` tool and shipped in `table`, so a lookup costs nothing:
+//! bid thresholds can be calibrated as "top 10% hands" and UIs can show where a
+//! hand stands.
+
+mod table;
+
+use rand::seq::index::sample;
+use rayon::prelude::*;
+
+use super::bidding::contract_strength;
+use super::common::sample_rng;
+
+/// Hands sampled per RNG stream by `strength_counts`.
+const CHUNK: usize = 100_000;
+
+/// Percentile (0-100) of `hand` among random hands for a contract in `trump`:
+/// the share of hands weaker than it, plus half of those as strong.
+pub fn hand_percentile(hand: u32, trump: u8) -> Result<f32, String> {
+    if hand.count_ones() != 8 {
+        return Err(format!("A hand has 8 cards, got {}", hand.count_ones()));
+    }
+    if trump >= 6 {
+        return Err(format!("Invalid trump {}", trump));
+    }
+    let strength = contract_strength(hand, trump);
+    Ok(percentile(
+        table::STRENGTH_COUNTS[trump as usize],
+        table::SAMPLED_HANDS,
+        strength,
+    ))
+}
+
+fn percentile(counts: &[(i32, u32)], total: u32, strength: i32) -> f32 {
+    let below: u32 = counts
+        .iter()
+        .filter(|&&(s, _)| s < strength)
+        .map(|&(_, n)| n)
+        .sum();
+    let equal = counts
+        .iter()
+        .find(|&&(s, _)| s == strength)
+        .map_or(0, |&(_, n)| n);
+    (100.0 * (below as f64 + equal as f64 / 2.0) / total as f64) as f32
+}
+
+/// Number of hands of each strength among `num_hands` random hands, by trump:
+/// (strength, hands) pairs in increasing strength. With a seed the counts only
+/// depend on (seed, num_hands).
+pub fn strength_counts(num_hands: usize, seed: Option<u64>) -> [Vec<(i32, u32)>; 6] {
+    let chunks: Vec<[Vec<(i32, u32)>; 6]> = (0..num_hands.div_ceil(CHUNK))
+        .into_par_iter()
+        .map(|chunk| {
+            let mut rng = sample_rng(seed, chunk as u64);
+            let mut counts: [Vec<(i32, u32)>; 6] = Default::default();
+            for _ in 0..CHUNK.min(num_hands - chunk * CHUNK) {
+                let hand = sample(&mut rng, 32, 8).iter().fold(0u32, |m, c| m | 1 << c);
+                for (trump, counts) in counts.iter_mut().enumerate() {
+                    add(counts, contract_strength(hand, trump as u8), 1);
+                }
+            }
+            counts
+        })
+        .collect();
+    let mut total: [Vec<(i32, u32)>; 6] = Default::default();
+    for counts in chunks {
+        for (total, counts) in total.iter_mut().zip(counts) {
+            for (strength, n) in counts {
+                add(total, strength, n);
+            }
+        }
+    }
+    total
+}
+
+fn add(counts: &mut Vec<(i32, u32)>, strength: i32, n: u32) {
+    match counts.binary_search_by_key(&strength, |&(s, _)| s) {
+        Ok(i) => counts[i].1 += n,
+        Err(i) => counts.insert(i, (strength, n)),
+    }
+}
+
+/// Rust source of the shipped `table` module for `counts` of `num_hands` hands.
+pub fn table_source(counts: &[Vec<(i32, u32)>; 6], num_hands: usize, seed: Option<u64>) -> String {
+    let seed = seed.map_or("none".to_string(), |s| s.to_string());
+    let mut source = format!(
+        "//! Generated by the `hand-percentiles` tool ({} hands, seed {}): do not edit.\n\n\
+         pub(super) const SAMPLED_HANDS: u32 = {};\n\n\
+         /// (strength, hands) of every strength sampled, by trump.\n\
+         pub(super) const STRENGTH_COUNTS: [&[(i32, u32)]; 6] = [\n",
+        num_hands, seed, num_hands
+    );
+    for counts in counts {
+        source += "    &[\n";
+        for (strength, n) in counts {
+            source += &format!("        ({}, {}),\n", strength, n);
+        }
+        source += "    ],\n";
+    }
+    source += "];\n";
+    source
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::playing::{HEARTS, SPADES};
+
+    #[test]
+    fn test_hand_percentile() {
+        let all_hearts = 0xFF << (HEARTS * 8);
+        assert!(hand_percentile(all_hearts, HEARTS).unwrap() > 99.9);
+        assert!(hand_percentile(all_hearts, SPADES).unwrap() < 50.0);
+        // Sevens and eights of every suit rank with the weakest hands, which
+        // share one percentile (a quarter of the hands score 0 in a suit).
+        let weak = 0x0303_0303;
+        for trump in 0..6 {
+            let p = hand_percentile(weak, trump).unwrap();
+            assert!((0.0..15.0).contains(&p), "trump {}: {}", trump, p);
+        }
+        assert!(hand_percentile(0xFF, 6).is_err());
+        assert!(hand_percentile(0x7F, HEARTS).is_err());
+
+        // The shipped table matches a fresh sample up to sampling noise.
+        let counts = strength_counts(20_000, Some(1));
+        assert_eq!(counts[0].iter().map(|&(_, n)| n).sum::<u32>(), 20_000);
+        let hand = 1 << (HEARTS * 8 + 4) | 1 << (HEARTS * 8 + 2) | 0x0101_0107;
+        let sampled = percentile(
+            &counts[HEARTS as usize],
+            20_000,
+            contract_strength(hand, HEARTS),
+        );
+        assert!((sampled - hand_percentile(hand, HEARTS).unwrap()).abs() < 2.0);
+    }
+}
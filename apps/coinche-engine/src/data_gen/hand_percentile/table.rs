@@ -0,0 +1,236 @@
+//! Generated by the `hand-percentiles` tool (10000000 hands, seed 0): do not edit.
+
+pub(super) const SAMPLED_HANDS: u32 = 10000000;
+
+/// (strength, hands) of every strength sampled, by trump.
+pub(super) const STRENGTH_COUNTS: [&[(i32, u32)]; 6] = [
+    &[
+        (0, 2721899),
+        (11, 3183160),
+        (20, 1001993),
+        (22, 1031800),
+        (31, 989378),
+        (33, 90887),
+        (34, 247005),
+        (40, 67451),
+        (42, 265478),
+        (45, 214290),
+        (51, 43882),
+        (53, 18995),
+        (54, 27934),
+        (56, 47747),
+        (60, 8322),
+        (62, 7443),
+        (65, 14032),
+        (67, 2680),
+        (71, 3955),
+        (73, 295),
+        (74, 6340),
+        (76, 1540),
+        (82, 366),
+        (85, 2770),
+        (87, 27),
+        (93, 9),
+        (96, 277),
+        (107, 6),
+        (10000, 39),
+    ],
+    &[
+        (0, 2720692),
+        (11, 3183284),
+        (20, 1004091),
+        (22, 1031477),
+        (31, 989720),
+        (33, 91258),
+        (34, 247387),
+        (40, 67212),
+        (42, 265500),
+        (45, 213146),
+        (51, 44010),
+        (53, 18481),
+        (54, 27698),
+        (56, 48116),
+        (60, 8301),
+        (62, 7374),
+        (65, 14016),
+        (67, 2755),
+        (71, 3841),
+        (73, 316),
+        (74, 6307),
+        (76, 1586),
+        (82, 361),
+        (85, 2705),
+        (87, 32),
+        (93, 7),
+        (96, 279),
+        (107, 3),
+        (10000, 45),
+    ],
+    &[
+        (0, 2721272),
+        (11, 3181484),
+        (20, 1001969),
+        (22, 1031425),
+        (31, 992691),
+        (33, 90743),
+        (34, 247858),
+        (40, 67300),
+        (42, 265564),
+        (45, 212866),
+        (51, 44215),
+        (53, 19011),
+        (54, 27989),
+        (56, 47953),
+        (60, 8371),
+        (62, 7331),
+        (65, 13882),
+        (67, 2718),
+        (71, 3776),
+        (73, 299),
+        (74, 6348),
+        (76, 1553),
+        (82, 373),
+        (85, 2682),
+        (87, 31),
+        (93, 4),
+        (96, 255),
+        (107, 4),
+        (10000, 33),
+    ],
+    &[
+        (0, 2723245),
+        (11, 3184106),
+        (20, 1002193),
+        (22, 1030041),
+        (31, 988388),
+        (33, 91477),
+        (34, 247217),
+        (40, 67386),
+        (42, 265898),
+        (45, 213498),
+        (51, 43847),
+        (53, 18825),
+        (54, 27747),
+        (56, 48092),
+        (60, 8428),
+        (62, 7354),
+        (65, 14012),
+        (67, 2737),
+        (71, 3870),
+        (73, 302),
+        (74, 6322),
+        (76, 1556),
+        (82, 416),
+        (85, 2698),
+        (87, 31),
+        (93, 5),
+        (96, 267),
+        (10000, 42),
+    ],
+    &[
+        (0, 464),
+        (1, 5959),
+        (2, 22289),
+        (3, 54404),
+        (4, 100774),
+        (5, 135357),
+        (6, 170634),
+        (7, 194311),
+        (8, 241282),
+        (9, 289312),
+        (10, 306085),
+        (11, 327303),
+        (12, 356770),
+        (13, 425845),
+        (14, 486284),
+        (15, 477993),
+        (16, 466066),
+        (17, 492435),
+        (18, 515791),
+        (19, 535108),
+        (20, 456656),
+        (21, 427831),
+        (22, 433944),
+        (23, 419513),
+        (24, 377990),
+        (25, 320552),
+        (26, 291889),
+        (27, 305074),
+        (28, 255182),
+        (29, 201197),
+        (30, 165921),
+        (31, 144514),
+        (32, 141489),
+        (33, 103998),
+        (34, 72199),
+        (35, 63561),
+        (36, 55622),
+        (37, 46938),
+        (38, 32019),
+        (39, 18702),
+        (40, 19161),
+        (41, 13708),
+        (42, 9650),
+        (43, 5516),
+        (44, 3659),
+        (45, 3614),
+        (46, 2333),
+        (47, 1029),
+        (48, 699),
+        (49, 646),
+        (50, 390),
+        (51, 207),
+        (52, 27),
+        (53, 53),
+        (54, 34),
+        (55, 14),
+        (58, 3),
+    ],
+    &[
+        (0, 28),
+        (1, 431),
+        (2, 1781),
+        (3, 4893),
+        (4, 11546),
+        (5, 26857),
+        (6, 48129),
+        (7, 78056),
+        (8, 122181),
+        (9, 178484),
+        (10, 235649),
+        (11, 297515),
+        (12, 388448),
+        (13, 456826),
+        (14, 516657),
+        (15, 582546),
+        (16, 646475),
+        (17, 669929),
+        (18, 660920),
+        (19, 692243),
+        (20, 661556),
+        (21, 607512),
+        (22, 556780),
+        (23, 514268),
+        (24, 446796),
+        (25, 354806),
+        (26, 315682),
+        (27, 250711),
+        (28, 189417),
+        (29, 143957),
+        (30, 111502),
+        (31, 80678),
+        (32, 48989),
+        (33, 38376),
+        (34, 24394),
+        (35, 14906),
+        (36, 8794),
+        (37, 5531),
+        (38, 3071),
+        (39, 1213),
+        (40, 916),
+        (41, 328),
+        (42, 146),
+        (43, 46),
+        (44, 31),
+    ],
+];
@@ -0,0 +1,205 @@
+//! Human-like play traces: solver values turned into imperfect choices, for
+//! imitation-learning baselines and training opponents that can be beaten.
+//!
+//! At every card the player to move values each legal card with the solver (its
+//! team's final points, double dummy) and draws one from the softmax of
+//! value / `temperature`: close calls are often missed, costly mistakes rarely.
+//! With probability `override_rate` it follows the bots' rule of thumb
+//! (`heuristic_card`) on its own observation instead, as a player on autopilot.
+
+use crate::gameplay::bot::{heuristic_card, observation};
+use crate::gameplay::deal::deal_id;
+use crate::gameplay::encoding::CARDS;
+use crate::gameplay::playing::PlayingState;
+use crate::solver::{solve_root_moves, Score};
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rayon::prelude::*;
+
+use super::common::{generate_random_hands, sample_rng};
+use super::selfplay::pick_card;
+
+/// How far from the solver's play the traces stray.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NoiseModel {
+    /// Points of value that make a card e times less likely. 0 always plays a
+    /// best card.
+    pub temperature: f32,
+    /// Probability of playing the rule-of-thumb card instead.
+    pub override_rate: f64,
+}
+
+impl NoiseModel {
+    pub fn new(temperature: f32, override_rate: f64) -> Result<Self, String> {
+        if temperature.is_nan() || temperature < 0.0 {
+            return Err(format!("Invalid temperature {}", temperature));
+        }
+        if !(0.0..=1.0).contains(&override_rate) {
+            return Err(format!("Invalid override rate {}", override_rate));
+        }
+        Ok(NoiseModel {
+            temperature,
+            override_rate,
+        })
+    }
+
+    /// Card of the player to move in `state`, the points it costs its team
+    /// against the best card, and whether the rule of thumb chose it.
+    pub fn choose<R: Rng>(
+        &self,
+        state: &PlayingState,
+        tt_log2: Option<u8>,
+        rng: &mut R,
+    ) -> (u8, Score, bool) {
+        let legal = state.get_legal_moves();
+        if legal.count_ones() == 1 {
+            return (legal.trailing_zeros() as u8, 0, false);
+        }
+        let team = (state.current_player % 2) as usize;
+        let values = solve_root_moves(state, team, Some(32), tt_log2);
+        let mut logits = [0.0f32; CARDS];
+        for &(card, value) in &values {
+            logits[card as usize] = value as f32;
+        }
+        let overridden = rng.gen_bool(self.override_rate);
+        let card = if overridden {
+            heuristic_card(&observation(state, state.current_player))
+        } else {
+            pick_card(&logits, legal, self.temperature, rng)
+        };
+        let best = values.iter().map(|&(_, v)| v).max().unwrap();
+        (card, best - logits[card as usize] as Score, overridden)
+    }
+}
+
+/// One deal played out by noisy players.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HumanPlayGame {
+    /// Hands as dealt.
+    #[pyo3(get)]
+    pub hands: [u32; 4],
+    #[pyo3(get)]
+    pub trump: u8,
+    /// The 32 cards in play order, South leading.
+    #[pyo3(get)]
+    pub plays: Vec<u8>,
+    /// Final points of NS and EW.
+    #[pyo3(get)]
+    pub points: [u16; 2],
+    /// Points each card cost the team playing it, against its best card.
+    #[pyo3(get)]
+    pub point_loss: Vec<Score>,
+    /// Whether each card came from the rule of thumb.
+    #[pyo3(get)]
+    pub overridden: Vec<bool>,
+}
+
+#[pymethods]
+impl HumanPlayGame {
+    /// ID of the deal, see `deal_id`.
+    #[getter]
+    pub fn deal_id(&self) -> u128 {
+        deal_id(&self.hands).expect("generated hands make a complete deal")
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "HumanPlayGame(trump={}, points={:?}, point_loss={})",
+            self.trump,
+            self.points,
+            self.point_loss.iter().sum::<Score>()
+        )
+    }
+}
+
+/// Plays `state` to the end with `noise`: the cards, their point losses and
+/// overrides, and the final state.
+fn play_out<R: Rng>(
+    mut state: PlayingState,
+    noise: &NoiseModel,
+    tt_log2: Option<u8>,
+    rng: &mut R,
+) -> (Vec<(u8, Score, bool)>, PlayingState) {
+    let mut moves = Vec::new();
+    while !state.is_terminal() {
+        let chosen = noise.choose(&state, tt_log2, rng);
+        state.play_card(chosen.0);
+        moves.push(chosen);
+    }
+    (moves, state)
+}
+
+/// `num_games` random deals (random suit trump, South leading, as in self-play)
+/// played to the end by four noisy players. With a seed, game `i` only depends
+/// on (seed, i).
+pub fn generate_human_play_batch(
+    num_games: usize,
+    noise: &NoiseModel,
+    seed: Option<u64>,
+    tt_log2: Option<u8>,
+) -> Vec<HumanPlayGame> {
+    let games = (0..num_games)
+        .into_par_iter()
+        .map(|i| {
+            let mut rng = sample_rng(seed, i as u64);
+            let hands = generate_random_hands(&mut rng);
+            let mut state = PlayingState::new(rng.gen_range(0..4));
+            state.hands = hands;
+            let (moves, end) = play_out(state, noise, tt_log2, &mut rng);
+            HumanPlayGame {
+                hands,
+                trump: end.trump,
+                plays: moves.iter().map(|m| m.0).collect(),
+                points: end.points,
+                point_loss: moves.iter().map(|m| m.1).collect(),
+                overridden: moves.iter().map(|m| m.2).collect(),
+            }
+        })
+        .collect();
+    crate::profiling::dump("generate_human_play_batch");
+    games
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::solver::{random_ending, solve};
+
+    #[test]
+    fn test_noise_model() {
+        let mut rng = sample_rng(Some(4), 0);
+        // An ending where the player to move has a losing card.
+        let state = (0..)
+            .map(|_| random_ending(12, &mut rng))
+            .find(|s| {
+                let values = solve_root_moves(s, (s.current_player % 2) as usize, Some(32), None);
+                values.iter().any(|&(_, v)| v != values[0].1)
+            })
+            .unwrap();
+
+        // Without noise every card is a best one: the deal ends at its value.
+        let exact = NoiseModel::new(0.0, 0.0).unwrap();
+        let (moves, end) = play_out(state, &exact, None, &mut rng);
+        assert_eq!(moves.len(), 12);
+        assert!(moves.iter().all(|&(_, loss, over)| loss == 0 && !over));
+        assert_eq!(
+            end.points[0] as Score,
+            solve(&state, false, Some(32), None).0
+        );
+
+        // Hot players make it, and overrides play the rule of thumb.
+        let noisy = NoiseModel::new(1000.0, 0.0).unwrap();
+        assert!((0..50).any(|_| noisy.choose(&state, None, &mut rng).1 > 0));
+        let autopilot = NoiseModel::new(0.0, 1.0).unwrap();
+        let (card, loss, overridden) = autopilot.choose(&state, None, &mut rng);
+        assert!(overridden && loss >= 0);
+        assert_eq!(
+            card,
+            heuristic_card(&observation(&state, state.current_player))
+        );
+
+        assert!(NoiseModel::new(-1.0, 0.0).is_err());
+        assert!(NoiseModel::new(1.0, 1.5).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+//! Post-processing of solved score labels into other training targets. All
+//! transforms read the declarer's final points (solve with the "declarer"
+//! perspective) and the contract value, so one solve pass can feed several
+//! objectives.
+
+/// Card points of a deal, dix de der included: the normalization scale.
+const DEAL_POINTS: f32 = 162.0;
+
+/// Points above the contract from which a deal counts as overtricks: enough to
+/// have made the next bid up.
+const OVERTRICK_MARGIN: f32 = 10.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LabelTransform {
+    /// The declarer's points, unchanged.
+    Points,
+    /// Declarer points minus the contract value.
+    Margin,
+    /// The margin over the points of a deal, clamped to [-1, 1].
+    Normalized,
+    /// 0 = down, 1 = made, 2 = overtricks (made by `OVERTRICK_MARGIN` or more).
+    Outcome,
+}
+
+impl LabelTransform {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "points" => Ok(LabelTransform::Points),
+            "margin" => Ok(LabelTransform::Margin),
+            "normalized" => Ok(LabelTransform::Normalized),
+            "outcome" => Ok(LabelTransform::Outcome),
+            _ => Err(format!(
+                "Unknown label transform '{}' (expected 'points', 'margin', 'normalized' or 'outcome')",
+                name
+            )),
+        }
+    }
+
+    /// Canonical name, as stored in dataset metadata.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LabelTransform::Points => "points",
+            LabelTransform::Margin => "margin",
+            LabelTransform::Normalized => "normalized",
+            LabelTransform::Outcome => "outcome",
+        }
+    }
+
+    /// Label of a deal where the declarer ends with `score` points for a
+    /// `contract`-point bid. A contract is made with as many points as its value.
+    pub fn apply(&self, score: f32, contract: u8) -> f32 {
+        let margin = score - contract as f32;
+        match self {
+            LabelTransform::Points => score,
+            LabelTransform::Margin => margin,
+            LabelTransform::Normalized => (margin / DEAL_POINTS).clamp(-1.0, 1.0),
+            LabelTransform::Outcome if margin < 0.0 => 0.0,
+            LabelTransform::Outcome if margin < OVERTRICK_MARGIN => 1.0,
+            LabelTransform::Outcome => 2.0,
+        }
+    }
+}
+
+/// Applies `transform` to a column of declarer scores, one contract per score.
+pub fn transform_labels(
+    scores: &[f32],
+    contracts: &[u8],
+    transform: LabelTransform,
+) -> Result<Vec<f32>, String> {
+    if scores.len() != contracts.len() {
+        return Err(format!(
+            "{} scores for {} contracts, expected one contract per score",
+            scores.len(),
+            contracts.len()
+        ));
+    }
+    Ok(scores
+        .iter()
+        .zip(contracts)
+        .map(|(&s, &c)| transform.apply(s, c))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_transforms() {
+        let scores = [62.0, 90.0, 99.5, 162.0, 252.0];
+        let contracts = [80, 90, 90, 80, 250];
+        let label = |t| transform_labels(&scores, &contracts, t).unwrap();
+
+        assert_eq!(label(LabelTransform::Points), scores);
+        assert_eq!(label(LabelTransform::Margin), [-18.0, 0.0, 9.5, 82.0, 2.0]);
+        assert_eq!(label(LabelTransform::Outcome), [0.0, 1.0, 1.0, 2.0, 1.0]);
+        let normalized = label(LabelTransform::Normalized);
+        assert!(normalized.iter().all(|v| (-1.0..=1.0).contains(v)));
+        assert_eq!(LabelTransform::Normalized.apply(0.0, 250), -1.0);
+
+        assert!(transform_labels(&scores, &contracts[1..], LabelTransform::Margin).is_err());
+        assert_eq!(
+            LabelTransform::parse(LabelTransform::Outcome.name()),
+            Ok(LabelTransform::Outcome)
+        );
+        assert!(LabelTransform::parse("ratio").is_err());
+    }
+}
@@ -0,0 +1,353 @@
+//! Consolidation of dataset shards, e.g. from generation runs on several machines,
+//! into size-balanced shards.
+//!
+//! Every input must have the same columns, in the same order and with the same
+//! types, and agree on the metadata giving the meaning of the labels
+//! (`LABEL_METADATA_KEYS`; a key absent from one input must be absent from all).
+//! The `solve_outcomes` counts of the inputs are summed; any other metadata key is
+//! kept when every input defining it agrees. Rows keep their order and are streamed
+//! one batch at a time; see `shuffle` to mix them instead.
+
+use arrow::datatypes::{Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::schema::SCHEMA_VERSION_KEY;
+use super::shuffle::writer;
+use super::verify::parquet_files;
+
+/// Metadata keys every input must agree on.
+pub const LABEL_METADATA_KEYS: &[&str] = &[
+    SCHEMA_VERSION_KEY,
+    "score_perspective",
+    "score_label",
+    "score_objective",
+    "contract_source",
+    "auction_layout",
+];
+
+/// Metadata key of the per-outcome solve counts (JSON object), summed on merge.
+const SOLVE_OUTCOMES_KEY: &str = "solve_outcomes";
+
+#[derive(Clone, Debug)]
+pub struct MergeOptions {
+    /// Largest shard; shards are as many as needed and differ by at most one row.
+    pub rows_per_shard: usize,
+    /// Number of shards, overriding `rows_per_shard`.
+    pub shards: Option<usize>,
+    /// Check the inputs without writing anything.
+    pub dry_run: bool,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        MergeOptions {
+            rows_per_shard: 1_000_000,
+            shards: None,
+            dry_run: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MergeReport {
+    pub files: usize,
+    pub rows: usize,
+    /// Rows of every output shard.
+    pub shard_rows: Vec<usize>,
+    /// Written shards (none for a dry run).
+    pub shards: Vec<PathBuf>,
+    pub metadata: HashMap<String, String>,
+}
+
+/// Checks that the Parquet files found at `inputs` (files or directories) can be
+/// merged and writes their rows to size-balanced shards `part-00000.parquet`, ...
+/// of `output_dir`.
+pub fn merge_datasets(
+    inputs: &[PathBuf],
+    output_dir: &Path,
+    options: &MergeOptions,
+) -> Result<MergeReport, String> {
+    if options.rows_per_shard == 0 || options.shards == Some(0) {
+        return Err("rows_per_shard and shards must be positive".to_string());
+    }
+    let mut files = Vec::new();
+    for root in inputs {
+        files.extend(parquet_files(root)?);
+    }
+    if files.is_empty() {
+        return Err("No Parquet file found in the inputs".to_string());
+    }
+
+    let mut schemas = Vec::new();
+    let mut rows = 0;
+    for path in &files {
+        let builder = open(path)?;
+        rows += builder.metadata().file_metadata().num_rows() as usize;
+        schemas.push(builder.schema().clone());
+    }
+    let schema = merged_schema(&files, &schemas)?;
+    let shard_rows = balanced_shards(rows, options);
+    let mut report = MergeReport {
+        files: files.len(),
+        rows,
+        shard_rows,
+        shards: Vec::new(),
+        metadata: schema.metadata().clone(),
+    };
+    if options.dry_run {
+        return Ok(report);
+    }
+
+    if output_dir.exists() && !parquet_files(output_dir)?.is_empty() {
+        return Err(format!(
+            "{} already holds Parquet files",
+            output_dir.display()
+        ));
+    }
+    std::fs::create_dir_all(output_dir).map_err(|e| format!("{}: {}", output_dir.display(), e))?;
+
+    let mut sizes = report.shard_rows.iter();
+    let mut current: Option<(ArrowWriter<File>, usize, usize)> = None;
+    for path in &files {
+        let reader = open(path)?
+            .build()
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        for batch in reader {
+            let batch = batch.map_err(|e| format!("{}: {}", path.display(), e))?;
+            let batch = RecordBatch::try_new(schema.clone(), batch.columns().to_vec())
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            let mut offset = 0;
+            while offset < batch.num_rows() {
+                let (w, written, size) = match current.as_mut() {
+                    Some(c) => c,
+                    None => {
+                        let size = *sizes.next().expect("the shards hold every row");
+                        let shard =
+                            output_dir.join(format!("part-{:05}.parquet", report.shards.len()));
+                        let w = writer(&shard, &schema, WriterProperties::builder().build())?;
+                        report.shards.push(shard);
+                        current.insert((w, 0, size))
+                    }
+                };
+                let take = (*size - *written).min(batch.num_rows() - offset);
+                w.write(&batch.slice(offset, take))
+                    .map_err(|e| e.to_string())?;
+                *written += take;
+                offset += take;
+                if written == size {
+                    let (w, _, _) = current.take().unwrap();
+                    w.close().map_err(|e| e.to_string())?;
+                }
+            }
+        }
+    }
+    if let Some((w, _, _)) = current {
+        w.close().map_err(|e| e.to_string())?;
+    }
+    Ok(report)
+}
+
+fn open(path: &Path) -> Result<ParquetRecordBatchReaderBuilder<File>, String> {
+    File::open(path)
+        .map_err(|e| e.to_string())
+        .and_then(|f| ParquetRecordBatchReaderBuilder::try_new(f).map_err(|e| e.to_string()))
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Rows of each output shard: as few shards as `rows_per_shard` allows (or
+/// `shards`), their sizes differing by at most one.
+fn balanced_shards(rows: usize, options: &MergeOptions) -> Vec<usize> {
+    let shards = options
+        .shards
+        .unwrap_or(rows.div_ceil(options.rows_per_shard))
+        .clamp(1, rows.max(1));
+    (0..shards)
+        .map(|i| rows / shards + usize::from(i < rows % shards))
+        .collect()
+}
+
+/// Schema of the merged shards: the columns of the first input, nullable where any
+/// input is, with the merged metadata.
+fn merged_schema(files: &[PathBuf], schemas: &[SchemaRef]) -> Result<SchemaRef, String> {
+    let first = &schemas[0];
+    let mut nullable: Vec<bool> = first.fields().iter().map(|f| f.is_nullable()).collect();
+    let mut metadata: HashMap<String, String> = HashMap::new();
+    let mut conflicts = Vec::new();
+    let mut outcomes: BTreeMap<String, u64> = BTreeMap::new();
+
+    for (path, schema) in files.iter().zip(schemas) {
+        let columns = |s: &SchemaRef| -> Vec<String> {
+            s.fields()
+                .iter()
+                .map(|f| format!("{}: {}", f.name(), f.data_type()))
+                .collect()
+        };
+        if columns(schema) != columns(first) {
+            return Err(format!(
+                "{} has columns {:?}, expected {:?} (from {})",
+                path.display(),
+                columns(schema),
+                columns(first),
+                files[0].display()
+            ));
+        }
+        for (n, field) in nullable.iter_mut().zip(schema.fields()) {
+            *n |= field.is_nullable();
+        }
+        for key in LABEL_METADATA_KEYS {
+            let (value, expected) = (schema.metadata().get(*key), first.metadata().get(*key));
+            if value != expected {
+                return Err(format!(
+                    "{}: metadata {} is {:?}, expected {:?} (from {})",
+                    path.display(),
+                    key,
+                    value,
+                    expected,
+                    files[0].display()
+                ));
+            }
+        }
+        for (key, value) in schema.metadata() {
+            if key == SOLVE_OUTCOMES_KEY {
+                let counts: HashMap<String, u64> = serde_json::from_str(value)
+                    .map_err(|e| format!("{}: metadata {}: {}", path.display(), key, e))?;
+                for (outcome, count) in counts {
+                    *outcomes.entry(outcome).or_default() += count;
+                }
+                continue;
+            }
+            match metadata.get(key) {
+                Some(v) if v != value => conflicts.push(key.clone()),
+                Some(_) => {}
+                None => {
+                    metadata.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+    for key in conflicts {
+        metadata.remove(&key);
+    }
+    if !outcomes.is_empty() {
+        metadata.insert(
+            SOLVE_OUTCOMES_KEY.to_string(),
+            serde_json::to_string(&outcomes).unwrap(),
+        );
+    }
+
+    let fields: Vec<Field> = first
+        .fields()
+        .iter()
+        .zip(nullable)
+        .map(|(f, n)| f.as_ref().clone().with_nullable(n))
+        .collect();
+    Ok(Arc::new(Schema::new(fields).with_metadata(metadata)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{ArrayRef, AsArray, Float32Array, UInt32Array, UInt8Array};
+    use arrow::datatypes::UInt32Type;
+
+    fn gameplay(path: &Path, rows: std::ops::Range<u32>, metadata: &[(&str, &str)]) {
+        let n = rows.len();
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            ("hand", Arc::new(UInt32Array::from_iter_values(rows))),
+            ("trump", Arc::new(UInt8Array::from(vec![2; n]))),
+            ("best_score", Arc::new(Float32Array::from(vec![81.0; n]))),
+        ];
+        let batch = RecordBatch::try_from_iter(columns).unwrap();
+        let schema = Arc::new(
+            batch.schema().as_ref().clone().with_metadata(
+                metadata
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ),
+        );
+        let batch = RecordBatch::try_new(schema.clone(), batch.columns().to_vec()).unwrap();
+        let mut writer = ArrowWriter::try_new(File::create(path).unwrap(), schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_merge_datasets() {
+        let dir = std::env::temp_dir().join(format!("merge_test_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("a")).unwrap();
+        std::fs::create_dir_all(dir.join("b")).unwrap();
+        let label = ("score_label", "double_dummy");
+        gameplay(
+            &dir.join("a/part-0.parquet"),
+            0..45,
+            &[
+                label,
+                ("solve_outcomes", r#"{"solved": 45}"#),
+                ("host", "a"),
+            ],
+        );
+        gameplay(
+            &dir.join("b/part-0.parquet"),
+            45..100,
+            &[
+                label,
+                ("solve_outcomes", r#"{"solved": 50, "forced": 5}"#),
+                ("host", "b"),
+            ],
+        );
+        let inputs = [dir.join("a"), dir.join("b")];
+
+        let options = MergeOptions {
+            rows_per_shard: 30,
+            ..MergeOptions::default()
+        };
+        let report = merge_datasets(&inputs, &dir.join("out"), &options).unwrap();
+        assert_eq!((report.files, report.rows), (2, 100));
+        assert_eq!(report.shard_rows, vec![25; 4]);
+        assert_eq!(
+            report.metadata["solve_outcomes"],
+            r#"{"forced":5,"solved":95}"#
+        );
+        assert!(!report.metadata.contains_key("host"));
+
+        let mut hands: Vec<u32> = Vec::new();
+        for (shard, &rows) in report.shards.iter().zip(&report.shard_rows) {
+            let builder = open(shard).unwrap();
+            assert_eq!(builder.schema().metadata()["score_label"], "double_dummy");
+            let batches: Vec<RecordBatch> = builder.build().unwrap().map(|b| b.unwrap()).collect();
+            assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), rows);
+            for b in &batches {
+                hands.extend(b.column(0).as_primitive::<UInt32Type>().values().iter());
+            }
+        }
+        assert_eq!(hands, (0..100).collect::<Vec<_>>());
+        assert!(merge_datasets(&inputs, &dir.join("out"), &options).is_err());
+
+        let three = MergeOptions {
+            shards: Some(3),
+            dry_run: true,
+            ..options
+        };
+        let report = merge_datasets(&inputs, &dir.join("dry"), &three).unwrap();
+        assert_eq!(report.shard_rows, vec![34, 33, 33]);
+        assert!(report.shards.is_empty() && !dir.join("dry").exists());
+
+        // Labels of another kind cannot be mixed in.
+        gameplay(
+            &dir.join("b/part-1.parquet"),
+            0..5,
+            &[("score_label", "ev")],
+        );
+        let err = merge_datasets(&inputs, &dir.join("dry"), &three).unwrap_err();
+        assert!(err.contains("metadata score_label"), "{}", err);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
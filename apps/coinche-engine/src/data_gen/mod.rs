@@ -1,6 +1,52 @@
 pub mod bidding;
+pub mod checkpoint;
 pub mod common;
+pub mod deal_ids;
+pub mod difficulty;
+pub mod distributed;
+pub mod evaluation;
 pub mod gameplay;
+pub mod hand_percentile;
+pub mod human_play;
+pub mod labels;
+pub mod merge;
+pub mod motifs;
+pub mod opening_leads;
+pub mod policy_eval;
+pub mod puzzles;
+pub mod quotas;
+pub mod reader;
+pub mod schema;
+pub mod selfplay;
+pub mod shuffle;
+pub mod vec_env;
+pub mod verify;
 
-pub use bidding::{generate_hand_batch, solve_hand_batch, write_bidding_parquet};
-pub use gameplay::{generate_raw_gameplay_batch, solve_gameplay_batch};
+pub use bidding::{
+    generate_hand_batch, solve_hand_batch, solve_leaders_batch, write_bidding_parquet,
+};
+pub use checkpoint::CheckpointConfig;
+pub use deal_ids::recompute_deal_ids;
+pub use difficulty::{deal_difficulty, difficulty_batch, downsample_easy_positions, Downsampling};
+pub use evaluation::{generate_evaluation_batch, EvaluationSample};
+pub use gameplay::{
+    generate_gameplay_batch, generate_gameplay_batch_for_side, generate_positions_batch,
+    generate_positions_for_hand, generate_raw_gameplay_batch,
+    generate_raw_gameplay_batch_with_plays, solve_gameplay_batch, solve_pimc_parallel,
+    AdaptivePimc, BidConstraint, ContractSource, GameplayBatch, GameplaySample,
+    GameplaySolveOptions, GameplaySolveRequest, InvalidReason, PimcConfidence, PimcDecision,
+    PimcVoting, ScoreLabel, SideFilter, StageConfig,
+};
+pub use hand_percentile::hand_percentile;
+pub use human_play::{generate_human_play_batch, HumanPlayGame, NoiseModel};
+pub use labels::{transform_labels, LabelTransform};
+pub use motifs::{deal_motifs, motifs_batch, Motif};
+pub use opening_leads::{generate_opening_lead_batch, OpeningLeadSample};
+pub use policy_eval::{evaluate_policy_on_dataset, PolicyEvaluation};
+pub use puzzles::{generate_puzzles, Puzzle, PuzzleConstraints};
+pub use quotas::{generate_quota_batch, DealProperty, DealQuota};
+pub use reader::{GameplayReader, GameplayRecord};
+pub use schema::SchemaVersion;
+pub use selfplay::SelfPlayGame;
+pub use vec_env::VecCoincheEnv;
+pub use verify::{verify_dataset, VerificationReport};
@@ -0,0 +1,280 @@
+//! Deal tags: notable double-dummy motifs of the card play, for themed training
+//! sets and puzzle generation.
+//!
+//! A deal is played out along its principal variation. In the last
+//! `MOTIF_CARDS` cards, each position where the side to move has a single best
+//! card (every other one costs its team at least `min_swing` points) is checked
+//! for the motifs of that card, and each discard for a squeeze:
+//!
+//! - throw-in: the only good card gives up a trick the player could have won,
+//!   putting the opponents on lead;
+//! - trump promotion: the only good card is a ruff the opponents overruff, after
+//!   which the partner wins a trick with a trump;
+//! - discard squeeze: a player who can neither follow nor trump gives a trick
+//!   away whatever it discards: after each discard, the opponents later win a
+//!   trick in the discarded suit with a card its best card of that suit beat.
+//!
+//! Detection follows the solver's line, one of possibly several optimal ones, so
+//! tags are indicative rather than exhaustive.
+
+use crate::gameplay::analysis::strength;
+use crate::gameplay::playing::{PlayingState, ALL_TRUMP};
+use crate::solver::{solve, solve_root_moves, Score};
+use rayon::prelude::*;
+
+use super::bidding::validate_deals;
+
+/// Cards left below which positions are searched for motifs.
+pub const MOTIF_CARDS: u32 = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Motif {
+    ThrowIn,
+    TrumpPromotion,
+    DiscardSqueeze,
+}
+
+impl Motif {
+    pub const ALL: [Motif; 3] = [Motif::ThrowIn, Motif::TrumpPromotion, Motif::DiscardSqueeze];
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        Motif::ALL
+            .into_iter()
+            .find(|m| m.name() == name.to_ascii_lowercase())
+            .ok_or_else(|| {
+                format!(
+                    "Unknown motif '{}' (expected 'throw_in', 'trump_promotion' or 'discard_squeeze')",
+                    name
+                )
+            })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Motif::ThrowIn => "throw_in",
+            Motif::TrumpPromotion => "trump_promotion",
+            Motif::DiscardSqueeze => "discard_squeeze",
+        }
+    }
+}
+
+fn is_trump(card: u8, trump: u8) -> bool {
+    trump == ALL_TRUMP || card / 8 == trump
+}
+
+fn cards_left(state: &PlayingState) -> u32 {
+    state.hands.iter().map(|h| h.count_ones()).sum()
+}
+
+/// Best card of the player to move.
+fn best_move(state: &PlayingState, tt_log2: Option<u8>) -> u8 {
+    solve(state, false, Some(32), tt_log2).1
+}
+
+/// Tricks completed from `state` to the end of the deal with both sides playing
+/// the solver's line after `card`: (winner, card it won with) of each.
+fn tricks_after(state: &PlayingState, card: u8, tt_log2: Option<u8>) -> Vec<(u8, u8)> {
+    let mut state = *state;
+    let mut card = card;
+    let mut tricks = Vec::new();
+    loop {
+        state.play_card(card);
+        if state.trick_size == 0 {
+            let winner = state.last_trick_winner.expect("a trick was completed");
+            tricks.push((winner, state.last_trick[winner as usize]));
+        }
+        if state.is_terminal() {
+            return tricks;
+        }
+        card = best_move(&state, tt_log2);
+    }
+}
+
+/// Best card among `values` (the mover's team points of each legal card, lowest
+/// card on ties) and the points the next best card costs. None with a single
+/// legal card.
+pub(crate) fn best_with_swing(values: &[(u8, Score)]) -> Option<(u8, Score)> {
+    let &(card, best) = values
+        .iter()
+        .max_by_key(|&&(c, v)| (v, std::cmp::Reverse(c)))?;
+    let next = values
+        .iter()
+        .filter(|&&(c, _)| c != card)
+        .map(|&(_, v)| v)
+        .max()?;
+    Some((card, best - next))
+}
+
+/// Card of the player to move all others are at least `min_swing` points worse
+/// than for its team, if any.
+fn unique_best(state: &PlayingState, min_swing: Score, tt_log2: Option<u8>) -> Option<u8> {
+    let team = (state.current_player % 2) as usize;
+    let values = solve_root_moves(state, team, Some(32), tt_log2);
+    best_with_swing(&values)
+        .filter(|&(_, swing)| swing >= min_swing)
+        .map(|(card, _)| card)
+}
+
+/// Whether `card` gives up a trick the player to move could have won.
+pub(crate) fn is_throw_in(state: &PlayingState, card: u8, tt_log2: Option<u8>) -> bool {
+    let team = state.current_player % 2;
+    let wins_trick = |c: u8| tricks_after(state, c, tt_log2)[0].0 % 2 == team;
+    let legal = state.get_legal_moves();
+    !wins_trick(card) && (0..32u8).any(|c| c != card && legal & (1 << c) != 0 && wins_trick(c))
+}
+
+/// Whether `card` is a ruff the opponents overruff, the partner later winning a
+/// trick with a trump.
+pub(crate) fn is_trump_promotion(state: &PlayingState, card: u8, tt_log2: Option<u8>) -> bool {
+    let trump = state.trump;
+    if trump >= 4 || state.trick_size == 0 || !is_trump(card, trump) {
+        return false;
+    }
+    let led = state.current_trick[state.trick_starter as usize];
+    if is_trump(led, trump) {
+        return false;
+    }
+    let team = state.current_player % 2;
+    let tricks = tricks_after(state, card, tt_log2);
+    let (winner, winning) = tricks[0];
+    let overruffed = winner % 2 != team && is_trump(winning, trump) && winning != card;
+    let partner = (state.current_player + 2) % 4;
+    overruffed
+        && tricks[1..]
+            .iter()
+            .any(|&(w, c)| w == partner && is_trump(c, trump))
+}
+
+/// Whether every discard of the player to move gives a trick away in its suit.
+pub(crate) fn is_squeezed(state: &PlayingState, tt_log2: Option<u8>) -> bool {
+    if state.trick_size == 0 {
+        return false;
+    }
+    let seat = state.current_player;
+    let hand = state.hands[seat as usize];
+    let led_suit = state.current_trick[state.trick_starter as usize] / 8;
+    let legal = state.get_legal_moves();
+    let discards: Vec<u8> = (0..32u8).filter(|&c| legal & (1 << c) != 0).collect();
+    let suits = discards.iter().fold(0u8, |m, &c| m | 1 << (c / 8));
+    if suits.count_ones() < 2
+        || discards
+            .iter()
+            .any(|&c| c / 8 == led_suit || is_trump(c, state.trump))
+    {
+        return false;
+    }
+    discards.iter().all(|&d| {
+        let suit = d / 8;
+        let guard = (0..32u8)
+            .filter(|&c| c / 8 == suit && hand & (1 << c) != 0)
+            .map(|c| strength(c, state.trump))
+            .max()
+            .unwrap();
+        tricks_after(state, d, tt_log2)[1..]
+            .iter()
+            .any(|&(w, c)| w % 2 != seat % 2 && c / 8 == suit && strength(c, state.trump) < guard)
+    })
+}
+
+/// Motifs met along the solver's line from `state`, each once, in `Motif` order.
+pub fn position_motifs(state: &PlayingState, min_swing: Score, tt_log2: Option<u8>) -> Vec<Motif> {
+    let mut motifs = Vec::new();
+    let mut state = *state;
+    while !state.is_terminal() {
+        let card = if cards_left(&state) > MOTIF_CARDS {
+            best_move(&state, tt_log2)
+        } else {
+            if is_squeezed(&state, tt_log2) {
+                motifs.push(Motif::DiscardSqueeze);
+            }
+            match unique_best(&state, min_swing, tt_log2) {
+                Some(card) => {
+                    if is_throw_in(&state, card, tt_log2) {
+                        motifs.push(Motif::ThrowIn);
+                    }
+                    if is_trump_promotion(&state, card, tt_log2) {
+                        motifs.push(Motif::TrumpPromotion);
+                    }
+                    card
+                }
+                None => best_move(&state, tt_log2),
+            }
+        };
+        state.play_card(card);
+    }
+    motifs.sort();
+    motifs.dedup();
+    motifs
+}
+
+/// Motifs of the full deal `hands` played in `trump`, South leading (the
+/// convention of the bidding datasets).
+pub fn deal_motifs(
+    hands: &[u32; 4],
+    trump: u8,
+    min_swing: Score,
+    tt_log2: Option<u8>,
+) -> Vec<Motif> {
+    let mut state = PlayingState::new(trump);
+    state.hands = *hands;
+    position_motifs(&state, min_swing, tt_log2)
+}
+
+/// `deal_motifs` over a batch: 4 flattened hands and one trump per deal.
+pub fn motifs_batch(
+    flattened_hands: &[u32],
+    trumps: &[u8],
+    min_swing: Score,
+    tt_log2: Option<u8>,
+) -> Result<Vec<Vec<Motif>>, String> {
+    validate_deals(flattened_hands, trumps)?;
+    Ok(flattened_hands
+        .par_chunks(4)
+        .zip(trumps.par_iter())
+        .map(|(chunk, &trump)| deal_motifs(chunk.try_into().unwrap(), trump, min_swing, tt_log2))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::snapshot::load_state;
+
+    #[test]
+    fn test_position_motifs() {
+        for motif in Motif::ALL {
+            assert_eq!(Motif::parse(&motif.name().to_uppercase()), Ok(motif));
+        }
+        assert!(Motif::parse("coup_en_blanc").is_err());
+
+        // Endings of random play, one per motif.
+        let cases = [
+            (
+                "trump=H turn=N lead=S hands=7D,JD/JS,JC/9S,JH,KH/9D,KD,8H trick=S:7H,W:10D \
+                 tricks=3,2 points=69,59 belote=1,0 last=E:QD,S:AD,W:8D,N:7S>S rules=90,true",
+                Motif::ThrowIn,
+            ),
+            (
+                "trump=S turn=W lead=N hands=KD,QC/AD,8S,10S/8D,QD/JS,QS trick=N:8H,E:KH,S:9S \
+                 tricks=3,2 points=54,26 belote=0,0 last=W:10C,N:AS,E:9C,S:KC>N rules=90,true",
+                Motif::TrumpPromotion,
+            ),
+            (
+                "trump=NT turn=S lead=S hands=AD,KH,10C/9H,JH,7C/KD,JC,AC/AH,9C,KC trick=- \
+                 tricks=4,1 points=40,29 belote=0,0 last=W:7H,N:QH,E:8H,S:10H>S rules=90,true",
+                Motif::DiscardSqueeze,
+            ),
+        ];
+        for (text, motif) in cases {
+            let state = load_state(text).unwrap();
+            let motifs = position_motifs(&state, 10, None);
+            assert!(motifs.contains(&motif), "{:?} in {}", motifs, text);
+            assert!(motifs.windows(2).all(|w| w[0] < w[1]));
+            // Card-choice motifs need a single good card: none is that good.
+            let strict = position_motifs(&state, 163, None);
+            assert!(strict
+                .iter()
+                .all(|m| *m == Motif::DiscardSqueeze && motifs.contains(m)));
+        }
+    }
+}
@@ -0,0 +1,198 @@
+//! Opening lead tables: deals in which a fixed contract was bid, with the
+//! double-dummy value of every card the defender on lead may start with.
+//!
+//! The declarer deals, so the defender on its left leads. The auction is the
+//! declarer's bid after three passes, then three passes. Deals are redrawn until
+//! one hand could have made the bid (`BidConstraint::is_consistent`), and that
+//! hand goes to the declarer.
+
+use crate::gameplay::bidding::{AuctionAction, Bid};
+use crate::gameplay::bot::BotAction;
+use crate::gameplay::deal::deal_id;
+use crate::gameplay::encoding::flat_action_index;
+use crate::gameplay::playing::PlayingState;
+use crate::solver::{solve_root_moves, Score};
+use pyo3::prelude::*;
+use rayon::prelude::*;
+
+use super::common::{generate_random_hands, sample_rng};
+use super::gameplay::BidConstraint;
+
+/// Deals drawn per sample before giving up on finding a hand for the contract.
+const MAX_DEALS: usize = 10_000;
+
+/// One deal of an opening lead table.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpeningLeadSample {
+    /// Hands as dealt, by seat.
+    #[pyo3(get)]
+    pub hands: [u32; 4],
+    #[pyo3(get)]
+    pub declarer: u8,
+    /// Seat on lead, left of the declarer.
+    #[pyo3(get)]
+    pub leader: u8,
+    #[pyo3(get)]
+    pub contract: Bid,
+    /// Calls in order from the leader, as flat action indices (see `encoding`).
+    #[pyo3(get)]
+    pub auction: Vec<u8>,
+    /// Cards the leader may lead, in index order.
+    #[pyo3(get)]
+    pub leads: Vec<u8>,
+    /// Double-dummy points of the defenders after each lead, best play after it.
+    #[pyo3(get)]
+    pub lead_scores: Vec<Score>,
+}
+
+#[pymethods]
+impl OpeningLeadSample {
+    /// ID of the deal, see `deal_id`.
+    #[getter]
+    pub fn deal_id(&self) -> u128 {
+        deal_id(&self.hands).expect("generated hands make a complete deal")
+    }
+
+    /// Best opening lead; ties go to the lowest card index.
+    pub fn best_lead(&self) -> u8 {
+        let best = self.lead_scores.iter().max().unwrap_or(&0);
+        let i = self.lead_scores.iter().position(|s| s == best).unwrap_or(0);
+        self.leads[i]
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "OpeningLeadSample(declarer={}, leader={}, leads={:?}, lead_scores={:?})",
+            self.declarer, self.leader, self.leads, self.lead_scores
+        )
+    }
+}
+
+/// Calls of the auction, from the leader: three passes, the contract, three passes.
+fn auction(contract: Bid) -> Vec<u8> {
+    let pass = BotAction::Call(AuctionAction::Pass);
+    let bid = BotAction::Call(AuctionAction::Bid(contract));
+    [pass, pass, pass, bid, pass, pass, pass]
+        .iter()
+        .map(|&a| flat_action_index(a).expect("valid contract") as u8)
+        .collect()
+}
+
+/// A deal from `rng` in which `declarer` holds a hand that could have bid `contract`.
+fn deal_for_contract<R: rand::Rng>(
+    contract: Bid,
+    declarer: u8,
+    rng: &mut R,
+) -> Result<[u32; 4], String> {
+    let constraint = BidConstraint::new(declarer, contract, 0);
+    (0..MAX_DEALS)
+        .find_map(|_| {
+            let dealt = generate_random_hands(rng);
+            let seat = (0..4).find(|&s| constraint.is_consistent(dealt[s]))?;
+            // Rotate the deal so that the fitting hand sits with the declarer.
+            Some(std::array::from_fn(|s| {
+                dealt[(s + 4 + seat - declarer as usize) % 4]
+            }))
+        })
+        .ok_or_else(|| {
+            format!(
+                "No hand for contract {} {} in {} deals",
+                contract.value, contract.trump, MAX_DEALS
+            )
+        })
+}
+
+/// Opening lead table of `hands` in which `declarer` bid `contract`.
+fn lead_table(
+    hands: [u32; 4],
+    contract: Bid,
+    declarer: u8,
+    tt_log2: Option<u8>,
+) -> OpeningLeadSample {
+    let leader = (declarer + 1) % 4;
+    let mut state = PlayingState::new(contract.trump);
+    state.hands = hands;
+    state.current_player = leader;
+    state.trick_starter = leader;
+    let (leads, lead_scores) = solve_root_moves(&state, (leader % 2) as usize, Some(32), tt_log2)
+        .into_iter()
+        .unzip();
+    OpeningLeadSample {
+        hands,
+        declarer,
+        leader,
+        contract,
+        auction: auction(contract),
+        leads,
+        lead_scores,
+    }
+}
+
+/// Opening lead tables of `num_deals` deals in which `declarer` bid `contract`.
+/// With a seed, deal `i` only depends on (seed, i).
+pub fn generate_opening_lead_batch(
+    num_deals: usize,
+    contract: Bid,
+    declarer: u8,
+    seed: Option<u64>,
+    tt_log2: Option<u8>,
+) -> Result<Vec<OpeningLeadSample>, String> {
+    if declarer >= 4 {
+        return Err(format!("Invalid declarer {}", declarer));
+    }
+    flat_action_index(BotAction::Call(AuctionAction::Bid(contract)))?;
+    let samples = (0..num_deals)
+        .into_par_iter()
+        .map(|i| {
+            let hands = deal_for_contract(contract, declarer, &mut sample_rng(seed, i as u64))?;
+            Ok(lead_table(hands, contract, declarer, tt_log2))
+        })
+        .collect();
+    crate::profiling::dump("generate_opening_lead_batch");
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::playing::{CLUBS, DIAMONDS, HEARTS, SPADES};
+
+    fn card(suit: u8, rank: u8) -> u8 {
+        suit * 8 + rank
+    }
+
+    #[test]
+    fn test_deal_for_contract() {
+        let contract = Bid::new(120, HEARTS);
+        let mut rng = sample_rng(Some(4), 0);
+        for declarer in 0..4 {
+            let hands = deal_for_contract(contract, declarer, &mut rng).unwrap();
+            assert!(
+                BidConstraint::new(declarer, contract, 0).is_consistent(hands[declarer as usize])
+            );
+            assert_eq!(hands.iter().fold(0, |m, &h| m | h), u32::MAX);
+        }
+        assert!(generate_opening_lead_batch(1, Bid::new(85, HEARTS), 0, None, None).is_err());
+        assert!(generate_opening_lead_batch(1, contract, 4, None, None).is_err());
+    }
+
+    #[test]
+    fn test_lead_table() {
+        // Two cards each, N declarer in hearts, E on lead. The AD wins its trick
+        // and the AS is then trumped; leading the AS first draws N's jack, and
+        // the AD takes the der.
+        let hands = [
+            1 << card(SPADES, 0) | 1 << card(SPADES, 1),
+            1 << card(CLUBS, 1) | 1 << card(CLUBS, 2),
+            1 << card(HEARTS, 4) | 1 << card(DIAMONDS, 0),
+            1 << card(SPADES, 7) | 1 << card(DIAMONDS, 7),
+        ];
+        let table = lead_table(hands, Bid::new(80, HEARTS), 2, None);
+        assert_eq!((table.declarer, table.leader), (2, 3));
+        assert_eq!(table.leads, vec![card(DIAMONDS, 7), card(SPADES, 7)]);
+        assert_eq!(table.lead_scores, vec![11, 21]);
+        assert_eq!(table.best_lead(), card(SPADES, 7));
+        assert_eq!(table.auction.len(), 7);
+    }
+}
@@ -0,0 +1,282 @@
+//! Agent quality measured on a gameplay dataset: how often a bot strength plays a
+//! labelled best card, and how many points its own card gives away.
+//!
+//! Only rows storing the full position (`deal`, `tricks_won` and `player`) can be
+//! played by an agent, and rows with a single legal card say nothing about it; both
+//! are left out. A card matches when it is in the row's `optimal_cards` (`best_card`
+//! alone in files without that column). The loss of a card is its double-dummy value
+//! for the mover's team against the best card's, in the `score_objective` of the
+//! file metadata; it is measured on the true deal whatever the file's `score_label`.
+
+use pyo3::prelude::*;
+use rayon::prelude::*;
+use std::path::Path;
+
+use super::reader::{GameplayReader, GameplayRecord};
+use crate::gameplay::bot::{strength_card, BotStrength};
+use crate::solver::{solve_root_moves_with_objective, Objective};
+
+/// Rows solved together, so a dataset is never held in memory at once.
+const CHUNK_ROWS: usize = 1024;
+
+#[pyclass]
+#[derive(Debug, Clone, Default)]
+pub struct PolicyEvaluation {
+    /// Bot strength evaluated ("heuristic", "pimc", ...).
+    #[pyo3(get)]
+    pub policy: String,
+    #[pyo3(get)]
+    pub rows_total: usize,
+    /// Rows with a full position and a choice of cards.
+    #[pyo3(get)]
+    pub rows_evaluated: usize,
+    /// Evaluated rows where the agent played an optimal card.
+    #[pyo3(get)]
+    pub matches: usize,
+    /// Sum of the value lost by the agent's cards over the evaluated rows.
+    #[pyo3(get)]
+    pub total_loss: f64,
+    /// Largest loss of a single card.
+    #[pyo3(get)]
+    pub max_loss: f64,
+}
+
+#[pymethods]
+impl PolicyEvaluation {
+    /// Fraction of the evaluated rows where the agent's card matches (0 without rows).
+    pub fn match_rate(&self) -> f64 {
+        if self.rows_evaluated == 0 {
+            0.0
+        } else {
+            self.matches as f64 / self.rows_evaluated as f64
+        }
+    }
+
+    /// Average value lost per evaluated row (0 without rows).
+    pub fn mean_loss(&self) -> f64 {
+        if self.rows_evaluated == 0 {
+            0.0
+        } else {
+            self.total_loss / self.rows_evaluated as f64
+        }
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "PolicyEvaluation(policy={}, evaluated={}/{}, match_rate={:.3}, mean_loss={:.2}, max_loss={})",
+            self.policy,
+            self.rows_evaluated,
+            self.rows_total,
+            self.match_rate(),
+            self.mean_loss(),
+            self.max_loss
+        )
+    }
+}
+
+/// Plays `strength` (PIMC with `iterations` worlds, reproducible with `seed`) at the
+/// rows of the dataset at `path` (a Parquet file or a directory of Parquet files)
+/// and compares its cards with the labels; only the first `max_rows` rows when given.
+pub fn evaluate_policy_on_dataset(
+    path: &Path,
+    strength: BotStrength,
+    iterations: usize,
+    seed: Option<u64>,
+    max_rows: Option<usize>,
+    tt_log2: Option<u8>,
+) -> Result<PolicyEvaluation, String> {
+    let mut reader = GameplayReader::open(path)?;
+    let metadata = reader.metadata();
+    let objective = match metadata.get("score_objective") {
+        Some(o) => Objective::parse(o)?,
+        None => Objective::Points,
+    };
+
+    let mut evaluation = PolicyEvaluation {
+        policy: strength.name().to_string(),
+        ..Default::default()
+    };
+    let limit = max_rows.unwrap_or(usize::MAX);
+    loop {
+        let chunk: Vec<GameplayRecord> = reader
+            .by_ref()
+            .take(CHUNK_ROWS.min(limit - evaluation.rows_total))
+            .collect::<Result<_, _>>()?;
+        if chunk.is_empty() {
+            break;
+        }
+        let offset = evaluation.rows_total;
+        evaluation.rows_total += chunk.len();
+        let outcomes: Vec<Option<(bool, f64)>> = chunk
+            .par_iter()
+            .enumerate()
+            .map(|(i, record)| {
+                let row_seed = seed.map(|s| s.wrapping_add((offset + i) as u64));
+                evaluate_row(record, strength, iterations, row_seed, objective, tt_log2)
+            })
+            .collect();
+        for (matched, loss) in outcomes.into_iter().flatten() {
+            evaluation.rows_evaluated += 1;
+            evaluation.matches += matched as usize;
+            evaluation.total_loss += loss;
+            evaluation.max_loss = evaluation.max_loss.max(loss);
+        }
+    }
+    Ok(evaluation)
+}
+
+/// Whether the agent's card at `record` matches, and the value it loses; `None` for
+/// rows without a full position or a choice of cards.
+fn evaluate_row(
+    record: &GameplayRecord,
+    strength: BotStrength,
+    iterations: usize,
+    seed: Option<u64>,
+    objective: Objective,
+    tt_log2: Option<u8>,
+) -> Option<(bool, f64)> {
+    let state = record.state()?;
+    if state.get_legal_moves().count_ones() < 2 {
+        return None;
+    }
+    let seat = state.current_player;
+    let card = strength_card(&state, seat, strength, iterations, seed, &[], [0; 4]);
+    let optimal = record.optimal_cards.unwrap_or(1 << record.best_card);
+    let matched = optimal & (1 << card) != 0;
+    // Even the labelled best card is solved: a PIMC label can lose on the true deal.
+    let values =
+        solve_root_moves_with_objective(&state, (seat % 2) as usize, objective, Some(32), tt_log2);
+    let best = values.iter().map(|&(_, v)| v).max()?;
+    let value = values.iter().find(|&&(c, _)| c == card)?.1;
+    Some((matched, (best - value) as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_gen::gameplay::{generate_positions_for_hand, reconstruct_state, StageConfig};
+    use crate::solver::solve_for_team;
+    use arrow::array::{
+        ArrayRef, Float32Array, ListBuilder, UInt32Array, UInt32Builder, UInt8Array, UInt8Builder,
+    };
+    use arrow::record_batch::RecordBatch;
+    use parquet::arrow::ArrowWriter;
+    use std::fs::File;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_evaluate_policy_on_dataset() {
+        let path = std::env::temp_dir().join(format!("policy_eval_{}.parquet", std::process::id()));
+        let config = StageConfig {
+            opening_weight: 0,
+            midgame_weight: 0,
+            endgame_weight: 1,
+        };
+        let (hands, boards, _, trumps, tricks_won, players) =
+            generate_positions_for_hand(0x0F0F_0000, 12, &config, Some(1)).unwrap();
+        let n = players.len();
+        let deal = |i: usize| -> [u32; 4] { hands[i * 4..i * 4 + 4].try_into().unwrap() };
+        let (cards, scores): (Vec<u8>, Vec<f32>) = (0..n)
+            .map(|i| {
+                let state = reconstruct_state(
+                    deal(i),
+                    &boards[i],
+                    trumps[i],
+                    [tricks_won[i][0], tricks_won[i][1]],
+                    players[i],
+                );
+                let (score, card) = solve_for_team(&state, (players[i] % 2) as usize, None, None);
+                (card, score as f32)
+            })
+            .unzip();
+
+        let u8_lists = |values: &[Vec<u8>]| -> ArrayRef {
+            let mut builder = ListBuilder::new(UInt8Builder::new());
+            for v in values {
+                builder.values().append_slice(v);
+                builder.append(true);
+            }
+            Arc::new(builder.finish())
+        };
+        let write = |cards: Vec<u8>| {
+            let mut deals = ListBuilder::new(UInt32Builder::new());
+            for i in 0..n {
+                deals.values().append_slice(&deal(i));
+                deals.append(true);
+            }
+            let columns: Vec<(&str, ArrayRef)> = vec![
+                (
+                    "hand",
+                    Arc::new(UInt32Array::from_iter_values(
+                        (0..n).map(|i| deal(i)[players[i] as usize]),
+                    )),
+                ),
+                ("board", u8_lists(&boards)),
+                ("trump", Arc::new(UInt8Array::from(trumps.clone()))),
+                ("best_card", Arc::new(UInt8Array::from(cards))),
+                ("best_score", Arc::new(Float32Array::from(scores.clone()))),
+                ("deal", Arc::new(deals.finish())),
+                ("tricks_won", u8_lists(&tricks_won)),
+                ("player", Arc::new(UInt8Array::from(players.clone()))),
+            ];
+            let batch = RecordBatch::try_from_iter(columns).unwrap();
+            let mut writer =
+                ArrowWriter::try_new(File::create(&path).unwrap(), batch.schema(), None).unwrap();
+            writer.write(&batch).unwrap();
+            writer.close().unwrap();
+        };
+        write(cards);
+
+        // The solver that labelled the rows agrees with itself.
+        let solver =
+            evaluate_policy_on_dataset(&path, BotStrength::DoubleDummy, 1, None, None, None)
+                .unwrap();
+        assert_eq!(solver.rows_total, n);
+        assert!(solver.rows_evaluated > 0);
+        assert_eq!(solver.match_rate(), 1.0);
+        assert_eq!(solver.total_loss, 0.0);
+
+        let heuristic =
+            evaluate_policy_on_dataset(&path, BotStrength::Heuristic, 1, None, None, None).unwrap();
+        assert_eq!(heuristic.rows_evaluated, solver.rows_evaluated);
+        assert!(heuristic.total_loss >= 0.0 && heuristic.max_loss <= heuristic.total_loss);
+        assert!(heuristic.matches <= heuristic.rows_evaluated);
+        assert_eq!(heuristic.policy, "heuristic");
+
+        // Labels that are the agent's own cards, as a PIMC label can be, still cost
+        // what the cards lose on the true deal.
+        write(
+            (0..n)
+                .map(|i| {
+                    let state = reconstruct_state(
+                        deal(i),
+                        &boards[i],
+                        trumps[i],
+                        [tricks_won[i][0], tricks_won[i][1]],
+                        players[i],
+                    );
+                    let seat = state.current_player;
+                    strength_card(&state, seat, BotStrength::Heuristic, 1, None, &[], [0; 4])
+                })
+                .collect(),
+        );
+        let labelled =
+            evaluate_policy_on_dataset(&path, BotStrength::Heuristic, 1, None, None, None).unwrap();
+        assert_eq!(labelled.match_rate(), 1.0);
+        assert!(heuristic.total_loss > 0.0);
+        assert_eq!(labelled.total_loss, heuristic.total_loss);
+
+        let pimc = |seed| {
+            evaluate_policy_on_dataset(&path, BotStrength::Pimc, 4, Some(seed), Some(5), None)
+                .unwrap()
+        };
+        let first = pimc(3);
+        assert_eq!(first.rows_total, 5.min(n));
+        assert_eq!(first.total_loss, pimc(3).total_loss);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(
+            evaluate_policy_on_dataset(&path, BotStrength::Heuristic, 1, None, None, None).is_err()
+        );
+    }
+}
@@ -0,0 +1,321 @@
+//! Card-play puzzles: positions where a single legal card reaches the
+//! double-dummy optimum and every other one costs its team a clear swing, for
+//! training apps.
+//!
+//! Candidates are random endings (`random_ending`) with a number of cards left
+//! drawn in the constraints' range. A puzzle keeps the values of every legal
+//! card, the motifs of the solution (see `motifs`) and a one-sentence
+//! explanation, and `Puzzle::to_json` gives the one-line JSON record apps load.
+
+use crate::gameplay::notation::{card_name, seat_name, trump_name};
+use crate::gameplay::playing::PlayingState;
+use crate::gameplay::snapshot::dump_state;
+use crate::solver::{random_ending, solve_root_moves, Score};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::Rng;
+use rayon::prelude::*;
+use serde_json::json;
+
+use super::common::sample_rng;
+use super::motifs::{best_with_swing, is_squeezed, is_throw_in, is_trump_promotion, Motif};
+
+/// What a generated position must satisfy to be a puzzle.
+#[pyclass]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PuzzleConstraints {
+    /// Points every other legal card costs the side to move, at least.
+    #[pyo3(get)]
+    pub min_swing: Score,
+    #[pyo3(get)]
+    pub min_cards: u8,
+    #[pyo3(get)]
+    pub max_cards: u8,
+    /// Motif the solution must show, if any.
+    pub motif: Option<Motif>,
+    /// Positions tried per puzzle before giving it up.
+    #[pyo3(get)]
+    pub max_attempts: usize,
+}
+
+impl Default for PuzzleConstraints {
+    fn default() -> Self {
+        PuzzleConstraints {
+            min_swing: 20,
+            min_cards: 4,
+            max_cards: 16,
+            motif: None,
+            max_attempts: 1000,
+        }
+    }
+}
+
+impl PuzzleConstraints {
+    pub fn new(
+        min_swing: Score,
+        min_cards: u8,
+        max_cards: u8,
+        motif: Option<Motif>,
+        max_attempts: usize,
+    ) -> Result<Self, String> {
+        if min_swing <= 0 {
+            return Err(format!("Minimum swing must be positive, got {}", min_swing));
+        }
+        // A single card left leaves no choice.
+        if min_cards < 2 || min_cards > max_cards || max_cards > 32 {
+            return Err(format!(
+                "Invalid cards left range {}..={}",
+                min_cards, max_cards
+            ));
+        }
+        Ok(PuzzleConstraints {
+            min_swing,
+            min_cards,
+            max_cards,
+            motif,
+            max_attempts,
+        })
+    }
+}
+
+#[pymethods]
+impl PuzzleConstraints {
+    #[new]
+    #[pyo3(signature = (min_swing=20, min_cards=4, max_cards=16, motif=None, max_attempts=1000))]
+    fn py_new(
+        min_swing: Score,
+        min_cards: u8,
+        max_cards: u8,
+        motif: Option<&str>,
+        max_attempts: usize,
+    ) -> PyResult<Self> {
+        let motif = motif.map(Motif::parse).transpose();
+        motif
+            .and_then(|m| Self::new(min_swing, min_cards, max_cards, m, max_attempts))
+            .map_err(PyValueError::new_err)
+    }
+
+    #[getter(motif)]
+    fn motif_name(&self) -> Option<&'static str> {
+        self.motif.map(|m| m.name())
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "PuzzleConstraints(min_swing={}, cards={}..={}, motif={})",
+            self.min_swing,
+            self.min_cards,
+            self.max_cards,
+            self.motif.map_or("None", |m| m.name())
+        )
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Puzzle {
+    /// The position as `dump_state` text.
+    #[pyo3(get)]
+    pub state: String,
+    /// Seat to play.
+    #[pyo3(get)]
+    pub player: u8,
+    #[pyo3(get)]
+    pub cards_left: u8,
+    /// The only card reaching `value`.
+    #[pyo3(get)]
+    pub solution: u8,
+    /// Final points of the team to play after the solution, double dummy.
+    #[pyo3(get)]
+    pub value: Score,
+    /// Points the best other card costs that team.
+    #[pyo3(get)]
+    pub swing: Score,
+    /// (card, value) of every legal card, in card index order.
+    #[pyo3(get)]
+    pub moves: Vec<(u8, Score)>,
+    /// Motifs of the solution, in `Motif` order.
+    pub motifs: Vec<Motif>,
+    #[pyo3(get)]
+    pub explanation: String,
+}
+
+#[pymethods]
+impl Puzzle {
+    #[getter(motifs)]
+    fn motif_names(&self) -> Vec<&'static str> {
+        self.motifs.iter().map(|m| m.name()).collect()
+    }
+
+    /// One-line JSON record of the puzzle, cards and seats by name.
+    pub fn to_json(&self) -> String {
+        let moves: Vec<_> = self
+            .moves
+            .iter()
+            .map(|&(card, value)| json!({"card": card_name(card), "value": value}))
+            .collect();
+        json!({
+            "state": self.state,
+            "player": seat_name(self.player),
+            "cards_left": self.cards_left,
+            "solution": card_name(self.solution),
+            "value": self.value,
+            "swing": self.swing,
+            "moves": moves,
+            "motifs": self.motif_names(),
+            "explanation": self.explanation,
+        })
+        .to_string()
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "Puzzle(solution={}, value={}, swing={}, cards_left={})",
+            card_name(self.solution),
+            self.value,
+            self.swing,
+            self.cards_left
+        )
+    }
+}
+
+fn explanation(state: &PlayingState, puzzle: &Puzzle) -> String {
+    let team = ["NS", "EW"][(puzzle.player % 2) as usize];
+    let (alternative, _) = puzzle
+        .moves
+        .iter()
+        .filter(|&&(c, _)| c != puzzle.solution)
+        .max_by_key(|&&(c, v)| (v, std::cmp::Reverse(c)))
+        .expect("a puzzle has several legal cards");
+    let mut text = format!(
+        "{} to play in {}: only {} makes {} points for {}; {} makes {} at best ({} fewer).",
+        seat_name(puzzle.player),
+        trump_name(state.trump),
+        card_name(puzzle.solution),
+        puzzle.value,
+        team,
+        card_name(*alternative),
+        puzzle.value - puzzle.swing,
+        puzzle.swing
+    );
+    if !puzzle.motifs.is_empty() {
+        let names: Vec<String> = puzzle
+            .motifs
+            .iter()
+            .map(|m| m.name().replace('_', " "))
+            .collect();
+        text.push_str(&format!(" Motif: {}.", names.join(", ")));
+    }
+    text
+}
+
+/// The puzzle of `state` when it meets `constraints`, cards left aside.
+pub fn find_puzzle(
+    state: &PlayingState,
+    constraints: &PuzzleConstraints,
+    tt_log2: Option<u8>,
+) -> Option<Puzzle> {
+    let team = (state.current_player % 2) as usize;
+    let moves = solve_root_moves(state, team, Some(32), tt_log2);
+    let (solution, swing) =
+        best_with_swing(&moves).filter(|&(_, swing)| swing >= constraints.min_swing)?;
+
+    let mut motifs = Vec::new();
+    if is_throw_in(state, solution, tt_log2) {
+        motifs.push(Motif::ThrowIn);
+    }
+    if is_trump_promotion(state, solution, tt_log2) {
+        motifs.push(Motif::TrumpPromotion);
+    }
+    if is_squeezed(state, tt_log2) {
+        motifs.push(Motif::DiscardSqueeze);
+    }
+    if constraints.motif.is_some_and(|m| !motifs.contains(&m)) {
+        return None;
+    }
+
+    let value = moves.iter().find(|&&(c, _)| c == solution).unwrap().1;
+    let mut puzzle = Puzzle {
+        state: dump_state(state),
+        player: state.current_player,
+        cards_left: state.hands.iter().map(|h| h.count_ones()).sum::<u32>() as u8,
+        solution,
+        value,
+        swing,
+        moves,
+        motifs,
+        explanation: String::new(),
+    };
+    puzzle.explanation = explanation(state, &puzzle);
+    Some(puzzle)
+}
+
+/// Up to `n` puzzles meeting `constraints`. Puzzle `i` tries up to
+/// `max_attempts` random positions and is left out when none qualifies, so rare
+/// motifs may give fewer puzzles. With a seed, puzzle `i` only depends on
+/// (seed, i).
+pub fn generate_puzzles(
+    n: usize,
+    constraints: &PuzzleConstraints,
+    seed: Option<u64>,
+    tt_log2: Option<u8>,
+) -> Vec<Puzzle> {
+    let puzzles = (0..n)
+        .into_par_iter()
+        .filter_map(|i| {
+            let mut rng = sample_rng(seed, i as u64);
+            (0..constraints.max_attempts).find_map(|_| {
+                let cards_left = rng.gen_range(constraints.min_cards..=constraints.max_cards);
+                let state = random_ending(cards_left as u32, &mut rng);
+                find_puzzle(&state, constraints, tt_log2)
+            })
+        })
+        .collect();
+    crate::profiling::dump("generate_puzzles");
+    puzzles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::snapshot::load_state;
+
+    #[test]
+    fn test_generate_puzzles() {
+        let constraints = PuzzleConstraints::new(15, 4, 8, None, 200).unwrap();
+        let puzzles = generate_puzzles(4, &constraints, Some(7), None);
+        assert_eq!(puzzles.len(), 4);
+        for puzzle in &puzzles {
+            let state = load_state(&puzzle.state).unwrap();
+            assert_eq!(puzzle.player, state.current_player);
+            assert!((4..=8).contains(&puzzle.cards_left));
+            let team = (state.current_player % 2) as usize;
+            assert_eq!(puzzle.moves, solve_root_moves(&state, team, Some(32), None));
+            assert!(puzzle.swing >= 15);
+            for &(card, value) in &puzzle.moves {
+                if card == puzzle.solution {
+                    assert_eq!(value, puzzle.value);
+                } else {
+                    assert!(value <= puzzle.value - puzzle.swing);
+                }
+            }
+            assert!(puzzle.explanation.contains(&card_name(puzzle.solution)));
+
+            let record: serde_json::Value = serde_json::from_str(&puzzle.to_json()).unwrap();
+            assert_eq!(record["solution"], card_name(puzzle.solution));
+            assert_eq!(
+                record["moves"].as_array().unwrap().len(),
+                puzzle.moves.len()
+            );
+        }
+        assert_eq!(generate_puzzles(4, &constraints, Some(7), None), puzzles);
+
+        // No swing is that large: every puzzle is given up.
+        let impossible = PuzzleConstraints::new(300, 4, 8, None, 5).unwrap();
+        assert!(generate_puzzles(2, &impossible, Some(7), None).is_empty());
+
+        assert!(PuzzleConstraints::new(0, 4, 8, None, 10).is_err());
+        assert!(PuzzleConstraints::new(10, 1, 8, None, 10).is_err());
+        assert!(PuzzleConstraints::new(10, 9, 8, None, 10).is_err());
+    }
+}
@@ -0,0 +1,346 @@
+//! Batch-level deal distributions: quotas fixing the share of a batch's deals that
+//! have a property (a fit, a point range...), which per-deal strategies and
+//! `HandBuilder` constraints cannot control.
+//!
+//! Candidate deals are drawn (uniformly, or from a `HandBuilder`) and kept in draw
+//! order when they fit every quota still open: a deal with a quota's property is
+//! kept while the quota lacks deals, one without it while the other deals have room.
+//! Each quota ends up met exactly, as `round(fraction * batch_size)` deals. With a
+//! seed, candidate `i` only depends on (seed, i), so the batch does not depend on
+//! the thread count.
+
+use indicatif::{ProgressBar, ProgressStyle};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rayon::prelude::*;
+
+use super::common::{generate_random_hands, sample_rng, HandBuilder};
+use crate::gameplay::playing::cards_points;
+
+/// Candidates drawn per deal of the batch before the quotas are declared unreachable.
+pub const DEFAULT_MAX_DRAWS_PER_DEAL: u64 = 10_000;
+
+/// Candidates drawn in parallel between two acceptance passes.
+const DRAW_CHUNK: u64 = 4096;
+
+/// A property of a deal, checked on the hands of `seats` (bit i = seat i) together.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DealProperty {
+    /// `min` to `max` cards of `suit` between them; of their longest suit when `suit`
+    /// is `None` (e.g. an 8+ card fit for N-S).
+    SuitLength {
+        seats: u8,
+        suit: Option<u8>,
+        min: u8,
+        max: u8,
+    },
+    /// `min` to `max` card points between them, counted under `trump` (0-5).
+    Points {
+        seats: u8,
+        trump: u8,
+        min: u16,
+        max: u16,
+    },
+}
+
+impl DealProperty {
+    fn seats(&self) -> u8 {
+        match *self {
+            DealProperty::SuitLength { seats, .. } | DealProperty::Points { seats, .. } => seats,
+        }
+    }
+
+    pub fn holds(&self, hands: &[u32; 4]) -> bool {
+        let cards = (0..4)
+            .filter(|&s| self.seats() & (1 << s) != 0)
+            .fold(0u32, |m, s| m | hands[s]);
+        match *self {
+            DealProperty::SuitLength { suit, min, max, .. } => {
+                let length = |s: u8| (cards & (0xFF << (s * 8))).count_ones() as u8;
+                let length = match suit {
+                    Some(s) => length(s),
+                    None => (0..4).map(length).max().unwrap(),
+                };
+                (min..=max).contains(&length)
+            }
+            DealProperty::Points {
+                trump, min, max, ..
+            } => (min..=max).contains(&cards_points(cards, trump)),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let seats = self.seats();
+        if seats == 0 || seats > 0b1111 {
+            return Err(format!("Invalid seat mask {:#06b}", seats));
+        }
+        match *self {
+            DealProperty::SuitLength { suit, min, max, .. } => {
+                if suit.is_some_and(|s| s >= 4) {
+                    return Err(format!("Invalid suit {}", suit.unwrap()));
+                }
+                if min > max {
+                    return Err(format!("Empty length range {}..={}", min, max));
+                }
+            }
+            DealProperty::Points {
+                trump, min, max, ..
+            } => {
+                if trump >= 6 {
+                    return Err(format!("Invalid trump {}", trump));
+                }
+                if min > max {
+                    return Err(format!("Empty point range {}..={}", min, max));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Share `fraction` of a batch's deals having `property`.
+#[pyclass]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DealQuota {
+    pub property: DealProperty,
+    #[pyo3(get)]
+    pub fraction: f64,
+}
+
+fn seat_mask(seats: &[u8]) -> PyResult<u8> {
+    seats.iter().try_fold(0u8, |m, &s| {
+        if s >= 4 {
+            Err(PyValueError::new_err(format!("Invalid seat {}", s)))
+        } else {
+            Ok(m | 1 << s)
+        }
+    })
+}
+
+#[pymethods]
+impl DealQuota {
+    /// `fraction` of the deals where `seats` hold `min` to `max` cards of `suit`
+    /// together (of their longest suit without `suit`).
+    #[staticmethod]
+    #[pyo3(signature = (seats, fraction, min, max=8, suit=None))]
+    pub fn suit_length(
+        seats: Vec<u8>,
+        fraction: f64,
+        min: u8,
+        max: u8,
+        suit: Option<u8>,
+    ) -> PyResult<Self> {
+        let property = DealProperty::SuitLength {
+            seats: seat_mask(&seats)?,
+            suit,
+            min,
+            max,
+        };
+        DealQuota::new(property, fraction).map_err(PyValueError::new_err)
+    }
+
+    /// `fraction` of the deals where `seats` hold `min` to `max` card points
+    /// together, counted under `trump`.
+    #[staticmethod]
+    #[pyo3(signature = (seats, trump, fraction, min, max=162))]
+    pub fn points(seats: Vec<u8>, trump: u8, fraction: f64, min: u16, max: u16) -> PyResult<Self> {
+        let property = DealProperty::Points {
+            seats: seat_mask(&seats)?,
+            trump,
+            min,
+            max,
+        };
+        DealQuota::new(property, fraction).map_err(PyValueError::new_err)
+    }
+
+    /// Whether the deal `hands` has the quota's property.
+    #[pyo3(name = "holds")]
+    fn py_holds(&self, hands: [u32; 4]) -> bool {
+        self.property.holds(&hands)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("DealQuota({:?}, fraction={})", self.property, self.fraction)
+    }
+}
+
+impl DealQuota {
+    pub fn new(property: DealProperty, fraction: f64) -> Result<Self, String> {
+        property.validate()?;
+        if !(0.0..=1.0).contains(&fraction) {
+            return Err(format!("Quota fraction {} is not in [0, 1]", fraction));
+        }
+        Ok(DealQuota { property, fraction })
+    }
+
+    /// Deals of a batch of `batch_size` that must have the property.
+    pub fn target(&self, batch_size: usize) -> usize {
+        (self.fraction * batch_size as f64).round() as usize
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct QuotaBatch {
+    /// 4 hands per deal.
+    pub hands: Vec<u32>,
+    /// Candidates drawn.
+    pub draws: u64,
+}
+
+/// `batch_size` deals meeting every quota (see the module documentation), drawn
+/// from `builder` or uniformly; fails after `max_draws` candidates
+/// (`DEFAULT_MAX_DRAWS_PER_DEAL` per deal by default).
+pub fn generate_quota_batch(
+    quotas: &[DealQuota],
+    batch_size: usize,
+    builder: Option<&HandBuilder>,
+    seed: Option<u64>,
+    max_draws: Option<u64>,
+) -> Result<QuotaBatch, String> {
+    let targets: Vec<usize> = quotas.iter().map(|q| q.target(batch_size)).collect();
+    let max_draws = max_draws.unwrap_or(DEFAULT_MAX_DRAWS_PER_DEAL * batch_size as u64);
+
+    let pb = ProgressBar::new(batch_size as u64);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template(
+                "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta}) {msg}",
+            )
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+
+    let mut batch = QuotaBatch::default();
+    let mut counts = vec![0usize; quotas.len()];
+    let mut kept = 0;
+    while kept < batch_size {
+        if batch.draws >= max_draws {
+            pb.abandon();
+            let filled: Vec<String> = counts
+                .iter()
+                .zip(&targets)
+                .map(|(c, t)| format!("{}/{}", c, t))
+                .collect();
+            return Err(format!(
+                "Only {} of {} deals met the quotas in {} draws (quotas filled: {})",
+                kept,
+                batch_size,
+                batch.draws,
+                filled.join(", ")
+            ));
+        }
+        let chunk = DRAW_CHUNK.min(max_draws - batch.draws);
+        let candidates: Vec<([u32; 4], Vec<bool>)> = (batch.draws..batch.draws + chunk)
+            .into_par_iter()
+            .map(|i| {
+                let mut rng = sample_rng(seed, i);
+                let hands = match builder {
+                    Some(b) => b.build_with(&mut rng).map_err(|e| e.to_string())?,
+                    None => generate_random_hands(&mut rng),
+                };
+                let has = quotas.iter().map(|q| q.property.holds(&hands)).collect();
+                Ok((hands, has))
+            })
+            .collect::<Result<_, String>>()?;
+
+        for (hands, has) in candidates {
+            batch.draws += 1;
+            let fits = has.iter().zip(&counts).zip(&targets).all(|((&h, &c), &t)| {
+                if h {
+                    c < t
+                } else {
+                    kept - c < batch_size - t
+                }
+            });
+            if fits {
+                batch.hands.extend(hands);
+                for (count, h) in counts.iter_mut().zip(&has) {
+                    *count += *h as usize;
+                }
+                kept += 1;
+                pb.inc(1);
+                if kept == batch_size {
+                    break;
+                }
+            }
+        }
+        pb.set_message(format!(
+            "{:.1}% of draws kept",
+            100.0 * kept as f64 / batch.draws as f64
+        ));
+    }
+    pb.finish_and_clear();
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quotas_are_met_exactly() {
+        let fit = DealQuota::new(
+            DealProperty::SuitLength {
+                seats: 0b0101,
+                suit: None,
+                min: 8,
+                max: 8,
+            },
+            0.25,
+        )
+        .unwrap();
+        let strong = DealQuota::new(
+            DealProperty::Points {
+                seats: 0b0001,
+                trump: 1,
+                min: 50,
+                max: 162,
+            },
+            0.5,
+        )
+        .unwrap();
+        let quotas = [fit.clone(), strong.clone()];
+        let batch = generate_quota_batch(&quotas, 200, None, Some(3), None).unwrap();
+        let deals: Vec<[u32; 4]> = batch
+            .hands
+            .chunks(4)
+            .map(|c| c.try_into().unwrap())
+            .collect();
+        assert_eq!(deals.len(), 200);
+        assert_eq!(deals.iter().filter(|d| fit.property.holds(d)).count(), 50);
+        assert_eq!(
+            deals.iter().filter(|d| strong.property.holds(d)).count(),
+            100
+        );
+        assert!(deals
+            .iter()
+            .all(|d| d.iter().fold(0, |m, h| m | h) == u32::MAX));
+        assert!(batch.draws > 200);
+        let again = generate_quota_batch(&quotas, 200, None, Some(3), None).unwrap();
+        assert_eq!(again.hands, batch.hands);
+
+        // Deals from a builder keep its constraints.
+        let mut builder = HandBuilder::new(1);
+        builder.suit_length(0, 1, 4, 8);
+        let built = generate_quota_batch(&quotas[..1], 20, Some(&builder), Some(4), None).unwrap();
+        assert!(built
+            .hands
+            .chunks(4)
+            .all(|d| (d[0] >> 8 & 0xFF).count_ones() >= 4));
+
+        // Every South hand holding all eight spades cannot be drawn in time.
+        let spades = DealQuota::new(
+            DealProperty::SuitLength {
+                seats: 0b0001,
+                suit: Some(1),
+                min: 8,
+                max: 8,
+            },
+            1.0,
+        )
+        .unwrap();
+        let err = generate_quota_batch(&[spades], 10, None, Some(5), Some(1000)).unwrap_err();
+        assert!(err.contains("quotas filled: 0/10"), "{}", err);
+        assert!(DealQuota::new(fit.property, 1.5).is_err());
+    }
+}
@@ -0,0 +1,336 @@
+//! Reading gameplay datasets back into Rust: the rows of the Parquet files the
+//! generators write, decoded into records whose positions rebuild as
+//! `PlayingState`s, for the verification tool and other Rust-side consumers.
+//!
+//! Files are read one record batch at a time, so memory holds a batch, not the
+//! dataset. Columns added by later schema versions are optional: a file without
+//! `deal`, `tricks_won` and `player` still reads, but its rows have no full
+//! position to rebuild.
+
+use arrow::array::{Array, AsArray};
+use arrow::datatypes::{Float32Type, UInt32Type, UInt8Type};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::{ParquetRecordBatchReader, ParquetRecordBatchReaderBuilder};
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use super::gameplay::reconstruct_state;
+use super::verify::{dataset_kind, list_column, parquet_files, primitive_column, required};
+use crate::gameplay::playing::PlayingState;
+
+/// One row of a gameplay dataset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameplayRecord {
+    /// Hand of the player to move.
+    pub hand: u32,
+    /// Cards on the table, in play order.
+    pub board: Vec<u8>,
+    pub trump: u8,
+    pub best_card: u8,
+    /// NaN for forced moves labelled without a solve.
+    pub best_score: f32,
+    /// The four remaining hands, from schema version 2.
+    pub deal: Option<[u32; 4]>,
+    pub tricks_won: Option<[u8; 2]>,
+    pub player: Option<u8>,
+    /// Every card as good as `best_card` (bit i = card i), from schema version 3.
+    pub optimal_cards: Option<u32>,
+    /// From schema version 5 (see `deal_ids`).
+    pub deal_id: Option<u128>,
+    /// From schema version 6; false when absent.
+    pub forced: bool,
+}
+
+impl GameplayRecord {
+    /// The position of the row, when the file stores the full deal.
+    pub fn state(&self) -> Option<PlayingState> {
+        Some(reconstruct_state(
+            self.deal?,
+            &self.board,
+            self.trump,
+            self.tricks_won?,
+            self.player?,
+        ))
+    }
+}
+
+/// Records of every row of a gameplay batch.
+pub fn decode_gameplay_batch(batch: &RecordBatch) -> Result<Vec<GameplayRecord>, String> {
+    let hands = required(primitive_column::<UInt32Type>(batch, "hand")?, "hand")?;
+    let boards = required(list_column::<UInt8Type>(batch, "board")?, "board")?;
+    let trumps = required(primitive_column::<UInt8Type>(batch, "trump")?, "trump")?;
+    let cards = required(
+        primitive_column::<UInt8Type>(batch, "best_card")?,
+        "best_card",
+    )?;
+    let scores = required(
+        primitive_column::<Float32Type>(batch, "best_score")?,
+        "best_score",
+    )?;
+    let deals = list_column::<UInt32Type>(batch, "deal")?;
+    let tricks_won = list_column::<UInt8Type>(batch, "tricks_won")?;
+    let players = primitive_column::<UInt8Type>(batch, "player")?;
+    let optimal_cards = primitive_column::<UInt32Type>(batch, "optimal_cards")?;
+    let deal_ids = batch
+        .column_by_name("deal_id")
+        .map(|c| {
+            c.as_fixed_size_binary_opt()
+                .filter(|ids| ids.value_length() == 16)
+                .ok_or_else(|| "Column deal_id is not 16-byte binary".to_string())
+        })
+        .transpose()?;
+    let forced = batch
+        .column_by_name("forced")
+        .map(|c| {
+            c.as_boolean_opt()
+                .ok_or_else(|| "Column forced is not boolean".to_string())
+        })
+        .transpose()?;
+
+    Ok((0..batch.num_rows())
+        .map(|i| GameplayRecord {
+            hand: hands[i],
+            board: boards[i].clone(),
+            trump: trumps[i],
+            best_card: cards[i],
+            best_score: scores[i],
+            deal: deals
+                .as_ref()
+                .and_then(|d| <[u32; 4]>::try_from(d[i].as_slice()).ok()),
+            tricks_won: tricks_won
+                .as_ref()
+                .and_then(|t| <[u8; 2]>::try_from(t[i].as_slice()).ok()),
+            player: players.as_ref().map(|p| p[i]),
+            optimal_cards: optimal_cards.as_ref().map(|o| o[i]),
+            deal_id: deal_ids
+                .filter(|ids| ids.is_valid(i))
+                .map(|ids| u128::from_be_bytes(ids.value(i).try_into().unwrap())),
+            forced: forced.is_some_and(|f| f.is_valid(i) && f.value(i)),
+        })
+        .collect())
+}
+
+/// Records of a gameplay dataset (a Parquet file or a directory of Parquet files,
+/// read in sorted order), in row order.
+pub struct GameplayReader {
+    files: std::vec::IntoIter<PathBuf>,
+    file: Option<PathBuf>,
+    batches: Option<ParquetRecordBatchReader>,
+    records: std::vec::IntoIter<GameplayRecord>,
+    metadata: HashMap<String, String>,
+}
+
+impl GameplayReader {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let files = parquet_files(path)?;
+        if files.is_empty() {
+            return Err(format!("No Parquet file found at {}", path.display()));
+        }
+        let mut reader = GameplayReader {
+            files: files.into_iter(),
+            file: None,
+            batches: None,
+            records: Vec::new().into_iter(),
+            metadata: HashMap::new(),
+        };
+        reader.next_file()?;
+        Ok(reader)
+    }
+
+    /// Metadata of the first file: score perspective, label, objective, schema version...
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
+
+    /// Opens the next file; false once every file is read.
+    fn next_file(&mut self) -> Result<bool, String> {
+        let Some(file) = self.files.next() else {
+            self.batches = None;
+            return Ok(false);
+        };
+        let at = |e: String| format!("{}: {}", file.display(), e);
+        let builder = File::open(&file)
+            .map_err(|e| e.to_string())
+            .and_then(|f| ParquetRecordBatchReaderBuilder::try_new(f).map_err(|e| e.to_string()))
+            .map_err(at)?;
+        let kind = dataset_kind(builder.schema()).map_err(at)?;
+        if kind != "gameplay" {
+            return Err(at(format!("{} dataset, expected gameplay", kind)));
+        }
+        if self.file.is_none() {
+            self.metadata = builder.schema().metadata().clone();
+        }
+        self.batches = Some(builder.build().map_err(|e| at(e.to_string()))?);
+        self.file = Some(file);
+        Ok(true)
+    }
+
+    fn next_batch(&mut self) -> Result<bool, String> {
+        loop {
+            let Some(batches) = self.batches.as_mut() else {
+                return Ok(false);
+            };
+            match batches.next() {
+                Some(batch) => {
+                    let file = self.file.as_deref().unwrap_or(Path::new(""));
+                    let at = |e: String| format!("{}: {}", file.display(), e);
+                    let batch = batch.map_err(|e| at(e.to_string()))?;
+                    self.records = decode_gameplay_batch(&batch).map_err(at)?.into_iter();
+                    return Ok(true);
+                }
+                None => {
+                    if !self.next_file()? {
+                        return Ok(false);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for GameplayReader {
+    type Item = Result<GameplayRecord, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(record) = self.records.next() {
+                return Some(Ok(record));
+            }
+            match self.next_batch() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(e) => {
+                    // Stop after an error rather than repeat it.
+                    self.batches = None;
+                    self.files = Vec::new().into_iter();
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_gen::gameplay::{generate_positions_for_hand, StageConfig};
+    use crate::gameplay::snapshot::dump_state;
+    use arrow::array::{
+        ArrayRef, BooleanArray, FixedSizeBinaryArray, Float32Array, ListBuilder, UInt32Array,
+        UInt32Builder, UInt8Array, UInt8Builder,
+    };
+    use parquet::arrow::ArrowWriter;
+    use std::sync::Arc;
+
+    fn u8_lists(values: &[Vec<u8>]) -> ArrayRef {
+        let mut builder = ListBuilder::new(UInt8Builder::new());
+        for v in values {
+            builder.values().append_slice(v);
+            builder.append(true);
+        }
+        Arc::new(builder.finish())
+    }
+
+    #[test]
+    fn test_read_gameplay_dataset() {
+        let dir = std::env::temp_dir().join(format!("reader_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = StageConfig {
+            opening_weight: 1,
+            midgame_weight: 1,
+            endgame_weight: 1,
+        };
+        let (hands, boards, _, trumps, tricks_won, players) =
+            generate_positions_for_hand(0x0000_F0F0, 5, &config, Some(4)).unwrap();
+        let n = players.len();
+        let deal = |i: usize| -> [u32; 4] { hands[i * 4..i * 4 + 4].try_into().unwrap() };
+
+        let mut deals = ListBuilder::new(UInt32Builder::new());
+        for i in 0..n {
+            deals.values().append_slice(&deal(i));
+            deals.append(true);
+        }
+        let ids: Vec<Option<[u8; 16]>> = (0..n)
+            .map(|i| (i != 1).then(|| (i as u128 + 7).to_be_bytes()))
+            .collect();
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            (
+                "hand",
+                Arc::new(UInt32Array::from_iter_values(
+                    (0..n).map(|i| deal(i)[players[i] as usize]),
+                )),
+            ),
+            ("board", u8_lists(&boards)),
+            ("trump", Arc::new(UInt8Array::from(trumps.clone()))),
+            ("best_card", Arc::new(UInt8Array::from(vec![0; n]))),
+            (
+                "best_score",
+                Arc::new(Float32Array::from_iter_values((0..n).map(|i| i as f32))),
+            ),
+            ("deal", Arc::new(deals.finish())),
+            ("tricks_won", u8_lists(&tricks_won)),
+            ("player", Arc::new(UInt8Array::from(players.clone()))),
+            (
+                "deal_id",
+                Arc::new(
+                    FixedSizeBinaryArray::try_from_sparse_iter_with_size(ids.into_iter(), 16)
+                        .unwrap(),
+                ),
+            ),
+            (
+                "forced",
+                Arc::new(BooleanArray::from_iter((0..n).map(|i| Some(i == 2)))),
+            ),
+        ];
+        let batch = RecordBatch::try_from_iter(columns).unwrap();
+        // Two files, so the reader moves from one to the next.
+        for (name, rows) in [("a.parquet", 0..2), ("b.parquet", 2..n)] {
+            let part = batch.slice(rows.start, rows.len());
+            let mut writer =
+                ArrowWriter::try_new(File::create(dir.join(name)).unwrap(), part.schema(), None)
+                    .unwrap();
+            writer.write(&part).unwrap();
+            writer.close().unwrap();
+        }
+
+        let records: Vec<GameplayRecord> = GameplayReader::open(&dir)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(records.len(), n);
+        for (i, record) in records.iter().enumerate() {
+            let expected = reconstruct_state(
+                deal(i),
+                &boards[i],
+                trumps[i],
+                [tricks_won[i][0], tricks_won[i][1]],
+                players[i],
+            );
+            assert_eq!(dump_state(&record.state().unwrap()), dump_state(&expected));
+            assert_eq!(record.best_score, i as f32);
+            assert_eq!(record.forced, i == 2);
+            assert_eq!(record.deal_id, (i != 1).then_some(i as u128 + 7));
+        }
+
+        // Older layouts read too, without the full positions.
+        let old = batch.project(&[0, 1, 2, 3, 4]).unwrap();
+        let mut writer = ArrowWriter::try_new(
+            File::create(dir.join("a.parquet")).unwrap(),
+            old.schema(),
+            None,
+        )
+        .unwrap();
+        writer.write(&old).unwrap();
+        writer.close().unwrap();
+        let first = GameplayReader::open(&dir.join("a.parquet"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert!(first.state().is_none() && first.deal_id.is_none() && !first.forced);
+        assert!(GameplayReader::open(&dir.join("missing")).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,280 @@
+//! Frozen layouts of the gameplay exchange formats: the tuples returned to Python
+//! and the columns of gameplay Parquet files. Fields are only ever added in a new
+//! version; callers pin the version they were written against and keep getting
+//! exactly that layout.
+//!
+//! - V1: raw batches are the 6-tuple (flat hands, boards, history, trumps,
+//!   tricks_won, players); solved batches are (best_cards, best_scores as integers,
+//!   valid); files hold hand, board, history, trump, best_card, best_score.
+//! - V2: raw batches are `GameplayBatch`; solved batches are `SolvedGameplayBatch`
+//!   (float scores, solve cost, PIMC confidence); files add the solve cost, the
+//!   confidence and the full position (deal, tricks_won, player).
+//! - V3: solved batches and files add `optimal_cards`, the mask of every card as good
+//!   as the label (within the epsilon the batch was solved with).
+//! - V4: solved batches add `invalid_reasons`, the `InvalidReason` code of each sample
+//!   (0 if valid); files are unchanged, as they only hold valid samples.
+//! - V5: solved batches are unchanged; files add `deal_id`, the ID of the deal the
+//!   position comes from (see `gameplay::deal::deal_id`) as 16 big-endian bytes, to
+//!   join them with other datasets generated from the same deals.
+//! - V6: solved batches and files add `forced`, whether the seat to move had a single
+//!   legal card.
+
+use super::gameplay::SolvedGameplayBatch;
+
+/// Parquet metadata key holding the version a file was written with.
+pub const SCHEMA_VERSION_KEY: &str = "schema_version";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SchemaVersion {
+    V1,
+    V2,
+    V3,
+    V4,
+    V5,
+    V6,
+}
+
+/// Solved gameplay batch in the V1 layout: (best_cards, best_scores, valid).
+pub type SolvedGameplayBatchV1 = (Vec<u8>, Vec<i16>, Vec<bool>);
+
+/// Solved gameplay batch in the V2 layout: the V3 one without `optimal_cards`.
+pub type SolvedGameplayBatchV2 = (
+    Vec<u8>,
+    Vec<f32>,
+    Vec<bool>,
+    Vec<u64>,
+    Vec<u64>,
+    Vec<f32>,
+    Vec<f32>,
+    Vec<f32>,
+);
+
+/// Solved gameplay batch in the V3 layout: the V4 one without `invalid_reasons`.
+pub type SolvedGameplayBatchV3 = (
+    Vec<u8>,
+    Vec<f32>,
+    Vec<bool>,
+    Vec<u64>,
+    Vec<u64>,
+    Vec<f32>,
+    Vec<f32>,
+    Vec<f32>,
+    Vec<u32>,
+);
+
+/// Solved gameplay batch in the V4 and V5 layout: `SolvedGameplayBatch` without `forced`.
+pub type SolvedGameplayBatchV4 = (
+    Vec<u8>,
+    Vec<f32>,
+    Vec<bool>,
+    Vec<u64>,
+    Vec<u64>,
+    Vec<f32>,
+    Vec<f32>,
+    Vec<f32>,
+    Vec<u32>,
+    Vec<u8>,
+);
+
+const GAMEPLAY_COLUMNS_V1: &[&str] = &[
+    "hand",
+    "board",
+    "history",
+    "trump",
+    "best_card",
+    "best_score",
+];
+
+const GAMEPLAY_COLUMNS_V2: &[&str] = &[
+    "hand",
+    "board",
+    "history",
+    "trump",
+    "best_card",
+    "best_score",
+    "nodes_searched",
+    "solve_time_us",
+    "agreement",
+    "vote_entropy",
+    "value_variance",
+    "deal",
+    "tricks_won",
+    "player",
+];
+
+const GAMEPLAY_COLUMNS_V3: &[&str] = &[
+    "hand",
+    "board",
+    "history",
+    "trump",
+    "best_card",
+    "best_score",
+    "nodes_searched",
+    "solve_time_us",
+    "agreement",
+    "vote_entropy",
+    "value_variance",
+    "deal",
+    "tricks_won",
+    "player",
+    "optimal_cards",
+];
+
+const GAMEPLAY_COLUMNS_V5: &[&str] = &[
+    "hand",
+    "board",
+    "history",
+    "trump",
+    "best_card",
+    "best_score",
+    "nodes_searched",
+    "solve_time_us",
+    "agreement",
+    "vote_entropy",
+    "value_variance",
+    "deal",
+    "tricks_won",
+    "player",
+    "optimal_cards",
+    "deal_id",
+];
+
+const GAMEPLAY_COLUMNS_V6: &[&str] = &[
+    "hand",
+    "board",
+    "history",
+    "trump",
+    "best_card",
+    "best_score",
+    "nodes_searched",
+    "solve_time_us",
+    "agreement",
+    "vote_entropy",
+    "value_variance",
+    "deal",
+    "tricks_won",
+    "player",
+    "optimal_cards",
+    "deal_id",
+    "forced",
+];
+
+impl SchemaVersion {
+    pub const LATEST: SchemaVersion = SchemaVersion::V6;
+
+    /// Version from its number; `None` selects the latest one.
+    pub fn parse(version: Option<u32>) -> Result<Self, String> {
+        match version {
+            None => Ok(Self::LATEST),
+            Some(1) => Ok(SchemaVersion::V1),
+            Some(2) => Ok(SchemaVersion::V2),
+            Some(3) => Ok(SchemaVersion::V3),
+            Some(4) => Ok(SchemaVersion::V4),
+            Some(5) => Ok(SchemaVersion::V5),
+            Some(6) => Ok(SchemaVersion::V6),
+            Some(v) => Err(format!(
+                "Unknown schema version {} (this build supports 1 to {})",
+                v,
+                Self::LATEST.number()
+            )),
+        }
+    }
+
+    pub fn number(&self) -> u32 {
+        match self {
+            SchemaVersion::V1 => 1,
+            SchemaVersion::V2 => 2,
+            SchemaVersion::V3 => 3,
+            SchemaVersion::V4 => 4,
+            SchemaVersion::V5 => 5,
+            SchemaVersion::V6 => 6,
+        }
+    }
+
+    /// Columns of a gameplay dataset file, in order.
+    pub fn gameplay_columns(&self) -> &'static [&'static str] {
+        match self {
+            SchemaVersion::V1 => GAMEPLAY_COLUMNS_V1,
+            SchemaVersion::V2 => GAMEPLAY_COLUMNS_V2,
+            SchemaVersion::V3 | SchemaVersion::V4 => GAMEPLAY_COLUMNS_V3,
+            SchemaVersion::V5 => GAMEPLAY_COLUMNS_V5,
+            SchemaVersion::V6 => GAMEPLAY_COLUMNS_V6,
+        }
+    }
+}
+
+/// Narrows a solved batch to the V1 layout. Scores are rounded, so V1 cannot carry
+/// PIMC expected-value labels faithfully.
+pub fn solved_batch_v1(batch: SolvedGameplayBatch) -> SolvedGameplayBatchV1 {
+    let (cards, scores, valid, ..) = batch;
+    let scores = scores.iter().map(|s| s.round() as i16).collect();
+    (cards, scores, valid)
+}
+
+/// Narrows a solved batch to the V2 layout.
+pub fn solved_batch_v2(batch: SolvedGameplayBatch) -> SolvedGameplayBatchV2 {
+    let (cards, scores, valid, nodes, times, agreement, entropy, variance, ..) = batch;
+    (
+        cards, scores, valid, nodes, times, agreement, entropy, variance,
+    )
+}
+
+/// Narrows a solved batch to the V3 layout.
+pub fn solved_batch_v3(batch: SolvedGameplayBatch) -> SolvedGameplayBatchV3 {
+    let (cards, scores, valid, nodes, times, agreement, entropy, variance, optimal, ..) = batch;
+    (
+        cards, scores, valid, nodes, times, agreement, entropy, variance, optimal,
+    )
+}
+
+/// Narrows a solved batch to the V4 (and V5) layout.
+pub fn solved_batch_v4(batch: SolvedGameplayBatch) -> SolvedGameplayBatchV4 {
+    let (cards, scores, valid, nodes, times, agreement, entropy, variance, optimal, reasons, _) =
+        batch;
+    (
+        cards, scores, valid, nodes, times, agreement, entropy, variance, optimal, reasons,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions_only_add_columns() {
+        assert_eq!(SchemaVersion::parse(None), Ok(SchemaVersion::LATEST));
+        assert_eq!(SchemaVersion::parse(Some(1)), Ok(SchemaVersion::V1));
+        assert!(SchemaVersion::parse(Some(0)).is_err());
+        assert!(SchemaVersion::parse(Some(SchemaVersion::LATEST.number() + 1)).is_err());
+
+        let v1 = SchemaVersion::V1.gameplay_columns();
+        let v2 = SchemaVersion::V2.gameplay_columns();
+        let v3 = SchemaVersion::V3.gameplay_columns();
+        let v4 = SchemaVersion::V4.gameplay_columns();
+        let v5 = SchemaVersion::V5.gameplay_columns();
+        let v6 = SchemaVersion::V6.gameplay_columns();
+        assert_eq!(&v2[..v1.len()], v1);
+        assert_eq!(&v3[..v2.len()], v2);
+        assert_eq!(&v4[..v3.len()], v3);
+        assert_eq!(&v5[..v4.len()], v4);
+        assert_eq!(&v6[..v5.len()], v5);
+
+        let solved = (
+            vec![3],
+            vec![81.6],
+            vec![true],
+            vec![10],
+            vec![5],
+            vec![1.0],
+            vec![0.0],
+            vec![0.0],
+            vec![1 << 3 | 1 << 4],
+            vec![0],
+            vec![false],
+        );
+        assert_eq!(solved_batch_v4(solved.clone()).9, vec![0]);
+        assert_eq!(solved_batch_v3(solved.clone()).8, vec![1 << 3 | 1 << 4]);
+        assert_eq!(solved_batch_v2(solved.clone()).1, vec![81.6]);
+        assert_eq!(solved_batch_v1(solved), (vec![3], vec![82], vec![true]));
+    }
+}
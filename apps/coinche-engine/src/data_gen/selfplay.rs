@@ -0,0 +1,219 @@
+//! Self-play generation driven by a policy evaluated in batches.
+//!
+//! All games advance in lockstep: at each step the observation of the player to
+//! move in every game is encoded (in parallel), the policy is called
+//! once on the whole batch, and each game plays the card chosen from its row of
+//! logits. A Python network thus crosses the GIL once per step for thousands of
+//! games instead of once per card.
+
+use crate::gameplay::deal::deal_id;
+use crate::gameplay::encoding::{gameplay_features, CARDS};
+use crate::gameplay::playing::PlayingState;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rayon::prelude::*;
+
+use super::common::{generate_random_hands, sample_rng};
+
+/// One finished self-play deal.
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfPlayGame {
+    /// Hands as dealt.
+    #[pyo3(get)]
+    pub hands: [u32; 4],
+    #[pyo3(get)]
+    pub trump: u8,
+    /// The 32 cards in play order, South leading.
+    #[pyo3(get)]
+    pub plays: Vec<u8>,
+    /// Final points of NS and EW.
+    #[pyo3(get)]
+    pub points: [u16; 2],
+}
+
+#[pymethods]
+impl SelfPlayGame {
+    /// ID of the deal, see `deal_id`.
+    #[getter]
+    pub fn deal_id(&self) -> u128 {
+        deal_id(&self.hands).expect("generated hands make a complete deal")
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "SelfPlayGame(trump={}, points={:?})",
+            self.trump, self.points
+        )
+    }
+}
+
+/// A deal in progress, with its own random stream.
+pub(super) struct Game {
+    pub state: PlayingState,
+    dealt: [u32; 4],
+    history: u32,
+    plays: Vec<u8>,
+    rng: StdRng,
+}
+
+impl Game {
+    /// A random deal with a random suit trump, South leading, drawn from `rng`.
+    pub fn deal(mut rng: StdRng) -> Self {
+        let dealt = generate_random_hands(&mut rng);
+        let mut state = PlayingState::new(rng.gen_range(0..4));
+        state.hands = dealt;
+        Game {
+            state,
+            dealt,
+            history: 0,
+            plays: Vec::with_capacity(32),
+            rng,
+        }
+    }
+
+    /// Replaces the deal by the next one of its random stream.
+    pub fn redeal(&mut self) {
+        *self = Game::deal(self.rng.clone());
+    }
+
+    pub fn observation(&self) -> Vec<f32> {
+        let s = &self.state;
+        gameplay_features(
+            s.hands[s.current_player as usize],
+            self.history,
+            &s.current_trick,
+            s.trump,
+        )
+    }
+
+    pub fn play(&mut self, card: u8) {
+        self.state.play_card(card);
+        self.plays.push(card);
+        if self.plays.len().is_multiple_of(4) {
+            self.history |= self.plays[self.plays.len() - 4..]
+                .iter()
+                .fold(0, |m, &c| m | 1 << c);
+        }
+    }
+}
+
+/// Card picked from `logits` among the `legal` ones: the best one at temperature
+/// 0, otherwise a draw from the softmax of logits / temperature.
+pub(super) fn pick_card<R: Rng>(logits: &[f32], legal: u32, temperature: f32, rng: &mut R) -> u8 {
+    let cards = (0..CARDS as u8).filter(|&c| legal & (1 << c) != 0);
+    if temperature <= 0.0 {
+        return cards
+            .max_by(|&a, &b| {
+                logits[a as usize]
+                    .total_cmp(&logits[b as usize])
+                    .then(b.cmp(&a))
+            })
+            .unwrap();
+    }
+    let cards: Vec<u8> = cards.collect();
+    let top = cards
+        .iter()
+        .map(|&c| logits[c as usize])
+        .fold(f32::NEG_INFINITY, f32::max);
+    let weights: Vec<f32> = cards
+        .iter()
+        .map(|&c| ((logits[c as usize] - top) / temperature).exp())
+        .collect();
+    let mut draw = rng.gen::<f32>() * weights.iter().sum::<f32>();
+    for (&card, &w) in cards.iter().zip(&weights) {
+        if draw < w {
+            return card;
+        }
+        draw -= w;
+    }
+    *cards.last().unwrap()
+}
+
+/// Plays `num_games` random deals (random suit trump, South leading) to the end.
+/// `policy` receives the observations of every game, flattened
+/// row-major (`GAMEPLAY_FEATURES` per game, see `encoding::gameplay_features`),
+/// and their legal-move masks, and returns `CARDS` logits per game in the same
+/// order. Illegal cards are never played, whatever their logits.
+pub fn generate_selfplay_batch<F>(
+    num_games: usize,
+    temperature: f32,
+    seed: Option<u64>,
+    mut policy: F,
+) -> Result<Vec<SelfPlayGame>, String>
+where
+    F: FnMut(&[f32], &[u32]) -> Result<Vec<f32>, String>,
+{
+    let mut games: Vec<Game> = (0..num_games)
+        .into_par_iter()
+        .map(|i| Game::deal(sample_rng(seed, i as u64)))
+        .collect();
+
+    // Every game lasts exactly 32 cards, so they all finish together.
+    for _ in 0..32 {
+        let observations: Vec<f32> = games.par_iter().flat_map(Game::observation).collect();
+        let legal: Vec<u32> = games.iter().map(|g| g.state.get_legal_moves()).collect();
+        let logits = policy(&observations, &legal)?;
+        if logits.len() != games.len() * CARDS {
+            return Err(format!(
+                "Policy returned {} logits for {} games, expected {} per game",
+                logits.len(),
+                games.len(),
+                CARDS
+            ));
+        }
+        games
+            .par_iter_mut()
+            .zip(logits.par_chunks(CARDS))
+            .zip(legal.par_iter())
+            .for_each(|((game, row), &legal)| {
+                let card = pick_card(row, legal, temperature, &mut game.rng);
+                game.play(card);
+            });
+    }
+
+    Ok(games
+        .into_iter()
+        .map(|g| SelfPlayGame {
+            hands: g.dealt,
+            trump: g.state.trump,
+            plays: g.plays,
+            points: g.state.points,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::encoding::GAMEPLAY_FEATURES;
+
+    #[test]
+    fn test_generate_selfplay_batch() {
+        let mut calls = 0;
+        // Prefers low card indices: every game plays its lowest legal card.
+        let games = generate_selfplay_batch(8, 0.0, Some(7), |obs, legal| {
+            calls += 1;
+            assert_eq!(obs.len(), legal.len() * GAMEPLAY_FEATURES);
+            Ok((0..legal.len() * CARDS)
+                .map(|i| -((i % CARDS) as f32))
+                .collect())
+        })
+        .unwrap();
+        assert_eq!(calls, 32);
+        assert_eq!(games.len(), 8);
+        for g in &games {
+            assert_eq!(g.plays.iter().fold(0u32, |m, &c| m | 1 << c), u32::MAX);
+            // 162 plus any belote or capot bonus.
+            assert!(g.points[0] + g.points[1] >= 162);
+            assert_eq!(g.plays[0], g.hands[0].trailing_zeros() as u8);
+        }
+
+        // Sampling is reproducible from the seed, and bad policies are reported.
+        let uniform = |_: &[f32], legal: &[u32]| Ok(vec![0.0; legal.len() * CARDS]);
+        let a = generate_selfplay_batch(4, 1.0, Some(3), uniform).unwrap();
+        let b = generate_selfplay_batch(4, 1.0, Some(3), uniform).unwrap();
+        assert_eq!(a, b);
+        assert!(generate_selfplay_batch(2, 0.0, None, |_, _| Ok(vec![0.0; 3])).is_err());
+    }
+}
@@ -0,0 +1,484 @@
+//! Global shuffle of Parquet datasets too large for memory (bucket shuffle).
+//!
+//! Pass 1 streams every input row into one of N temporary bucket files, picked at
+//! random; pass 2 loads one bucket at a time, permutes it and appends it to the
+//! output shards. Memory is bounded by one bucket (`bucket_rows`) plus a small
+//! write buffer per bucket, and every row can land anywhere in the output.
+//!
+//! Inputs must share their columns, unless `interleave` is set: bidding and
+//! gameplay rows are then mixed in the same shards, under the union of their
+//! columns (missing ones are null) and a `kind` column saying which one each row
+//! is. Metadata keys are kept when every input defining them agrees.
+
+use arrow::array::{new_null_array, ArrayRef, StringArray, UInt32Array};
+use arrow::compute::{concat_batches, take_record_batch};
+use arrow::datatypes::{DataType, Field, Schema, SchemaRef};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::file::properties::WriterProperties;
+use rand::prelude::*;
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::common::sample_rng;
+use super::verify::dataset_kind;
+
+/// Name of the column tagging interleaved rows with "bidding" or "gameplay".
+pub const KIND_COLUMN: &str = "kind";
+
+/// Rows buffered per bucket file before they are flushed to disk.
+const BUCKET_WRITE_ROWS: usize = 8192;
+
+#[derive(Clone, Debug)]
+pub struct ShuffleOptions {
+    /// Target rows per bucket: the largest amount of data held in memory at once.
+    pub bucket_rows: usize,
+    pub rows_per_shard: usize,
+    /// Mix bidding and gameplay inputs (see the module documentation).
+    pub interleave: bool,
+    pub seed: Option<u64>,
+}
+
+impl Default for ShuffleOptions {
+    fn default() -> Self {
+        ShuffleOptions {
+            bucket_rows: 4_000_000,
+            rows_per_shard: 1_000_000,
+            interleave: false,
+            seed: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ShuffleReport {
+    pub rows: usize,
+    pub buckets: usize,
+    pub shards: Vec<PathBuf>,
+}
+
+struct Input {
+    path: PathBuf,
+    schema: SchemaRef,
+    kind: Option<&'static str>,
+    rows: usize,
+}
+
+/// Shuffles the rows of every Parquet file found at `inputs` (files or
+/// directories) into shards `part-00000.parquet`, ... of `output_dir`.
+pub fn shuffle_dataset(
+    inputs: &[PathBuf],
+    output_dir: &Path,
+    options: &ShuffleOptions,
+) -> Result<ShuffleReport, String> {
+    if options.bucket_rows == 0 || options.rows_per_shard == 0 {
+        return Err("bucket_rows and rows_per_shard must be positive".to_string());
+    }
+    let inputs = open_inputs(inputs, options.interleave)?;
+    let schema = output_schema(&inputs, options.interleave)?;
+
+    if output_dir.exists() && !super::verify::parquet_files(output_dir)?.is_empty() {
+        return Err(format!(
+            "{} already holds Parquet files",
+            output_dir.display()
+        ));
+    }
+    let bucket_dir = output_dir.join(".buckets");
+    std::fs::create_dir_all(&bucket_dir).map_err(|e| format!("{}: {}", bucket_dir.display(), e))?;
+
+    let rows: usize = inputs.iter().map(|i| i.rows).sum();
+    let buckets = rows.div_ceil(options.bucket_rows).max(1);
+    let bucket_files = scatter(&inputs, &schema, &bucket_dir, buckets, options.seed)?;
+    let shards = gather(&bucket_files, &schema, output_dir, options)?;
+
+    std::fs::remove_dir_all(&bucket_dir).map_err(|e| format!("{}: {}", bucket_dir.display(), e))?;
+    Ok(ShuffleReport {
+        rows,
+        buckets,
+        shards,
+    })
+}
+
+fn open_inputs(paths: &[PathBuf], interleave: bool) -> Result<Vec<Input>, String> {
+    let mut inputs = Vec::new();
+    for root in paths {
+        for path in super::verify::parquet_files(root)? {
+            let builder = File::open(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|f| {
+                    ParquetRecordBatchReaderBuilder::try_new(f).map_err(|e| e.to_string())
+                })
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            let kind = if interleave {
+                Some(
+                    dataset_kind(builder.schema())
+                        .map_err(|e| format!("{}: {}", path.display(), e))?,
+                )
+            } else {
+                None
+            };
+            inputs.push(Input {
+                schema: builder.schema().clone(),
+                rows: builder.metadata().file_metadata().num_rows() as usize,
+                path,
+                kind,
+            });
+        }
+    }
+    if inputs.is_empty() {
+        return Err("No Parquet file found in the inputs".to_string());
+    }
+    Ok(inputs)
+}
+
+/// Columns of the shuffled files, all nullable; with `interleave`, the union of
+/// the inputs' columns followed by `KIND_COLUMN`.
+fn output_schema(inputs: &[Input], interleave: bool) -> Result<SchemaRef, String> {
+    let mut fields: Vec<Field> = Vec::new();
+    for input in inputs {
+        let names: Vec<&String> = input.schema.fields().iter().map(|f| f.name()).collect();
+        if !interleave && !fields.is_empty() {
+            let expected: Vec<&String> = fields.iter().map(|f| f.name()).collect();
+            if names != expected {
+                return Err(format!(
+                    "{} has columns {:?}, expected {:?} (use interleave to mix datasets)",
+                    input.path.display(),
+                    names,
+                    expected
+                ));
+            }
+        }
+        for field in input.schema.fields() {
+            match fields.iter().find(|f| f.name() == field.name()) {
+                Some(f) if f.data_type() != field.data_type() => {
+                    return Err(format!(
+                        "{}: column {} is {}, elsewhere {}",
+                        input.path.display(),
+                        field.name(),
+                        field.data_type(),
+                        f.data_type()
+                    ))
+                }
+                Some(_) => {}
+                None => fields.push(Field::new(field.name(), field.data_type().clone(), true)),
+            }
+        }
+    }
+    if interleave {
+        if fields.iter().any(|f| f.name() == KIND_COLUMN) {
+            return Err(format!("Inputs already have a {} column", KIND_COLUMN));
+        }
+        fields.push(Field::new(KIND_COLUMN, DataType::Utf8, false));
+    }
+
+    let mut metadata: HashMap<String, String> = HashMap::new();
+    let mut conflicts = Vec::new();
+    for input in inputs {
+        for (key, value) in input.schema.metadata() {
+            match metadata.get(key) {
+                Some(v) if v != value => conflicts.push(key.clone()),
+                Some(_) => {}
+                None => {
+                    metadata.insert(key.clone(), value.clone());
+                }
+            }
+        }
+    }
+    for key in conflicts {
+        metadata.remove(&key);
+    }
+    Ok(Arc::new(Schema::new(fields).with_metadata(metadata)))
+}
+
+/// `batch` with the columns of `schema`, nulls for the ones it lacks.
+fn conform(
+    batch: &RecordBatch,
+    schema: &SchemaRef,
+    kind: Option<&str>,
+) -> Result<RecordBatch, String> {
+    let columns: Vec<ArrayRef> = schema
+        .fields()
+        .iter()
+        .map(|f| match (batch.column_by_name(f.name()), kind) {
+            (Some(c), _) => c.clone(),
+            (None, Some(k)) if f.name() == KIND_COLUMN => {
+                Arc::new(StringArray::from(vec![k; batch.num_rows()]))
+            }
+            (None, _) => new_null_array(f.data_type(), batch.num_rows()),
+        })
+        .collect();
+    RecordBatch::try_new(schema.clone(), columns).map_err(|e| e.to_string())
+}
+
+pub(crate) fn writer(
+    path: &Path,
+    schema: &SchemaRef,
+    props: WriterProperties,
+) -> Result<ArrowWriter<File>, String> {
+    File::create(path)
+        .map_err(|e| e.to_string())
+        .and_then(|f| {
+            ArrowWriter::try_new(f, schema.clone(), Some(props)).map_err(|e| e.to_string())
+        })
+        .map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+/// Pass 1: every row goes to a random bucket file.
+fn scatter(
+    inputs: &[Input],
+    schema: &SchemaRef,
+    bucket_dir: &Path,
+    buckets: usize,
+    seed: Option<u64>,
+) -> Result<Vec<PathBuf>, String> {
+    let paths: Vec<PathBuf> = (0..buckets)
+        .map(|b| bucket_dir.join(format!("bucket-{:05}.parquet", b)))
+        .collect();
+    let props = || {
+        WriterProperties::builder()
+            .set_max_row_group_size(BUCKET_WRITE_ROWS)
+            .build()
+    };
+    let mut writers = paths
+        .iter()
+        .map(|p| writer(p, schema, props()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut rng = sample_rng(seed, 0);
+    for input in inputs {
+        let reader = File::open(&input.path)
+            .map_err(|e| e.to_string())
+            .and_then(|f| ParquetRecordBatchReaderBuilder::try_new(f).map_err(|e| e.to_string()))
+            .and_then(|b| b.build().map_err(|e| e.to_string()))
+            .map_err(|e| format!("{}: {}", input.path.display(), e))?;
+        for batch in reader {
+            let batch = batch.map_err(|e| format!("{}: {}", input.path.display(), e))?;
+            let batch = conform(&batch, schema, input.kind)?;
+            let mut rows: Vec<Vec<u32>> = vec![Vec::new(); buckets];
+            for row in 0..batch.num_rows() {
+                rows[rng.gen_range(0..buckets)].push(row as u32);
+            }
+            for (b, indices) in rows.into_iter().enumerate() {
+                if indices.is_empty() {
+                    continue;
+                }
+                let part = take_record_batch(&batch, &UInt32Array::from(indices))
+                    .map_err(|e| e.to_string())?;
+                writers[b].write(&part).map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    for w in writers {
+        w.close().map_err(|e| e.to_string())?;
+    }
+    Ok(paths)
+}
+
+/// Pass 2: each bucket is loaded, permuted and appended to the output shards.
+fn gather(
+    bucket_files: &[PathBuf],
+    schema: &SchemaRef,
+    output_dir: &Path,
+    options: &ShuffleOptions,
+) -> Result<Vec<PathBuf>, String> {
+    let mut shards = Vec::new();
+    let mut current: Option<(ArrowWriter<File>, usize)> = None;
+
+    for (b, path) in bucket_files.iter().enumerate() {
+        let reader = File::open(path)
+            .map_err(|e| e.to_string())
+            .and_then(|f| ParquetRecordBatchReaderBuilder::try_new(f).map_err(|e| e.to_string()))
+            .and_then(|b| b.build().map_err(|e| e.to_string()))
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let batches = reader
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string())?;
+        let bucket = concat_batches(schema, &batches).map_err(|e| e.to_string())?;
+        let mut order: Vec<u32> = (0..bucket.num_rows() as u32).collect();
+        order.shuffle(&mut sample_rng(options.seed, 1 + b as u64));
+        let bucket =
+            take_record_batch(&bucket, &UInt32Array::from(order)).map_err(|e| e.to_string())?;
+
+        let mut offset = 0;
+        while offset < bucket.num_rows() {
+            let (w, written) = match current.as_mut() {
+                Some(c) => c,
+                None => {
+                    let path = output_dir.join(format!("part-{:05}.parquet", shards.len()));
+                    let w = writer(&path, schema, WriterProperties::builder().build())?;
+                    shards.push(path);
+                    current.insert((w, 0))
+                }
+            };
+            let take = (options.rows_per_shard - *written).min(bucket.num_rows() - offset);
+            w.write(&bucket.slice(offset, take))
+                .map_err(|e| e.to_string())?;
+            *written += take;
+            offset += take;
+            if *written == options.rows_per_shard {
+                let (w, _) = current.take().unwrap();
+                w.close().map_err(|e| e.to_string())?;
+            }
+        }
+    }
+    if let Some((w, _)) = current {
+        w.close().map_err(|e| e.to_string())?;
+    }
+    Ok(shards)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Array, AsArray, Float32Array, UInt8Array};
+    use arrow::datatypes::UInt32Type;
+
+    fn write(path: &Path, columns: Vec<(&str, ArrayRef)>, metadata: &[(&str, &str)]) {
+        let schema = Arc::new(
+            Schema::new(
+                columns
+                    .iter()
+                    .map(|(n, a)| Field::new(*n, a.data_type().clone(), false))
+                    .collect::<Vec<_>>(),
+            )
+            .with_metadata(
+                metadata
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect(),
+            ),
+        );
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            columns.into_iter().map(|(_, a)| a).collect(),
+        )
+        .unwrap();
+        let mut writer = ArrowWriter::try_new(File::create(path).unwrap(), schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    fn gameplay(path: &Path, rows: std::ops::Range<u32>) {
+        let n = rows.len();
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            ("hand", Arc::new(UInt32Array::from_iter_values(rows))),
+            ("board", Arc::new(UInt8Array::from(vec![0; n]))),
+            ("trump", Arc::new(UInt8Array::from(vec![2; n]))),
+            ("best_card", Arc::new(UInt8Array::from(vec![5; n]))),
+            ("best_score", Arc::new(Float32Array::from(vec![81.0; n]))),
+        ];
+        write(path, columns, &[("schema_version", "2")]);
+    }
+
+    fn metadata(shard: &Path) -> HashMap<String, String> {
+        ParquetRecordBatchReaderBuilder::try_new(File::open(shard).unwrap())
+            .unwrap()
+            .schema()
+            .metadata()
+            .clone()
+    }
+
+    fn read(shards: &[PathBuf]) -> Vec<RecordBatch> {
+        shards
+            .iter()
+            .flat_map(|p| {
+                ParquetRecordBatchReaderBuilder::try_new(File::open(p).unwrap())
+                    .unwrap()
+                    .build()
+                    .unwrap()
+                    .map(|b| b.unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_shuffle_keeps_every_row() {
+        let dir = std::env::temp_dir().join(format!("shuffle_test_{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("in")).unwrap();
+        gameplay(&dir.join("in/a.parquet"), 0..60);
+        gameplay(&dir.join("in/b.parquet"), 60..100);
+
+        let options = ShuffleOptions {
+            bucket_rows: 30,
+            rows_per_shard: 32,
+            interleave: false,
+            seed: Some(3),
+        };
+        let report = shuffle_dataset(&[dir.join("in")], &dir.join("out"), &options).unwrap();
+        assert_eq!(
+            (report.rows, report.buckets, report.shards.len()),
+            (100, 4, 4)
+        );
+        assert!(!dir.join("out/.buckets").exists());
+
+        let batches = read(&report.shards);
+        assert_eq!(metadata(&report.shards[0])["schema_version"], "2");
+        let hands: Vec<u32> = batches
+            .iter()
+            .flat_map(|b| b.column(0).as_primitive::<UInt32Type>().values().to_vec())
+            .collect();
+        let mut sorted = hands.clone();
+        sorted.sort();
+        assert_eq!(sorted, (0..100).collect::<Vec<_>>());
+        assert_ne!(hands, sorted);
+
+        // The output directory is not overwritten.
+        assert!(shuffle_dataset(&[dir.join("in")], &dir.join("out"), &options).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_interleave_bidding_and_gameplay() {
+        let dir = std::env::temp_dir().join(format!("interleave_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        gameplay(&dir.join("gameplay.parquet"), 0..20);
+        let scores = Arc::new(arrow::array::ListArray::from_iter_primitive::<
+            arrow::datatypes::Float32Type,
+            _,
+            _,
+        >((0..10).map(|_| Some(vec![Some(80.0); 4]))));
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            ("hand_south", Arc::new(UInt32Array::from(vec![7; 10]))),
+            ("scores", scores),
+        ];
+        write(&dir.join("bidding.parquet"), columns, &[]);
+        let inputs = [dir.join("gameplay.parquet"), dir.join("bidding.parquet")];
+
+        let options = ShuffleOptions {
+            bucket_rows: 8,
+            seed: Some(4),
+            ..ShuffleOptions::default()
+        };
+        let err = shuffle_dataset(&inputs, &dir.join("mixed"), &options).unwrap_err();
+        assert!(err.contains("use interleave"), "{}", err);
+
+        let options = ShuffleOptions {
+            interleave: true,
+            ..options
+        };
+        let report = shuffle_dataset(&inputs, &dir.join("mixed"), &options).unwrap();
+        let batches = read(&report.shards);
+        let batch = concat_batches(&batches[0].schema(), &batches).unwrap();
+        assert_eq!(batch.num_rows(), 30);
+        // Only the gameplay file declared a schema version.
+        assert_eq!(metadata(&report.shards[0])["schema_version"], "2");
+
+        let kinds = batch
+            .column_by_name(KIND_COLUMN)
+            .unwrap()
+            .as_string::<i32>();
+        let bidding: Vec<usize> = (0..30).filter(|&i| kinds.value(i) == "bidding").collect();
+        assert_eq!(bidding.len(), 10);
+        let best_card = batch.column_by_name("best_card").unwrap();
+        let hand_south = batch.column_by_name("hand_south").unwrap();
+        for i in 0..30 {
+            let is_bidding = bidding.contains(&i);
+            assert_eq!(best_card.is_null(i), is_bidding);
+            assert_eq!(hand_south.is_null(i), !is_bidding);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,367 @@
+//! Vectorized card-play environment for reinforcement learning: `num_envs`
+//! independent deals stepped together, one card per deal and per `step`, with
+//! the games advanced in parallel on the Rust side.
+//!
+//! Deals are those of self-play (random suit trump, South leading) and the
+//! observation of each is that of its player to move (`gameplay_features`). A
+//! finished deal is replaced at once by the next one of its random stream, so
+//! `step` always returns observations of games in progress.
+//!
+//! Rewards follow a `RewardScheme` and always go to the team of the player who
+//! moved: its opponents' reward for the same step is the opposite.
+
+use crate::gameplay::encoding::{CARDS, GAMEPLAY_FEATURES};
+use crate::gameplay::notation::card_name;
+use crate::gameplay::playing::{PlayingState, LAST_TRICK_BONUS, TOTAL_CARD_POINTS};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+
+use super::common::{ne_bytes, numpy_array, sample_rng};
+use super::selfplay::Game;
+
+const BELOTE_BONUS: u16 = 20;
+
+/// How the points of a deal are turned into rewards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RewardScheme {
+    /// Points gained minus points conceded at each step, as tricks and the belote
+    /// are scored. They add up to the final score gap.
+    Trick,
+    /// The final score gap, on the step ending the deal only.
+    Score,
+    /// 1 for the team ending the deal ahead, -1 behind, 0 on a tie.
+    WinLoss,
+    /// The final score gap divided by the most points a deal can score, in [-1, 1].
+    Margin,
+}
+
+impl RewardScheme {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "trick" => Ok(RewardScheme::Trick),
+            "score" => Ok(RewardScheme::Score),
+            "win_loss" => Ok(RewardScheme::WinLoss),
+            "margin" => Ok(RewardScheme::Margin),
+            _ => Err(format!(
+                "Unknown reward scheme '{}' (expected 'trick', 'score', 'win_loss' or 'margin')",
+                name
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RewardScheme::Trick => "trick",
+            RewardScheme::Score => "score",
+            RewardScheme::WinLoss => "win_loss",
+            RewardScheme::Margin => "margin",
+        }
+    }
+
+    /// Reward of `team` for the step from points `before` to `state`.
+    fn reward(&self, before: [u16; 2], state: &PlayingState, team: usize) -> f32 {
+        let gap = |p: [u16; 2]| p[team] as f32 - p[1 - team] as f32;
+        match self {
+            RewardScheme::Trick => gap(state.points) - gap(before),
+            _ if !state.is_terminal() => 0.0,
+            RewardScheme::Score => gap(state.points),
+            RewardScheme::WinLoss => match state.points[team].cmp(&state.points[1 - team]) {
+                Ordering::Greater => 1.0,
+                Ordering::Less => -1.0,
+                Ordering::Equal => 0.0,
+            },
+            RewardScheme::Margin => {
+                let max_points =
+                    TOTAL_CARD_POINTS + LAST_TRICK_BONUS + state.rules.capot_bonus + BELOTE_BONUS;
+                gap(state.points) / max_points as f32
+            }
+        }
+    }
+}
+
+/// Outcome of one step of every game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StepResult {
+    /// Rewards of the team of the player who moved, see `RewardScheme`.
+    pub rewards: Vec<f32>,
+    /// Games whose deal ended with the step, and were dealt again.
+    pub dones: Vec<bool>,
+}
+
+pub struct VecEnv {
+    games: Vec<Game>,
+    scheme: RewardScheme,
+}
+
+impl VecEnv {
+    /// `num_envs` new deals; with a seed, game `i` only depends on (seed, i).
+    pub fn new(num_envs: usize, seed: Option<u64>, scheme: RewardScheme) -> Self {
+        VecEnv {
+            scheme,
+            games: (0..num_envs)
+                .into_par_iter()
+                .map(|i| Game::deal(sample_rng(seed, i as u64)))
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.games.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty()
+    }
+
+    /// Observations of every game, flattened row-major (`GAMEPLAY_FEATURES` each).
+    pub fn observations(&self) -> Vec<f32> {
+        self.games.par_iter().flat_map(Game::observation).collect()
+    }
+
+    pub fn legal_moves(&self) -> Vec<u32> {
+        self.games
+            .iter()
+            .map(|g| g.state.get_legal_moves())
+            .collect()
+    }
+
+    pub fn current_players(&self) -> Vec<u8> {
+        self.games.iter().map(|g| g.state.current_player).collect()
+    }
+
+    /// Plays `actions[i]` in game `i`. Nothing is played unless every action is
+    /// legal in its game.
+    pub fn step(&mut self, actions: &[u8]) -> Result<StepResult, String> {
+        if actions.len() != self.games.len() {
+            return Err(format!(
+                "Expected {} actions, got {}",
+                self.games.len(),
+                actions.len()
+            ));
+        }
+        for (i, (game, &card)) in self.games.iter().zip(actions).enumerate() {
+            if card as usize >= CARDS || game.state.get_legal_moves() & (1 << card) == 0 {
+                return Err(format!("Illegal action {} in game {}", card_text(card), i));
+            }
+        }
+
+        let scheme = self.scheme;
+        let (rewards, dones) = self
+            .games
+            .par_iter_mut()
+            .zip(actions.par_iter())
+            .map(|(game, &card)| {
+                let team = (game.state.current_player % 2) as usize;
+                let before = game.state.points;
+                game.play(card);
+                let reward = scheme.reward(before, &game.state, team);
+                let done = game.state.is_terminal();
+                if done {
+                    game.redeal();
+                }
+                (reward, done)
+            })
+            .unzip();
+        Ok(StepResult { rewards, dones })
+    }
+}
+
+fn card_text(card: u8) -> String {
+    if (card as usize) < CARDS {
+        card_name(card)
+    } else {
+        card.to_string()
+    }
+}
+
+/// `num_envs` card-play games stepped together, for PPO-style training:
+///
+/// ```python
+/// env = VecCoincheEnv(256, seed=0)
+/// obs, legal = env.reset()
+/// obs, legal, rewards, dones = env.step(actions)
+/// ```
+///
+/// Observations are float32 arrays of shape (num_envs, GAMEPLAY_FEATURES), legal
+/// masks uint32 arrays of shape (num_envs,), rewards float32 and dones bool. The
+/// reward of a game goes to the team of the player who chose its action, and
+/// `reward` picks how it is computed: "trick" (points as tricks are scored, the
+/// default), "score" (final score gap), "win_loss" (+1/-1/0 at the end) or
+/// "margin" (final score gap over the most a deal can score).
+#[pyclass]
+pub struct VecCoincheEnv {
+    env: VecEnv,
+    seed: Option<u64>,
+}
+
+impl VecCoincheEnv {
+    fn observe(&self, py: Python) -> PyResult<(PyObject, PyObject)> {
+        let observations = numpy_array(
+            py,
+            &ne_bytes(&self.env.observations(), f32::to_ne_bytes),
+            "float32",
+        )?
+        .call_method1("reshape", ((self.env.len(), GAMEPLAY_FEATURES),))?;
+        let legal = numpy_array(
+            py,
+            &ne_bytes(&self.env.legal_moves(), u32::to_ne_bytes),
+            "uint32",
+        )?;
+        Ok((observations.into_py(py), legal.into_py(py)))
+    }
+}
+
+#[pymethods]
+impl VecCoincheEnv {
+    #[new]
+    #[pyo3(signature = (num_envs, seed=None, reward="trick"))]
+    pub fn new(num_envs: usize, seed: Option<u64>, reward: &str) -> PyResult<Self> {
+        if num_envs == 0 {
+            return Err(PyValueError::new_err("num_envs must be positive"));
+        }
+        let scheme = RewardScheme::parse(reward).map_err(PyValueError::new_err)?;
+        Ok(VecCoincheEnv {
+            env: VecEnv::new(num_envs, seed, scheme),
+            seed,
+        })
+    }
+
+    #[getter]
+    pub fn num_envs(&self) -> usize {
+        self.env.len()
+    }
+
+    #[getter]
+    pub fn reward(&self) -> &'static str {
+        self.env.scheme.name()
+    }
+
+    pub fn __len__(&self) -> usize {
+        self.env.len()
+    }
+
+    /// Deals every game again (from `seed` if given, else from the constructor's
+    /// seed) and returns (observations, legal).
+    #[pyo3(signature = (seed=None))]
+    pub fn reset(&mut self, py: Python, seed: Option<u64>) -> PyResult<(PyObject, PyObject)> {
+        let seed = seed.or(self.seed);
+        let (num_envs, scheme) = (self.env.len(), self.env.scheme);
+        self.env = py.allow_threads(|| VecEnv::new(num_envs, seed, scheme));
+        self.observe(py)
+    }
+
+    /// Plays one card in every game (`actions`: one card index per game) and
+    /// returns (observations, legal, rewards, dones). Finished games are dealt
+    /// again, their observations being those of the new deal.
+    pub fn step(
+        &mut self,
+        py: Python,
+        actions: Vec<u8>,
+    ) -> PyResult<(PyObject, PyObject, PyObject, PyObject)> {
+        let env = &mut self.env;
+        let result = py
+            .allow_threads(|| env.step(&actions))
+            .map_err(PyValueError::new_err)?;
+        let (observations, legal) = self.observe(py)?;
+        let rewards = numpy_array(py, &ne_bytes(&result.rewards, f32::to_ne_bytes), "float32")?;
+        let dones: Vec<u8> = result.dones.iter().map(|&d| d as u8).collect();
+        let dones = numpy_array(py, &dones, "bool")?;
+        Ok((observations, legal, rewards.into_py(py), dones.into_py(py)))
+    }
+
+    /// Seat to move in every game.
+    pub fn current_players(&self) -> Vec<u8> {
+        self.env.current_players()
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "VecCoincheEnv(num_envs={}, reward={})",
+            self.env.len(),
+            self.env.scheme.name()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vec_env_plays_deals_to_the_end() {
+        let mut env = VecEnv::new(6, Some(5), RewardScheme::Trick);
+        assert_eq!(env.observations().len(), 6 * GAMEPLAY_FEATURES);
+        let mut returns = [0.0f32; 6];
+        let mut final_gaps = [0.0f32; 6];
+        for step in 0..32 {
+            let legal = env.legal_moves();
+            let players = env.current_players();
+            let actions: Vec<u8> = legal.iter().map(|l| l.trailing_zeros() as u8).collect();
+            for (gap, (game, &card)) in final_gaps.iter_mut().zip(env.games.iter().zip(&actions)) {
+                let mut state = game.state;
+                state.play_card(card);
+                *gap = state.points[0] as f32 - state.points[1] as f32;
+            }
+            let result = env.step(&actions).unwrap();
+            for i in 0..6 {
+                let sign = [1.0, -1.0][players[i] as usize % 2];
+                returns[i] += sign * result.rewards[i];
+            }
+            assert_eq!(result.dones, vec![step == 31; 6]);
+        }
+        // Rewards are zero-sum: from NS's side they add up to the final score gap.
+        assert_eq!(returns, final_gaps);
+        // Every deal was replaced by a fresh one.
+        assert!(env
+            .games
+            .iter()
+            .all(|g| g.state.hands.iter().all(|&h| h.count_ones() == 8)));
+
+        // A wrong action plays nothing.
+        let before = env.observations();
+        let mut actions: Vec<u8> = env
+            .legal_moves()
+            .iter()
+            .map(|l| l.trailing_zeros() as u8)
+            .collect();
+        actions[3] = (!env.legal_moves()[3]).trailing_zeros() as u8;
+        assert!(env.step(&actions).unwrap_err().contains("game 3"));
+        assert!(env.step(&actions[..2]).is_err());
+        assert_eq!(env.observations(), before);
+    }
+
+    #[test]
+    fn test_reward_schemes() {
+        let schemes =
+            ["trick", "score", "win_loss", "margin"].map(|n| RewardScheme::parse(n).unwrap());
+        let mut envs = schemes.map(|scheme| VecEnv::new(3, Some(8), scheme));
+        let mut totals = [[0.0f32; 3]; 4];
+        for step in 0..32 {
+            let players = envs[0].current_players();
+            let actions: Vec<u8> = envs[0]
+                .legal_moves()
+                .iter()
+                .map(|l| 31 - l.leading_zeros() as u8)
+                .collect();
+            for (env, total) in envs.iter_mut().zip(totals.iter_mut()) {
+                let result = env.step(&actions).unwrap();
+                for i in 0..3 {
+                    if step < 31 && env.scheme != RewardScheme::Trick {
+                        assert_eq!(result.rewards[i], 0.0);
+                    }
+                    total[i] += [1.0, -1.0][players[i] as usize % 2] * result.rewards[i];
+                }
+            }
+        }
+        // Same deals and cards: every scheme agrees with the final score gap.
+        let [trick, score, win_loss, margin] = totals;
+        for (i, &gap) in trick.iter().enumerate() {
+            assert_eq!(score[i], gap);
+            assert_eq!(win_loss[i], gap.clamp(-1.0, 1.0));
+            assert!((margin[i] * 272.0 - gap).abs() < 1e-3);
+        }
+        assert!(RewardScheme::parse("elo").is_err());
+    }
+}
@@ -0,0 +1,543 @@
+//! Dataset verification: re-solve a random subset of a generated Parquet dataset
+//! and compare against the stored labels.
+//!
+//! Gameplay rows can only be re-solved when the full deal was stored (`deal`,
+//! `tricks_won` and `player` columns); older files still get the structural checks.
+//! `best_score` is the double-dummy value of the true deal unless the file metadata says
+//! the labels are PIMC expected values (`score_label` = "ev"); double-dummy scores must
+//! match exactly, expected values cannot be re-solved and are not compared (nor are
+//! the NaN scores of forced moves labelled without a solve). Scores are
+//! re-solved for the `score_objective` of the metadata (points if absent). `best_card`
+//! may legitimately differ on ties or for PIMC labels, so card disagreements are
+//! reported separately.
+//!
+//! Files declaring a `schema_version` must hold every column of that version
+//! (see `schema`); files without one predate versioning and are read as found.
+
+use crate::gameplay::deal::validate_remaining_cards;
+use crate::gameplay::playing::PlayingState;
+use crate::solver::{solve_with_objective, Objective, Perspective, Score};
+use arrow::array::{Array, ArrayRef, AsArray, ListArray};
+use arrow::datatypes::{ArrowPrimitiveType, DataType, Field, Float32Type, Schema, UInt32Type};
+use arrow::record_batch::RecordBatch;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use pyo3::prelude::*;
+use rand::Rng;
+use rayon::prelude::*;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::common::sample_rng;
+use super::gameplay::{reconstruct_state, ScoreLabel};
+use super::reader::{decode_gameplay_batch, GameplayRecord};
+use super::schema::{SchemaVersion, SCHEMA_VERSION_KEY};
+
+/// Maximum number of individual problems kept in a report.
+const MAX_REPORTED_ERRORS: usize = 50;
+
+#[pyclass]
+#[derive(Debug, Clone, Default)]
+pub struct VerificationReport {
+    /// "gameplay" or "bidding".
+    #[pyo3(get)]
+    pub kind: String,
+    #[pyo3(get)]
+    pub rows_total: usize,
+    #[pyo3(get)]
+    pub rows_checked: usize,
+    /// Sampled rows that carried enough information to be re-solved.
+    #[pyo3(get)]
+    pub rows_resolved: usize,
+    /// Rows failing structural checks (card counts, illegal best card...).
+    #[pyo3(get)]
+    pub invalid_rows: usize,
+    #[pyo3(get)]
+    pub score_mismatches: usize,
+    #[pyo3(get)]
+    pub card_mismatches: usize,
+    /// First problems found, for inspection.
+    #[pyo3(get)]
+    pub errors: Vec<String>,
+}
+
+#[pymethods]
+impl VerificationReport {
+    /// True when no invalid row and no score mismatch was found.
+    pub fn is_ok(&self) -> bool {
+        self.invalid_rows == 0 && self.score_mismatches == 0
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "VerificationReport(kind={}, checked={}/{}, resolved={}, invalid={}, score_mismatches={}, card_mismatches={})",
+            self.kind,
+            self.rows_checked,
+            self.rows_total,
+            self.rows_resolved,
+            self.invalid_rows,
+            self.score_mismatches,
+            self.card_mismatches
+        )
+    }
+}
+
+impl VerificationReport {
+    fn push_error(&mut self, message: String) {
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(message);
+        }
+    }
+}
+
+enum RowOutcome {
+    Invalid(String),
+    Unresolved,
+    Resolved {
+        score: Option<(f32, Score)>,
+        card_differs: bool,
+    },
+}
+
+/// Reads the dataset at `path` (a Parquet file or a directory of Parquet files),
+/// re-solves about `sample_fraction` of its rows and reports any disagreement.
+pub fn verify_dataset(
+    path: &str,
+    sample_fraction: f64,
+    seed: Option<u64>,
+    tt_log2: Option<u8>,
+) -> Result<VerificationReport, String> {
+    if !(sample_fraction > 0.0 && sample_fraction <= 1.0) {
+        return Err("sample_fraction must be in (0, 1]".to_string());
+    }
+
+    let files = parquet_files(Path::new(path))?;
+    if files.is_empty() {
+        return Err(format!("No Parquet file found at {}", path));
+    }
+
+    let mut rng = sample_rng(seed, 0);
+    let mut report = VerificationReport::default();
+    let mut perspective = Perspective::Absolute;
+    let mut score_label = ScoreLabel::DoubleDummy;
+    let mut objective = Objective::Points;
+    let mut gameplay_rows = Vec::new();
+
+    for file in &files {
+        let builder = File::open(file)
+            .map_err(|e| e.to_string())
+            .and_then(|f| ParquetRecordBatchReaderBuilder::try_new(f).map_err(|e| e.to_string()))
+            .map_err(|e| format!("{}: {}", file.display(), e))?;
+
+        if let Some(p) = builder.schema().metadata().get("score_perspective") {
+            perspective = Perspective::parse(p)?;
+        }
+        if let Some(l) = builder.schema().metadata().get("score_label") {
+            score_label = ScoreLabel::parse(l)?;
+        }
+        if let Some(o) = builder.schema().metadata().get("score_objective") {
+            objective = Objective::parse(o)?;
+        }
+        let version = match builder.schema().metadata().get(SCHEMA_VERSION_KEY) {
+            Some(v) => {
+                let number = v
+                    .parse::<u32>()
+                    .map_err(|_| format!("{}: invalid schema version '{}'", file.display(), v))?;
+                Some(
+                    SchemaVersion::parse(Some(number))
+                        .map_err(|e| format!("{}: {}", file.display(), e))?,
+                )
+            }
+            None => None,
+        };
+        let reader = builder.build().map_err(|e| e.to_string())?;
+
+        for batch in reader {
+            let batch = batch.map_err(|e| e.to_string())?;
+            let kind = dataset_kind(&batch.schema())?;
+            if report.kind.is_empty() {
+                report.kind = kind.to_string();
+            } else if report.kind != kind {
+                return Err(format!(
+                    "{} holds {} rows, expected {}",
+                    file.display(),
+                    kind,
+                    report.kind
+                ));
+            }
+
+            let selected: Vec<usize> = (0..batch.num_rows())
+                .filter(|_| rng.gen_bool(sample_fraction))
+                .collect();
+            let offset = report.rows_total;
+            report.rows_total += batch.num_rows();
+            report.rows_checked += selected.len();
+
+            if kind == "gameplay" {
+                if let Some(version) = version {
+                    check_columns(&batch, version.gameplay_columns())
+                        .map_err(|e| format!("{}: {}", file.display(), e))?;
+                }
+                let records = decode_gameplay_batch(&batch)?;
+                gameplay_rows.extend(selected.iter().map(|&i| (offset + i, records[i].clone())));
+            } else {
+                check_bidding_rows(&batch, &selected, offset, &mut report)?;
+            }
+        }
+    }
+
+    if perspective == Perspective::Declarer {
+        return Err("Datasets labelled from the declarer perspective cannot be re-solved".into());
+    }
+
+    let outcomes: Vec<(usize, RowOutcome)> = gameplay_rows
+        .par_iter()
+        .map(|(row, record)| {
+            let outcome = check_gameplay_row(record, perspective, score_label, objective, tt_log2);
+            (*row, outcome)
+        })
+        .collect();
+
+    for (row, outcome) in outcomes {
+        match outcome {
+            RowOutcome::Invalid(msg) => {
+                report.invalid_rows += 1;
+                report.push_error(format!("row {}: {}", row, msg));
+            }
+            RowOutcome::Unresolved => {}
+            RowOutcome::Resolved {
+                score,
+                card_differs,
+            } => {
+                report.rows_resolved += 1;
+                if let Some((stored, solved)) = score {
+                    report.score_mismatches += 1;
+                    report.push_error(format!(
+                        "row {}: best_score {} but solver gives {}",
+                        row, stored, solved
+                    ));
+                }
+                if card_differs {
+                    report.card_mismatches += 1;
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Parquet files at `path`: the file itself, or every one below the directory, sorted.
+pub(crate) fn parquet_files(path: &Path) -> Result<Vec<PathBuf>, String> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    let entries = std::fs::read_dir(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    for entry in entries {
+        let p = entry.map_err(|e| e.to_string())?.path();
+        if p.is_dir() {
+            files.extend(parquet_files(&p)?);
+        } else if p.extension().is_some_and(|ext| ext == "parquet") {
+            files.push(p);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn check_columns(batch: &RecordBatch, columns: &[&str]) -> Result<(), String> {
+    match columns.iter().find(|c| batch.column_by_name(c).is_none()) {
+        Some(c) => Err(format!(
+            "Missing column {} of the declared schema version",
+            c
+        )),
+        None => Ok(()),
+    }
+}
+
+/// "gameplay" or "bidding", from the columns of a file.
+pub(crate) fn dataset_kind(schema: &Schema) -> Result<&'static str, String> {
+    let has = |name: &str| schema.column_with_name(name).is_some();
+    if ["hand", "board", "trump", "best_card", "best_score"]
+        .iter()
+        .all(|c| has(c))
+    {
+        Ok("gameplay")
+    } else if has("hand_south") && has("scores") {
+        Ok("bidding")
+    } else {
+        let names: Vec<String> = schema.fields().iter().map(|f| f.name().clone()).collect();
+        Err(format!("Unknown dataset schema: {:?}", names))
+    }
+}
+
+/// Column cast to `T`, whatever integer width the writer used.
+pub(crate) fn primitive_column<T: ArrowPrimitiveType>(
+    batch: &RecordBatch,
+    name: &str,
+) -> Result<Option<Vec<T::Native>>, String> {
+    let Some(col) = batch.column_by_name(name) else {
+        return Ok(None);
+    };
+    let cast =
+        arrow::compute::cast(col, &T::DATA_TYPE).map_err(|e| format!("Column {}: {}", name, e))?;
+    Ok(Some(cast.as_primitive::<T>().values().to_vec()))
+}
+
+/// List column cast to lists of `T`.
+pub(crate) fn list_column<T: ArrowPrimitiveType>(
+    batch: &RecordBatch,
+    name: &str,
+) -> Result<Option<Vec<Vec<T::Native>>>, String> {
+    let Some(col) = batch.column_by_name(name) else {
+        return Ok(None);
+    };
+    let target = DataType::List(Arc::new(Field::new("item", T::DATA_TYPE, true)));
+    let cast: ArrayRef =
+        arrow::compute::cast(col, &target).map_err(|e| format!("Column {}: {}", name, e))?;
+    let list = cast
+        .as_any()
+        .downcast_ref::<ListArray>()
+        .ok_or_else(|| format!("Column {} is not a list", name))?;
+    Ok(Some(
+        (0..list.len())
+            .map(|i| list.value(i).as_primitive::<T>().values().to_vec())
+            .collect(),
+    ))
+}
+
+pub(crate) fn required<V>(column: Option<V>, name: &str) -> Result<V, String> {
+    column.ok_or_else(|| format!("Missing column {}", name))
+}
+
+fn check_gameplay_row(
+    row: &GameplayRecord,
+    perspective: Perspective,
+    score_label: ScoreLabel,
+    objective: Objective,
+    tt_log2: Option<u8>,
+) -> RowOutcome {
+    if row.trump > 5 || row.board.len() > 3 || row.board.iter().any(|&c| c >= 32) {
+        return RowOutcome::Invalid("invalid trump or board".to_string());
+    }
+    if row.best_card >= 32 || (row.hand & (1 << row.best_card)) == 0 {
+        return RowOutcome::Invalid(format!("best_card {} is not in hand", row.best_card));
+    }
+
+    let (Some(deal), Some(tricks_won), Some(player)) = (row.deal, row.tricks_won, row.player)
+    else {
+        // Without the full deal only the mover's legality can be checked; seats are
+        // relative, so the mover is placed at seat 0.
+        let state = reconstruct_state([row.hand, 0, 0, 0], &row.board, row.trump, [0, 0], 0);
+        return match check_legal(&state, row.best_card) {
+            Some(msg) => RowOutcome::Invalid(msg),
+            None => RowOutcome::Unresolved,
+        };
+    };
+
+    if player >= 4 || deal[player as usize] != row.hand {
+        return RowOutcome::Invalid("hand does not match deal[player]".to_string());
+    }
+    let on_table: u32 = row.board.iter().fold(0, |m, &c| m | (1 << c));
+    if deal.iter().any(|&h| h & on_table != 0) {
+        return RowOutcome::Invalid("board cards are still in a hand".to_string());
+    }
+    let played: Vec<u8> = (0..32u8)
+        .filter(|&c| deal.iter().all(|&h| h & (1 << c) == 0))
+        .collect();
+    if let Err(e) = validate_remaining_cards(&deal, &played) {
+        return RowOutcome::Invalid(e);
+    }
+
+    let state = reconstruct_state(deal, &row.board, row.trump, tricks_won, player);
+    if let Some(msg) = check_legal(&state, row.best_card) {
+        return RowOutcome::Invalid(msg);
+    }
+
+    let team = match perspective.team(&state, None) {
+        Ok(t) => t,
+        Err(e) => return RowOutcome::Invalid(e),
+    };
+    let (score, card) = solve_with_objective(&state, team, objective, Some(32), tt_log2);
+    // Forced moves labelled without a solve have no score (NaN).
+    let comparable = score_label == ScoreLabel::DoubleDummy && !row.best_score.is_nan();
+    RowOutcome::Resolved {
+        score: (comparable && score as f32 != row.best_score).then_some((row.best_score, score)),
+        card_differs: card != row.best_card,
+    }
+}
+
+fn check_legal(state: &PlayingState, card: u8) -> Option<String> {
+    if state.get_legal_moves() & (1 << card) == 0 {
+        Some(format!("best_card {} is not a legal move", card))
+    } else {
+        None
+    }
+}
+
+/// Bidding rows only store South's hand, so they can't be re-solved: check shape only.
+fn check_bidding_rows(
+    batch: &RecordBatch,
+    selected: &[usize],
+    offset: usize,
+    report: &mut VerificationReport,
+) -> Result<(), String> {
+    let hands = required(
+        primitive_column::<UInt32Type>(batch, "hand_south")?,
+        "hand_south",
+    )?;
+    let scores = required(list_column::<Float32Type>(batch, "scores")?, "scores")?;
+
+    for &i in selected {
+        let problem = if hands[i].count_ones() != 8 {
+            Some(format!("hand has {} cards", hands[i].count_ones()))
+        } else if scores[i].len() != 4 {
+            Some(format!("{} scores, expected 4", scores[i].len()))
+        } else if scores[i].iter().any(|s| !s.is_finite() || *s < 0.0) {
+            Some(format!("invalid scores {:?}", scores[i]))
+        } else {
+            None
+        };
+        if let Some(msg) = problem {
+            report.invalid_rows += 1;
+            report.push_error(format!("row {}: {}", offset + i, msg));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_gen::gameplay::{generate_positions_for_hand, StageConfig};
+    use crate::solver::solve_for_team;
+    use arrow::array::{
+        Int16Array, ListBuilder, UInt32Array, UInt32Builder, UInt8Array, UInt8Builder,
+    };
+    use arrow::datatypes::Schema;
+    use parquet::arrow::ArrowWriter;
+
+    fn u8_lists(values: &[Vec<u8>]) -> ListArray {
+        let mut builder = ListBuilder::new(UInt8Builder::new());
+        for v in values {
+            builder.values().append_slice(v);
+            builder.append(true);
+        }
+        builder.finish()
+    }
+
+    fn write_gameplay(path: &Path, corrupt_row: Option<usize>, version: Option<&str>) {
+        // Endgames keep the solves fast.
+        let config = StageConfig {
+            opening_weight: 0,
+            midgame_weight: 0,
+            endgame_weight: 1,
+        };
+        let (hands, boards, _, trumps, tricks_won, players) =
+            generate_positions_for_hand(0x0000_F0F0, 6, &config, Some(2)).unwrap();
+
+        let mut cards = Vec::new();
+        let mut scores = Vec::new();
+        let mut deals = ListBuilder::new(UInt32Builder::new());
+        for i in 0..players.len() {
+            let deal = [
+                hands[i * 4],
+                hands[i * 4 + 1],
+                hands[i * 4 + 2],
+                hands[i * 4 + 3],
+            ];
+            let state = reconstruct_state(
+                deal,
+                &boards[i],
+                trumps[i],
+                [tricks_won[i][0], tricks_won[i][1]],
+                players[i],
+            );
+            let (score, card) = solve_for_team(&state, 0, Some(32), None);
+            let score = i16::try_from(score).unwrap();
+            cards.push(card);
+            scores.push(if corrupt_row == Some(i) {
+                score + 10
+            } else {
+                score
+            });
+            deals.values().append_slice(&deal);
+            deals.append(true);
+        }
+        let own_hands: Vec<u32> = (0..players.len())
+            .map(|i| hands[i * 4 + players[i] as usize])
+            .collect();
+
+        let columns: Vec<(&str, ArrayRef)> = vec![
+            ("hand", Arc::new(UInt32Array::from(own_hands))),
+            ("board", Arc::new(u8_lists(&boards))),
+            ("trump", Arc::new(UInt8Array::from(trumps))),
+            ("best_card", Arc::new(UInt8Array::from(cards))),
+            ("best_score", Arc::new(Int16Array::from(scores))),
+            ("deal", Arc::new(deals.finish())),
+            ("tricks_won", Arc::new(u8_lists(&tricks_won))),
+            ("player", Arc::new(UInt8Array::from(players))),
+        ];
+        let metadata = version
+            .map(|v| [(SCHEMA_VERSION_KEY.to_string(), v.to_string())].into())
+            .unwrap_or_default();
+        let schema = Arc::new(
+            Schema::new(
+                columns
+                    .iter()
+                    .map(|(n, a)| Field::new(*n, a.data_type().clone(), true))
+                    .collect::<Vec<_>>(),
+            )
+            .with_metadata(metadata),
+        );
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            columns.into_iter().map(|(_, a)| a).collect(),
+        )
+        .unwrap();
+        let mut writer = ArrowWriter::try_new(File::create(path).unwrap(), schema, None).unwrap();
+        writer.write(&batch).unwrap();
+        writer.close().unwrap();
+    }
+
+    #[test]
+    fn test_verify_detects_corrupted_label() {
+        let dir = std::env::temp_dir().join(format!("verify_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let clean = dir.join("clean.parquet");
+        write_gameplay(&clean, None, None);
+        let report = verify_dataset(clean.to_str().unwrap(), 1.0, Some(0), None).unwrap();
+        assert_eq!(report.kind, "gameplay");
+        assert_eq!(report.rows_resolved, report.rows_total);
+        assert!(report.is_ok(), "{:?}", report.errors);
+
+        let corrupt = dir.join("corrupt.parquet");
+        write_gameplay(&corrupt, Some(1), None);
+        let report = verify_dataset(corrupt.to_str().unwrap(), 1.0, Some(0), None).unwrap();
+        assert_eq!(report.score_mismatches, 1);
+        assert!(report.errors[0].starts_with("row 1:"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_checks_schema_version() {
+        let dir = std::env::temp_dir().join(format!("verify_schema_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // The test file has no `history` column, which V1 requires.
+        let v1 = dir.join("v1.parquet");
+        write_gameplay(&v1, None, Some("1"));
+        let err = verify_dataset(v1.to_str().unwrap(), 1.0, Some(0), None).unwrap_err();
+        assert!(err.contains("Missing column history"), "{}", err);
+
+        let future = dir.join("future.parquet");
+        write_gameplay(&future, None, Some("99"));
+        let err = verify_dataset(future.to_str().unwrap(), 1.0, Some(0), None).unwrap_err();
+        assert!(err.contains("Unknown schema version 99"), "{}", err);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+//! Timestamped record of everything that happened in a match: the auction calls,
+//! every card played and the forfeit that ended it, if any, in order.
+//!
+//! `CoincheMatch::action_log` is the authoritative history replays, persistence
+//! and the review screens read; `to_json` gives it in the card, call and seat
+//! notation of `notation`.
+
+use crate::gameplay::bidding::AuctionAction;
+use crate::gameplay::notation::{
+    call_name, card_name, parse_call, parse_card, parse_seat, seat_name,
+};
+use pyo3::prelude::*;
+use serde_json::{json, Value};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogEvent {
+    Call(AuctionAction),
+    Card(u8),
+    /// A held card played illegally under the revoke penalty.
+    Revoke(u8),
+    /// The seat ran out of time.
+    Timeout,
+}
+
+impl LogEvent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LogEvent::Call(_) => "call",
+            LogEvent::Card(_) => "card",
+            LogEvent::Revoke(_) => "revoke",
+            LogEvent::Timeout => "timeout",
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoggedAction {
+    #[pyo3(get)]
+    pub seat: u8,
+    pub event: LogEvent,
+    /// Milliseconds since the Unix epoch, None for actions of a position that
+    /// was rebuilt rather than played (`from_position`, transcripts).
+    #[pyo3(get)]
+    pub timestamp_ms: Option<u64>,
+}
+
+impl LoggedAction {
+    /// `event` of `seat`, stamped with the current time.
+    pub fn now(seat: u8, event: LogEvent) -> Self {
+        let timestamp_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .ok();
+        LoggedAction {
+            seat,
+            event,
+            timestamp_ms,
+        }
+    }
+
+    pub fn json_value(&self) -> Value {
+        let action = match self.event {
+            LogEvent::Call(ref call) => Some(call_name(call)),
+            LogEvent::Card(card) | LogEvent::Revoke(card) => Some(card_name(card)),
+            LogEvent::Timeout => None,
+        };
+        json!({
+            "seat": seat_name(self.seat),
+            "kind": self.event.kind(),
+            "action": action,
+            "timestamp_ms": self.timestamp_ms,
+        })
+    }
+
+    /// Action of a `json_value` record.
+    pub fn from_json_value(value: &Value) -> Option<Self> {
+        let action = || value["action"].as_str();
+        let event = match value["kind"].as_str()? {
+            "call" => LogEvent::Call(parse_call(action()?).ok()?),
+            "card" => LogEvent::Card(parse_card(action()?).ok()?),
+            "revoke" => LogEvent::Revoke(parse_card(action()?).ok()?),
+            "timeout" => LogEvent::Timeout,
+            _ => return None,
+        };
+        let timestamp_ms = match value["timestamp_ms"] {
+            Value::Null => None,
+            ref t => Some(t.as_u64()?),
+        };
+        Some(LoggedAction {
+            seat: parse_seat(value["seat"].as_str()?).ok()?,
+            event,
+            timestamp_ms,
+        })
+    }
+}
+
+#[pymethods]
+impl LoggedAction {
+    /// "call", "card", "revoke" or "timeout".
+    #[getter]
+    pub fn kind(&self) -> &'static str {
+        self.event.kind()
+    }
+
+    /// The call, as `CoincheMatch.auction` gives it (None for other kinds).
+    #[getter]
+    pub fn call(&self, py: Python) -> PyObject {
+        match self.event {
+            LogEvent::Call(call) => call.into_py(py),
+            _ => py.None(),
+        }
+    }
+
+    /// The card played or revoked, if any.
+    #[getter]
+    pub fn card(&self) -> Option<u8> {
+        match self.event {
+            LogEvent::Card(card) | LogEvent::Revoke(card) => Some(card),
+            _ => None,
+        }
+    }
+
+    /// One-line JSON record of the action, cards, calls and seats by name.
+    pub fn to_json(&self) -> String {
+        self.json_value().to_string()
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "LoggedAction(seat={}, kind={}, action={}, timestamp_ms={:?})",
+            seat_name(self.seat),
+            self.kind(),
+            self.json_value()["action"],
+            self.timestamp_ms
+        )
+    }
+}
+
+/// JSON array of `log`.
+pub fn log_to_json(log: &[LoggedAction]) -> String {
+    Value::Array(log.iter().map(LoggedAction::json_value).collect()).to_string()
+}
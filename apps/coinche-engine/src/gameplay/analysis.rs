@@ -0,0 +1,277 @@
+//! Per-card analysis of the hand of the player to move, double dummy (all hands
+//! known): which cards are masters of their suit, how many opponent cards beat
+//! them, and what each legal card is worth with perfect play afterwards. Meant
+//! for UIs and as handcrafted model features.
+//!
+//! `analyze_position` is the engine view of a position for interactive use: best
+//! move, principal variation and the value of every legal move, searched within a
+//! time budget.
+
+use crate::gameplay::playing::{
+    PlayingState, ALL_TRUMP, RANK_STRENGTH_NON_TRUMP, RANK_STRENGTH_TRUMP,
+};
+use crate::solver::{nodes_searched, solve_for_team, solve_root_moves, with_deadline, Score};
+use pyo3::prelude::*;
+use std::time::{Duration, Instant};
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CardAnalysis {
+    #[pyo3(get)]
+    pub card: u8,
+    /// No card still held by another seat beats it in its suit.
+    #[pyo3(get)]
+    pub is_master: bool,
+    /// Cards of the same suit in the opponents' hands that beat it.
+    #[pyo3(get)]
+    pub beaten_by: u8,
+    /// Final points of the mover's team when this card is played now and both
+    /// sides play perfectly afterwards; `None` when the card is not legal.
+    #[pyo3(get)]
+    pub dd_points: Option<Score>,
+}
+
+/// Strength of `card` within its suit under contract `trump`: every suit ranks
+/// like trumps in all trump.
+pub(crate) fn strength(card: u8, trump: u8) -> u8 {
+    let rank = (card % 8) as usize;
+    if trump == ALL_TRUMP || card / 8 == trump {
+        RANK_STRENGTH_TRUMP[rank]
+    } else {
+        RANK_STRENGTH_NON_TRUMP[rank]
+    }
+}
+
+/// Cards of `cards` in the suit of `card` that beat it.
+fn beating(card: u8, cards: u32, trump: u8) -> u32 {
+    let suit_mask = 0xFFu32 << (card / 8 * 8);
+    (0..32u8)
+        .filter(|&c| cards & suit_mask & (1 << c) != 0)
+        .filter(|&c| strength(c, trump) > strength(card, trump))
+        .fold(0, |m, c| m | 1 << c)
+}
+
+/// Analysis of every card of the player to move, in card index order. Legal
+/// cards are solved to the end of the deal, which dominates the cost.
+pub fn analyze_hand(state: &PlayingState, tt_log2: Option<u8>) -> Vec<CardAnalysis> {
+    let me = state.current_player as usize;
+    let hand = state.hands[me];
+    let others = (0..4)
+        .filter(|&p| p != me)
+        .fold(0u32, |m, p| m | state.hands[p]);
+    let opponents = state.hands[(me + 1) % 4] | state.hands[(me + 3) % 4];
+
+    let team = me % 2;
+    let values = solve_root_moves(state, team, Some(32), tt_log2);
+
+    (0..32u8)
+        .filter(|&c| hand & (1 << c) != 0)
+        .map(|card| CardAnalysis {
+            card,
+            is_master: beating(card, others, state.trump) == 0,
+            beaten_by: beating(card, opponents, state.trump).count_ones() as u8,
+            dd_points: values.iter().find(|(c, _)| *c == card).map(|&(_, v)| v),
+        })
+        .collect()
+}
+
+#[pyclass]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PositionAnalysis {
+    #[pyo3(get)]
+    pub best_move: u8,
+    /// Final points of the analysed team after `best_move`.
+    #[pyo3(get)]
+    pub score: Score,
+    /// Expected continuation, starting with `best_move`.
+    #[pyo3(get)]
+    pub pv: Vec<u8>,
+    /// (card, value) of every legal move, in card index order.
+    #[pyo3(get)]
+    pub moves: Vec<(u8, Score)>,
+    /// Search depth of the values, in cards played.
+    #[pyo3(get)]
+    pub depth: u8,
+    /// The search reached the end of the deal: values are exact, not estimates.
+    #[pyo3(get)]
+    pub complete: bool,
+    #[pyo3(get)]
+    pub nodes: u64,
+}
+
+#[pymethods]
+impl PositionAnalysis {
+    pub fn __repr__(&self) -> String {
+        format!(
+            "PositionAnalysis(best_move={}, score={}, depth={}, complete={})",
+            self.best_move, self.score, self.depth, self.complete
+        )
+    }
+}
+
+/// `f` run under `deadline` (see `with_deadline`), or to completion without one.
+fn before<T>(deadline: Option<Instant>, f: impl FnOnce() -> T) -> Option<T> {
+    match deadline {
+        Some(d) => with_deadline(d, f),
+        None => Some(f()),
+    }
+}
+
+/// Analysis of `state` for `team`, deepening one trick at a time until the end of
+/// the deal or until `time_budget` runs out. An iteration cut short by the budget
+/// is discarded, so the values come from the deepest finished one; the first
+/// iteration always finishes. The principal variation is then extended move by
+/// move at that depth while budget remains.
+pub fn analyze_position(
+    state: &PlayingState,
+    team: usize,
+    time_budget: Option<Duration>,
+    tt_log2: Option<u8>,
+) -> Result<PositionAnalysis, String> {
+    if state.is_terminal() || state.get_legal_moves() == 0 {
+        return Err("No move to analyse: the deal is over".to_string());
+    }
+    let deadline = time_budget.map(|b| Instant::now() + b);
+    let nodes_before = nodes_searched();
+    let plies = state.hands.iter().map(|h| h.count_ones()).sum::<u32>() as u8;
+
+    // The root move is one of the plies: children search one less.
+    let mut depth = 4.min(plies);
+    let mut moves = solve_root_moves(state, team, Some(depth - 1), tt_log2);
+    while depth < plies {
+        let next = (depth + 4).min(plies);
+        match before(deadline, || {
+            solve_root_moves(state, team, Some(next - 1), tt_log2)
+        }) {
+            Some(m) => (depth, moves) = (next, m),
+            None => break,
+        }
+    }
+    let complete = depth == plies;
+
+    // The side to move picks its best value, the other team its worst.
+    let maximize = (state.current_player % 2) as usize == team;
+    let &(best_move, score) = if maximize {
+        moves
+            .iter()
+            .max_by_key(|&&(c, v)| (v, std::cmp::Reverse(c)))
+    } else {
+        moves.iter().min_by_key(|&&(c, v)| (v, c))
+    }
+    .unwrap();
+
+    let mut pv = vec![best_move];
+    let mut child = *state;
+    child.play_card(best_move);
+    let mut remaining = depth - 1;
+    while remaining > 0 && !child.is_terminal() {
+        let Some((_, mv)) = before(deadline, || {
+            solve_for_team(&child, team, Some(remaining), tt_log2)
+        }) else {
+            break;
+        };
+        if mv >= 32 || child.get_legal_moves() & (1 << mv) == 0 {
+            break;
+        }
+        pv.push(mv);
+        child.play_card(mv);
+        remaining -= 1;
+    }
+
+    Ok(PositionAnalysis {
+        best_move,
+        score,
+        pv,
+        moves,
+        depth,
+        complete,
+        nodes: nodes_searched() - nodes_before,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::playing::{
+        CLUBS, DIAMONDS, HEARTS, RANK_10, RANK_7, RANK_8, RANK_9, RANK_A, RANK_J, RANK_K, SPADES,
+    };
+
+    fn card(suit: u8, rank: u8) -> u8 {
+        suit * 8 + rank
+    }
+
+    #[test]
+    fn test_analyze_hand() {
+        let mut state = PlayingState::new(HEARTS);
+        state.hands = [
+            1 << card(SPADES, RANK_A) | 1 << card(HEARTS, RANK_9),
+            1 << card(HEARTS, RANK_J) | 1 << card(DIAMONDS, RANK_7),
+            1 << card(SPADES, RANK_10) | 1 << card(DIAMONDS, RANK_8),
+            1 << card(SPADES, RANK_K) | 1 << card(CLUBS, RANK_7),
+        ];
+        let analysis = analyze_hand(&state, None);
+        assert_eq!(analysis.len(), 2);
+        let ace = analysis[0];
+        assert_eq!(ace.card, card(SPADES, RANK_A));
+        assert!(ace.is_master);
+        assert_eq!(ace.beaten_by, 0);
+        let nine = analysis[1];
+        assert!(!nine.is_master);
+        assert_eq!(nine.beaten_by, 1);
+
+        // West trumps the ace with the jack (45 points), South's nine then takes
+        // the last trick. Leading the nine loses it to the jack, and North has to
+        // give the ten to keep the last trick.
+        assert_eq!(ace.dd_points, Some(24));
+        assert_eq!(nine.dd_points, Some(21));
+
+        // West must trump: the seven of diamonds is not solved.
+        let mut west = state;
+        west.play_card(card(SPADES, RANK_A));
+        let analysis = analyze_hand(&west, None);
+        assert_eq!(analysis[0].card, card(DIAMONDS, RANK_7));
+        assert_eq!(analysis[0].dd_points, None);
+        assert_eq!(analysis[1].dd_points, Some(45));
+
+        // Cards on the table no longer count against the ten.
+        let mut north = west;
+        north.play_card(card(HEARTS, RANK_J));
+        let analysis = analyze_hand(&north, None);
+        assert_eq!(analysis[0].card, card(DIAMONDS, RANK_8));
+        assert_eq!(analysis[0].dd_points, None);
+        assert!(analysis[1].is_master);
+        assert_eq!(analysis[1].dd_points, Some(24));
+    }
+
+    #[test]
+    fn test_analyze_position() {
+        let mut state = PlayingState::new(HEARTS);
+        state.hands = [
+            1 << card(SPADES, RANK_A) | 1 << card(HEARTS, RANK_9),
+            1 << card(HEARTS, RANK_J) | 1 << card(DIAMONDS, RANK_7),
+            1 << card(SPADES, RANK_10) | 1 << card(DIAMONDS, RANK_8),
+            1 << card(SPADES, RANK_K) | 1 << card(CLUBS, RANK_7),
+        ];
+        let analysis = analyze_position(&state, 0, None, None).unwrap();
+        assert!(analysis.complete);
+        assert_eq!(analysis.depth, 8);
+        assert_eq!(analysis.best_move, card(SPADES, RANK_A));
+        assert_eq!(analysis.score, 24);
+        assert_eq!(
+            analysis.moves,
+            vec![(card(SPADES, RANK_A), 24), (card(HEARTS, RANK_9), 21)]
+        );
+        // West trumps, North and East follow, then the last trick.
+        assert_eq!(analysis.pv.len(), 8);
+        assert_eq!(analysis.pv[1], card(HEARTS, RANK_J));
+
+        // Counted for East-West, South still picks the ace: it minimizes.
+        let ew = analyze_position(&state, 1, None, None).unwrap();
+        assert_eq!((ew.best_move, ew.score), (card(SPADES, RANK_A), 45));
+
+        // A spent budget still finishes the first trick's iteration.
+        let rushed = analyze_position(&state, 0, Some(Duration::ZERO), None).unwrap();
+        assert_eq!((rushed.depth, rushed.complete), (4, false));
+        assert_eq!(rushed.pv, vec![rushed.best_move]);
+    }
+}
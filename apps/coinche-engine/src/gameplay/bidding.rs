@@ -10,7 +10,7 @@ pub struct Bid {
     /// Bid value in points (e.g., 80, 90, ... 160).
     #[pyo3(get, set)]
     pub value: u8,
-    /// Trump suit: 0=Clubs,1=Spades,2=Hearts,3=Clubs,4=NoTrump,5=AllTrump (same encoding as PlayingState).
+    /// Trump suit: 0=Diamonds,1=Spades,2=Hearts,3=Clubs,4=NoTrump,5=AllTrump (same encoding as PlayingState, see `Suit`).
     #[pyo3(get, set)]
     pub trump: u8,
 }
@@ -132,12 +132,98 @@ impl BiddingState {
     }
 }
 
+#[pymethods]
+impl BiddingState {
+    /// Calls the seat to move may make: pass, every bid beating the contract
+    /// (none once coinched), then coinche or surcoinche when available to its
+    /// team. Empty once the auction is over.
+    pub fn legal_actions(&self) -> Vec<AuctionAction> {
+        if self.is_finished() {
+            return Vec::new();
+        }
+        let mut actions = vec![AuctionAction::Pass];
+        if self.coinche_level == 0 {
+            actions.extend(
+                legal_bids(self.contract)
+                    .into_iter()
+                    .map(AuctionAction::Bid),
+            );
+        }
+        if let Some(owner) = self.contract_owner {
+            let own_team = self.current_player % 2 == owner % 2;
+            match self.coinche_level {
+                0 if !own_team => actions.push(AuctionAction::Coinche),
+                1 if own_team => actions.push(AuctionAction::Surcoinche),
+                _ => {}
+            }
+        }
+        actions
+    }
+}
+
+/// A single auction call: pass, bid, coinche or surcoinche.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuctionAction {
+    Pass,
+    Bid(Bid),
+    Coinche,
+    Surcoinche,
+}
+
+impl AuctionAction {
+    /// Applies this call to the auction, with the same validation as the individual methods.
+    pub fn apply(&self, state: &mut BiddingState) -> Result<(), &'static str> {
+        match *self {
+            AuctionAction::Pass => state.apply_bid(None),
+            AuctionAction::Bid(b) => state.apply_bid(Some(b)),
+            AuctionAction::Coinche => state.coinche(),
+            AuctionAction::Surcoinche => state.surcoinche(),
+        }
+    }
+}
+
+/// Python accepts `None` (pass), a `Bid`, or the strings "pass", "coinche", "surcoinche".
+impl<'source> FromPyObject<'source> for AuctionAction {
+    fn extract(ob: &'source PyAny) -> PyResult<Self> {
+        if ob.is_none() {
+            return Ok(AuctionAction::Pass);
+        }
+        if let Ok(b) = ob.extract::<Bid>() {
+            return Ok(AuctionAction::Bid(b));
+        }
+        if let Ok(s) = ob.extract::<&str>() {
+            match s.to_ascii_lowercase().as_str() {
+                "pass" => return Ok(AuctionAction::Pass),
+                "coinche" => return Ok(AuctionAction::Coinche),
+                "surcoinche" => return Ok(AuctionAction::Surcoinche),
+                _ => {}
+            }
+        }
+        Err(pyo3::exceptions::PyValueError::new_err(
+            "Auction actions must be None, a Bid, 'pass', 'coinche' or 'surcoinche'",
+        ))
+    }
+}
+
+impl IntoPy<PyObject> for AuctionAction {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        match self {
+            AuctionAction::Pass => py.None(),
+            AuctionAction::Bid(b) => b.into_py(py),
+            AuctionAction::Coinche => "coinche".into_py(py),
+            AuctionAction::Surcoinche => "surcoinche".into_py(py),
+        }
+    }
+}
+
+/// Values a bid can take, 252 being the capot.
+pub const BID_VALUES: [u8; 10] = [80, 90, 100, 110, 120, 130, 140, 150, 160, 252];
+
 /// Returns the list of legal bids given the current highest bid (or `None` if no bid yet).
 /// The ordering follows Contree rules: a higher value always beats a lower one;
 /// for equal values the suit order is Clubs < Diamonds < Hearts < Spades < AllTrump < NoTrump.
 pub fn legal_bids(current: Option<Bid>) -> Vec<Bid> {
     // All possible values and suits.
-    const VALUES: [u8; 10] = [80, 90, 100, 110, 120, 130, 140, 150, 160, 252];
     const SUITS: [u8; 6] = [0, 1, 2, 3, 4, 5]; // same encoding as PlayingState constants.
 
     let mut bids = Vec::new();
@@ -145,7 +231,7 @@ pub fn legal_bids(current: Option<Bid>) -> Vec<Bid> {
     match current {
         None => {
             // First player can bid any value/suit.
-            for &v in VALUES.iter() {
+            for &v in BID_VALUES.iter() {
                 for &s in SUITS.iter() {
                     bids.push(Bid::new(v, s));
                 }
@@ -153,7 +239,7 @@ pub fn legal_bids(current: Option<Bid>) -> Vec<Bid> {
         }
         Some(cur) => {
             // Higher value bids.
-            for &v in VALUES.iter() {
+            for &v in BID_VALUES.iter() {
                 if v > cur.value {
                     for &s in SUITS.iter() {
                         bids.push(Bid::new(v, s));
@@ -192,6 +278,31 @@ pub fn beats(current: Option<Bid>, candidate: Bid) -> bool {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_legal_actions_follow_coinche_state() {
+        let mut state = BiddingState::new(3);
+        assert_eq!(state.legal_actions().len(), 61);
+        state.apply_bid(Some(Bid::new(160, 2))).unwrap();
+        // West, an opponent: pass, higher bids or coinche.
+        let actions = state.legal_actions();
+        assert_eq!(actions.first(), Some(&AuctionAction::Pass));
+        assert_eq!(actions.last(), Some(&AuctionAction::Coinche));
+        assert!(!actions.contains(&AuctionAction::Bid(Bid::new(160, 1))));
+        assert!(actions.contains(&AuctionAction::Bid(Bid::new(160, 3))));
+        state.apply_bid(None).unwrap();
+        // North, the declarer's partner, cannot coinche.
+        assert!(!state.legal_actions().contains(&AuctionAction::Coinche));
+        state.apply_bid(None).unwrap();
+        state.coinche().unwrap();
+        // South, the declarer, may only pass or surcoinche.
+        assert_eq!(
+            state.legal_actions(),
+            vec![AuctionAction::Pass, AuctionAction::Surcoinche]
+        );
+        state.surcoinche().unwrap();
+        assert!(state.legal_actions().is_empty());
+    }
+
     #[test]
     fn test_initial_legal_bids() {
         // No current bid -> all bids are legal.
@@ -0,0 +1,330 @@
+//! Bots acting for one seat of a `CoincheMatch` from that seat's observation only:
+//! its own cards, the auction and the cards played. Hidden hands never reach the
+//! engines; the observation gives the other seats an arbitrary deal of the unseen
+//! cards, which PIMC then re-deals in every world it samples. The one exception is
+//! the double-dummy strength, which sees every hand on purpose.
+
+use crate::data_gen::bidding::contract_strength;
+use crate::data_gen::common::sample_rng;
+use crate::data_gen::gameplay::{solve_pimc_parallel, BidConstraint, PimcVoting};
+use crate::gameplay::bidding::{beats, AuctionAction, Bid, BiddingState};
+use crate::gameplay::manager::{CoincheMatch, Phase};
+use crate::gameplay::playing::{card_points, PlayingState};
+use crate::gameplay::threshold_bidder::ThresholdBidder;
+use crate::gameplay::worlds::{voids_from_plays, WorldConstraints};
+use crate::solver::solve_for_team;
+use pyo3::prelude::*;
+
+/// Engine a bot plays its cards with. Bids always come from the hand heuristic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BotStrength {
+    /// Rules of thumb on the current trick, no search.
+    Heuristic,
+    /// PIMC over sampled deals of the unseen cards, plurality voting.
+    Pimc,
+    /// PIMC with every card solved in each world, best mean value wins.
+    PimcExpectedValue,
+    /// The solver's best card on the real deal: cheats by seeing every hand.
+    DoubleDummy,
+}
+
+impl BotStrength {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "heuristic" => Ok(BotStrength::Heuristic),
+            "pimc" => Ok(BotStrength::Pimc),
+            "pimc_ev" => Ok(BotStrength::PimcExpectedValue),
+            "double_dummy" => Ok(BotStrength::DoubleDummy),
+            _ => Err(format!(
+                "Unknown bot strength '{}' (expected 'heuristic', 'pimc', 'pimc_ev' or 'double_dummy')",
+                name
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BotStrength::Heuristic => "heuristic",
+            BotStrength::Pimc => "pimc",
+            BotStrength::PimcExpectedValue => "pimc_ev",
+            BotStrength::DoubleDummy => "double_dummy",
+        }
+    }
+}
+
+/// Action of a deal, as chosen by a bot: an auction call or a card.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BotAction {
+    Call(AuctionAction),
+    Play(u8),
+}
+
+/// Python passes a card as an int, a call as an `AuctionAction` does.
+impl<'source> FromPyObject<'source> for BotAction {
+    fn extract(ob: &'source PyAny) -> PyResult<Self> {
+        match ob.extract::<u8>() {
+            Ok(card) => Ok(BotAction::Play(card)),
+            Err(_) => ob.extract().map(BotAction::Call),
+        }
+    }
+}
+
+impl IntoPy<PyObject> for BotAction {
+    fn into_py(self, py: Python<'_>) -> PyObject {
+        match self {
+            BotAction::Call(call) => call.into_py(py),
+            BotAction::Play(card) => card.into_py(py),
+        }
+    }
+}
+
+/// The play state as `seat` sees it: its own hand and the cards on the table are
+/// real, the unseen cards are dealt to the other seats in index order, keeping
+/// their hand sizes.
+pub fn observation(state: &PlayingState, seat: u8) -> PlayingState {
+    let me = seat as usize;
+    let mut unseen = (0..4)
+        .filter(|&p| p != me)
+        .fold(0u32, |m, p| m | state.hands[p]);
+    let mut obs = *state;
+    for p in (0..4).filter(|&p| p != me) {
+        let mut hand = 0;
+        for _ in 0..state.hands[p].count_ones() {
+            hand |= unseen & unseen.wrapping_neg();
+            unseen &= unseen - 1;
+        }
+        obs.hands[p] = hand;
+    }
+    obs
+}
+
+/// Action of the seat to move, `seat`, computed from its observation.
+pub fn bot_action(
+    m: &CoincheMatch,
+    seat: u8,
+    strength: BotStrength,
+    iterations: usize,
+    seed: Option<u64>,
+    bidder: Option<&ThresholdBidder>,
+) -> Result<BotAction, String> {
+    if m.current_seat() != Some(seat) {
+        return Err(format!("Seat {} is not to move", seat));
+    }
+    match m.phase {
+        Phase::Bidding(ref state) => {
+            let hand = m.initial_hands[seat as usize];
+            Ok(BotAction::Call(match bidder {
+                Some(bidder) => bidder.choose(state, hand),
+                None => heuristic_call(state, hand),
+            }))
+        }
+        Phase::Playing(ref state) => Ok(BotAction::Play(bot_card(
+            m, state, seat, strength, iterations, seed, true,
+        ))),
+        Phase::Finished(_) => Err("The match is over".to_string()),
+    }
+}
+
+/// Card of `seat`, to move in `state`, the play state of `m`. With `inference`,
+/// PIMC only samples worlds where the declarer's hand can have made its bid.
+pub(crate) fn bot_card(
+    m: &CoincheMatch,
+    state: &PlayingState,
+    seat: u8,
+    strength: BotStrength,
+    iterations: usize,
+    seed: Option<u64>,
+    inference: bool,
+) -> u8 {
+    // The declarer's played cards are public, so the constraint is too.
+    let constraints: Vec<BidConstraint> = match (m.contract, m.contract_owner) {
+        (Some(bid), Some(owner)) if inference && owner != seat => {
+            vec![BidConstraint::new(owner, bid, m.played_by(owner))]
+        }
+        _ => Vec::new(),
+    };
+    let voids = voids_from_plays(&m.plays());
+    strength_card(state, seat, strength, iterations, seed, &constraints, voids)
+}
+
+/// Card of `seat`, to move in `state`, with PIMC sampling only worlds that meet
+/// `constraints` and keep `voids`; the position alone, without the match it comes from.
+pub(crate) fn strength_card(
+    state: &PlayingState,
+    seat: u8,
+    strength: BotStrength,
+    iterations: usize,
+    seed: Option<u64>,
+    constraints: &[BidConstraint],
+    voids: [u8; 4],
+) -> u8 {
+    let obs = observation(state, seat);
+    let team = (seat % 2) as usize;
+    match strength {
+        BotStrength::Heuristic => heuristic_card(&obs),
+        BotStrength::DoubleDummy => solve_for_team(state, team, Some(32), None).1,
+        BotStrength::Pimc | BotStrength::PimcExpectedValue => {
+            let voting = if strength == BotStrength::Pimc {
+                PimcVoting::Plurality
+            } else {
+                PimcVoting::ExpectedValue
+            };
+            // Start from a sampled world rather than the arbitrary deal of
+            // the observation, which a single iteration would solve as is.
+            let world = WorldConstraints::new(&obs)
+                .with_voids(voids)
+                .sample(&obs, &mut sample_rng(seed, u64::MAX))
+                .expect("the voids of legal plays fit the deal");
+            solve_pimc_parallel(
+                &world,
+                team,
+                iterations,
+                voting,
+                constraints,
+                voids,
+                None,
+                seed,
+            )
+            .best_card
+        }
+    }
+}
+
+/// Highest contract value a hand of `contract_strength` `strength` supports.
+pub(crate) fn supported_value(strength: i32) -> i32 {
+    (70 + 2 * strength).min(160)
+}
+
+/// Bids the lowest contract beating the current one in the suit `hand` is best
+/// at, up to the value its strength supports (`BidConstraint` scale). Passes
+/// over a partner's contract, and never coinches.
+fn heuristic_call(state: &BiddingState, hand: u32) -> AuctionAction {
+    let partner_owns = state
+        .contract_owner
+        .is_some_and(|o| o % 2 == state.current_player % 2);
+    if state.coinche_level > 0 || partner_owns {
+        return AuctionAction::Pass;
+    }
+    let (strength, trump) = (0..6u8)
+        .map(|t| (contract_strength(hand, t), t))
+        .max_by_key(|&(s, t)| (s, std::cmp::Reverse(t)))
+        .unwrap();
+    (80..=supported_value(strength))
+        .step_by(10)
+        .map(|v| Bid::new(v as u8, trump))
+        .find(|&b| beats(state.contract, b))
+        .map_or(AuctionAction::Pass, AuctionAction::Bid)
+}
+
+/// Leads its cheapest card; otherwise loads points on a partner who is winning,
+/// wins the trick as cheaply as possible, or discards its cheapest card.
+pub(crate) fn heuristic_card(state: &PlayingState) -> u8 {
+    let seat = state.current_player;
+    let legal = state.get_legal_moves();
+    let cards = (0..32u8).filter(|&c| legal & (1 << c) != 0);
+    let cost = |&c: &u8| (card_points(c, state.trump), c);
+    let cheapest = cards.clone().min_by_key(cost).expect("no legal move");
+    if state.trick_size == 0 {
+        return cheapest;
+    }
+
+    if state.get_current_trick_winner_player() == (seat + 2) % 4 {
+        let is_trump = |c: u8| state.trump == 5 || c / 8 == state.trump;
+        return cards
+            .clone()
+            .filter(|&c| !is_trump(c))
+            .max_by_key(|&c| (card_points(c, state.trump), std::cmp::Reverse(c)))
+            .unwrap_or(cheapest);
+    }
+
+    let wins = |&c: &u8| {
+        let mut next = *state;
+        next.play_card(c);
+        if next.trick_size == 0 {
+            next.last_trick_winner == Some(seat)
+        } else {
+            next.get_current_trick_winner_player() == seat
+        }
+    };
+    cards.filter(wins).min_by_key(cost).unwrap_or(cheapest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::notation::parse_card;
+    use crate::gameplay::playing::{HEARTS, SPADES};
+
+    fn cards(names: &[&str]) -> u32 {
+        names.iter().fold(0, |m, n| m | 1 << parse_card(n).unwrap())
+    }
+
+    #[test]
+    fn test_bot_only_sees_its_observation() {
+        // The belote scenario after six tricks and South's Ace of Clubs: West to play.
+        let auction = [
+            AuctionAction::Bid(Bid::new(80, HEARTS)),
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+        ];
+        let played: Vec<u8> =
+            "JH 7H KH 7C 9H 8H QH 8C AH 8D 7S 9S 10H 9D 8S 10S AS JS 9C QC KS QS JC JD AC"
+                .split(' ')
+                .map(|n| parse_card(n).unwrap())
+                .collect();
+        let mut hands = [
+            cards(&["7D"]),
+            cards(&["10C", "KC"]),
+            cards(&["QD", "KD"]),
+            cards(&["10D", "AD"]),
+        ];
+        let a = CoincheMatch::from_position(3, hands, &auction, &played).unwrap();
+        // Same observation for West, with North and East's Diamonds swapped.
+        hands[2] = cards(&["10D", "KD"]);
+        hands[3] = cards(&["QD", "AD"]);
+        let b = CoincheMatch::from_position(3, hands, &auction, &played).unwrap();
+
+        let (Phase::Playing(ref sa), Phase::Playing(ref sb)) = (&a.phase, &b.phase) else {
+            panic!("Should be in Playing phase");
+        };
+        assert_eq!(observation(sa, 1).hands, observation(sb, 1).hands);
+
+        for strength in [
+            BotStrength::Heuristic,
+            BotStrength::Pimc,
+            BotStrength::PimcExpectedValue,
+        ] {
+            let action = bot_action(&a, 1, strength, 8, Some(7), None).unwrap();
+            assert_eq!(
+                bot_action(&b, 1, strength, 8, Some(7), None).unwrap(),
+                action
+            );
+            let BotAction::Play(card) = action else {
+                panic!("Expected a card");
+            };
+            assert!(sa.get_legal_moves() & (1 << card) != 0);
+        }
+        assert!(bot_action(&a, 2, BotStrength::Heuristic, 1, None, None).is_err());
+    }
+
+    #[test]
+    fn test_heuristic_calls() {
+        // P0 holds every Spade: it opens in Spades and its partner then passes.
+        let mut m = CoincheMatch::new_rs(3, [0x0000_FF00, 0x0000_00FF, 0x00FF_0000, 0xFF00_0000]);
+        let call = bot_action(&m, 0, BotStrength::Heuristic, 1, None, None).unwrap();
+        assert_eq!(
+            call,
+            BotAction::Call(AuctionAction::Bid(Bid::new(80, SPADES)))
+        );
+        let Phase::Bidding(ref mut state) = m.phase else {
+            panic!("Should be in Bidding phase");
+        };
+        state.apply_bid(Some(Bid::new(80, SPADES))).unwrap();
+        state.apply_bid(None).unwrap();
+        assert_eq!(
+            bot_action(&m, 2, BotStrength::Heuristic, 1, None, None).unwrap(),
+            BotAction::Call(AuctionAction::Pass)
+        );
+    }
+}
@@ -0,0 +1,250 @@
+//! Difficulty ladder of the bots a table can seat, from a beginner that blunders
+//! to a perfect-information player:
+//!
+//! - beginner: heuristic card play, a random legal card 15% of the time;
+//! - intermediate: PIMC over 10 worlds, without reading the auction;
+//! - expert: PIMC over 100 worlds, sampling only deals consistent with the bid;
+//! - perfect: double-dummy, it sees every hand.
+//!
+//! Calls always come from the hand heuristic, and blunders only hit card play: a
+//! random call would be a bid of any value, not a plausible mistake.
+
+use crate::data_gen::common::sample_rng;
+use crate::gameplay::bot::{bot_action, bot_card, BotAction, BotStrength};
+use crate::gameplay::manager::{CoincheMatch, Phase};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::seq::IteratorRandom;
+use rand::Rng;
+
+/// RNG streams of the blunders, one per number of cards left, apart from the one
+/// PIMC samples its first world with.
+const BLUNDER_STREAM: u64 = u64::MAX - 1;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BotLevel {
+    Beginner,
+    Intermediate,
+    Expert,
+    Perfect,
+}
+
+impl BotLevel {
+    pub const ALL: [BotLevel; 4] = [
+        BotLevel::Beginner,
+        BotLevel::Intermediate,
+        BotLevel::Expert,
+        BotLevel::Perfect,
+    ];
+
+    pub fn parse(name: &str) -> Result<Self, String> {
+        BotLevel::ALL
+            .into_iter()
+            .find(|l| l.name() == name.to_ascii_lowercase())
+            .ok_or_else(|| {
+                format!(
+                    "Unknown bot level '{}' (expected 'beginner', 'intermediate', 'expert' or 'perfect')",
+                    name
+                )
+            })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BotLevel::Beginner => "beginner",
+            BotLevel::Intermediate => "intermediate",
+            BotLevel::Expert => "expert",
+            BotLevel::Perfect => "perfect",
+        }
+    }
+}
+
+/// Settings of a bot: its card-play engine and how often it blunders.
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BotPreset {
+    pub strength: BotStrength,
+    /// Worlds sampled per card by the PIMC strengths.
+    #[pyo3(get, set)]
+    pub iterations: usize,
+    /// Whether PIMC only samples deals where the declarer could make its bid.
+    #[pyo3(get, set)]
+    pub inference: bool,
+    /// Probability of playing a random legal card instead of the engine's.
+    #[pyo3(get, set)]
+    pub blunder_rate: f64,
+}
+
+impl BotPreset {
+    pub fn for_level(level: BotLevel) -> Self {
+        let preset = |strength, iterations, inference, blunder_rate| BotPreset {
+            strength,
+            iterations,
+            inference,
+            blunder_rate,
+        };
+        match level {
+            BotLevel::Beginner => preset(BotStrength::Heuristic, 1, false, 0.15),
+            BotLevel::Intermediate => preset(BotStrength::Pimc, 10, false, 0.0),
+            BotLevel::Expert => preset(BotStrength::Pimc, 100, true, 0.0),
+            BotLevel::Perfect => preset(BotStrength::DoubleDummy, 1, false, 0.0),
+        }
+    }
+
+    /// Action of `seat`, which must be the seat to move. With a seed, blunders and
+    /// PIMC worlds only depend on (seed, position).
+    pub fn action(
+        &self,
+        m: &CoincheMatch,
+        seat: u8,
+        seed: Option<u64>,
+    ) -> Result<BotAction, String> {
+        let Phase::Playing(ref state) = m.phase else {
+            return bot_action(m, seat, self.strength, self.iterations, seed, None);
+        };
+        if m.current_seat() != Some(seat) {
+            return Err(format!("Seat {} is not to move", seat));
+        }
+        let cards_left: u32 = state.hands.iter().map(|h| h.count_ones()).sum();
+        let mut rng = sample_rng(seed, BLUNDER_STREAM - cards_left as u64);
+        if self.blunder_rate > 0.0 && rng.gen_bool(self.blunder_rate.min(1.0)) {
+            let legal = state.get_legal_moves();
+            let card = (0..32u8)
+                .filter(|&c| legal & (1 << c) != 0)
+                .choose(&mut rng);
+            return Ok(BotAction::Play(card.expect("no legal move")));
+        }
+        let card = bot_card(
+            m,
+            state,
+            seat,
+            self.strength,
+            self.iterations,
+            seed,
+            self.inference,
+        );
+        Ok(BotAction::Play(card))
+    }
+}
+
+#[pymethods]
+impl BotPreset {
+    /// Preset of a ladder level: "beginner", "intermediate", "expert" or "perfect".
+    #[new]
+    #[pyo3(signature = (level="intermediate"))]
+    pub fn new(level: &str) -> PyResult<Self> {
+        BotLevel::parse(level)
+            .map(BotPreset::for_level)
+            .map_err(PyValueError::new_err)
+    }
+
+    /// Card-play engine, as `CoincheMatch.bot_action` names it.
+    #[getter(strength)]
+    pub fn strength_name(&self) -> &'static str {
+        self.strength.name()
+    }
+
+    #[setter(strength)]
+    pub fn set_strength(&mut self, name: &str) -> PyResult<()> {
+        self.strength = BotStrength::parse(name).map_err(PyValueError::new_err)?;
+        Ok(())
+    }
+
+    /// Action of `seat` in `m` (see `CoincheMatch.bot_action` for its form).
+    #[pyo3(name = "action", signature = (m, seat, seed=None))]
+    pub fn py_action(
+        &self,
+        py: Python,
+        m: &CoincheMatch,
+        seat: u8,
+        seed: Option<u64>,
+    ) -> PyResult<PyObject> {
+        let action = py
+            .allow_threads(|| self.action(m, seat, seed))
+            .map_err(PyValueError::new_err)?;
+        Ok(action.into_py(py))
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "BotPreset(strength='{}', iterations={}, inference={}, blunder_rate={})",
+            self.strength.name(),
+            self.iterations,
+            self.inference,
+            self.blunder_rate
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::bidding::{AuctionAction, Bid};
+    use crate::gameplay::notation::parse_card;
+    use crate::gameplay::playing::HEARTS;
+    use crate::solver::solve_root_moves;
+
+    #[test]
+    fn test_bot_presets() {
+        for level in BotLevel::ALL {
+            assert_eq!(BotLevel::parse(&level.name().to_uppercase()), Ok(level));
+        }
+        assert!(BotLevel::parse("grandmaster").is_err());
+
+        // The belote scenario after six tricks and South's Ace of Clubs: West to play.
+        let auction = [
+            AuctionAction::Bid(Bid::new(80, HEARTS)),
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+        ];
+        let played: Vec<u8> =
+            "JH 7H KH 7C 9H 8H QH 8C AH 8D 7S 9S 10H 9D 8S 10S AS JS 9C QC KS QS JC JD AC"
+                .split(' ')
+                .map(|n| parse_card(n).unwrap())
+                .collect();
+        let hands = ["7D", "10C KC", "QD KD", "10D AD"].map(|h| {
+            h.split(' ')
+                .fold(0u32, |m, n| m | 1 << parse_card(n).unwrap())
+        });
+        let m = CoincheMatch::from_position(3, hands, &auction, &played).unwrap();
+        let Phase::Playing(ref state) = m.phase else {
+            panic!("Should be in Playing phase");
+        };
+        // A bot that always blunders still plays a legal card, and the same
+        // one for the same seed.
+        let reckless = BotPreset {
+            blunder_rate: 1.0,
+            ..BotPreset::for_level(BotLevel::Beginner)
+        };
+        let BotAction::Play(card) = reckless.action(&m, 1, Some(5)).unwrap() else {
+            panic!("Expected a card");
+        };
+        assert!(state.get_legal_moves() & (1 << card) != 0);
+        assert_eq!(
+            reckless.action(&m, 1, Some(5)).unwrap(),
+            BotAction::Play(card)
+        );
+        // The perfect bot plays one of the cards of highest double-dummy value.
+        let perfect = BotPreset::for_level(BotLevel::Perfect);
+        let BotAction::Play(card) = perfect.action(&m, 1, None).unwrap() else {
+            panic!("Expected a card");
+        };
+        let values = solve_root_moves(state, 1, Some(32), None);
+        let best = values.iter().map(|&(_, v)| v).max().unwrap();
+        assert!(values.contains(&(card, best)));
+        assert!(perfect.action(&m, 2, None).is_err());
+
+        // Calls come from the hand heuristic at every level.
+        let m = CoincheMatch::new_rs(3, [0x0000_00FF, 0x0000_FF00, 0x00FF_0000, 0xFF00_0000]);
+        let expert = BotPreset::for_level(BotLevel::Expert);
+        assert_eq!(
+            expert.action(&m, 0, None).unwrap(),
+            bot_action(&m, 0, BotStrength::Heuristic, 1, None, None).unwrap()
+        );
+        assert!(matches!(
+            expert.action(&m, 0, None).unwrap(),
+            BotAction::Call(AuctionAction::Bid(_))
+        ));
+    }
+}
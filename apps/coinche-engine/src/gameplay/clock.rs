@@ -0,0 +1,114 @@
+//! Thinking-time control for matches: per-seat time banks with a per-move increment
+//! and an optional hard cap on a single move.
+
+use pyo3::prelude::*;
+use std::time::Instant;
+
+#[pyclass]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeControl {
+    /// Starting time bank of each seat, in milliseconds.
+    #[pyo3(get)]
+    pub initial_ms: u64,
+    /// Added to a seat's bank after each of its legal actions.
+    #[pyo3(get)]
+    pub increment_ms: u64,
+    /// Maximum thinking time for a single action, whatever the bank.
+    #[pyo3(get)]
+    pub max_move_ms: Option<u64>,
+}
+
+#[pymethods]
+impl TimeControl {
+    #[new]
+    #[pyo3(signature = (initial_ms, increment_ms=0, max_move_ms=None))]
+    pub fn new(initial_ms: u64, increment_ms: u64, max_move_ms: Option<u64>) -> Self {
+        Self {
+            initial_ms,
+            increment_ms,
+            max_move_ms,
+        }
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "TimeControl(initial_ms={}, increment_ms={}, max_move_ms={:?})",
+            self.initial_ms, self.increment_ms, self.max_move_ms
+        )
+    }
+}
+
+/// Time banks of the four seats. The clock of the seat to move runs from the
+/// last `restart` until its action is charged.
+#[derive(Debug, Clone)]
+pub struct MatchClock {
+    pub control: TimeControl,
+    pub remaining_ms: [u64; 4],
+    turn_start: Instant,
+}
+
+impl MatchClock {
+    pub fn new(control: TimeControl) -> Self {
+        Self {
+            control,
+            remaining_ms: [control.initial_ms; 4],
+            turn_start: Instant::now(),
+        }
+    }
+
+    /// Starts timing the next action.
+    pub fn restart(&mut self) {
+        self.turn_start = Instant::now();
+    }
+
+    pub fn elapsed_ms(&self) -> u64 {
+        self.turn_start.elapsed().as_millis() as u64
+    }
+
+    /// True if `seat` would be out of time after thinking `elapsed_ms`.
+    pub fn is_flagged(&self, seat: u8, elapsed_ms: u64) -> bool {
+        elapsed_ms > self.remaining_ms[seat as usize]
+            || self.control.max_move_ms.is_some_and(|max| elapsed_ms > max)
+    }
+
+    /// Deducts `elapsed_ms` from the bank of `seat`. Returns false when the seat
+    /// ran out of time (its bank is then left at 0).
+    pub fn charge(&mut self, seat: u8, elapsed_ms: u64) -> bool {
+        let flagged = self.is_flagged(seat, elapsed_ms);
+        let bank = &mut self.remaining_ms[seat as usize];
+        *bank = bank.saturating_sub(elapsed_ms);
+        if flagged {
+            *bank = 0;
+        }
+        !flagged
+    }
+
+    /// Credits the increment once an action has been accepted.
+    pub fn credit_increment(&mut self, seat: u8) {
+        self.remaining_ms[seat as usize] += self.control.increment_ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bank_and_increment() {
+        let mut clock = MatchClock::new(TimeControl::new(1000, 100, None));
+        assert!(clock.charge(0, 400));
+        clock.credit_increment(0);
+        assert_eq!(clock.remaining_ms, [700, 1000, 1000, 1000]);
+
+        assert!(!clock.charge(0, 701));
+        assert_eq!(clock.remaining_ms[0], 0);
+    }
+
+    #[test]
+    fn test_max_move_budget() {
+        let mut clock = MatchClock::new(TimeControl::new(10_000, 0, Some(500)));
+        assert!(clock.charge(2, 500));
+        assert!(!clock.charge(2, 501));
+        assert_eq!(clock.remaining_ms[2], 0);
+    }
+}
@@ -0,0 +1,185 @@
+//! Corpora of recorded deals with their auctions, for strength measurements on
+//! realistic deals rather than only random ones.
+//!
+//! A corpus is a sequence of game records in the `transcript` format, each one
+//! starting at its `Dealer:` line; gzip-compressed files need the `deal-corpus`
+//! feature. That feature also embeds `data/deal_corpus.txt.gz` in the library: 200
+//! anonymous deals (no player names, dealer rotating) with threshold-bidder
+//! auctions and heuristic play, a test corpus for the benchmarks, the evaluator
+//! correlation tests and the tournament's fixed-deal mode until human recordings
+//! replace it.
+
+use std::path::Path;
+
+use crate::gameplay::manager::{CoincheMatch, Phase};
+use crate::gameplay::playing::PlayingState;
+use crate::gameplay::transcript::parse_transcript;
+
+/// Matches recorded in `text`, in order. Every record is replayed and checked as in
+/// `parse_transcript`.
+pub fn parse_corpus(text: &str) -> Result<Vec<CoincheMatch>, String> {
+    let mut records: Vec<Vec<&str>> = Vec::new();
+    for line in text.lines() {
+        if line.trim_start().starts_with("Dealer:") {
+            records.push(Vec::new());
+        }
+        match records.last_mut() {
+            Some(record) => record.push(line),
+            None if line.trim().is_empty() => {}
+            None => return Err(format!("Text before the first record: {}", line.trim())),
+        }
+    }
+    records
+        .iter()
+        .enumerate()
+        .map(|(i, lines)| {
+            parse_transcript(&lines.join("\n")).map_err(|e| format!("Record {}: {}", i, e))
+        })
+        .collect()
+}
+
+/// Matches of the corpus file at `path`, gzip-compressed when it ends in `.gz`.
+pub fn load_corpus(path: &Path) -> Result<Vec<CoincheMatch>, String> {
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let text = if path.extension().is_some_and(|e| e == "gz") {
+        gunzip(&bytes)?
+    } else {
+        String::from_utf8(bytes).map_err(|e| e.to_string())?
+    };
+    parse_corpus(&text).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+#[cfg(feature = "deal-corpus")]
+fn gunzip(bytes: &[u8]) -> Result<String, String> {
+    use std::io::Read;
+    let mut text = String::new();
+    flate2::read::GzDecoder::new(bytes)
+        .read_to_string(&mut text)
+        .map_err(|e| e.to_string())?;
+    Ok(text)
+}
+
+#[cfg(not(feature = "deal-corpus"))]
+fn gunzip(_bytes: &[u8]) -> Result<String, String> {
+    Err("Compressed corpora need the deal-corpus feature".to_string())
+}
+
+/// The corpus embedded at build time (see the module documentation).
+#[cfg(feature = "deal-corpus")]
+pub fn embedded_corpus() -> Result<Vec<CoincheMatch>, String> {
+    static CORPUS: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/data/deal_corpus.txt.gz"
+    ));
+    parse_corpus(&gunzip(CORPUS)?)
+}
+
+/// The embedded corpus, or an error without the deal-corpus feature.
+pub fn try_embedded_corpus() -> Result<Vec<CoincheMatch>, String> {
+    #[cfg(feature = "deal-corpus")]
+    {
+        embedded_corpus()
+    }
+    #[cfg(not(feature = "deal-corpus"))]
+    {
+        Err("The embedded corpus needs the deal-corpus feature".to_string())
+    }
+}
+
+/// Initial hands of `matches`, 4 per deal in seat order, as the batch solvers take them.
+pub fn corpus_hands(matches: &[CoincheMatch]) -> Vec<u32> {
+    matches.iter().flat_map(|m| m.initial_hands).collect()
+}
+
+/// Positions of the recorded play after `tricks` complete tricks, one per match
+/// played that far (deals passed out have none).
+pub fn corpus_positions(matches: &[CoincheMatch], tricks: usize) -> Vec<PlayingState> {
+    matches
+        .iter()
+        .filter(|m| m.played_cards.len() >= tricks * 4)
+        .filter_map(|m| {
+            let played = &m.played_cards[..tricks * 4];
+            let gone = played.iter().fold(0u32, |mask, &c| mask | 1 << c);
+            let hands = m.initial_hands.map(|h| h & !gone);
+            let position = CoincheMatch::from_position_with_rules(
+                m.dealer, hands, &m.auction, played, m.rules,
+            )
+            .ok()?;
+            match position.phase {
+                Phase::Playing(state) => Some(state),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::bidding::{AuctionAction, Bid};
+    use crate::gameplay::manager::Phase;
+    use crate::gameplay::transcript::to_transcript;
+
+    #[test]
+    fn test_parse_corpus() {
+        let deal: [u32; 4] =
+            std::array::from_fn(|p| (0..32).filter(|c| c % 4 == p).fold(0, |m, c| m | 1 << c));
+        let auction = [
+            AuctionAction::Bid(Bid::new(90, 0)),
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+        ];
+        let mut records = Vec::new();
+        for dealer in 0..3 {
+            let mut m = CoincheMatch::from_position(dealer, deal, &auction, &[]).unwrap();
+            while let Phase::Playing(ref state) = m.phase {
+                let card = state.get_legal_moves().trailing_zeros() as u8;
+                m.play_card(card).unwrap();
+            }
+            records.push(to_transcript(&m));
+        }
+        let text = format!("\n{}", records.join("\n"));
+
+        let matches = parse_corpus(&text).unwrap();
+        assert_eq!(matches.len(), 3);
+        assert_eq!(matches[2].dealer, 2);
+        assert_eq!(corpus_hands(&matches)[4..8], deal);
+        assert!(parse_corpus("").unwrap().is_empty());
+        assert!(parse_corpus("Contract: 90 S by S")
+            .unwrap_err()
+            .contains("before the first record"));
+        records[1] = records[1].replacen("Dealer: ", "Dealer: X", 1);
+        let broken = records.join("\n");
+        assert!(parse_corpus(&broken).unwrap_err().starts_with("Record 1:"));
+
+        let path = std::env::temp_dir().join(format!("corpus_{}.txt", std::process::id()));
+        std::fs::write(&path, &text).unwrap();
+        assert_eq!(load_corpus(&path).unwrap().len(), 3);
+        std::fs::remove_file(&path).unwrap();
+
+        let positions = corpus_positions(&matches, 5);
+        assert_eq!(positions.len(), 3);
+        for (state, m) in positions.iter().zip(&matches) {
+            assert_eq!(state.hands.map(u32::count_ones), [3; 4]);
+            assert_eq!(state.trick_size, 0);
+            assert_eq!(state.trump, 0);
+            assert!(state
+                .hands
+                .iter()
+                .all(|&h| m.initial_hands.iter().any(|&i| h & !i == 0)));
+        }
+        assert!(corpus_positions(&matches, 9).is_empty());
+    }
+
+    #[cfg(feature = "deal-corpus")]
+    #[test]
+    fn test_embedded_corpus() {
+        let matches = embedded_corpus().unwrap();
+        assert_eq!(matches.len(), 200);
+        assert!(matches
+            .iter()
+            .all(|m| m.initial_hands.iter().fold(0, |a, h| a | h) == u32::MAX));
+        assert!(!corpus_positions(&matches, 4).is_empty());
+    }
+}
@@ -0,0 +1,185 @@
+//! Deal validation helpers shared by position setup and analysis tools, and the
+//! deal IDs that name a deal across datasets, logs and caches.
+
+use crate::data_gen::common::{generate_random_hands, sample_rng};
+
+/// Mask with all 32 cards set.
+pub const FULL_DECK: u32 = 0xFFFF_FFFF;
+
+/// Checks that `hands` is a complete deal: 8 cards per hand and every card
+/// of the 32-card deck held by exactly one player.
+pub fn validate_deal(hands: &[u32; 4]) -> Result<(), String> {
+    for (p, &hand) in hands.iter().enumerate() {
+        let count = hand.count_ones();
+        if count != 8 {
+            return Err(format!("Hand {} has {} cards, expected 8", p, count));
+        }
+    }
+
+    let mut seen = 0u32;
+    for (p, &hand) in hands.iter().enumerate() {
+        let overlap = seen & hand;
+        if overlap != 0 {
+            return Err(format!(
+                "Card {} is dealt to several players (again in hand {})",
+                overlap.trailing_zeros(),
+                p
+            ));
+        }
+        seen |= hand;
+    }
+
+    if seen != FULL_DECK {
+        return Err(format!(
+            "Card {} is missing from the deal",
+            (!seen).trailing_zeros()
+        ));
+    }
+    Ok(())
+}
+
+/// 128-bit ID of a complete deal: its canonical form, hand `s` in bits `32 s` to
+/// `32 s + 31`. The ID is the deal itself rather than a hash, so two deals never
+/// share one and `deal_from_id` gives the hands back.
+pub fn deal_id(hands: &[u32; 4]) -> Result<u128, String> {
+    validate_deal(hands)?;
+    Ok(hands
+        .iter()
+        .enumerate()
+        .fold(0, |id, (seat, &hand)| id | (hand as u128) << (32 * seat)))
+}
+
+/// IDs of the deals of `flattened_hands` (4 hands per deal, S W N E).
+pub fn deal_ids(flattened_hands: &[u32]) -> Result<Vec<u128>, String> {
+    if !flattened_hands.len().is_multiple_of(4) {
+        return Err(format!(
+            "Expected 4 hands per deal, got {} hands",
+            flattened_hands.len()
+        ));
+    }
+    flattened_hands
+        .chunks(4)
+        .enumerate()
+        .map(|(i, hands)| {
+            deal_id(hands.try_into().unwrap()).map_err(|e| format!("Deal {}: {}", i, e))
+        })
+        .collect()
+}
+
+/// Hands of the deal with ID `id`, see `deal_id`.
+pub fn deal_from_id(id: u128) -> Result<[u32; 4], String> {
+    let hands = [0, 1, 2, 3].map(|seat| (id >> (32 * seat)) as u32);
+    validate_deal(&hands).map_err(|e| format!("Invalid deal ID {:#x}: {}", id, e))?;
+    Ok(hands)
+}
+
+/// Uniformly random deal and its ID; the same `seed` always gives the same deal.
+pub fn deal_random(seed: Option<u64>) -> ([u32; 4], u128) {
+    let hands = generate_random_hands(&mut sample_rng(seed, 0));
+    let id = deal_id(&hands).expect("a shuffled deck makes a complete deal");
+    (hands, id)
+}
+
+/// Checks that remaining hands and already played cards are disjoint and
+/// together cover the whole deck.
+pub fn validate_remaining_cards(hands: &[u32; 4], played_cards: &[u8]) -> Result<(), String> {
+    let mut played = 0u32;
+    for &card in played_cards {
+        if card >= 32 {
+            return Err(format!("Invalid card index {}", card));
+        }
+        if (played & (1 << card)) != 0 {
+            return Err(format!("Card {} is played twice", card));
+        }
+        played |= 1 << card;
+    }
+
+    let mut seen = played;
+    for (p, &hand) in hands.iter().enumerate() {
+        let overlap = seen & hand;
+        if overlap != 0 {
+            return Err(format!(
+                "Card {} in hand {} is already played or held by another player",
+                overlap.trailing_zeros(),
+                p
+            ));
+        }
+        seen |= hand;
+    }
+
+    if seen != FULL_DECK {
+        return Err(format!(
+            "Card {} is neither in a hand nor played",
+            (!seen).trailing_zeros()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted_deal() -> [u32; 4] {
+        [0x0000_00FF, 0x0000_FF00, 0x00FF_0000, 0xFF00_0000]
+    }
+
+    #[test]
+    fn test_validate_deal_ok() {
+        assert!(validate_deal(&sorted_deal()).is_ok());
+    }
+
+    #[test]
+    fn test_validate_deal_wrong_count() {
+        let mut hands = sorted_deal();
+        hands[1] &= !(1 << 8);
+        hands[0] |= 1 << 8;
+        let err = validate_deal(&hands).unwrap_err();
+        assert!(err.contains("Hand 0 has 9 cards"));
+    }
+
+    #[test]
+    fn test_validate_deal_duplicate() {
+        let mut hands = sorted_deal();
+        // Hand 1 swaps one of its own cards for a card of hand 0.
+        hands[1] = (hands[1] & !(1 << 8)) | 1;
+        let err = validate_deal(&hands).unwrap_err();
+        assert!(err.contains("Card 0"));
+    }
+
+    #[test]
+    fn test_deal_ids() {
+        let id = deal_id(&sorted_deal()).unwrap();
+        assert_eq!(id, 0xFF00_0000_00FF_0000_0000_FF00_0000_00FF);
+        assert_eq!(deal_from_id(id), Ok(sorted_deal()));
+        assert!(deal_id(&[0xFF, 0xFF, 0, 0]).is_err());
+        assert!(deal_from_id(id >> 1).is_err());
+
+        let (hands, id) = deal_random(Some(7));
+        assert_eq!(deal_random(Some(7)), (hands, id));
+        assert_eq!(deal_from_id(id), Ok(hands));
+        assert_ne!(deal_random(Some(8)).1, id);
+        // Another seat holding a hand is another deal.
+        let turned = [hands[1], hands[2], hands[3], hands[0]];
+        assert_ne!(deal_id(&turned).unwrap(), id);
+
+        let flattened: Vec<u32> = sorted_deal().into_iter().chain(hands).collect();
+        assert_eq!(
+            deal_ids(&flattened),
+            Ok(vec![deal_id(&sorted_deal()).unwrap(), id])
+        );
+        assert!(deal_ids(&flattened[1..]).is_err());
+        let err = deal_ids(&[flattened[..4].to_vec(), vec![0xFF; 4]].concat()).unwrap_err();
+        assert!(err.starts_with("Deal 1:"), "{}", err);
+    }
+
+    #[test]
+    fn test_validate_remaining_cards() {
+        let mut hands = sorted_deal();
+        hands[0] &= !1;
+        assert!(validate_remaining_cards(&hands, &[0]).is_ok());
+        assert!(validate_remaining_cards(&hands, &[]).is_err());
+        assert!(validate_remaining_cards(&hands, &[0, 0]).is_err());
+        assert!(validate_remaining_cards(&sorted_deal(), &[0]).is_err());
+    }
+}
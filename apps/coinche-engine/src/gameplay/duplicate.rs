@@ -0,0 +1,266 @@
+//! Duplicate scoring: a deal's result compared with a reference result for the
+//! same cards, either the other table's in duplicate play or the par result,
+//! and turned into an IMP-like swing.
+//!
+//! Results are compared on their net score, NS deal score minus EW deal score
+//! as `CoincheGame::score_deal` counts them (no litige pot). The swing follows
+//! the spirit of bridge IMPs: the point difference maps to a coarse scale, so a
+//! single doubled disaster does not outweigh many small edges.
+//!
+//! Par is the double-dummy auction outcome without sacrifices or coinches: the
+//! side making the highest contract declares, picking among its contracts above
+//! the other side's best the one that scores most.
+
+use crate::gameplay::bidding::{Bid, BID_VALUES};
+use crate::gameplay::game::{CoincheGame, DEFAULT_TARGET};
+use crate::gameplay::manager::MatchResult;
+use crate::gameplay::playing::{PlayingState, LAST_TRICK_BONUS, TOTAL_CARD_POINTS};
+use crate::solver::{solve_for_team, Score};
+use pyo3::prelude::*;
+use std::cmp::Ordering;
+
+/// Point differences from which a swing is worth 1, 2, ... IMPs. Beyond the
+/// last step it stays at `IMP_SCALE.len()`.
+pub const IMP_SCALE: [i32; 15] = [
+    10, 30, 50, 80, 110, 150, 200, 260, 330, 410, 500, 650, 850, 1100, 1400,
+];
+
+/// IMPs of a point difference, with its sign.
+pub fn imps(points: i32) -> i32 {
+    let steps = IMP_SCALE.iter().filter(|&&s| points.abs() >= s).count() as i32;
+    steps * points.signum()
+}
+
+/// NS deal score minus EW deal score of `result`.
+pub fn net_score(result: &MatchResult) -> i32 {
+    let game = CoincheGame::new_rs(DEFAULT_TARGET, Some(0), Some(0));
+    let (score, _) = game.score_deal(result);
+    score[0] as i32 - score[1] as i32
+}
+
+/// IMPs NS gained at the table of `result` over the table of `other`, where the
+/// same deal was played. EW gained the opposite.
+pub fn duplicate_swing(result: &MatchResult, other: &MatchResult) -> i32 {
+    imps(net_score(result) - net_score(other))
+}
+
+/// Double-dummy par of a deal.
+#[pyclass]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParResult {
+    /// None when no side makes 80.
+    #[pyo3(get)]
+    pub contract: Option<Bid>,
+    /// Declaring team, 0 = NS, 1 = EW.
+    #[pyo3(get)]
+    pub declarers: Option<u8>,
+    /// Final points of NS and EW with both sides playing perfectly.
+    #[pyo3(get)]
+    pub points: [Score; 2],
+    /// Net score of the par contract, as `net_score`.
+    #[pyo3(get)]
+    pub net_score: i32,
+}
+
+#[pymethods]
+impl ParResult {
+    pub fn __repr__(&self) -> String {
+        format!(
+            "ParResult(contract={:?}, declarers={:?}, net_score={})",
+            self.contract, self.declarers, self.net_score
+        )
+    }
+}
+
+impl ParResult {
+    /// The deal result of the par contract, as a table would report it.
+    fn as_result(&self, belote_team: Option<u8>) -> MatchResult {
+        MatchResult {
+            contract: self.contract,
+            contract_owner: self.declarers,
+            points_ns: self.points[0] as i16,
+            points_ew: self.points[1] as i16,
+            contract_made: self.contract.is_some(),
+            coinche_level: 0,
+            belote_team,
+            timeout_seat: None,
+            revoke_seat: None,
+            revoke_card: None,
+            play_stats: None,
+        }
+    }
+}
+
+/// IMPs NS gained at the table of `result` over the par of its deal.
+pub fn par_swing(result: &MatchResult, par: &ParResult) -> i32 {
+    imps(net_score(result) - par.net_score)
+}
+
+/// Par of `hands` dealt by `dealer`: every contract is solved with the seat on
+/// the dealer's left leading, twice per trump (once for each side), twelve full
+/// solves in all.
+pub fn par_result(hands: &[u32; 4], dealer: u8, tt_log2: Option<u8>) -> ParResult {
+    let leader = (dealer + 1) % 4;
+    let mut points = [[0; 2]; 6];
+    let mut belote = [None; 6];
+    for trump in 0..6u8 {
+        let mut state = PlayingState::new(trump);
+        state.hands = *hands;
+        state.current_player = leader;
+        state.trick_starter = leader;
+        belote[trump as usize] = (0..2u8).find(|&t| state.team_score_bounds(t as usize).0 > 0);
+        points[trump as usize] =
+            [0, 1].map(|team| solve_for_team(&state, team, Some(32), tt_log2).0);
+    }
+    par_from_points(&points, &belote, leader)
+}
+
+/// Par from `points[trump][team]`, the final points of `team` playing to
+/// maximize them in `trump`, `belote[trump]`, the team holding the belote, and
+/// the seat bidding first.
+pub fn par_from_points(
+    points: &[[Score; 2]; 6],
+    belote: &[Option<u8>; 6],
+    first_bidder: u8,
+) -> ParResult {
+    let all_points = (TOTAL_CARD_POINTS + LAST_TRICK_BONUS) as Score;
+    // Every made contract, with the final points of NS and EW.
+    let mut made = Vec::new();
+    for trump in 0..6 {
+        let bonus = |team: usize| {
+            if belote[trump] == Some(team as u8) {
+                20
+            } else {
+                0
+            }
+        };
+        for team in 0..2 {
+            let own = points[trump][team];
+            // A side making 80 wins a trick: the other side has no capot.
+            let other_cards = (all_points - (own - bonus(team))).max(0);
+            let mut both = [0; 2];
+            both[team] = own;
+            both[1 - team] = other_cards + bonus(1 - team);
+            for &value in BID_VALUES.iter().filter(|&&v| v as Score <= own) {
+                made.push((team as u8, Bid::new(value, trump as u8), both));
+            }
+        }
+    }
+
+    let best_value = |team: u8| made.iter().filter(|m| m.0 == team).map(|m| m.1.value).max();
+    let (ns, ew) = (best_value(0), best_value(1));
+    if ns.is_none() && ew.is_none() {
+        return ParResult {
+            contract: None,
+            declarers: None,
+            points: [0, 0],
+            net_score: 0,
+        };
+    }
+    // Level best contracts go to the side bidding first.
+    let first = first_bidder % 2;
+    let team = match ns.cmp(&ew) {
+        Ordering::Greater => 0,
+        Ordering::Less => 1,
+        Ordering::Equal => first,
+    };
+    let other_best = if team == 0 { ew } else { ns }.unwrap_or(0);
+    let sign = if team == 0 { 1 } else { -1 };
+    made.iter()
+        .filter(|m| {
+            m.0 == team && (m.1.value > other_best || team == first && m.1.value == other_best)
+        })
+        .map(|&(team, contract, points)| {
+            let mut par = ParResult {
+                contract: Some(contract),
+                declarers: Some(team),
+                points,
+                net_score: 0,
+            };
+            par.net_score = net_score(&par.as_result(belote[contract.trump as usize]));
+            par
+        })
+        .max_by_key(|p| p.net_score * sign)
+        .expect("the declarers make their best contract")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::playing::{CLUBS, HEARTS, NO_TRUMP, SPADES};
+
+    fn result(contract: (u8, u8), owner: u8, points: (i16, i16), made: bool) -> MatchResult {
+        MatchResult {
+            contract: Some(Bid::new(contract.0, contract.1)),
+            contract_owner: Some(owner),
+            points_ns: points.0,
+            points_ew: points.1,
+            contract_made: made,
+            coinche_level: 0,
+            belote_team: None,
+            timeout_seat: None,
+            revoke_seat: None,
+            revoke_card: None,
+            play_stats: None,
+        }
+    }
+
+    #[test]
+    fn test_duplicate_swing() {
+        assert_eq!(imps(0), 0);
+        assert_eq!(imps(9), 0);
+        assert_eq!(imps(10), 1);
+        assert_eq!(imps(-35), -2);
+        assert_eq!(imps(5000), IMP_SCALE.len() as i32);
+
+        // 80 hearts made by NS: 182 to 60.
+        let made = result((80, HEARTS), 0, (102, 60), true);
+        assert_eq!(net_score(&made), 122);
+        // The other table's NS went down in 100 hearts: EW score 162 + 100.
+        let down = result((100, HEARTS), 2, (70, 92), false);
+        assert_eq!(net_score(&down), -262);
+        assert_eq!(duplicate_swing(&made, &down), imps(384));
+        assert_eq!(duplicate_swing(&down, &made), -imps(384));
+    }
+
+    #[test]
+    fn test_par_result() {
+        // NS make 110 in hearts, with the belote, or 90 in no trump; EW make 100
+        // in spades.
+        let mut points = [[60, 60]; 6];
+        points[HEARTS as usize] = [118, 64];
+        points[SPADES as usize] = [50, 105];
+        points[NO_TRUMP as usize] = [95, 67];
+        let mut belote = [None; 6];
+        belote[HEARTS as usize] = Some(0);
+        let par = par_from_points(&points, &belote, 1);
+        assert_eq!(par.contract, Some(Bid::new(110, HEARTS)));
+        assert_eq!(par.declarers, Some(0));
+        // EW take the other 64 card points.
+        assert_eq!(par.points, [118, 64]);
+        assert_eq!(par.net_score, 118 + 110 - 64);
+        assert_eq!(par_swing(&par.as_result(Some(0)), &par), 0);
+
+        // EW make 110 in spades too: level, the contract goes to West, first to bid.
+        points[SPADES as usize] = [50, 112];
+        let par = par_from_points(&points, &belote, 1);
+        assert_eq!(par.contract, Some(Bid::new(110, SPADES)));
+        assert_eq!(par.declarers, Some(1));
+        assert_eq!(par.points, [50, 112]);
+        assert_eq!(par.net_score, 50 - (112 + 110));
+        // NS bidding first keep the contract.
+        let par = par_from_points(&points, &belote, 2);
+        assert_eq!(par.contract, Some(Bid::new(110, HEARTS)));
+
+        // A capot: the other side has no card points, only its belote.
+        points[CLUBS as usize] = [252, 0];
+        belote[CLUBS as usize] = Some(1);
+        let par = par_from_points(&points, &belote, 1);
+        assert_eq!(par.contract, Some(Bid::new(252, CLUBS)));
+        assert_eq!(par.points, [252, 20]);
+
+        // Nobody makes 80: the deal is passed.
+        let passed = par_from_points(&[[70, 70]; 6], &[None; 6], 0);
+        assert_eq!((passed.contract, passed.net_score), (None, 0));
+    }
+}
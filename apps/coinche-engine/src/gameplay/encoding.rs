@@ -0,0 +1,409 @@
+//! Canonical tensor encodings of cards for the models, with their inverses.
+//!
+//! Cards are indexed `suit * 8 + rank` (see `playing`), so a hand mask maps bit
+//! for bit onto a 32-dim multi-hot vector. The gameplay model input is the
+//! concatenation hand | history | board (multi-hot) | trump (one-hot over the 6
+//! contracts): `GAMEPLAY_FEATURES` floats. The evaluation features describe a
+//! whole position, all hands visible, for fitting the solver's static evaluation:
+//! `EVALUATION_FEATURES` floats. The auction features give play-phase models
+//! the bidding context seen from one seat: `AUCTION_FEATURES` floats.
+//!
+//! Actions have two index layouts. The flat one numbers every action of a deal
+//! in one space: the `AUCTION_ACTIONS` calls (pass, the bids by value then
+//! contract, coinche, surcoinche), then the 32 cards. The dual-head one gives
+//! each phase its own head: head 0 indexes the calls as above, head 1 the cards.
+
+use crate::gameplay::bidding::{AuctionAction, Bid, BID_VALUES};
+use crate::gameplay::bot::BotAction;
+use crate::gameplay::playing::PlayingState;
+
+/// Cards in the deck: the width of card vectors.
+pub const CARDS: usize = 32;
+/// Contracts: 4 suits, no trump, all trump.
+pub const TRUMPS: usize = 6;
+/// Width of `gameplay_features`.
+pub const GAMEPLAY_FEATURES: usize = 3 * CARDS + TRUMPS;
+/// Width of `evaluation_features`.
+pub const EVALUATION_FEATURES: usize = 8 * CARDS + 4 + TRUMPS + 4;
+/// Bids of each seat kept by `auction_features`, the most recent first.
+pub const AUCTION_HISTORY_BIDS: usize = 2;
+/// Width of a bid: its value, then its contract (one-hot each).
+const BID_FEATURES: usize = BID_VALUES.len() + TRUMPS;
+/// Width of `auction_features`.
+pub const AUCTION_FEATURES: usize =
+    4 * AUCTION_HISTORY_BIDS * BID_FEATURES + BID_FEATURES + 4 + 2 + 4;
+
+/// Auction calls: pass, the 60 bids, coinche and surcoinche.
+pub const AUCTION_ACTIONS: usize = 1 + BID_VALUES.len() * TRUMPS + 2;
+/// Width of the flat action space: auction calls, then cards.
+pub const FLAT_ACTIONS: usize = AUCTION_ACTIONS + CARDS;
+/// Heads of the dual-head layout.
+pub const AUCTION_HEAD: u8 = 0;
+pub const CARD_HEAD: u8 = 1;
+
+/// Marker of an empty seat in a trick, as in `PlayingState::current_trick`.
+const NO_CARD: u8 = 0xFF;
+
+pub fn card_index(suit: u8, rank: u8) -> u8 {
+    suit * 8 + rank
+}
+
+/// (suit, rank) of a card index.
+pub fn card_suit_rank(card: u8) -> (u8, u8) {
+    (card / 8, card % 8)
+}
+
+/// Multi-hot vector of a hand mask.
+pub fn encode_hand(hand: u32) -> [f32; CARDS] {
+    std::array::from_fn(|i| ((hand >> i) & 1) as f32)
+}
+
+/// Hand mask of a multi-hot vector: the cards whose entry is above 0.5.
+pub fn decode_hand(vector: &[f32]) -> Result<u32, String> {
+    if vector.len() != CARDS {
+        return Err(format!(
+            "Card vector has {} entries, expected {}",
+            vector.len(),
+            CARDS
+        ));
+    }
+    Ok(vector
+        .iter()
+        .enumerate()
+        .filter(|(_, &v)| v > 0.5)
+        .fold(0, |mask, (i, _)| mask | 1 << i))
+}
+
+pub fn encode_card(card: u8) -> [f32; CARDS] {
+    encode_hand(1 << card)
+}
+
+/// Card of a one-hot vector; an error unless exactly one card is set.
+pub fn decode_card(vector: &[f32]) -> Result<u8, String> {
+    let mask = decode_hand(vector)?;
+    if mask.count_ones() != 1 {
+        return Err(format!(
+            "One-hot card vector has {} cards set",
+            mask.count_ones()
+        ));
+    }
+    Ok(mask.trailing_zeros() as u8)
+}
+
+/// 4x32 matrix of a trick indexed by seat (0xFF = no card yet): row `p` is the
+/// one-hot card of seat `p`, or zeros.
+pub fn encode_trick(trick: &[u8; 4]) -> [[f32; CARDS]; 4] {
+    trick.map(|c| {
+        if (c as usize) < CARDS {
+            encode_card(c)
+        } else {
+            [0.0; CARDS]
+        }
+    })
+}
+
+/// Seat-indexed trick of a 4x32 matrix, 0xFF for empty rows.
+pub fn decode_trick(matrix: &[Vec<f32>]) -> Result<[u8; 4], String> {
+    if matrix.len() != 4 {
+        return Err(format!(
+            "Trick matrix has {} rows, expected 4",
+            matrix.len()
+        ));
+    }
+    let mut trick = [NO_CARD; 4];
+    for (seat, row) in matrix.iter().enumerate() {
+        if decode_hand(row)? != 0 {
+            trick[seat] = decode_card(row).map_err(|e| format!("Seat {}: {}", seat, e))?;
+        }
+    }
+    Ok(trick)
+}
+
+pub fn encode_trump(trump: u8) -> [f32; TRUMPS] {
+    std::array::from_fn(|t| (t == trump as usize) as u8 as f32)
+}
+
+/// Index of an auction call among the `AUCTION_ACTIONS`.
+pub fn auction_action_index(action: AuctionAction) -> Result<usize, String> {
+    match action {
+        AuctionAction::Pass => Ok(0),
+        AuctionAction::Bid(bid) => {
+            let value = BID_VALUES
+                .iter()
+                .position(|&v| v == bid.value)
+                .ok_or_else(|| format!("Invalid bid value {}", bid.value))?;
+            if bid.trump as usize >= TRUMPS {
+                return Err(format!("Invalid bid contract {}", bid.trump));
+            }
+            Ok(1 + value * TRUMPS + bid.trump as usize)
+        }
+        AuctionAction::Coinche => Ok(AUCTION_ACTIONS - 2),
+        AuctionAction::Surcoinche => Ok(AUCTION_ACTIONS - 1),
+    }
+}
+
+/// Auction call of an index among the `AUCTION_ACTIONS`.
+pub fn auction_action(index: usize) -> Result<AuctionAction, String> {
+    match index {
+        0 => Ok(AuctionAction::Pass),
+        i if i < AUCTION_ACTIONS - 2 => {
+            let bid = i - 1;
+            Ok(AuctionAction::Bid(Bid::new(
+                BID_VALUES[bid / TRUMPS],
+                (bid % TRUMPS) as u8,
+            )))
+        }
+        i if i == AUCTION_ACTIONS - 2 => Ok(AuctionAction::Coinche),
+        i if i == AUCTION_ACTIONS - 1 => Ok(AuctionAction::Surcoinche),
+        _ => Err(format!(
+            "Auction action index {} out of range (0..{})",
+            index, AUCTION_ACTIONS
+        )),
+    }
+}
+
+/// (head, index within the head) of an action in the dual-head layout.
+pub fn dual_head_index(action: BotAction) -> Result<(u8, usize), String> {
+    match action {
+        BotAction::Call(call) => Ok((AUCTION_HEAD, auction_action_index(call)?)),
+        BotAction::Play(card) if (card as usize) < CARDS => Ok((CARD_HEAD, card as usize)),
+        BotAction::Play(card) => Err(format!("Invalid card index {}", card)),
+    }
+}
+
+/// Action of an index of `head` in the dual-head layout.
+pub fn dual_head_action(head: u8, index: usize) -> Result<BotAction, String> {
+    match head {
+        AUCTION_HEAD => auction_action(index).map(BotAction::Call),
+        CARD_HEAD if index < CARDS => Ok(BotAction::Play(index as u8)),
+        CARD_HEAD => Err(format!("Card index {} out of range (0..{})", index, CARDS)),
+        _ => Err(format!("Invalid action head {}", head)),
+    }
+}
+
+/// Index of an action in the flat layout.
+pub fn flat_action_index(action: BotAction) -> Result<usize, String> {
+    let (head, index) = dual_head_index(action)?;
+    Ok(if head == CARD_HEAD {
+        AUCTION_ACTIONS + index
+    } else {
+        index
+    })
+}
+
+/// Action of an index in the flat layout.
+pub fn flat_action(index: usize) -> Result<BotAction, String> {
+    if index < AUCTION_ACTIONS {
+        dual_head_action(AUCTION_HEAD, index)
+    } else if index < FLAT_ACTIONS {
+        dual_head_action(CARD_HEAD, index - AUCTION_ACTIONS)
+    } else {
+        Err(format!(
+            "Action index {} out of range (0..{})",
+            index, FLAT_ACTIONS
+        ))
+    }
+}
+
+/// Gameplay model input: the hand, the cards of completed tricks (`history`
+/// mask) and of the current trick (`board`, entries of 32 and above ignored),
+/// then the trump.
+pub fn gameplay_features(hand: u32, history: u32, board: &[u8], trump: u8) -> Vec<f32> {
+    let board = board
+        .iter()
+        .filter(|&&c| (c as usize) < CARDS)
+        .fold(0u32, |m, &c| m | 1 << c);
+    let mut features = Vec::with_capacity(GAMEPLAY_FEATURES);
+    features.extend(encode_hand(hand));
+    features.extend(encode_hand(history));
+    features.extend(encode_hand(board));
+    features.extend(encode_trump(trump));
+    features
+}
+
+/// Value and contract one-hots of a bid.
+fn encode_bid(bid: Bid) -> Result<[f32; BID_FEATURES], String> {
+    let value = BID_VALUES
+        .iter()
+        .position(|&v| v == bid.value)
+        .ok_or_else(|| format!("Invalid bid value {}", bid.value))?;
+    if bid.trump as usize >= TRUMPS {
+        return Err(format!("Invalid bid contract {}", bid.trump));
+    }
+    let mut features = [0.0; BID_FEATURES];
+    features[value] = 1.0;
+    features[BID_VALUES.len() + bid.trump as usize] = 1.0;
+    Ok(features)
+}
+
+/// Auction context of `seat`, seats in order from `seat`: the last
+/// `AUCTION_HISTORY_BIDS` bids of each seat (most recent first, zeros when it
+/// made fewer), the contract, its owner (one-hot, zeros without a contract),
+/// the coinche and surcoinche flags, then `seat`'s position after the dealer
+/// (one-hot, the dealer last). `auction` holds the calls in order from the
+/// seat after `dealer`, as `CoincheMatch.auction`.
+pub fn auction_features(
+    auction: &[AuctionAction],
+    dealer: u8,
+    seat: u8,
+) -> Result<Vec<f32>, String> {
+    if dealer >= 4 || seat >= 4 {
+        return Err(format!("Invalid dealer {} or seat {}", dealer, seat));
+    }
+    // Calls go round the table from the seat after the dealer.
+    let caller = |i: usize| (dealer as usize + 1 + i) % 4;
+    let relative = |s: usize| (s + 4 - seat as usize) % 4;
+
+    let mut bids: [Vec<Bid>; 4] = Default::default();
+    let mut contract = None;
+    let mut coinche_level = 0;
+    for (i, call) in auction.iter().enumerate() {
+        match *call {
+            AuctionAction::Bid(bid) => {
+                bids[relative(caller(i))].push(bid);
+                contract = Some((bid, relative(caller(i))));
+            }
+            AuctionAction::Coinche => coinche_level = coinche_level.max(1),
+            AuctionAction::Surcoinche => coinche_level = 2,
+            AuctionAction::Pass => {}
+        }
+    }
+
+    let mut features = Vec::with_capacity(AUCTION_FEATURES);
+    for seat_bids in &bids {
+        for k in 0..AUCTION_HISTORY_BIDS {
+            match seat_bids.iter().rev().nth(k) {
+                Some(&bid) => features.extend(encode_bid(bid)?),
+                None => features.extend([0.0; BID_FEATURES]),
+            }
+        }
+    }
+    let mut owner = [0.0; 4];
+    match contract {
+        Some((bid, s)) => {
+            features.extend(encode_bid(bid)?);
+            owner[s] = 1.0;
+        }
+        None => features.extend([0.0; BID_FEATURES]),
+    }
+    features.extend(owner);
+    features.push((coinche_level >= 1) as u8 as f32);
+    features.push((coinche_level == 2) as u8 as f32);
+    let position = (seat as usize + 3 - dealer as usize) % 4;
+    features.extend((0..4).map(|p| (p == position) as u8 as f32));
+    Ok(features)
+}
+
+/// Perfect-information input of a position seen from `team`, seats in order
+/// from the first seat of `team`: the four hands, the current trick (one row per
+/// seat), the player to move (one-hot), the trump, then the points (/162) and
+/// tricks (/8) won by `team` and by the other team.
+pub fn evaluation_features(state: &PlayingState, team: usize) -> Vec<f32> {
+    let seats = (0..4).map(|i| (team + i) % 4);
+    let trick = encode_trick(&state.current_trick);
+    let mut features = Vec::with_capacity(EVALUATION_FEATURES);
+    for seat in seats.clone() {
+        features.extend(encode_hand(state.hands[seat]));
+    }
+    for seat in seats.clone() {
+        features.extend(trick[seat]);
+    }
+    features.extend(seats.map(|seat| (seat == state.current_player as usize) as u8 as f32));
+    features.extend(encode_trump(state.trump));
+    for t in [team, 1 - team] {
+        features.push(state.points[t] as f32 / 162.0);
+    }
+    for t in [team, 1 - team] {
+        features.push(state.tricks_won[t] as f32 / 8.0);
+    }
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::playing::{HEARTS, RANK_J};
+
+    #[test]
+    fn test_encodings_roundtrip() {
+        let jack = card_index(HEARTS, RANK_J);
+        assert_eq!(card_suit_rank(jack), (HEARTS, RANK_J));
+        assert_eq!(decode_card(&encode_card(jack)), Ok(jack));
+        assert!(decode_card(&encode_hand(0b11)).is_err());
+
+        let hand = 0x8000_F00F;
+        assert_eq!(decode_hand(&encode_hand(hand)), Ok(hand));
+        assert!(decode_hand(&[1.0; 31]).is_err());
+
+        let trick = [3, NO_CARD, 31, 20];
+        let matrix: Vec<Vec<f32>> = encode_trick(&trick).iter().map(|r| r.to_vec()).collect();
+        assert_eq!(decode_trick(&matrix), Ok(trick));
+
+        let features = gameplay_features(1, 2, &[4, 255], HEARTS);
+        assert_eq!(features.len(), GAMEPLAY_FEATURES);
+        let ones: Vec<usize> = (0..GAMEPLAY_FEATURES)
+            .filter(|&i| features[i] == 1.0)
+            .collect();
+        assert_eq!(ones, vec![0, 33, 68, 96 + HEARTS as usize]);
+    }
+
+    #[test]
+    fn test_auction_features() {
+        // Dealer E: S opens 80 H, W passes, N raises to 90 H, E coinches.
+        let auction = [
+            AuctionAction::Bid(Bid::new(80, HEARTS)),
+            AuctionAction::Pass,
+            AuctionAction::Bid(Bid::new(90, HEARTS)),
+            AuctionAction::Coinche,
+        ];
+        let features = auction_features(&auction, 3, 1).unwrap();
+        assert_eq!(features.len(), AUCTION_FEATURES);
+        let ones: Vec<usize> = (0..AUCTION_FEATURES)
+            .filter(|&i| features[i] == 1.0)
+            .collect();
+        // Seen from W: N is one seat on, S three.
+        let slot = |seat: usize, k: usize| (seat * AUCTION_HISTORY_BIDS + k) * BID_FEATURES;
+        let hearts = BID_VALUES.len() + HEARTS as usize;
+        let contract = 4 * AUCTION_HISTORY_BIDS * BID_FEATURES;
+        let flags = contract + BID_FEATURES + 4;
+        assert_eq!(
+            ones,
+            vec![
+                slot(1, 0) + 1,
+                slot(1, 0) + hearts,
+                slot(3, 0),
+                slot(3, 0) + hearts,
+                contract + 1,
+                contract + hearts,
+                contract + BID_FEATURES + 1,
+                flags,
+                flags + 2 + 1,
+            ]
+        );
+
+        // Before any call only the position is set: the dealer is last.
+        let empty = auction_features(&[], 3, 3).unwrap();
+        assert_eq!(empty.iter().sum::<f32>(), 1.0);
+        assert_eq!(empty[AUCTION_FEATURES - 1], 1.0);
+        let bad = [AuctionAction::Bid(Bid::new(85, HEARTS))];
+        assert!(auction_features(&bad, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_action_layouts_roundtrip() {
+        assert_eq!((AUCTION_ACTIONS, FLAT_ACTIONS), (63, 95));
+        for index in 0..FLAT_ACTIONS {
+            let action = flat_action(index).unwrap();
+            assert_eq!(flat_action_index(action), Ok(index));
+            let (head, i) = dual_head_index(action).unwrap();
+            assert_eq!(dual_head_action(head, i), Ok(action));
+        }
+        let bid = BotAction::Call(AuctionAction::Bid(Bid::new(90, HEARTS)));
+        assert_eq!(flat_action_index(bid), Ok(1 + 6 + HEARTS as usize));
+        assert_eq!(flat_action_index(BotAction::Play(0)), Ok(63));
+        assert_eq!(dual_head_index(BotAction::Play(31)), Ok((CARD_HEAD, 31)));
+        assert!(flat_action(FLAT_ACTIONS).is_err());
+        assert!(dual_head_action(CARD_HEAD, 32).is_err());
+        assert!(flat_action_index(BotAction::Call(AuctionAction::Bid(Bid::new(85, 0)))).is_err());
+    }
+}
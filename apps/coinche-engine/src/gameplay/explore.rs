@@ -0,0 +1,105 @@
+//! What-if exploration for analysis UIs: `CoincheMatch.explore` plays a
+//! hypothetical line on a branch of the match, leaving the match itself
+//! untouched, and evaluates where the line leads.
+
+use crate::gameplay::analysis::{analyze_position, PositionAnalysis};
+use crate::gameplay::manager::{CoincheMatch, MatchResult, Phase};
+use crate::solver::Score;
+use pyo3::prelude::*;
+use std::time::Duration;
+
+#[pyclass]
+#[derive(Debug, Clone)]
+pub struct Exploration {
+    /// The branch after the line, to explore further.
+    #[pyo3(get)]
+    pub position: CoincheMatch,
+    /// Result of the deal, when the line ends it.
+    #[pyo3(get)]
+    pub result: Option<MatchResult>,
+    /// Double-dummy analysis for the declaring team, when the line stops
+    /// during the play.
+    #[pyo3(get)]
+    pub analysis: Option<PositionAnalysis>,
+    /// Final points of the declaring team: scored when the deal is over,
+    /// expected with perfect play otherwise. None during the auction or when
+    /// nobody bid.
+    #[pyo3(get)]
+    pub declarer_points: Option<Score>,
+}
+
+#[pymethods]
+impl Exploration {
+    pub fn __repr__(&self) -> String {
+        format!(
+            "Exploration(phase={}, declarer_points={:?})",
+            self.position.phase.name(),
+            self.declarer_points
+        )
+    }
+}
+
+/// Evaluation of `position`, the branch a line led to. During the play the
+/// position is searched for at most about `time_budget` (see `analyze_position`).
+pub fn evaluate(
+    position: CoincheMatch,
+    time_budget: Option<Duration>,
+    tt_log2: Option<u8>,
+) -> Result<Exploration, String> {
+    let team = position.contract_owner.map(|o| o % 2);
+    let (result, analysis, declarer_points) = match position.phase {
+        Phase::Bidding(_) => (None, None, None),
+        Phase::Playing(ref state) => {
+            let team = team.expect("the play has a declarer");
+            let analysis = analyze_position(state, team as usize, time_budget, tt_log2)?;
+            let points = analysis.score;
+            (None, Some(analysis), Some(points))
+        }
+        Phase::Finished(ref r) => {
+            let points = team.map(|t| if t == 0 { r.points_ns } else { r.points_ew } as Score);
+            (Some(r.clone()), None, points)
+        }
+    };
+    Ok(Exploration {
+        position,
+        result,
+        analysis,
+        declarer_points,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::bidding::AuctionAction;
+    use crate::gameplay::bidding::Bid;
+    use crate::gameplay::bot::BotAction;
+    use crate::gameplay::playing::SPADES;
+
+    fn sorted_deal() -> [u32; 4] {
+        [0xFF, 0xFF00, 0xFF_0000, 0xFF00_0000]
+    }
+
+    #[test]
+    fn test_evaluate_each_phase() {
+        let mut m = CoincheMatch::new_rs(3, sorted_deal());
+        m.bid(Some(Bid::new(80, SPADES))).unwrap();
+        let auction = evaluate(m.clone(), None, None).unwrap();
+        assert!(auction.result.is_none() && auction.declarer_points.is_none());
+
+        for _ in 0..3 {
+            m.apply_action(BotAction::Call(AuctionAction::Pass))
+                .unwrap();
+        }
+        // A spent budget still searches the first trick.
+        let play = evaluate(m.clone(), Some(Duration::ZERO), None).unwrap();
+        let analysis = play.analysis.unwrap();
+        assert_eq!((analysis.depth, analysis.complete), (4, false));
+        assert_eq!(play.declarer_points, Some(analysis.score));
+
+        m.forfeit(1);
+        let over = evaluate(m, None, None).unwrap();
+        assert_eq!(over.declarer_points, Some(162));
+        assert!(over.analysis.is_none());
+    }
+}
@@ -0,0 +1,397 @@
+//! Multi-deal games: deals are scored and added up until a team reaches the
+//! target. The rules that span deals live here:
+//! - the first dealer is chosen by cutting: every seat draws a card and the
+//!   lowest deals (ties draw again), then the deal passes to the next seat after
+//!   every deal, passed ones included;
+//! - litige: when the declarers of a plain contract end level with the
+//!   defenders, the defenders score now and the declarers' score is set aside,
+//!   to be won by the team that wins the next contract;
+//! - on a failed contract the defenders score all the points plus the contract,
+//!   or, when `defenders_keep_points` is set, only the points they won plus the
+//!   contract.
+//!
+//! A deal's score: a made contract gives the declarers their points plus its
+//! value and the defenders their points; a coinched one doubles (surcoinched:
+//! quadruples) the value and the winners take all the points. The belote
+//! always stays with the team that announced it.
+
+use crate::data_gen::common::generate_random_hands;
+use crate::gameplay::manager::{CoincheMatch, MatchResult};
+use crate::gameplay::playing::{LAST_TRICK_BONUS, RANK_STRENGTH_NON_TRUMP, TOTAL_CARD_POINTS};
+use crate::gameplay::rules::RuleSet;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use rand::prelude::*;
+use rand::rngs::StdRng;
+
+/// Usual target of a game.
+pub const DEFAULT_TARGET: u32 = 1000;
+const BELOTE_BONUS: u32 = 20;
+
+/// Seat dealing first: every seat draws a card from a shuffled deck and the
+/// lowest (7 up to ace, as in a side suit) deals; tied seats draw again.
+pub fn cut_for_dealer<R: Rng>(rng: &mut R) -> u8 {
+    let mut seats: Vec<u8> = (0..4).collect();
+    loop {
+        let mut deck: Vec<u8> = (0..32).collect();
+        deck.shuffle(rng);
+        let strength = |i: usize| RANK_STRENGTH_NON_TRUMP[(deck[i] % 8) as usize];
+        let lowest = (0..seats.len()).map(strength).min().unwrap();
+        seats = (0..seats.len())
+            .filter(|&i| strength(i) == lowest)
+            .map(|i| seats[i])
+            .collect();
+        if seats.len() == 1 {
+            return seats[0];
+        }
+    }
+}
+
+/// Points a deal added to each team.
+#[pyclass]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DealScore {
+    /// Points of NS and EW, a litige pot won included.
+    #[pyo3(get)]
+    pub points: [u32; 2],
+    /// Whether the deal was a litige, its declarers' score set aside.
+    #[pyo3(get)]
+    pub litige: bool,
+    /// Litige points this deal won, included in `points`.
+    #[pyo3(get)]
+    pub pot_won: u32,
+}
+
+#[pymethods]
+impl DealScore {
+    pub fn __repr__(&self) -> String {
+        format!(
+            "DealScore(points={:?}, litige={}, pot_won={})",
+            self.points, self.litige, self.pot_won
+        )
+    }
+}
+
+#[pyclass]
+#[derive(Clone, Debug)]
+pub struct CoincheGame {
+    /// Score a team must reach to win.
+    #[pyo3(get)]
+    pub target: u32,
+    /// Whether level plain contracts are litiges.
+    #[pyo3(get)]
+    pub litige: bool,
+    /// Whether the defenders of a failed contract only score the points they won.
+    #[pyo3(get)]
+    pub defenders_keep_points: bool,
+    /// Scoring conventions of every deal.
+    #[pyo3(get)]
+    pub rules: RuleSet,
+    /// Totals of NS and EW.
+    #[pyo3(get)]
+    pub scores: [u32; 2],
+    /// Seat dealing the current deal.
+    #[pyo3(get)]
+    pub dealer: u8,
+    /// Seat that won the cut.
+    #[pyo3(get)]
+    pub first_dealer: u8,
+    /// Litige points waiting for the next contract's winners.
+    #[pyo3(get)]
+    pub pot: u32,
+    /// Score of every deal recorded so far.
+    #[pyo3(get)]
+    pub history: Vec<DealScore>,
+    rng: StdRng,
+}
+
+impl CoincheGame {
+    /// A game whose first dealer is `first_dealer`, or cut for when None.
+    pub fn new_rs(target: u32, first_dealer: Option<u8>, seed: Option<u64>) -> Self {
+        let mut rng = match seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let first_dealer = first_dealer.unwrap_or_else(|| cut_for_dealer(&mut rng));
+        CoincheGame {
+            target,
+            litige: true,
+            defenders_keep_points: false,
+            rules: RuleSet::default(),
+            scores: [0; 2],
+            dealer: first_dealer,
+            first_dealer,
+            pot: 0,
+            history: Vec::new(),
+            rng,
+        }
+    }
+
+    /// Points each team scores for `result`, and whether it is a litige.
+    pub fn score_deal(&self, result: &MatchResult) -> ([u32; 2], bool) {
+        let (contract, owner) = match (result.contract, result.contract_owner) {
+            (Some(c), Some(o)) => (c, (o % 2) as usize),
+            _ => return ([0, 0], false),
+        };
+        let defenders = 1 - owner;
+        let mut points = [
+            result.points_ns.max(0) as u32,
+            result.points_ew.max(0) as u32,
+        ];
+        let mut belote = [0u32; 2];
+        if let Some(team) = result.belote_team {
+            belote[team as usize] = BELOTE_BONUS;
+            points[team as usize] = points[team as usize].saturating_sub(BELOTE_BONUS);
+        }
+        let all_points = (TOTAL_CARD_POINTS + LAST_TRICK_BONUS) as u32;
+        let value = contract.value as u32 * (1 << result.coinche_level.min(2));
+
+        let litige = self.litige
+            && result.coinche_level == 0
+            && result.contract_made
+            && result.points_ns == result.points_ew;
+        let mut score = [0u32; 2];
+        if result.contract_made && result.coinche_level == 0 {
+            score[owner] = points[owner] + value;
+            score[defenders] = points[defenders];
+        } else if result.contract_made {
+            score[owner] = all_points.max(points[owner]) + value;
+        } else if self.defenders_keep_points {
+            score[defenders] = points[defenders] + value;
+        } else {
+            score[defenders] = all_points.max(points[defenders]) + value;
+        }
+        score[0] += belote[0];
+        score[1] += belote[1];
+        (score.map(|s| self.rules.round_score(s)), litige)
+    }
+
+    /// Adds a finished deal to the totals and passes the deal to the next seat.
+    pub fn record_result(&mut self, result: &MatchResult) -> DealScore {
+        let (mut points, litige) = self.score_deal(result);
+        let mut pot_won = 0;
+        if let (Some(owner), false) = (result.contract_owner, litige) {
+            let winners = if result.contract_made {
+                owner % 2
+            } else {
+                1 - owner % 2
+            };
+            pot_won = std::mem::take(&mut self.pot);
+            points[winners as usize] += pot_won;
+        }
+        if litige {
+            let owner = (result.contract_owner.unwrap() % 2) as usize;
+            self.pot += std::mem::take(&mut points[owner]);
+        }
+
+        self.scores[0] += points[0];
+        self.scores[1] += points[1];
+        self.dealer = (self.dealer + 1) % 4;
+        let score = DealScore {
+            points,
+            litige,
+            pot_won,
+        };
+        self.history.push(score.clone());
+        score
+    }
+
+    /// Team (0 = NS, 1 = EW) that won: at least the target and more than the other.
+    pub fn winner(&self) -> Option<u8> {
+        let [ns, ew] = self.scores;
+        if ns >= self.target && ns > ew {
+            Some(0)
+        } else if ew >= self.target && ew > ns {
+            Some(1)
+        } else {
+            None
+        }
+    }
+}
+
+#[pymethods]
+impl CoincheGame {
+    #[new]
+    #[pyo3(signature = (target=DEFAULT_TARGET, litige=true, defenders_keep_points=false, rules=None, first_dealer=None, seed=None))]
+    pub fn new(
+        target: u32,
+        litige: bool,
+        defenders_keep_points: bool,
+        rules: Option<RuleSet>,
+        first_dealer: Option<u8>,
+        seed: Option<u64>,
+    ) -> PyResult<Self> {
+        if first_dealer.is_some_and(|d| d >= 4) {
+            return Err(PyValueError::new_err(format!(
+                "Invalid dealer {}",
+                first_dealer.unwrap()
+            )));
+        }
+        let mut game = CoincheGame::new_rs(target, first_dealer, seed);
+        game.litige = litige;
+        game.defenders_keep_points = defenders_keep_points;
+        game.rules = rules.unwrap_or_default();
+        Ok(game)
+    }
+
+    /// A freshly shuffled deal for the current dealer, under the game's rules.
+    pub fn new_deal(&mut self) -> PyResult<CoincheMatch> {
+        if self.winner().is_some() {
+            return Err(PyValueError::new_err("The game is over"));
+        }
+        let mut m = CoincheMatch::new_rs(self.dealer, generate_random_hands(&mut self.rng));
+        m.rules = self.rules;
+        Ok(m)
+    }
+
+    /// Scores a finished deal (see `new_deal`) and moves the deal on.
+    pub fn record(&mut self, result: &MatchResult) -> PyResult<DealScore> {
+        if self.winner().is_some() {
+            return Err(PyValueError::new_err("The game is over"));
+        }
+        Ok(self.record_result(result))
+    }
+
+    #[getter(winner)]
+    fn py_winner(&self) -> Option<u8> {
+        self.winner()
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.winner().is_some()
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "CoincheGame(scores={:?}, dealer={}, pot={}, deals={})",
+            self.scores,
+            self.dealer,
+            self.pot,
+            self.history.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::bidding::Bid;
+    use crate::gameplay::playing::HEARTS;
+
+    fn result(owner: u8, value: u8, points: (i16, i16), made: bool) -> MatchResult {
+        MatchResult {
+            contract: Some(Bid::new(value, HEARTS)),
+            contract_owner: Some(owner),
+            points_ns: points.0,
+            points_ew: points.1,
+            contract_made: made,
+            coinche_level: 0,
+            belote_team: None,
+            timeout_seat: None,
+            revoke_seat: None,
+            revoke_card: None,
+            play_stats: None,
+        }
+    }
+
+    #[test]
+    fn test_deal_scores() {
+        let game = CoincheGame::new_rs(DEFAULT_TARGET, Some(0), Some(1));
+        // Made: points plus value, the defenders keep theirs.
+        assert_eq!(
+            game.score_deal(&result(0, 80, (102, 60), true)).0,
+            [182, 60]
+        );
+        // Failed: the defenders take everything plus the value.
+        assert_eq!(
+            game.score_deal(&result(1, 100, (90, 72), false)).0,
+            [262, 0]
+        );
+        // Belote stays with its team, even on a failed contract.
+        let with_belote = MatchResult {
+            belote_team: Some(1),
+            ..result(1, 100, (90, 92), false)
+        };
+        assert_eq!(game.score_deal(&with_belote).0, [262, 20]);
+        // Coinched and surcoinched values.
+        let coinched = MatchResult {
+            coinche_level: 1,
+            ..result(0, 90, (100, 62), true)
+        };
+        assert_eq!(game.score_deal(&coinched).0, [162 + 180, 0]);
+        let surcoinched = MatchResult {
+            coinche_level: 2,
+            ..result(0, 90, (70, 92), false)
+        };
+        assert_eq!(game.score_deal(&surcoinched).0, [0, 162 + 360]);
+
+        let keep = CoincheGame {
+            defenders_keep_points: true,
+            ..game.clone()
+        };
+        assert_eq!(
+            keep.score_deal(&result(1, 100, (90, 72), false)).0,
+            [190, 0]
+        );
+        assert_eq!(
+            game.score_deal(&result(0, 80, (81, 81), true)),
+            ([161, 81], true)
+        );
+        let no_litige = CoincheGame {
+            litige: false,
+            ..game.clone()
+        };
+        assert_eq!(
+            no_litige.score_deal(&result(0, 80, (81, 81), true)),
+            ([161, 81], false)
+        );
+        let cafe = CoincheGame {
+            rules: RuleSet::from_preset("classic-cafe").unwrap(),
+            ..game.clone()
+        };
+        assert_eq!(
+            cafe.score_deal(&result(0, 80, (102, 60), true)).0,
+            [180, 60]
+        );
+        assert_eq!(cafe.score_deal(&result(0, 80, (85, 77), true)).0, [170, 80]);
+    }
+
+    #[test]
+    fn test_game_flow() {
+        let mut game = CoincheGame::new_rs(300, Some(3), Some(1));
+        // A litige: EW score 81 now, NS's 161 wait for the next contract.
+        let score = game.record_result(&result(0, 80, (81, 81), true));
+        assert_eq!((score.points, score.litige), ([0, 81], true));
+        assert_eq!((game.pot, game.dealer), (161, 0));
+        // A passed deal leaves the pot alone.
+        let passed = MatchResult {
+            contract: None,
+            contract_owner: None,
+            ..result(0, 80, (0, 0), false)
+        };
+        assert_eq!(game.record_result(&passed).points, [0, 0]);
+        assert_eq!((game.pot, game.dealer), (161, 1));
+        // EW set NS's contract: they win the pot too.
+        let score = game.record_result(&result(2, 90, (70, 92), false));
+        assert_eq!((score.points, score.pot_won), ([0, 162 + 90 + 161], 161));
+        assert_eq!(game.pot, 0);
+        assert_eq!(game.scores, [0, 81 + 252 + 161]);
+        assert_eq!(game.winner(), Some(1));
+        assert_eq!(game.history.len(), 3);
+        assert_eq!(game.dealer, 2);
+    }
+
+    #[test]
+    fn test_cut_for_dealer() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut counts = [0; 4];
+        for _ in 0..400 {
+            counts[cut_for_dealer(&mut rng) as usize] += 1;
+        }
+        assert!(counts.iter().all(|&n| n > 60), "{:?}", counts);
+        let a = CoincheGame::new_rs(DEFAULT_TARGET, None, Some(9));
+        let b = CoincheGame::new_rs(DEFAULT_TARGET, None, Some(9));
+        assert_eq!(a.first_dealer, b.first_dealer);
+        assert_eq!(a.dealer, a.first_dealer);
+    }
+}
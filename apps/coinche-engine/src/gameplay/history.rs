@@ -0,0 +1,192 @@
+//! Ordered play history: which seat played which card in which trick.
+//!
+//! A play is packed into a `u16` as `trick << 7 | seat << 5 | card`
+//! (3 bits trick, 2 bits seat, 5 bits card), so a full deal fits in 32 values.
+
+use crate::gameplay::playing::PlayingState;
+use pyo3::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlayRecord {
+    /// Trick index (0-7).
+    pub trick: u8,
+    /// Seat that played the card (0-3).
+    pub seat: u8,
+    /// Card index (0-31).
+    pub card: u8,
+}
+
+impl PlayRecord {
+    /// Record of a play, or an error when a field is out of range (it would not
+    /// fit its bits in `encode`).
+    pub fn checked(trick: u8, seat: u8, card: u8) -> Result<Self, String> {
+        if trick > 7 || seat > 3 || card > 31 {
+            return Err(format!(
+                "Invalid play (trick {}, seat {}, card {}): expected trick 0-7, seat 0-3, card 0-31",
+                trick, seat, card
+            ));
+        }
+        Ok(PlayRecord { trick, seat, card })
+    }
+
+    pub fn encode(&self) -> u16 {
+        ((self.trick as u16 & 0x7) << 7)
+            | ((self.seat as u16 & 0x3) << 5)
+            | (self.card as u16 & 0x1F)
+    }
+
+    pub fn decode(value: u16) -> Self {
+        PlayRecord {
+            trick: ((value >> 7) & 0x7) as u8,
+            seat: ((value >> 5) & 0x3) as u8,
+            card: (value & 0x1F) as u8,
+        }
+    }
+}
+
+pub fn encode_history(plays: &[PlayRecord]) -> Vec<u16> {
+    plays.iter().map(|p| p.encode()).collect()
+}
+
+pub fn decode_history(encoded: &[u16]) -> Vec<PlayRecord> {
+    encoded.iter().map(|&v| PlayRecord::decode(v)).collect()
+}
+
+/// A finished trick of a deal.
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompletedTrick {
+    /// Seat that led the trick.
+    #[pyo3(get)]
+    pub leader: u8,
+    /// Card of each seat, seat-indexed like `PlayingState::current_trick`.
+    #[pyo3(get)]
+    pub cards: [u8; 4],
+    #[pyo3(get)]
+    pub winner: u8,
+}
+
+#[pymethods]
+impl CompletedTrick {
+    /// Cards in play order from the leader.
+    pub fn cards_in_order(&self) -> [u8; 4] {
+        std::array::from_fn(|i| self.cards[(self.leader as usize + i) % 4])
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "CompletedTrick(leader={}, cards={:?}, winner={})",
+            self.leader,
+            self.cards_in_order(),
+            self.winner
+        )
+    }
+}
+
+/// Finished tricks of an ordered card sequence whose first card `leader` played;
+/// the cards of an unfinished last trick are left out.
+pub fn completed_tricks(
+    leader: u8,
+    trump: u8,
+    played_cards: &[u8],
+) -> impl Iterator<Item = CompletedTrick> + '_ {
+    let mut state = PlayingState::new(trump);
+    state.current_player = leader;
+    state.trick_starter = leader;
+    played_cards.chunks_exact(4).map(move |trick| {
+        for &card in trick {
+            state.hands[state.current_player as usize] |= 1 << card;
+            state.play_card(card);
+        }
+        CompletedTrick {
+            leader: state.last_trick_starter,
+            cards: state.last_trick,
+            winner: state.last_trick_winner.expect("a full trick has a winner"),
+        }
+    })
+}
+
+/// Collapses an ordered history into the flat played-cards mask used by the datasets.
+pub fn history_mask(plays: &[PlayRecord]) -> u32 {
+    plays.iter().fold(0, |mask, p| mask | (1 << p.card))
+}
+
+/// Replays the trick mechanics to find which seat played each card of an ordered
+/// card sequence, starting with `leader`. Only trick winners matter here, so the
+/// hands are filled in on the fly and no legality check is made.
+pub fn attribute_played_cards(leader: u8, trump: u8, played_cards: &[u8]) -> Vec<PlayRecord> {
+    let mut state = PlayingState::new(trump);
+    state.current_player = leader;
+    state.trick_starter = leader;
+
+    let mut plays = Vec::with_capacity(played_cards.len());
+    for (i, &card) in played_cards.iter().enumerate() {
+        let seat = state.current_player;
+        state.hands[seat as usize] |= 1 << card;
+        state.play_card(card);
+        plays.push(PlayRecord {
+            trick: (i / 4) as u8,
+            seat,
+            card,
+        });
+    }
+    plays
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::playing::{DIAMONDS, HEARTS, SPADES};
+
+    fn card(suit: u8, rank: u8) -> u8 {
+        suit * 8 + rank
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        for trick in 0..8 {
+            for seat in 0..4 {
+                for card in 0..32 {
+                    let p = PlayRecord { trick, seat, card };
+                    assert_eq!(PlayRecord::decode(p.encode()), p);
+                    assert_eq!(PlayRecord::checked(trick, seat, card), Ok(p));
+                }
+            }
+        }
+        assert!(PlayRecord::checked(8, 0, 0).is_err());
+        assert!(PlayRecord::checked(0, 4, 0).is_err());
+        assert!(PlayRecord::checked(0, 0, 32).is_err());
+    }
+
+    #[test]
+    fn test_attribution_follows_trick_winner() {
+        // Spades trump. P1 leads A of Diamonds, P2 trumps, so P2 leads trick 2.
+        let played = [
+            card(DIAMONDS, 7),
+            card(SPADES, 0),
+            card(DIAMONDS, 0),
+            card(DIAMONDS, 1),
+            card(HEARTS, 7),
+        ];
+        let plays = attribute_played_cards(1, SPADES, &played);
+        let seats: Vec<u8> = plays.iter().map(|p| p.seat).collect();
+        assert_eq!(seats, vec![1, 2, 3, 0, 2]);
+        assert_eq!(plays[4].trick, 1);
+
+        let tricks: Vec<CompletedTrick> = completed_tricks(1, SPADES, &played).collect();
+        assert_eq!(tricks.len(), 1);
+        assert_eq!((tricks[0].leader, tricks[0].winner), (1, 2));
+        assert_eq!(
+            tricks[0].cards_in_order(),
+            [played[0], played[1], played[2], played[3]]
+        );
+        assert_eq!(tricks[0].cards[0], card(DIAMONDS, 1));
+
+        let encoded = encode_history(&plays);
+        assert_eq!(decode_history(&encoded), plays);
+        assert_eq!(
+            history_mask(&plays),
+            played.iter().fold(0u32, |m, &c| m | (1 << c))
+        );
+    }
+}
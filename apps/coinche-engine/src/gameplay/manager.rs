@@ -1,6 +1,22 @@
-use crate::gameplay::bidding::{Bid, BiddingState};
+use crate::gameplay::action_log::{log_to_json, LogEvent, LoggedAction};
+use crate::gameplay::bidding::{AuctionAction, Bid, BiddingState};
+use crate::gameplay::bot::{bot_action, BotAction, BotStrength};
+use crate::gameplay::clock::{MatchClock, TimeControl};
+use crate::gameplay::deal::{validate_deal, validate_remaining_cards};
+use crate::gameplay::encoding;
+use crate::gameplay::explore::{self, Exploration};
+use crate::gameplay::history::{
+    attribute_played_cards, completed_tricks, CompletedTrick, PlayRecord,
+};
+use crate::gameplay::match_snapshot;
+use crate::gameplay::play_stats::PlayStats;
 use crate::gameplay::playing::PlayingState;
+use crate::gameplay::rules::RuleSet;
+use crate::gameplay::snapshot::state_error;
+use crate::gameplay::threshold_bidder::ThresholdBidder;
+use crate::gameplay::transcript;
 use pyo3::prelude::*;
+use pyo3::types::PyBytes;
 
 #[derive(Debug, Clone)]
 pub enum Phase {
@@ -9,6 +25,24 @@ pub enum Phase {
     Finished(MatchResult),
 }
 
+/// Members of the Python `Phase` enum, in `Phase::index` order.
+pub const PHASE_MEMBERS: [(&str, u8); 3] = [("BIDDING", 0), ("PLAYING", 1), ("FINISHED", 2)];
+
+impl Phase {
+    /// Value of the phase in the Python `Phase` enum.
+    pub fn index(&self) -> u8 {
+        match self {
+            Phase::Bidding(_) => 0,
+            Phase::Playing(_) => 1,
+            Phase::Finished(_) => 2,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        PHASE_MEMBERS[self.index() as usize].0
+    }
+}
+
 #[pyclass]
 #[derive(Debug, Clone)]
 pub struct MatchResult {
@@ -22,9 +56,31 @@ pub struct MatchResult {
     pub points_ew: i16,
     #[pyo3(get)]
     pub contract_made: bool,
+    /// 0 = plain contract, 1 = coinched, 2 = surcoinched.
+    #[pyo3(get)]
+    pub coinche_level: u8,
+    /// Team (0 = NS, 1 = EW) that announced the belote, if any.
+    #[pyo3(get)]
+    pub belote_team: Option<u8>,
+    /// Seat that ran out of time, if the match ended on a timeout forfeit.
+    #[pyo3(get)]
+    pub timeout_seat: Option<u8>,
+    /// Seat that revoked and the card it played, if the match ended on a revoke.
+    #[pyo3(get)]
+    pub revoke_seat: Option<u8>,
+    #[pyo3(get)]
+    pub revoke_card: Option<u8>,
+    /// Per-seat statistics of the cards played (None when nobody bid).
+    #[pyo3(get)]
+    pub play_stats: Option<PlayStats>,
+}
+
+fn belote_team(state: &PlayingState) -> Option<u8> {
+    state.belote_scored.iter().position(|&b| b).map(|t| t as u8)
 }
 
 #[pyclass]
+#[derive(Debug, Clone)]
 pub struct CoincheMatch {
     pub phase: Phase,
     #[pyo3(get)]
@@ -39,7 +95,29 @@ pub struct CoincheMatch {
 
     // Internal storage for initial hands (optional, or we can rely on phase state)
     // We need to keep it for Bidding phase where state is inside enum.
+    #[pyo3(get)]
     pub initial_hands: [u32; 4],
+
+    /// Time banks, when the match is played under a time control.
+    pub clock: Option<MatchClock>,
+
+    /// Illegal plays of a held card end the deal as a revoke instead of erroring.
+    #[pyo3(get)]
+    pub revoke_penalty: bool,
+
+    /// Scoring conventions the cards are played under.
+    #[pyo3(get)]
+    pub rules: RuleSet,
+
+    /// Every auction call and card played so far, in order.
+    #[pyo3(get)]
+    pub auction: Vec<AuctionAction>,
+    #[pyo3(get)]
+    pub played_cards: Vec<u8>,
+
+    /// Both of the above and the forfeit ending the deal, if any, with who
+    /// acted and when.
+    pub log: Vec<LoggedAction>,
 }
 
 impl CoincheMatch {
@@ -51,29 +129,448 @@ impl CoincheMatch {
             contract: None,
             contract_owner: None,
             coinche_level: 0,
+            clock: None,
+            revoke_penalty: false,
+            rules: RuleSet::default(),
+            auction: Vec::new(),
+            played_cards: Vec::new(),
+            log: Vec::new(),
+        }
+    }
+
+    /// Seat expected to act next, if the match is not over.
+    pub fn current_seat(&self) -> Option<u8> {
+        match self.phase {
+            Phase::Bidding(ref s) => Some(s.current_player),
+            Phase::Playing(ref s) => Some(s.current_player),
+            Phase::Finished(_) => None,
         }
     }
+
+    /// Charges `elapsed_ms` of thinking time to the seat to move. When its time
+    /// runs out the match ends as a forfeit and an error is returned.
+    pub fn charge_clock(&mut self, elapsed_ms: u64) -> Result<(), String> {
+        let (Some(seat), Some(clock)) = (self.current_seat(), self.clock.as_mut()) else {
+            return Ok(());
+        };
+        let in_time = clock.charge(seat, elapsed_ms);
+        clock.restart();
+        if in_time {
+            Ok(())
+        } else {
+            self.forfeit(seat);
+            Err(format!("Player {} ran out of time", seat))
+        }
+    }
+
+    /// Ends the match with `seat`'s team forfeiting: it scores 0 and the opponents
+    /// take all 162 points. A contract counts as made only if the opponents own it.
+    pub fn forfeit(&mut self, seat: u8) {
+        self.log.push(LoggedAction::now(seat, LogEvent::Timeout));
+        self.phase = Phase::Finished(MatchResult {
+            timeout_seat: Some(seat),
+            ..self.forfeit_result(seat, 0)
+        });
+    }
+
+    /// Penalises a revoke: `seat` played `card`, which it holds but may not play.
+    /// The deal ends as a forfeit, the opponents also taking every belote
+    /// announced so far by either side.
+    pub fn revoke(&mut self, seat: u8, card: u8) {
+        let announced = match self.phase {
+            Phase::Playing(ref s) => 20 * s.belote_scored.iter().filter(|&&b| b).count() as i16,
+            _ => 0,
+        };
+        self.log
+            .push(LoggedAction::now(seat, LogEvent::Revoke(card)));
+        self.phase = Phase::Finished(MatchResult {
+            revoke_seat: Some(seat),
+            revoke_card: Some(card),
+            ..self.forfeit_result(seat, announced)
+        });
+    }
+
+    fn forfeit_result(&self, seat: u8, bonus: i16) -> MatchResult {
+        let winner_team = (seat + 1) % 2;
+        let points = 162 + bonus;
+        let (points_ns, points_ew) = if winner_team == 0 {
+            (points, 0)
+        } else {
+            (0, points)
+        };
+        MatchResult {
+            contract: self.contract,
+            contract_owner: self.contract_owner,
+            points_ns,
+            points_ew,
+            contract_made: self.contract_owner.is_some_and(|o| o % 2 == winner_team),
+            coinche_level: self.coinche_level,
+            belote_team: match self.phase {
+                Phase::Playing(ref s) => belote_team(s),
+                _ => None,
+            },
+            timeout_seat: None,
+            revoke_seat: None,
+            revoke_card: None,
+            play_stats: self.play_stats(),
+        }
+    }
+
+    /// Times the pending action of the current seat on the wall clock.
+    fn charge_wall_clock(&mut self) -> PyResult<Option<u8>> {
+        let elapsed = match self.clock {
+            Some(ref c) => c.elapsed_ms(),
+            None => return Ok(None),
+        };
+        let seat = self.current_seat();
+        self.charge_clock(elapsed)
+            .map_err(pyo3::exceptions::PyRuntimeError::new_err)?;
+        Ok(seat)
+    }
+
+    /// Python error carrying the card-play position, if the deal is being played.
+    fn error(&self, message: impl std::fmt::Display) -> PyErr {
+        match self.phase {
+            Phase::Playing(ref state) => state_error(state, message),
+            _ => pyo3::exceptions::PyValueError::new_err(message.to_string()),
+        }
+    }
+
+    fn record_call(&mut self, seat: u8, call: AuctionAction) {
+        self.auction.push(call);
+        self.log.push(LoggedAction::now(seat, LogEvent::Call(call)));
+    }
+
+    fn credit_increment(&mut self, seat: Option<u8>) {
+        if let (Some(seat), Some(clock)) = (seat, self.clock.as_mut()) {
+            clock.credit_increment(seat);
+            clock.restart();
+        }
+    }
+
+    /// Builds a match at an arbitrary position: `hands` are the cards still held,
+    /// `auction` the calls made so far and `played_cards` every card played, in order.
+    /// The initial deal is reconstructed from the play sequence and the whole history
+    /// is replayed, so impossible positions are rejected instead of silently built.
+    pub fn from_position(
+        dealer: u8,
+        hands: [u32; 4],
+        auction: &[AuctionAction],
+        played_cards: &[u8],
+    ) -> Result<Self, String> {
+        Self::from_position_with_rules(dealer, hands, auction, played_cards, RuleSet::default())
+    }
+
+    /// `from_position` with the cards played under `rules`.
+    pub fn from_position_with_rules(
+        dealer: u8,
+        hands: [u32; 4],
+        auction: &[AuctionAction],
+        played_cards: &[u8],
+        rules: RuleSet,
+    ) -> Result<Self, String> {
+        if dealer >= 4 {
+            return Err(format!("Invalid dealer {}", dealer));
+        }
+        validate_remaining_cards(&hands, played_cards)?;
+
+        let mut bidding = BiddingState::new(dealer);
+        let mut log = Vec::new();
+        for (i, action) in auction.iter().enumerate() {
+            if bidding.is_finished() {
+                return Err(format!("Auction continues after it ended (call #{})", i));
+            }
+            if *action == AuctionAction::Surcoinche && !rules.surcoinche {
+                return Err(format!(
+                    "Surcoinche is not played under these rules (call #{})",
+                    i
+                ));
+            }
+            log.push(LoggedAction {
+                seat: bidding.current_player,
+                event: LogEvent::Call(*action),
+                timestamp_ms: None,
+            });
+            action
+                .apply(&mut bidding)
+                .map_err(|e| format!("Illegal auction call #{} ({:?}): {}", i, action, e))?;
+        }
+
+        let initial_hands = if played_cards.is_empty() {
+            hands
+        } else {
+            let contract = match (bidding.is_finished(), bidding.contract) {
+                (true, Some(c)) => c,
+                _ => return Err("Cards were played but the auction is not complete".to_string()),
+            };
+            let mut initial = hands;
+            for play in attribute_played_cards((dealer + 1) % 4, contract.trump, played_cards) {
+                initial[play.seat as usize] |= 1 << play.card;
+            }
+            initial
+        };
+        validate_deal(&initial_hands)?;
+
+        let mut m = CoincheMatch::new_rs(dealer, initial_hands);
+        m.rules = rules;
+        m.auction = auction.to_vec();
+        m.log = log;
+        m.coinche_level = bidding.coinche_level;
+        let finished = bidding.is_finished();
+        m.phase = Phase::Bidding(bidding);
+        if finished {
+            m.transition_from_bidding();
+        }
+
+        for (i, &card) in played_cards.iter().enumerate() {
+            let state = match m.phase {
+                Phase::Playing(ref mut state) => state,
+                _ => return Err(format!("Card #{} played after the deal ended", i)),
+            };
+            if (state.get_legal_moves() & (1 << card)) == 0 {
+                return Err(format!(
+                    "Card #{} ({}) is not a legal play for player {}",
+                    i, card, state.current_player
+                ));
+            }
+            m.log.push(LoggedAction {
+                seat: state.current_player,
+                event: LogEvent::Card(card),
+                timestamp_ms: None,
+            });
+            state.play_card(card);
+            m.played_cards.push(card);
+            if state.is_terminal() {
+                let state = *state;
+                m.finish_playing(&state);
+            }
+        }
+        Ok(m)
+    }
+
+    fn finish_playing(&mut self, state: &PlayingState) {
+        let ns_score = state.points[0] as i16;
+        let ew_score = state.points[1] as i16;
+        let contract = self.contract.unwrap();
+        let owner = self.contract_owner.unwrap();
+        let threshold = contract.value as i16;
+
+        let (owner_score, _) = if owner % 2 == 0 {
+            (ns_score, ew_score)
+        } else {
+            (ew_score, ns_score)
+        };
+        let contract_made = owner_score >= threshold;
+
+        self.phase = Phase::Finished(MatchResult {
+            contract: self.contract,
+            contract_owner: self.contract_owner,
+            points_ns: ns_score,
+            points_ew: ew_score,
+            contract_made,
+            coinche_level: self.coinche_level,
+            belote_team: belote_team(state),
+            timeout_seat: None,
+            revoke_seat: None,
+            revoke_card: None,
+            play_stats: self.play_stats(),
+        });
+    }
+
+    /// Tricks finished so far, in order.
+    pub fn tricks(&self) -> impl Iterator<Item = CompletedTrick> + '_ {
+        let trump = self.contract.map_or(0, |c| c.trump);
+        completed_tricks((self.dealer + 1) % 4, trump, &self.played_cards)
+    }
+
+    /// Error of an accessor of the `expected` phase called in another one.
+    fn wrong_phase(&self, expected: &str) -> PyErr {
+        pyo3::exceptions::PyRuntimeError::new_err(format!(
+            "Not in the {} phase (current: {})",
+            expected,
+            self.phase.name()
+        ))
+    }
+
+    /// Cards played so far with their seats, in order.
+    pub fn plays(&self) -> Vec<PlayRecord> {
+        let trump = self.contract.map_or(0, |c| c.trump);
+        attribute_played_cards((self.dealer + 1) % 4, trump, &self.played_cards)
+    }
+
+    /// Cards `seat` played so far.
+    pub fn played_by(&self, seat: u8) -> u32 {
+        self.plays()
+            .iter()
+            .filter(|p| p.seat == seat)
+            .fold(0, |m, p| m | 1 << p.card)
+    }
+
+    /// Statistics of the cards played so far, replayed from the initial deal.
+    pub(crate) fn play_stats(&self) -> Option<PlayStats> {
+        let contract = self.contract?;
+        let mut start = PlayingState::new(contract.trump);
+        start.hands = self.initial_hands;
+        start.rules = self.rules;
+        start.current_player = (self.dealer + 1) % 4;
+        start.trick_starter = start.current_player;
+        Some(PlayStats::from_play(&start, &self.played_cards))
+    }
 }
 
 #[pymethods]
 impl CoincheMatch {
+    /// With `time_control`, each seat's thinking time (between its turn starting
+    /// and its action) is charged to its bank; running out forfeits the match.
+    /// With `revoke_penalty`, playing a held card illegally is a revoke that
+    /// forfeits the deal (see `revoke`) rather than an error. `rules` sets the
+    /// scoring conventions (default: capot 252 with the der).
     #[new]
-    pub fn new(dealer: u8, hands: Vec<u32>) -> PyResult<Self> {
+    #[pyo3(signature = (dealer, hands, time_control=None, revoke_penalty=false, rules=None))]
+    pub fn new(
+        dealer: u8,
+        hands: Vec<u32>,
+        time_control: Option<TimeControl>,
+        revoke_penalty: bool,
+        rules: Option<RuleSet>,
+    ) -> PyResult<Self> {
         if hands.len() != 4 {
             return Err(pyo3::exceptions::PyValueError::new_err(
                 "Hands must have 4 entries",
             ));
         }
         let h: [u32; 4] = hands.try_into().unwrap();
-        Ok(CoincheMatch::new_rs(dealer, h))
+        let mut m = CoincheMatch::new_rs(dealer, h);
+        m.clock = time_control.map(MatchClock::new);
+        m.revoke_penalty = revoke_penalty;
+        m.rules = rules.unwrap_or_default();
+        Ok(m)
+    }
+
+    /// Remaining time bank of each seat in milliseconds (None without time control).
+    #[getter]
+    pub fn remaining_ms(&self) -> Option<[u64; 4]> {
+        self.clock.as_ref().map(|c| c.remaining_ms)
+    }
+
+    /// Forfeits the seat to move if it has already exceeded its time, without
+    /// waiting for its action. Returns true if the match ended on a timeout.
+    pub fn check_time(&mut self) -> bool {
+        let (Some(seat), Some(clock)) = (self.current_seat(), self.clock.as_ref()) else {
+            return false;
+        };
+        let elapsed = clock.elapsed_ms();
+        if clock.is_flagged(seat, elapsed) {
+            let _ = self.charge_clock(elapsed);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Replaces this match with the given position after validating it.
+    /// See `CoincheMatch::from_position` for the meaning of the arguments.
+    pub fn set_position(
+        &mut self,
+        hands: Vec<u32>,
+        auction: Vec<AuctionAction>,
+        played_cards: Vec<u8>,
+    ) -> PyResult<()> {
+        let h: [u32; 4] = hands
+            .try_into()
+            .map_err(|_| pyo3::exceptions::PyValueError::new_err("Hands must have 4 entries"))?;
+        let m = CoincheMatch::from_position_with_rules(
+            self.dealer,
+            h,
+            &auction,
+            &played_cards,
+            self.rules,
+        )
+        .map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let clock = self.clock.take();
+        let revoke_penalty = self.revoke_penalty;
+        *self = m;
+        self.clock = clock;
+        self.revoke_penalty = revoke_penalty;
+        if let Some(ref mut c) = self.clock {
+            c.restart();
+        }
+        Ok(())
+    }
+
+    /// Every action of the match so far in order, calls and cards of both
+    /// phases and the forfeit ending the deal, with seats and timestamps.
+    pub fn action_log(&self) -> Vec<LoggedAction> {
+        self.log.clone()
+    }
+
+    /// `action_log` as a JSON array of `LoggedAction.to_json` records.
+    pub fn action_log_json(&self) -> String {
+        log_to_json(&self.log)
+    }
+
+    /// Independent copy of the match, auction and play state included: actions
+    /// on either side leave the other unchanged.
+    pub fn branch(&self) -> Self {
+        self.clone()
+    }
+
+    /// Plays `actions` (as `apply_action` takes them) on a branch of the match
+    /// without its clock and evaluates the position reached, searching it for
+    /// at most about `time_budget_ms` during the play. The match is unchanged.
+    #[pyo3(signature = (actions, time_budget_ms=None, tt_log2=None))]
+    pub fn explore(
+        &self,
+        py: Python,
+        actions: Vec<BotAction>,
+        time_budget_ms: Option<u64>,
+        tt_log2: Option<u8>,
+    ) -> PyResult<Exploration> {
+        let mut branch = self.clone();
+        branch.clock = None;
+        for action in actions {
+            branch.apply_action(action)?;
+        }
+        let budget = time_budget_ms.map(std::time::Duration::from_millis);
+        py.allow_threads(|| explore::evaluate(branch, budget, tt_log2))
+            .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// Opaque bytes capturing the full state of the match, see `match_snapshot`.
+    pub fn snapshot(&self, py: Python) -> PyObject {
+        PyBytes::new(py, &match_snapshot::snapshot(self)).into()
+    }
+
+    /// Match of a `snapshot` blob, independent of the one it was taken from.
+    /// The clock of the seat to move restarts now.
+    #[staticmethod]
+    pub fn restore(blob: &[u8]) -> PyResult<Self> {
+        match_snapshot::restore(blob).map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// Text record of the match so far: deal, auction, tricks and result.
+    pub fn to_transcript(&self) -> String {
+        transcript::to_transcript(self)
+    }
+
+    /// Match replayed from a `to_transcript` record.
+    #[staticmethod]
+    pub fn from_transcript(text: &str) -> PyResult<Self> {
+        transcript::parse_transcript(text).map_err(pyo3::exceptions::PyValueError::new_err)
     }
 
     pub fn bid(&mut self, bid: Option<Bid>) -> PyResult<()> {
-        let (finished, level) = if let Phase::Bidding(ref mut state) = self.phase {
+        let seat = if let Phase::Bidding(_) = self.phase {
+            self.charge_wall_clock()?
+        } else {
+            None
+        };
+        let (caller, finished, level) = if let Phase::Bidding(ref mut state) = self.phase {
+            let caller = state.current_player;
             state
                 .apply_bid(bid)
                 .map_err(|e| pyo3::exceptions::PyValueError::new_err(e))?;
-            (state.is_finished(), state.coinche_level)
+            (caller, state.is_finished(), state.coinche_level)
         } else {
             return Err(pyo3::exceptions::PyRuntimeError::new_err(
                 "Not in bidding phase",
@@ -81,6 +578,14 @@ impl CoincheMatch {
         };
 
         self.coinche_level = level;
+        self.record_call(
+            caller,
+            match bid {
+                Some(b) => AuctionAction::Bid(b),
+                None => AuctionAction::Pass,
+            },
+        );
+        self.credit_increment(seat);
         if finished {
             self.transition_from_bidding();
         }
@@ -88,11 +593,17 @@ impl CoincheMatch {
     }
 
     pub fn coinche(&mut self) -> PyResult<()> {
-        let (finished, level) = if let Phase::Bidding(ref mut state) = self.phase {
+        let seat = if let Phase::Bidding(_) = self.phase {
+            self.charge_wall_clock()?
+        } else {
+            None
+        };
+        let (caller, finished, level) = if let Phase::Bidding(ref mut state) = self.phase {
+            let caller = state.current_player;
             state
                 .coinche()
                 .map_err(|e| pyo3::exceptions::PyValueError::new_err(e))?;
-            (state.is_finished(), state.coinche_level)
+            (caller, state.is_finished(), state.coinche_level)
         } else {
             return Err(pyo3::exceptions::PyRuntimeError::new_err(
                 "Not in bidding phase",
@@ -100,6 +611,8 @@ impl CoincheMatch {
         };
 
         self.coinche_level = level;
+        self.record_call(caller, AuctionAction::Coinche);
+        self.credit_increment(seat);
         if finished {
             self.transition_from_bidding();
         }
@@ -107,11 +620,22 @@ impl CoincheMatch {
     }
 
     pub fn surcoinche(&mut self) -> PyResult<()> {
-        let (finished, level) = if let Phase::Bidding(ref mut state) = self.phase {
+        if !self.rules.surcoinche {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "Surcoinche is not played under these rules",
+            ));
+        }
+        let seat = if let Phase::Bidding(_) = self.phase {
+            self.charge_wall_clock()?
+        } else {
+            None
+        };
+        let (caller, finished, level) = if let Phase::Bidding(ref mut state) = self.phase {
+            let caller = state.current_player;
             state
                 .surcoinche()
                 .map_err(|e| pyo3::exceptions::PyValueError::new_err(e))?;
-            (state.is_finished(), state.coinche_level)
+            (caller, state.is_finished(), state.coinche_level)
         } else {
             return Err(pyo3::exceptions::PyRuntimeError::new_err(
                 "Not in bidding phase",
@@ -119,6 +643,8 @@ impl CoincheMatch {
         };
 
         self.coinche_level = level;
+        self.record_call(caller, AuctionAction::Surcoinche);
+        self.credit_increment(seat);
         if finished {
             self.transition_from_bidding();
         }
@@ -141,6 +667,7 @@ impl CoincheMatch {
 
                 let mut game = PlayingState::new(final_contract.trump);
                 game.hands = self.initial_hands;
+                game.rules = self.rules;
                 game.current_player = (self.dealer + 1) % 4;
                 game.trick_starter = game.current_player;
                 // Passing coinche info?
@@ -156,42 +683,125 @@ impl CoincheMatch {
                     points_ns: 0,
                     points_ew: 0,
                     contract_made: false,
+                    coinche_level: 0,
+                    belote_team: None,
+                    timeout_seat: None,
+                    revoke_seat: None,
+                    revoke_card: None,
+                    play_stats: None,
                 });
             }
         }
     }
 
+    /// Calls the seat to move may make during the auction (empty in other phases),
+    /// see `BiddingState.legal_actions`; no surcoinche when the rules forbid it.
+    pub fn legal_actions(&self) -> Vec<AuctionAction> {
+        match self.phase {
+            Phase::Bidding(ref state) => state
+                .legal_actions()
+                .into_iter()
+                .filter(|&a| self.rules.surcoinche || a != AuctionAction::Surcoinche)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Plays an action of the seat to move: a call during the auction (as
+    /// `bot_action` returns it), a card index during play.
+    pub fn apply_action(&mut self, action: BotAction) -> PyResult<()> {
+        match action {
+            BotAction::Call(AuctionAction::Pass) => self.bid(None),
+            BotAction::Call(AuctionAction::Bid(bid)) => self.bid(Some(bid)),
+            BotAction::Call(AuctionAction::Coinche) => self.coinche(),
+            BotAction::Call(AuctionAction::Surcoinche) => self.surcoinche(),
+            BotAction::Play(card) => self.play_card(card),
+        }
+    }
+
+    /// Head of the dual-head action layout the current phase uses: 0 during the
+    /// auction, 1 during play, None once finished (see `encoding`).
+    pub fn action_head(&self) -> Option<u8> {
+        match self.phase {
+            Phase::Bidding(_) => Some(encoding::AUCTION_HEAD),
+            Phase::Playing(_) => Some(encoding::CARD_HEAD),
+            Phase::Finished(_) => None,
+        }
+    }
+
+    /// Legal actions of the seat to move as a mask over the flat action space
+    /// (`FLAT_ACTIONS` entries), or with `dual_head` over the head of the current
+    /// phase (`AUCTION_ACTIONS` or 32 entries, empty once finished).
+    #[pyo3(signature = (dual_head=false))]
+    pub fn legal_action_mask(&self, dual_head: bool) -> Vec<bool> {
+        let (head_size, offset) = match self.phase {
+            Phase::Bidding(_) => (encoding::AUCTION_ACTIONS, 0),
+            Phase::Playing(_) => (encoding::CARDS, encoding::AUCTION_ACTIONS),
+            Phase::Finished(_) if dual_head => return Vec::new(),
+            Phase::Finished(_) => return vec![false; encoding::FLAT_ACTIONS],
+        };
+        let mut mask = vec![false; head_size];
+        match self.phase {
+            Phase::Bidding(_) => {
+                for action in self.legal_actions() {
+                    let index =
+                        encoding::auction_action_index(action).expect("legal calls have an index");
+                    mask[index] = true;
+                }
+            }
+            Phase::Playing(ref state) => {
+                let legal = state.get_legal_moves();
+                for (card, entry) in mask.iter_mut().enumerate() {
+                    *entry = legal & (1 << card) != 0;
+                }
+            }
+            Phase::Finished(_) => {}
+        }
+        if dual_head {
+            return mask;
+        }
+        let mut flat = vec![false; encoding::FLAT_ACTIONS];
+        flat[offset..offset + head_size].copy_from_slice(&mask);
+        flat
+    }
+
+    /// `dump_state` text of the card-play position (None outside the play phase).
+    pub fn dump_state(&self) -> Option<String> {
+        match self.phase {
+            Phase::Playing(ref state) => Some(state.dump_state()),
+            _ => None,
+        }
+    }
+
     pub fn play_card(&mut self, card: u8) -> PyResult<()> {
+        let seat = if let Phase::Playing(_) = self.phase {
+            self.charge_wall_clock()?
+        } else {
+            None
+        };
         if let Phase::Playing(ref mut state) = self.phase {
             let legal = state.get_legal_moves();
             if (legal & (1 << card)) == 0 {
-                return Err(pyo3::exceptions::PyValueError::new_err("Illegal move"));
+                let seat = state.current_player;
+                if !self.revoke_penalty || state.hands[seat as usize] & (1 << card) == 0 {
+                    return Err(state_error(state, format!("Illegal move {}", card)));
+                }
+                self.revoke(seat, card);
+                return Ok(());
             }
 
+            self.log.push(LoggedAction::now(
+                state.current_player,
+                LogEvent::Card(card),
+            ));
             state.play_card(card);
+            self.played_cards.push(card);
 
             if state.is_terminal() {
-                let ns_score = state.points[0] as i16;
-                let ew_score = state.points[1] as i16;
-                let contract = self.contract.unwrap();
-                let owner = self.contract_owner.unwrap();
-                let threshold = contract.value as i16;
-
-                let (owner_score, _) = if owner % 2 == 0 {
-                    (ns_score, ew_score)
-                } else {
-                    (ew_score, ns_score)
-                };
-                let contract_made = owner_score >= threshold;
-
-                self.phase = Phase::Finished(MatchResult {
-                    contract: self.contract,
-                    contract_owner: self.contract_owner,
-                    points_ns: ns_score,
-                    points_ew: ew_score,
-                    contract_made,
-                });
+                let state = *state;
+                self.finish_playing(&state);
             }
+            self.credit_increment(seat);
             Ok(())
         } else {
             Err(pyo3::exceptions::PyRuntimeError::new_err(
@@ -200,15 +810,63 @@ impl CoincheMatch {
         }
     }
 
-    // Accessors for Phase info
-    pub fn phase_name(&self) -> String {
-        match self.phase {
-            Phase::Bidding(_) => "BIDDING".to_string(),
-            Phase::Playing(_) => "PLAYING".to_string(),
-            Phase::Finished(_) => "FINISHED".to_string(),
+    /// Action for `seat`, which must be the seat to move, computed from what that
+    /// seat can see: a call (None for pass, a Bid, "coinche" or "surcoinche") during
+    /// the auction, a card index during play. `strength` picks the card-play engine:
+    /// "heuristic", "pimc" or "pimc_ev", with `iterations` sampled worlds, or
+    /// "double_dummy", which sees every hand. Calls come from `bidder` when given,
+    /// from the hand heuristic otherwise.
+    #[pyo3(signature = (seat, strength="pimc", iterations=32, seed=None, bidder=None))]
+    pub fn bot_action(
+        &self,
+        py: Python,
+        seat: u8,
+        strength: &str,
+        iterations: usize,
+        seed: Option<u64>,
+        bidder: Option<ThresholdBidder>,
+    ) -> PyResult<PyObject> {
+        let strength =
+            BotStrength::parse(strength).map_err(pyo3::exceptions::PyValueError::new_err)?;
+        let action = py
+            .allow_threads(|| bot_action(self, seat, strength, iterations, seed, bidder.as_ref()))
+            .map_err(|e| self.error(e))?;
+        Ok(action.into_py(py))
+    }
+
+    /// Tricks finished so far, in order.
+    pub fn completed_tricks(&self) -> Vec<CompletedTrick> {
+        self.tricks().collect()
+    }
+
+    /// Mask of the cards `seat` played so far, or of every card played without a
+    /// seat. Public information: the same for every observer.
+    #[pyo3(signature = (seat=None))]
+    pub fn played_cards_mask(&self, seat: Option<u8>) -> PyResult<u32> {
+        match seat {
+            Some(seat) if seat >= 4 => Err(pyo3::exceptions::PyValueError::new_err(format!(
+                "Invalid seat {}",
+                seat
+            ))),
+            Some(seat) => Ok(self.played_by(seat)),
+            None => Ok(self.played_cards.iter().fold(0, |m, &c| m | 1 << c)),
         }
     }
 
+    /// Current phase, a member of the `Phase` IntEnum.
+    #[getter(phase)]
+    pub fn phase_enum(&self, py: Python) -> PyResult<PyObject> {
+        let phase = crate::PHASE_ENUM
+            .get(py)
+            .expect("registered with the module");
+        phase.call1(py, (self.phase.index(),))
+    }
+
+    /// Name of the current phase, as in `Phase`. Prefer the `phase` property.
+    pub fn phase_name(&self) -> String {
+        self.phase.name().to_string()
+    }
+
     pub fn get_bidding_state(&self) -> Option<BiddingState> {
         if let Phase::Bidding(ref s) = self.phase {
             Some(s.clone())
@@ -233,6 +891,24 @@ impl CoincheMatch {
         }
     }
 
+    /// The auction; raises `RuntimeError` in another phase.
+    pub fn bidding_state(&self) -> PyResult<BiddingState> {
+        self.get_bidding_state()
+            .ok_or_else(|| self.wrong_phase("BIDDING"))
+    }
+
+    /// The card play; raises `RuntimeError` in another phase.
+    pub fn playing_state(&self) -> PyResult<PlayingState> {
+        self.get_playing_state()
+            .ok_or_else(|| self.wrong_phase("PLAYING"))
+    }
+
+    /// The result of the finished deal; raises `RuntimeError` before.
+    pub fn result(&self) -> PyResult<MatchResult> {
+        self.get_result()
+            .ok_or_else(|| self.wrong_phase("FINISHED"))
+    }
+
     #[getter]
     pub fn hands(&self) -> [u32; 4] {
         match self.phase {
@@ -246,6 +922,7 @@ impl CoincheMatch {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::gameplay::notation::card_name;
     use crate::gameplay::playing::{HEARTS, SPADES};
 
     fn card(suit: u8, rank: u8) -> u8 {
@@ -315,4 +992,254 @@ mod tests {
             _ => panic!("Should be Finished"),
         }
     }
+
+    fn sorted_deal() -> [u32; 4] {
+        [0x0000_00FF, 0x0000_FF00, 0x00FF_0000, 0xFF00_0000]
+    }
+
+    #[test]
+    fn test_from_position_replays_history() {
+        // Dealer 3 -> P0 opens. Diamonds (suit 0) are all in P0's hand.
+        let auction = [
+            AuctionAction::Bid(Bid::new(80, SPADES)),
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+        ];
+        let played = [card(0, 7), card(SPADES, 0), card(HEARTS, 0), card(3, 0)];
+        let mut hands = sorted_deal();
+        for (p, &c) in played.iter().enumerate() {
+            hands[p] &= !(1 << c);
+        }
+
+        let m = CoincheMatch::from_position(3, hands, &auction, &played).unwrap();
+        match m.phase {
+            Phase::Playing(ref g) => {
+                // P1 trumped the Ace of Diamonds with the 7 of Spades.
+                assert_eq!(g.current_player, 1);
+                assert_eq!(g.tricks_won, [0, 1]);
+                assert_eq!(g.hands, hands);
+            }
+            _ => panic!("Should be in Playing phase"),
+        }
+        assert_eq!(m.initial_hands, sorted_deal());
+
+        let tricks = m.completed_tricks();
+        assert_eq!(tricks.len(), 1);
+        assert_eq!((tricks[0].leader, tricks[0].winner), (0, 1));
+        assert_eq!(tricks[0].cards, played);
+        assert_eq!(m.played_by(1), 1 << card(SPADES, 0));
+        assert_eq!(
+            m.played_cards_mask(None).unwrap(),
+            !hands.iter().fold(0, |a, &h| a | h)
+        );
+        assert!(m.played_cards_mask(Some(4)).is_err());
+    }
+
+    #[test]
+    fn test_from_position_rejects_impossible_states() {
+        let auction = [
+            AuctionAction::Bid(Bid::new(80, SPADES)),
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+        ];
+
+        // Not a full deal.
+        let mut hands = sorted_deal();
+        hands[0] &= !1;
+        assert!(CoincheMatch::from_position(3, hands, &auction, &[]).is_err());
+
+        // Card played before the auction ended.
+        let mut hands = sorted_deal();
+        hands[0] &= !(1 << card(0, 7));
+        assert!(CoincheMatch::from_position(3, hands, &auction[..2], &[card(0, 7)]).is_err());
+
+        // P1 (7H instead of 7S) discards a Heart on the Ace of Diamonds while holding trumps.
+        let played = [card(0, 7), card(HEARTS, 0)];
+        let mut hands = sorted_deal();
+        hands[0] &= !(1 << card(0, 7));
+        hands[1] &= !(1 << card(SPADES, 0));
+        hands[2] = (hands[2] & !(1 << card(HEARTS, 0))) | (1 << card(SPADES, 0));
+        let err = CoincheMatch::from_position(3, hands, &auction, &played).unwrap_err();
+        assert!(err.contains("not a legal play"));
+
+        // Illegal auction: coinche by the declaring side.
+        let bad_auction = [
+            AuctionAction::Bid(Bid::new(80, SPADES)),
+            AuctionAction::Pass,
+            AuctionAction::Coinche,
+        ];
+        assert!(CoincheMatch::from_position(3, sorted_deal(), &bad_auction, &[]).is_err());
+    }
+
+    #[test]
+    fn test_surcoinche_follows_rules() {
+        let auction = [
+            AuctionAction::Bid(Bid::new(80, SPADES)),
+            AuctionAction::Coinche,
+        ];
+        let cafe = RuleSet::from_preset("classic-cafe").unwrap();
+        let mut m =
+            CoincheMatch::from_position_with_rules(3, sorted_deal(), &auction, &[], cafe).unwrap();
+        assert!(!m.legal_actions().contains(&AuctionAction::Surcoinche));
+        assert!(!m.legal_action_mask(true)[encoding::AUCTION_ACTIONS - 1]);
+        assert!(m.surcoinche().is_err());
+        m.bid(None).unwrap();
+
+        let surcoinched = [&auction[..], &[AuctionAction::Surcoinche]].concat();
+        assert!(
+            CoincheMatch::from_position_with_rules(3, sorted_deal(), &surcoinched, &[], cafe)
+                .is_err()
+        );
+        let m = CoincheMatch::from_position(3, sorted_deal(), &auction, &[]).unwrap();
+        assert!(m.legal_actions().contains(&AuctionAction::Surcoinche));
+    }
+
+    #[test]
+    fn test_action_masks_follow_phase() {
+        let mut m = CoincheMatch::new_rs(3, sorted_deal());
+        assert_eq!(m.action_head(), Some(encoding::AUCTION_HEAD));
+        // The opening seat may pass or make any of the 60 bids.
+        let mask = m.legal_action_mask(false);
+        assert_eq!(mask.len(), encoding::FLAT_ACTIONS);
+        assert_eq!(mask.iter().filter(|&&b| b).count(), 61);
+        assert_eq!(m.legal_action_mask(true), mask[..encoding::AUCTION_ACTIONS]);
+
+        let bid = BotAction::Call(AuctionAction::Bid(Bid::new(80, SPADES)));
+        m.apply_action(bid).unwrap();
+        for _ in 0..3 {
+            m.apply_action(BotAction::Call(AuctionAction::Pass))
+                .unwrap();
+        }
+        // P0 leads from the diamonds: only its 8 cards are legal.
+        assert_eq!(m.action_head(), Some(encoding::CARD_HEAD));
+        let mask = m.legal_action_mask(false);
+        let cards = &mask[encoding::AUCTION_ACTIONS..];
+        assert_eq!(cards.iter().filter(|&&b| b).count(), 8);
+        assert!(cards[..8].iter().all(|&b| b));
+        assert_eq!(m.legal_action_mask(true), cards);
+        m.apply_action(BotAction::Play(card(0, 7))).unwrap();
+        assert_eq!(m.played_cards, vec![card(0, 7)]);
+    }
+
+    #[test]
+    fn test_timeout_forfeits_match() {
+        let mut m = CoincheMatch::new_rs(3, sorted_deal());
+        m.clock = Some(MatchClock::new(TimeControl::new(1000, 0, None)));
+
+        // P0 opens 80 Spades within its bank.
+        m.charge_clock(600).unwrap();
+        m.bid(Some(Bid::new(80, SPADES))).unwrap();
+        assert_eq!(m.current_seat(), Some(1));
+
+        // P1 thinks for too long: EW forfeit and NS take all points. The auction
+        // never ended, so there is no contract to make.
+        assert!(m.charge_clock(1001).is_err());
+        let banks = m.remaining_ms().unwrap();
+        assert!(banks[0] <= 400);
+        assert_eq!(banks[1], 0);
+        let res = m.get_result().unwrap();
+        assert_eq!(res.timeout_seat, Some(1));
+        assert_eq!((res.points_ns, res.points_ew), (162, 0));
+        assert!(res.contract.is_none() && !res.contract_made);
+        assert!(m.charge_clock(5000).is_ok());
+    }
+
+    #[test]
+    fn test_phase_accessors() {
+        let mut m = CoincheMatch::new_rs(3, sorted_deal());
+        assert_eq!((m.phase.index(), m.phase_name().as_str()), (0, "BIDDING"));
+        assert!(m.bidding_state().is_ok());
+        assert!(m.playing_state().is_err() && m.result().is_err());
+
+        let auction = [
+            AuctionAction::Bid(Bid::new(80, SPADES)),
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+        ];
+        m = CoincheMatch::from_position(3, sorted_deal(), &auction, &[]).unwrap();
+        assert_eq!((m.phase.index(), m.phase.name()), (1, "PLAYING"));
+        assert!(m.playing_state().is_ok() && m.bidding_state().is_err());
+
+        m.forfeit(0);
+        assert_eq!((m.phase.index(), m.phase.name()), (2, "FINISHED"));
+        assert!(m.result().is_ok() && m.playing_state().is_err());
+        for (i, (name, value)) in PHASE_MEMBERS.iter().enumerate() {
+            assert_eq!(*value as usize, i, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_revoke_forfeits_deal() {
+        let auction = [
+            AuctionAction::Bid(Bid::new(80, SPADES)),
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+        ];
+        // P1 holds the 7H instead of the 7S: it may not discard it on the Ace of Diamonds.
+        let mut hands = sorted_deal();
+        hands[1] = (hands[1] & !(1 << card(SPADES, 0))) | (1 << card(HEARTS, 0));
+        hands[2] = (hands[2] & !(1 << card(HEARTS, 0))) | (1 << card(SPADES, 0));
+        let mut m = CoincheMatch::from_position(3, hands, &auction, &[]).unwrap();
+        m.play_card(card(0, 7)).unwrap();
+        assert!(m.play_card(card(HEARTS, 0)).is_err());
+        assert_eq!(m.phase_name(), "PLAYING");
+
+        // With the penalty, the revoke hands the deal to NS, the declarers.
+        m.revoke_penalty = true;
+        assert!(m.play_card(card(0, 0)).is_err()); // Not P1's card: still an error
+        m.play_card(card(HEARTS, 0)).unwrap();
+        let res = m.get_result().unwrap();
+        assert_eq!(
+            (res.revoke_seat, res.revoke_card),
+            (Some(1), Some(card(HEARTS, 0)))
+        );
+        assert_eq!((res.points_ns, res.points_ew), (162, 0));
+        assert!(res.contract_made && res.timeout_seat.is_none());
+    }
+
+    #[test]
+    fn test_action_log_spans_both_phases() {
+        let mut m = CoincheMatch::new_rs(3, sorted_deal());
+        m.bid(Some(Bid::new(80, SPADES))).unwrap();
+        m.coinche().unwrap();
+        m.bid(None).unwrap();
+        m.bid(None).unwrap();
+        m.bid(None).unwrap();
+        m.play_card(card(0, 7)).unwrap();
+        m.play_card(card(SPADES, 0)).unwrap();
+
+        let log = m.action_log();
+        let seats: Vec<u8> = log.iter().map(|a| a.seat).collect();
+        assert_eq!(seats, [0, 1, 2, 3, 0, 0, 1]);
+        assert_eq!(log[1].event, LogEvent::Call(AuctionAction::Coinche));
+        assert_eq!(log[6].event, LogEvent::Card(card(SPADES, 0)));
+        assert!(log.iter().all(|a| a.timestamp_ms.is_some()));
+        assert!(log
+            .windows(2)
+            .all(|w| w[0].timestamp_ms <= w[1].timestamp_ms));
+
+        let json: serde_json::Value = serde_json::from_str(&m.action_log_json()).unwrap();
+        assert_eq!(json[0]["seat"], "S");
+        assert_eq!(json[0]["action"], "80 S");
+        assert_eq!(json[1]["kind"], "call");
+        assert_eq!(json[6]["kind"], "card");
+        assert_eq!(json[6]["action"], card_name(card(SPADES, 0)));
+
+        // A rebuilt position logs the same actions, without times.
+        let rebuilt = CoincheMatch::from_position(3, m.hands(), &m.auction, &m.played_cards)
+            .unwrap()
+            .action_log();
+        let events = |log: &[LoggedAction]| -> Vec<(u8, LogEvent)> {
+            log.iter().map(|a| (a.seat, a.event)).collect()
+        };
+        assert_eq!(events(&rebuilt), events(&log));
+        assert!(rebuilt.iter().all(|a| a.timestamp_ms.is_none()));
+
+        m.forfeit(1);
+        assert_eq!(m.action_log().last().unwrap().event, LogEvent::Timeout);
+    }
 }
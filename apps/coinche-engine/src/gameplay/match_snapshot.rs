@@ -0,0 +1,327 @@
+//! Opaque snapshots of a whole `CoincheMatch`, for servers persisting a game
+//! between requests and tests branching "what-if" lines from a mid-game point.
+//!
+//! Unlike `from_position`, which replays the history, a snapshot stores the
+//! phase as it is: the auction state with its pass count, the card-play state
+//! (as `dump_state` text) or the result, next to the deal, the history, the
+//! action log and the time banks. The blob is versioned JSON; restoring one and
+//! taking its snapshot again gives the same bytes. The clock of the seat to move
+//! restarts when the match is restored.
+
+use crate::gameplay::action_log::LoggedAction;
+use crate::gameplay::bidding::{AuctionAction, Bid, BiddingState};
+use crate::gameplay::clock::{MatchClock, TimeControl};
+use crate::gameplay::deal::validate_deal;
+use crate::gameplay::manager::{CoincheMatch, MatchResult, Phase};
+use crate::gameplay::notation::{bid_name, call_name, parse_call};
+use crate::gameplay::rules::RuleSet;
+use crate::gameplay::snapshot::{dump_state, load_state};
+use serde_json::{json, Value};
+
+/// Format of the blobs written by `snapshot`; `restore` rejects other versions.
+const VERSION: u64 = 1;
+
+/// Blob capturing the full state of `m`.
+pub fn snapshot(m: &CoincheMatch) -> Vec<u8> {
+    let phase = match m.phase {
+        Phase::Bidding(ref s) => json!({
+            "bidding": {
+                "history": s.history.iter().map(|&b| call_value(b)).collect::<Vec<_>>(),
+                "current_player": s.current_player,
+                "contract": s.contract.map(|b| bid_name(&b)),
+                "contract_owner": s.contract_owner,
+                "coinche_level": s.coinche_level,
+                "consecutive_passes": s.consecutive_passes,
+            }
+        }),
+        Phase::Playing(ref s) => json!({ "playing": dump_state(s) }),
+        Phase::Finished(ref r) => json!({
+            "finished": {
+                "points": [r.points_ns, r.points_ew],
+                "contract_made": r.contract_made,
+                "belote_team": r.belote_team,
+                "timeout_seat": r.timeout_seat,
+                "revoke_seat": r.revoke_seat,
+                "revoke_card": r.revoke_card,
+            }
+        }),
+    };
+    let clock = m.clock.as_ref().map(|c| {
+        json!({
+            "control": [c.control.initial_ms, c.control.increment_ms, c.control.max_move_ms],
+            "remaining_ms": c.remaining_ms,
+        })
+    });
+    let snapshot = json!({
+        "version": VERSION,
+        "dealer": m.dealer,
+        "initial_hands": m.initial_hands,
+        "contract": m.contract.map(|b| bid_name(&b)),
+        "contract_owner": m.contract_owner,
+        "coinche_level": m.coinche_level,
+        "revoke_penalty": m.revoke_penalty,
+        "rules": [
+            m.rules.capot_bonus,
+            m.rules.der_in_capot,
+            m.rules.belote,
+            m.rules.round_scores,
+            m.rules.surcoinche,
+        ],
+        "auction": m.auction.iter().map(call_name).collect::<Vec<_>>(),
+        "played_cards": m.played_cards,
+        "log": m.log.iter().map(LoggedAction::json_value).collect::<Vec<_>>(),
+        "clock": clock,
+        "phase": phase,
+    });
+    serde_json::to_vec(&snapshot).expect("JSON values serialize")
+}
+
+/// Match of a `snapshot` blob.
+pub fn restore(blob: &[u8]) -> Result<CoincheMatch, String> {
+    let value: Value =
+        serde_json::from_slice(blob).map_err(|e| format!("Invalid match snapshot: {}", e))?;
+    if value["version"].as_u64() != Some(VERSION) {
+        return Err(format!(
+            "Unsupported match snapshot version {}",
+            value["version"]
+        ));
+    }
+    let m = from_json(&value).ok_or("Invalid match snapshot: missing or malformed field")?;
+    validate_deal(&m.initial_hands).map_err(|e| format!("Invalid match snapshot: {}", e))?;
+    Ok(m)
+}
+
+fn from_json(value: &Value) -> Option<CoincheMatch> {
+    let mut m = CoincheMatch::new_rs(seat(&value["dealer"])?, u32_array(&value["initial_hands"])?);
+    m.contract = parse_bid(&value["contract"])?;
+    m.contract_owner = optional(&value["contract_owner"], seat)?;
+    m.coinche_level = small(&value["coinche_level"])?;
+    m.revoke_penalty = value["revoke_penalty"].as_bool()?;
+    // Snapshots taken before the belote, rounding and surcoinche policies have the
+    // usual ones.
+    let usual = RuleSet::default();
+    let flag = |i: usize, default: bool| match value["rules"].get(i) {
+        Some(flag) => flag.as_bool(),
+        None => Some(default),
+    };
+    m.rules = RuleSet {
+        capot_bonus: value["rules"][0].as_u64()?.try_into().ok()?,
+        der_in_capot: value["rules"][1].as_bool()?,
+        belote: flag(2, usual.belote)?,
+        round_scores: flag(3, usual.round_scores)?,
+        surcoinche: flag(4, usual.surcoinche)?,
+    }
+    .validated()
+    .ok()?;
+    m.auction = value["auction"]
+        .as_array()?
+        .iter()
+        .map(|c| parse_call(c.as_str()?).ok())
+        .collect::<Option<_>>()?;
+    m.played_cards = value["played_cards"]
+        .as_array()?
+        .iter()
+        .map(card)
+        .collect::<Option<_>>()?;
+    m.log = value["log"]
+        .as_array()?
+        .iter()
+        .map(LoggedAction::from_json_value)
+        .collect::<Option<_>>()?;
+    m.clock = optional(&value["clock"], clock_from_json)?;
+    m.phase = phase_from_json(&m, &value["phase"])?;
+    Some(m)
+}
+
+fn phase_from_json(m: &CoincheMatch, value: &Value) -> Option<Phase> {
+    if let Some(s) = value.get("bidding") {
+        let history = s["history"]
+            .as_array()?
+            .iter()
+            .map(|c| match parse_call(c.as_str()?).ok()? {
+                AuctionAction::Pass => Some(None),
+                AuctionAction::Bid(b) => Some(Some(b)),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        return Some(Phase::Bidding(BiddingState {
+            history,
+            current_player: seat(&s["current_player"])?,
+            contract: parse_bid(&s["contract"])?,
+            contract_owner: optional(&s["contract_owner"], seat)?,
+            coinche_level: small(&s["coinche_level"])?,
+            consecutive_passes: small(&s["consecutive_passes"])?,
+        }));
+    }
+    if let Some(s) = value.get("playing") {
+        return load_state(s.as_str()?).ok().map(Phase::Playing);
+    }
+    let r = value.get("finished")?;
+    Some(Phase::Finished(MatchResult {
+        contract: m.contract,
+        contract_owner: m.contract_owner,
+        points_ns: r["points"][0].as_i64()?.try_into().ok()?,
+        points_ew: r["points"][1].as_i64()?.try_into().ok()?,
+        contract_made: r["contract_made"].as_bool()?,
+        coinche_level: m.coinche_level,
+        belote_team: optional(&r["belote_team"], small)?,
+        timeout_seat: optional(&r["timeout_seat"], seat)?,
+        revoke_seat: optional(&r["revoke_seat"], seat)?,
+        revoke_card: optional(&r["revoke_card"], card)?,
+        // Derived from the cards played, as when the deal ended.
+        play_stats: m.play_stats(),
+    }))
+}
+
+fn clock_from_json(value: &Value) -> Option<MatchClock> {
+    let control = &value["control"];
+    let mut clock = MatchClock::new(TimeControl::new(
+        control[0].as_u64()?,
+        control[1].as_u64()?,
+        optional(&control[2], Value::as_u64)?,
+    ));
+    let remaining = value["remaining_ms"].as_array()?;
+    if remaining.len() != 4 {
+        return None;
+    }
+    for (bank, v) in clock.remaining_ms.iter_mut().zip(remaining) {
+        *bank = v.as_u64()?;
+    }
+    Some(clock)
+}
+
+/// "pass" or the bid, as `call_name` writes them.
+fn call_value(bid: Option<Bid>) -> String {
+    call_name(&bid.map_or(AuctionAction::Pass, AuctionAction::Bid))
+}
+
+fn parse_bid(value: &Value) -> Option<Option<Bid>> {
+    optional(value, |v| match parse_call(v.as_str()?).ok()? {
+        AuctionAction::Bid(b) => Some(b),
+        _ => None,
+    })
+}
+
+/// None for a JSON null, `parse` of the value otherwise (None if that fails).
+fn optional<T>(value: &Value, parse: impl Fn(&Value) -> Option<T>) -> Option<Option<T>> {
+    match value {
+        Value::Null => Some(None),
+        v => parse(v).map(Some),
+    }
+}
+
+fn small(value: &Value) -> Option<u8> {
+    value.as_u64()?.try_into().ok()
+}
+
+fn seat(value: &Value) -> Option<u8> {
+    small(value).filter(|&s| s < 4)
+}
+
+fn card(value: &Value) -> Option<u8> {
+    small(value).filter(|&c| c < 32)
+}
+
+fn u32_array(value: &Value) -> Option<[u32; 4]> {
+    let values = value.as_array()?;
+    if values.len() != 4 {
+        return None;
+    }
+    let mut out = [0; 4];
+    for (o, v) in out.iter_mut().zip(values) {
+        *o = v.as_u64()?.try_into().ok()?;
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::clock::TimeControl;
+    use crate::gameplay::playing::SPADES;
+
+    fn sorted_deal() -> [u32; 4] {
+        [0xFF, 0xFF00, 0xFF_0000, 0xFF00_0000]
+    }
+
+    /// Restores `m` and checks the copy is the same match.
+    fn round_trip(m: &CoincheMatch) -> CoincheMatch {
+        let blob = snapshot(m);
+        let restored = restore(&blob).unwrap();
+        assert_eq!(snapshot(&restored), blob);
+        assert_eq!(format!("{:?}", restored.phase), format!("{:?}", m.phase));
+        assert_eq!(restored.log, m.log);
+        restored
+    }
+
+    #[test]
+    fn test_snapshot_round_trips_every_phase() {
+        // P1 holds the 7H instead of the 7S, so it can revoke on a diamond lead.
+        let mut hands = sorted_deal();
+        hands[1] = (hands[1] & !(1 << 8)) | 1 << 16;
+        hands[2] = (hands[2] & !(1 << 16)) | 1 << 8;
+        let mut m = CoincheMatch::new_rs(3, hands);
+        m.clock = Some(MatchClock::new(TimeControl::new(60_000, 500, Some(10_000))));
+        round_trip(&m);
+
+        m.bid(Some(Bid::new(80, SPADES))).unwrap();
+        m.bid(None).unwrap();
+        m.bid(Some(Bid::new(90, SPADES))).unwrap();
+        m.coinche().unwrap();
+        let restored = round_trip(&m);
+        assert_eq!(restored.remaining_ms(), m.remaining_ms());
+        let Phase::Bidding(ref s) = restored.phase else {
+            panic!("still bidding");
+        };
+        assert_eq!((s.coinche_level, s.current_player), (1, 0));
+
+        for _ in 0..3 {
+            m.bid(None).unwrap();
+        }
+        m.play_card(7).unwrap();
+        round_trip(&m);
+
+        // A revoke ends the deal: its result and statistics survive.
+        m.revoke_penalty = true;
+        m.play_card(16).unwrap();
+        let restored = round_trip(&m);
+        assert_eq!(restored.get_result().unwrap().revoke_seat, Some(1));
+    }
+
+    #[test]
+    fn test_restored_match_branches_independently() {
+        let mut m = CoincheMatch::new_rs(3, sorted_deal());
+        m.bid(Some(Bid::new(80, SPADES))).unwrap();
+        for _ in 0..3 {
+            m.bid(None).unwrap();
+        }
+        m.play_card(0).unwrap();
+        let blob = snapshot(&m);
+
+        let mut branch = restore(&blob).unwrap();
+        branch.play_card(8).unwrap();
+        m.play_card(15).unwrap();
+        assert_eq!(branch.played_cards, [0, 8]);
+        assert_eq!(m.played_cards, [0, 15]);
+        assert_eq!(restore(&blob).unwrap().played_cards, [0]);
+    }
+
+    #[test]
+    fn test_restore_rejects_bad_blobs() {
+        let blob = snapshot(&CoincheMatch::new_rs(0, sorted_deal()));
+        assert!(restore(b"not json").is_err());
+        assert!(restore(&blob[..blob.len() - 1]).is_err());
+
+        let mut value: Value = serde_json::from_slice(&blob).unwrap();
+        value["version"] = json!(99);
+        let err = restore(&serde_json::to_vec(&value).unwrap()).unwrap_err();
+        assert!(err.contains("version"), "{}", err);
+
+        value["version"] = json!(VERSION);
+        value["initial_hands"][0] = json!(0xFF00);
+        assert!(restore(&serde_json::to_vec(&value).unwrap()).is_err());
+        value["initial_hands"][0] = json!(0xFF);
+        value["phase"] = json!({ "shuffling": {} });
+        assert!(restore(&serde_json::to_vec(&value).unwrap()).is_err());
+    }
+}
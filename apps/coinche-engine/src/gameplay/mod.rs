@@ -1,5 +1,28 @@
 //! Contree rules implementation for bidding and play phases.
 
+pub mod action_log;
+pub mod analysis;
 pub mod bidding;
+pub mod bot;
+pub mod bot_preset;
+pub mod clock;
+pub mod corpus;
+pub mod deal;
+pub mod duplicate;
+pub mod encoding;
+pub mod explore;
+pub mod game;
+pub mod history;
 pub mod manager;
+pub mod match_snapshot;
+pub mod notation;
+pub mod play_stats;
 pub mod playing;
+pub mod rules;
+#[cfg(test)]
+pub mod scenario;
+pub mod snapshot;
+pub mod stats;
+pub mod threshold_bidder;
+pub mod transcript;
+pub mod worlds;
@@ -0,0 +1,167 @@
+//! Text notation of cards, calls and seats, shared by the rules scenarios and
+//! game transcripts: cards as rank then suit letter ("7D", "10S", "AH"), calls as
+//! "pass", "coinche", "surcoinche" or a bid such as "80 H", "120 NT", "252 AT".
+
+use crate::gameplay::bidding::{AuctionAction, Bid};
+use crate::gameplay::playing::{
+    ALL_TRUMP, CLUBS, DIAMONDS, HEARTS, NO_TRUMP, RANK_10, RANK_7, RANK_8, RANK_9, RANK_A, RANK_J,
+    RANK_K, RANK_Q, SPADES,
+};
+
+const RANKS: [&str; 8] = ["7", "8", "9", "10", "J", "Q", "K", "A"];
+const SUITS: [&str; 4] = ["D", "S", "H", "C"];
+const TRUMPS: [&str; 6] = ["D", "S", "H", "C", "NT", "AT"];
+const SEATS: [&str; 4] = ["S", "W", "N", "E"];
+
+/// Members of the Python `Suit` enum, which doubles as the contract type.
+pub const SUIT_MEMBERS: [(&str, u8); 6] = [
+    ("DIAMONDS", DIAMONDS),
+    ("SPADES", SPADES),
+    ("HEARTS", HEARTS),
+    ("CLUBS", CLUBS),
+    ("NO_TRUMP", NO_TRUMP),
+    ("ALL_TRUMP", ALL_TRUMP),
+];
+
+/// Members of the Python `Rank` enum.
+pub const RANK_MEMBERS: [(&str, u8); 8] = [
+    ("SEVEN", RANK_7),
+    ("EIGHT", RANK_8),
+    ("NINE", RANK_9),
+    ("TEN", RANK_10),
+    ("JACK", RANK_J),
+    ("QUEEN", RANK_Q),
+    ("KING", RANK_K),
+    ("ACE", RANK_A),
+];
+
+/// Card index of `rank` in `suit` (a real suit, not a contract).
+pub fn card_index(suit: u8, rank: u8) -> Result<u8, String> {
+    if suit >= 4 || rank >= 8 {
+        return Err(format!("Invalid card: suit {}, rank {}", suit, rank));
+    }
+    Ok(suit * 8 + rank)
+}
+
+/// Card index of a name such as "7D", "10S" or "AH" (rank, then suit letter).
+pub fn parse_card(name: &str) -> Result<u8, String> {
+    let name = name.trim().to_ascii_uppercase();
+    let split = name
+        .len()
+        .checked_sub(1)
+        .ok_or_else(|| "Empty card name".to_string())?;
+    let (rank, suit) = name.split_at(split);
+    let rank = RANKS.iter().position(|&r| r == rank);
+    let suit = SUITS.iter().position(|&s| s == suit);
+    match (rank, suit) {
+        (Some(r), Some(s)) => Ok((s * 8 + r) as u8),
+        _ => Err(format!("Invalid card '{}'", name)),
+    }
+}
+
+pub fn card_name(card: u8) -> String {
+    format!(
+        "{}{}",
+        RANKS[(card % 8) as usize],
+        SUITS[(card / 8) as usize]
+    )
+}
+
+/// Names of the cards of a mask, in card index order.
+pub fn card_names(mut cards: u32) -> Vec<String> {
+    let mut names = Vec::new();
+    while cards != 0 {
+        names.push(card_name(cards.trailing_zeros() as u8));
+        cards &= cards - 1;
+    }
+    names
+}
+
+/// Auction call from "pass", "coinche", "surcoinche" or a bid such as "80 H",
+/// "120 NT" or "252 AT".
+pub fn parse_call(call: &str) -> Result<AuctionAction, String> {
+    let call = call.trim().to_ascii_uppercase();
+    match call.as_str() {
+        "PASS" => return Ok(AuctionAction::Pass),
+        "COINCHE" => return Ok(AuctionAction::Coinche),
+        "SURCOINCHE" => return Ok(AuctionAction::Surcoinche),
+        _ => {}
+    }
+    let (value, trump) = call
+        .split_once(' ')
+        .ok_or_else(|| format!("Invalid call '{}'", call))?;
+    let value = value
+        .parse::<u8>()
+        .map_err(|_| format!("Invalid bid value in '{}'", call))?;
+    let trump = TRUMPS
+        .iter()
+        .position(|&t| t == trump.trim())
+        .ok_or_else(|| format!("Invalid trump in '{}'", call))?;
+    Ok(AuctionAction::Bid(Bid::new(value, trump as u8)))
+}
+
+pub fn call_name(call: &AuctionAction) -> String {
+    match call {
+        AuctionAction::Pass => "pass".to_string(),
+        AuctionAction::Bid(b) => bid_name(b),
+        AuctionAction::Coinche => "coinche".to_string(),
+        AuctionAction::Surcoinche => "surcoinche".to_string(),
+    }
+}
+
+pub fn bid_name(bid: &Bid) -> String {
+    format!("{} {}", bid.value, TRUMPS[bid.trump as usize])
+}
+
+/// Contract of "D", "S", "H", "C", "NT" or "AT".
+pub fn parse_trump(name: &str) -> Result<u8, String> {
+    TRUMPS
+        .iter()
+        .position(|&t| t.eq_ignore_ascii_case(name.trim()))
+        .map(|t| t as u8)
+        .ok_or_else(|| format!("Invalid trump '{}'", name.trim()))
+}
+
+pub fn trump_name(trump: u8) -> &'static str {
+    TRUMPS[trump as usize]
+}
+
+pub fn suit_name(suit: u8) -> &'static str {
+    SUITS[suit as usize]
+}
+
+/// Seat index of "S", "W", "N" or "E".
+pub fn parse_seat(name: &str) -> Result<u8, String> {
+    SEATS
+        .iter()
+        .position(|&s| s.eq_ignore_ascii_case(name.trim()))
+        .map(|s| s as u8)
+        .ok_or_else(|| format!("Invalid seat '{}'", name.trim()))
+}
+
+pub fn seat_name(seat: u8) -> &'static str {
+    SEATS[seat as usize]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_notation() {
+        assert_eq!(parse_card("7D"), Ok(0));
+        assert_eq!(parse_card("10s"), Ok(11));
+        assert_eq!(parse_card("AC"), Ok(31));
+        assert!(parse_card("1H").is_err());
+        assert_eq!(card_names(1 << 11 | 1 << 31), vec!["10S", "AC"]);
+        assert_eq!(card_index(SPADES, RANK_10), Ok(11));
+        assert!(card_index(NO_TRUMP, RANK_A).is_err());
+        assert_eq!(parse_trump("at"), Ok(ALL_TRUMP));
+        assert_eq!(parse_call("90 nt"), Ok(AuctionAction::Bid(Bid::new(90, 4))));
+        assert_eq!(parse_call("Coinche"), Ok(AuctionAction::Coinche));
+        assert!(parse_call("90 X").is_err());
+        let bid = AuctionAction::Bid(Bid::new(252, 5));
+        assert_eq!(parse_call(&call_name(&bid)), Ok(bid));
+        assert_eq!(parse_seat(seat_name(3)), Ok(3));
+    }
+}
@@ -0,0 +1,156 @@
+//! Per-seat card-play statistics of a deal, replayed from its cards: who won
+//! the tricks and their points, what each seat put into its own team's tricks,
+//! trumps and forced overcuts, and who announced the belote.
+
+use crate::gameplay::playing::{card_points, PlayingState, LAST_TRICK_BONUS, RANK_STRENGTH_TRUMP};
+use pyo3::prelude::*;
+
+#[pyclass]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlayStats {
+    /// Tricks won by each seat.
+    #[pyo3(get)]
+    pub tricks_won: [u8; 4],
+    /// Card points of the tricks won by each seat, the der included (no capot
+    /// bonus nor belote).
+    #[pyo3(get)]
+    pub points_won: [u16; 4],
+    /// Card points each seat played into tricks won by its own team.
+    #[pyo3(get)]
+    pub points_contributed: [u16; 4],
+    /// Trumps played by each seat (suit contracts only).
+    #[pyo3(get)]
+    pub trumps_played: [u8; 4],
+    /// Trumps each seat had to play over a trump winning the trick, every legal
+    /// card being such an overcut.
+    #[pyo3(get)]
+    pub forced_overcuts: [u8; 4],
+    /// Seat that announced the belote.
+    #[pyo3(get)]
+    pub belote_seat: Option<u8>,
+}
+
+impl PlayStats {
+    /// Statistics of `cards` played in order from `start`; the replay stops at
+    /// the first illegal card (a revoke ends the deal there anyway).
+    pub fn from_play(start: &PlayingState, cards: &[u8]) -> Self {
+        let mut stats = PlayStats::default();
+        let mut state = *start;
+        let mut trick: Vec<(u8, u8)> = Vec::with_capacity(4);
+        let suit_trump = (state.trump < 4).then_some(state.trump);
+
+        for &card in cards {
+            let legal = state.get_legal_moves();
+            if legal & (1 << card) == 0 {
+                break;
+            }
+            let seat = state.current_player;
+            if let Some(trump) = suit_trump.filter(|&t| card / 8 == t) {
+                stats.trumps_played[seat as usize] += 1;
+                let higher = overcutting_trumps(&state, trump);
+                if higher & (1 << card) != 0 && legal & !higher == 0 {
+                    stats.forced_overcuts[seat as usize] += 1;
+                }
+            }
+
+            let belote = state.belote_scored;
+            state.play_card(card);
+            if state.belote_scored != belote {
+                stats.belote_seat = Some(seat);
+            }
+            trick.push((seat, card));
+
+            if trick.len() == 4 {
+                let winner = state.last_trick_winner.expect("a full trick has a winner");
+                let mut points: u16 = trick
+                    .iter()
+                    .map(|&(_, c)| card_points(c, state.trump))
+                    .sum();
+                if state.hands.iter().all(|&h| h == 0) {
+                    points += LAST_TRICK_BONUS;
+                }
+                stats.tricks_won[winner as usize] += 1;
+                stats.points_won[winner as usize] += points;
+                for &(s, c) in trick.iter().filter(|&&(s, _)| s % 2 == winner % 2) {
+                    stats.points_contributed[s as usize] += card_points(c, state.trump);
+                }
+                trick.clear();
+            }
+        }
+        stats
+    }
+}
+
+/// Trumps of the player to move beating a trump that currently wins the trick
+/// (none when no trump wins it).
+fn overcutting_trumps(state: &PlayingState, trump: u8) -> u32 {
+    if state.trick_size == 0 {
+        return 0;
+    }
+    let winning = state.current_trick[state.get_current_trick_winner_player() as usize];
+    if winning / 8 != trump {
+        return 0;
+    }
+    let strength = RANK_STRENGTH_TRUMP[(winning % 8) as usize];
+    let hand = state.hands[state.current_player as usize];
+    (0..8)
+        .filter(|&r| RANK_STRENGTH_TRUMP[r as usize] > strength)
+        .map(|r| trump * 8 + r)
+        .filter(|&c| hand & (1 << c) != 0)
+        .fold(0, |mask, c| mask | 1 << c)
+}
+
+#[pymethods]
+impl PlayStats {
+    pub fn __repr__(&self) -> String {
+        format!(
+            "PlayStats(tricks_won={:?}, points_won={:?}, points_contributed={:?}, trumps_played={:?}, forced_overcuts={:?}, belote_seat={})",
+            self.tricks_won,
+            self.points_won,
+            self.points_contributed,
+            self.trumps_played,
+            self.forced_overcuts,
+            self.belote_seat
+                .map_or("None".to_string(), |seat| seat.to_string())
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::playing::{HEARTS, SPADES};
+
+    fn card(suit: u8, rank: u8) -> u8 {
+        suit * 8 + rank
+    }
+
+    #[test]
+    fn test_play_stats() {
+        let mut state = PlayingState::new(HEARTS);
+        // S leads the AS, W cuts with the 8H and N, void in spades, must overcut
+        // with the JH. N leads the 7H: S must go over it and announces the belote.
+        state.hands = [
+            1 << card(SPADES, 7) | 1 << card(HEARTS, 5) | 1 << card(HEARTS, 6),
+            1 << card(HEARTS, 1) | 1 << card(HEARTS, 2),
+            1 << card(HEARTS, 4) | 1 << card(HEARTS, 0),
+            1 << card(SPADES, 0) | 1 << card(SPADES, 1),
+        ];
+        let cards = [
+            card(SPADES, 7),
+            card(HEARTS, 1),
+            card(HEARTS, 4),
+            card(SPADES, 0),
+            card(HEARTS, 0),
+            card(SPADES, 1),
+            card(HEARTS, 5),
+        ];
+        let stats = PlayStats::from_play(&state, &cards);
+        assert_eq!(stats.tricks_won, [0, 0, 1, 0]);
+        assert_eq!(stats.points_won, [0, 0, 31, 0]);
+        assert_eq!(stats.points_contributed, [11, 0, 20, 0]);
+        assert_eq!(stats.trumps_played, [1, 1, 2, 0]);
+        assert_eq!(stats.forced_overcuts, [1, 0, 1, 0]);
+        assert_eq!(stats.belote_seat, Some(0));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::gameplay::rules::RuleSet;
 use pyo3::prelude::*;
 
 // Card mapping constants
@@ -30,6 +31,35 @@ pub const RANK_A: u8 = 7;
 pub const POINTS_NON_TRUMP: [u16; 8] = [0, 0, 0, 10, 2, 3, 4, 11];
 // Trump: 7=0, 8=0, 9=14, 10=10, J=20, Q=3, K=4, A=11
 pub const POINTS_TRUMP: [u16; 8] = [0, 0, 14, 10, 20, 3, 4, 11];
+// No-Trump (every suit): 7=0, 8=0, 9=0, 10=10, J=2, Q=3, K=4, A=19
+pub const POINTS_NO_TRUMP: [u16; 8] = [0, 0, 0, 10, 2, 3, 4, 19];
+// All-Trump (every suit): 7=0, 8=0, 9=9, 10=5, J=14, Q=1, K=3, A=6
+pub const POINTS_ALL_TRUMP: [u16; 8] = [0, 0, 9, 5, 14, 1, 3, 6];
+// Whatever the contract, the cards add up to 152 (162 with the dix de der).
+pub const TOTAL_CARD_POINTS: u16 = 152;
+pub const LAST_TRICK_BONUS: u16 = 10;
+
+/// Point value of `card` under contract `trump` (a suit, NO_TRUMP or ALL_TRUMP).
+pub fn card_points(card: u8, trump: u8) -> u16 {
+    let rank = (card % 8) as usize;
+    match trump {
+        NO_TRUMP => POINTS_NO_TRUMP[rank],
+        ALL_TRUMP => POINTS_ALL_TRUMP[rank],
+        t if card / 8 == t => POINTS_TRUMP[rank],
+        _ => POINTS_NON_TRUMP[rank],
+    }
+}
+
+/// Sum of the card points of a card mask.
+pub fn cards_points(mut cards: u32, trump: u8) -> u16 {
+    let mut points = 0;
+    while cards != 0 {
+        let c = cards.trailing_zeros() as u8;
+        cards &= cards - 1;
+        points += card_points(c, trump);
+    }
+    points
+}
 
 // Order (Strength)
 // Non-Trump: 7, 8, 9, J, Q, K, 10, A (Indices: 0, 1, 2, 4, 5, 6, 3, 7)
@@ -65,6 +95,14 @@ pub struct PlayingState {
     pub last_trick_starter: u8,
     #[pyo3(get)]
     pub last_trick_winner: Option<u8>,
+    /// Scoring conventions (capot bonus, der in a capot).
+    #[pyo3(get)]
+    pub rules: RuleSet,
+    /// Card points in hands and on the table, i.e. not yet won by a team.
+    /// Counted down by `play_card`; see `recount_points_in_play` for states whose
+    /// hands are set mid-deal.
+    #[pyo3(get)]
+    pub points_in_play: u16,
 }
 
 impl PlayingState {
@@ -82,7 +120,55 @@ impl PlayingState {
             last_trick: [255; 4],
             last_trick_starter: 0,
             last_trick_winner: None,
+            rules: RuleSet::default(),
+            points_in_play: TOTAL_CARD_POINTS,
+        }
+    }
+
+    /// Sets `points_in_play` from the cards actually in hands and on the table.
+    pub fn recount_points_in_play(&mut self) {
+        let table = self
+            .current_trick
+            .iter()
+            .filter(|&&c| c < 32)
+            .fold(0u32, |m, &c| m | 1 << c);
+        let in_play = self.hands.iter().fold(table, |m, &h| m | h);
+        self.points_in_play = cards_points(in_play, self.trump);
+    }
+
+    /// Whether a player of `team` holds both the King and the Queen of a suit
+    /// trump: the belote is then certain, as every card gets played.
+    fn holds_belote(&self, team: usize) -> bool {
+        if !self.rules.belote || self.trump >= 4 || self.belote_scored[team] {
+            return false;
+        }
+        let pair = (1 << (self.trump * 8 + RANK_K)) | (1 << (self.trump * 8 + RANK_Q));
+        self.hands[team] & pair == pair || self.hands[team + 2] & pair == pair
+    }
+
+    /// Cards no longer in any hand: those of finished tricks and of the current one.
+    pub fn played_cards(&self) -> u32 {
+        !self.hands.iter().fold(0, |m, &h| m | h)
+    }
+
+    /// (lowest, highest) final points of `team` whatever happens next. Lowest:
+    /// points won plus a certain belote. Highest: also every point still in play,
+    /// the dix de der and, while the opponents have no trick, the capot bonus.
+    pub fn team_score_bounds(&self, team: usize) -> (u16, u16) {
+        let belote = if self.holds_belote(team) { 20 } else { 0 };
+        let lower = self.points[team] + belote;
+        if self.is_terminal() {
+            return (lower, lower);
         }
+        let capot = if self.tricks_won[1 - team] == 0 {
+            self.rules.capot_bonus
+        } else {
+            0
+        };
+        (
+            lower,
+            lower + self.points_in_play + LAST_TRICK_BONUS + capot,
+        )
     }
 }
 
@@ -93,12 +179,60 @@ impl PlayingState {
         PlayingState::new(trump)
     }
 
+    /// One-line text rebuilding this exact state with `load_state`, to paste in
+    /// bug reports.
+    pub fn dump_state(&self) -> String {
+        crate::gameplay::snapshot::dump_state(self)
+    }
+
+    #[staticmethod]
+    pub fn load_state(text: &str) -> PyResult<Self> {
+        crate::gameplay::snapshot::load_state(text).map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
     pub fn set_hand(&mut self, player: u8, cards: u32) {
         if player < 4 {
             self.hands[player as usize] = cards;
+            self.recount_points_in_play();
+        }
+    }
+
+    /// [(lowest, highest)] final points of NS and EW still reachable, see
+    /// `team_score_bounds`.
+    #[getter]
+    pub fn score_bounds(&self) -> [(u16, u16); 2] {
+        [self.team_score_bounds(0), self.team_score_bounds(1)]
+    }
+
+    /// Whether a contract of `value` declared by `declarer` is already decided:
+    /// True when it is made whatever happens, False when it can no longer be,
+    /// None while both are possible.
+    pub fn contract_decided(&self, value: u16, declarer: u8) -> Option<bool> {
+        let (lower, upper) = self.team_score_bounds((declarer % 2) as usize);
+        if lower >= value {
+            Some(true)
+        } else if upper < value {
+            Some(false)
+        } else {
+            None
         }
     }
 
+    /// Mask of the cards played so far, the current trick included.
+    pub fn played_cards_mask(&self) -> u32 {
+        self.played_cards()
+    }
+
+    /// Mask of the cards `seat` has seen: those played and its own hand. The
+    /// others are still out, in the hands of the other seats.
+    pub fn seen_cards_mask(&self, seat: u8) -> u32 {
+        self.played_cards() | self.get_hand(seat)
+    }
+
+    pub fn set_rules(&mut self, rules: RuleSet) {
+        self.rules = rules;
+    }
+
     pub fn get_hand(&self, player: u8) -> u32 {
         if player < 4 {
             self.hands[player as usize]
@@ -109,6 +243,7 @@ impl PlayingState {
 
     /// Returns a bitmask of legal moves for the current player
     pub fn get_legal_moves(&self) -> u32 {
+        crate::profiling::count(crate::profiling::Counter::LegalMoves);
         let hand = self.hands[self.current_player as usize];
 
         // If leading, any card is legal
@@ -227,7 +362,8 @@ impl PlayingState {
         best_card
     }
 
-    fn get_current_trick_winner_player(&self) -> u8 {
+    /// Seat currently winning the trick (the leader while the trick is empty).
+    pub fn get_current_trick_winner_player(&self) -> u8 {
         let mut best_card = self.current_trick[self.trick_starter as usize];
         let mut best_player = self.trick_starter;
         let lead_suit = best_card / 8;
@@ -277,8 +413,8 @@ impl PlayingState {
     /// Play a card (index 0-31)
     pub fn play_card(&mut self, card: u8) {
         // Check for Belote/Rebelote
-        // Only if trump is valid (0-3)
-        if self.trump < 4 {
+        // Only if trump is valid (0-3) and the table plays it
+        if self.rules.belote && self.trump < 4 {
             let suit = card / 8;
             if suit == self.trump {
                 let rank = card % 8;
@@ -319,25 +455,26 @@ impl PlayingState {
         let winner = self.get_current_trick_winner_player();
         let winning_team = (winner % 2) as usize;
 
-        let mut points = 0;
-        for i in 0..4 {
-            let c = self.current_trick[i];
-            let s = c / 8;
-            let r = (c % 8) as usize;
-            if s == self.trump {
-                points += POINTS_TRUMP[r];
-            } else {
-                points += POINTS_NON_TRUMP[r];
-            }
-        }
+        let mut points: u16 = self
+            .current_trick
+            .iter()
+            .map(|&c| card_points(c, self.trump))
+            .sum();
+        self.points_in_play = self.points_in_play.saturating_sub(points);
+
+        self.tricks_won[winning_team] += 1;
+        let capot = self.tricks_won[winning_team] == 8;
 
         // Dix de Der (10 points for last trick)
-        // How to know if it's the last trick? Check if hands are empty.
-        // Actually, simpler: we can track turn number or just check hands.
-        // Since we modify hands in play_card, if hands[0] == 0 after this trick, it was the last one.
-        // But we just removed the card. So if all hands are 0 now.
-        if self.hands[0] == 0 && self.hands[1] == 0 && self.hands[2] == 0 && self.hands[3] == 0 {
-            points += 10;
+        // Since we modify hands in play_card, if all hands are 0 now this was the last trick.
+        // Some rules leave it out of a capot, which then scores only the bonus.
+        if self.is_terminal() && (!capot || self.rules.der_in_capot) {
+            points += LAST_TRICK_BONUS;
+        }
+
+        // Capot Bonus (252 points total = 162 + 90 bonus with the default rules)
+        if capot {
+            points += self.rules.capot_bonus;
         }
 
         self.points[winning_team] += points;
@@ -352,13 +489,6 @@ impl PlayingState {
         self.trick_size = 0;
         self.trick_starter = winner;
         self.current_player = winner;
-
-        self.tricks_won[winning_team] += 1;
-
-        // Capot Bonus (252 points total = 162 + 90 bonus)
-        if self.tricks_won[winning_team] == 8 {
-            self.points[winning_team] += 90;
-        }
     }
 
     pub fn is_terminal(&self) -> bool {
@@ -426,6 +556,15 @@ mod tests {
         // P0 leads again.
 
         // Just verify initial belote trigger
+
+        // Tables without announcements score no belote.
+        let mut state = PlayingState::new(HEARTS);
+        state.rules = RuleSet::from_preset("no-announcements").unwrap();
+        state.hands[0] = (1 << card(HEARTS, 6)) | (1 << card(HEARTS, 5));
+        assert_eq!(state.team_score_bounds(0).0, 0);
+        state.play_card(card(HEARTS, 6));
+        assert!(!state.belote_scored[0]);
+        assert_eq!(state.points[0], 0);
     }
 
     #[test]
@@ -464,6 +603,41 @@ mod tests {
         assert!(state.points[0] >= 111);
     }
 
+    #[test]
+    fn test_capot_rules() {
+        // NS already won seven tricks and 141 card points; South cashes the ace.
+        let last_trick = |rules: RuleSet, ns_tricks: u8| {
+            let mut state = PlayingState::new(HEARTS);
+            state.set_rules(rules);
+            state.tricks_won = [ns_tricks, 7 - ns_tricks];
+            state.points = [141, 0];
+            state.hands = [
+                1 << card(HEARTS, 7),
+                1 << card(CLUBS, 0),
+                1 << card(CLUBS, 1),
+                1 << card(CLUBS, 2),
+            ];
+            for c in [
+                card(HEARTS, 7),
+                card(CLUBS, 0),
+                card(CLUBS, 1),
+                card(CLUBS, 2),
+            ] {
+                state.play_card(c);
+            }
+            state.points[0]
+        };
+
+        // Default: cards, der and bonus make 252.
+        assert_eq!(last_trick(RuleSet::default(), 7), 252);
+        // Flat capot: 250, no der.
+        assert_eq!(last_trick(RuleSet::flat_capot(), 7), 250);
+        assert_eq!(last_trick(RuleSet::checked(100, true).unwrap(), 7), 262);
+        // Without a capot the der is scored whatever the rules.
+        assert_eq!(last_trick(RuleSet::default(), 6), 162);
+        assert_eq!(last_trick(RuleSet::flat_capot(), 6), 162);
+    }
+
     #[test]
     fn test_must_follow() {
         let mut state = PlayingState::new(HEARTS);
@@ -727,4 +901,65 @@ mod tests {
 
         assert_eq!(legal, c(HEARTS, 3) | c(HEARTS, 5));
     }
+
+    #[test]
+    fn test_contract_point_tables() {
+        for trump in [DIAMONDS, SPADES, HEARTS, CLUBS, NO_TRUMP, ALL_TRUMP] {
+            assert_eq!(cards_points(0xFFFF_FFFF, trump), TOTAL_CARD_POINTS);
+        }
+        assert_eq!(card_points(card(SPADES, RANK_A), NO_TRUMP), 19);
+        assert_eq!(card_points(card(SPADES, RANK_J), ALL_TRUMP), 14);
+        assert_eq!(card_points(card(SPADES, RANK_J), HEARTS), 2);
+
+        // No-Trump trick: A, 10, K, Q of Diamonds = 19 + 10 + 4 + 3 (+ dix de der).
+        let mut state = PlayingState::new(NO_TRUMP);
+        let trick = [RANK_A, RANK_10, RANK_K, RANK_Q];
+        for (p, &r) in trick.iter().enumerate() {
+            state.hands[p] = 1 << card(DIAMONDS, r);
+        }
+        for &r in trick.iter() {
+            state.play_card(card(DIAMONDS, r));
+        }
+        assert_eq!(state.points[0], 36 + LAST_TRICK_BONUS);
+    }
+
+    #[test]
+    fn test_score_bounds() {
+        // Seat p holds the cards whose index is p modulo 4, except that South
+        // swaps 7D and JD for the King (North's) and Queen (West's) of Hearts.
+        let mut hands: [u32; 4] =
+            std::array::from_fn(|p| (0..32).filter(|c| c % 4 == p).fold(0, |m, c| m | 1 << c));
+        let (k, q) = (card(HEARTS, RANK_K), card(HEARTS, RANK_Q));
+        let (seven, jack) = (card(DIAMONDS, RANK_7), card(DIAMONDS, RANK_J));
+        hands[2] ^= 1 << k | 1 << seven;
+        hands[1] ^= 1 << q | 1 << jack;
+        hands[0] ^= 1 << k | 1 << q | 1 << seven | 1 << jack;
+        let mut state = PlayingState::new(HEARTS);
+        state.hands = hands;
+
+        assert_eq!(state.score_bounds()[0], (20, 20 + 152 + 10 + 90));
+        assert_eq!(state.contract_decided(80, 0), None);
+        assert_eq!(state.contract_decided(20, 2), Some(true));
+
+        let mut history = vec![state];
+        while !state.is_terminal() {
+            state.play_card(state.get_legal_moves().trailing_zeros() as u8);
+            let mut recounted = state;
+            recounted.recount_points_in_play();
+            assert_eq!(state.points_in_play, recounted.points_in_play);
+            history.push(state);
+        }
+        for team in 0..2 {
+            let final_points = state.points[team];
+            assert_eq!(state.team_score_bounds(team), (final_points, final_points));
+            for (before, after) in history.iter().zip(&history[1..]) {
+                let (lo, hi) = before.team_score_bounds(team);
+                assert!(lo <= final_points && final_points <= hi);
+                let (next_lo, next_hi) = after.team_score_bounds(team);
+                assert!(lo <= next_lo && next_hi <= hi);
+            }
+        }
+        let made = state.points[0] >= 80;
+        assert_eq!(state.contract_decided(80, 2), Some(made));
+    }
 }
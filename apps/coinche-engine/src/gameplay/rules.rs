@@ -0,0 +1,232 @@
+//! Scoring conventions that vary between tables. The default is the usual
+//! contree count: a capot scores its cards, the dix de der and a 90 point bonus
+//! (252). Some tables score a capot as a flat 250 instead, without the der. Tables
+//! also differ in whether the belote is announced, whether deal scores are rounded
+//! to the ten and whether a coinche may be surcoinched.
+//!
+//! Named presets bundle the conventions of common tables, so integrators pick a
+//! table by name rather than setting every knob: see `PRESETS`.
+
+use pyo3::prelude::*;
+
+/// Capot bonus of the default rules (152 card points + 10 de der + 90 = 252).
+pub const DEFAULT_CAPOT_BONUS: u16 = 90;
+
+/// Capot bonus of a flat 250 capot (152 card points + 98, no der).
+pub const FLAT_CAPOT_BONUS: u16 = 98;
+
+/// Largest capot bonus: a team's points (the 162 of the cards and the der, the
+/// belote and the bonus) stay within a u16, with room for the der the score bounds
+/// add on top.
+pub const MAX_CAPOT_BONUS: u16 = u16::MAX - 162 - 20 - 10;
+
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RuleSet {
+    /// Points added to the card points of a team winning all eight tricks.
+    #[pyo3(get)]
+    pub capot_bonus: u16,
+    /// Whether a capot still scores the dix de der on top of the bonus.
+    #[pyo3(get)]
+    pub der_in_capot: bool,
+    /// Whether the King and Queen of trumps in one hand score the 20 point belote.
+    #[pyo3(get)]
+    pub belote: bool,
+    /// Whether deal scores are rounded to the nearest ten (a 5 rounds up).
+    #[pyo3(get)]
+    pub round_scores: bool,
+    /// Whether the declarers may surcoinche a coinche.
+    #[pyo3(get)]
+    pub surcoinche: bool,
+}
+
+/// Default rules, usable in constants.
+const CLASSIC: RuleSet = RuleSet {
+    capot_bonus: DEFAULT_CAPOT_BONUS,
+    der_in_capot: true,
+    belote: true,
+    round_scores: false,
+    surcoinche: true,
+};
+
+/// Named rule sets: (name, description, rules). Names are matched ignoring case.
+pub const PRESETS: [(&str, &str, RuleSet); 4] = [
+    (
+        "classic",
+        "Usual contree count: capot 252 with the der",
+        CLASSIC,
+    ),
+    (
+        "ffb-tournament",
+        "Federation tournament count: capot a flat 250, no der",
+        RuleSet {
+            capot_bonus: FLAT_CAPOT_BONUS,
+            der_in_capot: false,
+            ..CLASSIC
+        },
+    ),
+    (
+        "classic-cafe",
+        "Cafe table: scores rounded to the ten, no surcoinche",
+        RuleSet {
+            round_scores: true,
+            surcoinche: false,
+            ..CLASSIC
+        },
+    ),
+    (
+        "no-announcements",
+        "Usual count without the belote",
+        RuleSet {
+            belote: false,
+            ..CLASSIC
+        },
+    ),
+];
+
+impl RuleSet {
+    /// Default rules with a capot worth `capot_bonus` on top of its points, at
+    /// most `MAX_CAPOT_BONUS`.
+    pub fn checked(capot_bonus: u16, der_in_capot: bool) -> Result<Self, String> {
+        RuleSet {
+            capot_bonus,
+            der_in_capot,
+            ..CLASSIC
+        }
+        .validated()
+    }
+
+    /// These rules, or an error when the capot bonus is above `MAX_CAPOT_BONUS`.
+    pub fn validated(self) -> Result<Self, String> {
+        if self.capot_bonus > MAX_CAPOT_BONUS {
+            return Err(format!(
+                "capot_bonus {} is above the maximum of {}",
+                self.capot_bonus, MAX_CAPOT_BONUS
+            ));
+        }
+        Ok(self)
+    }
+
+    /// Deal score `points` as the table writes it down.
+    pub fn round_score(&self, points: u32) -> u32 {
+        if self.round_scores {
+            (points + 5) / 10 * 10
+        } else {
+            points
+        }
+    }
+
+    /// Rules of the preset `name`, see `PRESETS`.
+    pub fn from_preset(name: &str) -> Result<Self, String> {
+        PRESETS
+            .iter()
+            .find(|(preset, _, _)| preset.eq_ignore_ascii_case(name))
+            .map(|&(_, _, rules)| rules)
+            .ok_or_else(|| {
+                let names: Vec<&str> = PRESETS.iter().map(|p| p.0).collect();
+                format!(
+                    "Unknown rule preset '{}' (expected one of: {})",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        CLASSIC
+    }
+}
+
+#[pymethods]
+impl RuleSet {
+    #[new]
+    #[pyo3(signature = (capot_bonus=DEFAULT_CAPOT_BONUS, der_in_capot=true, belote=true, round_scores=false, surcoinche=true))]
+    pub fn new(
+        capot_bonus: u16,
+        der_in_capot: bool,
+        belote: bool,
+        round_scores: bool,
+        surcoinche: bool,
+    ) -> PyResult<Self> {
+        RuleSet {
+            capot_bonus,
+            der_in_capot,
+            belote,
+            round_scores,
+            surcoinche,
+        }
+        .validated()
+        .map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// Capot worth a flat 250: 152 card points and a 98 point bonus, no der.
+    #[staticmethod]
+    pub fn flat_capot() -> Self {
+        RuleSet {
+            capot_bonus: FLAT_CAPOT_BONUS,
+            der_in_capot: false,
+            ..CLASSIC
+        }
+    }
+
+    /// Rules of a named preset, e.g. `RuleSet.preset("ffb-tournament")`.
+    #[staticmethod]
+    pub fn preset(name: &str) -> PyResult<Self> {
+        RuleSet::from_preset(name).map_err(pyo3::exceptions::PyValueError::new_err)
+    }
+
+    /// Names and descriptions of the presets.
+    #[staticmethod]
+    pub fn presets() -> Vec<(&'static str, &'static str)> {
+        PRESETS
+            .iter()
+            .map(|&(name, desc, _)| (name, desc))
+            .collect()
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "RuleSet(capot_bonus={}, der_in_capot={}, belote={}, round_scores={}, surcoinche={})",
+            self.capot_bonus, self.der_in_capot, self.belote, self.round_scores, self.surcoinche
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        assert_eq!(RuleSet::from_preset("classic"), Ok(RuleSet::default()));
+        assert_eq!(
+            RuleSet::from_preset("FFB-Tournament"),
+            Ok(RuleSet::flat_capot())
+        );
+        assert!(RuleSet::from_preset("beach")
+            .unwrap_err()
+            .contains("classic, ffb-tournament, classic-cafe, no-announcements"));
+        let cafe = RuleSet::from_preset("classic-cafe").unwrap();
+        assert!(cafe.round_scores && !cafe.surcoinche && cafe.belote);
+        assert!(!RuleSet::from_preset("no-announcements").unwrap().belote);
+    }
+
+    #[test]
+    fn test_round_score() {
+        let cafe = RuleSet::from_preset("classic-cafe").unwrap();
+        assert_eq!(cafe.round_score(84), 80);
+        assert_eq!(cafe.round_score(85), 90);
+        assert_eq!(cafe.round_score(250), 250);
+        assert_eq!(RuleSet::default().round_score(84), 84);
+    }
+
+    #[test]
+    fn test_capot_bonus_range() {
+        assert!(RuleSet::checked(MAX_CAPOT_BONUS, true).is_ok());
+        assert!(RuleSet::checked(MAX_CAPOT_BONUS + 1, true)
+            .unwrap_err()
+            .contains("maximum"));
+    }
+}
@@ -0,0 +1,199 @@
+//! Rules conformance scenarios: deals, auctions and plays written in YAML under
+//! `scenarios/`, replayed through `CoincheMatch` with the expected legal moves
+//! and scores checked along the way (format in `scenarios/README.md`).
+
+use crate::gameplay::bidding::AuctionAction;
+use crate::gameplay::manager::{CoincheMatch, Phase};
+use crate::gameplay::notation::{card_names, parse_call, parse_card};
+use serde::Deserialize;
+use std::path::Path;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default)]
+    pub dealer: u8,
+    pub deal: Deal,
+    pub auction: Vec<String>,
+    #[serde(default)]
+    pub plays: Vec<Step>,
+    /// Substring of the error the whole position must be rejected with.
+    pub error: Option<String>,
+    pub result: Option<ExpectedResult>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Deal {
+    pub south: Vec<String>,
+    pub west: Vec<String>,
+    pub north: Vec<String>,
+    pub east: Vec<String>,
+}
+
+/// One card played, with optional checks on the position before and after it.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Step {
+    /// Exact legal moves of the seat to move, before the play.
+    pub legal: Option<Vec<String>>,
+    /// Cards the seat to move holds but may not play (revoke, under-trump...).
+    pub rejects: Option<Vec<String>>,
+    pub play: Option<String>,
+    /// Card points [NS, EW] after the play, belote included.
+    pub points: Option<[u16; 2]>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExpectedResult {
+    pub points: [i16; 2],
+    pub contract_made: bool,
+}
+
+fn parse_cards(names: &[String]) -> Result<u32, String> {
+    names
+        .iter()
+        .try_fold(0u32, |mask, n| Ok(mask | 1 << parse_card(n)?))
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        serde_yaml::from_str(&text).map_err(|e| e.to_string())
+    }
+
+    /// Replays the scenario and checks every expectation, stopping at the first
+    /// one that does not hold.
+    pub fn run(&self) -> Result<(), String> {
+        let deal = [
+            parse_cards(&self.deal.south)?,
+            parse_cards(&self.deal.west)?,
+            parse_cards(&self.deal.north)?,
+            parse_cards(&self.deal.east)?,
+        ];
+        let auction = self
+            .auction
+            .iter()
+            .map(|c| parse_call(c))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut played = Vec::new();
+        let mut m = self.position(deal, &auction, &played);
+        for (i, step) in self.plays.iter().enumerate() {
+            let state = match m?.phase {
+                Phase::Playing(state) => state,
+                _ => return Err(format!("step {}: the deal is not being played", i)),
+            };
+            let legal = state.get_legal_moves();
+            let hand = state.hands[state.current_player as usize];
+
+            if let Some(expected) = &step.legal {
+                if legal != parse_cards(expected)? {
+                    return Err(format!(
+                        "step {}: legal moves {:?}, expected {:?}",
+                        i,
+                        card_names(legal),
+                        expected
+                    ));
+                }
+            }
+            for name in step.rejects.iter().flatten() {
+                let card = parse_card(name)?;
+                if hand & (1 << card) == 0 {
+                    return Err(format!("step {}: {} is not in hand", i, name));
+                }
+                let mut attempt = played.clone();
+                attempt.push(card);
+                if self.position(deal, &auction, &attempt).is_ok() {
+                    return Err(format!("step {}: {} was accepted", i, name));
+                }
+            }
+
+            let Some(name) = &step.play else {
+                m = self.position(deal, &auction, &played);
+                continue;
+            };
+            played.push(parse_card(name)?);
+            m = self.position(deal, &auction, &played);
+            if let (Some(points), Ok(next)) = (step.points, &m) {
+                let actual = match next.phase {
+                    Phase::Playing(ref s) => s.points,
+                    Phase::Finished(ref r) => [r.points_ns as u16, r.points_ew as u16],
+                    Phase::Bidding(_) => [0, 0],
+                };
+                if actual != points {
+                    return Err(format!(
+                        "step {} ({}): points {:?}, expected {:?}",
+                        i, name, actual, points
+                    ));
+                }
+            }
+        }
+
+        match (&self.error, m) {
+            (Some(expected), Err(e)) if e.contains(expected.as_str()) => Ok(()),
+            (Some(expected), Err(e)) => Err(format!("error '{}', expected '{}'", e, expected)),
+            (Some(expected), Ok(_)) => Err(format!("accepted, expected error '{}'", expected)),
+            (None, Err(e)) => Err(e),
+            (None, Ok(m)) => self.check_result(&m),
+        }
+    }
+
+    fn position(
+        &self,
+        deal: [u32; 4],
+        auction: &[AuctionAction],
+        played: &[u8],
+    ) -> Result<CoincheMatch, String> {
+        let mask = played.iter().fold(0u32, |m, &c| m | 1 << c);
+        let hands = deal.map(|h| h & !mask);
+        CoincheMatch::from_position(self.dealer, hands, auction, played)
+    }
+
+    fn check_result(&self, m: &CoincheMatch) -> Result<(), String> {
+        let Some(expected) = &self.result else {
+            return Ok(());
+        };
+        let Phase::Finished(ref result) = m.phase else {
+            return Err("result expected but the deal is not over".to_string());
+        };
+        let actual = [result.points_ns, result.points_ew];
+        if actual != expected.points || result.contract_made != expected.contract_made {
+            return Err(format!(
+                "result {:?} (made: {}), expected {:?} (made: {})",
+                actual, result.contract_made, expected.points, expected.contract_made
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conformance_scenarios() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("scenarios");
+        let mut files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .filter(|p| p.extension().is_some_and(|e| e == "yaml"))
+            .collect();
+        files.sort();
+        assert!(!files.is_empty(), "no scenario in {}", dir.display());
+
+        let failures: Vec<String> = files
+            .iter()
+            .filter_map(|f| {
+                Scenario::load(f)
+                    .and_then(|s| s.run())
+                    .err()
+                    .map(|e| format!("{}: {}", f.display(), e))
+            })
+            .collect();
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
@@ -0,0 +1,229 @@
+//! One-line canonical text of a `PlayingState`, for bug reports: errors raised to
+//! Python about a position carry it, and `load_state` rebuilds the exact state.
+//!
+//! Fields are `key=value` pairs separated by spaces, in this order:
+//!
+//! ```text
+//! trump=H turn=N lead=W hands=7D,8D/9S,AH/-/KC trick=W:AH tricks=3,2 points=40,22
+//!     belote=0,1 last=S:7S,W:8S,N:9S,E:10S>N rules=90,true,true,false,true
+//! ```
+//!
+//! Hands are in seat order S/W/N/E ("-" when empty), trick and last trick in play
+//! order from their leader, `rules` is the capot bonus, whether a capot scores the
+//! der, then whether the belote, rounded scores and surcoinche are played (these
+//! three default to the usual rules when left out). Cards played earlier are those in no hand and on no table. Only
+//! `trump` and `hands` are required by `load_state`; other fields default to a
+//! fresh deal.
+
+use crate::gameplay::notation::{
+    card_name, card_names, parse_card, parse_seat, parse_trump, seat_name, trump_name,
+};
+use crate::gameplay::playing::PlayingState;
+use crate::gameplay::rules::RuleSet;
+use pyo3::exceptions::PyValueError;
+use pyo3::PyErr;
+use std::fmt::Display;
+
+/// Canonical one-line text of `state`, see the module doc.
+pub fn dump_state(state: &PlayingState) -> String {
+    let hands: Vec<String> = state.hands.iter().map(|&h| cards_text(h)).collect();
+    let trick = trick_text(&state.current_trick, state.trick_starter);
+    let last = match state.last_trick_winner {
+        Some(winner) => format!(
+            "{}>{}",
+            trick_text(&state.last_trick, state.last_trick_starter),
+            seat_name(winner)
+        ),
+        None => "-".to_string(),
+    };
+    format!(
+        "trump={} turn={} lead={} hands={} trick={} tricks={},{} points={},{} belote={},{} last={} rules={},{},{},{},{}",
+        trump_name(state.trump),
+        seat_name(state.current_player),
+        seat_name(state.trick_starter),
+        hands.join("/"),
+        trick,
+        state.tricks_won[0],
+        state.tricks_won[1],
+        state.points[0],
+        state.points[1],
+        state.belote_scored[0] as u8,
+        state.belote_scored[1] as u8,
+        last,
+        state.rules.capot_bonus,
+        state.rules.der_in_capot,
+        state.rules.belote,
+        state.rules.round_scores,
+        state.rules.surcoinche,
+    )
+}
+
+/// State of a `dump_state` text.
+pub fn load_state(text: &str) -> Result<PlayingState, String> {
+    let mut fields = Vec::new();
+    for field in text.split_whitespace() {
+        let (key, value) = field
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid field '{}'", field))?;
+        fields.push((key, value));
+    }
+    let get = |key: &str| fields.iter().find(|(k, _)| *k == key).map(|&(_, v)| v);
+
+    let trump = parse_trump(get("trump").ok_or("Missing trump")?)?;
+    let mut state = PlayingState::new(trump);
+    let hands: Vec<&str> = get("hands").ok_or("Missing hands")?.split('/').collect();
+    if hands.len() != 4 {
+        return Err(format!("Expected 4 hands, got {}", hands.len()));
+    }
+    for (seat, hand) in hands.iter().enumerate() {
+        state.hands[seat] = parse_cards(hand)?;
+    }
+
+    for &(key, value) in &fields {
+        match key {
+            "trump" | "hands" => {}
+            "turn" => state.current_player = parse_seat(value)?,
+            "lead" => state.trick_starter = parse_seat(value)?,
+            "trick" => {
+                let (trick, _) = parse_trick(value)?;
+                state.current_trick = trick;
+                state.trick_size = trick.iter().filter(|&&c| c != 0xFF).count() as u8;
+            }
+            "tricks" => state.tricks_won = parse_pair(value)?,
+            "points" => state.points = parse_pair(value)?,
+            "belote" => state.belote_scored = parse_pair::<u8>(value)?.map(|b| b != 0),
+            "last" if value != "-" => {
+                let (trick, winner) = value
+                    .split_once('>')
+                    .ok_or_else(|| format!("Invalid last trick '{}'", value))?;
+                let (cards, starter) = parse_trick(trick)?;
+                state.last_trick = cards;
+                state.last_trick_starter = starter.unwrap_or(0);
+                state.last_trick_winner = Some(parse_seat(winner)?);
+            }
+            "last" => {}
+            "rules" => state.rules = parse_rules(value)?,
+            _ => return Err(format!("Unknown field '{}'", key)),
+        }
+    }
+    state.recount_points_in_play();
+    Ok(state)
+}
+
+/// Python error about `state`: `message` followed by the state's dump.
+pub fn state_error(state: &PlayingState, message: impl Display) -> PyErr {
+    PyValueError::new_err(format!("{} [state: {}]", message, dump_state(state)))
+}
+
+fn cards_text(cards: u32) -> String {
+    if cards == 0 {
+        return "-".to_string();
+    }
+    card_names(cards).join(",")
+}
+
+fn parse_cards(text: &str) -> Result<u32, String> {
+    if text == "-" {
+        return Ok(0);
+    }
+    text.split(',')
+        .try_fold(0u32, |mask, name| Ok(mask | 1 << parse_card(name)?))
+}
+
+/// Cards of a seat-indexed trick in play order from `starter`, as "W:AH,N:7H".
+fn trick_text(trick: &[u8; 4], starter: u8) -> String {
+    let plays: Vec<String> = (0..4)
+        .map(|i| (starter + i) % 4)
+        .filter(|&seat| trick[seat as usize] < 32)
+        .map(|seat| format!("{}:{}", seat_name(seat), card_name(trick[seat as usize])))
+        .collect();
+    if plays.is_empty() {
+        "-".to_string()
+    } else {
+        plays.join(",")
+    }
+}
+
+/// Seat-indexed trick of a `trick_text`, and the seat that led it.
+fn parse_trick(text: &str) -> Result<([u8; 4], Option<u8>), String> {
+    let mut trick = [0xFF; 4];
+    if text == "-" {
+        return Ok((trick, None));
+    }
+    let mut starter = None;
+    for play in text.split(',') {
+        let (seat, card) = play
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid play '{}'", play))?;
+        let seat = parse_seat(seat)?;
+        trick[seat as usize] = parse_card(card)?;
+        starter.get_or_insert(seat);
+    }
+    Ok((trick, starter))
+}
+
+fn parse_rules(text: &str) -> Result<RuleSet, String> {
+    let invalid = || format!("Invalid rules '{}'", text);
+    let fields: Vec<&str> = text.split(',').collect();
+    if fields.len() != 2 && fields.len() != 5 {
+        return Err(invalid());
+    }
+    let usual = RuleSet::default();
+    let flag = |i: usize, default: bool| -> Result<bool, String> {
+        match fields.get(i) {
+            Some(f) => f.parse().map_err(|_| invalid()),
+            None => Ok(default),
+        }
+    };
+    RuleSet {
+        capot_bonus: fields[0].parse().map_err(|_| invalid())?,
+        der_in_capot: flag(1, usual.der_in_capot)?,
+        belote: flag(2, usual.belote)?,
+        round_scores: flag(3, usual.round_scores)?,
+        surcoinche: flag(4, usual.surcoinche)?,
+    }
+    .validated()
+}
+
+fn parse_pair<T: std::str::FromStr>(text: &str) -> Result<[T; 2], String> {
+    let invalid = || format!("Invalid pair '{}'", text);
+    let (a, b) = text.split_once(',').ok_or_else(invalid)?;
+    Ok([
+        a.parse().map_err(|_| invalid())?,
+        b.parse().map_err(|_| invalid())?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_gen::common::sample_rng;
+    use crate::solver::random_ending;
+
+    #[test]
+    fn test_dump_and_load_round_trip() {
+        let mut rng = sample_rng(Some(2), 0);
+        for cards_left in [32, 27, 14, 5, 0] {
+            let state = random_ending(cards_left, &mut rng);
+            let text = dump_state(&state);
+            assert!(!text.contains('\n'));
+            let loaded = load_state(&text).unwrap();
+            assert_eq!(dump_state(&loaded), text);
+            assert_eq!(format!("{:?}", loaded), format!("{:?}", state));
+        }
+
+        let state = load_state("trump=AT hands=7D,AH/-/-/KC").unwrap();
+        assert_eq!(state.hands, [1 | 1 << 23, 0, 0, 1 << 30]);
+        assert_eq!(state.trick_size, 0);
+        assert!(load_state("hands=7D/-/-/-").is_err());
+        assert!(load_state("trump=H hands=7D/-/-").is_err());
+        assert!(load_state("trump=H hands=7D/-/-/- colour=blue").is_err());
+
+        // Rules written before the policies load with the usual ones.
+        let state = load_state("trump=H hands=7D/-/-/- rules=98,false").unwrap();
+        assert_eq!(state.rules, RuleSet::flat_capot());
+        let state = load_state("trump=H hands=7D/-/-/- rules=90,true,false,false,true").unwrap();
+        assert!(!state.rules.belote);
+        assert!(load_state("trump=H hands=7D/-/-/- rules=90,true,false").is_err());
+    }
+}
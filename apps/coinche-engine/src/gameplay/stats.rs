@@ -0,0 +1,247 @@
+//! Aggregates over finished matches, for arenas and servers tracking how bots
+//! bid and play: contract success by value and trump, points as declarer and
+//! defender, coinches and belotes.
+//!
+//! Deals ended by a timeout or a revoke only count as forfeits: their points say
+//! nothing about the play.
+
+use crate::gameplay::manager::MatchResult;
+use pyo3::prelude::*;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Tally {
+    played: u32,
+    made: u32,
+}
+
+impl Tally {
+    fn add(&mut self, made: bool) {
+        self.played += 1;
+        self.made += made as u32;
+    }
+
+    fn merge(&mut self, other: &Tally) {
+        self.played += other.played;
+        self.made += other.made;
+    }
+
+    fn rate(&self) -> Option<f64> {
+        (self.played > 0).then(|| self.made as f64 / self.played as f64)
+    }
+}
+
+fn ratio(count: impl Into<f64>, total: u32) -> Option<f64> {
+    (total > 0).then(|| count.into() / total as f64)
+}
+
+#[pyclass]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MatchStats {
+    /// Results added, forfeits and passed deals included.
+    #[pyo3(get)]
+    pub deals: u32,
+    /// Deals where all four players passed.
+    #[pyo3(get)]
+    pub passed: u32,
+    /// Deals ended by a timeout or a revoke.
+    #[pyo3(get)]
+    pub forfeits: u32,
+    contracts: Tally,
+    by_value: BTreeMap<u8, Tally>,
+    by_trump: BTreeMap<u8, Tally>,
+    /// Coinched or surcoinched contracts; `made` counts those the declarer made.
+    coinched: Tally,
+    declarer_points: i64,
+    defender_points: i64,
+    belotes: u32,
+}
+
+impl MatchStats {
+    /// Success rates of `tallies`, for the keys with at least one contract.
+    fn rates(tallies: &BTreeMap<u8, Tally>) -> BTreeMap<u8, f64> {
+        tallies
+            .iter()
+            .filter_map(|(&k, t)| t.rate().map(|r| (k, r)))
+            .collect()
+    }
+}
+
+#[pymethods]
+impl MatchStats {
+    #[new]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a finished match.
+    pub fn add(&mut self, result: &MatchResult) {
+        self.deals += 1;
+        if result.timeout_seat.is_some() || result.revoke_seat.is_some() {
+            self.forfeits += 1;
+            return;
+        }
+        let (Some(contract), Some(owner)) = (result.contract, result.contract_owner) else {
+            self.passed += 1;
+            return;
+        };
+
+        let made = result.contract_made;
+        self.contracts.add(made);
+        self.by_value.entry(contract.value).or_default().add(made);
+        self.by_trump.entry(contract.trump).or_default().add(made);
+        if result.coinche_level > 0 {
+            self.coinched.add(made);
+        }
+        let (declarer, defender) = if owner % 2 == 0 {
+            (result.points_ns, result.points_ew)
+        } else {
+            (result.points_ew, result.points_ns)
+        };
+        self.declarer_points += declarer as i64;
+        self.defender_points += defender as i64;
+        self.belotes += result.belote_team.is_some() as u32;
+    }
+
+    /// Adds every match counted by `other`.
+    pub fn merge(&mut self, other: &MatchStats) {
+        self.deals += other.deals;
+        self.passed += other.passed;
+        self.forfeits += other.forfeits;
+        self.contracts.merge(&other.contracts);
+        for (&k, t) in &other.by_value {
+            self.by_value.entry(k).or_default().merge(t);
+        }
+        for (&k, t) in &other.by_trump {
+            self.by_trump.entry(k).or_default().merge(t);
+        }
+        self.coinched.merge(&other.coinched);
+        self.declarer_points += other.declarer_points;
+        self.defender_points += other.defender_points;
+        self.belotes += other.belotes;
+    }
+
+    /// Contracts played to the end.
+    #[getter]
+    pub fn contracts(&self) -> u32 {
+        self.contracts.played
+    }
+
+    #[getter]
+    pub fn contracts_made(&self) -> u32 {
+        self.contracts.made
+    }
+
+    /// Share of the contracts made (None before any contract).
+    pub fn success_rate(&self) -> Option<f64> {
+        self.contracts.rate()
+    }
+
+    /// Success rate per contract value.
+    pub fn success_rate_by_value(&self) -> BTreeMap<u8, f64> {
+        Self::rates(&self.by_value)
+    }
+
+    /// Success rate per trump (0-3 suits, 4 = no trump, 5 = all trump).
+    pub fn success_rate_by_trump(&self) -> BTreeMap<u8, f64> {
+        Self::rates(&self.by_trump)
+    }
+
+    /// Mean final points of the declaring team.
+    pub fn avg_declarer_points(&self) -> Option<f64> {
+        ratio(self.declarer_points as f64, self.contracts.played)
+    }
+
+    /// Mean final points of the defending team.
+    pub fn avg_defender_points(&self) -> Option<f64> {
+        ratio(self.defender_points as f64, self.contracts.played)
+    }
+
+    /// Share of the contracts that were coinched (or surcoinched).
+    pub fn coinche_rate(&self) -> Option<f64> {
+        ratio(self.coinched.played, self.contracts.played)
+    }
+
+    /// Share of the coinched contracts that went down: how often a coinche pays.
+    pub fn coinche_success_rate(&self) -> Option<f64> {
+        self.coinched.rate().map(|made| 1.0 - made)
+    }
+
+    /// Share of the contracts played with a belote announced.
+    pub fn belote_rate(&self) -> Option<f64> {
+        ratio(self.belotes, self.contracts.played)
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "MatchStats(deals={}, contracts={}, made={}, passed={}, forfeits={})",
+            self.deals, self.contracts.played, self.contracts.made, self.passed, self.forfeits
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::bidding::Bid;
+    use crate::gameplay::playing::{HEARTS, NO_TRUMP};
+
+    fn result(
+        contract: Option<(u8, u8)>,
+        owner: u8,
+        points: (i16, i16),
+        made: bool,
+    ) -> MatchResult {
+        MatchResult {
+            contract: contract.map(|(value, trump)| Bid::new(value, trump)),
+            contract_owner: contract.map(|_| owner),
+            points_ns: points.0,
+            points_ew: points.1,
+            contract_made: made,
+            coinche_level: 0,
+            belote_team: None,
+            timeout_seat: None,
+            revoke_seat: None,
+            revoke_card: None,
+            play_stats: None,
+        }
+    }
+
+    #[test]
+    fn test_match_stats() {
+        let mut stats = MatchStats::new();
+        assert_eq!(stats.success_rate(), None);
+
+        stats.add(&result(Some((80, HEARTS)), 0, (102, 60), true));
+        stats.add(&MatchResult {
+            coinche_level: 1,
+            belote_team: Some(1),
+            ..result(Some((100, HEARTS)), 1, (90, 72), false)
+        });
+        stats.add(&result(None, 0, (0, 0), false));
+        let mut other = MatchStats::new();
+        other.add(&MatchResult {
+            timeout_seat: Some(2),
+            ..result(Some((80, NO_TRUMP)), 0, (0, 162), false)
+        });
+        other.add(&result(Some((80, NO_TRUMP)), 3, (30, 132), true));
+        stats.merge(&other);
+
+        assert_eq!((stats.deals, stats.passed, stats.forfeits), (5, 1, 1));
+        assert_eq!((stats.contracts(), stats.contracts_made()), (3, 2));
+        assert_eq!(
+            stats.success_rate_by_value(),
+            BTreeMap::from([(80, 1.0), (100, 0.0)])
+        );
+        assert_eq!(
+            stats.success_rate_by_trump(),
+            BTreeMap::from([(HEARTS, 0.5), (NO_TRUMP, 1.0)])
+        );
+        // Declarers: 102, 72, 132. Defenders: 60, 90, 30.
+        assert_eq!(stats.avg_declarer_points(), Some(102.0));
+        assert_eq!(stats.avg_defender_points(), Some(60.0));
+        assert_eq!(stats.coinche_rate(), Some(1.0 / 3.0));
+        assert_eq!(stats.coinche_success_rate(), Some(1.0));
+        assert_eq!(stats.belote_rate(), Some(1.0 / 3.0));
+    }
+}
@@ -0,0 +1,201 @@
+//! Parametric auction baseline: every call follows from `contract_strength`
+//! thresholds, so a bidder is fully described by its three parameters and plays
+//! the same auction for the same deal. Meant as a fixed opponent for arenas and
+//! to simulate auctions in data generation.
+//!
+//! Strengths are on the `contract_strength` scale, where a hand of strength `s`
+//! supports contracts up to `70 + 2 * s` (as the heuristic bot's calls).
+
+use crate::data_gen::bidding::contract_strength;
+use crate::gameplay::bidding::{beats, AuctionAction, Bid, BiddingState};
+use crate::gameplay::bot::supported_value;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+#[pyclass]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ThresholdBidder {
+    /// Strength in its best trump a hand needs to bid when its partner does not
+    /// own the contract, opening or overcalling up to the value it supports.
+    #[pyo3(get, set)]
+    pub open_strength: i32,
+    /// Strength in the partner's trump a hand needs to raise its contract by 10,
+    /// once and only if it has not bid itself.
+    #[pyo3(get, set)]
+    pub raise_support: i32,
+    /// Coinche an opponents' contract when its value plus what the hand supports
+    /// against it exceeds the 162 points of the deal by this much.
+    #[pyo3(get, set)]
+    pub coinche_margin: i32,
+}
+
+impl Default for ThresholdBidder {
+    fn default() -> Self {
+        ThresholdBidder {
+            open_strength: 20,
+            raise_support: 15,
+            coinche_margin: 20,
+        }
+    }
+}
+
+impl ThresholdBidder {
+    /// Call of the seat to move in `state`, holding `hand`.
+    pub fn choose(&self, state: &BiddingState, hand: u32) -> AuctionAction {
+        let seat = state.current_player;
+        if state.is_finished() || state.coinche_level > 0 {
+            return AuctionAction::Pass;
+        }
+        match (state.contract, state.contract_owner) {
+            (Some(_), Some(owner)) if owner == seat => AuctionAction::Pass,
+            (Some(contract), Some(owner)) if owner % 2 == seat % 2 => {
+                let support = contract_strength(hand, contract.trump);
+                let raised = Bid::new(contract.value + 10, contract.trump);
+                if support >= self.raise_support && raised.value <= 160 && !has_bid(state, seat) {
+                    AuctionAction::Bid(raised)
+                } else {
+                    AuctionAction::Pass
+                }
+            }
+            (Some(contract), Some(_)) => {
+                let defense = contract_strength(hand, contract.trump);
+                let overreach = contract.value as i32 + 2 * defense - 162;
+                if overreach >= self.coinche_margin {
+                    AuctionAction::Coinche
+                } else {
+                    self.bid(state, hand)
+                }
+            }
+            _ => self.bid(state, hand),
+        }
+    }
+
+    /// Lowest contract beating the current one in the trump `hand` is best at,
+    /// if strong enough and within the value it supports.
+    fn bid(&self, state: &BiddingState, hand: u32) -> AuctionAction {
+        let (strength, trump) = (0..6u8)
+            .map(|t| (contract_strength(hand, t), t))
+            .max_by_key(|&(s, t)| (s, std::cmp::Reverse(t)))
+            .unwrap();
+        if strength < self.open_strength {
+            return AuctionAction::Pass;
+        }
+        (80..=supported_value(strength))
+            .step_by(10)
+            .map(|v| Bid::new(v as u8, trump))
+            .find(|&b| beats(state.contract, b))
+            .map_or(AuctionAction::Pass, AuctionAction::Bid)
+    }
+
+    /// Whole auction of `hands` dealt by `dealer`, this bidder at every seat.
+    pub fn auction(&self, dealer: u8, hands: &[u32; 4]) -> Vec<AuctionAction> {
+        let mut state = BiddingState::new(dealer);
+        let mut calls = Vec::new();
+        while !state.is_finished() {
+            let call = self.choose(&state, hands[state.current_player as usize]);
+            call.apply(&mut state).expect("threshold calls are legal");
+            calls.push(call);
+        }
+        calls
+    }
+}
+
+/// Whether `seat` has bid in the auction so far. Bids are only looked for
+/// before any coinche, while the history has one entry per call.
+fn has_bid(state: &BiddingState, seat: u8) -> bool {
+    let calls = state.history.len();
+    state.history.iter().enumerate().any(|(i, bid)| {
+        bid.is_some() && (state.current_player as usize + 4 - (calls - i) % 4) % 4 == seat as usize
+    })
+}
+
+#[pymethods]
+impl ThresholdBidder {
+    #[new]
+    #[pyo3(signature = (open_strength=20, raise_support=15, coinche_margin=20))]
+    pub fn new(open_strength: i32, raise_support: i32, coinche_margin: i32) -> Self {
+        ThresholdBidder {
+            open_strength,
+            raise_support,
+            coinche_margin,
+        }
+    }
+
+    /// Call of the seat to move (None for pass, a Bid or "coinche").
+    #[pyo3(name = "choose")]
+    pub fn py_choose(&self, state: &BiddingState, hand: u32) -> AuctionAction {
+        self.choose(state, hand)
+    }
+
+    /// Every call of the auction of `hands` dealt by `dealer`, this bidder at
+    /// every seat.
+    #[pyo3(name = "auction")]
+    pub fn py_auction(&self, dealer: u8, hands: [u32; 4]) -> PyResult<Vec<AuctionAction>> {
+        if dealer >= 4 {
+            return Err(PyValueError::new_err(format!("Invalid dealer {}", dealer)));
+        }
+        Ok(self.auction(dealer, &hands))
+    }
+
+    pub fn __repr__(&self) -> String {
+        format!(
+            "ThresholdBidder(open_strength={}, raise_support={}, coinche_margin={})",
+            self.open_strength, self.raise_support, self.coinche_margin
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::notation::parse_card;
+    use crate::gameplay::playing::{HEARTS, SPADES};
+
+    fn cards(names: &[&str]) -> u32 {
+        names.iter().fold(0, |m, n| m | 1 << parse_card(n).unwrap())
+    }
+
+    #[test]
+    fn test_threshold_calls() {
+        let bidder = ThresholdBidder::default();
+        // S (65 in hearts) opens, N (22 in hearts: two side aces) raises once;
+        // W and E are below 20 in every contract.
+        let hands = [
+            cards(&["JH", "9H", "KH", "QH", "AS", "7D", "8D", "7C"]),
+            cards(&["7S", "8S", "9S", "QS", "8H", "9D", "QD", "QC"]),
+            cards(&["AH", "10H", "AD", "AC", "JS", "JD", "JC", "10D"]),
+            cards(&["10S", "KS", "7H", "KD", "8C", "9C", "10C", "KC"]),
+        ];
+        let pass = AuctionAction::Pass;
+        let calls = bidder.auction(3, &hands);
+        assert_eq!(
+            calls,
+            vec![
+                AuctionAction::Bid(Bid::new(80, HEARTS)),
+                pass,
+                AuctionAction::Bid(Bid::new(90, HEARTS)),
+                pass,
+                pass,
+                pass,
+            ]
+        );
+
+        // S does not raise back its partner's raise.
+        let mut state = BiddingState::new(3);
+        for &call in &calls[..4] {
+            call.apply(&mut state).unwrap();
+        }
+        assert!(has_bid(&state, 0) && !has_bid(&state, 1));
+        assert_eq!(bidder.choose(&state, hands[0]), pass);
+
+        // Over W's 150 hearts, N coinches: 150 + 2 * 22 - 162 = 32. With a higher
+        // margin it outbids in spades, its best trump (53).
+        let mut state = BiddingState::new(0);
+        state.apply_bid(Some(Bid::new(150, HEARTS))).unwrap();
+        assert_eq!(bidder.choose(&state, hands[2]), AuctionAction::Coinche);
+        assert_eq!(
+            ThresholdBidder::new(20, 15, 33).choose(&state, hands[2]),
+            AuctionAction::Bid(Bid::new(160, SPADES))
+        );
+    }
+}
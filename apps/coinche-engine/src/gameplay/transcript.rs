@@ -0,0 +1,349 @@
+//! Human-readable game records: the deal, the auction as a table, the play trick
+//! by trick with winners and the result, in the card and call notation of
+//! `notation`. `parse_transcript` rebuilds the match from a record and checks that
+//! replaying it gives back the same record. The rules line names the belote,
+//! rounding and surcoinche policies only where they differ from the default.
+//!
+//! ```text
+//! Dealer: E
+//! Rules: capot_bonus=90 der_in_capot=true
+//! Deal:
+//!   S: 7D 8D 9D 10D JD QD KD AD
+//!   ...
+//! Auction:
+//!   S           W           N           E
+//!   80 H        coinche     pass        pass
+//!   pass
+//! Contract: 80 H by S, coinched
+//! Play:
+//!    1. S: AD  W: 7S  N: 7H  E: 7C  -> S
+//!   ...
+//! Result: NS 82, EW 80, made
+//! ```
+
+use crate::gameplay::bidding::AuctionAction;
+use crate::gameplay::manager::{CoincheMatch, Phase};
+use crate::gameplay::notation::{
+    bid_name, call_name, card_name, card_names, parse_call, parse_card, parse_seat, seat_name,
+};
+use crate::gameplay::playing::PlayingState;
+use crate::gameplay::rules::RuleSet;
+
+/// Width of an auction table column.
+const CALL_WIDTH: usize = 12;
+
+/// Record of `m` so far; a match still in progress has no result line.
+pub fn to_transcript(m: &CoincheMatch) -> String {
+    let mut lines = vec![
+        format!("Dealer: {}", seat_name(m.dealer)),
+        rules_line(&m.rules),
+        "Deal:".to_string(),
+    ];
+    for seat in 0..4 {
+        lines.push(format!(
+            "  {}: {}",
+            seat_name(seat),
+            card_names(m.initial_hands[seat as usize]).join(" ")
+        ));
+    }
+
+    lines.push("Auction:".to_string());
+    let first = (m.dealer + 1) % 4;
+    let cells: Vec<String> = (0..first)
+        .map(|_| "-".to_string())
+        .chain(m.auction.iter().map(call_name))
+        .collect();
+    lines.push(auction_row(&["S", "W", "N", "E"].map(String::from)));
+    for row in cells.chunks(4) {
+        lines.push(auction_row(row));
+    }
+
+    if !matches!(m.phase, Phase::Bidding(_)) {
+        lines.push(match (m.contract, m.contract_owner) {
+            (Some(c), Some(owner)) => format!(
+                "Contract: {} by {}{}",
+                bid_name(&c),
+                seat_name(owner),
+                [", coinched", ", surcoinched"]
+                    .get(m.coinche_level.wrapping_sub(1) as usize)
+                    .unwrap_or(&"")
+            ),
+            _ => "Contract: none".to_string(),
+        });
+    }
+
+    if let Some(contract) = m.contract.filter(|_| !m.played_cards.is_empty()) {
+        lines.push("Play:".to_string());
+        let mut state = PlayingState::new(contract.trump);
+        state.hands = m.initial_hands;
+        state.rules = m.rules;
+        state.current_player = first;
+        state.trick_starter = first;
+        for (n, trick) in m.played_cards.chunks(4).enumerate() {
+            let mut line = format!("  {:>2}.", n + 1);
+            for &card in trick {
+                line += &format!(" {}: {} ", seat_name(state.current_player), card_name(card));
+                state.play_card(card);
+            }
+            if trick.len() == 4 {
+                line += &format!(" -> {}", seat_name(state.last_trick_winner.unwrap()));
+            }
+            lines.push(line.trim_end().to_string());
+        }
+    }
+
+    if let Phase::Finished(ref r) = m.phase {
+        let outcome = match (r.contract, r.contract_made) {
+            (None, _) => "passed",
+            (Some(_), true) => "made",
+            (Some(_), false) => "down",
+        };
+        let mut line = format!(
+            "Result: NS {}, EW {}, {}",
+            r.points_ns, r.points_ew, outcome
+        );
+        if let Some(seat) = r.timeout_seat {
+            line += &format!(", timeout {}", seat_name(seat));
+        }
+        if let (Some(seat), Some(card)) = (r.revoke_seat, r.revoke_card) {
+            line += &format!(", revoke {} {}", seat_name(seat), card_name(card));
+        }
+        lines.push(line);
+    }
+
+    lines.join("\n") + "\n"
+}
+
+fn rules_line(rules: &RuleSet) -> String {
+    let mut line = format!(
+        "Rules: capot_bonus={} der_in_capot={}",
+        rules.capot_bonus, rules.der_in_capot
+    );
+    let default = RuleSet::default();
+    for (key, value, usual) in [
+        ("belote", rules.belote, default.belote),
+        ("round_scores", rules.round_scores, default.round_scores),
+        ("surcoinche", rules.surcoinche, default.surcoinche),
+    ] {
+        if value != usual {
+            line += &format!(" {}={}", key, value);
+        }
+    }
+    line
+}
+
+fn auction_row(cells: &[String]) -> String {
+    let row: String = cells
+        .iter()
+        .map(|c| format!("{:<width$}", c, width = CALL_WIDTH))
+        .collect();
+    format!("  {}", row.trim_end())
+}
+
+/// Same text up to spacing and blank lines.
+fn normalize(text: &str) -> Vec<String> {
+    text.lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+/// Rebuilds the match recorded by `text` (see `to_transcript`). The deal,
+/// auction and cards are replayed with full validation, and the record must
+/// match its replay line for line: contract, trick winners and result included.
+pub fn parse_transcript(text: &str) -> Result<CoincheMatch, String> {
+    let mut dealer = None;
+    let mut rules = RuleSet::default();
+    let mut deal = [0u32; 4];
+    let mut auction: Vec<AuctionAction> = Vec::new();
+    let mut played: Vec<u8> = Vec::new();
+    let mut forfeit: Option<(u8, Option<u8>)> = None;
+
+    let mut section = "";
+    for (i, raw) in text.lines().enumerate() {
+        let line = raw.trim();
+        let err = |e: String| format!("Line {}: {}", i + 1, e);
+        if line.is_empty() {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once(':') {
+            let value = value.trim();
+            match key {
+                "Dealer" => {
+                    dealer = Some(parse_seat(value).map_err(err)?);
+                    continue;
+                }
+                "Rules" => {
+                    rules = parse_rules(value).map_err(err)?;
+                    continue;
+                }
+                "Deal" | "Auction" | "Play" => {
+                    section = key;
+                    continue;
+                }
+                "Contract" => continue,
+                "Result" => {
+                    forfeit = parse_forfeit(value).map_err(err)?;
+                    continue;
+                }
+                _ => {}
+            }
+        }
+        match section {
+            "Deal" => {
+                let (seat, cards) = line
+                    .split_once(':')
+                    .ok_or_else(|| err(format!("Invalid hand '{}'", line)))?;
+                let seat = parse_seat(seat).map_err(err)?;
+                for name in cards.split_whitespace() {
+                    deal[seat as usize] |= 1 << parse_card(name).map_err(err)?;
+                }
+            }
+            // Cells are separated by two spaces or more: bids hold a single one.
+            "Auction" if line.split_whitespace().collect::<String>() == "SWNE" => {}
+            "Auction" => {
+                for cell in line.split("  ").map(str::trim).filter(|c| !c.is_empty()) {
+                    if cell != "-" {
+                        auction.push(parse_call(cell).map_err(err)?);
+                    }
+                }
+            }
+            "Play" => {
+                let plays = line.split_once('.').map_or(line, |(_, p)| p);
+                let plays = plays.split("->").next().unwrap_or("");
+                for play in plays.split_whitespace().filter(|t| !t.ends_with(':')) {
+                    played.push(parse_card(play).map_err(err)?);
+                }
+            }
+            _ => return Err(err(format!("Unexpected line '{}'", line))),
+        }
+    }
+
+    let dealer = dealer.ok_or("Transcript has no dealer")?;
+    let mask = played.iter().fold(0u32, |m, &c| m | 1 << c);
+    let hands = deal.map(|h| h & !mask);
+    let mut m = CoincheMatch::from_position_with_rules(dealer, hands, &auction, &played, rules)?;
+    match forfeit {
+        Some((seat, Some(card))) => m.revoke(seat, card),
+        Some((seat, None)) => m.forfeit(seat),
+        None => {}
+    }
+    // The record has no times: the forfeit did not happen now.
+    if forfeit.is_some() {
+        if let Some(last) = m.log.last_mut() {
+            last.timestamp_ms = None;
+        }
+    }
+
+    let replayed = to_transcript(&m);
+    for (expected, actual) in normalize(text).iter().zip(normalize(&replayed).iter()) {
+        if expected != actual {
+            return Err(format!(
+                "Transcript does not match its replay: '{}', replayed as '{}'",
+                expected, actual
+            ));
+        }
+    }
+    if normalize(text).len() != normalize(&replayed).len() {
+        return Err("Transcript does not match its replay: different number of lines".into());
+    }
+    Ok(m)
+}
+
+fn parse_rules(value: &str) -> Result<RuleSet, String> {
+    let mut rules = RuleSet::default();
+    for field in value.split_whitespace() {
+        let invalid = || format!("Invalid rule '{}'", field);
+        match field.split_once('=').ok_or_else(invalid)? {
+            ("capot_bonus", v) => rules.capot_bonus = v.parse().map_err(|_| invalid())?,
+            ("der_in_capot", v) => rules.der_in_capot = v.parse().map_err(|_| invalid())?,
+            ("belote", v) => rules.belote = v.parse().map_err(|_| invalid())?,
+            ("round_scores", v) => rules.round_scores = v.parse().map_err(|_| invalid())?,
+            ("surcoinche", v) => rules.surcoinche = v.parse().map_err(|_| invalid())?,
+            _ => return Err(invalid()),
+        }
+    }
+    rules.validated()
+}
+
+/// Forfeit of a result line: (seat, None) for a timeout, (seat, card) for a revoke.
+fn parse_forfeit(value: &str) -> Result<Option<(u8, Option<u8>)>, String> {
+    for part in value.split(',').map(str::trim) {
+        let words: Vec<&str> = part.split_whitespace().collect();
+        match words[..] {
+            ["timeout", seat] => return Ok(Some((parse_seat(seat)?, None))),
+            ["revoke", seat, card] => {
+                return Ok(Some((parse_seat(seat)?, Some(parse_card(card)?))))
+            }
+            _ => {}
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::bidding::Bid;
+    use crate::gameplay::playing::HEARTS;
+
+    /// Seat `p` holds every card whose index is `p` modulo 4.
+    fn deal() -> [u32; 4] {
+        std::array::from_fn(|p| (0..32).filter(|c| c % 4 == p).fold(0, |m, c| m | 1 << c))
+    }
+
+    #[test]
+    fn test_transcript_roundtrip() {
+        let auction = [
+            AuctionAction::Bid(Bid::new(80, HEARTS)),
+            AuctionAction::Coinche,
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+            AuctionAction::Pass,
+        ];
+        let mut m = CoincheMatch::from_position(3, deal(), &auction, &[]).unwrap();
+        // Everyone plays their lowest legal card.
+        while let Phase::Playing(ref state) = m.phase {
+            let card = state.get_legal_moves().trailing_zeros() as u8;
+            m.play_card(card).unwrap();
+        }
+        let text = to_transcript(&m);
+        assert!(text.contains("Contract: 80 H by S, coinched"), "{}", text);
+        assert!(text.contains("\n   8. "), "{}", text);
+        assert!(text.contains("Result: NS "), "{}", text);
+
+        let parsed = parse_transcript(&text).unwrap();
+        assert_eq!(to_transcript(&parsed), text);
+
+        // A record that does not match its replay is rejected.
+        let tampered = text.replace("-> S", "-> W").replace("-> N", "-> W");
+        if tampered != text {
+            assert!(parse_transcript(&tampered).is_err());
+        }
+        let wrong_result = text
+            .replace(", made", ", down")
+            .replace(", down\n", ", made\n");
+        assert!(parse_transcript(&wrong_result).is_err());
+
+        // In progress, then a revoke.
+        let mut m = CoincheMatch::from_position(3, deal(), &auction, &[]).unwrap();
+        let text = to_transcript(&m);
+        assert!(!text.contains("Result"));
+        assert_eq!(parse_transcript(&text).unwrap().auction, auction.to_vec());
+        m.revoke(0, 3);
+        let text = to_transcript(&m);
+        assert!(text.ends_with(", revoke S 10D\n"), "{}", text);
+        assert_eq!(to_transcript(&parse_transcript(&text).unwrap()), text);
+
+        // Policies other than the default are recorded.
+        let rules = RuleSet::from_preset("no-announcements").unwrap();
+        let m = CoincheMatch::from_position_with_rules(3, deal(), &auction, &[], rules).unwrap();
+        let text = to_transcript(&m);
+        assert!(
+            text.contains("der_in_capot=true belote=false\n"),
+            "{}",
+            text
+        );
+        assert_eq!(parse_transcript(&text).unwrap().rules, rules);
+    }
+}
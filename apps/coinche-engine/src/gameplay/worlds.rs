@@ -0,0 +1,332 @@
+//! Worlds consistent with what the player to move sees: the deals of the cards
+//! it cannot see among the other seats, keeping their hand sizes and the suits
+//! they showed they lack. Shared by PIMC and the bots, which count, sample or
+//! enumerate them.
+//!
+//! Counting is exact: a table of the ways to deal the remaining hidden cards,
+//! given the room left in two of the hands (the third one takes the rest).
+//! Sampling draws every card's seat in proportion to the worlds that follow, so
+//! each consistent world is equally likely.
+
+use crate::gameplay::history::PlayRecord;
+use crate::gameplay::playing::PlayingState;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+/// Suit of a card index (8 cards per suit).
+fn suit_mask(suit: u8) -> u32 {
+    0xFF << (suit * 8)
+}
+
+/// Suits each seat showed it lacks in `plays`: a seat that did not follow the suit
+/// led to a trick has none left. `plays` are in play order, by trick.
+pub fn voids_from_plays(plays: &[PlayRecord]) -> [u8; 4] {
+    let mut voids = [0u8; 4];
+    for trick in plays.chunk_by(|a, b| a.trick == b.trick) {
+        let led = trick[0].card / 8;
+        for p in &trick[1..] {
+            if p.card / 8 != led {
+                voids[p.seat as usize % 4] |= 1 << led;
+            }
+        }
+    }
+    voids
+}
+
+/// Where the hidden cards of a position may be.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WorldConstraints {
+    /// The player to move, whose hand is known.
+    me: usize,
+    /// Cards held by the other seats.
+    hidden: u32,
+    /// Number of cards of every seat.
+    sizes: [u32; 4],
+    /// Cards every seat cannot hold.
+    excluded: [u32; 4],
+}
+
+impl WorldConstraints {
+    /// Worlds of `state` seen by its player to move: only the hand sizes are known.
+    pub fn new(state: &PlayingState) -> Self {
+        let me = state.current_player as usize;
+        let hidden = (0..4)
+            .filter(|&p| p != me)
+            .fold(0, |m, p| m | state.hands[p]);
+        WorldConstraints {
+            me,
+            hidden,
+            sizes: state.hands.map(u32::count_ones),
+            excluded: [0; 4],
+        }
+    }
+
+    /// Adds the suits known to be missing from each seat (`voids[seat]` has bit
+    /// `suit` set), e.g. from `voids_from_plays`.
+    pub fn with_voids(mut self, voids: [u8; 4]) -> Self {
+        for (p, &v) in voids.iter().enumerate() {
+            if p != self.me {
+                self.excluded[p] |= (0..4)
+                    .filter(|&s| v & (1 << s) != 0)
+                    .fold(0, |m, s| m | suit_mask(s));
+            }
+        }
+        self
+    }
+
+    /// The other seats, in seat order.
+    fn seats(&self) -> [usize; 3] {
+        let mut seats = [0; 3];
+        for (i, p) in (0..4).filter(|&p| p != self.me).enumerate() {
+            seats[i] = p;
+        }
+        seats
+    }
+
+    fn hidden_cards(&self) -> Vec<u8> {
+        (0..32u8).filter(|&c| self.hidden & (1 << c) != 0).collect()
+    }
+
+    /// `ways[i][a][b]`: deals of the hidden cards from the `i`-th on, with `a` and `b`
+    /// cards left to give to the first two other seats.
+    fn ways(&self, cards: &[u8]) -> Vec<[[u64; 9]; 9]> {
+        let seats = self.seats();
+        let mut ways = vec![[[0u64; 9]; 9]; cards.len() + 1];
+        ways[cards.len()][0][0] = 1;
+        for i in (0..cards.len()).rev() {
+            let card = 1 << cards[i];
+            let left = (cards.len() - i) as u32;
+            for a in 0..9 {
+                for b in 0..9 {
+                    if a + b > left {
+                        continue;
+                    }
+                    let next = &ways[i + 1];
+                    let allowed = |s: usize| self.excluded[seats[s]] & card == 0;
+                    let mut n = 0;
+                    if a > 0 && allowed(0) {
+                        n += next[a as usize - 1][b as usize];
+                    }
+                    if b > 0 && allowed(1) {
+                        n += next[a as usize][b as usize - 1];
+                    }
+                    if a + b < left && allowed(2) {
+                        n += next[a as usize][b as usize];
+                    }
+                    ways[i][a as usize][b as usize] = n;
+                }
+            }
+        }
+        ways
+    }
+
+    /// Number of consistent worlds.
+    pub fn count(&self) -> u64 {
+        let seats = self.seats();
+        let cards = self.hidden_cards();
+        self.ways(&cards)[0][self.sizes[seats[0]] as usize][self.sizes[seats[1]] as usize]
+    }
+
+    /// Whether `world` keeps the hand sizes, hidden cards and voids.
+    pub fn is_consistent(&self, world: &PlayingState) -> bool {
+        self.seats().iter().all(|&p| {
+            world.hands[p].count_ones() == self.sizes[p] && world.hands[p] & self.excluded[p] == 0
+        }) && self.seats().iter().fold(0, |m, &p| m | world.hands[p]) == self.hidden
+    }
+
+    /// A uniformly drawn consistent world of `state`, `None` when there is none.
+    pub fn sample<R: Rng>(&self, state: &PlayingState, rng: &mut R) -> Option<PlayingState> {
+        let seats = self.seats();
+        let mut cards = self.hidden_cards();
+        let mut world = *state;
+        if self.excluded == [0; 4] {
+            // Every deal fits: a shuffle is uniform and cheaper than the table.
+            cards.shuffle(rng);
+            let mut cards = cards.into_iter();
+            for p in seats {
+                world.hands[p] = cards
+                    .by_ref()
+                    .take(self.sizes[p] as usize)
+                    .fold(0, |h, c| h | (1 << c));
+            }
+            return Some(world);
+        }
+
+        let ways = self.ways(&cards);
+        let (mut a, mut b) = (self.sizes[seats[0]] as usize, self.sizes[seats[1]] as usize);
+        if ways[0][a][b] == 0 {
+            return None;
+        }
+        world.hands[seats[0]] = 0;
+        world.hands[seats[1]] = 0;
+        world.hands[seats[2]] = 0;
+        for (i, &card) in cards.iter().enumerate() {
+            let bit = 1 << card;
+            let allowed = |s: usize| self.excluded[seats[s]] & bit == 0;
+            let left = cards.len() - i;
+            let options = [
+                (a > 0 && allowed(0)).then(|| ways[i + 1][a - 1][b]),
+                (b > 0 && allowed(1)).then(|| ways[i + 1][a][b - 1]),
+                (a + b < left && allowed(2)).then(|| ways[i + 1][a][b]),
+            ];
+            let mut pick = rng.gen_range(0..ways[i][a][b]);
+            let seat = (0..3)
+                .find(|&s| {
+                    let n = options[s].unwrap_or(0);
+                    if pick < n {
+                        true
+                    } else {
+                        pick -= n;
+                        false
+                    }
+                })
+                .expect("the table counts every option");
+            world.hands[seats[seat]] |= bit;
+            match seat {
+                0 => a -= 1,
+                1 => b -= 1,
+                _ => {}
+            }
+        }
+        Some(world)
+    }
+
+    /// Every consistent world of `state`, in a fixed order.
+    pub fn enumerate(&self, state: &PlayingState) -> Vec<PlayingState> {
+        let [s0, s1, s2] = self.seats();
+        let mut worlds = Vec::new();
+        for first in subsets(self.hidden & !self.excluded[s0], self.sizes[s0]) {
+            let rest = self.hidden & !first;
+            for second in subsets(rest & !self.excluded[s1], self.sizes[s1]) {
+                let third = rest & !second;
+                if third & self.excluded[s2] != 0 {
+                    continue;
+                }
+                let mut world = *state;
+                world.hands[s0] = first;
+                world.hands[s1] = second;
+                world.hands[s2] = third;
+                worlds.push(world);
+            }
+        }
+        worlds
+    }
+}
+
+/// Subsets of `mask` with `k` cards.
+fn subsets(mask: u32, k: u32) -> Vec<u32> {
+    fn extend(mask: u32, k: u32, chosen: u32, out: &mut Vec<u32>) {
+        if k == 0 {
+            out.push(chosen);
+        } else if mask.count_ones() >= k {
+            let low = mask & mask.wrapping_neg();
+            extend(mask & !low, k - 1, chosen | low, out);
+            extend(mask & !low, k, chosen, out);
+        }
+    }
+    let mut out = Vec::new();
+    extend(mask, k, 0, &mut out);
+    out
+}
+
+/// Samples a world consistent with what the player to move sees: the other
+/// players' cards are shuffled among them, keeping their hand sizes.
+pub fn determinize<R: Rng>(state: &PlayingState, rng: &mut R) -> PlayingState {
+    WorldConstraints::new(state)
+        .sample(state, rng)
+        .expect("hand sizes alone always fit")
+}
+
+/// Number of worlds `determinize` can deal for `state`.
+pub fn world_count(state: &PlayingState) -> u64 {
+    WorldConstraints::new(state).count()
+}
+
+/// Every world `determinize` can deal for `state`, in a fixed order.
+pub fn enumerate_worlds(state: &PlayingState) -> Vec<PlayingState> {
+    WorldConstraints::new(state).enumerate(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_gen::common::sample_rng;
+    use crate::solver::random_ending;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_counts_match_enumeration() {
+        let mut rng = sample_rng(Some(3), 0);
+        for cards in [4, 8, 12] {
+            for _ in 0..8 {
+                let state = random_ending(cards, &mut rng);
+                let voids = [
+                    rng.gen_range(0..16),
+                    rng.gen_range(0..16),
+                    rng.gen_range(0..16),
+                    0,
+                ];
+                for worlds in [
+                    WorldConstraints::new(&state),
+                    WorldConstraints::new(&state).with_voids(voids),
+                ] {
+                    let all = worlds.enumerate(&state);
+                    assert_eq!(all.len() as u64, worlds.count());
+                    assert!(all.iter().all(|w| worlds.is_consistent(w)));
+                    match worlds.sample(&state, &mut rng) {
+                        Some(world) => assert!(worlds.is_consistent(&world)),
+                        None => assert!(all.is_empty()),
+                    }
+                }
+            }
+        }
+        // 6 hidden cards, 2 per seat.
+        let state = random_ending(8, &mut rng);
+        assert_eq!(world_count(&state), 90);
+        let me = state.current_player as usize;
+        let world = determinize(&state, &mut rng);
+        assert_eq!(world.hands[me], state.hands[me]);
+        assert!(WorldConstraints::new(&state).is_consistent(&world));
+    }
+
+    #[test]
+    fn test_sampling_is_uniform() {
+        let mut rng = sample_rng(Some(7), 0);
+        let state = random_ending(8, &mut rng);
+        let me = state.current_player as usize;
+        let other = (me + 1) % 4;
+        // The first other seat lacks the suit of one of its cards.
+        let suit = state.hands[other].trailing_zeros() as u8 / 8;
+        let mut voids = [0; 4];
+        voids[other] = 1 << suit;
+        let worlds = WorldConstraints::new(&state).with_voids(voids);
+        let count = worlds.count() as usize;
+        assert!(count > 1 && count < 90);
+
+        let draws = 400 * count;
+        let mut seen: HashMap<[u32; 4], usize> = HashMap::new();
+        for _ in 0..draws {
+            *seen
+                .entry(worlds.sample(&state, &mut rng).unwrap().hands)
+                .or_default() += 1;
+        }
+        assert_eq!(seen.len(), count);
+        assert!(seen.values().all(|&n| n > 300 && n < 500), "{:?}", seen);
+    }
+
+    #[test]
+    fn test_voids_from_plays() {
+        let play = |trick, seat, card| PlayRecord { trick, seat, card };
+        // Diamonds led; West discards a spade, North and East follow.
+        let plays = [
+            play(0, 0, 0),
+            play(0, 1, 9),
+            play(0, 2, 3),
+            play(0, 3, 4),
+            // Spades led by West; North plays a club.
+            play(1, 1, 10),
+            play(1, 2, 25),
+        ];
+        assert_eq!(voids_from_plays(&plays), [0, 0b0001, 0b0010, 0]);
+    }
+}
@@ -1,41 +1,599 @@
 pub mod data_gen;
 pub mod gameplay;
-mod solver;
+pub mod profiling;
+pub mod solver;
 
+use data_gen::common::{generate_constrained_batch, ne_bytes, numpy_array, HandBuilder};
+use data_gen::evaluation::{
+    generate_evaluation_batch as generate_evaluation_batch_impl, EvaluationSample,
+};
+use data_gen::opening_leads::generate_opening_lead_batch as generate_opening_lead_batch_impl;
+use data_gen::schema::{solved_batch_v1, solved_batch_v2, solved_batch_v3, solved_batch_v4};
+use data_gen::selfplay::generate_selfplay_batch as generate_selfplay_impl;
 use data_gen::{
-    generate_hand_batch, generate_raw_gameplay_batch as gen_raw_gameplay_impl,
-    solve_gameplay_batch as solve_gameplay_impl, solve_hand_batch,
+    difficulty_batch, generate_gameplay_batch_for_side, generate_hand_batch,
+    generate_positions_batch, solve_gameplay_batch as solve_gameplay_impl, solve_hand_batch,
+    solve_leaders_batch, solve_pimc_parallel, transform_labels,
+    verify_dataset as verify_dataset_impl, AdaptivePimc, BidConstraint, CheckpointConfig,
+    ContractSource, GameplayBatch, GameplaySample, GameplaySolveOptions, GameplaySolveRequest,
+    LabelTransform, OpeningLeadSample, PimcConfidence, PimcDecision, PimcVoting, PolicyEvaluation,
+    SchemaVersion, ScoreLabel, SelfPlayGame, SideFilter, StageConfig, VecCoincheEnv,
+    VerificationReport,
+};
+use gameplay::analysis::{
+    analyze_hand as analyze_hand_impl, analyze_position as analyze_position_impl, CardAnalysis,
+    PositionAnalysis,
+};
+use gameplay::bidding::AuctionAction;
+use gameplay::bot::BotAction;
+use gameplay::duplicate;
+use gameplay::encoding;
+use gameplay::history::{
+    attribute_played_cards, decode_history, encode_history, history_mask, CompletedTrick,
+    PlayRecord,
 };
+use gameplay::manager::MatchResult;
+use gameplay::notation;
 use gameplay::playing::PlayingState;
-use pyo3::exceptions::PyRuntimeError;
+use gameplay::snapshot::state_error;
+use gameplay::worlds::{voids_from_plays, WorldConstraints};
+use pyo3::exceptions::{PyRuntimeError, PyValueError};
 use pyo3::prelude::*;
-use solver::solve;
+use pyo3::sync::GILOnceCell;
+use solver::{
+    solve_with_mode, solve_with_objective, Objective, Perspective, Score, SearchMode,
+    DEFAULT_MCTS_ITERATIONS,
+};
 
+/// `perspective` selects whose points are returned: "ns" (default), "current"
+/// (team of the player to move) or "declarer" (requires `declarer`). `search` is
+/// "minimax" (default), "mcts" (trick-level MCTS of `iterations` iterations,
+/// reproducible with `seed`) or "auto" (MCTS for All-Trump, minimax otherwise).
+/// `objective` is what the score counts: "points" (default), "tricks" or
+/// "lexicographic" (tricks * 400 + points); minimax only.
 #[pyfunction]
-#[pyo3(signature = (state, max_depth=None))]
-fn solve_game(state: &PlayingState, max_depth: Option<u8>) -> PyResult<(i16, u8)> {
-    let (score, best_move) = solve(state, false, max_depth, None);
+#[pyo3(signature = (state, max_depth=None, perspective="ns", declarer=None, search="minimax", iterations=DEFAULT_MCTS_ITERATIONS, seed=None, objective="points"))]
+#[allow(clippy::too_many_arguments)]
+fn solve_game(
+    py: Python,
+    state: &PlayingState,
+    max_depth: Option<u8>,
+    perspective: &str,
+    declarer: Option<u8>,
+    search: &str,
+    iterations: u32,
+    seed: Option<u64>,
+    objective: &str,
+) -> PyResult<(Score, u8)> {
+    let team = Perspective::parse(perspective)
+        .and_then(|p| p.team(state, declarer))
+        .map_err(|e| state_error(state, e))?;
+    let mode =
+        SearchMode::parse(search, state.trump, iterations).map_err(|e| state_error(state, e))?;
+    let objective = Objective::parse(objective).map_err(|e| state_error(state, e))?;
+    let state = *state;
+    let (score, best_move) = match (mode, objective) {
+        (_, Objective::Points) => {
+            py.allow_threads(|| solve_with_mode(&state, team, mode, max_depth, None, seed))
+        }
+        (SearchMode::Minimax, _) => {
+            py.allow_threads(|| solve_with_objective(&state, team, objective, max_depth, None))
+        }
+        (SearchMode::TrickMcts { .. }, _) => {
+            return Err(state_error(&state, "MCTS only solves for points"))
+        }
+    };
     Ok((score, best_move))
 }
 
+/// Picks a move for the player to move by PIMC: `iterations` worlds consistent
+/// with their hand are solved in parallel. `voting` is "plurality" (each world
+/// votes for its best card) or "ev" (every card is solved in each world, best
+/// mean value wins). With `constraints` (BidConstraint list), sampled worlds must
+/// agree with the auction; with `plays` (the ordered plays of the deal so far, packed
+/// as by `encode_play_history`), they keep the suits each seat showed it lacks.
+/// The decision carries the vote distribution and a confidence.
+#[pyfunction]
+#[pyo3(signature = (state, iterations, perspective="ns", declarer=None, voting="plurality", tt_log2=None, seed=None, constraints=None, plays=None))]
+#[allow(clippy::too_many_arguments)]
+fn solve_pimc(
+    py: Python,
+    state: &PlayingState,
+    iterations: usize,
+    perspective: &str,
+    declarer: Option<u8>,
+    voting: &str,
+    tt_log2: Option<u8>,
+    seed: Option<u64>,
+    constraints: Option<Vec<BidConstraint>>,
+    plays: Option<Vec<u16>>,
+) -> PyResult<PimcDecision> {
+    let team = Perspective::parse(perspective)
+        .and_then(|p| p.team(state, declarer))
+        .map_err(|e| state_error(state, e))?;
+    let voting = PimcVoting::parse(voting).map_err(|e| state_error(state, e))?;
+    let state = *state;
+    let constraints = constraints.unwrap_or_default();
+    let voids = plays.map_or([0; 4], |p| voids_from_plays(&decode_history(&p)));
+    if WorldConstraints::new(&state).with_voids(voids).count() == 0 {
+        return Err(state_error(&state, "No deal fits the voids of the plays"));
+    }
+    warn_seeded_tt_sharing(py, seed)?;
+    Ok(py.allow_threads(|| {
+        solve_pimc_parallel(
+            &state,
+            team,
+            iterations,
+            voting,
+            &constraints,
+            voids,
+            tt_log2,
+            seed,
+        )
+    }))
+}
+
+/// Whether the team of `perspective` wins every remaining trick against any
+/// defence. Only tracks trick ownership, so it is much cheaper than `solve_game`.
+#[pyfunction]
+#[pyo3(signature = (state, perspective="ns", declarer=None))]
+fn forces_capot(
+    py: Python,
+    state: &PlayingState,
+    perspective: &str,
+    declarer: Option<u8>,
+) -> PyResult<bool> {
+    let team = Perspective::parse(perspective)
+        .and_then(|p| p.team(state, declarer))
+        .map_err(|e| state_error(state, e))?;
+    let state = *state;
+    Ok(py.allow_threads(|| solver::forces_capot(&state, team)))
+}
+
+/// Per-card analysis of the hand of the player to move, all hands known: master
+/// cards, opponent cards beating each one and the double-dummy points of every
+/// legal card for the mover's team.
+#[pyfunction]
+#[pyo3(signature = (state, tt_log2=None))]
+fn analyze_hand(py: Python, state: &PlayingState, tt_log2: Option<u8>) -> Vec<CardAnalysis> {
+    let state = *state;
+    py.allow_threads(|| analyze_hand_impl(&state, tt_log2))
+}
+
+/// Best move, principal variation and value of every legal move of `state`,
+/// searched for at most about `time_budget_ms` (unbounded when None). Values are
+/// the final points of the team chosen by `perspective`, as in `solve_game`
+/// (default: the team of the player to move).
 #[pyfunction]
-fn generate_bidding_hands(num_samples: usize) -> PyResult<(Vec<u32>, Vec<u8>)> {
-    let (hands, strategies) = generate_hand_batch(num_samples);
+#[pyo3(signature = (state, time_budget_ms=None, perspective="current", declarer=None, tt_log2=None))]
+fn analyze_position(
+    py: Python,
+    state: &PlayingState,
+    time_budget_ms: Option<u64>,
+    perspective: &str,
+    declarer: Option<u8>,
+    tt_log2: Option<u8>,
+) -> PyResult<PositionAnalysis> {
+    let team = Perspective::parse(perspective)
+        .and_then(|p| p.team(state, declarer))
+        .map_err(|e| state_error(state, e))?;
+    let state = *state;
+    let budget = time_budget_ms.map(std::time::Duration::from_millis);
+    py.allow_threads(|| analyze_position_impl(&state, team, budget, tt_log2))
+        .map_err(|e| state_error(&state, e))
+}
+
+/// IMP-like swing of a point difference (see `gameplay::duplicate`).
+#[pyfunction]
+fn imps(points: i32) -> i32 {
+    duplicate::imps(points)
+}
+
+/// NS deal score minus EW deal score of a finished deal.
+#[pyfunction]
+fn net_score(result: &MatchResult) -> i32 {
+    duplicate::net_score(result)
+}
+
+/// IMPs NS gained at the table of `result` over the table of `other`, the same
+/// deal played by other teams.
+#[pyfunction]
+fn duplicate_swing(result: &MatchResult, other: &MatchResult) -> i32 {
+    duplicate::duplicate_swing(result, other)
+}
+
+/// Double-dummy par of a deal dealt by `dealer`: twelve full solves.
+#[pyfunction]
+#[pyo3(signature = (hands, dealer, tt_log2=None))]
+fn par_result(
+    py: Python,
+    hands: [u32; 4],
+    dealer: u8,
+    tt_log2: Option<u8>,
+) -> PyResult<duplicate::ParResult> {
+    gameplay::deal::validate_deal(&hands).map_err(PyValueError::new_err)?;
+    if dealer >= 4 {
+        return Err(PyValueError::new_err(format!("Invalid dealer {}", dealer)));
+    }
+    Ok(py.allow_threads(|| duplicate::par_result(&hands, dealer, tt_log2)))
+}
+
+/// IMPs NS gained at the table of `result` over the par of its deal.
+#[pyfunction]
+fn par_swing(result: &MatchResult, par: &duplicate::ParResult) -> i32 {
+    duplicate::par_swing(result, par)
+}
+
+/// Deals of a corpus of recorded games (see `gameplay::corpus`), as (dealer,
+/// hands) pairs: the file at `path` (gzip-compressed when it ends in `.gz`, which
+/// needs the deal-corpus feature), or the embedded corpus without a path.
+#[pyfunction]
+#[pyo3(signature = (path=None))]
+fn load_deal_corpus(py: Python, path: Option<String>) -> PyResult<Vec<(u8, [u32; 4])>> {
+    py.allow_threads(|| match path {
+        Some(path) => gameplay::corpus::load_corpus(std::path::Path::new(&path)),
+        None => gameplay::corpus::try_embedded_corpus(),
+    })
+    .map(|matches| {
+        matches
+            .iter()
+            .map(|m| (m.dealer, m.initial_hands))
+            .collect()
+    })
+    .map_err(PyValueError::new_err)
+}
+
+/// Enables the partition cache (abstract positions shared across deals) for all
+/// solver threads. Worth it for batch generation; results are unchanged.
+#[pyfunction]
+fn set_partition_cache(enabled: bool) {
+    solver::set_partition_cache(enabled);
+}
+
+/// Opens the deal cache file at `path` (created if missing): double-dummy values
+/// of full deals that batch solves look up before solving, and append to.
+/// Returns the number of deals already in it.
+#[pyfunction]
+fn open_deal_cache(path: &str) -> PyResult<usize> {
+    solver::open_deal_cache(std::path::Path::new(path)).map_err(PyValueError::new_err)
+}
+
+#[pyfunction]
+fn close_deal_cache() {
+    solver::close_deal_cache();
+}
+
+/// (entries, hits, misses, write_errors) of the open deal cache, None when none is
+/// open. Write errors count solved deals that could not be saved to the file.
+#[pyfunction]
+fn deal_cache_stats() -> Option<(usize, u64, u64, u64)> {
+    solver::deal_cache_stats().map(|s| (s.entries, s.hits, s.misses, s.write_errors))
+}
+
+/// Lets the worlds of a PIMC decision share each solver thread's TT (off by
+/// default). Faster, but not reproducible: a world may vote for another card of
+/// the same value depending on the worlds solved before it on its thread. Seeded
+/// solves ignore it and warn.
+#[pyfunction]
+fn set_pimc_tt_sharing(enabled: bool) {
+    solver::set_pimc_tt_sharing(enabled);
+}
+
+/// Warns that TT sharing is ignored when a seeded PIMC solve runs with it on.
+fn warn_seeded_tt_sharing(py: Python, seed: Option<u64>) -> PyResult<()> {
+    if seed.is_some() && solver::pimc_tt_sharing_enabled() {
+        PyErr::warn(
+            py,
+            py.get_type::<pyo3::exceptions::PyUserWarning>(),
+            "PIMC TT sharing is ignored by seeded solves, which solve every world cold",
+            1,
+        )?;
+    }
+    Ok(())
+}
+
+/// Experimental solver settings for all solver threads (see `SolveOptions`).
+/// `discard_pruning` trades a few points of accuracy for speed: benchmark it with
+/// `bench pruning` before generating data with it.
+#[pyfunction]
+#[pyo3(signature = (discard_pruning=false))]
+fn set_solve_options(discard_pruning: bool) {
+    solver::set_solve_options(solver::SolveOptions { discard_pruning });
+}
+
+#[pyfunction]
+fn validate_deal(hands: Vec<u32>) -> PyResult<()> {
+    let h: [u32; 4] = hands
+        .try_into()
+        .map_err(|_| PyValueError::new_err("Hands must have 4 entries"))?;
+    gameplay::deal::validate_deal(&h).map_err(PyValueError::new_err)
+}
+
+/// 128-bit ID of a complete deal (4 hands of 8 cards), the same wherever the deal
+/// shows up: hand `s` in bits `32 s` to `32 s + 31`.
+#[pyfunction]
+fn deal_id(hands: [u32; 4]) -> PyResult<u128> {
+    gameplay::deal::deal_id(&hands).map_err(PyValueError::new_err)
+}
+
+/// IDs of flattened deals (4 hands per deal), see `deal_id`.
+#[pyfunction]
+fn deal_ids(hands: Vec<u32>) -> PyResult<Vec<u128>> {
+    gameplay::deal::deal_ids(&hands).map_err(PyValueError::new_err)
+}
+
+/// Hands of the deal with ID `id`, see `deal_id`.
+#[pyfunction]
+fn deal_from_id(id: u128) -> PyResult<[u32; 4]> {
+    gameplay::deal::deal_from_id(id).map_err(PyValueError::new_err)
+}
+
+/// Uniformly random deal as (hands, deal_id). Passing `seed` makes it reproducible.
+#[pyfunction]
+#[pyo3(signature = (seed=None))]
+fn deal_random(seed: Option<u64>) -> ([u32; 4], u128) {
+    gameplay::deal::deal_random(seed)
+}
+
+/// `num_deals` deals meeting the constraints of `builder` (a `HandBuilder`),
+/// flattened 4 hands per deal. Passing `seed` makes the batch reproducible.
+#[pyfunction]
+#[pyo3(signature = (builder, num_deals, seed=None))]
+fn generate_constrained_deals(
+    py: Python,
+    builder: HandBuilder,
+    num_deals: usize,
+    seed: Option<u64>,
+) -> PyResult<Vec<u32>> {
+    Ok(py.allow_threads(|| generate_constrained_batch(&builder, num_deals, seed))?)
+}
+
+/// `num_deals` deals meeting every quota of `quotas` (`DealQuota`s, each met
+/// exactly), drawn from `builder` (a `HandBuilder`) or uniformly, flattened 4 hands
+/// per deal, with the number of candidate deals drawn. Fails after `max_draws`
+/// candidates (10000 per deal by default). Passing `seed` makes the batch reproducible.
+#[pyfunction]
+#[pyo3(signature = (quotas, num_deals, builder=None, seed=None, max_draws=None))]
+fn generate_quota_deals(
+    py: Python,
+    quotas: Vec<data_gen::DealQuota>,
+    num_deals: usize,
+    builder: Option<HandBuilder>,
+    seed: Option<u64>,
+    max_draws: Option<u64>,
+) -> PyResult<(Vec<u32>, u64)> {
+    py.allow_threads(|| {
+        data_gen::generate_quota_batch(&quotas, num_deals, builder.as_ref(), seed, max_draws)
+    })
+    .map(|batch| (batch.hands, batch.draws))
+    .map_err(PyValueError::new_err)
+}
+
+/// Passing `seed` makes the generated batch reproducible.
+#[pyfunction]
+#[pyo3(signature = (num_samples, seed=None))]
+fn generate_bidding_hands(num_samples: usize, seed: Option<u64>) -> PyResult<(Vec<u32>, Vec<u8>)> {
+    let (hands, strategies) = generate_hand_batch(num_samples, seed);
     Ok((hands, strategies))
 }
 
+/// With `checkpoint` (a file path), progress is saved every `checkpoint_every`
+/// samples; `resume=True` reloads it and only solves the missing samples.
 #[pyfunction]
-#[pyo3(signature = (hands, pimc_iterations, tt_log2=None))]
+#[pyo3(signature = (hands, pimc_iterations, tt_log2=None, seed=None, checkpoint=None, checkpoint_every=1000, resume=false))]
+#[allow(clippy::too_many_arguments)]
 fn solve_bidding_batch(
     py: Python,
     hands: Vec<u32>,
     pimc_iterations: usize,
     tt_log2: Option<u8>,
+    seed: Option<u64>,
+    checkpoint: Option<String>,
+    checkpoint_every: usize,
+    resume: bool,
 ) -> PyResult<Vec<Vec<f32>>> {
+    warn_seeded_tt_sharing(py, seed)?;
+    let checkpoint = checkpoint.map(|p| CheckpointConfig::new(p, checkpoint_every, resume));
+    py.allow_threads(|| {
+        solve_hand_batch(hands, pimc_iterations, tt_log2, seed, checkpoint.as_ref())
+            .map_err(PyRuntimeError::new_err)
+    })
+}
+
+/// Double-dummy NS points of a deal in `trump` with each seat leading the first
+/// trick: entry `i` is the value when seat `i` leads.
+#[pyfunction]
+#[pyo3(signature = (hands, trump, tt_log2=None))]
+fn solve_all_leaders(
+    py: Python,
+    hands: Vec<u32>,
+    trump: u8,
+    tt_log2: Option<u8>,
+) -> PyResult<[Score; 4]> {
+    let h: [u32; 4] = hands
+        .try_into()
+        .map_err(|_| PyValueError::new_err("Hands must have 4 entries"))?;
+    if trump >= 6 {
+        return Err(PyValueError::new_err(format!("Invalid trump {}", trump)));
+    }
+    gameplay::deal::validate_deal(&h).map_err(PyValueError::new_err)?;
+    Ok(py.allow_threads(|| solver::solve_all_leaders(&h, trump, tt_log2)))
+}
+
+/// `solve_all_leaders` over a batch: 4 flattened hands and one trump per deal.
+#[pyfunction]
+#[pyo3(signature = (hands, trumps, tt_log2=None))]
+fn solve_all_leaders_batch(
+    py: Python,
+    hands: Vec<u32>,
+    trumps: Vec<u8>,
+    tt_log2: Option<u8>,
+) -> PyResult<Vec<[Score; 4]>> {
+    py.allow_threads(|| solve_leaders_batch(&hands, &trumps, tt_log2))
+        .map_err(PyValueError::new_err)
+}
+
+/// Opening lead tables of `num_deals` random deals in which `declarer` bid
+/// `contract`: the defender on its left leads, and each sample holds the
+/// double-dummy defenders' points of every card it may lead.
+#[pyfunction]
+#[pyo3(signature = (num_deals, contract, declarer=0, seed=None, tt_log2=None))]
+fn generate_opening_lead_batch(
+    py: Python,
+    num_deals: usize,
+    contract: gameplay::bidding::Bid,
+    declarer: u8,
+    seed: Option<u64>,
+    tt_log2: Option<u8>,
+) -> PyResult<Vec<OpeningLeadSample>> {
+    py.allow_threads(|| {
+        generate_opening_lead_batch_impl(num_deals, contract, declarer, seed, tt_log2)
+    })
+    .map_err(PyValueError::new_err)
+}
+
+/// Static evaluation samples: `num_samples` random positions with between
+/// `min_cards` and `max_cards` cards left, their `evaluation_features`, the
+/// solver's heuristic value and the exact value for the team to move.
+#[pyfunction]
+#[pyo3(signature = (num_samples, seed=None, min_cards=4, max_cards=20, tt_log2=None))]
+fn generate_evaluation_batch(
+    py: Python,
+    num_samples: usize,
+    seed: Option<u64>,
+    min_cards: u8,
+    max_cards: u8,
+    tt_log2: Option<u8>,
+) -> PyResult<Vec<EvaluationSample>> {
     py.allow_threads(|| {
-        let scores = solve_hand_batch(hands, pimc_iterations, tt_log2);
-        Ok(scores)
+        generate_evaluation_batch_impl(num_samples, seed, min_cards, max_cards, tt_log2)
     })
+    .map_err(PyValueError::new_err)
+}
+
+/// Percentile (0-100) of the bidding strength of `hand` among random hands for
+/// a contract in `trump`, from the shipped table (see `data_gen::hand_percentile`).
+#[pyfunction]
+fn hand_percentile(hand: u32, trump: u8) -> PyResult<f32> {
+    data_gen::hand_percentile(hand, trump).map_err(PyValueError::new_err)
+}
+
+/// Difficulty label in [0, 1] of a deal in `trump`, South leading: search cost,
+/// sensitivity of the value to the opening lead and trump balance combined.
+#[pyfunction]
+#[pyo3(signature = (hands, trump, tt_log2=None))]
+fn deal_difficulty(py: Python, hands: Vec<u32>, trump: u8, tt_log2: Option<u8>) -> PyResult<f32> {
+    if hands.len() != 4 {
+        return Err(PyValueError::new_err("Hands must have 4 entries"));
+    }
+    py.allow_threads(|| difficulty_batch(&hands, &[trump], tt_log2))
+        .map(|d| d[0])
+        .map_err(PyValueError::new_err)
+}
+
+/// `deal_difficulty` over a batch: 4 flattened hands and one trump per deal.
+#[pyfunction]
+#[pyo3(signature = (hands, trumps, tt_log2=None))]
+fn deal_difficulty_batch(
+    py: Python,
+    hands: Vec<u32>,
+    trumps: Vec<u8>,
+    tt_log2: Option<u8>,
+) -> PyResult<Vec<f32>> {
+    py.allow_threads(|| difficulty_batch(&hands, &trumps, tt_log2))
+        .map_err(PyValueError::new_err)
+}
+
+/// Downsamples the easy positions of a gameplay batch (columns as for
+/// `solve_gameplay_batch`): positions with a difficulty under `threshold` or a
+/// single legal card are kept with probability `keep_fraction`, the others
+/// always. Returns (keep, weights, difficulty) per position; kept easy positions
+/// weigh 1 / keep_fraction, so that weighted training stays unbiased.
+#[pyfunction]
+#[pyo3(signature = (hands, boards, trumps, tricks_won, players, threshold, keep_fraction, seed=None, tt_log2=None))]
+#[allow(clippy::too_many_arguments)]
+fn downsample_easy_positions(
+    py: Python,
+    hands: Vec<u32>,
+    boards: Vec<Vec<u8>>,
+    trumps: Vec<u8>,
+    tricks_won: Vec<Vec<u8>>,
+    players: Vec<u8>,
+    threshold: f32,
+    keep_fraction: f32,
+    seed: Option<u64>,
+    tt_log2: Option<u8>,
+) -> PyResult<(Vec<bool>, Vec<f32>, Vec<f32>)> {
+    py.allow_threads(|| {
+        data_gen::downsample_easy_positions(
+            &hands,
+            &boards,
+            &trumps,
+            &tricks_won,
+            &players,
+            threshold,
+            keep_fraction,
+            seed,
+            tt_log2,
+        )
+    })
+    .map(|d| (d.keep, d.weights, d.difficulty))
+    .map_err(PyValueError::new_err)
+}
+
+fn motif_names(motifs: &[data_gen::Motif]) -> Vec<String> {
+    motifs.iter().map(|m| m.name().to_string()).collect()
+}
+
+/// Play motifs of a deal in `trump`, South leading, along the solver's line:
+/// "throw_in", "trump_promotion" and "discard_squeeze" (see `data_gen::motifs`).
+/// A card-choice motif counts when every other card costs at least `min_swing`.
+#[pyfunction]
+#[pyo3(signature = (hands, trump, min_swing=10, tt_log2=None))]
+fn deal_tags(
+    py: Python,
+    hands: Vec<u32>,
+    trump: u8,
+    min_swing: Score,
+    tt_log2: Option<u8>,
+) -> PyResult<Vec<String>> {
+    if hands.len() != 4 {
+        return Err(PyValueError::new_err("Hands must have 4 entries"));
+    }
+    py.allow_threads(|| data_gen::motifs_batch(&hands, &[trump], min_swing, tt_log2))
+        .map(|tags| motif_names(&tags[0]))
+        .map_err(PyValueError::new_err)
+}
+
+/// `deal_tags` over a batch: 4 flattened hands and one trump per deal.
+#[pyfunction]
+#[pyo3(signature = (hands, trumps, min_swing=10, tt_log2=None))]
+fn deal_tags_batch(
+    py: Python,
+    hands: Vec<u32>,
+    trumps: Vec<u8>,
+    min_swing: Score,
+    tt_log2: Option<u8>,
+) -> PyResult<Vec<Vec<String>>> {
+    py.allow_threads(|| data_gen::motifs_batch(&hands, &trumps, min_swing, tt_log2))
+        .map(|tags| tags.iter().map(|t| motif_names(t)).collect())
+        .map_err(PyValueError::new_err)
+}
+
+/// Up to `n` card-play puzzles: random positions where a single legal card
+/// reaches the double-dummy optimum and every other one costs at least the
+/// constraints' swing (see `data_gen::puzzles`).
+#[pyfunction]
+#[pyo3(signature = (n, constraints=None, seed=None, tt_log2=None))]
+fn generate_puzzles(
+    py: Python,
+    n: usize,
+    constraints: Option<data_gen::PuzzleConstraints>,
+    seed: Option<u64>,
+    tt_log2: Option<u8>,
+) -> Vec<data_gen::Puzzle> {
+    let constraints = constraints.unwrap_or_default();
+    py.allow_threads(|| data_gen::generate_puzzles(n, &constraints, seed, tt_log2))
 }
 
 #[pyfunction]
@@ -46,27 +604,296 @@ fn generate_bidding_data(path: String, num_samples: usize) -> PyResult<()> {
     ))
 }
 
+/// Layout of a generated batch for `schema_version` (default: latest).
+fn raw_batch_for_version(py: Python, batch: GameplayBatch, version: SchemaVersion) -> PyObject {
+    match version {
+        SchemaVersion::V1 => batch.columns().into_py(py),
+        SchemaVersion::V2
+        | SchemaVersion::V3
+        | SchemaVersion::V4
+        | SchemaVersion::V5
+        | SchemaVersion::V6 => batch.into_py(py),
+    }
+}
+
+/// Random positions as a `GameplayBatch`. Each sample carries its ordered plays,
+/// packed as `trick << 7 | seat << 5 | card` (see `decode_play_history`).
+/// `schema_version=1` returns the legacy 6-tuple of columns instead. `side`
+/// ("declarer" or "defense") keeps only positions whose player to move is on
+/// that side of the sample's declarer. `contracts` picks each deal's contract:
+/// "random" (a random suit) or "threshold" (the auction of `ThresholdBidder`s).
 #[pyfunction]
+#[pyo3(signature = (num_samples, seed=None, schema_version=None, side=None, contracts="random"))]
 fn generate_raw_gameplay_batch(
     py: Python,
     num_samples: usize,
-) -> PyResult<(
-    Vec<u32>,
-    Vec<Vec<u8>>,
-    Vec<u32>,
-    Vec<u8>,
-    Vec<Vec<u8>>,
-    Vec<u8>,
-)> {
-    py.allow_threads(|| {
-        let (hands, boards, history, trumps, tricks_won, players) =
-            gen_raw_gameplay_impl(num_samples);
-        Ok((hands, boards, history, trumps, tricks_won, players))
-    })
+    seed: Option<u64>,
+    schema_version: Option<u32>,
+    side: Option<&str>,
+    contracts: &str,
+) -> PyResult<PyObject> {
+    let version = SchemaVersion::parse(schema_version).map_err(PyValueError::new_err)?;
+    let side = side
+        .map(SideFilter::parse)
+        .transpose()
+        .map_err(PyValueError::new_err)?;
+    let contracts = ContractSource::parse(contracts).map_err(PyValueError::new_err)?;
+    let batch =
+        py.allow_threads(|| generate_gameplay_batch_for_side(num_samples, seed, side, contracts));
+    Ok(raw_batch_for_version(py, batch, version))
+}
+
+/// One call of a Python policy on a batch: observations as a float32 numpy array
+/// of shape (n, GAMEPLAY_FEATURES), legal masks as uint32 of shape (n,); the
+/// returned (n, 32) logits are read back as float32.
+fn call_policy(
+    py: Python,
+    policy: &PyObject,
+    observations: &[f32],
+    legal: &[u32],
+) -> PyResult<Vec<f32>> {
+    let observations = numpy_array(py, &ne_bytes(observations, f32::to_ne_bytes), "float32")?
+        .call_method1("reshape", ((legal.len(), encoding::GAMEPLAY_FEATURES),))?;
+    let legal = numpy_array(py, &ne_bytes(legal, u32::to_ne_bytes), "uint32")?;
+    let logits = policy.call1(py, (observations, legal))?;
+    let raw = py
+        .import("numpy")?
+        .call_method1("ascontiguousarray", (logits, "float32"))?
+        .call_method0("tobytes")?;
+    let raw: &[u8] = raw.extract()?;
+    Ok(raw
+        .chunks_exact(4)
+        .map(|b| f32::from_ne_bytes(b.try_into().unwrap()))
+        .collect())
+}
+
+/// Plays `num_games` random deals to the end with cards chosen by `policy`, a
+/// callable `(observations, legal) -> logits` evaluated once per card for all
+/// games together (see `call_policy` for the array shapes). `temperature` 0
+/// plays the best legal card, above 0 samples from the softmax of the logits.
+#[pyfunction]
+#[pyo3(signature = (num_games, policy, temperature=0.0, seed=None))]
+fn generate_selfplay_batch(
+    py: Python,
+    num_games: usize,
+    policy: PyObject,
+    temperature: f32,
+    seed: Option<u64>,
+) -> PyResult<Vec<SelfPlayGame>> {
+    // Python errors of the policy are raised as is, not as strings.
+    let mut policy_error = None;
+    let games = py.allow_threads(|| {
+        generate_selfplay_impl(num_games, temperature, seed, |observations, legal| {
+            Python::with_gil(|py| call_policy(py, &policy, observations, legal)).map_err(|e| {
+                let message = e.to_string();
+                policy_error = Some(e);
+                message
+            })
+        })
+    });
+    match (games, policy_error) {
+        (_, Some(e)) => Err(e),
+        (games, None) => games.map_err(PyValueError::new_err),
+    }
+}
+
+/// Plays `num_games` random deals to the end with human-like noise on the
+/// solver's choices: each card is drawn from the softmax of the legal cards'
+/// values / `temperature` (in points), or with probability `override_rate` is the
+/// bots' rule-of-thumb card.
+#[pyfunction]
+#[pyo3(signature = (num_games, temperature=10.0, override_rate=0.05, seed=None, tt_log2=None))]
+fn generate_human_play_batch(
+    py: Python,
+    num_games: usize,
+    temperature: f32,
+    override_rate: f64,
+    seed: Option<u64>,
+    tt_log2: Option<u8>,
+) -> PyResult<Vec<data_gen::HumanPlayGame>> {
+    let noise =
+        data_gen::NoiseModel::new(temperature, override_rate).map_err(PyValueError::new_err)?;
+    Ok(py.allow_threads(|| data_gen::generate_human_play_batch(num_games, &noise, seed, tt_log2)))
+}
+
+/// 32-dim multi-hot vector of a hand mask (see `gameplay::encoding`).
+#[pyfunction]
+fn encode_hand(hand: u32) -> Vec<f32> {
+    encoding::encode_hand(hand).to_vec()
+}
+
+/// Hand mask of a multi-hot vector: the cards whose entry is above 0.5.
+#[pyfunction]
+fn decode_hand(vector: Vec<f32>) -> PyResult<u32> {
+    encoding::decode_hand(&vector).map_err(PyValueError::new_err)
+}
+
+#[pyfunction]
+fn encode_card(card: u8) -> PyResult<Vec<f32>> {
+    if card as usize >= encoding::CARDS {
+        return Err(PyValueError::new_err(format!(
+            "Invalid card index {}",
+            card
+        )));
+    }
+    Ok(encoding::encode_card(card).to_vec())
+}
+
+#[pyfunction]
+fn decode_card(vector: Vec<f32>) -> PyResult<u8> {
+    encoding::decode_card(&vector).map_err(PyValueError::new_err)
+}
+
+/// 4x32 matrix of a seat-indexed trick (255 = no card), one row per seat.
+#[pyfunction]
+fn encode_trick(trick: [u8; 4]) -> Vec<Vec<f32>> {
+    encoding::encode_trick(&trick)
+        .iter()
+        .map(|r| r.to_vec())
+        .collect()
+}
+
+#[pyfunction]
+fn decode_trick(matrix: Vec<Vec<f32>>) -> PyResult<[u8; 4]> {
+    encoding::decode_trick(&matrix).map_err(PyValueError::new_err)
+}
+
+/// Gameplay model input (`GAMEPLAY_FEATURES` floats): hand, history and board
+/// multi-hot vectors, then the trump one-hot.
+#[pyfunction]
+fn encode_gameplay_features(hand: u32, history: u32, board: Vec<u8>, trump: u8) -> Vec<f32> {
+    encoding::gameplay_features(hand, history, &board, trump)
+}
+
+/// Auction context of `seat` (`AUCTION_FEATURES` floats) for play-phase models:
+/// recent bids of each seat, the contract, coinche flags and the position after
+/// the dealer. `auction` holds the calls as `CoincheMatch.auction` does.
+#[pyfunction]
+fn encode_auction_features(
+    auction: Vec<AuctionAction>,
+    dealer: u8,
+    seat: u8,
+) -> PyResult<Vec<f32>> {
+    encoding::auction_features(&auction, dealer, seat).map_err(PyValueError::new_err)
+}
+
+/// Index of an action (a call as `CoincheMatch.bot_action` returns it, or a card
+/// index) in the flat action space of `FLAT_ACTIONS` entries.
+#[pyfunction]
+fn flat_action_index(action: BotAction) -> PyResult<usize> {
+    encoding::flat_action_index(action).map_err(PyValueError::new_err)
+}
+
+/// Action of an index of the flat action space.
+#[pyfunction]
+fn flat_action(py: Python, index: usize) -> PyResult<PyObject> {
+    let action = encoding::flat_action(index).map_err(PyValueError::new_err)?;
+    Ok(action.into_py(py))
 }
 
+/// (head, index) of an action in the dual-head layout: head 0 indexes the
+/// `AUCTION_ACTIONS` calls, head 1 the 32 cards.
 #[pyfunction]
-#[pyo3(signature = (hands, boards, history, trumps, tricks_won, players, pimc_iterations, tt_log2=None))]
+fn dual_head_action_index(action: BotAction) -> PyResult<(u8, usize)> {
+    encoding::dual_head_index(action).map_err(PyValueError::new_err)
+}
+
+/// Action of an index of `head` in the dual-head layout.
+#[pyfunction]
+fn dual_head_action(py: Python, head: u8, index: usize) -> PyResult<PyObject> {
+    let action = encoding::dual_head_action(head, index).map_err(PyValueError::new_err)?;
+    Ok(action.into_py(py))
+}
+
+/// Packs (trick, seat, card) triples into the u16 history format; raises
+/// ValueError naming the first triple out of range.
+#[pyfunction]
+fn encode_play_history(plays: Vec<(u8, u8, u8)>) -> PyResult<Vec<u16>> {
+    let records: Vec<PlayRecord> = plays
+        .into_iter()
+        .enumerate()
+        .map(|(i, (trick, seat, card))| {
+            PlayRecord::checked(trick, seat, card).map_err(|e| format!("Play {}: {}", i, e))
+        })
+        .collect::<Result<_, _>>()
+        .map_err(PyValueError::new_err)?;
+    Ok(encode_history(&records))
+}
+
+/// Unpacks the u16 history format into (trick, seat, card) triples.
+#[pyfunction]
+fn decode_play_history(encoded: Vec<u16>) -> Vec<(u8, u8, u8)> {
+    decode_history(&encoded)
+        .into_iter()
+        .map(|p| (p.trick, p.seat, p.card))
+        .collect()
+}
+
+/// Flat played-cards mask of a packed history.
+#[pyfunction]
+fn play_history_mask(encoded: Vec<u16>) -> u32 {
+    history_mask(&decode_history(&encoded))
+}
+
+/// Attributes an ordered list of played cards to seats, starting with `leader`.
+#[pyfunction]
+fn attribute_plays(played_cards: Vec<u8>, leader: u8, trump: u8) -> PyResult<Vec<u16>> {
+    if leader >= 4 || played_cards.iter().any(|&c| c >= 32) || played_cards.len() > 32 {
+        return Err(PyValueError::new_err("Invalid leader or card index"));
+    }
+    Ok(encode_history(&attribute_played_cards(
+        leader,
+        trump,
+        &played_cards,
+    )))
+}
+
+#[pyfunction]
+#[pyo3(signature = (south_hand, num_samples, stage_config=None, seed=None, schema_version=None))]
+fn generate_positions_for_hand(
+    py: Python,
+    south_hand: u32,
+    num_samples: usize,
+    stage_config: Option<StageConfig>,
+    seed: Option<u64>,
+    schema_version: Option<u32>,
+) -> PyResult<PyObject> {
+    let version = SchemaVersion::parse(schema_version).map_err(PyValueError::new_err)?;
+    let config = stage_config.unwrap_or_default();
+    let batch = py.allow_threads(|| {
+        generate_positions_batch(south_hand, num_samples, &config, seed)
+            .map_err(PyValueError::new_err)
+    })?;
+    Ok(raw_batch_for_version(py, batch, version))
+}
+
+/// With PIMC, `score_label` selects the score of each sample: "double_dummy" (value
+/// of the true deal, the default) or "ev" (mean value over the sampled worlds).
+/// `objective` ("points", "tricks" or "lexicographic", see `solve_game`) sets
+/// what the scores count. `optimal_epsilon` solves every root move and fills `optimal_cards` with all the
+/// cards within that many points of the best one (schema version 3 or later).
+/// `schema_version=1` returns the legacy (best_cards, best_scores, valid) tuple.
+/// From version 4, `invalid_reasons` tells why a sample is invalid: 1 the deal is
+/// over, 2 no legal card, 3 an inconsistent board, 4 a history that does not fit the
+/// board and tricks won, 5 hand sizes that do not fit the trick starter, 6 an
+/// illegal board card, 7 a solve longer than `time_limit_ms` (see
+/// `invalid_reason_counts`). From version 6, `forced` flags the samples whose seat to
+/// move had a single legal card; with `skip_forced`, those are not solved but
+/// labelled with their only card and a NaN score.
+/// `pimc_error_rate` makes PIMC adaptive: worlds are solved until the vote leader is
+/// decided (a sequential test against the runner-up, wrong at most that often) or
+/// `pimc_iterations` is reached, and at least `min_pimc_iterations`.
+/// With `max_exact_worlds`, positions with at most that many ways to deal the hidden
+/// cards solve every one of them once instead of sampling, which makes their labels
+/// exact and independent of the seed.
+/// `checkpoint`, `checkpoint_every` and `resume` work as in `solve_bidding_batch`.
+/// `plays` (the ordered plays of each sample, as `GameplayBatch.plays`) replays
+/// the samples from the deal, so that the scores count the points won and the
+/// belote announced before the position, and PIMC worlds keep the suits each seat
+/// showed it lacks; without them those are left out.
+#[pyfunction]
+#[pyo3(signature = (hands, boards, history, trumps, tricks_won, players, pimc_iterations, tt_log2=None, perspective="ns", declarers=None, seed=None, score_label="double_dummy", schema_version=None, checkpoint=None, checkpoint_every=1000, resume=false, optimal_epsilon=None, objective="points", plays=None, time_limit_ms=None, skip_forced=false, pimc_error_rate=None, min_pimc_iterations=8, max_exact_worlds=None))]
+#[allow(clippy::too_many_arguments)]
 fn solve_gameplay_batch(
     py: Python,
     hands: Vec<u32>,
@@ -77,35 +904,367 @@ fn solve_gameplay_batch(
     players: Vec<u8>,
     pimc_iterations: usize,
     tt_log2: Option<u8>,
-) -> PyResult<(Vec<u8>, Vec<i16>, Vec<bool>)> {
+    perspective: &str,
+    declarers: Option<Vec<u8>>,
+    seed: Option<u64>,
+    score_label: &str,
+    schema_version: Option<u32>,
+    checkpoint: Option<String>,
+    checkpoint_every: usize,
+    resume: bool,
+    optimal_epsilon: Option<f32>,
+    objective: &str,
+    plays: Option<Vec<Vec<u16>>>,
+    time_limit_ms: Option<u64>,
+    skip_forced: bool,
+    pimc_error_rate: Option<f64>,
+    min_pimc_iterations: usize,
+    max_exact_worlds: Option<u64>,
+) -> PyResult<PyObject> {
+    let perspective = Perspective::parse(perspective).map_err(PyValueError::new_err)?;
+    let score_label = ScoreLabel::parse(score_label).map_err(PyValueError::new_err)?;
+    let objective = Objective::parse(objective).map_err(PyValueError::new_err)?;
+    let version = SchemaVersion::parse(schema_version).map_err(PyValueError::new_err)?;
+    if version == SchemaVersion::V1 && score_label == ScoreLabel::Expected {
+        return Err(PyValueError::new_err(
+            "Expected-value labels need schema version 2 or later (V1 scores are integers)",
+        ));
+    }
+    if version < SchemaVersion::V3 && optimal_epsilon.is_some() {
+        return Err(PyValueError::new_err(
+            "Optimal card sets need schema version 3 or later",
+        ));
+    }
+    if optimal_epsilon.is_some_and(|e| e.is_nan() || e < 0.0) {
+        return Err(PyValueError::new_err(
+            "optimal_epsilon must be a non-negative number",
+        ));
+    }
+    warn_seeded_tt_sharing(py, seed)?;
+    let mut request = GameplaySolveRequest::new(
+        (hands, boards, history, trumps, tricks_won, players),
+        pimc_iterations,
+    )
+    .map_err(PyValueError::new_err)?;
+    if let Some(plays) = plays {
+        request = request.with_plays(plays).map_err(PyValueError::new_err)?;
+    }
+    request.options = GameplaySolveOptions {
+        tt_log2,
+        perspective,
+        declarers,
+        seed,
+        score_label,
+        objective,
+        optimal_epsilon,
+        time_limit: time_limit_ms.map(std::time::Duration::from_millis),
+        skip_forced,
+        adaptive: pimc_error_rate
+            .map(|rate| AdaptivePimc::new(min_pimc_iterations, rate))
+            .transpose()
+            .map_err(PyValueError::new_err)?,
+        max_exact_worlds,
+    };
+    let checkpoint = checkpoint.map(|p| CheckpointConfig::new(p, checkpoint_every, resume));
+    let batch = py.allow_threads(|| {
+        solve_gameplay_impl(&request, checkpoint.as_ref()).map_err(PyValueError::new_err)
+    })?;
+    Ok(match version {
+        SchemaVersion::V1 => solved_batch_v1(batch).into_py(py),
+        SchemaVersion::V2 => solved_batch_v2(batch).into_py(py),
+        SchemaVersion::V3 => solved_batch_v3(batch).into_py(py),
+        SchemaVersion::V4 | SchemaVersion::V5 => solved_batch_v4(batch).into_py(py),
+        SchemaVersion::V6 => batch.into_py(py),
+    })
+}
+
+/// Turns declarer scores (solved with perspective="declarer") into another
+/// training target, given the contract value of each sample: "points", "margin"
+/// (score - contract), "normalized" (margin / 162 clamped to [-1, 1]) or "outcome"
+/// (0 = down, 1 = made, 2 = made by 10 points or more).
+#[pyfunction]
+fn transform_score_labels(
+    scores: Vec<f32>,
+    contracts: Vec<u8>,
+    transform: &str,
+) -> PyResult<Vec<f32>> {
+    let transform = LabelTransform::parse(transform).map_err(PyValueError::new_err)?;
+    transform_labels(&scores, &contracts, transform).map_err(PyValueError::new_err)
+}
+
+/// Number of samples per outcome of the `invalid_reasons` of a solved gameplay batch,
+/// as (name, count) pairs: "valid", then every reason ("terminal", "no_legal_moves",
+/// ...) in code order.
+#[pyfunction]
+fn invalid_reason_counts(invalid_reasons: Vec<u8>) -> PyResult<Vec<(&'static str, usize)>> {
+    data_gen::gameplay::invalid_reason_counts(&invalid_reasons).map_err(PyValueError::new_err)
+}
+
+/// Columns of a gameplay dataset file for `schema_version` (default: latest).
+#[pyfunction]
+#[pyo3(signature = (schema_version=None))]
+fn gameplay_schema_columns(schema_version: Option<u32>) -> PyResult<Vec<&'static str>> {
+    let version = SchemaVersion::parse(schema_version).map_err(PyValueError::new_err)?;
+    Ok(version.gameplay_columns().to_vec())
+}
+
+/// Re-solves a random `sample_fraction` of the rows of a Parquet dataset (file or
+/// directory) and reports label mismatches.
+#[pyfunction]
+#[pyo3(signature = (path, sample_fraction, seed=None, tt_log2=None))]
+fn verify_dataset(
+    py: Python,
+    path: String,
+    sample_fraction: f64,
+    seed: Option<u64>,
+    tt_log2: Option<u8>,
+) -> PyResult<VerificationReport> {
+    py.allow_threads(|| {
+        verify_dataset_impl(&path, sample_fraction, seed, tt_log2).map_err(PyValueError::new_err)
+    })
+}
+
+/// Plays the bot strength `policy` ("heuristic", "pimc", "pimc_ev" or
+/// "double_dummy"; PIMC with `iterations` worlds, reproducible with `seed`) at the
+/// rows of a gameplay dataset (file or directory) and measures how often it plays
+/// an optimal card and the double-dummy value its cards lose. Only the first
+/// `max_rows` rows are read when given.
+#[pyfunction]
+#[pyo3(signature = (policy, dataset_path, iterations=20, seed=None, max_rows=None, tt_log2=None))]
+fn evaluate_policy_on_dataset(
+    py: Python,
+    policy: &str,
+    dataset_path: String,
+    iterations: usize,
+    seed: Option<u64>,
+    max_rows: Option<usize>,
+    tt_log2: Option<u8>,
+) -> PyResult<PolicyEvaluation> {
+    let strength = gameplay::bot::BotStrength::parse(policy).map_err(PyValueError::new_err)?;
     py.allow_threads(|| {
-        let (best_cards, best_scores, valid) = solve_gameplay_impl(
-            hands,
-            boards,
-            history,
-            trumps,
-            tricks_won,
-            players,
-            pimc_iterations,
+        data_gen::evaluate_policy_on_dataset(
+            std::path::Path::new(&dataset_path),
+            strength,
+            iterations,
+            seed,
+            max_rows,
             tt_log2,
-        );
-        Ok((best_cards, best_scores, valid))
+        )
+        .map_err(PyValueError::new_err)
     })
 }
 
+/// Deal ID of every row of a Parquet dataset (file or directory), from the 4 hands
+/// of `hands_column`. The cards of a `plays` column, when the file has one, are
+/// given back to their seats first, so gameplay positions give their deal's ID.
+#[pyfunction]
+#[pyo3(signature = (path, hands_column="hands"))]
+fn recompute_deal_ids(py: Python, path: String, hands_column: &str) -> PyResult<Vec<u128>> {
+    py.allow_threads(|| {
+        data_gen::recompute_deal_ids(std::path::Path::new(&path), hands_column)
+            .map_err(PyValueError::new_err)
+    })
+}
+
+/// Card index (0-31) of `rank` in `suit`; both accept `Suit`/`Rank` members.
+#[pyfunction]
+fn card_index(suit: u8, rank: u8) -> PyResult<u8> {
+    notation::card_index(suit, rank).map_err(PyValueError::new_err)
+}
+
+/// (`Suit`, `Rank`) of a card index.
+#[pyfunction]
+fn card_suit_rank(py: Python, card: u8) -> PyResult<(PyObject, PyObject)> {
+    if card >= 32 {
+        return Err(PyValueError::new_err(format!(
+            "Invalid card index {}",
+            card
+        )));
+    }
+    let (suit, rank) = CARD_ENUMS.get(py).expect("registered with the module");
+    Ok((suit.call1(py, (card / 8,))?, rank.call1(py, (card % 8,))?))
+}
+
+/// Name of a card index, such as "10S" or "AH".
+#[pyfunction]
+fn card_name(card: u8) -> PyResult<String> {
+    if card >= 32 {
+        return Err(PyValueError::new_err(format!(
+            "Invalid card index {}",
+            card
+        )));
+    }
+    Ok(notation::card_name(card))
+}
+
+/// Card index of a name such as "7D", "10S" or "AH".
+#[pyfunction]
+fn parse_card(name: &str) -> PyResult<u8> {
+    notation::parse_card(name).map_err(PyValueError::new_err)
+}
+
+/// The `Suit` and `Rank` types, once the module is initialised.
+static CARD_ENUMS: GILOnceCell<(PyObject, PyObject)> = GILOnceCell::new();
+
+/// Adds the `Suit` and `Rank` IntEnums, and the suits as module constants.
+/// Being ints, their members are accepted wherever a suit, trump or rank is.
+fn add_card_enums(py: Python, m: &PyModule) -> PyResult<()> {
+    let int_enum = py.import("enum")?.getattr("IntEnum")?;
+    let suit = int_enum.call1(("Suit", notation::SUIT_MEMBERS.to_vec()))?;
+    let rank = int_enum.call1(("Rank", notation::RANK_MEMBERS.to_vec()))?;
+    for enum_type in [suit, rank] {
+        enum_type.setattr("__module__", "coinche_engine")?;
+    }
+    m.add("Suit", suit)?;
+    m.add("Rank", rank)?;
+    for (name, _) in notation::SUIT_MEMBERS {
+        m.add(name, suit.getattr(name)?)?;
+    }
+    let _ = CARD_ENUMS.set(py, (suit.into(), rank.into()));
+    Ok(())
+}
+
+/// The `Phase` type, once the module is initialised.
+static PHASE_ENUM: GILOnceCell<PyObject> = GILOnceCell::new();
+
+/// Adds the `Phase` IntEnum of `CoincheMatch.phase`.
+fn add_phase_enum(py: Python, m: &PyModule) -> PyResult<()> {
+    let int_enum = py.import("enum")?.getattr("IntEnum")?;
+    let phase = int_enum.call1(("Phase", gameplay::manager::PHASE_MEMBERS.to_vec()))?;
+    phase.setattr("__module__", "coinche_engine")?;
+    m.add("Phase", phase)?;
+    let _ = PHASE_ENUM.set(py, phase.into());
+    Ok(())
+}
+
 /// A Python module implemented in Rust.
 #[pymodule]
-fn coinche_engine(_py: Python, m: &PyModule) -> PyResult<()> {
+fn coinche_engine(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<gameplay::playing::PlayingState>()?;
     m.add_class::<gameplay::manager::CoincheMatch>()?;
     m.add_class::<gameplay::manager::MatchResult>()?;
+    m.add_class::<gameplay::action_log::LoggedAction>()?;
+    m.add_class::<gameplay::explore::Exploration>()?;
+    m.add_class::<gameplay::play_stats::PlayStats>()?;
+    m.add_class::<CompletedTrick>()?;
+    m.add_class::<gameplay::threshold_bidder::ThresholdBidder>()?;
+    m.add_class::<gameplay::bot_preset::BotPreset>()?;
+    m.add_class::<gameplay::stats::MatchStats>()?;
+    m.add_class::<duplicate::ParResult>()?;
+    m.add_class::<gameplay::game::CoincheGame>()?;
+    m.add_class::<gameplay::game::DealScore>()?;
     m.add_class::<gameplay::bidding::Bid>()?;
     m.add_class::<gameplay::bidding::BiddingState>()?;
+    m.add_class::<gameplay::clock::TimeControl>()?;
+    m.add_class::<gameplay::rules::RuleSet>()?;
+    m.add_class::<StageConfig>()?;
+    m.add_class::<GameplaySample>()?;
+    m.add_class::<GameplayBatch>()?;
+    m.add_class::<SelfPlayGame>()?;
+    m.add_class::<data_gen::HumanPlayGame>()?;
+    m.add_class::<VecCoincheEnv>()?;
+    m.add_class::<HandBuilder>()?;
+    m.add_class::<data_gen::DealQuota>()?;
+    m.add(
+        "TooManyForcedCardsError",
+        py.get_type::<data_gen::common::TooManyForcedCardsError>(),
+    )?;
+    m.add(
+        "ShapeImpossibleError",
+        py.get_type::<data_gen::common::ShapeImpossibleError>(),
+    )?;
+    m.add(
+        "UnsatisfiableError",
+        py.get_type::<data_gen::common::UnsatisfiableError>(),
+    )?;
+    m.add_class::<VerificationReport>()?;
+    m.add_class::<PolicyEvaluation>()?;
+    m.add_class::<PimcDecision>()?;
+    m.add_class::<PimcConfidence>()?;
+    m.add_class::<BidConstraint>()?;
+    m.add_class::<CardAnalysis>()?;
+    m.add_class::<PositionAnalysis>()?;
 
     m.add_function(wrap_pyfunction!(solve_game, m)?)?;
+    m.add_function(wrap_pyfunction!(solve_pimc, m)?)?;
+    m.add_function(wrap_pyfunction!(forces_capot, m)?)?;
+    m.add_function(wrap_pyfunction!(analyze_hand, m)?)?;
+    m.add_function(wrap_pyfunction!(analyze_position, m)?)?;
+    m.add_function(wrap_pyfunction!(imps, m)?)?;
+    m.add_function(wrap_pyfunction!(net_score, m)?)?;
+    m.add_function(wrap_pyfunction!(duplicate_swing, m)?)?;
+    m.add_function(wrap_pyfunction!(par_result, m)?)?;
+    m.add_function(wrap_pyfunction!(par_swing, m)?)?;
+    m.add_function(wrap_pyfunction!(load_deal_corpus, m)?)?;
+    m.add_function(wrap_pyfunction!(set_partition_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(open_deal_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(close_deal_cache, m)?)?;
+    m.add_function(wrap_pyfunction!(deal_cache_stats, m)?)?;
+    m.add_function(wrap_pyfunction!(set_pimc_tt_sharing, m)?)?;
+    m.add_function(wrap_pyfunction!(set_solve_options, m)?)?;
+    m.add_function(wrap_pyfunction!(validate_deal, m)?)?;
+    m.add_function(wrap_pyfunction!(deal_id, m)?)?;
+    m.add_function(wrap_pyfunction!(deal_from_id, m)?)?;
+    m.add_function(wrap_pyfunction!(deal_random, m)?)?;
+    m.add_function(wrap_pyfunction!(deal_ids, m)?)?;
     m.add_function(wrap_pyfunction!(generate_bidding_hands, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_constrained_deals, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_quota_deals, m)?)?;
     m.add_function(wrap_pyfunction!(solve_bidding_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(solve_all_leaders, m)?)?;
+    m.add_function(wrap_pyfunction!(solve_all_leaders_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_opening_lead_batch, m)?)?;
+    m.add_class::<OpeningLeadSample>()?;
+    m.add_function(wrap_pyfunction!(generate_evaluation_batch, m)?)?;
+    m.add_class::<EvaluationSample>()?;
+    m.add_function(wrap_pyfunction!(hand_percentile, m)?)?;
+    m.add_function(wrap_pyfunction!(deal_difficulty, m)?)?;
+    m.add_function(wrap_pyfunction!(deal_difficulty_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(downsample_easy_positions, m)?)?;
+    m.add_function(wrap_pyfunction!(deal_tags, m)?)?;
+    m.add_function(wrap_pyfunction!(deal_tags_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_puzzles, m)?)?;
+    m.add_class::<data_gen::PuzzleConstraints>()?;
+    m.add_class::<data_gen::Puzzle>()?;
     m.add_function(wrap_pyfunction!(generate_raw_gameplay_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_selfplay_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_human_play_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(generate_positions_for_hand, m)?)?;
+    m.add_function(wrap_pyfunction!(encode_hand, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_hand, m)?)?;
+    m.add_function(wrap_pyfunction!(encode_card, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_card, m)?)?;
+    m.add_function(wrap_pyfunction!(encode_trick, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_trick, m)?)?;
+    m.add_function(wrap_pyfunction!(encode_gameplay_features, m)?)?;
+    m.add_function(wrap_pyfunction!(encode_auction_features, m)?)?;
+    m.add("GAMEPLAY_FEATURES", encoding::GAMEPLAY_FEATURES)?;
+    m.add("EVALUATION_FEATURES", encoding::EVALUATION_FEATURES)?;
+    m.add("AUCTION_FEATURES", encoding::AUCTION_FEATURES)?;
+    m.add("AUCTION_HISTORY_BIDS", encoding::AUCTION_HISTORY_BIDS)?;
+    m.add_function(wrap_pyfunction!(flat_action_index, m)?)?;
+    m.add_function(wrap_pyfunction!(flat_action, m)?)?;
+    m.add_function(wrap_pyfunction!(dual_head_action_index, m)?)?;
+    m.add_function(wrap_pyfunction!(dual_head_action, m)?)?;
+    m.add("AUCTION_ACTIONS", encoding::AUCTION_ACTIONS)?;
+    m.add("FLAT_ACTIONS", encoding::FLAT_ACTIONS)?;
+    m.add_function(wrap_pyfunction!(encode_play_history, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_play_history, m)?)?;
+    m.add_function(wrap_pyfunction!(play_history_mask, m)?)?;
+    m.add_function(wrap_pyfunction!(attribute_plays, m)?)?;
     m.add_function(wrap_pyfunction!(solve_gameplay_batch, m)?)?;
+    m.add_function(wrap_pyfunction!(transform_score_labels, m)?)?;
+    m.add_function(wrap_pyfunction!(invalid_reason_counts, m)?)?;
+    m.add_function(wrap_pyfunction!(gameplay_schema_columns, m)?)?;
+    m.add("SCHEMA_VERSION", SchemaVersion::LATEST.number())?;
+    m.add_function(wrap_pyfunction!(verify_dataset, m)?)?;
+    m.add_function(wrap_pyfunction!(evaluate_policy_on_dataset, m)?)?;
+    m.add_function(wrap_pyfunction!(recompute_deal_ids, m)?)?;
+    add_card_enums(py, m)?;
+    add_phase_enum(py, m)?;
+    m.add_function(wrap_pyfunction!(card_index, m)?)?;
+    m.add_function(wrap_pyfunction!(card_suit_rank, m)?)?;
+    m.add_function(wrap_pyfunction!(card_name, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_card, m)?)?;
     Ok(())
 }
@@ -0,0 +1,129 @@
+//! Hot-path call counters, compiled in only with the `profiling` feature:
+//! `cargo build --release --features profiling`. Without it `count` is empty and
+//! the report has no entries, so the solver pays nothing.
+//!
+//! Counters are global atomics shared by all threads: totals are exact, but the
+//! contention slows the search down, so time profiled builds only relative to
+//! each other. Batch solvers dump the report to stderr when they finish.
+
+#[cfg(feature = "profiling")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Counter {
+    /// `minimax` calls.
+    Nodes,
+    /// `PlayingState::get_legal_moves` calls.
+    LegalMoves,
+    /// Full Zobrist hashes (incremental updates not included).
+    Hashes,
+    TtProbes,
+    TtHits,
+    TtStores,
+    PartitionProbes,
+    PartitionHits,
+    /// Static evaluations at the search horizon.
+    Evaluations,
+}
+
+pub const COUNTERS: [Counter; 9] = [
+    Counter::Nodes,
+    Counter::LegalMoves,
+    Counter::Hashes,
+    Counter::TtProbes,
+    Counter::TtHits,
+    Counter::TtStores,
+    Counter::PartitionProbes,
+    Counter::PartitionHits,
+    Counter::Evaluations,
+];
+
+impl Counter {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Counter::Nodes => "nodes",
+            Counter::LegalMoves => "legal_moves",
+            Counter::Hashes => "hashes",
+            Counter::TtProbes => "tt_probes",
+            Counter::TtHits => "tt_hits",
+            Counter::TtStores => "tt_stores",
+            Counter::PartitionProbes => "partition_probes",
+            Counter::PartitionHits => "partition_hits",
+            Counter::Evaluations => "evaluations",
+        }
+    }
+}
+
+#[cfg(feature = "profiling")]
+static COUNTS: [AtomicU64; COUNTERS.len()] = [const { AtomicU64::new(0) }; COUNTERS.len()];
+
+/// Adds one to `counter`.
+#[inline(always)]
+pub fn count(counter: Counter) {
+    #[cfg(feature = "profiling")]
+    COUNTS[counter as usize].fetch_add(1, Ordering::Relaxed);
+    #[cfg(not(feature = "profiling"))]
+    let _ = counter;
+}
+
+pub fn enabled() -> bool {
+    cfg!(feature = "profiling")
+}
+
+/// Every counter and its total since the last `reset`; empty without the feature.
+pub fn report() -> Vec<(&'static str, u64)> {
+    #[cfg(feature = "profiling")]
+    return COUNTERS
+        .iter()
+        .map(|&c| (c.name(), COUNTS[c as usize].load(Ordering::Relaxed)))
+        .collect();
+    #[cfg(not(feature = "profiling"))]
+    Vec::new()
+}
+
+pub fn reset() {
+    #[cfg(feature = "profiling")]
+    for count in &COUNTS {
+        count.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Prints the report of a finished batch run to stderr, then resets the counters.
+pub fn dump(label: &str) {
+    if !enabled() {
+        return;
+    }
+    let nodes = report()
+        .iter()
+        .find(|(name, _)| *name == Counter::Nodes.name())
+        .map_or(0, |&(_, n)| n);
+    eprintln!("profile [{}]:", label);
+    for (name, total) in report() {
+        let per_node = total as f64 / nodes.max(1) as f64;
+        eprintln!("  {:<18} {:>14} {:>8.3}/node", name, total, per_node);
+    }
+    reset();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::playing::{PlayingState, HEARTS};
+
+    #[test]
+    fn test_counters_follow_feature() {
+        let mut state = PlayingState::new(HEARTS);
+        state.hands = [0x0000_000F, 0x0000_00F0, 0x0000_0F00, 0x0000_F000];
+        crate::solver::solve(&state, false, Some(16), None);
+        let report = report();
+        if enabled() {
+            // Other tests solve concurrently: only check that these counted.
+            let total = |name| report.iter().find(|r| r.0 == name).unwrap().1;
+            assert!(total("nodes") > 0 && total("legal_moves") > 0);
+            assert!(total("tt_probes") >= total("tt_hits"));
+            assert_eq!(report.len(), COUNTERS.len());
+        } else {
+            assert!(report.is_empty());
+        }
+    }
+}
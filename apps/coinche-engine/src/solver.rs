@@ -1,8 +1,54 @@
-use crate::gameplay::playing::PlayingState;
+use crate::gameplay::playing::{
+    card_points, cards_points, PlayingState, ALL_TRUMP, LAST_TRICK_BONUS,
+};
+use crate::profiling::{self, Counter};
 use std::cmp::{max, min};
 use std::collections::HashMap;
 
-const INF: i16 = 1000;
+mod bounds;
+mod capot;
+mod check;
+mod deal_cache;
+mod mcts;
+mod partition;
+mod tt;
+pub use capot::{forces_capot, forces_capot_within};
+pub use check::{cross_check, random_ending, reference_value, MAX_CHECK_CARDS};
+pub use deal_cache::{
+    close_deal_cache, deal_cache_stats, open_deal_cache, solve_deal, DealCache, DealCacheStats,
+};
+pub use mcts::{equivalent_moves, solve_mcts};
+pub use partition::{
+    clear_partition_cache, partition_cache_enabled, partition_hits, set_partition_cache,
+};
+use tt::TTEntry;
+pub use tt::{
+    new_tt_scope, pimc_tt_scope, pimc_tt_sharing_enabled, set_pimc_tt_sharing, tt_stats,
+    with_tt_scope, TtStats,
+};
+
+/// Search values: points, tricks or a mix of both (see `Objective`), for one team.
+/// Wide enough for every objective and bonus with room to spare, so the search
+/// adds and subtracts them without overflow checks.
+pub type Score = i32;
+
+/// Bound of the root search window, beyond any value a search can return
+/// (`check_score_range` enforces it).
+pub const INF: Score = 1_000_000;
+
+/// Checks that every final value of `team` reachable from `state` lies strictly
+/// inside the search window.
+fn check_score_range(state: &PlayingState, team: usize, objective: Objective) {
+    let (lower, upper) = objective.bounds(state, team);
+    debug_assert!(
+        -INF < lower && upper < INF,
+        "{:?} values {}..{} exceed the search window of {}",
+        objective,
+        lower,
+        upper,
+        INF
+    );
+}
 
 use lazy_static::lazy_static;
 use rand::rngs::StdRng;
@@ -18,6 +64,9 @@ struct ZobristTable {
     turn: [u64; 4],
     // [team] - If team has won at least one trick (makes opponent Capot impossible)
     has_won_trick: [u64; 2],
+    // [team] - If team has scored the belote: positions of different PIMC worlds may
+    // share cards and differ in who still can (see `with_tt_scope`)
+    belote_scored: [u64; 2],
 }
 
 impl ZobristTable {
@@ -28,6 +77,7 @@ impl ZobristTable {
             trick: [[0; 32]; 4],
             turn: [0; 4],
             has_won_trick: [0; 2],
+            belote_scored: [0; 2],
         };
 
         for p in 0..4 {
@@ -39,11 +89,13 @@ impl ZobristTable {
         }
         table.has_won_trick[0] = rng.gen();
         table.has_won_trick[1] = rng.gen();
+        table.belote_scored[0] = rng.gen();
+        table.belote_scored[1] = rng.gen();
         table
     }
 }
 
-use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 
 lazy_static! {
     static ref ZOBRIST: ZobristTable = ZobristTable::new();
@@ -53,39 +105,100 @@ static TOTAL_NODES: AtomicU64 = AtomicU64::new(0);
 static TT_HITS: AtomicU64 = AtomicU64::new(0);
 static HAND_COUNT: AtomicUsize = AtomicUsize::new(0);
 
-// Fixed-size TT
-const TT_SIZE: usize = 1 << 24; // 16 Million entries ~ 256MB
-const TT_MASK: u64 = (TT_SIZE as u64) - 1;
+use std::cell::Cell;
+use std::time::Instant;
+
+// Bound flags of stored scores (TT and partition cache)
+const EXACT: u8 = 0;
+const LOWER_BOUND: u8 = 1;
+const UPPER_BOUND: u8 = 2;
+
+/// Alpha-beta window in the frame of stored scores: points `team` still wins
+/// from the node on (final points minus those already won). Stored bounds are
+/// only ever compared and classified in this frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Window {
+    alpha: Score,
+    beta: Score,
+}
 
-use std::cell::RefCell;
+impl Window {
+    fn relative(alpha: Score, beta: Score, current_points: Score) -> Self {
+        Window {
+            alpha: alpha - current_points,
+            beta: beta - current_points,
+        }
+    }
 
-#[derive(Clone, Copy)]
-struct TTEntry {
-    key: u64, // For collision detection
-    score: i16,
-    best_move: u8,
-    flag: u8,
-    depth: u8, // Added for Iterative Deepening
-    gen: u32,  // Generation ID for zero-cost reset
-}
+    /// Applies a stored bound: returns the score if it decides the node,
+    /// otherwise narrows the window.
+    fn apply(&mut self, score: Score, flag: u8) -> Option<Score> {
+        match flag {
+            EXACT => return Some(score),
+            LOWER_BOUND if score >= self.beta => return Some(score),
+            LOWER_BOUND => self.alpha = max(self.alpha, score),
+            UPPER_BOUND if score <= self.alpha => return Some(score),
+            UPPER_BOUND => self.beta = min(self.beta, score),
+            _ => {}
+        }
+        (self.alpha >= self.beta).then_some(score)
+    }
 
-impl Default for TTEntry {
-    fn default() -> Self {
-        TTEntry {
-            key: 0,
-            score: 0,
-            best_move: 0xFF,
-            flag: 0,
-            depth: 0,
-            gen: 0,
+    /// Flag of a fail-soft search result `score` obtained with this window.
+    fn classify(&self, score: Score) -> u8 {
+        if score <= self.alpha {
+            UPPER_BOUND
+        } else if score >= self.beta {
+            LOWER_BOUND
+        } else {
+            EXACT
         }
     }
 }
 
 // Thread Local Storage for Persistent TT
 thread_local! {
-    static TT: RefCell<Vec<TTEntry>> = RefCell::new(vec![TTEntry::default(); TT_SIZE]);
-    static TT_GEN: RefCell<u32> = RefCell::new(1); // Start at generation 1
+    static NODE_COUNT: Cell<u64> = const { Cell::new(0) }; // Nodes visited by this thread
+    static DEADLINE: Cell<Option<Instant>> = const { Cell::new(None) }; // See `with_deadline`
+    static ABORTED: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Nodes between two deadline checks.
+const DEADLINE_CHECK_NODES: u64 = 4096;
+
+/// Runs `f` with the searches of the calling thread interrupted once `deadline`
+/// passes. Returns `None` when that happened: whatever `f` computed is then
+/// meaningless. Interrupted nodes are kept out of the TT and partition cache.
+pub fn with_deadline<T>(deadline: Instant, f: impl FnOnce() -> T) -> Option<T> {
+    DEADLINE.with(|d| d.set(Some(deadline)));
+    ABORTED.with(|a| a.set(Instant::now() >= deadline));
+    let result = f();
+    DEADLINE.with(|d| d.set(None));
+    (!ABORTED.with(|a| a.replace(false))).then_some(result)
+}
+
+/// Whether a search of the calling thread ran out of time under `with_deadline`, in
+/// which case its result must not be used.
+pub fn search_aborted() -> bool {
+    ABORTED.with(|a| a.get())
+}
+
+/// Whether the running search ran out of time, checking the clock every
+/// `DEADLINE_CHECK_NODES` nodes.
+fn out_of_time(nodes: u64) -> bool {
+    ABORTED.with(|a| {
+        if !a.get() && nodes.is_multiple_of(DEADLINE_CHECK_NODES) {
+            let deadline = DEADLINE.with(|d| d.get());
+            a.set(deadline.is_some_and(|d| Instant::now() >= d));
+        }
+        a.get()
+    })
+}
+
+/// Cumulative number of nodes searched by the calling thread.
+/// Take the difference around a solve to get its cost.
+pub fn nodes_searched() -> u64 {
+    NODE_COUNT.with(|n| n.get())
 }
 
 // Helper to check if we are solving the first hand (for debug stats)
@@ -95,6 +208,7 @@ fn is_first_hand() -> bool {
 
 // Optimized Zobrist Hash using bit iteration
 fn compute_zobrist_hash(state: &PlayingState) -> u64 {
+    profiling::count(Counter::Hashes);
     let mut h: u64 = 0;
 
     // Hands - Iterate only set bits
@@ -126,9 +240,29 @@ fn compute_zobrist_hash(state: &PlayingState) -> u64 {
         h ^= ZOBRIST.has_won_trick[1];
     }
 
+    for team in 0..2 {
+        if state.belote_scored[team] {
+            h ^= ZOBRIST.belote_scored[team];
+        }
+    }
+
     h
 }
 
+// Ranks of a suit from strongest to weakest, ordered as `PlayingState` compares them.
+fn ranks_by_strength(suit: u8, trump: u8) -> [u8; 8] {
+    let strength = if suit == trump {
+        &crate::gameplay::playing::RANK_STRENGTH_TRUMP
+    } else {
+        &crate::gameplay::playing::RANK_STRENGTH_NON_TRUMP
+    };
+    let mut order = [0u8; 8];
+    for (rank, &s) in strength.iter().enumerate() {
+        order[7 - s as usize] = rank as u8;
+    }
+    order
+}
+
 // Heuristic Evaluation
 // Returns estimated final score delta for Team 0 (NS) relative to current points?
 // No, minimax returns absolute score for Team 0.
@@ -138,22 +272,28 @@ fn compute_zobrist_hash(state: &PlayingState) -> u64 {
 // Actually, standard minimax returns the leaf value.
 // If we cut off, we return static evaluation of the state.
 // Static Eval = state.points[0] + MaterialDifference?
-// Coinche is zero-sum (total points fixed ~162).
+// Coinche is zero-sum (card points always total 162 with the dix de der).
 // So MAXimizing Player 0 wants to maximize Pts0. MINimizing Player 1 wants to minimize Pts0.
 // Eval = state.points[0] + (Material0 / (Material0 + Material1)) * RemainingPoints?
 // Simpler: Eval = state.points[0] + MaterialHeuristic(Team0) - MaterialHeuristic(Team1)?
 // Let's use a weighted material sum.
-fn evaluate_state(state: &PlayingState) -> i16 {
-    let current_score = state.points[0] as i32;
-    let opponent_score = state.points[1] as i32;
+// `team` is the side whose points are being estimated (0 = NS, 1 = EW). Tricks
+// still to play are shared out the same way as the points.
+fn evaluate_state(state: &PlayingState, team: usize, objective: Objective) -> Score {
+    let current_score = state.points[team] as i32;
 
-    // Total points in a standard game is 162 (excluding Belote)
-    // Remaining points to fight for
-    let remaining_points = 162 - current_score - opponent_score;
+    if state.is_terminal() {
+        return objective.value(state, team);
+    }
 
-    if remaining_points <= 0 {
-        return current_score as i16;
+    // Remaining points to fight for: cards still in hands or on the table, plus
+    // the dix de der. Counted from the cards so NT/AT card values are right; a
+    // belote still to be announced is not counted.
+    let mut in_play = state.hands.iter().fold(0u32, |m, &h| m | h);
+    for &c in state.current_trick.iter().filter(|&&c| c < 32) {
+        in_play |= 1 << c;
     }
+    let remaining_points = (cards_points(in_play, state.trump) + LAST_TRICK_BONUS) as i32;
 
     let mut strength0: i32 = 0;
     let mut strength1: i32 = 0;
@@ -162,7 +302,7 @@ fn evaluate_state(state: &PlayingState) -> i16 {
 
     for p in 0..4 {
         let mut hand = state.hands[p];
-        let is_team0 = p % 2 == 0;
+        let is_own_team = p % 2 == team;
 
         while hand != 0 {
             let c = hand.trailing_zeros() as u8;
@@ -171,12 +311,12 @@ fn evaluate_state(state: &PlayingState) -> i16 {
             let s = c / 8;
             let r = (c % 8) as usize;
 
-            let val;
-            let control;
-
-            if s == trump {
-                val = crate::gameplay::playing::POINTS_TRUMP[r] as i32;
-                control = match r {
+            let val = card_points(c, trump) as i32;
+            // In All-Trump every suit gets trump-like control values; in No-Trump
+            // none does. Provisional: tricks (`PlayingState::is_card_better`) still
+            // rank All-Trump suits in the plain order until the AT trick rules land.
+            let control = if trump == ALL_TRUMP || s == trump {
+                match r {
                     4 => 50, // J
                     2 => 35, // 9
                     7 => 25, // A
@@ -184,19 +324,18 @@ fn evaluate_state(state: &PlayingState) -> i16 {
                     6 => 15, // K
                     5 => 10, // Q
                     _ => 0,
-                };
+                }
             } else {
-                val = crate::gameplay::playing::POINTS_NON_TRUMP[r] as i32;
-                control = match r {
+                match r {
                     7 => 30, // A
                     3 => 20, // 10
                     6 => 10, // K
                     _ => 0,
-                };
-            }
+                }
+            };
 
             // Add to respective team's strength
-            if is_team0 {
+            if is_own_team {
                 strength0 += val + control;
             } else {
                 strength1 += val + control;
@@ -206,13 +345,244 @@ fn evaluate_state(state: &PlayingState) -> i16 {
 
     // Calculate expected additional points based on strength ratio
     let total_strength = strength0 + strength1;
-    let estimated_future = if total_strength > 0 {
-        (remaining_points * strength0) / total_strength
-    } else {
-        remaining_points / 2 // Fallback if no cards valuable (unlikely)
+    let share = |remaining: i32| {
+        if total_strength > 0 {
+            (remaining * strength0) / total_strength
+        } else {
+            remaining / 2 // Fallback if no cards valuable (unlikely)
+        }
     };
+    let tricks_left = state.hands[state.current_player as usize].count_ones() as i32;
+    let tricks = state.tricks_won[team] as i32 + share(tricks_left);
+
+    objective.combine(
+        tricks as Score,
+        (current_score + share(remaining_points)) as Score,
+    )
+}
+
+/// Final points of `team` the search assumes at its depth horizon: the static
+/// evaluation, kept within the points still reachable.
+pub fn static_evaluation(state: &PlayingState, team: usize) -> Score {
+    let (lower, upper) = Objective::Points.bounds(state, team);
+    evaluate_state(state, team, Objective::Points).clamp(lower, upper)
+}
+
+/// Which team's final points a solve reports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Perspective {
+    /// Always North-South (team 0), the historical convention.
+    Absolute,
+    /// Team of the player to move at the root.
+    CurrentPlayer,
+    /// Declaring team (needs the contract owner).
+    Declarer,
+}
+
+impl Perspective {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "ns" | "absolute" => Ok(Perspective::Absolute),
+            "current" | "current_player" => Ok(Perspective::CurrentPlayer),
+            "declarer" => Ok(Perspective::Declarer),
+            _ => Err(format!(
+                "Unknown perspective '{}' (expected 'ns', 'current' or 'declarer')",
+                name
+            )),
+        }
+    }
+
+    /// Canonical name, as stored in dataset metadata.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Perspective::Absolute => "ns",
+            Perspective::CurrentPlayer => "current",
+            Perspective::Declarer => "declarer",
+        }
+    }
+
+    /// Team (0 = NS, 1 = EW) whose points are reported for `state`.
+    pub fn team(&self, state: &PlayingState, declarer: Option<u8>) -> Result<usize, String> {
+        match self {
+            Perspective::Absolute => Ok(0),
+            Perspective::CurrentPlayer => Ok((state.current_player % 2) as usize),
+            Perspective::Declarer => declarer
+                .map(|d| (d % 2) as usize)
+                .ok_or_else(|| "Declarer perspective requires the contract owner".to_string()),
+        }
+    }
+}
+
+/// Points of a trick in the `Lexicographic` objective: more than a team can score.
+pub const LEXICOGRAPHIC_TRICK_WEIGHT: Score = 400;
+
+/// What the searching team maximizes (and the other team minimizes).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Objective {
+    /// Final points, bonuses included: the value of every other solver entry point.
+    Points,
+    /// Tricks won, for capot and generale analyses.
+    Tricks,
+    /// Tricks first, points to break ties: `tricks * LEXICOGRAPHIC_TRICK_WEIGHT + points`.
+    Lexicographic,
+}
+
+impl Objective {
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "points" => Ok(Objective::Points),
+            "tricks" => Ok(Objective::Tricks),
+            "lexicographic" => Ok(Objective::Lexicographic),
+            _ => Err(format!(
+                "Unknown objective '{}' (expected 'points', 'tricks' or 'lexicographic')",
+                name
+            )),
+        }
+    }
+
+    /// Canonical name, as stored in dataset metadata.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Objective::Points => "points",
+            Objective::Tricks => "tricks",
+            Objective::Lexicographic => "lexicographic",
+        }
+    }
+
+    fn combine(&self, tricks: Score, points: Score) -> Score {
+        match self {
+            Objective::Points => points,
+            Objective::Tricks => tricks,
+            Objective::Lexicographic => tricks * LEXICOGRAPHIC_TRICK_WEIGHT + points,
+        }
+    }
+
+    /// Value of `state` for `team` so far: its final value once the deal is over.
+    pub fn value(&self, state: &PlayingState, team: usize) -> Score {
+        self.combine(state.tricks_won[team] as Score, state.points[team] as Score)
+    }
+
+    /// (lower, upper) final values of `team` still reachable from `state`.
+    fn bounds(&self, state: &PlayingState, team: usize) -> (Score, Score) {
+        let (lower, upper) = state.team_score_bounds(team);
+        let won = state.tricks_won[team] as Score;
+        let tricks_left = if state.is_terminal() {
+            0
+        } else {
+            state.hands[state.current_player as usize].count_ones() as Score
+        };
+        (
+            self.combine(won, lower as Score),
+            self.combine(won + tricks_left, upper as Score),
+        )
+    }
+}
+
+/// Plies cut from the search of the discard in `SolveOptions::discard_pruning`.
+const DISCARD_REDUCTION: u8 = 4;
+
+static DISCARD_PRUNING: AtomicBool = AtomicBool::new(false);
+
+/// Experimental search settings trading exactness for speed. Off by default;
+/// `set_solve_options` changes the settings every solve starts from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SolveOptions {
+    /// "Pass the trick" pruning, in the spirit of null-move pruning: when following
+    /// to a trick worth no points, the side to move first tries its weakest
+    /// pointless card, searched one trick shallower. If that already decides the
+    /// node, the other cards are not searched. Values may be off by a few points
+    /// and results are kept out of the partition cache.
+    pub discard_pruning: bool,
+}
+
+/// Default options of every solve of the process (see `SolveOptions`).
+pub fn set_solve_options(options: SolveOptions) {
+    DISCARD_PRUNING.store(options.discard_pruning, Ordering::Relaxed);
+}
+
+pub fn solve_options() -> SolveOptions {
+    SolveOptions {
+        discard_pruning: DISCARD_PRUNING.load(Ordering::Relaxed),
+    }
+}
+
+/// Card `discard_pruning` tries first at `state`: the weakest legal card worth
+/// no points, when following to a trick that holds no points yet.
+fn pruning_discard(state: &PlayingState) -> Option<u8> {
+    let on_table = state.current_trick.iter().filter(|&&c| c < 32);
+    if state.trick_size == 0 || on_table.map(|&c| card_points(c, state.trump)).sum::<u16>() > 0 {
+        return None;
+    }
+    let legal = state.get_legal_moves();
+    (0..32u8)
+        .filter(|&c| legal & (1 << c) != 0 && card_points(c, state.trump) == 0)
+        .min_by_key(|&c| {
+            let strength = if c / 8 == state.trump || state.trump == ALL_TRUMP {
+                crate::gameplay::playing::RANK_STRENGTH_TRUMP
+            } else {
+                crate::gameplay::playing::RANK_STRENGTH_NON_TRUMP
+            };
+            (c / 8 == state.trump, strength[(c % 8) as usize], c)
+        })
+}
+
+/// Search used to solve a position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchMode {
+    /// Alpha-beta minimax (`solve_for_team`): exact to the depth searched.
+    Minimax,
+    /// Trick-level Monte Carlo tree search (`solve_mcts`): sampled, but its cost
+    /// only grows with `iterations`.
+    TrickMcts { iterations: u32 },
+}
+
+/// MCTS iterations when a mode is picked by name or by contract.
+pub const DEFAULT_MCTS_ITERATIONS: u32 = 10_000;
+
+impl SearchMode {
+    /// Search suited to a contract: MCTS for All-Trump, whose forced overtaking
+    /// leaves minimax too many lines to reach the end of the deal, minimax otherwise.
+    pub fn for_contract(trump: u8) -> Self {
+        if trump == ALL_TRUMP {
+            SearchMode::TrickMcts {
+                iterations: DEFAULT_MCTS_ITERATIONS,
+            }
+        } else {
+            SearchMode::Minimax
+        }
+    }
 
-    (current_score + estimated_future) as i16
+    /// "minimax", "mcts" (`iterations` iterations) or "auto" (`for_contract`).
+    pub fn parse(name: &str, trump: u8, iterations: u32) -> Result<Self, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "minimax" => Ok(SearchMode::Minimax),
+            "mcts" => Ok(SearchMode::TrickMcts { iterations }),
+            "auto" => Ok(match SearchMode::for_contract(trump) {
+                SearchMode::TrickMcts { .. } => SearchMode::TrickMcts { iterations },
+                mode => mode,
+            }),
+            _ => Err(format!(
+                "Unknown search '{}' (expected 'minimax', 'mcts' or 'auto')",
+                name
+            )),
+        }
+    }
+}
+
+/// Final points of `team` and the best move, by the search `mode` selects.
+/// `max_depth_force` and `tt_log2` only apply to minimax, `seed` to MCTS.
+pub fn solve_with_mode(
+    state: &PlayingState,
+    team: usize,
+    mode: SearchMode,
+    max_depth_force: Option<u8>,
+    tt_log2: Option<u8>,
+    seed: Option<u64>,
+) -> (Score, u8) {
+    match mode {
+        SearchMode::Minimax => solve_for_team(state, team, max_depth_force, tt_log2),
+        SearchMode::TrickMcts { iterations } => solve_mcts(state, team, iterations, seed),
+    }
 }
 
 // Output: (Score, BestMove)
@@ -220,19 +590,44 @@ pub fn solve(
     state: &PlayingState,
     _generate_graph: bool,
     max_depth_force: Option<u8>,
+    tt_log2: Option<u8>,
+) -> (Score, u8) {
+    solve_for_team(state, 0, max_depth_force, tt_log2)
+}
+
+/// Same as `solve`, but the score is the final points of `team` (0 = NS, 1 = EW):
+/// that team maximizes, the other one minimizes.
+pub fn solve_for_team(
+    state: &PlayingState,
+    team: usize,
+    max_depth_force: Option<u8>,
+    tt_log2: Option<u8>,
+) -> (Score, u8) {
+    solve_with_objective(state, team, Objective::Points, max_depth_force, tt_log2)
+}
+
+/// Same as `solve_for_team`, with the score in the units of `objective`.
+pub fn solve_with_objective(
+    state: &PlayingState,
+    team: usize,
+    objective: Objective,
+    max_depth_force: Option<u8>,
     _tt_log2: Option<u8>,
-) -> (i16, u8) {
+) -> (Score, u8) {
+    solve_with_options(state, team, objective, max_depth_force, solve_options())
+}
+
+/// Same as `solve_with_objective`, with explicit `options` instead of the
+/// process defaults.
+pub fn solve_with_options(
+    state: &PlayingState,
+    team: usize,
+    objective: Objective,
+    max_depth_force: Option<u8>,
+    options: SolveOptions,
+) -> (Score, u8) {
     // 1. Manage Generation ID (Zero-Cost Clear)
-    let my_gen = TT_GEN.with(|g| {
-        let mut gen = g.borrow_mut();
-        // Increment generation for new solve.
-        // If we wrap around (very rare), we should technically clear, but 4 billion hands is fine.
-        *gen = gen.wrapping_add(1);
-        if *gen == 0 {
-            *gen = 1;
-        } // Avoid 0 if we want default to be 0
-        *gen
-    });
+    let my_gen = tt::new_generation();
 
     // We don't allocate TT here anymore!
 
@@ -242,6 +637,7 @@ pub fn solve(
         TT_HITS.store(0, Ordering::Relaxed);
     }
 
+    check_score_range(state, team, objective);
     let hash = compute_zobrist_hash(state);
 
     let cards_left = state.hands[state.current_player as usize].count_ones() as u8;
@@ -251,10 +647,18 @@ pub fn solve(
         min(cards_left * 4, 8)
     };
 
+    let ctx = SearchContext {
+        gen: my_gen,
+        team,
+        objective,
+        options,
+        debug: is_first,
+    };
+
     let mut best_score = 0;
     let mut best_move = 0xFF;
 
-    fn try_claim(state: &PlayingState) -> Option<(i16, u8)> {
+    fn try_claim(state: &PlayingState) -> Option<(Score, u8)> {
         if state.trick_size > 0 {
             return None;
         }
@@ -276,13 +680,7 @@ pub fn solve(
         let mut points = 0;
         for i in 0..32 {
             if (all_hands & (1 << i)) != 0 {
-                let suit = i / 8;
-                let rank = i % 8;
-                if suit == state.trump as usize {
-                    points += crate::gameplay::playing::POINTS_TRUMP[rank] as i16;
-                } else {
-                    points += crate::gameplay::playing::POINTS_NON_TRUMP[rank] as i16;
-                }
+                points += card_points(i as u8, state.trump) as Score;
             }
         }
         points += 10;
@@ -293,7 +691,7 @@ pub fn solve(
         }
 
         let best_move = my_hand.trailing_zeros() as u8;
-        Some(((state.points[team] as i16) + points, best_move))
+        Some(((state.points[team] as Score) + points, best_move))
     }
 
     for depth in 1..=max_depth {
@@ -305,7 +703,7 @@ pub fn solve(
             */
         }
 
-        let (score, mv) = minimax(state, hash, -INF, INF, my_gen, depth, is_first);
+        let (score, mv) = minimax(state, hash, -INF, INF, depth, &ctx);
         best_score = score;
         best_move = mv;
     }
@@ -318,65 +716,185 @@ pub fn solve(
     (best_score, best_move)
 }
 
+/// Double-dummy NS points of `hands` played in `trump`, once for each seat leading
+/// the first trick: entry `i` is the value when seat `i` leads. Looked up in the
+/// deal cache when one is open.
+pub fn solve_all_leaders(hands: &[u32; 4], trump: u8, tt_log2: Option<u8>) -> [Score; 4] {
+    std::array::from_fn(|leader| solve_deal(hands, trump, leader as u8, tt_log2))
+}
+
+/// Value for `team` of every legal move at the root, each move solved to the end.
+pub fn solve_root_moves(
+    state: &PlayingState,
+    team: usize,
+    max_depth_force: Option<u8>,
+    tt_log2: Option<u8>,
+) -> Vec<(u8, Score)> {
+    solve_root_moves_with_objective(state, team, Objective::Points, max_depth_force, tt_log2)
+}
+
+/// Same as `solve_root_moves`, with the values in the units of `objective`.
+pub fn solve_root_moves_with_objective(
+    state: &PlayingState,
+    team: usize,
+    objective: Objective,
+    max_depth_force: Option<u8>,
+    tt_log2: Option<u8>,
+) -> Vec<(u8, Score)> {
+    let legal = state.get_legal_moves();
+    (0..32u8)
+        .filter(|&c| legal & (1 << c) != 0)
+        .map(|card| {
+            let mut child = *state;
+            child.play_card(card);
+            let score = if child.is_terminal() {
+                objective.value(&child, team)
+            } else {
+                solve_with_objective(&child, team, objective, max_depth_force, tt_log2).0
+            };
+            (card, score)
+        })
+        .collect()
+}
+
 /*
 fn generate_dot_file(root_state: &PlayingState, tt: &HashMap<u64, TTEntry>) {
     // ... (content commented out for now as it needs update for Vec TT and Zobrist)
 }
 */
 
+// Per-solve constants threaded through the search
+struct SearchContext {
+    gen: u32,    // TT generation of this solve
+    team: usize, // Maximizing team
+    objective: Objective,
+    options: SolveOptions,
+    debug: bool, // Collect global stats (first hand only)
+}
+
 fn minimax(
     state: &PlayingState,
     hash: u64,
-    mut alpha: i16,
-    mut beta: i16,
-    my_gen: u32,
+    mut alpha: Score,
+    mut beta: Score,
     depth: u8,
-    debug: bool,
-) -> (i16, u8) {
+    ctx: &SearchContext,
+) -> (Score, u8) {
+    let (my_gen, team, objective, debug) = (ctx.gen, ctx.team, ctx.objective, ctx.debug);
+    profiling::count(Counter::Nodes);
+    let nodes = NODE_COUNT.with(|n| {
+        n.set(n.get() + 1);
+        n.get()
+    });
     if debug {
         TOTAL_NODES.fetch_add(1, Ordering::Relaxed);
     }
+    if out_of_time(nodes) {
+        return (0, 0xFF);
+    }
 
     if state.is_terminal() {
-        return (state.points[0] as i16, 0xFF);
+        return (objective.value(state, team), 0xFF);
+    }
+    // Final values still reachable: a window outside them is already decided,
+    // and no cut-off evaluation may leave them.
+    let (lower, upper) = objective.bounds(state, team);
+    if lower >= beta {
+        return (lower, 0xFF);
+    }
+    if upper <= alpha {
+        return (upper, 0xFF);
     }
     if depth == 0 {
-        return (evaluate_state(state), 0xFF);
+        profiling::count(Counter::Evaluations);
+        return (
+            evaluate_state(state, team, objective).clamp(lower, upper),
+            0xFF,
+        );
     }
 
-    let current_points = state.points[0] as i16;
-    let alpha_norm = alpha.saturating_sub(current_points);
-    let beta_norm = beta.saturating_sub(current_points);
+    // 0. Quick-trick bounds: skip the node when they already decide the window.
+    // Only in searches reaching the end of the deal, where they compare to exact
+    // values, and only for points (the bounds and the capot values are in points).
+    let cards_left: u32 = state.hands.iter().map(|h| h.count_ones()).sum();
+    if objective == Objective::Points && depth as u32 >= cards_left {
+        let (lower, upper) = bounds::quick_bounds(state, team);
+        if lower >= beta {
+            return (lower, 0xFF);
+        }
+        if upper <= alpha {
+            return (upper, 0xFF);
+        }
+
+        // Forced capot for either side settles the value outright.
+        if state.trick_size == 0 {
+            for capot_team in [team, 1 - team] {
+                if state.tricks_won[1 - capot_team] == 0 && capot::capot_possible(state, capot_team)
+                {
+                    if let Some(mv) = capot::capot_move(state, capot_team, capot::ORACLE_BUDGET) {
+                        return (bounds::capot_value(state, team, capot_team), mv);
+                    }
+                }
+            }
+        }
+    }
+
+    let current_points = objective.value(state, team);
+    let mut window = Window::relative(alpha, beta, current_points);
 
     // 1. TT Lookup
-    let tt_idx = (hash & TT_MASK) as usize;
-    let entry = TT.with(|tt| tt.borrow()[tt_idx]);
+    let entry = tt::probe(hash, my_gen);
 
-    if entry.key == hash && entry.gen == my_gen && entry.depth >= depth {
+    if let Some(entry) = entry.filter(|e| e.depth >= depth) {
         if debug {
             TT_HITS.fetch_add(1, Ordering::Relaxed);
         }
-        if entry.flag == 0 {
-            return (entry.score + current_points, entry.best_move);
-        } else if entry.flag == 1 {
-            if entry.score >= beta_norm {
-                return (entry.score + current_points, entry.best_move);
-            }
-            alpha = max(alpha, entry.score + current_points);
-        } else if entry.flag == 2 {
-            if entry.score <= alpha_norm {
-                return (entry.score + current_points, entry.best_move);
-            }
-            beta = min(beta, entry.score + current_points);
+        if let Some(score) = window.apply(entry.score, entry.flag) {
+            return (score + current_points, entry.best_move);
         }
-        if alpha >= beta {
-            return (entry.score + current_points, entry.best_move);
+    }
+
+    // 1b. Partition cache: same abstract position met in another deal or world
+    let exact = objective == Objective::Points && !ctx.options.discard_pruning;
+    let partition_key = if exact && partition::applies(state, depth) {
+        Some(partition::abstract_key(state, team))
+    } else {
+        None
+    };
+    if partition_key.is_some() {
+        profiling::count(Counter::PartitionProbes);
+    }
+    if let Some(cached) = partition_key.and_then(partition::probe) {
+        profiling::count(Counter::PartitionHits);
+        if let Some(score) = window.apply(cached.score, cached.flag) {
+            let mv = partition::concrete_move(state, cached.best_move);
+            return (score + current_points, mv);
         }
     }
 
+    // Children are searched in final points; the window is kept to classify the result
+    // (alpha and beta themselves move during the loop).
+    let search_window = window;
+    alpha = window.alpha + current_points;
+    beta = window.beta + current_points;
+
     let legal_moves_mask = state.get_legal_moves();
     let mut best_move = 0xFF;
-    let is_maximizing = state.current_player % 2 == 0;
+    let is_maximizing = (state.current_player % 2) as usize == team;
+
+    // 1c. Discard pruning: a shallower search of the weakest discard may settle it.
+    if ctx.options.discard_pruning && depth > DISCARD_REDUCTION + 1 {
+        if let Some(card) = pruning_discard(state) {
+            let mut child = *state;
+            child.play_card(card);
+            let child_hash = compute_zobrist_hash(&child);
+            let reduced = depth - 1 - DISCARD_REDUCTION;
+            let (bound, _) = minimax(&child, child_hash, alpha, beta, reduced, ctx);
+            if (is_maximizing && bound >= beta) || (!is_maximizing && bound <= alpha) {
+                return (bound, card);
+            }
+        }
+    }
 
     // SCALAR REPLACEMENT: Array instead of Vec
     let mut moves = [0u8; 8];
@@ -436,7 +954,7 @@ fn minimax(
 
     moves_slice.sort_unstable_by(|&a, &b| {
         // Use entry from TT if valid
-        if entry.key == hash && entry.gen == my_gen {
+        if let Some(entry) = entry {
             if a == entry.best_move {
                 return std::cmp::Ordering::Less;
             }
@@ -470,12 +988,12 @@ fn minimax(
             crate::gameplay::playing::RANK_STRENGTH_NON_TRUMP[rank_b]
         };
 
-        // Sort by Strength Descending
-        str_b.cmp(&str_a)
+        // Sort by Strength Descending; equal strengths fall back to card index so
+        // the search order (and thus the reported best move) is deterministic.
+        str_b.cmp(&str_a).then(a.cmp(&b))
     });
 
     let mut val = if is_maximizing { -INF } else { INF };
-    let original_alpha = alpha;
 
     for &i in moves_slice.iter() {
         // INCREMENTAL HASH CALCULATION
@@ -503,6 +1021,11 @@ fn minimax(
         // COPY STATE (Still copying for now, Phase 1)
         let mut next_state = *state;
         next_state.play_card(i);
+        for team in 0..2 {
+            if next_state.belote_scored[team] != state.belote_scored[team] {
+                next_hash ^= ZOBRIST.belote_scored[team];
+            }
+        }
 
         // Check if trick was cleared in `play_card`
         // `play_card` clears trick if size was 4.
@@ -550,15 +1073,7 @@ fn minimax(
             next_hash ^= ZOBRIST.turn[next_player];
         }
 
-        let (eval, _) = minimax(
-            &next_state,
-            next_hash,
-            alpha,
-            beta,
-            my_gen,
-            depth - 1,
-            debug,
-        );
+        let (eval, _) = minimax(&next_state, next_hash, alpha, beta, depth - 1, ctx);
 
         if is_maximizing {
             if eval > val {
@@ -578,25 +1093,29 @@ fn minimax(
         }
     }
 
-    let val_norm = val.saturating_sub(current_points);
-    let flag = if val <= original_alpha {
-        2
-    } else if val >= beta {
-        1
-    } else {
-        0
-    };
+    if ABORTED.with(|a| a.get()) {
+        return (val, best_move);
+    }
+
+    let val_norm = val - current_points;
+    let flag = search_window.classify(val_norm);
 
-    TT.with(|tt| {
-        let mut tt = tt.borrow_mut();
-        tt[tt_idx] = TTEntry {
-            key: hash,
-            score: val_norm,
-            best_move,
+    if let Some(key) = partition_key {
+        partition::store(
+            key,
+            val_norm,
             flag,
-            depth,
-            gen: my_gen,
-        };
+            partition::abstract_move(state, best_move),
+        );
+    }
+
+    tt::store(TTEntry {
+        key: hash,
+        score: val_norm,
+        best_move,
+        flag,
+        depth,
+        gen: my_gen,
     });
 
     (val, best_move)
@@ -604,7 +1123,8 @@ fn minimax(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::gameplay::playing::{PlayingState, CLUBS, HEARTS, SPADES};
+    use crate::gameplay::playing::{PlayingState, CLUBS, DIAMONDS, HEARTS, NO_TRUMP, SPADES};
+    use crate::gameplay::rules::RuleSet;
 
     fn card(suit: u8, rank: u8) -> u8 {
         suit * 8 + rank
@@ -632,6 +1152,20 @@ mod tests {
         assert_eq!(score, 21);
     }
 
+    #[test]
+    fn test_solve_all_leaders() {
+        // One card each, nobody holds a trump: whoever leads sets the suit.
+        // P0: A Spades, P1: 10 Diamonds, P2: 7 Spades, P3: 8 Diamonds.
+        let hands = [
+            1 << card(SPADES, 7),
+            1 << card(DIAMONDS, 3),
+            1 << card(SPADES, 0),
+            1 << card(DIAMONDS, 1),
+        ];
+        // A Spades and 10 Diamonds with the last trick: 31 points to the winner.
+        assert_eq!(solve_all_leaders(&hands, HEARTS, None), [31, 0, 31, 0]);
+    }
+
     #[test]
     fn test_solve_two_tricks_simple() {
         let mut state = PlayingState::new(HEARTS);
@@ -706,4 +1240,279 @@ mod tests {
         let (score, _) = solve(&state, false, Some(32), None);
         assert_eq!(score, 195);
     }
+
+    #[test]
+    fn test_scores_beyond_i16() {
+        // South cashes its four top trumps for a capot worth far more than an i16.
+        let mut state = PlayingState::new(HEARTS);
+        state.tricks_won[0] = 4;
+        state.rules.capot_bonus = 60_000;
+        state.hands = [
+            0b1001_1100 << (HEARTS * 8),
+            0x0F << (CLUBS * 8),
+            0xF0 << (CLUBS * 8),
+            0x0F << (SPADES * 8),
+        ];
+        assert_eq!(solve(&state, false, Some(32), None).0, 60_105);
+        let (lexicographic, _) =
+            solve_with_objective(&state, 0, Objective::Lexicographic, Some(32), None);
+        assert_eq!(lexicographic, 8 * LEXICOGRAPHIC_TRICK_WEIGHT + 60_105);
+    }
+
+    #[test]
+    fn test_nodes_searched_counts_solve() {
+        let mut state = PlayingState::new(HEARTS);
+        state.hands[0] = (1 << card(HEARTS, 7)) | (1 << card(HEARTS, 6));
+        state.hands[1] = (1 << card(HEARTS, 0)) | (1 << card(HEARTS, 1));
+        state.hands[2] = (1 << card(SPADES, 0)) | (1 << card(SPADES, 1));
+        state.hands[3] = (1 << card(SPADES, 2)) | (1 << card(SPADES, 3));
+
+        let before = nodes_searched();
+        solve(&state, false, Some(32), None);
+        // At least the root and one full line of 8 plays.
+        assert!(nodes_searched() - before >= 9);
+    }
+
+    #[test]
+    fn test_solve_for_team_is_complementary() {
+        let mut state = PlayingState::new(HEARTS);
+        state.hands[0] = (1 << card(HEARTS, 7)) | (1 << card(HEARTS, 6));
+        state.hands[1] = (1 << card(HEARTS, 0)) | (1 << card(HEARTS, 1));
+        state.hands[2] = (1 << card(SPADES, 0)) | (1 << card(SPADES, 1));
+        state.hands[3] = (1 << card(SPADES, 2)) | (1 << card(SPADES, 3));
+
+        // NS takes both tricks (35 points, see above), EW gets nothing.
+        assert_eq!(solve_for_team(&state, 0, Some(32), None).0, 35);
+        assert_eq!(solve_for_team(&state, 1, Some(32), None).0, 0);
+
+        state.current_player = 1;
+        state.trick_starter = 1;
+        let team = Perspective::CurrentPlayer.team(&state, None).unwrap();
+        assert_eq!(team, 1);
+        assert_eq!(Perspective::Declarer.team(&state, Some(2)), Ok(0));
+        assert!(Perspective::Declarer.team(&state, None).is_err());
+        assert_eq!(Perspective::parse("declarer"), Ok(Perspective::Declarer));
+    }
+
+    #[test]
+    fn test_window_bounds() {
+        // Frame conversion: with 30 points already won, final points 30..130
+        // are 0..100 still to win.
+        let window = Window::relative(30, 130, 30);
+        assert_eq!(
+            window,
+            Window {
+                alpha: 0,
+                beta: 100
+            }
+        );
+
+        // A result inside the original window is exact, whichever side moved
+        // (the loop narrows beta down to the value at min nodes).
+        assert_eq!(window.classify(50), EXACT);
+        assert_eq!(window.classify(0), UPPER_BOUND);
+        assert_eq!(window.classify(100), LOWER_BOUND);
+
+        // Bounds narrow the window until they decide the node.
+        let mut w = window;
+        assert_eq!(w.apply(60, LOWER_BOUND), None);
+        assert_eq!(
+            w,
+            Window {
+                alpha: 60,
+                beta: 100
+            }
+        );
+        assert_eq!(w.apply(80, UPPER_BOUND), None);
+        assert_eq!(
+            w,
+            Window {
+                alpha: 60,
+                beta: 80
+            }
+        );
+        assert_eq!(w.apply(60, UPPER_BOUND), Some(60));
+        let mut fresh = window;
+        assert_eq!(fresh.apply(100, LOWER_BOUND), Some(100));
+        assert_eq!(fresh.apply(42, EXACT), Some(42));
+    }
+
+    #[test]
+    fn test_min_node_stores_exact_value() {
+        // East to lead: the root minimizes NS points. Both sides already hold
+        // tricks, so no capot shortcut answers before the TT store.
+        let mut state = PlayingState::new(HEARTS);
+        state.tricks_won = [3, 3];
+        state.points = [40, 40];
+        state.current_player = 1;
+        state.trick_starter = 1;
+        state.hands[0] = (1 << card(HEARTS, 7)) | (1 << card(SPADES, 3));
+        state.hands[1] = (1 << card(HEARTS, 0)) | (1 << card(SPADES, 7));
+        state.hands[2] = (1 << card(SPADES, 6)) | (1 << card(CLUBS, 0));
+        state.hands[3] = (1 << card(HEARTS, 1)) | (1 << card(CLUBS, 1));
+
+        let (score, _) = solve_for_team(&state, 0, Some(32), None);
+        assert_eq!(score, reference_value(&state, 0));
+
+        let hash = compute_zobrist_hash(&state);
+        let entry = tt::probe(hash, tt::generation()).unwrap();
+        assert_eq!(entry.flag, EXACT);
+        assert_eq!(entry.score, score - 40);
+    }
+
+    #[test]
+    fn test_search_mode_for_contract() {
+        use crate::gameplay::playing::NO_TRUMP;
+        assert_eq!(SearchMode::for_contract(HEARTS), SearchMode::Minimax);
+        assert_eq!(SearchMode::for_contract(NO_TRUMP), SearchMode::Minimax);
+        assert_eq!(
+            SearchMode::for_contract(ALL_TRUMP),
+            SearchMode::TrickMcts {
+                iterations: DEFAULT_MCTS_ITERATIONS
+            }
+        );
+        assert_eq!(
+            SearchMode::parse("auto", ALL_TRUMP, 50),
+            Ok(SearchMode::TrickMcts { iterations: 50 })
+        );
+        assert_eq!(
+            SearchMode::parse("MCTS", HEARTS, 50),
+            Ok(SearchMode::TrickMcts { iterations: 50 })
+        );
+        assert!(SearchMode::parse("dfs", HEARTS, 50).is_err());
+
+        // Both searches agree on a one-trick ending.
+        let mut state = PlayingState::new(HEARTS);
+        state.hands = [
+            1 << card(HEARTS, 7),
+            1 << card(HEARTS, 0),
+            1 << card(HEARTS, 1),
+            1 << card(SPADES, 2),
+        ];
+        for mode in [
+            SearchMode::Minimax,
+            SearchMode::TrickMcts { iterations: 10 },
+        ] {
+            assert_eq!(
+                solve_with_mode(&state, 0, mode, None, None, Some(1)),
+                (21, card(HEARTS, 7))
+            );
+        }
+    }
+
+    #[test]
+    fn test_solve_root_moves() {
+        let mut state = PlayingState::new(HEARTS);
+        state.hands[0] = (1 << card(HEARTS, 7)) | (1 << card(HEARTS, 6));
+        state.hands[1] = (1 << card(HEARTS, 0)) | (1 << card(HEARTS, 1));
+        state.hands[2] = (1 << card(SPADES, 0)) | (1 << card(SPADES, 1));
+        state.hands[3] = (1 << card(SPADES, 2)) | (1 << card(SPADES, 3));
+
+        let moves = solve_root_moves(&state, 0, Some(32), None);
+        assert_eq!(moves.len(), 2);
+        let best = moves.iter().map(|&(_, v)| v).max().unwrap();
+        assert_eq!(best, solve_for_team(&state, 0, Some(32), None).0);
+
+        // Last card of the deal: the child is terminal.
+        let mut last = PlayingState::new(HEARTS);
+        last.hands[0] = 1 << card(HEARTS, 7);
+        last.hands[1] = 1 << card(HEARTS, 0);
+        last.hands[2] = 1 << card(HEARTS, 1);
+        last.hands[3] = 1 << card(SPADES, 2);
+        last.play_card(card(HEARTS, 7));
+        last.play_card(card(HEARTS, 0));
+        last.play_card(card(HEARTS, 1));
+        assert_eq!(
+            solve_root_moves(&last, 0, Some(32), None),
+            vec![(card(SPADES, 2), 21)]
+        );
+    }
+
+    #[test]
+    fn test_partition_cache_across_deals() {
+        // Same abstract position: only the 7/8 spot cards of Spades and Clubs differ.
+        let mut a = PlayingState::new(HEARTS);
+        a.hands = [
+            (1 << card(HEARTS, 7)) | (1 << card(SPADES, 0)),
+            (1 << card(HEARTS, 0)) | (1 << card(CLUBS, 0)),
+            (1 << card(SPADES, 7)) | (1 << card(CLUBS, 7)),
+            (1 << card(HEARTS, 3)) | (1 << card(CLUBS, 3)),
+        ];
+        // Both sides already hold tricks, so the capot shortcut stays out of the way.
+        a.tricks_won = [3, 3];
+        let mut b = a;
+        b.hands[0] = (1 << card(HEARTS, 7)) | (1 << card(SPADES, 1));
+        b.hands[1] = (1 << card(HEARTS, 0)) | (1 << card(CLUBS, 1));
+
+        let expected = solve_for_team(&b, 0, Some(32), None);
+
+        set_partition_cache(true);
+        solve_for_team(&a, 0, Some(32), None);
+        let hits = partition_hits();
+        let cached = solve_for_team(&b, 0, Some(32), None);
+        set_partition_cache(false);
+
+        assert!(partition_hits() > hits);
+        assert_eq!(cached, expected);
+    }
+
+    #[test]
+    fn test_partition_cache_keeps_rules_apart() {
+        // North-South may still take every trick, a capot only worth its card
+        // points under the second rules.
+        let mut state = PlayingState::new(NO_TRUMP);
+        state.hands = [
+            (1 << card(DIAMONDS, 1)) | (1 << card(DIAMONDS, 3)) | (1 << card(DIAMONDS, 7)),
+            (1 << card(SPADES, 1)) | (1 << card(SPADES, 5)) | (1 << card(HEARTS, 1)),
+            (1 << card(DIAMONDS, 2)) | (1 << card(DIAMONDS, 4)) | (1 << card(SPADES, 2)),
+            (1 << card(DIAMONDS, 0)) | (1 << card(DIAMONDS, 5)) | (1 << card(DIAMONDS, 6)),
+        ];
+        state.tricks_won = [5, 0];
+        let mut no_bonus = state;
+        no_bonus.set_rules(RuleSet::checked(0, false).unwrap());
+        let value = |s: &PlayingState| solve_for_team(s, 0, Some(32), None).0;
+        let expected = [value(&state), value(&no_bonus)];
+        assert_ne!(expected[0], expected[1]);
+
+        // Both solved on this thread, sharing its cache.
+        set_partition_cache(true);
+        clear_partition_cache();
+        let cached = [value(&state), value(&no_bonus)];
+        set_partition_cache(false);
+        assert_eq!(cached, expected);
+    }
+
+    #[test]
+    fn test_discard_pruning_stays_close() {
+        let pruned = SolveOptions {
+            discard_pruning: true,
+        };
+        assert_eq!(solve_options(), SolveOptions::default());
+        for i in 0..20 {
+            let mut rng = crate::data_gen::common::sample_rng(Some(2685), i);
+            let state = random_ending(12 - (i % 4) as u32, &mut rng);
+            let exact = solve_with_options(
+                &state,
+                0,
+                Objective::Points,
+                Some(32),
+                SolveOptions::default(),
+            );
+            assert_eq!(exact, solve_for_team(&state, 0, Some(32), None));
+
+            let (value, card) = solve_with_options(&state, 0, Objective::Points, Some(32), pruned);
+            assert!(state.get_legal_moves() & (1 << card) != 0);
+            let (lower, upper) = Objective::Points.bounds(&state, 0);
+            assert!((lower..=upper).contains(&value), "position {}", i);
+        }
+        // Leading a trick, or following to one worth points, is never pruned.
+        let mut lead = PlayingState::new(HEARTS);
+        lead.hands = [0b11, 0b11 << 8, 0b11 << 16, 0b11 << 24];
+        assert_eq!(pruning_discard(&lead), None);
+        lead.play_card(0);
+        assert_eq!(pruning_discard(&lead), Some(8));
+        let mut ace = lead;
+        ace.current_trick[0] = card(DIAMONDS, 7);
+        assert_eq!(pruning_discard(&ace), None);
+    }
 }
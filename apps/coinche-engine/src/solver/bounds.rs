@@ -0,0 +1,172 @@
+//! Cheap admissible bounds on the final points of a team, used to cut nodes whose
+//! alpha-beta window is already decided without searching them.
+//!
+//! Upper bound: everything still in play, the dix de der, the team's certain
+//! belote and, while the opponents have no trick, the capot bonus. Lower bound:
+//! points already won, the certain belote and the quick tricks of the leader.
+//! Quick tricks are the master cards the leader can cash one after the other:
+//! the top trumps in a suit contract, the top cards of every suit otherwise
+//! (no suit ranks as trump in NT/AT, see `PlayingState::is_card_better`).
+
+use super::{ranks_by_strength, Score};
+use crate::gameplay::playing::{cards_points, PlayingState, LAST_TRICK_BONUS, RANK_K, RANK_Q};
+
+const BELOTE_BONUS: Score = 20;
+
+/// Whether `team` will score the belote: one of its players holds both the King
+/// and the Queen of trumps, and every card gets played.
+fn certain_belote(state: &PlayingState, team: usize) -> bool {
+    if !state.rules.belote || state.trump >= 4 || state.belote_scored[team] {
+        return false;
+    }
+    let pair = (1 << (state.trump * 8 + RANK_K)) | (1 << (state.trump * 8 + RANK_Q));
+    state.hands[team] & pair == pair || state.hands[team + 2] & pair == pair
+}
+
+/// Tricks the player to lead wins for sure by cashing master cards, and the
+/// points of those cards. Only meaningful at a trick boundary.
+fn quick_tricks(state: &PlayingState) -> (u32, Score) {
+    let hand = state.hands[state.current_player as usize];
+    let in_play = state.hands.iter().fold(0u32, |m, &h| m | h);
+    let suits = if state.trump < 4 {
+        state.trump..state.trump + 1
+    } else {
+        0..4
+    };
+
+    let mut masters = 0u32;
+    for suit in suits {
+        for rank in ranks_by_strength(suit, state.trump) {
+            let card = suit * 8 + rank;
+            if in_play & (1 << card) == 0 {
+                continue;
+            }
+            if hand & (1 << card) == 0 {
+                break;
+            }
+            masters |= 1 << card;
+        }
+    }
+    (
+        masters.count_ones(),
+        cards_points(masters, state.trump) as Score,
+    )
+}
+
+// Points of `team` once it has everything it is sure to score: already won and belote.
+fn secured(state: &PlayingState, team: usize) -> Score {
+    let belote = if certain_belote(state, team) {
+        BELOTE_BONUS
+    } else {
+        0
+    };
+    state.points[team] as Score + belote
+}
+
+// Card points still to be won (hands and current trick) plus the dix de der
+// (an upper bound when the rules leave the der out of a capot).
+fn remaining(state: &PlayingState) -> Score {
+    let mut in_play = state.hands.iter().fold(0u32, |m, &h| m | h);
+    for &c in state.current_trick.iter().filter(|&&c| c < 32) {
+        in_play |= 1 << c;
+    }
+    (cards_points(in_play, state.trump) + LAST_TRICK_BONUS) as Score
+}
+
+/// Final points of `team` when `capot_team` wins every remaining trick. The capot
+/// bonus is only paid if that makes all eight tricks.
+pub(super) fn capot_value(state: &PlayingState, team: usize, capot_team: usize) -> Score {
+    if team != capot_team {
+        return secured(state, team);
+    }
+    let tricks_left = state.hands[state.current_player as usize].count_ones();
+    if state.tricks_won[team] as u32 + tricks_left < 8 {
+        return secured(state, team) + remaining(state);
+    }
+    let der = if state.rules.der_in_capot {
+        0
+    } else {
+        LAST_TRICK_BONUS as Score
+    };
+    secured(state, team) + remaining(state) - der + state.rules.capot_bonus as Score
+}
+
+/// (lower, upper) bounds on the final points of `team` for a non-terminal `state`.
+pub(super) fn quick_bounds(state: &PlayingState, team: usize) -> (Score, Score) {
+    let secured = secured(state, team);
+    let remaining = remaining(state);
+    let capot = if state.tricks_won[1 - team] == 0 {
+        state.rules.capot_bonus as Score
+    } else {
+        0
+    };
+
+    let mut lower = secured;
+    let mut upper = secured + remaining + capot;
+
+    if state.trick_size == 0 {
+        let (tricks, points) = quick_tricks(state);
+        let tricks_left = state.hands[state.current_player as usize].count_ones();
+        let leader_team = (state.current_player % 2) as usize;
+        let takes_all = tricks == tricks_left;
+
+        if takes_all {
+            let exact = capot_value(state, team, leader_team);
+            return (exact, exact);
+        }
+        if leader_team == team {
+            lower += points;
+        } else {
+            upper -= points;
+        }
+    }
+
+    (lower, upper)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::playing::{CLUBS, HEARTS, SPADES};
+    use crate::gameplay::rules::RuleSet;
+    use crate::solver::check::reference_value;
+    use crate::solver::solve_for_team;
+
+    fn card(suit: u8, rank: u8) -> u8 {
+        suit * 8 + rank
+    }
+
+    #[test]
+    fn test_bounds_contain_solved_value() {
+        // South holds the four top trumps: every trick is theirs.
+        let mut state = PlayingState::new(HEARTS);
+        state.tricks_won[0] = 4;
+        state.hands[0] = (1 << card(HEARTS, 4))
+            | (1 << card(HEARTS, 2))
+            | (1 << card(HEARTS, 7))
+            | (1 << card(HEARTS, 3));
+        state.hands[1] = 0x0F << (CLUBS * 8);
+        state.hands[2] = 0xF0 << (CLUBS * 8);
+        state.hands[3] = 0x0F << (SPADES * 8);
+
+        let exact = solve_for_team(&state, 0, Some(32), None).0;
+        assert_eq!(quick_bounds(&state, 0), (exact, exact));
+        assert_eq!(quick_bounds(&state, 1), (0, 0));
+
+        // A flat capot leaves the der out: still exact.
+        let mut flat = state;
+        flat.set_rules(RuleSet::flat_capot());
+        let exact = solve_for_team(&flat, 0, Some(32), None).0;
+        assert_eq!(exact, reference_value(&flat, 0));
+        assert_eq!(quick_bounds(&flat, 0), (exact, exact));
+
+        // East to lead instead: only loose bounds, but they must hold.
+        state.current_player = 1;
+        state.trick_starter = 1;
+        for team in 0..2 {
+            let exact = solve_for_team(&state, team, Some(32), None).0;
+            let (lower, upper) = quick_bounds(&state, team);
+            assert!(lower <= exact && exact <= upper);
+        }
+    }
+}
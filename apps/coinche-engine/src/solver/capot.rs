@@ -0,0 +1,161 @@
+//! Forced-capot detection: can a team win every remaining trick, whatever the
+//! defence does? The search only tracks who wins each trick, never points, and
+//! stops at the first trick the defenders take.
+
+use crate::gameplay::playing::PlayingState;
+use std::collections::HashMap;
+
+/// Node budget of the detector when used as a pruning oracle by the main search.
+pub(super) const ORACLE_BUDGET: u32 = 512;
+
+// Results at trick boundaries: the question only depends on the hands and the leader.
+type Memo = HashMap<([u32; 4], u8), bool>;
+
+fn search(
+    state: &PlayingState,
+    team: usize,
+    defender_tricks: u8,
+    budget: &mut u32,
+    memo: &mut Memo,
+) -> Option<bool> {
+    if state.is_terminal() {
+        return Some(true);
+    }
+    let key = (state.hands, state.current_player);
+    if state.trick_size == 0 {
+        if let Some(&known) = memo.get(&key) {
+            return Some(known);
+        }
+    }
+    if *budget == 0 {
+        return None;
+    }
+    *budget -= 1;
+
+    let attacking = (state.current_player % 2) as usize == team;
+    // Attackers need one move that keeps the capot, defenders one move that breaks it.
+    let mut result = !attacking;
+    let mut moves = state.get_legal_moves();
+    while moves != 0 {
+        let card = moves.trailing_zeros() as u8;
+        moves &= moves - 1;
+
+        let mut next = *state;
+        next.play_card(card);
+        let holds = next.tricks_won[1 - team] == defender_tricks
+            && search(&next, team, defender_tricks, budget, memo)?;
+        if holds == attacking {
+            result = attacking;
+            break;
+        }
+    }
+
+    if state.trick_size == 0 {
+        memo.insert(key, result);
+    }
+    Some(result)
+}
+
+/// Proves or refutes a forced capot for `team` within `budget` search nodes.
+/// Returns `None` when the budget runs out first.
+pub fn forces_capot_within(state: &PlayingState, team: usize, budget: u32) -> Option<bool> {
+    let mut budget = budget;
+    search(
+        state,
+        team,
+        state.tricks_won[1 - team],
+        &mut budget,
+        &mut Memo::new(),
+    )
+}
+
+/// Whether `team` wins all the remaining tricks against any defence.
+pub fn forces_capot(state: &PlayingState, team: usize) -> bool {
+    forces_capot_within(state, team, u32::MAX).unwrap_or(false)
+}
+
+/// Move keeping a forced capot for `team`, or `None` if there is none (or it could
+/// not be proven within `budget`). When a defender is to move, every move loses the
+/// capot equally and the lowest card is returned.
+pub(super) fn capot_move(state: &PlayingState, team: usize, budget: u32) -> Option<u8> {
+    let legal = state.get_legal_moves();
+    if legal == 0 {
+        return None;
+    }
+    let defender_tricks = state.tricks_won[1 - team];
+    let mut budget = budget;
+    let mut memo = Memo::new();
+
+    if (state.current_player % 2) as usize != team {
+        return search(state, team, defender_tricks, &mut budget, &mut memo)?
+            .then_some(legal.trailing_zeros() as u8);
+    }
+
+    let mut moves = legal;
+    while moves != 0 {
+        let card = moves.trailing_zeros() as u8;
+        moves &= moves - 1;
+        let mut next = *state;
+        next.play_card(card);
+        if next.tricks_won[1 - team] == defender_tricks
+            && search(&next, team, defender_tricks, &mut budget, &mut memo)?
+        {
+            return Some(card);
+        }
+    }
+    None
+}
+
+/// Cheap necessary condition in a suit contract: a defender holding the highest
+/// trump still in play will take a trick with it.
+pub(super) fn capot_possible(state: &PlayingState, team: usize) -> bool {
+    if state.trump >= 4 {
+        return true;
+    }
+    let in_play = state.hands.iter().fold(0u32, |m, &h| m | h);
+    let top_trump = super::ranks_by_strength(state.trump, state.trump)
+        .iter()
+        .map(|&r| state.trump * 8 + r)
+        .find(|&c| in_play & (1 << c) != 0);
+    match top_trump {
+        Some(card) => (0..4)
+            .filter(|&p| p % 2 != team)
+            .all(|p| state.hands[p] & (1 << card) == 0),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::playing::{CLUBS, HEARTS, SPADES};
+
+    fn card(suit: u8, rank: u8) -> u8 {
+        suit * 8 + rank
+    }
+
+    #[test]
+    fn test_forced_capot() {
+        // South holds the four top trumps and leads.
+        let mut state = PlayingState::new(HEARTS);
+        state.hands[0] = (1 << card(HEARTS, 4))
+            | (1 << card(HEARTS, 2))
+            | (1 << card(HEARTS, 7))
+            | (1 << card(HEARTS, 3));
+        state.hands[1] = 0x0F << (CLUBS * 8);
+        state.hands[2] = 0xF0 << (CLUBS * 8);
+        state.hands[3] = 0x0F << (SPADES * 8);
+        assert!(forces_capot(&state, 0));
+        assert!(!forces_capot(&state, 1));
+        assert!(capot_possible(&state, 0));
+        assert!(!capot_possible(&state, 1));
+
+        // Trade South's Ten of trumps for the Jack of Spades: West's Ace of Spades
+        // takes the spade trick, whenever South leads it.
+        state.hands[0] &= !(1 << card(HEARTS, 3));
+        state.hands[0] |= 1 << card(SPADES, 4);
+        state.hands[3] = (0x07 << (SPADES * 8)) | (1 << card(SPADES, 7));
+        assert!(!forces_capot(&state, 0));
+        assert_eq!(forces_capot_within(&state, 0, 1), None);
+    }
+}
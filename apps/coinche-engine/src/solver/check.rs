@@ -0,0 +1,149 @@
+//! Correctness cross-check: the alpha-beta search (TT, bounds, capot oracle,
+//! partition cache) against a plain minimax with none of them. Exponential, so
+//! only meant for small endings.
+
+use super::{solve_for_team, Objective, Score};
+use crate::gameplay::playing::PlayingState;
+use rand::Rng;
+
+/// Cards left above which `cross_check` refuses to run the reference search.
+pub const MAX_CHECK_CARDS: u32 = 16;
+
+/// Final points of `team` by exhaustive minimax: no TT, no pruning, no shortcut.
+pub fn reference_value(state: &PlayingState, team: usize) -> Score {
+    reference_objective_value(state, team, Objective::Points)
+}
+
+/// Same as `reference_value`, in the units of `objective`.
+pub fn reference_objective_value(state: &PlayingState, team: usize, objective: Objective) -> Score {
+    if state.is_terminal() {
+        return objective.value(state, team);
+    }
+    let maximizing = (state.current_player % 2) as usize == team;
+    let mut best = if maximizing { Score::MIN } else { Score::MAX };
+    let mut moves = state.get_legal_moves();
+    while moves != 0 {
+        let card = moves.trailing_zeros() as u8;
+        moves &= moves - 1;
+        let mut next = *state;
+        next.play_card(card);
+        let value = reference_objective_value(&next, team, objective);
+        best = if maximizing {
+            best.max(value)
+        } else {
+            best.min(value)
+        };
+    }
+    best
+}
+
+/// Solves `state` for `team` with both searches and checks that they agree on the
+/// value, and that the move returned by the solver actually reaches it.
+/// Returns the value, or a description of the disagreement.
+pub fn cross_check(state: &PlayingState, team: usize) -> Result<Score, String> {
+    let cards_left: u32 = state.hands.iter().map(|h| h.count_ones()).sum();
+    if cards_left > MAX_CHECK_CARDS {
+        return Err(format!(
+            "{} cards left, the reference search is limited to {}",
+            cards_left, MAX_CHECK_CARDS
+        ));
+    }
+
+    let expected = reference_value(state, team);
+    let (score, best_move) = solve_for_team(state, team, Some(32), None);
+    if score != expected {
+        return Err(format!(
+            "solver value {} != reference {} for {:?}",
+            score, expected, state
+        ));
+    }
+
+    if best_move >= 32 || state.get_legal_moves() & (1 << best_move) == 0 {
+        return Err(format!(
+            "solver move {} is not legal in {:?}",
+            best_move, state
+        ));
+    }
+    let mut next = *state;
+    next.play_card(best_move);
+    let reached = reference_value(&next, team);
+    if reached != expected {
+        return Err(format!(
+            "solver move {} reaches {} instead of {} in {:?}",
+            best_move, reached, expected, state
+        ));
+    }
+    Ok(expected)
+}
+
+/// Random ending with `cards_left` cards still in hands (a multiple of 4 gives a
+/// trick boundary, anything else a trick in progress): a random deal and contract,
+/// played out at random up to that point.
+pub fn random_ending<R: Rng>(cards_left: u32, rng: &mut R) -> PlayingState {
+    let mut state = PlayingState::new(rng.gen_range(0..6));
+    state.hands = crate::data_gen::common::generate_random_hands(rng);
+    let leader = rng.gen_range(0..4);
+    state.current_player = leader;
+    state.trick_starter = leader;
+
+    while state.hands.iter().map(|h| h.count_ones()).sum::<u32>() > cards_left {
+        let legal = state.get_legal_moves();
+        let pick = rng.gen_range(0..legal.count_ones());
+        let mut moves = legal;
+        for _ in 0..pick {
+            moves &= moves - 1;
+        }
+        state.play_card(moves.trailing_zeros() as u8);
+    }
+    state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data_gen::common::sample_rng;
+
+    fn check_random_endings(count: u64, cards_left: u32) {
+        for i in 0..count {
+            let mut rng = sample_rng(Some(2640), i);
+            let state = random_ending(cards_left, &mut rng);
+            for team in 0..2 {
+                if let Err(e) = cross_check(&state, team) {
+                    panic!("position {} (team {}): {}", i, team, e);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_cross_check_small_endings() {
+        check_random_endings(40, 8);
+        check_random_endings(40, 6);
+    }
+
+    #[test]
+    fn test_trick_objectives_match_reference() {
+        use crate::solver::solve_with_objective;
+        for i in 0..30 {
+            let mut rng = sample_rng(Some(2683), i);
+            let state = random_ending(8 - (i % 3) as u32, &mut rng);
+            for objective in [Objective::Tricks, Objective::Lexicographic] {
+                let expected = reference_objective_value(&state, 1, objective);
+                let (value, best_move) = solve_with_objective(&state, 1, objective, Some(32), None);
+                assert_eq!(value, expected, "position {} ({:?})", i, objective);
+                let mut next = state;
+                next.play_card(best_move);
+                assert_eq!(reference_objective_value(&next, 1, objective), expected);
+            }
+        }
+    }
+
+    // Long run: `cargo test --release --no-default-features -- --ignored`
+    #[test]
+    #[ignore]
+    fn test_cross_check_random_endings() {
+        for cards_left in 9..=MAX_CHECK_CARDS {
+            check_random_endings(500, cards_left);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Deal cache: double-dummy values of deals from their first card, kept in a file
+//! so repeated analyses of the same deals (reruns, sweeps over other parameters,
+//! PIMC worlds drawn from the same seed) look them up instead of solving again.
+//!
+//! Entries are keyed by the canonical form of (deal, trump, leader): seats are
+//! turned so the leader comes first, and suits that play alike (the side suits of
+//! a suit contract, every suit in NT and AT) are sorted. Rotated or suit-renamed
+//! copies of a deal therefore share one entry. The file is a header followed by
+//! fixed-size records, appended as deals are solved; values are for the default
+//! rules, and solves with `discard_pruning` bypass the cache.
+
+use super::{solve_for_team, solve_options, Score};
+use crate::gameplay::playing::PlayingState;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+const MAGIC: &[u8; 4] = b"CDC1";
+const RECORD: usize = 21; // 16 bytes of cards, trump and team, 4 bytes of value
+
+static CACHE: Mutex<Option<DealCache>> = Mutex::new(None);
+
+/// Cards of the canonical deal, suit by suit, each suit one byte per seat; then
+/// the canonical trump and the team the value is for.
+type DealKey = (u128, u8);
+
+/// Size and activity of a deal cache since it was opened.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DealCacheStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub misses: u64,
+    /// Records that could not be appended to the file; their values stay usable
+    /// until the cache is closed.
+    pub write_errors: u64,
+}
+
+pub struct DealCache {
+    file: File,
+    values: HashMap<DealKey, Score>,
+    stats: DealCacheStats,
+}
+
+impl DealCache {
+    /// Opens the cache file at `path`, creating it if missing.
+    pub fn open(path: &Path) -> Result<Self, String> {
+        let error = |e: std::io::Error| format!("Deal cache {}: {}", path.display(), e);
+        let mut values = HashMap::new();
+        if path.exists() {
+            let mut bytes = Vec::new();
+            File::open(path)
+                .and_then(|mut f| f.read_to_end(&mut bytes))
+                .map_err(error)?;
+            if !bytes.starts_with(MAGIC) {
+                return Err(format!("{} is not a deal cache", path.display()));
+            }
+            let records = bytes[MAGIC.len()..].chunks_exact(RECORD);
+            // A record cut short by an interrupted run is dropped, so that new
+            // records stay aligned.
+            if !records.remainder().is_empty() {
+                let whole = bytes.len() - records.remainder().len();
+                OpenOptions::new()
+                    .write(true)
+                    .open(path)
+                    .and_then(|f| f.set_len(whole as u64))
+                    .map_err(error)?;
+            }
+            for record in records {
+                let cards = u128::from_le_bytes(record[..16].try_into().unwrap());
+                let value = Score::from_le_bytes(record[17..].try_into().unwrap());
+                values.insert((cards, record[16]), value);
+            }
+        } else {
+            File::create(path)
+                .and_then(|mut f| f.write_all(MAGIC))
+                .map_err(error)?;
+        }
+        let file = OpenOptions::new().append(true).open(path).map_err(error)?;
+        let stats = DealCacheStats {
+            entries: values.len(),
+            ..DealCacheStats::default()
+        };
+        Ok(DealCache {
+            file,
+            values,
+            stats,
+        })
+    }
+
+    pub fn stats(&self) -> DealCacheStats {
+        self.stats
+    }
+
+    fn get(&mut self, key: DealKey) -> Option<Score> {
+        let value = self.values.get(&key).copied();
+        match value {
+            Some(_) => self.stats.hits += 1,
+            None => self.stats.misses += 1,
+        }
+        value
+    }
+
+    fn insert(&mut self, key: DealKey, value: Score) {
+        if self.values.insert(key, value).is_some() {
+            return;
+        }
+        self.stats.entries += 1;
+        let mut record = [0u8; RECORD];
+        record[..16].copy_from_slice(&key.0.to_le_bytes());
+        record[16] = key.1;
+        record[17..].copy_from_slice(&value.to_le_bytes());
+        if self.file.write_all(&record).is_err() {
+            self.stats.write_errors += 1;
+        }
+    }
+}
+
+/// Opens the deal cache every `solve_deal` consults, replacing the one open.
+/// Returns the number of entries already in the file.
+pub fn open_deal_cache(path: &Path) -> Result<usize, String> {
+    let cache = DealCache::open(path)?;
+    let entries = cache.stats().entries;
+    *CACHE.lock().unwrap() = Some(cache);
+    Ok(entries)
+}
+
+pub fn close_deal_cache() {
+    *CACHE.lock().unwrap() = None;
+}
+
+/// Statistics of the open deal cache, if any.
+pub fn deal_cache_stats() -> Option<DealCacheStats> {
+    CACHE.lock().unwrap().as_ref().map(DealCache::stats)
+}
+
+/// Double-dummy NS points of `hands` played in `trump` with `leader` leading the
+/// first trick, from the open deal cache when it has them.
+pub fn solve_deal(hands: &[u32; 4], trump: u8, leader: u8, tt_log2: Option<u8>) -> Score {
+    if solve_options().discard_pruning || CACHE.lock().unwrap().is_none() {
+        return solve_fresh(hands, trump, leader, tt_log2);
+    }
+    let key = canonical_key(hands, trump, leader);
+    if let Some(value) = CACHE.lock().unwrap().as_mut().and_then(|c| c.get(key)) {
+        return value;
+    }
+    // The lock is not held while solving: another thread may solve the same deal,
+    // and the second insert is a no-op.
+    let value = solve_fresh(hands, trump, leader, tt_log2);
+    if let Some(cache) = CACHE.lock().unwrap().as_mut() {
+        cache.insert(key, value);
+    }
+    value
+}
+
+fn solve_fresh(hands: &[u32; 4], trump: u8, leader: u8, tt_log2: Option<u8>) -> Score {
+    let mut state = PlayingState::new(trump);
+    state.hands = *hands;
+    state.current_player = leader;
+    state.trick_starter = leader;
+    solve_for_team(&state, 0, Some(32), tt_log2).0
+}
+
+/// Key shared by every rotation and suit renaming of the deal. Once the leader is
+/// seat 0, NS is team `leader % 2`, which the key records.
+fn canonical_key(hands: &[u32; 4], trump: u8, leader: u8) -> DealKey {
+    let column = |suit: u8| {
+        (0..4).fold(0u32, |col, seat| {
+            let hand = hands[(seat + leader as usize) % 4];
+            col | (hand >> (suit * 8) & 0xFF) << (seat * 8)
+        })
+    };
+    let mut suits: Vec<u32> = (0..4).filter(|&s| s != trump).map(column).collect();
+    suits.sort_unstable_by(|a, b| b.cmp(a));
+    let canonical_trump = if trump < 4 {
+        suits.insert(0, column(trump));
+        0
+    } else {
+        trump
+    };
+    let cards = suits
+        .iter()
+        .enumerate()
+        .fold(0u128, |key, (i, &col)| key | (col as u128) << (i * 32));
+    (cards, canonical_trump | (leader % 2) << 3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::notation::parse_card;
+    use crate::gameplay::playing::{HEARTS, NO_TRUMP};
+
+    fn cards(names: &[&str]) -> u32 {
+        names.iter().fold(0, |m, n| m | 1 << parse_card(n).unwrap())
+    }
+
+    #[test]
+    fn test_deal_cache() {
+        let path = std::env::temp_dir().join(format!("deal_cache_{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let hands = [
+            cards(&["JH", "AS", "7D"]),
+            cards(&["9H", "10S", "AD"]),
+            cards(&["7H", "KS", "10D"]),
+            cards(&["AH", "8C", "KD"]),
+        ];
+        // Same deal with spades and diamonds swapped, then turned by two seats.
+        let swap = |h: u32| h & 0xFFFF_0000 | (h & 0xFF) << 8 | (h >> 8) & 0xFF;
+        let swapped: [u32; 4] = std::array::from_fn(|i| swap(hands[i]));
+        let turned: [u32; 4] = std::array::from_fn(|i| swapped[(i + 2) % 4]);
+
+        let mut cache = DealCache::open(&path).unwrap();
+        for (trump, leader) in [(HEARTS, 0u8), (HEARTS, 1), (NO_TRUMP, 2)] {
+            let key = canonical_key(&hands, trump, leader);
+            assert_eq!(canonical_key(&swapped, trump, leader), key);
+            assert_eq!(canonical_key(&turned, trump, (leader + 2) % 4), key);
+            let value = solve_fresh(&hands, trump, leader, None);
+            assert_eq!(solve_fresh(&turned, trump, (leader + 2) % 4, None), value);
+            cache.insert(key, value);
+        }
+        // Turned by one seat, NS holds the other hands; hearts is the trump, so
+        // renaming it makes another deal.
+        let turned_once: [u32; 4] = std::array::from_fn(|i| hands[(i + 3) % 4]);
+        assert_ne!(
+            canonical_key(&turned_once, HEARTS, 1),
+            canonical_key(&hands, HEARTS, 0)
+        );
+        let renamed: [u32; 4] = std::array::from_fn(|i| hands[i].rotate_left(8));
+        assert_ne!(
+            canonical_key(&renamed, HEARTS, 0),
+            canonical_key(&hands, HEARTS, 0)
+        );
+
+        // A reopened cache has every entry back, and a truncated record is dropped.
+        drop(cache);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[1, 2, 3]).unwrap();
+        let mut cache = DealCache::open(&path).unwrap();
+        assert_eq!(cache.stats().entries, 3);
+        let key = canonical_key(&turned, HEARTS, 3);
+        assert_eq!(cache.get(key), Some(solve_fresh(&hands, HEARTS, 1, None)));
+        assert_eq!(cache.get(canonical_key(&hands, HEARTS, 2)), None);
+        assert_eq!((cache.stats().hits, cache.stats().misses), (1, 1));
+        cache.insert(canonical_key(&hands, HEARTS, 2), 7);
+        assert_eq!(cache.stats().write_errors, 0);
+        // A record the file refuses is counted, and its value kept in memory.
+        let mut read_only = DealCache::open(&path).unwrap();
+        read_only.file = File::open(&path).unwrap();
+        read_only.insert(canonical_key(&hands, NO_TRUMP, 0), 9);
+        assert_eq!(read_only.stats().write_errors, 1);
+        assert_eq!(read_only.get(canonical_key(&hands, NO_TRUMP, 0)), Some(9));
+        drop(cache);
+        assert_eq!(DealCache::open(&path).unwrap().stats().entries, 4);
+        std::fs::remove_file(&path).unwrap();
+        assert!(DealCache::open(Path::new("Cargo.toml")).is_err());
+    }
+}
@@ -0,0 +1,311 @@
+//! Trick-level Monte Carlo tree search, for contracts whose branching makes
+//! full-depth minimax intractable (All-Trump, where every trick forces players
+//! to overtake and the cheap cuts of `bounds` and `capot` rarely apply).
+//!
+//! Two abstractions keep the tree small:
+//! - moves are equivalence classes: cards of one hand, of the same suit and the
+//!   same points, with no card left in play ranking between them, win the same
+//!   tricks and score the same, so only the strongest of each run is searched;
+//! - positions at a trick boundary are shared whatever the order the previous
+//!   tricks were played in, the tree is thus a graph of trick outcomes. Nodes
+//!   hold the points the searching team still wins from there, which does not
+//!   depend on the path.
+//!
+//! Each iteration descends by UCB1, expands one class and finishes the deal with
+//! random legal cards. The result is a sampled estimate, not an exact value.
+
+use super::{ranks_by_strength, Score};
+use crate::gameplay::playing::{card_points, PlayingState, LAST_TRICK_BONUS, TOTAL_CARD_POINTS};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+/// UCB1 exploration constant, on values scaled to [0, 1].
+const EXPLORATION: f64 = 1.4;
+const BELOTE_BONUS: u16 = 20;
+
+/// Legal moves of the player to move, one card per equivalence class: the
+/// strongest card of each run of equivalent cards.
+pub fn equivalent_moves(state: &PlayingState) -> u32 {
+    let legal = state.get_legal_moves();
+    let hand = state.hands[state.current_player as usize];
+    // Cards that can separate two of ours: still in hands or on the table.
+    let trick = state
+        .current_trick
+        .iter()
+        .filter(|&&c| c < 32)
+        .fold(0u32, |m, &c| m | 1 << c);
+    let in_play = state.hands.iter().fold(trick, |m, &h| m | h);
+
+    let mut moves = 0u32;
+    for suit in 0..4u8 {
+        // Strongest first: a card joins the run of the previous one of ours
+        // when nothing else in play ranks between them and the points match.
+        let mut previous: Option<u8> = None;
+        for rank in ranks_by_strength(suit, state.trump) {
+            let card = suit * 8 + rank;
+            if in_play & (1 << card) == 0 {
+                continue;
+            }
+            if hand & (1 << card) == 0 {
+                previous = None;
+                continue;
+            }
+            let same_run = previous.is_some_and(|p| {
+                card_points(p, state.trump) == card_points(card, state.trump)
+                    && !is_belote_card(state, p)
+                    && !is_belote_card(state, card)
+            });
+            if !same_run {
+                moves |= 1 << card;
+            }
+            previous = Some(card);
+        }
+    }
+    moves & legal
+}
+
+/// King or queen of a suit trump, whose play may score the belote.
+fn is_belote_card(state: &PlayingState, card: u8) -> bool {
+    state.rules.belote
+        && state.trump < 4
+        && card / 8 == state.trump
+        && (card % 8 == 5 || card % 8 == 6)
+}
+
+#[derive(Default)]
+struct Node {
+    visits: u32,
+    /// Sum over visits of the points `team` won from this node to the end.
+    total: f64,
+    /// Classes not expanded yet.
+    untried: u32,
+    children: Vec<(u8, usize)>,
+}
+
+struct Tree {
+    nodes: Vec<Node>,
+    /// Nodes at a trick boundary, by position.
+    boundaries: HashMap<BoundaryKey, usize>,
+    team: usize,
+    scale: f64,
+}
+
+/// Everything the rest of a deal depends on at a trick boundary, apart from the
+/// points already scored.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct BoundaryKey {
+    hands: [u32; 4],
+    leader: u8,
+    has_trick: [bool; 2],
+    belote_scored: [bool; 2],
+}
+
+impl BoundaryKey {
+    fn of(state: &PlayingState) -> Self {
+        BoundaryKey {
+            hands: state.hands,
+            leader: state.current_player,
+            has_trick: state.tricks_won.map(|t| t > 0),
+            belote_scored: state.belote_scored,
+        }
+    }
+}
+
+impl Tree {
+    fn node_for(&mut self, state: &PlayingState) -> (usize, bool) {
+        let key = (state.trick_size == 0).then(|| BoundaryKey::of(state));
+        if let Some(&idx) = key.as_ref().and_then(|k| self.boundaries.get(k)) {
+            return (idx, false);
+        }
+        let idx = self.nodes.len();
+        self.nodes.push(Node {
+            untried: if state.is_terminal() {
+                0
+            } else {
+                equivalent_moves(state)
+            },
+            ..Node::default()
+        });
+        if let Some(key) = key {
+            self.boundaries.insert(key, idx);
+        }
+        (idx, true)
+    }
+
+    /// Mean points `team` gets from `state` on by playing `card`, the card's own
+    /// trick included.
+    fn mean_after(&self, state: &PlayingState, card: u8, child: usize) -> f64 {
+        let mut next = *state;
+        next.play_card(card);
+        let gained = (next.points[self.team] - state.points[self.team]) as f64;
+        let node = &self.nodes[child];
+        gained + node.total / node.visits.max(1) as f64
+    }
+
+    /// UCB1 child of `idx`, from the point of view of the player to move.
+    fn select(&self, state: &PlayingState, idx: usize) -> (u8, usize) {
+        let node = &self.nodes[idx];
+        let maximizing = (state.current_player % 2) as usize == self.team;
+        let log_visits = (node.visits.max(1) as f64).ln();
+        let ucb = |&(card, child): &(u8, usize)| {
+            let mean = self.mean_after(state, card, child) / self.scale;
+            let value = if maximizing { mean } else { 1.0 - mean };
+            let visits = self.nodes[child].visits.max(1) as f64;
+            value + EXPLORATION * (log_visits / visits).sqrt()
+        };
+        *node
+            .children
+            .iter()
+            .max_by(|a, b| ucb(a).total_cmp(&ucb(b)))
+            .expect("selected node has children")
+    }
+
+    fn iterate<R: Rng>(&mut self, root: &PlayingState, rng: &mut R) {
+        let mut state = *root;
+        let mut path = vec![(0, state.points[self.team])];
+        loop {
+            let idx = path.last().unwrap().0;
+            if state.is_terminal() {
+                break;
+            }
+            let untried = self.nodes[idx].untried;
+            let (card, child) = if untried != 0 {
+                let card = nth_card(untried, rng.gen_range(0..untried.count_ones()));
+                self.nodes[idx].untried &= !(1 << card);
+                let mut next = state;
+                next.play_card(card);
+                let (child, new) = self.node_for(&next);
+                self.nodes[idx].children.push((card, child));
+                if new {
+                    state = next;
+                    path.push((child, state.points[self.team]));
+                    rollout(&mut state, rng);
+                    break;
+                }
+                (card, child)
+            } else {
+                self.select(&state, idx)
+            };
+            state.play_card(card);
+            path.push((child, state.points[self.team]));
+        }
+
+        let result = state.points[self.team];
+        for (idx, scored) in path {
+            let node = &mut self.nodes[idx];
+            node.visits += 1;
+            node.total += (result - scored) as f64;
+        }
+    }
+}
+
+/// Plays the deal out with random legal cards.
+fn rollout<R: Rng>(state: &mut PlayingState, rng: &mut R) {
+    while !state.is_terminal() {
+        let legal = state.get_legal_moves();
+        state.play_card(nth_card(legal, rng.gen_range(0..legal.count_ones())));
+    }
+}
+
+/// Card of rank `n` (from 0) among the set bits of `cards`.
+fn nth_card(mut cards: u32, n: u32) -> u8 {
+    for _ in 0..n {
+        cards &= cards - 1;
+    }
+    cards.trailing_zeros() as u8
+}
+
+/// Estimated final points of `team` (0 = NS, 1 = EW) and the move to play, from
+/// `iterations` MCTS iterations. The move is the most visited one; the score is
+/// its mean outcome. `seed` makes the search reproducible.
+pub fn solve_mcts(
+    state: &PlayingState,
+    team: usize,
+    iterations: u32,
+    seed: Option<u64>,
+) -> (Score, u8) {
+    if state.is_terminal() {
+        return (state.points[team] as Score, 0xFF);
+    }
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let max_points = TOTAL_CARD_POINTS + LAST_TRICK_BONUS + state.rules.capot_bonus + BELOTE_BONUS;
+    let mut tree = Tree {
+        nodes: Vec::new(),
+        boundaries: HashMap::new(),
+        team,
+        scale: max_points as f64,
+    };
+    tree.node_for(state);
+    for _ in 0..iterations.max(1) {
+        tree.iterate(state, &mut rng);
+    }
+
+    let &(card, child) = tree.nodes[0]
+        .children
+        .iter()
+        .max_by_key(|&&(card, child)| (tree.nodes[child].visits, std::cmp::Reverse(card)))
+        .expect("root has a legal move");
+    let mean = tree.mean_after(state, card, child);
+    ((state.points[team] as f64 + mean).round() as Score, card)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::playing::{ALL_TRUMP, HEARTS, SPADES};
+    use crate::solver::{random_ending, solve_root_moves};
+
+    fn card(suit: u8, rank: u8) -> u8 {
+        suit * 8 + rank
+    }
+
+    #[test]
+    fn test_equivalent_moves() {
+        let mut state = PlayingState::new(HEARTS);
+        // South: 7S 8S 9S (0 points each, touching) and AS 10S (touching but
+        // 11 and 10 points): three classes.
+        state.hands[0] = [0, 1, 2, 3, 7]
+            .iter()
+            .fold(0, |m, &r| m | 1 << card(SPADES, r));
+        state.hands[1] = 1 << card(SPADES, 5);
+        assert_eq!(
+            equivalent_moves(&state),
+            1 << card(SPADES, 2) | 1 << card(SPADES, 3) | 1 << card(SPADES, 7)
+        );
+
+        // A card on the table splits a run: the 8S led separates 7S from 9S.
+        state.hands[0] = 1 << card(SPADES, 0) | 1 << card(SPADES, 2);
+        state.hands[3] = 1 << card(SPADES, 1);
+        state.current_player = 3;
+        state.trick_starter = 3;
+        state.play_card(card(SPADES, 1));
+        assert_eq!(equivalent_moves(&state), state.get_legal_moves());
+        assert_eq!(equivalent_moves(&state).count_ones(), 2);
+    }
+
+    #[test]
+    fn test_solve_mcts_finds_best_move() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for _ in 0..10 {
+            let state = random_ending(12, &mut rng);
+            let team = (state.current_player % 2) as usize;
+            let values = solve_root_moves(&state, team, Some(32), None);
+            let best = values.iter().map(|&(_, v)| v).max().unwrap();
+            let (score, mv) = solve_mcts(&state, team, 4000, Some(1));
+            let chosen = values.iter().find(|&&(c, _)| c == mv).unwrap().1;
+            assert!(best - chosen <= 5, "{:?}: {} vs {}", values, mv, best);
+            assert!((score - best).abs() <= 20, "{} vs {}", score, best);
+        }
+        // Reproducible from the seed, whatever the contract.
+        let mut state = PlayingState::new(ALL_TRUMP);
+        state.hands = crate::data_gen::common::generate_random_hands(&mut rng);
+        assert_eq!(
+            solve_mcts(&state, 0, 500, Some(9)),
+            solve_mcts(&state, 0, 500, Some(9))
+        );
+    }
+}
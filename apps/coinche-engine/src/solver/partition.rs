@@ -0,0 +1,198 @@
+//! Partition cache: results of searches that reach the end of the deal, keyed by
+//! the abstract shape of the position instead of its exact cards.
+//!
+//! At a trick boundary, two positions play out identically when, suit by suit and
+//! from the strongest card down, the remaining cards have the same owners, the same
+//! point values and the same belote role. The key below encodes exactly that, so
+//! positions from different deals (or different PIMC worlds) share entries. Values
+//! are the points `team` still has to win (independent of what is already scored),
+//! which makes them valid across deals; the cache therefore outlives a single solve.
+
+use super::{ranks_by_strength, Score};
+use crate::gameplay::playing::{card_points, PlayingState, RANK_K, RANK_Q};
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const CACHE_SIZE: usize = 1 << 20; // 1M entries ~ 16MB per thread
+const CACHE_MASK: u64 = (CACHE_SIZE as u64) - 1;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Default)]
+pub(super) struct PartitionEntry {
+    pub key: u64,      // 0 = empty
+    pub score: Score,  // Points still to be won by the searching team
+    pub flag: u8,      // Same encoding as the main TT: 0 exact, 1 lower, 2 upper bound
+    pub best_move: u8, // As given by `abstract_move`
+}
+
+thread_local! {
+    // Allocated on first use, i.e. only by threads that solve with the cache enabled.
+    static CACHE: RefCell<Vec<PartitionEntry>> =
+        RefCell::new(vec![PartitionEntry::default(); CACHE_SIZE]);
+    static HITS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Turns the partition cache on or off for all threads. Off by default: it only
+/// pays off when many related positions are solved, as in batch generation.
+pub fn set_partition_cache(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn partition_cache_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Cumulative partition cache hits of the calling thread.
+pub fn partition_hits() -> u64 {
+    HITS.with(|h| h.get())
+}
+
+/// Empties the calling thread's cache (entries never go stale, this only frees room).
+pub fn clear_partition_cache() {
+    CACHE.with(|c| c.borrow_mut().fill(PartitionEntry::default()));
+}
+
+/// Whether `state` may use the cache when searched `depth` plies deep: only at a
+/// trick boundary, and only if the search reaches the end of the deal.
+pub(super) fn applies(state: &PlayingState, depth: u8) -> bool {
+    if state.trick_size != 0 || !partition_cache_enabled() {
+        return false;
+    }
+    let cards_left: u32 = state.hands.iter().map(|h| h.count_ones()).sum();
+    depth as u32 >= cards_left
+}
+
+#[inline]
+fn mix(h: u64, v: u64) -> u64 {
+    // FNV-1a step followed by a multiply-xorshift to spread small inputs.
+    let h = (h ^ v).wrapping_mul(0x0000_0100_0000_01B3);
+    h ^ (h >> 29)
+}
+
+/// Abstract key of a trick-boundary position searched for `team`.
+pub(super) fn abstract_key(state: &PlayingState, team: usize) -> u64 {
+    let mut h: u64 = 0xCBF2_9CE4_8422_2325;
+    h = mix(h, state.trump as u64);
+    h = mix(h, state.current_player as u64);
+    h = mix(h, team as u64);
+    // Capot is still possible for a team only while the other has no trick.
+    h = mix(
+        h,
+        (state.tricks_won[0] > 0) as u64 | ((state.tricks_won[1] > 0) as u64) << 1,
+    );
+    h = mix(
+        h,
+        state.belote_scored[0] as u64 | (state.belote_scored[1] as u64) << 1,
+    );
+    // The capot is worth what the rules make it: the cache outlives a rule change.
+    h = mix(
+        h,
+        state.rules.capot_bonus as u64
+            | (state.rules.der_in_capot as u64) << 16
+            | (state.rules.belote as u64) << 17,
+    );
+
+    for suit in 0..4u8 {
+        for rank in ranks_by_strength(suit, state.trump) {
+            let card = suit * 8 + rank;
+            let Some(owner) = (0..4).find(|&p| state.hands[p] & (1 << card) != 0) else {
+                continue;
+            };
+            let belote =
+                state.rules.belote && suit == state.trump && (rank == RANK_K || rank == RANK_Q);
+            let code =
+                owner as u64 | (card_points(card, state.trump) as u64) << 2 | (belote as u64) << 8;
+            h = mix(h, code);
+        }
+        h = mix(h, 0x1FF); // Suit separator (not a valid card code)
+    }
+
+    // 0 marks empty slots.
+    h.max(1)
+}
+
+/// Position of `card` within the remaining cards of its suit (strongest first),
+/// which names the same card in every position sharing the abstract key.
+pub(super) fn abstract_move(state: &PlayingState, card: u8) -> u8 {
+    let suit = card / 8;
+    let in_play = state.hands.iter().fold(0u32, |m, &h| m | h);
+    let position = ranks_by_strength(suit, state.trump)
+        .iter()
+        .map(|&r| suit * 8 + r)
+        .filter(|&c| in_play & (1 << c) != 0)
+        .position(|c| c == card)
+        .unwrap_or(0) as u8;
+    suit * 8 + position
+}
+
+/// Inverse of `abstract_move` for `state`.
+pub(super) fn concrete_move(state: &PlayingState, code: u8) -> u8 {
+    let suit = code / 8;
+    let in_play = state.hands.iter().fold(0u32, |m, &h| m | h);
+    ranks_by_strength(suit, state.trump)
+        .iter()
+        .map(|&r| suit * 8 + r)
+        .filter(|&c| in_play & (1 << c) != 0)
+        .nth((code % 8) as usize)
+        .unwrap_or(0xFF)
+}
+
+pub(super) fn probe(key: u64) -> Option<PartitionEntry> {
+    let entry = CACHE.with(|c| c.borrow()[(key & CACHE_MASK) as usize]);
+    if entry.key == key {
+        HITS.with(|h| h.set(h.get() + 1));
+        Some(entry)
+    } else {
+        None
+    }
+}
+
+pub(super) fn store(key: u64, score: Score, flag: u8, best_move: u8) {
+    CACHE.with(|c| {
+        c.borrow_mut()[(key & CACHE_MASK) as usize] = PartitionEntry {
+            key,
+            score,
+            flag,
+            best_move,
+        };
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameplay::playing::{CLUBS, HEARTS, SPADES};
+    use crate::gameplay::rules::RuleSet;
+
+    #[test]
+    fn test_equivalent_deals_share_key() {
+        // Each side holds the same shape; only the non-scoring spot cards differ
+        // (7 vs 8 of Spades/Clubs), with no card between them still in play.
+        let mut a = PlayingState::new(HEARTS);
+        a.hands = [
+            (1 << (HEARTS * 8 + 7)) | (1 << (SPADES * 8)),
+            (1 << (HEARTS * 8)) | (1 << (CLUBS * 8)),
+            (1 << (SPADES * 8 + 7)) | (1 << (CLUBS * 8 + 7)),
+            (1 << (HEARTS * 8 + 3)) | (1 << (CLUBS * 8 + 3)),
+        ];
+        let mut b = a;
+        b.hands[0] = (1 << (HEARTS * 8 + 7)) | (1 << (SPADES * 8 + 1));
+        b.hands[1] = (1 << (HEARTS * 8)) | (1 << (CLUBS * 8 + 1));
+        assert_eq!(abstract_key(&a, 0), abstract_key(&b, 0));
+        assert_ne!(abstract_key(&a, 0), abstract_key(&a, 1));
+        let mut flat = a;
+        flat.set_rules(RuleSet::flat_capot());
+        assert_ne!(abstract_key(&a, 0), abstract_key(&flat, 0));
+
+        // Swapping the owners of two scoring cards changes the position.
+        let mut c = a;
+        c.hands[2] = (1 << (SPADES * 8 + 7)) | (1 << (CLUBS * 8 + 3));
+        c.hands[3] = (1 << (HEARTS * 8 + 3)) | (1 << (CLUBS * 8 + 7));
+        assert_ne!(abstract_key(&a, 0), abstract_key(&c, 0));
+
+        // Moves translate between equivalent deals.
+        let code = abstract_move(&a, SPADES * 8);
+        assert_eq!(concrete_move(&b, code), SPADES * 8 + 1);
+    }
+}
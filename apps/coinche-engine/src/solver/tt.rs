@@ -0,0 +1,271 @@
+//! Transposition table of the minimax search: one per thread, reused across solves.
+//!
+//! Entries are grouped in buckets of `WAYS` adjacent slots, indexed by the low
+//! bits of the Zobrist hash. Each solve starts a new generation, so entries of
+//! earlier solves are stale without clearing the table. A store goes,
+//! in order of preference, to the slot already holding the position, to a free or
+//! stale slot, and lastly to the shallowest entry of the bucket: deep results of
+//! early iterations are not clobbered by the many shallow nodes searched later.
+//!
+//! The worlds of one PIMC decision differ only in a few hidden cards, and once
+//! those are played their searches meet the same positions. With sharing on (see
+//! `set_pimc_tt_sharing`, off by default), solves run inside `with_tt_scope` share
+//! one generation per thread and scope instead of starting cold. Values are stored
+//! relative to the points already won and the hash covers the rest of the deal, so
+//! a world's value is unchanged; but the best moves and depth-limited bounds left
+//! by earlier worlds steer the search, so a world may pick another card of equal
+//! value. PIMC votes then depend on which worlds ran before on the same thread,
+//! which for parallel decisions is up to the scheduler: shared mode is not
+//! reproducible, and seeded decisions never share (see `pimc_tt_scope`).
+
+use super::Score;
+use crate::profiling::{self, Counter};
+use std::cell::{Cell, RefCell};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+const WAYS: usize = 4;
+const BUCKETS: usize = 1 << 22; // 16 Million entries ~ 384MB per thread
+const BUCKET_MASK: u64 = (BUCKETS as u64) - 1;
+
+static SHARING: AtomicBool = AtomicBool::new(false);
+static NEXT_SCOPE: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Clone, Copy)]
+pub(super) struct TTEntry {
+    pub key: u64, // For collision detection
+    pub score: Score,
+    pub best_move: u8,
+    pub flag: u8,
+    pub depth: u8, // Added for Iterative Deepening
+    pub gen: u32,  // Generation ID for zero-cost reset, 0 = empty
+}
+
+impl Default for TTEntry {
+    fn default() -> Self {
+        TTEntry {
+            key: 0,
+            score: 0,
+            best_move: 0xFF,
+            flag: super::EXACT,
+            depth: 0,
+            gen: 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Bucket([TTEntry; WAYS]);
+
+/// Cumulative table activity of one thread.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TtStats {
+    pub probes: u64,
+    /// Probes finding the position, whatever the depth it was searched to.
+    pub hits: u64,
+    pub stores: u64,
+    /// Stores that evicted another position of the current solve.
+    pub replacements: u64,
+}
+
+impl TtStats {
+    /// Activity between `before` and `self`.
+    pub fn since(&self, before: &TtStats) -> TtStats {
+        TtStats {
+            probes: self.probes - before.probes,
+            hits: self.hits - before.hits,
+            stores: self.stores - before.stores,
+            replacements: self.replacements - before.replacements,
+        }
+    }
+
+    pub fn hit_rate(&self) -> f64 {
+        self.hits as f64 / self.probes.max(1) as f64
+    }
+}
+
+thread_local! {
+    static TABLE: RefCell<Vec<Bucket>> = RefCell::new(vec![Bucket::default(); BUCKETS]);
+    static GEN: Cell<u32> = const { Cell::new(0) };
+    // Generation every solve reuses while set, see `with_tt_scope`.
+    static PINNED: Cell<Option<u32>> = const { Cell::new(None) };
+    // Last scope entered by this thread and its generation.
+    static SCOPE: Cell<(u64, u32)> = const { Cell::new((0, 0)) };
+    static STATS: Cell<TtStats> = const {
+        Cell::new(TtStats { probes: 0, hits: 0, stores: 0, replacements: 0 })
+    };
+}
+
+/// Table statistics of the calling thread since it started.
+pub fn tt_stats() -> TtStats {
+    STATS.with(|s| s.get())
+}
+
+fn record(update: impl FnOnce(&mut TtStats)) {
+    STATS.with(|s| {
+        let mut stats = s.get();
+        update(&mut stats);
+        s.set(stats);
+    });
+}
+
+/// Lets PIMC worlds share TT entries (off by default): faster, but the votes depend
+/// on the order worlds are solved in, see the module doc. Seeded decisions ignore it.
+pub fn set_pimc_tt_sharing(enabled: bool) {
+    SHARING.store(enabled, Ordering::Relaxed);
+}
+
+pub fn pimc_tt_sharing_enabled() -> bool {
+    SHARING.load(Ordering::Relaxed)
+}
+
+/// A scope id no other caller gets, for `with_tt_scope`.
+pub fn new_tt_scope() -> u64 {
+    NEXT_SCOPE.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Scope for the worlds of a PIMC decision, `None` (cold tables) with sharing off
+/// or when the decision is seeded: a seeded decision must not depend on what
+/// earlier worlds left in the table.
+pub fn pimc_tt_scope(seed: Option<u64>) -> Option<u64> {
+    (pimc_tt_sharing_enabled() && seed.is_none()).then(new_tt_scope)
+}
+
+/// Runs `f` with the solves of the calling thread sharing the TT with the other
+/// solves of `scope` run earlier on this thread, as long as no other scope came
+/// in between; from a cold table without a scope. Every solve of a scope must be
+/// for the same contract, team and objective.
+pub fn with_tt_scope<T>(scope: Option<u64>, f: impl FnOnce() -> T) -> T {
+    let Some(scope) = scope else {
+        return f();
+    };
+    let gen = match SCOPE.with(|s| s.get()) {
+        (last, gen) if last == scope => gen,
+        _ => {
+            let gen = fresh_generation();
+            SCOPE.with(|s| s.set((scope, gen)));
+            gen
+        }
+    };
+    let outer = PINNED.with(|p| p.replace(Some(gen)));
+    let result = f();
+    PINNED.with(|p| p.set(outer));
+    result
+}
+
+/// Starts the generation of a new solve, which ages every entry stored so far;
+/// inside `with_tt_scope`, returns the scope's generation instead.
+pub(super) fn new_generation() -> u32 {
+    PINNED.with(|p| p.get()).unwrap_or_else(fresh_generation)
+}
+
+fn fresh_generation() -> u32 {
+    GEN.with(|g| {
+        // Skip 0 on wrap-around: it marks empty slots.
+        let gen = g.get().wrapping_add(1).max(1);
+        g.set(gen);
+        gen
+    })
+}
+
+/// Generation of the calling thread's latest solve.
+#[cfg(test)]
+pub(super) fn generation() -> u32 {
+    GEN.with(|g| g.get())
+}
+
+/// Entry of the position `hash` stored during generation `gen`.
+pub(super) fn probe(hash: u64, gen: u32) -> Option<TTEntry> {
+    profiling::count(Counter::TtProbes);
+    let found = TABLE.with(|t| {
+        t.borrow()[(hash & BUCKET_MASK) as usize]
+            .0
+            .iter()
+            .find(|e| e.key == hash && e.gen == gen)
+            .copied()
+    });
+    if found.is_some() {
+        profiling::count(Counter::TtHits);
+    }
+    record(|s| {
+        s.probes += 1;
+        s.hits += found.is_some() as u64;
+    });
+    found
+}
+
+/// Stores `entry` (of generation `entry.gen`) in its bucket.
+pub(super) fn store(entry: TTEntry) {
+    profiling::count(Counter::TtStores);
+    let evicted = TABLE.with(|t| {
+        let mut table = t.borrow_mut();
+        let bucket = &mut table[(entry.key & BUCKET_MASK) as usize].0;
+        let slot = match bucket.iter().position(|e| e.key == entry.key) {
+            Some(i) => {
+                let old = bucket[i];
+                // A deeper result of this solve is worth more than a shallower one.
+                if old.gen == entry.gen && old.depth > entry.depth {
+                    return false;
+                }
+                i
+            }
+            None => bucket
+                .iter()
+                .position(|e| e.gen != entry.gen)
+                .unwrap_or_else(|| {
+                    (0..WAYS)
+                        .min_by_key(|&i| bucket[i].depth)
+                        .expect("buckets are not empty")
+                }),
+        };
+        let evicted = bucket[slot].gen == entry.gen && bucket[slot].key != entry.key;
+        bucket[slot] = entry;
+        evicted
+    });
+    record(|s| {
+        s.stores += 1;
+        s.replacements += evicted as u64;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: u64, depth: u8, gen: u32) -> TTEntry {
+        TTEntry {
+            key,
+            depth,
+            gen,
+            ..TTEntry::default()
+        }
+    }
+
+    #[test]
+    fn test_replacement_policy() {
+        let gen = new_generation();
+        // Five positions of one bucket: the fifth evicts the shallowest.
+        let keys: Vec<u64> = (1..=5).map(|i| i << 40 | 7).collect();
+        for (i, &key) in keys[..4].iter().enumerate() {
+            store(entry(key, 10 - i as u8, gen));
+        }
+        let before = tt_stats();
+        store(entry(keys[4], 9, gen));
+        assert!(probe(keys[3], gen).is_none());
+        assert!(keys[..3].iter().all(|&k| probe(k, gen).is_some()));
+        let stats = tt_stats().since(&before);
+        assert_eq!((stats.stores, stats.replacements), (1, 1));
+        assert_eq!((stats.probes, stats.hits), (4, 3));
+
+        // A shallower result does not overwrite a deeper one of the same solve.
+        store(entry(keys[0], 2, gen));
+        assert_eq!(probe(keys[0], gen).unwrap().depth, 10);
+
+        // After a new generation the old entries are free slots, deep or not.
+        let next = new_generation();
+        assert!(probe(keys[0], next).is_none());
+        let before = tt_stats();
+        store(entry(keys[3], 1, next));
+        assert_eq!(tt_stats().since(&before).replacements, 0);
+        assert!(probe(keys[3], next).is_some());
+    }
+}
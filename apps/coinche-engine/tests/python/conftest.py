@@ -0,0 +1,67 @@
+"""Shared fixtures of the Python integration tests.
+
+The tests import the extension module installed by `maturin develop` (see the
+`test-python` target of project.json) and drive it the way the training and
+serving code does. Positions are kept small so that the whole suite runs in
+seconds against a release build.
+"""
+
+import pytest
+
+import coinche_engine as ce
+
+# Each seat holds a whole suit: S the diamonds, W the spades, N the hearts and
+# E the clubs.
+SORTED_DEAL = [0xFF, 0xFF00, 0xFF_0000, 0xFF00_0000]
+
+# Each seat holds two ranks of every suit: S the sevens and eights, W the nines
+# and tens, N the jacks and queens, E the kings and aces. Full solves of it take
+# a fraction of a second, unlike most deals (and the sorted one).
+RANK_DEAL = [0x0303_0303, 0x0C0C_0C0C, 0x3030_3030, 0xC0C0_C0C0]
+
+
+def lowest_card(mask):
+    """Lowest card index of a card mask."""
+    return (mask & -mask).bit_length() - 1
+
+
+def play_out(match):
+    """Plays the lowest legal card until the deal is over."""
+    while match.phase == ce.Phase.PLAYING:
+        match.play_card(lowest_card(match.get_playing_state().get_legal_moves()))
+    return match
+
+
+@pytest.fixture
+def sorted_deal():
+    return list(SORTED_DEAL)
+
+
+@pytest.fixture
+def rank_deal():
+    return list(RANK_DEAL)
+
+
+@pytest.fixture
+def endgame():
+    """Two cards left per seat, spades trumps, South to lead."""
+    state = ce.PlayingState(ce.SPADES)
+    for seat in range(4):
+        state.set_hand(seat, 0b11 << (8 * seat))
+    return state
+
+
+@pytest.fixture
+def playing_match(sorted_deal):
+    """South declared 80 in spades, no card played yet."""
+    match = ce.CoincheMatch(3, sorted_deal)
+    match.bid(ce.Bid(80, ce.SPADES))
+    for _ in range(3):
+        match.bid(None)
+    return match
+
+
+@pytest.fixture
+def finished_match(playing_match):
+    """The deal of `playing_match`, played out."""
+    return play_out(playing_match)
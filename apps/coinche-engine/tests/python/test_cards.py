@@ -0,0 +1,156 @@
+"""Card notation, deal validation and the model encodings."""
+
+import pytest
+
+import coinche_engine as ce
+
+EXPORTS = {
+    "Suit",
+    "Rank",
+    "DIAMONDS",
+    "SPADES",
+    "HEARTS",
+    "CLUBS",
+    "NO_TRUMP",
+    "ALL_TRUMP",
+    "card_index",
+    "card_suit_rank",
+    "card_name",
+    "parse_card",
+    "validate_deal",
+    "deal_id",
+    "deal_from_id",
+    "deal_random",
+    "deal_ids",
+    "encode_hand",
+    "decode_hand",
+    "encode_card",
+    "decode_card",
+    "encode_trick",
+    "decode_trick",
+    "encode_gameplay_features",
+    "GAMEPLAY_FEATURES",
+    "encode_auction_features",
+    "AUCTION_FEATURES",
+    "AUCTION_HISTORY_BIDS",
+    "flat_action_index",
+    "flat_action",
+    "FLAT_ACTIONS",
+    "dual_head_action_index",
+    "dual_head_action",
+    "AUCTION_ACTIONS",
+    "encode_play_history",
+    "decode_play_history",
+    "play_history_mask",
+    "attribute_plays",
+}
+
+
+def test_suit_and_rank_enums():
+    assert [s.name for s in ce.Suit][:4] == ["DIAMONDS", "SPADES", "HEARTS", "CLUBS"]
+    assert (ce.NO_TRUMP, ce.ALL_TRUMP) == (ce.Suit.NO_TRUMP, ce.Suit.ALL_TRUMP)
+    assert ce.SPADES == 1 and ce.Rank.JACK == 4
+
+
+def test_card_notation_round_trips():
+    card = ce.card_index(ce.Suit.HEARTS, ce.Rank.TEN)
+    assert ce.card_suit_rank(card) == (ce.HEARTS, ce.Rank.TEN)
+    assert ce.card_name(card) == "10H"
+    assert ce.parse_card("10H") == card
+    with pytest.raises(ValueError):
+        ce.parse_card("ZZ")
+    with pytest.raises(ValueError):
+        ce.card_index(7, 0)
+    with pytest.raises(ValueError):
+        ce.card_name(32)
+    with pytest.raises(ValueError):
+        ce.card_suit_rank(32)
+
+
+def test_validate_deal(sorted_deal):
+    ce.validate_deal(sorted_deal)
+    with pytest.raises(ValueError, match="cards"):
+        ce.validate_deal([0xFF, 0xFF, 0, 0])
+    with pytest.raises(ValueError):
+        ce.validate_deal(sorted_deal[:3])
+
+
+def test_deal_ids(sorted_deal):
+    deal_id = ce.deal_id(sorted_deal)
+    assert deal_id == sum(hand << (32 * seat) for seat, hand in enumerate(sorted_deal))
+    assert ce.deal_from_id(deal_id) == sorted_deal
+    with pytest.raises(ValueError):
+        ce.deal_id([0xFF, 0xFF, 0, 0])
+    with pytest.raises(ValueError):
+        ce.deal_from_id(1)
+
+    hands, deal_id = ce.deal_random(seed=3)
+    ce.validate_deal(hands)
+    assert ce.deal_random(seed=3) == (hands, deal_id)
+    assert ce.deal_id(hands) == deal_id and deal_id >= 1 << 96
+    assert ce.deal_ids(sorted_deal + hands) == [ce.deal_id(sorted_deal), deal_id]
+    with pytest.raises(ValueError, match="Deal 1"):
+        ce.deal_ids(sorted_deal + [0xFF] * 4)
+
+
+def test_hand_and_card_encodings():
+    vector = ce.encode_hand(0xF0F)
+    assert len(vector) == 32 and sum(vector) == 8
+    assert ce.decode_hand(vector) == 0xF0F
+    assert ce.decode_card(ce.encode_card(3)) == 3
+    with pytest.raises(ValueError):
+        ce.encode_card(32)
+    with pytest.raises(ValueError):
+        ce.decode_hand([1.0] * 3)
+
+
+def test_trick_encoding():
+    matrix = ce.encode_trick([1, 255, 9, 255])
+    assert len(matrix) == 4 and sum(matrix[1]) == 0
+    assert ce.decode_trick(matrix) == [1, 255, 9, 255]
+
+
+def test_model_features():
+    features = ce.encode_gameplay_features(0xFF, 0xFF00, [16], ce.SPADES)
+    assert len(features) == ce.GAMEPLAY_FEATURES
+
+    auction = [ce.Bid(80, ce.SPADES), None, "coinche", None, None, None]
+    assert len(ce.encode_auction_features(auction, 3, 0)) == ce.AUCTION_FEATURES
+    assert ce.AUCTION_HISTORY_BIDS >= 1
+    with pytest.raises(ValueError):
+        ce.encode_auction_features(auction, 3, 7)
+
+
+@pytest.mark.parametrize("action", [None, "coinche", "surcoinche", 5])
+def test_action_spaces_round_trip(action):
+    index = ce.flat_action_index(action)
+    assert 0 <= index < ce.FLAT_ACTIONS
+    assert ce.flat_action(index) == action
+    head, head_index = ce.dual_head_action_index(action)
+    assert ce.dual_head_action(head, head_index) == action
+    assert head_index < (ce.AUCTION_ACTIONS if head == 0 else 32)
+
+
+def test_bid_action_round_trips():
+    bid = ce.flat_action(ce.flat_action_index(ce.Bid(80, ce.SPADES)))
+    assert (bid.value, bid.trump) == (80, ce.SPADES)
+    bid = ce.dual_head_action(*ce.dual_head_action_index(ce.Bid(90, ce.HEARTS)))
+    assert (bid.value, bid.trump) == (90, ce.HEARTS)
+    with pytest.raises(ValueError):
+        ce.flat_action(ce.FLAT_ACTIONS)
+
+
+def test_play_history():
+    packed = ce.encode_play_history([(0, 0, 3), (0, 1, 9)])
+    assert ce.decode_play_history(packed) == [(0, 0, 3), (0, 1, 9)]
+    assert ce.play_history_mask(packed) == 1 << 3 | 1 << 9
+    with pytest.raises(ValueError, match="Play 1"):
+        ce.encode_play_history([(0, 0, 3), (8, 1, 9)])
+    with pytest.raises(ValueError):
+        ce.encode_play_history([(0, 4, 3)])
+
+    # West trumps the diamond lead and leads the second trick.
+    attributed = ce.attribute_plays([0, 8, 16, 24, 9], 0, ce.SPADES)
+    assert ce.decode_play_history(attributed)[-1] == (1, 1, 9)
+    with pytest.raises(ValueError):
+        ce.attribute_plays([40], 0, ce.SPADES)
@@ -0,0 +1,534 @@
+"""Dataset generation: deals, positions, labels, self-play and verification."""
+
+import json
+import math
+
+import pytest
+
+import coinche_engine as ce
+from conftest import lowest_card
+
+EXPORTS = {
+    "generate_bidding_hands",
+    "solve_bidding_batch",
+    "HandBuilder",
+    "generate_constrained_deals",
+    "DealQuota",
+    "generate_quota_deals",
+    "TooManyForcedCardsError",
+    "ShapeImpossibleError",
+    "UnsatisfiableError",
+    "generate_raw_gameplay_batch",
+    "GameplayBatch",
+    "GameplaySample",
+    "generate_positions_for_hand",
+    "StageConfig",
+    "solve_gameplay_batch",
+    "transform_score_labels",
+    "gameplay_schema_columns",
+    "SCHEMA_VERSION",
+    "invalid_reason_counts",
+    "verify_dataset",
+    "VerificationReport",
+    "evaluate_policy_on_dataset",
+    "PolicyEvaluation",
+    "recompute_deal_ids",
+    "generate_opening_lead_batch",
+    "OpeningLeadSample",
+    "generate_evaluation_batch",
+    "EvaluationSample",
+    "EVALUATION_FEATURES",
+    "hand_percentile",
+    "deal_difficulty",
+    "deal_difficulty_batch",
+    "downsample_easy_positions",
+    "deal_tags",
+    "deal_tags_batch",
+    "generate_puzzles",
+    "PuzzleConstraints",
+    "Puzzle",
+    "generate_selfplay_batch",
+    "SelfPlayGame",
+    "generate_human_play_batch",
+    "HumanPlayGame",
+    "VecCoincheEnv",
+}
+
+
+@pytest.fixture
+def endgames():
+    """Three endings of 4 cards per seat or fewer around a fixed South hand."""
+    return ce.generate_positions_for_hand(
+        0x0F0F, 3, stage_config=ce.StageConfig(0, 0, 1), seed=2
+    )
+
+
+def solve(batch, **kwargs):
+    return ce.solve_gameplay_batch(
+        batch.flat_hands,
+        batch.boards,
+        batch.history,
+        batch.trumps,
+        batch.tricks_won,
+        batch.players,
+        0,
+        **kwargs,
+    )
+
+
+def test_bidding_data(tmp_path, rank_deal):
+    hands, strategies = ce.generate_bidding_hands(2, seed=1)
+    assert len(hands) == 8 and len(strategies) == 2
+    assert ce.generate_bidding_hands(2, seed=1) == (hands, strategies)
+
+    checkpoint = str(tmp_path / "bidding")
+    scores = ce.solve_bidding_batch(rank_deal, 0, seed=1, checkpoint=checkpoint)
+    assert len(scores) == 1 and len(scores[0]) == 4
+    resumed = ce.solve_bidding_batch(rank_deal, 0, seed=1, checkpoint=checkpoint, resume=True)
+    assert resumed == scores
+
+
+def test_hand_builder():
+    builder = (
+        ce.HandBuilder(ce.HEARTS)
+        .suit_length(0, ce.HEARTS, 3, 8)
+        .points(0, 20, 162)
+        .hold(0, 1 << 20)
+        .exclude(1, 1 << 21)
+        .belote(0)
+        .balanced(1)
+        .shape(2, [2, 2, 2, 2])
+        .trump_split([3, 2, 2, 1])
+        .max_attempts(100_000)
+    )
+    deal = builder.build(seed=3)
+    ce.validate_deal(deal)
+    assert deal[0] >> 20 & 1 and not deal[1] >> 21 & 1
+    assert builder.build(seed=3) == deal
+
+    deals = ce.generate_constrained_deals(builder, 2, seed=1)
+    assert len(deals) == 8
+    ce.validate_deal(deals[4:])
+
+
+def test_hand_builder_errors():
+    with pytest.raises(ce.TooManyForcedCardsError):
+        ce.HandBuilder(ce.HEARTS).hold(0, 0x1FF).build(seed=1)
+    with pytest.raises(ce.ShapeImpossibleError):
+        ce.HandBuilder(ce.HEARTS).shape(0, [4, 4, 4, 4]).build(seed=1)
+    with pytest.raises(ce.UnsatisfiableError):
+        ce.HandBuilder(ce.HEARTS).points(0, 160, 162).max_attempts(10).build(seed=1)
+    # All of them are ValueErrors for callers that do not tell them apart.
+    with pytest.raises(ValueError):
+        ce.HandBuilder(ce.HEARTS).hold(0, 1).hold(1, 1).build(seed=1)
+
+
+def test_quota_deals():
+    # A quarter of the deals with an 8-card N-S fit, half with 50+ points for South in hearts
+    fit = ce.DealQuota.suit_length([0, 2], 0.25, 8)
+    strong = ce.DealQuota.points([0], ce.HEARTS, 0.5, 50)
+    deals, draws = ce.generate_quota_deals([fit, strong], 40, seed=2)
+    hands = [deals[i : i + 4] for i in range(0, len(deals), 4)]
+    assert len(hands) == 40 and draws >= 40
+    assert sum(fit.holds(h) for h in hands) == 10
+    assert sum(strong.holds(h) for h in hands) == 20
+    assert ce.generate_quota_deals([fit, strong], 40, seed=2) == (deals, draws)
+
+    builder = ce.HandBuilder(ce.HEARTS).suit_length(0, ce.HEARTS, 4, 8)
+    deals, _ = ce.generate_quota_deals([fit], 8, builder=builder, seed=2)
+    assert all(bin(deals[i] >> 8 * ce.HEARTS & 0xFF).count("1") >= 4 for i in range(0, 32, 4))
+    with pytest.raises(ValueError):
+        ce.DealQuota.suit_length([0, 4], 0.5, 8)
+    with pytest.raises(ValueError):
+        ce.generate_quota_deals([ce.DealQuota.suit_length([0], 1.0, 8, suit=0)], 2, max_draws=100)
+
+
+def test_raw_gameplay_batch():
+    batch = ce.generate_raw_gameplay_batch(4, seed=1)
+    assert len(batch) == 4 and len(batch.hands) == 4
+    assert len(batch.flat_hands) == 16
+    for column in (
+        batch.boards,
+        batch.history,
+        batch.trumps,
+        batch.tricks_won,
+        batch.players,
+        batch.plays,
+        batch.declarers,
+        batch.contract_values,
+        batch.deal_ids,
+        batch.declaring_side,
+    ):
+        assert len(column) == 4
+
+    sample = batch[0]
+    assert sample.board == batch.boards[0] and sample.player == batch.players[0]
+    assert sample.hand == sample.hands[sample.player]
+    assert sample.history == batch.history[0] and sample.trump == batch.trumps[0]
+    assert list(sample.tricks_won) == list(batch.tricks_won[0])
+    assert sample.plays == batch.plays[0] and sample.declarer == batch.declarers[0]
+    assert sample.contract is None or sample.contract.trump == sample.trump
+    assert sample.declaring_side == (sample.player % 2 == sample.declarer % 2)
+    assert sample.state().current_player == sample.player
+    dealt = ce.deal_from_id(sample.deal_id)
+    assert all(dealt[seat] & hand == hand for seat, hand in enumerate(sample.hands))
+
+    legacy = ce.generate_raw_gameplay_batch(2, seed=1, schema_version=1)
+    assert isinstance(legacy, tuple) and len(legacy) == 6
+
+
+def test_raw_gameplay_options():
+    batch = ce.generate_raw_gameplay_batch(3, seed=1, side="declarer", contracts="threshold")
+    for sample in batch:
+        assert sample.player % 2 == sample.declarer % 2
+        assert sample.contract.value >= 80
+    with pytest.raises(ValueError):
+        ce.generate_raw_gameplay_batch(1, side="north")
+    with pytest.raises(ValueError):
+        ce.generate_raw_gameplay_batch(1, contracts="auction")
+    with pytest.raises(ValueError):
+        ce.generate_raw_gameplay_batch(1, schema_version=ce.SCHEMA_VERSION + 1)
+
+
+def test_positions_for_hand(endgames):
+    config = ce.StageConfig()
+    assert (config.opening_weight, config.midgame_weight, config.endgame_weight) == (20, 30, 50)
+    assert len(endgames) == 3
+    assert all(sample.hands[0] & ~0x0F0F == 0 for sample in endgames)
+    with pytest.raises(ValueError):
+        ce.generate_positions_for_hand(0xFF, 1, seed=1, schema_version=0)
+
+
+def test_solve_gameplay_batch(endgames, tmp_path):
+    best_cards, best_scores, valid, nodes, times, agreement, entropy, variance, optimal, reasons, forced = (
+        solve(endgames, optimal_epsilon=0.0)
+    )
+    assert all(valid) and len(best_cards) == len(endgames)
+    for sample, card, cards in zip(endgames, best_cards, optimal):
+        assert sample.hand >> card & 1 and cards >> card & 1
+    assert all(n > 0 for n in nodes) and len(times) == len(agreement) == 3
+    assert all(e == 0 for e in entropy) and all(v == 0 for v in variance)
+
+    _, scores, valid = solve(endgames, schema_version=1)
+    assert scores == [int(s) for s in best_scores] and all(valid)
+    assert reasons == [0, 0, 0]
+    assert len(solve(endgames, schema_version=2)) == 8
+    assert len(solve(endgames, schema_version=3)) == 9
+    assert len(solve(endgames, schema_version=5)) == 10
+    assert len(forced) == 3
+    checkpoint = str(tmp_path / "gameplay")
+    assert solve(endgames, checkpoint=checkpoint, checkpoint_every=1)[1] == best_scores
+    with pytest.raises(ValueError):
+        solve(endgames, optimal_epsilon=1.0, schema_version=2)
+    with pytest.raises(ValueError):
+        solve(endgames, score_label="ev", schema_version=1)
+    with pytest.raises(ValueError):
+        solve(endgames, objective="tempo")
+
+    # Values of the team to move need no flip for East-West to move.
+    current = solve(endgames, perspective="current")[1]
+    for sample, ns, mover in zip(endgames, best_scores, current):
+        if sample.player % 2 == 0:
+            assert mover == ns
+
+
+def test_forced_moves():
+    # Twice the last trick: South's only card is forced.
+    hands = [1, 1 << 8, 1 << 16, 1 << 24] * 2
+    history = [~0x0101_0101 & 0xFFFF_FFFF] * 2
+    columns = (hands, [[], []], history, [ce.HEARTS] * 2, [[7, 0]] * 2, [0, 0], 0)
+    cards, scores, valid, *_, forced = ce.solve_gameplay_batch(*columns)
+    assert all(valid) and forced == [True, True] and cards == [0, 0]
+    cards, scores, valid, nodes, *_, forced = ce.solve_gameplay_batch(*columns, skip_forced=True)
+    assert all(valid) and forced == [True, True] and cards == [0, 0]
+    assert all(math.isnan(s) for s in scores) and nodes == [0, 0]
+
+
+def test_adaptive_pimc(endgames):
+    columns = (
+        endgames.flat_hands,
+        endgames.boards,
+        endgames.history,
+        endgames.trumps,
+        endgames.tricks_won,
+        endgames.players,
+        32,
+    )
+    fixed = ce.solve_gameplay_batch(*columns, seed=1)
+    adaptive = ce.solve_gameplay_batch(*columns, seed=1, pimc_error_rate=0.05)
+    assert all(adaptive[2]) and sum(adaptive[3]) <= sum(fixed[3])
+    with pytest.raises(ValueError, match="error_rate"):
+        ce.solve_gameplay_batch(*columns, pimc_error_rate=0.5)
+
+
+def test_exact_worlds(endgames):
+    columns = (
+        endgames.flat_hands,
+        endgames.boards,
+        endgames.history,
+        endgames.trumps,
+        endgames.tricks_won,
+        endgames.players,
+        4,
+    )
+    # Endgames hide at most 9 cards, 1680 worlds: the labels no longer depend on the seed.
+    a = ce.solve_gameplay_batch(*columns, seed=1, score_label="ev", max_exact_worlds=1680)
+    b = ce.solve_gameplay_batch(*columns, seed=2, score_label="ev", max_exact_worlds=1680)
+    assert a[:2] == b[:2] and all(a[2])
+
+
+def test_solve_marks_impossible_positions(endgames):
+    # Without the board cards in the history, the positions cannot arise.
+    history = [0] * len(endgames)
+    *_, valid, nodes, times, agreement, entropy, variance, optimal, reasons, _ = ce.solve_gameplay_batch(
+        endgames.flat_hands,
+        endgames.boards,
+        history,
+        endgames.trumps,
+        endgames.tricks_won,
+        endgames.players,
+        0,
+    )
+    assert not any(valid) and reasons == [4, 4, 4]
+    counts = dict(ce.invalid_reason_counts(reasons))
+    assert counts["valid"] == 0 and counts["history_mismatch"] == 3
+    assert sum(counts.values()) == 3
+    with pytest.raises(ValueError):
+        ce.invalid_reason_counts([99])
+
+    reasons = solve(endgames, time_limit_ms=0)[9]
+    assert dict(ce.invalid_reason_counts(reasons))["timeout"] == 3
+
+
+def test_solve_from_plays(endgames):
+    # Replaying the plays adds the points banked before the ending to the scores.
+    scores = solve(endgames)[1]
+    _, replayed, valid, *_ = solve(endgames, plays=endgames.plays)
+    assert all(valid)
+    for sample, score, with_plays in zip(endgames, scores, replayed):
+        assert with_plays == score + sample.state().points[0]
+    with pytest.raises(ValueError):
+        solve(endgames, plays=endgames.plays[1:])
+    with pytest.raises(ValueError):
+        solve(endgames, plays=endgames.plays[::-1])
+
+
+def test_labels_and_schema():
+    assert ce.transform_score_labels([100.0, 70.0, 95.0], [80, 80, 80], "outcome") == [
+        2.0,
+        0.0,
+        2.0,
+    ]
+    assert ce.transform_score_labels([100.0], [80], "margin") == [20.0]
+    with pytest.raises(ValueError):
+        ce.transform_score_labels([1.0], [80], "bogus")
+
+    columns = ce.gameplay_schema_columns()
+    assert columns == ce.gameplay_schema_columns(ce.SCHEMA_VERSION)
+    assert ce.gameplay_schema_columns(1) == [
+        "hand",
+        "board",
+        "history",
+        "trump",
+        "best_card",
+        "best_score",
+    ]
+    assert set(ce.gameplay_schema_columns(1)) < set(columns)
+
+
+def test_verify_dataset(endgames, tmp_path):
+    pa = pytest.importorskip("pyarrow")
+    pq = pytest.importorskip("pyarrow.parquet")
+    best_cards, best_scores = solve(endgames)[:2]
+    table = pa.table(
+        {
+            "hand": pa.array([s.hand for s in endgames], pa.uint32()),
+            "board": pa.array(endgames.boards, pa.list_(pa.uint8())),
+            "history": pa.array(endgames.history, pa.uint32()),
+            "trump": pa.array(endgames.trumps, pa.uint8()),
+            "best_card": pa.array(best_cards, pa.uint8()),
+            "best_score": pa.array(best_scores, pa.float32()),
+            "deal": pa.array([list(h) for h in endgames.hands], pa.list_(pa.uint32())),
+            "tricks_won": pa.array(
+                [list(t) for t in endgames.tricks_won], pa.list_(pa.uint8())
+            ),
+            "player": pa.array(endgames.players, pa.uint8()),
+        }
+    )
+    path = tmp_path / "part-0.parquet"
+    pq.write_table(table, path)
+
+    report = ce.verify_dataset(str(path), 1.0, seed=1)
+    assert report.is_ok() and report.kind == "gameplay"
+    assert report.rows_total == report.rows_checked == report.rows_resolved == 3
+    assert report.score_mismatches == report.card_mismatches == report.invalid_rows == 0
+    assert report.errors == []
+    with pytest.raises(ValueError):
+        ce.verify_dataset(str(tmp_path / "missing.parquet"), 1.0)
+
+
+def test_evaluate_policy_on_dataset(endgames, tmp_path):
+    pa = pytest.importorskip("pyarrow")
+    pq = pytest.importorskip("pyarrow.parquet")
+    best_cards, best_scores = solve(endgames)[:2]
+    table = pa.table(
+        {
+            "hand": pa.array([s.hand for s in endgames], pa.uint32()),
+            "board": pa.array(endgames.boards, pa.list_(pa.uint8())),
+            "trump": pa.array(endgames.trumps, pa.uint8()),
+            "best_card": pa.array(best_cards, pa.uint8()),
+            "best_score": pa.array(best_scores, pa.float32()),
+            "deal": pa.array([list(h) for h in endgames.hands], pa.list_(pa.uint32())),
+            "tricks_won": pa.array(
+                [list(t) for t in endgames.tricks_won], pa.list_(pa.uint8())
+            ),
+            "player": pa.array(endgames.players, pa.uint8()),
+        }
+    )
+    path = tmp_path / "part-0.parquet"
+    pq.write_table(table, path)
+
+    solver = ce.evaluate_policy_on_dataset("double_dummy", str(path))
+    assert solver.policy == "double_dummy" and solver.rows_total == 3
+    assert solver.match_rate() == 1.0 and solver.total_loss == 0.0
+    heuristic = ce.evaluate_policy_on_dataset("heuristic", str(path), max_rows=2)
+    assert heuristic.rows_total == 2 and heuristic.mean_loss() >= 0.0
+    with pytest.raises(ValueError):
+        ce.evaluate_policy_on_dataset("oracle", str(path))
+    with pytest.raises(ValueError):
+        ce.evaluate_policy_on_dataset("heuristic", str(tmp_path / "missing.parquet"))
+
+
+def test_recompute_deal_ids(tmp_path):
+    pa = pytest.importorskip("pyarrow")
+    pq = pytest.importorskip("pyarrow.parquet")
+    batch = ce.generate_raw_gameplay_batch(4, seed=5)
+    table = pa.table(
+        {
+            "hands": pa.array([list(h) for h in batch.hands], pa.list_(pa.uint32())),
+            "plays": pa.array(batch.plays, pa.list_(pa.uint16())),
+        }
+    )
+    pq.write_table(table, tmp_path / "positions.parquet")
+    assert ce.recompute_deal_ids(str(tmp_path)) == batch.deal_ids
+    with pytest.raises(ValueError, match="Missing column deal"):
+        ce.recompute_deal_ids(str(tmp_path), hands_column="deal")
+
+
+def test_opening_leads():
+    (sample,) = ce.generate_opening_lead_batch(1, ce.Bid(80, ce.HEARTS), seed=1)
+    assert (sample.declarer, sample.leader) == (0, 1)
+    assert sample.contract.value == 80 and len(sample.hands) == 4
+    assert len(sample.leads) == len(sample.lead_scores) == 8
+    assert sample.best_lead() in sample.leads
+    assert ce.deal_from_id(sample.deal_id) == list(sample.hands)
+    assert sample.auction
+
+
+def test_evaluation_batch():
+    samples = ce.generate_evaluation_batch(2, seed=1, min_cards=4, max_cards=8)
+    assert len(samples) == 2
+    for sample in samples:
+        assert 4 <= sample.cards_left <= 8 and sample.team in (0, 1)
+        assert len(sample.features) == ce.EVALUATION_FEATURES
+        assert ce.PlayingState.load_state(sample.state).current_player % 2 == sample.team
+        assert isinstance(sample.exact, int) and isinstance(sample.heuristic, int)
+
+
+def test_deal_labels(rank_deal):
+    assert 0 <= ce.hand_percentile(0x0F0F_0000, ce.HEARTS) <= 100
+    with pytest.raises(ValueError):
+        ce.hand_percentile(0xFF, 9)
+
+    difficulty = ce.deal_difficulty(rank_deal, ce.SPADES)
+    assert 0 <= difficulty <= 1
+    assert ce.deal_difficulty_batch(rank_deal, [ce.SPADES]) == [difficulty]
+    tags = ce.deal_tags(rank_deal, ce.SPADES)
+    assert ce.deal_tags_batch(rank_deal, [ce.SPADES], min_swing=10) == [tags]
+    with pytest.raises(ValueError):
+        ce.deal_difficulty(rank_deal[:3], ce.SPADES)
+    with pytest.raises(ValueError):
+        ce.deal_tags(rank_deal[:3], ce.SPADES)
+
+
+def test_downsample_easy_positions(endgames):
+    columns = (
+        endgames.flat_hands,
+        endgames.boards,
+        endgames.trumps,
+        endgames.tricks_won,
+        endgames.players,
+    )
+    keep, weights, difficulty = ce.downsample_easy_positions(*columns, 2.0, 0.25, seed=1)
+    assert len(keep) == len(weights) == len(difficulty) == 3
+    assert all(w == (4.0 if k else 0.0) for k, w in zip(keep, weights))
+    assert all(0 <= d <= 1 for d in difficulty)
+    assert ce.downsample_easy_positions(*columns, 2.0, 0.25, seed=1) == (keep, weights, difficulty)
+    # Keeping every easy position changes nothing.
+    assert ce.downsample_easy_positions(*columns, 2.0, 1.0)[:2] == ([True] * 3, [1.0] * 3)
+    with pytest.raises(ValueError):
+        ce.downsample_easy_positions(*columns, 0.5, 0.0)
+
+
+def test_puzzles():
+    constraints = ce.PuzzleConstraints(min_swing=10, max_cards=8)
+    assert (constraints.min_swing, constraints.min_cards, constraints.max_cards) == (10, 4, 8)
+    assert constraints.motif is None and constraints.max_attempts == 1000
+    (puzzle,) = ce.generate_puzzles(1, constraints, seed=1)
+    assert puzzle.swing >= 10 and 4 <= puzzle.cards_left <= 8
+    assert puzzle.solution in [card for card, _ in puzzle.moves]
+    assert max(value for _, value in puzzle.moves) == puzzle.value
+    assert ce.PlayingState.load_state(puzzle.state).current_player == puzzle.player
+    assert puzzle.explanation and isinstance(puzzle.motifs, list)
+    assert json.loads(puzzle.to_json())["cards_left"] == puzzle.cards_left
+
+
+def test_human_play():
+    (game,) = ce.generate_human_play_batch(1, seed=1)
+    assert len(game.plays) == 32 and sum(game.points) >= 162
+    assert len(game.hands) == 4 and game.trump < 4
+    assert game.deal_id == ce.deal_id(game.hands)
+    assert len(game.overridden) == len(game.point_loss) == 32
+    with pytest.raises(ValueError):
+        ce.generate_human_play_batch(1, temperature=-1.0)
+
+
+def test_selfplay():
+    np = pytest.importorskip("numpy")
+    calls = []
+
+    def policy(observations, legal):
+        assert observations.dtype == np.float32 and legal.dtype == np.uint32
+        assert observations.shape == (len(legal), ce.GAMEPLAY_FEATURES)
+        calls.append(len(legal))
+        return np.zeros((len(legal), 32), dtype=np.float32)
+
+    games = ce.generate_selfplay_batch(2, policy, seed=1)
+    assert len(games) == 2 and len(calls) == 32
+    for game in games:
+        assert len(game.plays) == 32 and len(game.hands) == 4
+        assert sum(game.points) >= 162 and game.trump < 4
+    assert games[0].deal_id != games[1].deal_id
+
+    def broken(observations, legal):
+        raise KeyError("policy failure")
+
+    with pytest.raises(KeyError):
+        ce.generate_selfplay_batch(1, broken, seed=1)
+
+
+def test_vec_env():
+    np = pytest.importorskip("numpy")
+    env = ce.VecCoincheEnv(3, seed=1)
+    assert env.num_envs == len(env) == 3 and env.reward == "trick"
+    observations, legal = env.reset()
+    assert observations.shape == (3, ce.GAMEPLAY_FEATURES)
+    assert env.current_players() == [0, 0, 0]
+
+    for _ in range(32):
+        actions = [lowest_card(int(mask)) for mask in legal]
+        observations, legal, rewards, dones = env.step(actions)
+    assert rewards.dtype == np.float32 and dones.dtype == np.bool_
+    assert dones.all()
+    with pytest.raises(ValueError):
+        ce.VecCoincheEnv(1, reward="bogus")
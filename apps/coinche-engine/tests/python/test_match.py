@@ -0,0 +1,302 @@
+"""Match management: auction, card play, results, persistence and bots."""
+
+import json
+
+import pytest
+
+import coinche_engine as ce
+from conftest import SORTED_DEAL, play_out
+
+EXPORTS = {
+    "CoincheMatch",
+    "MatchResult",
+    "PlayStats",
+    "CompletedTrick",
+    "LoggedAction",
+    "Exploration",
+    "Phase",
+    "Bid",
+    "BiddingState",
+    "TimeControl",
+    "RuleSet",
+    "CoincheGame",
+    "DealScore",
+    "MatchStats",
+    "ThresholdBidder",
+    "BotPreset",
+    "imps",
+    "net_score",
+    "duplicate_swing",
+    "par_result",
+    "par_swing",
+    "ParResult",
+    "load_deal_corpus",
+}
+
+
+def test_auction(sorted_deal):
+    match = ce.CoincheMatch(3, sorted_deal)
+    assert match.phase == ce.Phase.BIDDING and match.phase_name() == "BIDDING"
+    state = match.get_bidding_state()
+    assert state.current_player == 0 and state.history == []
+    assert len(match.legal_actions()) == len(state.legal_actions())
+    assert match.action_head() == 0
+
+    match.bid(ce.Bid(80, ce.SPADES))
+    match.coinche()
+    match.surcoinche()
+    assert match.phase == ce.Phase.PLAYING
+    assert match.coinche_level == 2 and match.contract_owner == 0
+    assert (match.contract.value, match.contract.trump) == (80, ce.SPADES)
+    assert match.auction[1:] == ["coinche", "surcoinche"]
+
+    cafe = ce.CoincheMatch(3, sorted_deal, rules=ce.RuleSet.preset("classic-cafe"))
+    cafe.bid(ce.Bid(80, ce.SPADES))
+    cafe.coinche()
+    with pytest.raises(ValueError):
+        cafe.surcoinche()
+
+
+def test_bidding_state_fields(sorted_deal):
+    match = ce.CoincheMatch(3, sorted_deal)
+    match.apply_action(ce.Bid(90, ce.HEARTS))
+    match.apply_action(None)
+    state = match.bidding_state()
+    assert state.contract.value == 90 and state.contract_owner == 0
+    assert (state.current_player, state.consecutive_passes) == (2, 1)
+    assert state.coinche_level == 0 and len(state.history) == 2
+    with pytest.raises(RuntimeError):
+        match.play_card(0)
+
+
+def test_card_play(playing_match):
+    assert playing_match.action_head() == 1
+    mask = playing_match.legal_action_mask()
+    assert len(mask) == ce.FLAT_ACTIONS and sum(mask) == 8
+    state = playing_match.get_playing_state()
+    assert state.current_player == 0 and state.trump == ce.SPADES
+    assert playing_match.playing_state().hands == SORTED_DEAL
+
+    playing_match.play_card(0)
+    assert playing_match.played_cards == [0]
+    assert playing_match.played_cards_mask() == 1
+    assert playing_match.hands[0] == 0xFE
+    with pytest.raises(ValueError):
+        playing_match.play_card(16)
+
+
+def test_transition_from_bidding(sorted_deal):
+    match = ce.CoincheMatch(3, sorted_deal)
+    match.bid(ce.Bid(80, ce.SPADES))
+    match.transition_from_bidding()
+    assert match.phase == ce.Phase.PLAYING
+
+
+def test_result_and_statistics(finished_match):
+    assert finished_match.phase == ce.Phase.FINISHED
+    result = finished_match.get_result()
+    assert finished_match.result().points_ns == result.points_ns
+    # West holds every trump: South goes down.
+    assert not result.contract_made
+    assert (result.points_ns, result.points_ew) == (0, 272)
+    assert result.contract_owner == 0 and result.coinche_level == 0
+    assert result.contract.value == 80
+    assert result.belote_team == 1
+    assert (result.timeout_seat, result.revoke_seat, result.revoke_card) == (None,) * 3
+
+    stats = result.play_stats
+    assert stats.tricks_won == [0, 8, 0, 0] and stats.points_won[1] == 162
+    assert stats.trumps_played[1] == 8 and stats.belote_seat == 1
+    assert len(stats.forced_overcuts) == len(stats.points_contributed) == 4
+
+    tricks = finished_match.completed_tricks()
+    assert len(tricks) == 8
+    assert (tricks[0].leader, tricks[0].winner) == (0, 1)
+    assert tricks[0].cards == [0, 8, 16, 24]
+    assert tricks[0].cards_in_order() == [0, 8, 16, 24]
+
+
+def test_result_before_the_end(playing_match):
+    assert playing_match.get_result() is None
+    with pytest.raises(RuntimeError):
+        playing_match.result()
+
+
+def test_options(sorted_deal):
+    control = ce.TimeControl(60_000, 500, 10_000)
+    assert (control.initial_ms, control.increment_ms, control.max_move_ms) == (
+        60_000,
+        500,
+        10_000,
+    )
+    match = ce.CoincheMatch(
+        3,
+        sorted_deal,
+        time_control=control,
+        revoke_penalty=True,
+        rules=ce.RuleSet.flat_capot(),
+    )
+    assert match.remaining_ms == [60_000] * 4 and not match.check_time()
+    assert match.revoke_penalty and match.rules.capot_bonus == 98
+    assert ce.CoincheMatch(3, sorted_deal).remaining_ms is None
+
+
+def test_rule_sets():
+    rules = ce.RuleSet()
+    assert (rules.capot_bonus, rules.der_in_capot) == (90, True)
+    names = [name for name, _ in ce.RuleSet.presets()]
+    assert names == ["classic", "ffb-tournament", "classic-cafe", "no-announcements"]
+    assert ce.RuleSet.preset("classic").capot_bonus == 90
+    cafe = ce.RuleSet.preset("classic-cafe")
+    assert cafe.round_scores and not cafe.surcoinche and cafe.belote
+    assert not ce.RuleSet(belote=False).belote
+    with pytest.raises(ValueError):
+        ce.RuleSet.preset("unknown")
+    # A bonus that would overflow a team's points.
+    assert ce.RuleSet(60000).capot_bonus == 60000
+    with pytest.raises(ValueError, match="maximum"):
+        ce.RuleSet(65500)
+
+
+def test_position_and_transcript(playing_match):
+    playing_match.play_card(0)
+    playing_match.play_card(8)
+    match = ce.CoincheMatch(3, SORTED_DEAL)
+    match.set_position(playing_match.hands, playing_match.auction, [0, 8])
+    assert match.played_cards == [0, 8] and match.phase == ce.Phase.PLAYING
+    assert match.dump_state() == playing_match.dump_state()
+
+    parsed = ce.CoincheMatch.from_transcript(playing_match.to_transcript())
+    assert parsed.played_cards == [0, 8]
+    assert parsed.initial_hands == SORTED_DEAL
+    with pytest.raises(ValueError):
+        ce.CoincheMatch.from_transcript("not a transcript")
+
+
+def test_action_log(playing_match):
+    playing_match.play_card(0)
+    log = playing_match.action_log()
+    assert [a.kind for a in log] == ["call"] * 4 + ["card"]
+    assert log[0].seat == 0 and log[0].call.value == 80 and log[0].card is None
+    assert log[-1].card == 0 and log[-1].timestamp_ms is not None
+    assert json.loads(log[-1].to_json())["action"] == "7D"
+    assert len(json.loads(playing_match.action_log_json())) == 5
+
+
+def test_snapshot_restore_and_branch(playing_match):
+    playing_match.play_card(0)
+    blob = playing_match.snapshot()
+    assert isinstance(blob, bytes)
+    restored = ce.CoincheMatch.restore(blob)
+    assert restored.snapshot() == blob
+
+    branch = playing_match.branch()
+    branch.play_card(8)
+    assert playing_match.played_cards == [0] and branch.played_cards == [0, 8]
+    with pytest.raises(ValueError):
+        ce.CoincheMatch.restore(b"not a snapshot")
+
+
+def test_explore(playing_match):
+    exploration = playing_match.explore([0, 8], time_budget_ms=0)
+    assert playing_match.played_cards == []
+    assert exploration.position.played_cards == [0, 8]
+    assert exploration.result is None
+    assert exploration.declarer_points == exploration.analysis.score
+
+    over = play_out(exploration.position).explore([])
+    assert over.result.points_ew == 272 and over.declarer_points == 0
+    assert over.analysis is None
+    with pytest.raises(ValueError):
+        playing_match.explore([16])
+
+
+def test_bots(sorted_deal):
+    match = ce.CoincheMatch(3, sorted_deal)
+    bidder = ce.ThresholdBidder()
+    assert (bidder.open_strength, bidder.raise_support, bidder.coinche_margin) == (20, 15, 20)
+    assert len(bidder.auction(3, sorted_deal)) >= 4
+    call = bidder.choose(match.bidding_state(), sorted_deal[0])
+    match.apply_action(call)
+
+    preset = ce.BotPreset("beginner")
+    assert preset.strength == "heuristic" and 0 < preset.blunder_rate < 1
+    assert preset.iterations >= 1 and not preset.inference
+    match.apply_action(preset.action(match, 1, seed=1))
+    with pytest.raises(ValueError):
+        ce.BotPreset("grandmaster")
+
+    for seat in (2, 3):
+        match.apply_action(match.bot_action(seat, strength="heuristic", seed=1))
+    if match.phase == ce.Phase.PLAYING:
+        seat = match.get_playing_state().current_player
+        card = match.bot_action(seat, strength="heuristic", seed=1)
+        assert match.get_playing_state().get_legal_moves() >> card & 1
+
+
+def test_game_scoring(finished_match):
+    game = ce.CoincheGame(target=500, seed=1)
+    assert game.target == 500 and game.litige and not game.defenders_keep_points
+    assert game.scores == [0, 0] and game.pot == 0 and not game.is_over()
+    assert game.rules.capot_bonus == 90 and game.first_dealer == game.dealer
+    deal = game.new_deal()
+    assert deal.dealer == game.dealer
+    for _ in range(4):
+        deal.bid(None)
+
+    score = game.record(deal.get_result())
+    assert score.points == [0, 0] and not score.litige and score.pot_won == 0
+    assert len(game.history) == 1 and game.winner is None
+
+    score = game.record(finished_match.get_result())
+    assert score.points[1] > 0 and game.scores == score.points
+
+
+def test_match_stats(finished_match):
+    stats = ce.MatchStats()
+    stats.add(finished_match.get_result())
+    merged = ce.MatchStats()
+    merged.merge(stats)
+    assert (merged.deals, merged.contracts, merged.contracts_made) == (1, 1, 0)
+    assert (merged.passed, merged.forfeits) == (0, 0)
+    assert merged.success_rate() == 0.0
+    assert merged.success_rate_by_trump() == {ce.SPADES: 0.0}
+    assert merged.success_rate_by_value() == {80: 0.0}
+    assert merged.avg_declarer_points() == 0.0
+    assert merged.avg_defender_points() == 272.0
+    assert merged.belote_rate() == 1.0 and merged.coinche_rate() == 0.0
+    assert merged.coinche_success_rate() is None
+
+
+def test_duplicate_scoring(finished_match, rank_deal):
+    result = finished_match.get_result()
+    assert ce.imps(0) == 0 and ce.imps(100) > 0
+    assert ce.net_score(result) == -352
+    assert ce.duplicate_swing(result, result) == 0
+
+    par = ce.par_result(rank_deal, 3)
+    # East-West hold the aces, tens, kings and nines: they make a capot.
+    assert par.contract.value == 252 and par.declarers == 1
+    assert par.points == [0, 252] and par.net_score < 0
+    passed = ce.CoincheMatch(3, rank_deal)
+    for _ in range(4):
+        passed.bid(None)
+    assert ce.par_swing(passed.get_result(), par) == ce.imps(-par.net_score)
+    with pytest.raises(ValueError):
+        ce.par_result(rank_deal, 4)
+
+
+def test_load_deal_corpus(finished_match, tmp_path):
+    path = tmp_path / "corpus.txt"
+    path.write_text(finished_match.to_transcript() + "\n" + finished_match.to_transcript())
+    deals = ce.load_deal_corpus(str(path))
+    assert deals == [(finished_match.dealer, finished_match.initial_hands)] * 2
+    try:
+        embedded = ce.load_deal_corpus()
+    except ValueError as e:
+        # Built without the deal-corpus feature
+        assert "deal-corpus" in str(e)
+    else:
+        assert len(embedded) == 200
+    with pytest.raises(ValueError):
+        ce.load_deal_corpus(str(tmp_path / "missing.txt"))
@@ -0,0 +1,150 @@
+"""Card-play state, the double-dummy and PIMC solvers and their settings."""
+
+import pytest
+
+import coinche_engine as ce
+
+EXPORTS = {
+    "PlayingState",
+    "solve_game",
+    "solve_pimc",
+    "PimcDecision",
+    "PimcConfidence",
+    "BidConstraint",
+    "forces_capot",
+    "analyze_hand",
+    "CardAnalysis",
+    "analyze_position",
+    "PositionAnalysis",
+    "solve_all_leaders",
+    "solve_all_leaders_batch",
+    "set_partition_cache",
+    "set_pimc_tt_sharing",
+    "set_solve_options",
+    "open_deal_cache",
+    "close_deal_cache",
+    "deal_cache_stats",
+}
+
+
+def test_playing_state(endgame):
+    assert endgame.trump == ce.SPADES and endgame.current_player == 0
+    assert endgame.get_hand(1) == 0b11 << 8
+    assert endgame.get_legal_moves() == 0b11
+    assert not endgame.is_terminal() and endgame.trick_size == 0
+    assert endgame.is_card_better(8, 0, 0)
+    assert endgame.rules.capot_bonus == 90
+    endgame.set_rules(ce.RuleSet.flat_capot())
+    assert endgame.rules.capot_bonus == 98
+
+    for card in (0, 8, 16):
+        endgame.play_card(card)
+    assert endgame.current_trick == [0, 8, 16, 255]
+    assert endgame.get_current_trick_winner_player() == 1
+    endgame.play_card(24)
+    assert endgame.last_trick == [0, 8, 16, 24] and endgame.last_trick_winner == 1
+    assert endgame.tricks_won == [0, 1] and endgame.current_player == 1
+    assert endgame.played_cards_mask() >> 24 & 1
+    assert endgame.seen_cards_mask(0) & endgame.played_cards_mask() == endgame.played_cards_mask()
+
+
+def test_state_text_round_trips(endgame):
+    endgame.play_card(0)
+    text = endgame.dump_state()
+    assert ce.PlayingState.load_state(text).dump_state() == text
+    with pytest.raises(ValueError):
+        ce.PlayingState.load_state("trump=Z")
+
+
+def test_solve_game(endgame):
+    # West trumps both tricks and takes the der.
+    assert ce.solve_game(endgame) == (0, 0)
+    assert ce.solve_game(endgame, perspective="declarer", declarer=1)[0] == 10
+    assert ce.solve_game(endgame, objective="tricks")[0] == 0
+    assert ce.solve_game(endgame, search="mcts", iterations=50, seed=1)[0] == 0
+    with pytest.raises(ValueError, match="perspective"):
+        ce.solve_game(endgame, perspective="bogus")
+    with pytest.raises(ValueError):
+        ce.solve_game(endgame, perspective="declarer")
+    assert ce.forces_capot(endgame, perspective="declarer", declarer=1)
+    assert not ce.forces_capot(endgame)
+
+
+def test_solve_pimc(endgame):
+    decision = ce.solve_pimc(endgame, 4, seed=1)
+    assert decision.best_card in (0, 1)
+    # tt_log2 and seed keep their positions.
+    assert ce.solve_pimc(endgame, 4, "ns", None, "plurality", None, 1).votes == decision.votes
+    assert sum(decision.votes) == 4 and len(decision.card_values) == 32
+    assert decision.expected_score == pytest.approx(0.0, abs=20)
+    confidence = decision.confidence
+    assert 0 <= confidence.agreement <= 1
+    assert confidence.vote_entropy >= 0 and confidence.value_variance >= 0
+
+    constraint = ce.BidConstraint(1, ce.Bid(80, ce.SPADES))
+    assert (constraint.seat, constraint.played, constraint.bid.value) == (1, 0, 80)
+    assert not constraint.is_consistent(0xFF)
+    decision = ce.solve_pimc(endgame, 4, voting="ev", constraints=[constraint], seed=1)
+    assert decision.best_card in (0, 1)
+
+    # West discarded on a heart lead: the hidden hearts are East's or North's.
+    plays = ce.encode_play_history([(0, 0, 23), (0, 1, 4), (0, 2, 22), (0, 3, 21)])
+    decision = ce.solve_pimc(endgame, 4, seed=1, plays=plays)
+    assert decision.best_card in (0, 1)
+    # Nobody followed a spade lead, yet two spades are hidden.
+    plays = ce.encode_play_history([(0, 0, 15), (0, 1, 4), (0, 2, 5), (0, 3, 6)])
+    with pytest.raises(ValueError, match="voids"):
+        ce.solve_pimc(endgame, 4, seed=1, plays=plays)
+
+
+def test_analysis(endgame):
+    cards = ce.analyze_hand(endgame)
+    assert sorted(c.card for c in cards) == [0, 1]
+    assert all(c.dd_points == 0 for c in cards)
+    # Nobody else holds a diamond.
+    assert all(c.is_master and c.beaten_by == 0 for c in cards)
+
+    analysis = ce.analyze_position(endgame)
+    assert analysis.complete and analysis.best_move in (0, 1)
+    assert analysis.score == 0 and len(analysis.pv) == 8
+    assert len(analysis.moves) == 2 and analysis.depth >= 8 and analysis.nodes > 0
+
+
+def test_all_leaders(rank_deal):
+    values = list(ce.solve_all_leaders(rank_deal, ce.SPADES))
+    assert len(values) == 4 and all(0 <= v <= 162 for v in values)
+    batch = ce.solve_all_leaders_batch(rank_deal + rank_deal, [ce.SPADES, ce.HEARTS])
+    assert list(batch[0]) == values
+    with pytest.raises(ValueError):
+        ce.solve_all_leaders(rank_deal, 6)
+    with pytest.raises(ValueError):
+        ce.solve_all_leaders_batch(rank_deal, [ce.SPADES, ce.HEARTS])
+
+
+def test_solver_settings(endgame):
+    ce.set_partition_cache(True)
+    ce.set_pimc_tt_sharing(True)
+    ce.set_solve_options(discard_pruning=True)
+    try:
+        assert ce.solve_game(endgame)[0] == 0
+        # Seeded solves do not share the TT, and say so.
+        with pytest.warns(UserWarning, match="seeded"):
+            ce.solve_pimc(endgame, 2, seed=1)
+    finally:
+        ce.set_partition_cache(False)
+        ce.set_pimc_tt_sharing(False)
+        ce.set_solve_options()
+
+
+def test_deal_cache(tmp_path, rank_deal):
+    assert ce.deal_cache_stats() is None
+    assert ce.open_deal_cache(str(tmp_path / "deals.bin")) == 0
+    try:
+        ce.solve_all_leaders(rank_deal, ce.SPADES)
+        entries, hits, misses, write_errors = ce.deal_cache_stats()
+        assert entries >= 1 and misses == 4 and write_errors == 0
+        ce.solve_all_leaders(rank_deal, ce.SPADES)
+        assert ce.deal_cache_stats()[1] == hits + 4
+    finally:
+        ce.close_deal_cache()
+    assert ce.deal_cache_stats() is None
@@ -3,6 +3,7 @@ import torch
 from torch.utils.data import Dataset
 import pandas as pd
 import numpy as np
+import coinche_engine
 
 class BiddingDataset(Dataset):
     def __init__(self, parquet_file):
@@ -16,7 +17,7 @@ class BiddingDataset(Dataset):
         
         # Features: Hand (32-bit int) -> One-hot (32 floats)
         hand_int = row['hand_south']
-        hand_vec = self._bits_to_vec(hand_int)
+        hand_vec = np.array(coinche_engine.encode_hand(int(hand_int)), dtype=np.float32)
         
         # Targets: Scores (List of 4 ints) -> Float tensor normalized
         # Max score is 162 (182 with belote?), let's div by 162 for now.
@@ -27,10 +28,3 @@ class BiddingDataset(Dataset):
             'features': torch.from_numpy(hand_vec),
             'targets': torch.from_numpy(scores_normalized)
         }
-
-    def _bits_to_vec(self, bits):
-        vec = np.zeros(32, dtype=np.float32)
-        for i in range(32):
-            if (bits & (1 << i)) != 0:
-                vec[i] = 1.0
-        return vec
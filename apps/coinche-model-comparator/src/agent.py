@@ -1,4 +1,5 @@
 
+import coinche_engine
 import torch
 import numpy as np
 import sys
@@ -27,9 +28,10 @@ class BaseAgent(ABC):
         pass
 
     @abstractmethod
-    def get_card(self, hand_int, history_int, board_cards, is_trump, legal_mask):
+    def get_card(self, hand_int, history_int, board_cards, is_trump, legal_mask, match=None, seat=None):
         """
-        Returns best card (0-31)
+        Returns best card (0-31).
+        `match` (CoincheMatch) and `seat` let search-based agents call `match.bot_action`.
         """
         pass
 
@@ -55,10 +57,7 @@ class AI_Agent(BaseAgent):
 
     def get_bid(self, hand_int, current_contract=None, partner_contract=None):
         # Feature Engineering: 32-bit hand to One Hot
-        hand_vec = np.zeros(32, dtype=np.float32)
-        for i in range(32):
-            if (hand_int & (1 << i)) != 0:
-                hand_vec[i] = 1.0
+        hand_vec = np.array(coinche_engine.encode_hand(hand_int), dtype=np.float32)
         
         input_tensor = torch.from_numpy(hand_vec).unsqueeze(0).to(self.device)
         
@@ -71,28 +70,11 @@ class AI_Agent(BaseAgent):
         
         return best_suit_idx, best_score
 
-    def get_card(self, hand_int, history_int, board_cards, trump_val, legal_mask):
+    def get_card(self, hand_int, history_int, board_cards, trump_val, legal_mask, match=None, seat=None):
         # Feature Engineering
-        hand_vec = np.zeros(32, dtype=np.float32)
-        for i in range(32):
-            if (hand_int & (1 << i)) != 0:
-                hand_vec[i] = 1.0
-        
-        history_vec = np.zeros(32, dtype=np.float32)
-        for i in range(32):
-            if (history_int & (1 << i)) != 0:
-                history_vec[i] = 1.0
-                
-        board_vec = np.zeros(32, dtype=np.float32)
-        for card in board_cards:
-            if card < 32:
-                board_vec[card] = 1.0
-                
-        trump_vec = np.zeros(6, dtype=np.float32)
-        if trump_val < 6:
-            trump_vec[trump_val] = 1.0
-            
-        features = np.concatenate([hand_vec, history_vec, board_vec, trump_vec])
+        features = np.array(coinche_engine.encode_gameplay_features(
+            hand_int, history_int, list(board_cards), trump_val
+        ), dtype=np.float32)
         input_tensor = torch.from_numpy(features).unsqueeze(0).to(self.device)
         
         with torch.no_grad():
@@ -116,7 +98,7 @@ class RandomAgent(BaseAgent):
         # Let's say it evaluates random potential.
         return random.randint(0, 3), random.uniform(70, 100)
 
-    def get_card(self, hand_int, history_int, board_cards, trump_val, legal_mask):
+    def get_card(self, hand_int, history_int, board_cards, trump_val, legal_mask, match=None, seat=None):
         legal_moves = []
         for i in range(32):
             if (legal_mask & (1 << i)) != 0:
@@ -169,7 +151,7 @@ class HeuristicAgent(BaseAgent):
         # Expected score ~= points + partner help (20?)
         return best_suit, max_points + 20
 
-    def get_card(self, hand_int, history_int, board_cards, trump_val, legal_mask):
+    def get_card(self, hand_int, history_int, board_cards, trump_val, legal_mask, match=None, seat=None):
         # Deterministic Rules
         # 1. If partner controls trick and I don't need to cut -> Play small score (dump trash) or points (if safe)?
         # 2. If valid to cut, do I?
@@ -208,7 +190,29 @@ class HeuristicAgent(BaseAgent):
                 
         return best_card
 
-def load_agent(bidding_path, playing_path, device, name):
+class PimcAgent(HeuristicAgent):
+    """
+    Plays by PIMC search in the engine (bids like HeuristicAgent).
+    voting: 'plurality' (most voted card) or 'ev' (best mean value over the worlds).
+    """
+    def __init__(self, voting="plurality", iterations=20, name="PIMC"):
+        super().__init__(name)
+        self.voting = voting
+        self.iterations = iterations
+
+    def get_card(self, hand_int, history_int, board_cards, trump_val, legal_mask, match=None, seat=None):
+        if match is None:
+            raise ValueError("PimcAgent needs the CoincheMatch and its seat")
+        # The engine samples the hidden hands from the seat's own observation
+        strength = "pimc_ev" if self.voting == "ev" else "pimc"
+        return match.bot_action(seat, strength, self.iterations)
+
+PIMC_AGENTS = {'pimc-plurality': 'plurality', 'pimc-ev': 'ev'}
+
+def load_agent(bidding_path, playing_path, device, name, pimc_iterations=20):
+    # PIMC players: 'pimc-plurality' or 'pimc-ev' as playing model
+    if playing_path.lower() in PIMC_AGENTS:
+        return PimcAgent(PIMC_AGENTS[playing_path.lower()], pimc_iterations, name)
     # If paths are 'heuristic' or 'random'
     if bidding_path.lower() == 'heuristic':
         return HeuristicAgent(name)
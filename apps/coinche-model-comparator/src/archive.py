@@ -0,0 +1,166 @@
+"""
+SQLite archive of finished games, so tournaments, long self-play runs and servers
+keep a durable, queryable history without a separate database service.
+
+Each row holds the MatchResult fields, the agent at every seat, the full action
+log (auction calls and cards played, in order) and the match transcript, from
+which `replay` rebuilds the match. Opening the same file again appends to it.
+"""
+import json
+import sqlite3
+import time
+
+import coinche_engine
+
+SCHEMA = """
+CREATE TABLE IF NOT EXISTS games (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    recorded_at REAL NOT NULL,
+    tag TEXT,
+    dealer INTEGER NOT NULL,
+    hands TEXT NOT NULL,
+    agent_s TEXT, agent_w TEXT, agent_n TEXT, agent_e TEXT,
+    contract_value INTEGER,
+    contract_trump INTEGER,
+    contract_owner INTEGER,
+    coinche_level INTEGER NOT NULL,
+    points_ns INTEGER NOT NULL,
+    points_ew INTEGER NOT NULL,
+    swing INTEGER NOT NULL,
+    contract_made INTEGER NOT NULL,
+    belote_team INTEGER,
+    timeout_seat INTEGER,
+    revoke_seat INTEGER,
+    revoke_card INTEGER,
+    auction TEXT NOT NULL,
+    played_cards TEXT NOT NULL,
+    transcript TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS games_contract ON games (contract_value, contract_trump);
+CREATE INDEX IF NOT EXISTS games_swing ON games (swing);
+CREATE INDEX IF NOT EXISTS games_tag ON games (tag);
+"""
+
+SEAT_COLUMNS = ["agent_s", "agent_w", "agent_n", "agent_e"]
+
+
+def _call(action):
+    """JSON form of an auction call: "pass", "coinche", "surcoinche" or a bid dict."""
+    if action is None:
+        return "pass"
+    if isinstance(action, str):
+        return action
+    return {"value": action.value, "trump": action.trump}
+
+
+class GameArchive:
+    """Finished games stored in the SQLite file at `path` (":memory:" for tests)."""
+
+    def __init__(self, path):
+        self.path = path
+        self.conn = sqlite3.connect(path)
+        self.conn.row_factory = sqlite3.Row
+        self.conn.executescript(SCHEMA)
+
+    def close(self):
+        self.conn.close()
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exc):
+        self.close()
+
+    def record(self, match, agents, tag=None):
+        """
+        Stores a finished `match`. `agents` names the agent at each seat (S, W, N, E);
+        `tag` groups games, e.g. a tournament or a server session. Returns the row id.
+        """
+        res = match.get_result()
+        if res is None:
+            raise ValueError("Only finished matches can be archived")
+        if len(agents) != 4:
+            raise ValueError(f"Expected 4 agent names, got {len(agents)}")
+        row = {
+            "recorded_at": time.time(),
+            "tag": tag,
+            "dealer": match.dealer,
+            "hands": json.dumps(list(match.initial_hands)),
+            **dict(zip(SEAT_COLUMNS, agents)),
+            "contract_value": res.contract.value if res.contract else None,
+            "contract_trump": res.contract.trump if res.contract else None,
+            "contract_owner": res.contract_owner,
+            "coinche_level": res.coinche_level,
+            "points_ns": res.points_ns,
+            "points_ew": res.points_ew,
+            "swing": res.points_ns - res.points_ew,
+            "contract_made": int(res.contract_made),
+            "belote_team": res.belote_team,
+            "timeout_seat": res.timeout_seat,
+            "revoke_seat": res.revoke_seat,
+            "revoke_card": res.revoke_card,
+            "auction": json.dumps([_call(a) for a in match.auction]),
+            "played_cards": json.dumps(list(match.played_cards)),
+            "transcript": match.to_transcript(),
+        }
+        columns = ", ".join(row)
+        marks = ", ".join("?" for _ in row)
+        with self.conn:
+            cur = self.conn.execute(f"INSERT INTO games ({columns}) VALUES ({marks})", list(row.values()))
+        return cur.lastrowid
+
+    def _select(self, where="1", params=(), order="id", limit=None):
+        sql = f"SELECT * FROM games WHERE {where} ORDER BY {order}"
+        if limit is not None:
+            sql += f" LIMIT {int(limit)}"
+        rows = self.conn.execute(sql, params).fetchall()
+        return [self._decode(r) for r in rows]
+
+    @staticmethod
+    def _decode(row):
+        game = dict(row)
+        for key in ("hands", "auction", "played_cards"):
+            game[key] = json.loads(game[key])
+        game["contract_made"] = bool(game["contract_made"])
+        return game
+
+    def count(self):
+        return self.conn.execute("SELECT COUNT(*) FROM games").fetchone()[0]
+
+    def get(self, game_id):
+        games = self._select("id = ?", (game_id,))
+        return games[0] if games else None
+
+    def by_contract(self, value=None, trump=None, made=None, min_coinche=0, tag=None, limit=None):
+        """Games played in a contract matching every given filter, oldest first."""
+        clauses, params = ["contract_value IS NOT NULL", "coinche_level >= ?"], [min_coinche]
+        for column, wanted in (("contract_value", value), ("contract_trump", trump),
+                               ("contract_made", made), ("tag", tag)):
+            if wanted is not None:
+                clauses.append(f"{column} = ?")
+                params.append(int(wanted) if isinstance(wanted, bool) else wanted)
+        return self._select(" AND ".join(clauses), params, limit=limit)
+
+    def by_swing(self, min_swing=0, tag=None, limit=None):
+        """Games whose point difference between the teams is at least `min_swing`, largest first."""
+        where, params = "ABS(swing) >= ?", [min_swing]
+        if tag is not None:
+            where += " AND tag = ?"
+            params.append(tag)
+        return self._select(where, params, order="ABS(swing) DESC, id", limit=limit)
+
+    def by_agent(self, name, tag=None, limit=None):
+        """Games where `name` held at least one seat, oldest first."""
+        where = "(" + " OR ".join(f"{c} = ?" for c in SEAT_COLUMNS) + ")"
+        params = [name] * 4
+        if tag is not None:
+            where += " AND tag = ?"
+            params.append(tag)
+        return self._select(where, params, limit=limit)
+
+    def replay(self, game_id):
+        """The archived game rebuilt as a CoincheMatch, e.g. to step through it again."""
+        game = self.get(game_id)
+        if game is None:
+            raise KeyError(game_id)
+        return coinche_engine.CoincheMatch.from_transcript(game["transcript"])
@@ -10,24 +10,30 @@ import time
 import agent
 from agent import load_agent
 from tournament import TournamentEngine, Team
+from ratings import RatingTracker
+from archive import GameArchive
 
 def main():
     parser = argparse.ArgumentParser(description="Coinche Model Comparator (Duplicate Tournament)")
     
     # Team A Models
     parser.add_argument("--team_a_bidding", type=str, required=True, help="Path to Team A Bidding Model (or 'heuristic', 'random')")
-    parser.add_argument("--team_a_playing", type=str, required=True, help="Path to Team A Playing Model (or 'heuristic', 'random')")
+    parser.add_argument("--team_a_playing", type=str, required=True, help="Path to Team A Playing Model (or 'heuristic', 'random', 'pimc-plurality', 'pimc-ev')")
     parser.add_argument("--team_a_name", type=str, default="Team_A_Baseline", help="Name of Team A")
     
     # Team B Models
     parser.add_argument("--team_b_bidding", type=str, required=True, help="Path to Team B Bidding Model (or 'heuristic', 'random')")
-    parser.add_argument("--team_b_playing", type=str, required=True, help="Path to Team B Playing Model (or 'heuristic', 'random')")
+    parser.add_argument("--team_b_playing", type=str, required=True, help="Path to Team B Playing Model (or 'heuristic', 'random', 'pimc-plurality', 'pimc-ev')")
     parser.add_argument("--team_b_name", type=str, default="Team_B_Challenger", help="Name of Team B")
     
     # Tournament Settings
     parser.add_argument("--nb_games", type=int, default=1000, help="Number of duplicate hands to play")
     parser.add_argument("--device", type=str, default="cpu", help="Device (cpu/cuda)")
+    parser.add_argument("--pimc_iterations", type=int, default=20, help="Worlds sampled per move by PIMC players")
     parser.add_argument("--log_dir", type=str, default="runs/tournament", help="TensorBoard log dir")
+    parser.add_argument("--ratings", type=str, default=None, help="Glicko-2 ratings JSON to update with this tournament (team names identify agents)")
+    parser.add_argument("--archive", type=str, default=None, help="SQLite file to append every finished game to (results, action logs, transcripts)")
+    parser.add_argument("--deal_corpus", type=str, default=None, help="Play the deals of this corpus of recorded games (transcripts, .gz with the engine's deal-corpus feature) in order instead of random deals; 'embedded' for the corpus built into the engine")
     
     args = parser.parse_args()
     
@@ -38,15 +44,21 @@ def main():
     
     # Initialize Agents
     print("Loading Team A Agents...")
-    agent_a = load_agent(args.team_a_bidding, args.team_a_playing, device, name=args.team_a_name)
+    agent_a = load_agent(args.team_a_bidding, args.team_a_playing, device, name=args.team_a_name, pimc_iterations=args.pimc_iterations)
     team_a = Team(args.team_a_name, agent_a)
     
     print("Loading Team B Agents...")
-    agent_b = load_agent(args.team_b_bidding, args.team_b_playing, device, name=args.team_b_name)
+    agent_b = load_agent(args.team_b_bidding, args.team_b_playing, device, name=args.team_b_name, pimc_iterations=args.pimc_iterations)
     team_b = Team(args.team_b_name, agent_b)
     
     # Initialize Engine
-    engine = TournamentEngine(team_a, team_b)
+    archive = GameArchive(args.archive) if args.archive else None
+    deals = None
+    if args.deal_corpus:
+        import coinche_engine
+        deals = coinche_engine.load_deal_corpus(None if args.deal_corpus == "embedded" else args.deal_corpus)
+        print(f"Fixed-deal mode: {len(deals)} deals from {args.deal_corpus}")
+    engine = TournamentEngine(team_a, team_b, archive=archive, archive_tag=os.path.basename(log_dir), deals=deals)
     
     # Determine which is 'Baseline' (Heuristic) for Margin Metric
     baseline_team = None
@@ -73,6 +85,8 @@ def main():
         relative_score = res['relative_score_b'] # (Score B - Score A)
         
         writer.add_scalar('Score/Relative_Diff_Per_Hand', relative_score, step)
+        writer.add_scalar('Score/Relative_IMPs_Per_Hand', res['relative_imps_b'], step)
+        writer.add_scalar('Score/Total_IMPs_B', sum(engine.metrics.relative_imps), step)
         writer.add_scalar('Score/Total_Team_A', engine.metrics.team_a_score, step)
         writer.add_scalar('Score/Total_Team_B', engine.metrics.team_b_score, step)
         
@@ -135,6 +149,8 @@ def main():
     print("-" * 30)
     diff = engine.metrics.team_b_score - engine.metrics.team_a_score
     print(f"Net Difference (B - A): {diff} pts")
+    imps_b = sum(engine.metrics.relative_imps)
+    print(f"IMPs (B - A): {imps_b:+d} ({imps_b / args.nb_games:+.2f} per hand)")
     if diff > 0:
         print(f"WINNER: {args.team_b_name}")
     elif diff < 0:
@@ -142,6 +158,16 @@ def main():
     else:
         print("DRAW")
     print("="*30 + "\n")
+
+    stats = engine.metrics.match_stats
+    if stats.contracts > 0:
+        by_value = ", ".join(f"{v}: {r * 100:.0f}%" for v, r in stats.success_rate_by_value().items())
+        print(f"Contracts: {stats.contracts} ({stats.success_rate() * 100:.1f}% made; {by_value})")
+        print(f"Declarer/Defender avg: {stats.avg_declarer_points():.1f} / {stats.avg_defender_points():.1f} pts")
+        coinche_success = stats.coinche_success_rate()
+        coinche_success = f"{coinche_success * 100:.1f}%" if coinche_success is not None else "N/A"
+        print(f"Coinched: {stats.coinche_rate() * 100:.1f}% (success {coinche_success}), "
+              f"Belote: {stats.belote_rate() * 100:.1f}%, Passed: {stats.passed}, Forfeits: {stats.forfeits}\n")
     
     # Final Stats
     wr_a = engine.metrics.team_a_wins / total_games
@@ -164,7 +190,8 @@ def main():
     summary_text = f"### Tournament Results\n\n" \
                    f"**{args.team_a_name}** vs **{args.team_b_name}**\n\n" \
                    f"- **Hands Played**: {args.nb_games} (Duplicate, {total_games} games total)\n" \
-                   f"- **Net Diff (B-A)**: {diff} pts\n\n" \
+                   f"- **Net Diff (B-A)**: {diff} pts\n" \
+                   f"- **IMPs (B-A)**: {imps_b:+d}\n\n" \
                    f"#### Team A Stats:\n" \
                    f"- Score: {engine.metrics.team_a_score} (Avg: {avg_a:.2f})\n" \
                    f"- Win Rate: {wr_a:.1%}\n" \
@@ -177,7 +204,22 @@ def main():
                    f"- Defense: {get_def_stats(engine.metrics.team_b_defense_stats)}\n"
     writer.add_text("Final_Results", summary_text, 0)
     print(summary_text)
-    
+
+    if args.ratings:
+        # One rated game per duplicate hand: card luck cancels out between its two games
+        games = [([args.team_a_name], [args.team_b_name], 0.0 if rel > 0 else 1.0 if rel < 0 else 0.5)
+                 for rel in engine.metrics.relative_points]
+        tracker = RatingTracker(args.ratings)
+        tracker.record_period(games)
+        tracker.save()
+        print(f"Ratings ({args.ratings}):")
+        for name, r in tracker.leaderboard():
+            print(f"  {name}: {r.rating:.0f} +- {2 * r.rd:.0f} ({r.games} games)")
+    
+    if archive is not None:
+        print(f"Archived {archive.count()} games to {args.archive}")
+        archive.close()
+
     writer.flush()
     writer.close()
 
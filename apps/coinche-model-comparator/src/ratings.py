@@ -0,0 +1,158 @@
+"""
+Glicko-2 ratings of agents from arena results, persisted to a JSON file so every
+tournament adds to the same history.
+
+A rating period is one call to `record_period` (typically one tournament): all its
+games are rated together against the ratings at the start of the period. Teams
+are lists of agent names; in a 2v2 game each agent is rated against a composite
+of the opposing team (mean rating, RMS deviation), so partners share the result.
+"""
+import json
+import math
+import os
+from dataclasses import dataclass, asdict
+
+# Glicko-2 system constant: how fast volatility may change (0.3 - 1.2)
+TAU = 0.5
+# Convergence tolerance of the volatility iteration
+EPSILON = 1e-6
+# Conversion between the Glicko scale (1500 +- 350) and the Glicko-2 scale
+SCALE = 173.7178
+
+DEFAULT_RATING = 1500.0
+DEFAULT_RD = 350.0
+DEFAULT_VOLATILITY = 0.06
+
+
+@dataclass
+class Rating:
+    rating: float = DEFAULT_RATING
+    rd: float = DEFAULT_RD
+    volatility: float = DEFAULT_VOLATILITY
+    games: int = 0
+
+    @property
+    def mu(self):
+        return (self.rating - DEFAULT_RATING) / SCALE
+
+    @property
+    def phi(self):
+        return self.rd / SCALE
+
+
+def _g(phi):
+    return 1.0 / math.sqrt(1.0 + 3.0 * phi * phi / (math.pi * math.pi))
+
+
+def _expected(mu, opp_mu, opp_phi):
+    return 1.0 / (1.0 + math.exp(-_g(opp_phi) * (mu - opp_mu)))
+
+
+def _new_volatility(phi, sigma, delta, v):
+    """Illinois iteration of step 5 of the Glicko-2 paper."""
+    a = math.log(sigma * sigma)
+
+    def f(x):
+        ex = math.exp(x)
+        num = ex * (delta * delta - phi * phi - v - ex)
+        den = 2.0 * (phi * phi + v + ex) ** 2
+        return num / den - (x - a) / (TAU * TAU)
+
+    big_a = a
+    if delta * delta > phi * phi + v:
+        big_b = math.log(delta * delta - phi * phi - v)
+    else:
+        k = 1
+        while f(a - k * TAU) < 0:
+            k += 1
+        big_b = a - k * TAU
+    f_a, f_b = f(big_a), f(big_b)
+    while abs(big_b - big_a) > EPSILON:
+        big_c = big_a + (big_a - big_b) * f_a / (f_b - f_a)
+        f_c = f(big_c)
+        if f_c * f_b <= 0:
+            big_a, f_a = big_b, f_b
+        else:
+            f_a /= 2.0
+        big_b, f_b = big_c, f_c
+    return math.exp(big_a / 2.0)
+
+
+def _update(r, results):
+    """Rating of `r` after a period with `results`: (opp_mu, opp_phi, score) list."""
+    if not results:
+        # Did not play: only the uncertainty grows
+        phi = math.sqrt(r.phi ** 2 + r.volatility ** 2)
+        return Rating(r.rating, phi * SCALE, r.volatility, r.games)
+
+    mu, phi = r.mu, r.phi
+    v_inv = 0.0
+    delta_sum = 0.0
+    for opp_mu, opp_phi, score in results:
+        g = _g(opp_phi)
+        e = _expected(mu, opp_mu, opp_phi)
+        v_inv += g * g * e * (1.0 - e)
+        delta_sum += g * (score - e)
+    v = 1.0 / v_inv
+    sigma = _new_volatility(phi, r.volatility, v * delta_sum, v)
+
+    phi_star = math.sqrt(phi * phi + sigma * sigma)
+    new_phi = 1.0 / math.sqrt(1.0 / (phi_star * phi_star) + 1.0 / v)
+    new_mu = mu + new_phi * new_phi * delta_sum
+    return Rating(new_mu * SCALE + DEFAULT_RATING, new_phi * SCALE, sigma, r.games + len(results))
+
+
+class RatingTracker:
+    """Ratings of every agent seen so far, loaded from and saved to `path`."""
+
+    def __init__(self, path=None):
+        self.path = path
+        self.ratings = {}
+        if path and os.path.exists(path):
+            with open(path) as f:
+                data = json.load(f)
+            self.ratings = {name: Rating(**r) for name, r in data["agents"].items()}
+
+    def rating(self, name):
+        return self.ratings.get(name, Rating())
+
+    def record_period(self, games):
+        """
+        Rates one period of games: (team_a, team_b, score_a) tuples, teams being
+        lists of agent names and score_a 1 for a team A win, 0.5 a draw, 0 a loss.
+        Agents known but absent from the period see their deviation grow.
+        """
+        start = {name: self.rating(name) for g in games for name in g[0] + g[1]}
+        start.update({name: r for name, r in self.ratings.items() if name not in start})
+
+        def composite(team):
+            mu = sum(start[n].mu for n in team) / len(team)
+            phi = math.sqrt(sum(start[n].phi ** 2 for n in team) / len(team))
+            return mu, phi
+
+        results = {name: [] for name in start}
+        for team_a, team_b, score_a in games:
+            if not 0.0 <= score_a <= 1.0:
+                raise ValueError(f"Game score must be in [0, 1], got {score_a}")
+            mu_a, phi_a = composite(team_a)
+            mu_b, phi_b = composite(team_b)
+            for name in team_a:
+                results[name].append((mu_b, phi_b, score_a))
+            for name in team_b:
+                results[name].append((mu_a, phi_a, 1.0 - score_a))
+
+        self.ratings = {name: _update(start[name], results[name]) for name in start}
+
+    def leaderboard(self):
+        """(name, Rating) pairs, best conservative rating (rating - 2 RD) first."""
+        return sorted(self.ratings.items(), key=lambda item: item[1].rating - 2 * item[1].rd, reverse=True)
+
+    def save(self, path=None):
+        path = path or self.path
+        data = {"system": "glicko2", "tau": TAU,
+                "agents": {name: asdict(r) for name, r in self.ratings.items()}}
+        # Written next to the target and renamed: a crash never leaves a truncated file
+        tmp = path + ".tmp"
+        with open(tmp, "w") as f:
+            json.dump(data, f, indent=2)
+        os.replace(tmp, path)
@@ -1,5 +1,6 @@
 
 import coinche_engine
+from coinche_engine import Phase
 import random
 import torch
 import numpy as np
@@ -31,11 +32,25 @@ class MatchMetrics:
         
         # Relative points: Score(Team B) - Score(Team A) in duplicate setting
         self.relative_points = []
+        # IMPs of Team B over Team A per duplicate hand (coinche_engine.duplicate_swing)
+        self.relative_imps = []
+
+        # Both teams pooled: contract success by value/trump, coinches, belotes
+        self.match_stats = coinche_engine.MatchStats()
 
 class TournamentEngine:
-    def __init__(self, team_a, team_b):
+    def __init__(self, team_a, team_b, time_control=None, archive=None, archive_tag=None, deals=None):
         self.team_a = team_a # Team A (Agent A)
         self.team_b = team_b # Team B (Agent B)
+        # Optional fixed deals, (dealer, hands) pairs as coinche_engine.load_deal_corpus
+        # gives them: played in order (cycling) instead of random deals
+        self.deals = list(deals) if deals else None
+        self.next_deal = 0
+        # Optional coinche_engine.TimeControl: agents exceeding it forfeit the game
+        self.time_control = time_control
+        # Optional archive.GameArchive: every finished game is stored with its seats
+        self.archive = archive
+        self.archive_tag = archive_tag
         self.metrics = MatchMetrics()
         
     def play_duplicate_hand(self):
@@ -48,8 +63,13 @@ class TournamentEngine:
            Crucial: Uses EXACT SAME 'hands' array.
            This compares Team A's performance with Hand 0 (North) vs Team B's performance with Hand 0 (North).
         """
-        hands = self._deal_random_hands()
-        dealer = random.randint(0, 3)
+        if self.deals:
+            dealer, hands = self.deals[self.next_deal % len(self.deals)]
+            hands = list(hands)
+            self.next_deal += 1
+        else:
+            hands = self._deal_random_hands()
+            dealer = random.randint(0, 3)
         
         # --- Game 1: NS=A, EW=B ---
         # Agents: 0=A, 1=B, 2=A, 3=B
@@ -84,6 +104,10 @@ class TournamentEngine:
         total_relative_points_b = -total_diff_for_a
         
         self.metrics.relative_points.append(total_relative_points_b)
+
+        # Same comparison on deal scores, as IMPs: B sat NS in game 2
+        imps_b = coinche_engine.duplicate_swing(res_g2['result'], res_g1['result'])
+        self.metrics.relative_imps.append(imps_b)
         
         # Update Metrics
         self.metrics.games_played += 2 # 2 games
@@ -135,6 +159,7 @@ class TournamentEngine:
         
         return {
             'relative_score_b': total_relative_points_b,
+            'relative_imps_b': imps_b,
             'g1': res_g1,
             'g2': res_g2
         }
@@ -143,13 +168,13 @@ class TournamentEngine:
         """
         Simulates a full game.
         """
-        match = coinche_engine.CoincheMatch(dealer, hands)
+        match = coinche_engine.CoincheMatch(dealer, hands, self.time_control)
         
         # --- Bidding Phase ---
         contract_info = {'taker': None, 'value': 0}
 
         # --- Bidding Phase ---
-        while "BIDDING" in match.phase_name():
+        while match.phase == Phase.BIDDING:
             state = match.get_bidding_state()
             
             # Update Contract Info (Track the active contract)
@@ -224,6 +249,8 @@ class TournamentEngine:
             try:
                 match.bid(action)
             except Exception as e:
+                if match.phase == Phase.FINISHED:
+                    break # Agent ran out of time
                 # Fallback to Pass if illegal (e.g. error in logic)
                 # print(f"Bid Error: {e}. Force Pass.")
                 match.bid(None)
@@ -238,7 +265,7 @@ class TournamentEngine:
         # Or check PlayingState as backup.
         try:
              # If we are playing, check playing state for final contract
-             if "PLAYING" in match.phase_name():
+             if match.phase == Phase.PLAYING:
                  ps = match.get_playing_state()
                  if hasattr(ps, 'contract') and ps.contract is not None:
                      contract_info['value'] = ps.contract.value
@@ -251,11 +278,11 @@ class TournamentEngine:
 
         # --- Playing Phase ---
         # If passed out?
-        if "FINISHED" in match.phase_name():
+        if match.phase == Phase.FINISHED:
              # If passed out, taker is None.
-             return self._extract_result(match, contract_info)
+             return self._extract_result(match, agents, contract_info)
             
-        while "PLAYING" in match.phase_name():
+        while match.phase == Phase.PLAYING:
             state = match.get_playing_state()
             current_player = state.current_player
             agent = agents[current_player]
@@ -292,14 +319,22 @@ class TournamentEngine:
             trump = state.trump
             legal_mask = state.get_legal_moves()
             
-            best_card = agent.get_card(p_hand, history_int, current_trick, trump, legal_mask)
+            best_card = agent.get_card(p_hand, history_int, current_trick, trump, legal_mask, match=match, seat=current_player)
             
-            match.play_card(best_card)
+            try:
+                match.play_card(best_card)
+            except RuntimeError:
+                if match.phase == Phase.FINISHED:
+                    break # Agent ran out of time
+                raise
             
-        return self._extract_result(match, contract_info)
+        return self._extract_result(match, agents, contract_info)
 
-    def _extract_result(self, match, contract_info={}):
+    def _extract_result(self, match, agents, contract_info={}):
         res = match.get_result()
+        self.metrics.match_stats.add(res)
+        if self.archive is not None:
+            self.archive.record(match, [a.name for a in agents], tag=self.archive_tag)
         
         # We need Taker and Value. 
         # MatchResult (rust) has: winner_team, points_ns, points_ew, ...?
@@ -324,8 +359,11 @@ class TournamentEngine:
             'points_ns': res.points_ns,
             'points_ew': res.points_ew,
             'contract_made': res.contract_made,
+            'timeout_seat': res.timeout_seat,
+            'revoke_seat': res.revoke_seat,
             'taker': contract_info.get('taker'), 
-            'contract_value': contract_info.get('value', 0)
+            'contract_value': contract_info.get('value', 0),
+            'result': res
         }
 
     def _deal_random_hands(self):